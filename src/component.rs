@@ -0,0 +1,246 @@
+mod types;
+mod canonical;
+mod instance;
+mod value;
+mod wit;
+mod bindgen;
+
+pub use types::{
+    InterfaceType,
+    ComponentFuncType,
+    ComponentType,
+    ResourceType,
+    ResourceDtor,
+    Resource,
+    ComponentVal,
+};
+pub use instance::{
+    ComponentInst,
+    ComponentExternVal,
+    ComponentFunc,
+    ComponentHostFunc,
+    component_instanciate,
+    component_export,
+    component_func_host,
+    component_invoke,
+    component_resource_drop,
+};
+pub use value::{
+    ComponentValue,
+    ComponentPayload,
+    component_next,
+    component_flags,
+};
+pub use wit::WitError;
+pub use bindgen::wit_bindgen;
+pub(crate) use canonical::{
+    CanonOptions,
+    flatten_functype,
+    lift_call,
+    lower_call,
+};
+pub(crate) use types::{
+    HandleTable,
+    lock,
+};
+
+use crate::{
+    Name,
+    Module,
+    FuncType,
+    Error,
+};
+
+/// Component of the component model, whose definitions are kept in their binary order
+/// since every one of them appends to an index space seen by the following ones.
+#[derive(Default)]
+pub struct Component {
+    pub defs: Vec<ComponentDef>,
+}
+
+pub enum ComponentDef {
+    CoreModule(Box<Module>),
+    CoreInstance(CoreInstanceExpr),
+    CoreType(FuncType),
+    Component(Component),
+    Instance(InstanceExpr),
+    Alias(Alias),
+    Type(DefType),
+    Canon(Canon),
+    Import(ComponentImport),
+    Export(ComponentExport),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CoreSort {
+    Func,
+    Table,
+    Memory,
+    Global,
+    Tag,
+    Type,
+    Module,
+    Instance,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Sort {
+    Core(CoreSort),
+    Func,
+    Value,
+    Type,
+    Component,
+    Instance,
+}
+
+pub enum CoreInstanceExpr {
+    /// Module index, with the core instances given by import module names.
+    Instantiate(u32, Vec<(Name, u32)>),
+    Exports(Vec<(Name, CoreSort, u32)>),
+}
+
+pub enum InstanceExpr {
+    /// Component index, with the definitions given by import names.
+    Instantiate(u32, Vec<(Name, Sort, u32)>),
+    Exports(Vec<(Name, Sort, u32)>),
+}
+
+pub struct Alias {
+    pub sort: Sort,
+    pub target: AliasTarget,
+}
+
+pub enum AliasTarget {
+    Export(u32, Name),
+    CoreExport(u32, Name),
+    /// Number of enclosing components to go out of, and the index in that component.
+    Outer(u32, u32),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PrimValType {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    F32,
+    F64,
+    Char,
+    String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ValTypeRef {
+    Prim(PrimValType),
+    Type(u32),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum DefValType {
+    Prim(PrimValType),
+    Record(Vec<(Name, ValTypeRef)>),
+    Variant(Vec<(Name, Option<ValTypeRef>)>),
+    List(ValTypeRef),
+    Tuple(Vec<ValTypeRef>),
+    Flags(Vec<Name>),
+    Enum(Vec<Name>),
+    Option(ValTypeRef),
+    Result(Option<ValTypeRef>, Option<ValTypeRef>),
+    Own(u32),
+    Borrow(u32),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct FuncTypeDef {
+    pub params: Vec<(Name, ValTypeRef)>,
+    pub results: Vec<ValTypeRef>,
+}
+
+pub enum DefType {
+    Val(DefValType),
+    Func(FuncTypeDef),
+    Component(Vec<ComponentDecl>),
+    Instance(Vec<InstanceDecl>),
+    /// Resource represented by an `i32`, with its optional core destructor.
+    Resource(Option<u32>),
+}
+
+pub enum ComponentDecl {
+    Import(Name, ExternDesc),
+    Instance(InstanceDecl),
+}
+
+pub enum InstanceDecl {
+    CoreType(FuncType),
+    Type(DefType),
+    Alias(Alias),
+    Export(Name, ExternDesc),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ExternDesc {
+    CoreModule(u32),
+    Func(u32),
+    Value(ValTypeRef),
+    Type(TypeBound),
+    Component(u32),
+    Instance(u32),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TypeBound {
+    Eq(u32),
+    SubResource,
+}
+
+pub enum Canon {
+    /// Core function, options and component function type.
+    Lift(u32, Vec<CanonOpt>, u32),
+    Lower(u32, Vec<CanonOpt>),
+    ResourceNew(u32),
+    ResourceDrop(u32),
+    ResourceRep(u32),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CanonOpt {
+    StringEncoding(StringEncoding),
+    Memory(u32),
+    Realloc(u32),
+    PostReturn(u32),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StringEncoding {
+    Utf8,
+    Utf16,
+    Latin1Utf16,
+}
+
+pub struct ComponentImport {
+    pub name: Name,
+    pub desc: ExternDesc,
+}
+
+pub struct ComponentExport {
+    pub name: Name,
+    pub sort: Sort,
+    pub idx: u32,
+    pub desc: Option<ExternDesc>,
+}
+
+use std::io::Read;
+use crate::decode_component;
+
+pub fn component_decode(reader: &mut impl Read) -> Result<Component, Error> {
+    if let Ok(component) = decode_component(reader) {
+        Ok(component)
+    } else {
+        Err(Error::Invalid)
+    }
+}
@@ -0,0 +1,398 @@
+use super::wit::{
+    WitError,
+    WitDocument,
+    WitWorld,
+    WitWorldItem,
+    WitUse,
+    WitTypeDef,
+    WitTypeKind,
+    WitFunc,
+    WitType,
+    wit_parse,
+};
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Generate Rust bindings of the interfaces and worlds of a WIT document.
+///
+/// Every interface becomes a module with its types, a `Host` trait with `add_to_imports`
+/// to provide it to a component, and a `Guest` calling it when exported by a component.
+/// Every world becomes a module with a `Host` trait for its imported functions, and a
+/// struct calling its exports. The output names this crate `wasmiq`, and is meant to be
+/// written by a build script and brought in with `include!`.
+pub fn wit_bindgen(src: &str) -> Result<String, WitError> {
+    let document = wit_parse(src)?;
+    let mut out = String::new();
+    for interface in &document.interfaces {
+        out.push_str(&format!("pub mod {} {{\n", snake(&interface.name)));
+        out.push_str(PRELUDE);
+        uses(&mut out, &interface.uses);
+        for typedef in &interface.types {
+            typedef_(&mut out, typedef);
+        }
+        let name = instance_name(&document, &interface.name);
+        host(&mut out, &interface.funcs, Some(&name));
+        guest(&mut out, "Guest", &interface.funcs, &[], &document);
+        out.push_str("}\n\n");
+    }
+    for world in &document.worlds {
+        world_(&mut out, world, &document);
+    }
+    Ok(out)
+}
+
+const PRELUDE: &str = "    #![allow(unused, clippy::all)]
+    use std::sync::{Arc, Mutex};
+    use wasmiq::{
+        Store,
+        Error,
+        ComponentVal,
+        ComponentValue,
+        ComponentFuncType,
+        ComponentFunc,
+        ComponentInst,
+        ComponentExternVal,
+        InterfaceType,
+        component_export,
+        component_func_host,
+        component_invoke,
+        component_next,
+        component_flags,
+    };
+
+";
+
+/// Name of an interface as imported or exported by a component.
+fn instance_name(document: &WitDocument, name: &str) -> String {
+    match (&document.package, &document.version) {
+        (Some(package), Some(version)) => format!("{}/{}@{}", package, name, version),
+        (Some(package), None) => format!("{}/{}", package, name),
+        _ => name.to_string(),
+    }
+}
+
+fn world_(out: &mut String, world: &WitWorld, document: &WitDocument) {
+    out.push_str(&format!("pub mod {} {{\n", snake(&world.name)));
+    out.push_str(PRELUDE);
+    uses(out, &world.uses);
+    for typedef in &world.types {
+        typedef_(out, typedef);
+    }
+    let imports: Vec<WitFunc> = world.imports.iter().filter_map(|item| match item {
+        WitWorldItem::Func(func) => Some(func.clone()),
+        WitWorldItem::Interface(_) => None,
+    }).collect();
+    if !imports.is_empty() {
+        host(out, &imports, None);
+    }
+    let mut funcs = vec![];
+    let mut interfaces = vec![];
+    for item in &world.exports {
+        match item {
+            WitWorldItem::Func(func) => funcs.push(func.clone()),
+            WitWorldItem::Interface(name) => interfaces.push(name.as_str()),
+        }
+    }
+    guest(out, &camel(&world.name), &funcs, &interfaces, document);
+    out.push_str("}\n\n");
+}
+
+fn uses(out: &mut String, uses: &[WitUse]) {
+    for used in uses {
+        let names: Vec<String> = used.names.iter().map(|name| camel(name)).collect();
+        out.push_str(&format!("    pub use super::{}::{{{}}};\n", snake(&used.interface), names.join(", ")));
+    }
+    if !uses.is_empty() {
+        out.push('\n');
+    }
+}
+
+fn typedef_(out: &mut String, typedef: &WitTypeDef) {
+    let name = camel(&typedef.name);
+    let (tp, into_val, from_val) = match &typedef.kind {
+        WitTypeKind::Alias(tp) => {
+            out.push_str(&format!("    pub type {} = {};\n\n", name, rust_type(tp)));
+            return;
+        },
+        WitTypeKind::Record(fields) => {
+            out.push_str(&format!("    #[derive(Clone, Debug, PartialEq)]\n    pub struct {} {{\n", name));
+            for (field, tp) in fields {
+                out.push_str(&format!("        pub {}: {},\n", snake(field), rust_type(tp)));
+            }
+            out.push_str("    }\n\n");
+            let tps: Vec<String> = fields.iter().map(|(field, tp)| {
+                format!("({:?}.to_string(), {})", field, interface_type(tp))
+            }).collect();
+            let vals: Vec<String> = fields.iter().map(|(field, _)| {
+                format!("({:?}.to_string(), self.{}.into_val())", field, snake(field))
+            }).collect();
+            let nexts: Vec<String> = fields.iter().map(|(field, _)| {
+                format!("{}: component_next(&mut vals)?", snake(field))
+            }).collect();
+            (
+                format!("InterfaceType::Record(vec![{}])", tps.join(", ")),
+                format!("ComponentVal::Record(vec![{}])", vals.join(", ")),
+                format!("ComponentVal::Record(fields) => {{
+                let mut vals = fields.into_iter().map(|(_, val)| val);
+                Ok({} {{ {} }})
+            }},", name, nexts.join(", ")),
+            )
+        },
+        WitTypeKind::Variant(cases) => {
+            out.push_str(&format!("    #[derive(Clone, Debug, PartialEq)]\n    pub enum {} {{\n", name));
+            for (case, tp) in cases {
+                match tp {
+                    Some(tp) => out.push_str(&format!("        {}({}),\n", camel(case), rust_type(tp))),
+                    None => out.push_str(&format!("        {},\n", camel(case))),
+                }
+            }
+            out.push_str("    }\n\n");
+            let tps: Vec<String> = cases.iter().map(|(case, tp)| match tp {
+                Some(tp) => format!("({:?}.to_string(), Some({}))", case, interface_type(tp)),
+                None => format!("({:?}.to_string(), None)", case),
+            }).collect();
+            let mut into_val = "match self {\n".to_string();
+            let mut from_val = "ComponentVal::Variant(case, payload) => match (case.as_str(), payload) {\n".to_string();
+            for (case, tp) in cases {
+                match tp {
+                    Some(tp) => {
+                        into_val.push_str(&format!("                {}::{}(val) => ComponentVal::Variant({:?}.to_string(), Some(Box::new(val.into_val()))),\n", name, camel(case), case));
+                        from_val.push_str(&format!("                ({:?}, Some(val)) => Ok({}::{}(<{} as ComponentValue>::from_val(*val)?)),\n", case, name, camel(case), rust_type(tp)));
+                    },
+                    None => {
+                        into_val.push_str(&format!("                {}::{} => ComponentVal::Variant({:?}.to_string(), None),\n", name, camel(case), case));
+                        from_val.push_str(&format!("                ({:?}, None) => Ok({}::{}),\n", case, name, camel(case)));
+                    },
+                }
+            }
+            into_val.push_str("            }");
+            from_val.push_str("                _ => Err(Error::Invalid),\n            },");
+            (format!("InterfaceType::Variant(vec![{}])", tps.join(", ")), into_val, from_val)
+        },
+        WitTypeKind::Enum(cases) => {
+            out.push_str(&format!("    #[derive(Clone, Copy, Debug, PartialEq, Eq)]\n    pub enum {} {{\n", name));
+            for case in cases {
+                out.push_str(&format!("        {},\n", camel(case)));
+            }
+            out.push_str("    }\n\n");
+            let tps: Vec<String> = cases.iter().map(|case| format!("{:?}.to_string()", case)).collect();
+            let mut into_val = "ComponentVal::Enum(match self {\n".to_string();
+            let mut from_val = "ComponentVal::Enum(case) => match case.as_str() {\n".to_string();
+            for case in cases {
+                into_val.push_str(&format!("                {}::{} => {:?},\n", name, camel(case), case));
+                from_val.push_str(&format!("                {:?} => Ok({}::{}),\n", case, name, camel(case)));
+            }
+            into_val.push_str("            }.to_string())");
+            from_val.push_str("                _ => Err(Error::Invalid),\n            },");
+            (format!("InterfaceType::Enum(vec![{}])", tps.join(", ")), into_val, from_val)
+        },
+        WitTypeKind::Flags(flags) => {
+            out.push_str(&format!("    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]\n    pub struct {} {{\n", name));
+            for flag in flags {
+                out.push_str(&format!("        pub {}: bool,\n", snake(flag)));
+            }
+            out.push_str("    }\n\n");
+            let tps: Vec<String> = flags.iter().map(|flag| format!("{:?}.to_string()", flag)).collect();
+            let sets: Vec<String> = flags.iter().map(|flag| format!("({:?}, self.{})", flag, snake(flag))).collect();
+            let gets: Vec<String> = flags.iter().map(|flag| {
+                format!("{}: names.iter().any(|name| name == {:?})", snake(flag), flag)
+            }).collect();
+            (
+                format!("InterfaceType::Flags(vec![{}])", tps.join(", ")),
+                format!("ComponentVal::Flags(component_flags(&[{}]))", sets.join(", ")),
+                format!("ComponentVal::Flags(names) => Ok({} {{ {} }}),", name, gets.join(", ")),
+            )
+        },
+    };
+    out.push_str(&format!("    impl ComponentValue for {} {{
+        fn tp() -> InterfaceType {{
+            {}
+        }}
+        fn into_val(self) -> ComponentVal {{
+            {}
+        }}
+        fn from_val(val: ComponentVal) -> Result<Self, Error> {{
+            match val {{
+            {}
+            _ => Err(Error::Invalid),
+            }}
+        }}
+    }}
+
+", name, tp, into_val, from_val));
+}
+
+fn functype(func: &WitFunc) -> String {
+    let params: Vec<String> = func.params.iter().map(|(param, tp)| {
+        format!("({:?}.to_string(), {})", param, interface_type(tp))
+    }).collect();
+    let results = match &func.result {
+        Some(tp) => interface_type(tp),
+        None => String::new(),
+    };
+    format!("ComponentFuncType {{ params: vec![{}], results: vec![{}] }}", params.join(", "), results)
+}
+
+fn params(func: &WitFunc) -> String {
+    func.params.iter().map(|(param, tp)| format!(", {}: {}", snake(param), rust_type(tp))).collect()
+}
+
+/// Trait of the host implementing `funcs`, pushed to the imports of a component
+/// as the instance `instance`, or else each by its name.
+fn host(out: &mut String, funcs: &[WitFunc], instance: Option<&str>) {
+    out.push_str("    pub trait Host {\n");
+    for func in funcs {
+        let result = match &func.result {
+            Some(tp) => format!(" -> {}", rust_type(tp)),
+            None => String::new(),
+        };
+        out.push_str(&format!("        fn {}(&mut self{}){};\n", snake(&func.name), params(func), result));
+    }
+    out.push_str("    }\n\n");
+    out.push_str("    pub fn add_to_imports<T: Host + Send + 'static>(imports: &mut Vec<(String, ComponentExternVal)>, host: Arc<Mutex<T>>) {\n");
+    if instance.is_some() {
+        out.push_str("        let mut exports = vec![];\n");
+    }
+    for func in funcs {
+        let args: Vec<String> = func.params.iter().map(|(param, _)| snake(param)).collect();
+        out.push_str(&format!("        {{
+            let host = host.clone();
+            let func = component_func_host({}, Arc::new(move |_: &mut Store, args: Vec<ComponentVal>| {{
+                let mut args = args.into_iter();
+", functype(func)));
+        for arg in &args {
+            out.push_str(&format!("                let {} = component_next(&mut args)?;\n", arg));
+        }
+        let call = format!("host.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).{}({})", snake(&func.name), args.join(", "));
+        match func.result {
+            Some(_) => out.push_str(&format!("                Ok(vec![{}.into_val()])\n", call)),
+            None => out.push_str(&format!("                {};\n                Ok(vec![])\n", call)),
+        }
+        let push = if instance.is_some() { "exports" } else { "imports" };
+        out.push_str(&format!("            }}));
+            {}.push(({:?}.to_string(), ComponentExternVal::Func(func)));
+        }}
+", push, func.name));
+    }
+    if let Some(instance) = instance {
+        out.push_str(&format!("        imports.push(({:?}.to_string(), ComponentExternVal::Instance(ComponentInst {{ exports }})));\n", instance));
+    }
+    out.push_str("    }\n\n");
+}
+
+/// Struct calling `funcs` and the interfaces `interfaces` exported by a component instance.
+fn guest(out: &mut String, name: &str, funcs: &[WitFunc], interfaces: &[&str], document: &WitDocument) {
+    out.push_str(&format!("    #[derive(Clone)]\n    pub struct {} {{\n", name));
+    for func in funcs {
+        out.push_str(&format!("        {}: ComponentFunc,\n", snake(&func.name)));
+    }
+    for interface in interfaces {
+        out.push_str(&format!("        pub {}: super::{}::Guest,\n", snake(interface), snake(interface)));
+    }
+    out.push_str(&format!("    }}
+
+    impl {} {{
+        pub fn from_instance(instance: &ComponentInst) -> Result<Self, Error> {{
+            Ok({} {{
+", name, name));
+    for func in funcs {
+        out.push_str(&format!("                {}: match component_export(instance.clone(), {:?}.to_string())? {{
+                    ComponentExternVal::Func(func) => func,
+                    _ => return Err(Error::Invalid),
+                }},
+", snake(&func.name), func.name));
+    }
+    for interface in interfaces {
+        out.push_str(&format!("                {}: match component_export(instance.clone(), {:?}.to_string())? {{
+                    ComponentExternVal::Instance(instance) => super::{}::Guest::from_instance(&instance)?,
+                    _ => return Err(Error::Invalid),
+                }},
+", snake(interface), instance_name(document, interface), snake(interface)));
+    }
+    out.push_str("            })\n        }\n");
+    for func in funcs {
+        let result = match &func.result {
+            Some(tp) => rust_type(tp),
+            None => "()".to_string(),
+        };
+        let args: Vec<String> = func.params.iter().map(|(param, _)| format!("{}.into_val()", snake(param))).collect();
+        out.push_str(&format!("
+        pub fn {}(&self, store: &mut Store{}) -> Result<{}, Error> {{
+", snake(&func.name), params(func), result));
+        let call = format!("component_invoke(store, &self.{}, vec![{}])?", snake(&func.name), args.join(", "));
+        match func.result {
+            Some(_) => out.push_str(&format!("            let mut results = {}.into_iter();\n            component_next(&mut results)\n", call)),
+            None => out.push_str(&format!("            {};\n            Ok(())\n", call)),
+        }
+        out.push_str("        }\n");
+    }
+    out.push_str("    }\n");
+}
+
+fn rust_type(tp: &WitType) -> String {
+    let payload = |tp: &Option<Box<WitType>>| match tp {
+        Some(tp) => rust_type(tp),
+        None => "()".to_string(),
+    };
+    match tp {
+        WitType::Bool => "bool".to_string(),
+        WitType::S8 => "i8".to_string(),
+        WitType::U8 => "u8".to_string(),
+        WitType::S16 => "i16".to_string(),
+        WitType::U16 => "u16".to_string(),
+        WitType::S32 => "i32".to_string(),
+        WitType::U32 => "u32".to_string(),
+        WitType::S64 => "i64".to_string(),
+        WitType::U64 => "u64".to_string(),
+        WitType::F32 => "f32".to_string(),
+        WitType::F64 => "f64".to_string(),
+        WitType::Char => "char".to_string(),
+        WitType::String => "String".to_string(),
+        WitType::List(tp) => format!("Vec<{}>", rust_type(tp)),
+        WitType::Option(tp) => format!("Option<{}>", rust_type(tp)),
+        WitType::Result(ok, err) => format!("Result<{}, {}>", payload(ok), payload(err)),
+        WitType::Tuple(tps) => {
+            let tps: Vec<String> = tps.iter().map(rust_type).collect();
+            format!("({},)", tps.join(", "))
+        },
+        WitType::Named(name) => camel(name),
+    }
+}
+
+fn interface_type(tp: &WitType) -> String {
+    format!("<{} as ComponentValue>::tp()", rust_type(tp))
+}
+
+/// `snake_case` of a WIT identifier, escaping Rust keywords.
+fn snake(name: &str) -> String {
+    let name = name.to_lowercase().replace('-', "_");
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
+
+/// `UpperCamelCase` of a WIT identifier.
+fn camel(name: &str) -> String {
+    name.split('-').map(|word| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(c) => c.to_ascii_uppercase().to_string() + &chars.as_str().to_lowercase(),
+            None => String::new(),
+        }
+    }).collect()
+}
+
+// the output is compared against a snapshot and compiled in tests/bindgen.rs
+#[test]
+fn test_wit_bindgen() {
+    let err = wit_bindgen("interface a { f: func(x: point); }").unwrap_err();
+    assert_eq!(err, WitError { line: 1, message: "unknown type `point`".to_string() });
+}
@@ -0,0 +1,744 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    Store,
+    Val,
+    ValType,
+    FuncType,
+    FuncAddr,
+    MemAddr,
+    Error,
+    func_invoke,
+};
+use super::{
+    StringEncoding,
+    InterfaceType,
+    ComponentFuncType,
+    ComponentVal,
+    HandleTable,
+    lock,
+};
+
+const MAX_FLAT_PARAMS: usize = 16;
+const MAX_FLAT_RESULTS: usize = 1;
+
+/// Options of a lifted or lowered function, with the handles of its instance.
+#[derive(Clone)]
+pub struct CanonOptions {
+    pub encoding: StringEncoding,
+    pub memory: Option<MemAddr>,
+    pub realloc: Option<FuncAddr>,
+    pub post_return: Option<FuncAddr>,
+    pub handles: Arc<Mutex<HandleTable>>,
+}
+
+/// Core type of a function lifted from core code, or lowered into it.
+/// Values not fitting in the flat parameters or results are passed in linear memory,
+/// a lowered function writing its results where its last parameter points.
+pub fn flatten_functype(tp: &ComponentFuncType, lower: bool) -> FuncType {
+    let params: Vec<InterfaceType> = tp.params.iter().map(|(_, tp)| tp.clone()).collect();
+    let mut flat_params = flatten_types(&params);
+    let mut flat_results = flatten_types(&tp.results);
+    if flat_params.len() > MAX_FLAT_PARAMS {
+        flat_params = vec![ValType::I32];
+    }
+    if flat_results.len() > MAX_FLAT_RESULTS {
+        if lower {
+            flat_params.push(ValType::I32);
+            flat_results = vec![];
+        } else {
+            flat_results = vec![ValType::I32];
+        }
+    }
+    (flat_params, flat_results)
+}
+
+/// Lift the parameters (`params`) or results of a call out of core values.
+fn lift_values(store: &mut Store, opts: &CanonOptions, vals: Vec<Val>, tps: &[InterfaceType], params: bool) -> Result<Vec<ComponentVal>, Error> {
+    let max_flat = if params { MAX_FLAT_PARAMS } else { MAX_FLAT_RESULTS };
+    let mut cx = Cx { store, opts };
+    if flatten_types(tps).len() > max_flat {
+        let ptr = match vals.first() {
+            Some(Val::I32Const(ptr)) => *ptr,
+            _ => return Err(Error::Invalid),
+        };
+        let tuple = InterfaceType::Tuple(tps.to_vec());
+        match cx.load(ptr, &tuple)? {
+            ComponentVal::Tuple(vals) => Ok(vals),
+            _ => unreachable!(),
+        }
+    } else {
+        let mut vals = vals.into_iter();
+        tps.iter().map(|tp| cx.lift_flat(&mut vals, tp)).collect()
+    }
+}
+
+/// Lower the parameters or results of a call into core values.
+/// Results are written at `retptr` when given and not fitting the flat results.
+fn lower_values(store: &mut Store, opts: &CanonOptions, vals: Vec<ComponentVal>, tps: &[InterfaceType], retptr: Option<u32>) -> Result<Vec<Val>, Error> {
+    if vals.len() != tps.len() {
+        return Err(Error::Invalid);
+    }
+    let max_flat = if retptr.is_none() { MAX_FLAT_PARAMS } else { MAX_FLAT_RESULTS };
+    let mut cx = Cx { store, opts };
+    if flatten_types(tps).len() > max_flat {
+        let tuple = InterfaceType::Tuple(tps.to_vec());
+        let ptr = match retptr {
+            Some(ptr) => ptr,
+            None => cx.realloc(alignment(&tuple), elem_size(&tuple))?,
+        };
+        cx.store(ComponentVal::Tuple(vals), &tuple, ptr)?;
+        Ok(if retptr.is_some() { vec![] } else { vec![Val::I32Const(ptr)] })
+    } else {
+        let mut flat = vec![];
+        for (val, tp) in vals.into_iter().zip(tps) {
+            flat.extend(cx.lower_flat(val, tp)?);
+        }
+        Ok(flat)
+    }
+}
+
+/// Call a core function lifted with `opts`.
+pub fn lift_call(store: &mut Store, funcaddr: FuncAddr, opts: &CanonOptions, tp: &ComponentFuncType, args: Vec<ComponentVal>) -> Result<Vec<ComponentVal>, Error> {
+    let params: Vec<InterfaceType> = tp.params.iter().map(|(_, tp)| tp.clone()).collect();
+    let args = lower_values(store, opts, args, &params, None)?;
+    let vals = func_invoke(store, funcaddr, args).1?;
+    let results = lift_values(store, opts, vals.clone(), &tp.results, false)?;
+    if let Some(post_return) = opts.post_return {
+        func_invoke(store, post_return, vals).1?;
+    }
+    Ok(results)
+}
+
+/// Call `f` from core code, where it is lowered with `opts`.
+pub fn lower_call(store: &mut Store, opts: &CanonOptions, tp: &ComponentFuncType, args: &[Val], f: impl FnOnce(&mut Store, Vec<ComponentVal>) -> Result<Vec<ComponentVal>, Error>) -> Result<Vec<Val>, Error> {
+    let params: Vec<InterfaceType> = tp.params.iter().map(|(_, tp)| tp.clone()).collect();
+    let mut args = args.to_vec();
+    let retptr = if flatten_types(&tp.results).len() > MAX_FLAT_RESULTS {
+        match args.pop() {
+            Some(Val::I32Const(ptr)) => Some(ptr),
+            _ => return Err(Error::Invalid),
+        }
+    } else {
+        None
+    };
+    let args = lift_values(store, opts, args, &params, true)?;
+    let results = f(store, args)?;
+    // results fitting the flat results are returned whatever their limit
+    lower_values(store, opts, results, &tp.results, retptr)
+}
+
+/* flattening */
+
+fn flatten_types(tps: &[InterfaceType]) -> Vec<ValType> {
+    let mut flat = vec![];
+    for tp in tps {
+        flatten(tp, &mut flat);
+    }
+    flat
+}
+
+fn flatten(tp: &InterfaceType, flat: &mut Vec<ValType>) {
+    match tp {
+        InterfaceType::S64 | InterfaceType::U64 => flat.push(ValType::I64),
+        InterfaceType::F32 => flat.push(ValType::F32),
+        InterfaceType::F64 => flat.push(ValType::F64),
+        InterfaceType::String | InterfaceType::List(_) => flat.extend([ValType::I32, ValType::I32]),
+        InterfaceType::Record(fields) => fields.iter().for_each(|(_, tp)| flatten(tp, flat)),
+        InterfaceType::Tuple(tps) => tps.iter().for_each(|tp| flatten(tp, flat)),
+        InterfaceType::Flags(names) => flat.extend(vec![ValType::I32; names.len().div_ceil(32)]),
+        InterfaceType::Variant(_) | InterfaceType::Enum(_) | InterfaceType::Option(_) | InterfaceType::Result(_, _) => {
+            flat.push(ValType::I32);
+            flat.extend(flatten_cases(&tp.cases().unwrap()));
+        },
+        _ => flat.push(ValType::I32),
+    }
+}
+
+/// Payloads of all cases share the same core values, of the widest type needed.
+fn flatten_cases(cases: &[Option<InterfaceType>]) -> Vec<ValType> {
+    let mut joined: Vec<ValType> = vec![];
+    for tp in cases.iter().flatten() {
+        let mut flat = vec![];
+        flatten(tp, &mut flat);
+        for (i, valtype) in flat.into_iter().enumerate() {
+            if i < joined.len() {
+                joined[i] = join(&joined[i], &valtype);
+            } else {
+                joined.push(valtype);
+            }
+        }
+    }
+    joined
+}
+
+fn join(a: &ValType, b: &ValType) -> ValType {
+    match (a, b) {
+        _ if a == b => *a,
+        (ValType::I32, ValType::F32) | (ValType::F32, ValType::I32) => ValType::I32,
+        _ => ValType::I64,
+    }
+}
+
+/// Core value of a case payload, as read from the joined type.
+fn coerce_from_joined(val: Val, valtype: &ValType) -> Val {
+    match (val, valtype) {
+        (Val::I64Const(n), ValType::I32) => Val::I32Const(n as u32),
+        (Val::I32Const(n), ValType::F32) => Val::F32Const(f32::from_bits(n)),
+        (Val::I64Const(n), ValType::F32) => Val::F32Const(f32::from_bits(n as u32)),
+        (Val::I64Const(n), ValType::F64) => Val::F64Const(f64::from_bits(n)),
+        (val, _) => val,
+    }
+}
+
+fn coerce_to_joined(val: Val, valtype: &ValType) -> Val {
+    match (val, valtype) {
+        (Val::F32Const(f), ValType::I32) => Val::I32Const(f.to_bits()),
+        (Val::I32Const(n), ValType::I64) => Val::I64Const(n as u64),
+        (Val::F32Const(f), ValType::I64) => Val::I64Const(f.to_bits() as u64),
+        (Val::F64Const(f), ValType::I64) => Val::I64Const(f.to_bits()),
+        (val, _) => val,
+    }
+}
+
+fn zero(valtype: &ValType) -> Val {
+    match valtype {
+        ValType::I64 => Val::I64Const(0),
+        ValType::F32 => Val::F32Const(0.0),
+        ValType::F64 => Val::F64Const(0.0),
+        _ => Val::I32Const(0),
+    }
+}
+
+/* memory layout */
+
+fn align_to(ptr: u32, align: u32) -> u32 {
+    ptr.div_ceil(align) * align
+}
+
+fn discriminant_size(n: usize) -> u32 {
+    if n <= 0x100 { 1 } else if n <= 0x10000 { 2 } else { 4 }
+}
+
+fn flags_size(n: usize) -> u32 {
+    match n {
+        0 => 0,
+        1..=8 => 1,
+        9..=16 => 2,
+        _ => 4 * n.div_ceil(32) as u32,
+    }
+}
+
+fn max_case_alignment(cases: &[Option<InterfaceType>]) -> u32 {
+    cases.iter().flatten().map(alignment).max().unwrap_or(1)
+}
+
+fn alignment(tp: &InterfaceType) -> u32 {
+    match tp {
+        InterfaceType::Bool | InterfaceType::S8 | InterfaceType::U8 => 1,
+        InterfaceType::S16 | InterfaceType::U16 => 2,
+        InterfaceType::S64 | InterfaceType::U64 | InterfaceType::F64 => 8,
+        InterfaceType::Record(fields) => fields.iter().map(|(_, tp)| alignment(tp)).max().unwrap_or(1),
+        InterfaceType::Tuple(tps) => tps.iter().map(alignment).max().unwrap_or(1),
+        InterfaceType::Flags(names) => match flags_size(names.len()) {
+            0 => 1,
+            size => size.min(4),
+        },
+        InterfaceType::Variant(_) | InterfaceType::Enum(_) | InterfaceType::Option(_) | InterfaceType::Result(_, _) => {
+            let cases = tp.cases().unwrap();
+            discriminant_size(cases.len()).max(max_case_alignment(&cases))
+        },
+        _ => 4,
+    }
+}
+
+fn elem_size(tp: &InterfaceType) -> u32 {
+    match tp {
+        InterfaceType::Bool | InterfaceType::S8 | InterfaceType::U8 => 1,
+        InterfaceType::S16 | InterfaceType::U16 => 2,
+        InterfaceType::S64 | InterfaceType::U64 | InterfaceType::F64 => 8,
+        InterfaceType::String | InterfaceType::List(_) => 8,
+        InterfaceType::Record(_) | InterfaceType::Tuple(_) => {
+            let mut size = 0;
+            for tp in field_types(tp) {
+                size = align_to(size, alignment(tp)) + elem_size(tp);
+            }
+            align_to(size, alignment(tp))
+        },
+        InterfaceType::Flags(names) => flags_size(names.len()),
+        InterfaceType::Variant(_) | InterfaceType::Enum(_) | InterfaceType::Option(_) | InterfaceType::Result(_, _) => {
+            let cases = tp.cases().unwrap();
+            let payload = align_to(discriminant_size(cases.len()), max_case_alignment(&cases));
+            let max_case_size = cases.iter().flatten().map(elem_size).max().unwrap_or(0);
+            align_to(payload + max_case_size, alignment(tp))
+        },
+        _ => 4,
+    }
+}
+
+fn field_types(tp: &InterfaceType) -> Vec<&InterfaceType> {
+    match tp {
+        InterfaceType::Record(fields) => fields.iter().map(|(_, tp)| tp).collect(),
+        InterfaceType::Tuple(tps) => tps.iter().collect(),
+        _ => vec![],
+    }
+}
+
+/* values */
+
+/// Case index and payload of a variant value, or of a value represented as one.
+fn case_of(tp: &InterfaceType, val: ComponentVal) -> Result<(usize, Option<ComponentVal>), Error> {
+    let (idx, payload) = match (tp, val) {
+        (InterfaceType::Variant(cases), ComponentVal::Variant(name, payload)) => {
+            (cases.iter().position(|(case, _)| case == &name), payload.map(|val| *val))
+        },
+        (InterfaceType::Enum(names), ComponentVal::Enum(name)) => (names.iter().position(|case| case == &name), None),
+        (InterfaceType::Option(_), ComponentVal::Option(None)) => (Some(0), None),
+        (InterfaceType::Option(_), ComponentVal::Option(Some(val))) => (Some(1), Some(*val)),
+        (InterfaceType::Result(_, _), ComponentVal::Result(Ok(val))) => (Some(0), val.map(|val| *val)),
+        (InterfaceType::Result(_, _), ComponentVal::Result(Err(val))) => (Some(1), val.map(|val| *val)),
+        _ => return Err(Error::Invalid),
+    };
+    let idx = idx.ok_or(Error::Invalid)?;
+    if tp.cases().unwrap()[idx].is_some() != payload.is_some() {
+        return Err(Error::Invalid);
+    }
+    Ok((idx, payload))
+}
+
+fn val_of_case(tp: &InterfaceType, idx: usize, payload: Option<ComponentVal>) -> ComponentVal {
+    let payload = payload.map(Box::new);
+    match tp {
+        InterfaceType::Variant(cases) => ComponentVal::Variant(cases[idx].0.clone(), payload),
+        InterfaceType::Enum(names) => ComponentVal::Enum(names[idx].clone()),
+        InterfaceType::Option(_) => ComponentVal::Option(payload),
+        InterfaceType::Result(_, _) if idx == 0 => ComponentVal::Result(Ok(payload)),
+        InterfaceType::Result(_, _) => ComponentVal::Result(Err(payload)),
+        _ => unreachable!(),
+    }
+}
+
+fn flags_to_bits(names: &[String], set: &[String]) -> Result<Vec<u32>, Error> {
+    let mut bits = vec![0u32; names.len().div_ceil(32)];
+    for name in set {
+        let i = names.iter().position(|flag| flag == name).ok_or(Error::Invalid)?;
+        bits[i / 32] |= 1 << (i % 32);
+    }
+    Ok(bits)
+}
+
+fn bits_to_flags(names: &[String], bits: &[u32]) -> Vec<String> {
+    names.iter().enumerate().filter(|(i, _)| bits[i / 32] & (1 << (i % 32)) != 0).map(|(_, name)| name.clone()).collect()
+}
+
+fn fields_of(tp: &InterfaceType, val: ComponentVal) -> Result<Vec<ComponentVal>, Error> {
+    let tps = field_types(tp);
+    let vals = match (tp, val) {
+        (InterfaceType::Record(fields), ComponentVal::Record(vals)) => {
+            if fields.iter().map(|(name, _)| name).ne(vals.iter().map(|(name, _)| name)) {
+                return Err(Error::Invalid);
+            }
+            vals.into_iter().map(|(_, val)| val).collect()
+        },
+        (InterfaceType::Tuple(_), ComponentVal::Tuple(vals)) => vals,
+        _ => return Err(Error::Invalid),
+    };
+    if vals.len() != tps.len() {
+        return Err(Error::Invalid);
+    }
+    Ok(vals)
+}
+
+fn val_of_fields(tp: &InterfaceType, vals: Vec<ComponentVal>) -> ComponentVal {
+    match tp {
+        InterfaceType::Record(fields) => ComponentVal::Record(fields.iter().map(|(name, _)| name.clone()).zip(vals).collect()),
+        _ => ComponentVal::Tuple(vals),
+    }
+}
+
+/// Lifting and lowering context, on the memory and handles of an instance.
+struct Cx<'a> {
+    store: &'a mut Store,
+    opts: &'a CanonOptions,
+}
+
+impl<'a> Cx<'a> {
+    fn memory(&self) -> Result<MemAddr, Error> {
+        self.opts.memory.ok_or(Error::Invalid)
+    }
+
+    fn read(&self, ptr: u32, n: u32) -> Result<Vec<u8>, Error> {
+        let memory = self.memory()?;
+        self.store.mems[memory].read(ptr as usize, n as usize).ok_or(Error::Trap)
+    }
+
+    fn write(&mut self, ptr: u32, bytes: &[u8]) -> Result<(), Error> {
+        let memory = self.memory()?;
+        self.store.mems[memory].write(ptr as usize, bytes).ok_or(Error::Trap)
+    }
+
+    fn read_uint(&self, ptr: u32, n: u32) -> Result<u64, Error> {
+        let mut bytes = [0; 8];
+        bytes[..n as usize].copy_from_slice(&self.read(ptr, n)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn write_uint(&mut self, ptr: u32, n: u32, v: u64) -> Result<(), Error> {
+        self.write(ptr, &v.to_le_bytes()[..n as usize])
+    }
+
+    /// Allocate in the linear memory with the `realloc` of the instance.
+    fn realloc(&mut self, align: u32, size: u32) -> Result<u32, Error> {
+        let realloc = self.opts.realloc.ok_or(Error::Invalid)?;
+        let args = vec![Val::I32Const(0), Val::I32Const(0), Val::I32Const(align), Val::I32Const(size)];
+        let ptr = match func_invoke(self.store, realloc, args).1?.as_slice() {
+            [Val::I32Const(ptr)] => *ptr,
+            _ => return Err(Error::Invalid),
+        };
+        if !ptr.is_multiple_of(align) || self.read(ptr, size).is_err() {
+            return Err(Error::Trap);
+        }
+        Ok(ptr)
+    }
+
+    fn lift_handle(&mut self, idx: u32, tp: &InterfaceType) -> Result<ComponentVal, Error> {
+        let mut handles = lock(&self.opts.handles);
+        match tp {
+            InterfaceType::Own(rt) => match handles.remove(idx, rt) {
+                Some((resource, true)) => Ok(ComponentVal::Own(resource)),
+                _ => Err(Error::Trap),
+            },
+            InterfaceType::Borrow(rt) => handles.get(idx, rt).map(ComponentVal::Borrow).ok_or(Error::Trap),
+            _ => unreachable!(),
+        }
+    }
+
+    fn lower_handle(&mut self, val: ComponentVal, tp: &InterfaceType) -> Result<u32, Error> {
+        let mut handles = lock(&self.opts.handles);
+        match (tp, val) {
+            (InterfaceType::Own(rt), ComponentVal::Own(resource)) if &resource.tp == rt => Ok(handles.insert(resource, true)),
+            (InterfaceType::Borrow(rt), ComponentVal::Borrow(resource)) if &resource.tp == rt => {
+                if handles.local.contains(rt) {
+                    Ok(resource.rep)
+                } else {
+                    Ok(handles.insert(resource, false))
+                }
+            },
+            _ => Err(Error::Invalid),
+        }
+    }
+
+    fn load_string(&self, ptr: u32, tagged_len: u32) -> Result<String, Error> {
+        let utf16 = match self.opts.encoding {
+            StringEncoding::Utf8 => {
+                let bytes = self.read(ptr, tagged_len)?;
+                return String::from_utf8(bytes).map_err(|_| Error::Trap);
+            },
+            StringEncoding::Utf16 => true,
+            StringEncoding::Latin1Utf16 => tagged_len & (1 << 31) != 0,
+        };
+        if !ptr.is_multiple_of(2) {
+            return Err(Error::Trap);
+        }
+        let len = tagged_len & !(1 << 31);
+        if utf16 {
+            let bytes = self.read(ptr, len.checked_mul(2).ok_or(Error::Trap)?)?;
+            let units: Vec<u16> = bytes.chunks(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect();
+            String::from_utf16(&units).map_err(|_| Error::Trap)
+        } else {
+            Ok(self.read(ptr, len)?.into_iter().map(char::from).collect())
+        }
+    }
+
+    fn store_string(&mut self, s: &str) -> Result<(u32, u32), Error> {
+        let latin1 = s.chars().all(|c| (c as u32) < 0x100);
+        match self.opts.encoding {
+            StringEncoding::Utf8 => {
+                let ptr = self.realloc(1, s.len() as u32)?;
+                self.write(ptr, s.as_bytes())?;
+                Ok((ptr, s.len() as u32))
+            },
+            StringEncoding::Latin1Utf16 if latin1 => {
+                let bytes: Vec<u8> = s.chars().map(|c| c as u8).collect();
+                let ptr = self.realloc(2, bytes.len() as u32)?;
+                self.write(ptr, &bytes)?;
+                Ok((ptr, bytes.len() as u32))
+            },
+            encoding => {
+                let bytes: Vec<u8> = s.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+                let ptr = self.realloc(2, bytes.len() as u32)?;
+                self.write(ptr, &bytes)?;
+                let len = bytes.len() as u32 / 2;
+                Ok((ptr, if encoding == StringEncoding::Utf16 { len } else { len | 1 << 31 }))
+            },
+        }
+    }
+
+    fn load_list(&mut self, ptr: u32, len: u32, tp: &InterfaceType) -> Result<Vec<ComponentVal>, Error> {
+        if !ptr.is_multiple_of(alignment(tp)) {
+            return Err(Error::Trap);
+        }
+        let size = elem_size(tp);
+        (0..len).map(|i| self.load(ptr + i * size, tp)).collect()
+    }
+
+    fn store_list(&mut self, vals: Vec<ComponentVal>, tp: &InterfaceType) -> Result<(u32, u32), Error> {
+        let size = elem_size(tp);
+        let len = vals.len() as u32;
+        let ptr = self.realloc(alignment(tp), len.checked_mul(size).ok_or(Error::Trap)?)?;
+        for (i, val) in vals.into_iter().enumerate() {
+            self.store(val, tp, ptr + i as u32 * size)?;
+        }
+        Ok((ptr, len))
+    }
+
+    fn load(&mut self, ptr: u32, tp: &InterfaceType) -> Result<ComponentVal, Error> {
+        if !ptr.is_multiple_of(alignment(tp)) {
+            return Err(Error::Trap);
+        }
+        let val = match tp {
+            InterfaceType::Bool => ComponentVal::Bool(self.read_uint(ptr, 1)? != 0),
+            InterfaceType::S8 => ComponentVal::S8(self.read_uint(ptr, 1)? as i8),
+            InterfaceType::U8 => ComponentVal::U8(self.read_uint(ptr, 1)? as u8),
+            InterfaceType::S16 => ComponentVal::S16(self.read_uint(ptr, 2)? as i16),
+            InterfaceType::U16 => ComponentVal::U16(self.read_uint(ptr, 2)? as u16),
+            InterfaceType::S32 => ComponentVal::S32(self.read_uint(ptr, 4)? as i32),
+            InterfaceType::U32 => ComponentVal::U32(self.read_uint(ptr, 4)? as u32),
+            InterfaceType::S64 => ComponentVal::S64(self.read_uint(ptr, 8)? as i64),
+            InterfaceType::U64 => ComponentVal::U64(self.read_uint(ptr, 8)?),
+            InterfaceType::F32 => ComponentVal::F32(f32::from_bits(self.read_uint(ptr, 4)? as u32)),
+            InterfaceType::F64 => ComponentVal::F64(f64::from_bits(self.read_uint(ptr, 8)?)),
+            InterfaceType::Char => ComponentVal::Char(char::from_u32(self.read_uint(ptr, 4)? as u32).ok_or(Error::Trap)?),
+            InterfaceType::String => {
+                let (ptr, len) = (self.read_uint(ptr, 4)? as u32, self.read_uint(ptr + 4, 4)? as u32);
+                ComponentVal::String(self.load_string(ptr, len)?)
+            },
+            InterfaceType::List(tp) => {
+                let (ptr, len) = (self.read_uint(ptr, 4)? as u32, self.read_uint(ptr + 4, 4)? as u32);
+                ComponentVal::List(self.load_list(ptr, len, tp)?)
+            },
+            InterfaceType::Record(_) | InterfaceType::Tuple(_) => {
+                let mut vals = vec![];
+                let mut offset = 0;
+                for tp in field_types(tp) {
+                    offset = align_to(offset, alignment(tp));
+                    vals.push(self.load(ptr + offset, tp)?);
+                    offset += elem_size(tp);
+                }
+                val_of_fields(tp, vals)
+            },
+            InterfaceType::Flags(names) => {
+                let bits = match flags_size(names.len()) {
+                    0 => vec![],
+                    size @ 1..=2 => vec![self.read_uint(ptr, size)? as u32],
+                    size => (0..size / 4).map(|i| self.read_uint(ptr + i * 4, 4).map(|n| n as u32)).collect::<Result<_, _>>()?,
+                };
+                ComponentVal::Flags(bits_to_flags(names, &bits))
+            },
+            InterfaceType::Own(_) | InterfaceType::Borrow(_) => {
+                let idx = self.read_uint(ptr, 4)? as u32;
+                self.lift_handle(idx, tp)?
+            },
+            _ => {
+                let cases = tp.cases().unwrap();
+                let disc_size = discriminant_size(cases.len());
+                let idx = self.read_uint(ptr, disc_size)? as usize;
+                let payload = match cases.get(idx).ok_or(Error::Trap)? {
+                    Some(case) => Some(self.load(ptr + align_to(disc_size, max_case_alignment(&cases)), case)?),
+                    None => None,
+                };
+                val_of_case(tp, idx, payload)
+            },
+        };
+        Ok(val)
+    }
+
+    fn store(&mut self, val: ComponentVal, tp: &InterfaceType, ptr: u32) -> Result<(), Error> {
+        if !ptr.is_multiple_of(alignment(tp)) {
+            return Err(Error::Trap);
+        }
+        match (tp, val) {
+            (InterfaceType::Bool, ComponentVal::Bool(b)) => self.write_uint(ptr, 1, b as u64),
+            (InterfaceType::S8, ComponentVal::S8(n)) => self.write_uint(ptr, 1, n as u64),
+            (InterfaceType::U8, ComponentVal::U8(n)) => self.write_uint(ptr, 1, n as u64),
+            (InterfaceType::S16, ComponentVal::S16(n)) => self.write_uint(ptr, 2, n as u64),
+            (InterfaceType::U16, ComponentVal::U16(n)) => self.write_uint(ptr, 2, n as u64),
+            (InterfaceType::S32, ComponentVal::S32(n)) => self.write_uint(ptr, 4, n as u64),
+            (InterfaceType::U32, ComponentVal::U32(n)) => self.write_uint(ptr, 4, n as u64),
+            (InterfaceType::S64, ComponentVal::S64(n)) => self.write_uint(ptr, 8, n as u64),
+            (InterfaceType::U64, ComponentVal::U64(n)) => self.write_uint(ptr, 8, n),
+            (InterfaceType::F32, ComponentVal::F32(f)) => self.write_uint(ptr, 4, f.to_bits() as u64),
+            (InterfaceType::F64, ComponentVal::F64(f)) => self.write_uint(ptr, 8, f.to_bits()),
+            (InterfaceType::Char, ComponentVal::Char(c)) => self.write_uint(ptr, 4, c as u64),
+            (InterfaceType::String, ComponentVal::String(s)) => {
+                let (begin, len) = self.store_string(&s)?;
+                self.write_uint(ptr, 4, begin as u64)?;
+                self.write_uint(ptr + 4, 4, len as u64)
+            },
+            (InterfaceType::List(tp), ComponentVal::List(vals)) => {
+                let (begin, len) = self.store_list(vals, tp)?;
+                self.write_uint(ptr, 4, begin as u64)?;
+                self.write_uint(ptr + 4, 4, len as u64)
+            },
+            (InterfaceType::Record(_), val) | (InterfaceType::Tuple(_), val) => {
+                let mut offset = 0;
+                for (val, tp) in fields_of(tp, val)?.into_iter().zip(field_types(tp)) {
+                    offset = align_to(offset, alignment(tp));
+                    self.store(val, tp, ptr + offset)?;
+                    offset += elem_size(tp);
+                }
+                Ok(())
+            },
+            (InterfaceType::Flags(names), ComponentVal::Flags(set)) => {
+                let bits = flags_to_bits(names, &set)?;
+                match flags_size(names.len()) {
+                    0 => Ok(()),
+                    size @ 1..=2 => self.write_uint(ptr, size, bits[0] as u64),
+                    _ => {
+                        for (i, n) in bits.into_iter().enumerate() {
+                            self.write_uint(ptr + i as u32 * 4, 4, n as u64)?;
+                        }
+                        Ok(())
+                    },
+                }
+            },
+            (InterfaceType::Own(_), val) | (InterfaceType::Borrow(_), val) => {
+                let idx = self.lower_handle(val, tp)?;
+                self.write_uint(ptr, 4, idx as u64)
+            },
+            (_, val) => {
+                let cases = tp.cases().ok_or(Error::Invalid)?;
+                let disc_size = discriminant_size(cases.len());
+                let (idx, payload) = case_of(tp, val)?;
+                self.write_uint(ptr, disc_size, idx as u64)?;
+                match (&cases[idx], payload) {
+                    (Some(case), Some(val)) => self.store(val, case, ptr + align_to(disc_size, max_case_alignment(&cases))),
+                    _ => Ok(()),
+                }
+            },
+        }
+    }
+
+    fn lift_flat(&mut self, vals: &mut std::vec::IntoIter<Val>, tp: &InterfaceType) -> Result<ComponentVal, Error> {
+        fn next_i32(vals: &mut std::vec::IntoIter<Val>) -> Result<u32, Error> {
+            match vals.next() {
+                Some(Val::I32Const(n)) => Ok(n),
+                _ => Err(Error::Invalid),
+            }
+        }
+        let val = match tp {
+            InterfaceType::Bool => ComponentVal::Bool(next_i32(vals)? != 0),
+            InterfaceType::S8 => ComponentVal::S8(next_i32(vals)? as i8),
+            InterfaceType::U8 => ComponentVal::U8(next_i32(vals)? as u8),
+            InterfaceType::S16 => ComponentVal::S16(next_i32(vals)? as i16),
+            InterfaceType::U16 => ComponentVal::U16(next_i32(vals)? as u16),
+            InterfaceType::S32 => ComponentVal::S32(next_i32(vals)? as i32),
+            InterfaceType::U32 => ComponentVal::U32(next_i32(vals)?),
+            InterfaceType::S64 | InterfaceType::U64 => match vals.next() {
+                Some(Val::I64Const(n)) if tp == &InterfaceType::S64 => ComponentVal::S64(n as i64),
+                Some(Val::I64Const(n)) => ComponentVal::U64(n),
+                _ => return Err(Error::Invalid),
+            },
+            InterfaceType::F32 => match vals.next() {
+                Some(Val::F32Const(f)) => ComponentVal::F32(f),
+                _ => return Err(Error::Invalid),
+            },
+            InterfaceType::F64 => match vals.next() {
+                Some(Val::F64Const(f)) => ComponentVal::F64(f),
+                _ => return Err(Error::Invalid),
+            },
+            InterfaceType::Char => ComponentVal::Char(char::from_u32(next_i32(vals)?).ok_or(Error::Trap)?),
+            InterfaceType::String => {
+                let (ptr, len) = (next_i32(vals)?, next_i32(vals)?);
+                ComponentVal::String(self.load_string(ptr, len)?)
+            },
+            InterfaceType::List(tp) => {
+                let (ptr, len) = (next_i32(vals)?, next_i32(vals)?);
+                ComponentVal::List(self.load_list(ptr, len, tp)?)
+            },
+            InterfaceType::Record(_) | InterfaceType::Tuple(_) => {
+                let vals = field_types(tp).into_iter().map(|tp| self.lift_flat(vals, tp)).collect::<Result<_, _>>()?;
+                val_of_fields(tp, vals)
+            },
+            InterfaceType::Flags(names) => {
+                let bits = (0..names.len().div_ceil(32)).map(|_| next_i32(vals)).collect::<Result<Vec<_>, _>>()?;
+                ComponentVal::Flags(bits_to_flags(names, &bits))
+            },
+            InterfaceType::Own(_) | InterfaceType::Borrow(_) => {
+                let idx = next_i32(vals)?;
+                self.lift_handle(idx, tp)?
+            },
+            _ => {
+                let cases = tp.cases().unwrap();
+                let idx = next_i32(vals)? as usize;
+                let joined: Vec<Val> = vals.take(flatten_cases(&cases).len()).collect();
+                let payload = match cases.get(idx).ok_or(Error::Trap)? {
+                    Some(case) => {
+                        let mut flat = vec![];
+                        flatten(case, &mut flat);
+                        let coerced: Vec<Val> = joined.into_iter().zip(flat.iter()).map(|(val, valtype)| coerce_from_joined(val, valtype)).collect();
+                        Some(self.lift_flat(&mut coerced.into_iter(), case)?)
+                    },
+                    None => None,
+                };
+                val_of_case(tp, idx, payload)
+            },
+        };
+        Ok(val)
+    }
+
+    fn lower_flat(&mut self, val: ComponentVal, tp: &InterfaceType) -> Result<Vec<Val>, Error> {
+        let vals = match (tp, val) {
+            (InterfaceType::Bool, ComponentVal::Bool(b)) => vec![Val::I32Const(b as u32)],
+            (InterfaceType::S8, ComponentVal::S8(n)) => vec![Val::I32Const(n as u32)],
+            (InterfaceType::U8, ComponentVal::U8(n)) => vec![Val::I32Const(n as u32)],
+            (InterfaceType::S16, ComponentVal::S16(n)) => vec![Val::I32Const(n as u32)],
+            (InterfaceType::U16, ComponentVal::U16(n)) => vec![Val::I32Const(n as u32)],
+            (InterfaceType::S32, ComponentVal::S32(n)) => vec![Val::I32Const(n as u32)],
+            (InterfaceType::U32, ComponentVal::U32(n)) => vec![Val::I32Const(n)],
+            (InterfaceType::S64, ComponentVal::S64(n)) => vec![Val::I64Const(n as u64)],
+            (InterfaceType::U64, ComponentVal::U64(n)) => vec![Val::I64Const(n)],
+            (InterfaceType::F32, ComponentVal::F32(f)) => vec![Val::F32Const(f)],
+            (InterfaceType::F64, ComponentVal::F64(f)) => vec![Val::F64Const(f)],
+            (InterfaceType::Char, ComponentVal::Char(c)) => vec![Val::I32Const(c as u32)],
+            (InterfaceType::String, ComponentVal::String(s)) => {
+                let (ptr, len) = self.store_string(&s)?;
+                vec![Val::I32Const(ptr), Val::I32Const(len)]
+            },
+            (InterfaceType::List(tp), ComponentVal::List(vals)) => {
+                let (ptr, len) = self.store_list(vals, tp)?;
+                vec![Val::I32Const(ptr), Val::I32Const(len)]
+            },
+            (InterfaceType::Record(_), val) | (InterfaceType::Tuple(_), val) => {
+                let mut flat = vec![];
+                for (val, tp) in fields_of(tp, val)?.into_iter().zip(field_types(tp)) {
+                    flat.extend(self.lower_flat(val, tp)?);
+                }
+                flat
+            },
+            (InterfaceType::Flags(names), ComponentVal::Flags(set)) => {
+                flags_to_bits(names, &set)?.into_iter().map(Val::I32Const).collect()
+            },
+            (InterfaceType::Own(_), val) | (InterfaceType::Borrow(_), val) => vec![Val::I32Const(self.lower_handle(val, tp)?)],
+            (_, val) => {
+                let cases = tp.cases().ok_or(Error::Invalid)?;
+                let joined = flatten_cases(&cases);
+                let (idx, payload) = case_of(tp, val)?;
+                let payload = match (&cases[idx], payload) {
+                    (Some(case), Some(val)) => self.lower_flat(val, case)?,
+                    _ => vec![],
+                };
+                let mut flat = vec![Val::I32Const(idx as u32)];
+                for (i, valtype) in joined.iter().enumerate() {
+                    flat.push(match payload.get(i) {
+                        Some(val) => coerce_to_joined(*val, valtype),
+                        None => zero(valtype),
+                    });
+                }
+                flat
+            },
+        };
+        Ok(vals)
+    }
+}
+
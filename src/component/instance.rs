@@ -0,0 +1,719 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    Name,
+    Module,
+    FuncType,
+    ValType,
+    Store,
+    Val,
+    ExternVal,
+    FuncAddr,
+    TableAddr,
+    MemAddr,
+    GlobalAddr,
+    TagAddr,
+    HostCode,
+    HostResult,
+    Error,
+    alloc_hostfunc,
+    func_type,
+    func_invoke,
+    instanciate_module,
+};
+use super::{
+    Component,
+    ComponentDef,
+    CoreSort,
+    Sort,
+    CoreInstanceExpr,
+    InstanceExpr,
+    Alias,
+    AliasTarget,
+    PrimValType,
+    ValTypeRef,
+    DefValType,
+    DefType,
+    InstanceDecl,
+    ExternDesc,
+    TypeBound,
+    Canon,
+    CanonOpt,
+    StringEncoding,
+    InterfaceType,
+    ComponentFuncType,
+    ComponentType,
+    ResourceType,
+    ResourceDtor,
+    Resource,
+    ComponentVal,
+    CanonOptions,
+    HandleTable,
+    lock,
+    flatten_functype,
+    lift_call,
+    lower_call,
+};
+
+#[derive(Clone, Default)]
+pub struct ComponentInst {
+    pub exports: Vec<(Name, ComponentExternVal)>,
+}
+
+#[derive(Clone)]
+pub enum ComponentExternVal {
+    Func(ComponentFunc),
+    Instance(ComponentInst),
+    Type(ComponentType),
+}
+
+/// Host function of the component model, called with values of its parameter types.
+pub type ComponentHostFunc = Arc<dyn Fn(&mut Store, Vec<ComponentVal>) -> Result<Vec<ComponentVal>, Error> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct ComponentFunc {
+    pub tp: ComponentFuncType,
+    code: ComponentCode,
+}
+
+#[derive(Clone)]
+enum ComponentCode {
+    Lifted(FuncAddr, CanonOptions),
+    Host(ComponentHostFunc),
+}
+
+pub fn component_func_host(tp: ComponentFuncType, hostfunc: ComponentHostFunc) -> ComponentFunc {
+    ComponentFunc { tp, code: ComponentCode::Host(hostfunc) }
+}
+
+pub fn component_export(componentinst: ComponentInst, name: Name) -> Result<ComponentExternVal, Error> {
+    for (export, value) in componentinst.exports {
+        if export == name {
+            return Ok(value);
+        }
+    }
+    Err(Error::Invalid)
+}
+
+pub fn component_invoke(store: &mut Store, func: &ComponentFunc, args: Vec<ComponentVal>) -> Result<Vec<ComponentVal>, Error> {
+    if args.len() != func.tp.params.len() {
+        return Err(Error::Invalid);
+    }
+    let results = match &func.code {
+        ComponentCode::Lifted(funcaddr, opts) => lift_call(store, *funcaddr, opts, &func.tp, args)?,
+        ComponentCode::Host(hostfunc) => hostfunc(store, args)?,
+    };
+    if results.len() != func.tp.results.len() {
+        return Err(Error::Invalid);
+    }
+    Ok(results)
+}
+
+/// Instanciate a component, whose imports are given by name.
+/// A versioned import may be given under another version of the same semver track,
+/// as `wasi:cli/stdout@0.2.6` by `wasi:cli/stdout@0.2.0`.
+/// Its core modules are instanciated in `store`, and nested components with it.
+pub fn component_instanciate(store: &mut Store, component: &Component, imports: Vec<(Name, ComponentExternVal)>) -> Result<ComponentInst, Error> {
+    instanciate(store, component, imports, None)
+}
+
+fn instanciate<'a>(store: &mut Store, component: &'a Component, imports: Vec<(Name, ComponentExternVal)>, outer: Option<&'a Scope<'a>>) -> Result<ComponentInst, Error> {
+    let mut scope = Scope { outer, ..Scope::default() };
+    let mut exports = vec![];
+    for def in &component.defs {
+        match def {
+            ComponentDef::Import(import) => {
+                let value = imports.iter().find(|(name, _)| name == &import.name)
+                    .or_else(|| imports.iter().find(|(name, _)| compatible(name, &import.name)))
+                    .map(|(_, value)| value.clone());
+                scope.import(value, &import.desc)?;
+            },
+            ComponentDef::Export(export) => {
+                let value = scope.extern_of(export.sort, export.idx)?;
+                scope.push_extern(export.sort, value.clone())?;
+                exports.push((export.name.clone(), value));
+            },
+            _ => scope.define(store, def)?,
+        }
+    }
+    Ok(ComponentInst { exports })
+}
+
+/// Index spaces of a component being instanciated.
+#[derive(Default)]
+struct Scope<'a> {
+    outer: Option<&'a Scope<'a>>,
+    handles: Arc<Mutex<HandleTable>>,
+    core_modules: Vec<&'a Module>,
+    core_instances: Vec<Vec<(Name, ExternVal)>>,
+    core_funcs: Vec<FuncAddr>,
+    core_tables: Vec<TableAddr>,
+    core_mems: Vec<MemAddr>,
+    core_globals: Vec<GlobalAddr>,
+    core_tags: Vec<TagAddr>,
+    core_types: Vec<FuncType>,
+    components: Vec<&'a Component>,
+    instances: Vec<ComponentInst>,
+    funcs: Vec<ComponentFunc>,
+    types: Vec<ComponentType>,
+}
+
+/// Whether two names differ only by versions on the same semver track.
+fn compatible(name1: &str, name2: &str) -> bool {
+    match (name1.split_once('@'), name2.split_once('@')) {
+        (Some((name1, version1)), Some((name2, version2))) => {
+            name1 == name2 && track(version1).is_some() && track(version1) == track(version2)
+        },
+        _ => false,
+    }
+}
+
+// the leading numbers up to the first non-zero one, which compatible versions share
+fn track(version: &str) -> Option<Vec<u64>> {
+    let numbers = version.split('.').map(|n| n.parse().ok()).collect::<Option<Vec<u64>>>()?;
+    if numbers.len() != 3 {
+        return None;
+    }
+    let n = numbers.iter().position(|n| *n != 0).map_or(3, |i| i + 1);
+    Some(numbers[..n].to_vec())
+}
+
+fn get<T: Clone>(space: &[T], idx: u32) -> Result<T, Error> {
+    space.get(idx as usize).cloned().ok_or(Error::OutOfIndex("component".to_string()))
+}
+
+impl<'a> Scope<'a> {
+    fn define(&mut self, store: &mut Store, def: &'a ComponentDef) -> Result<(), Error> {
+        match def {
+            ComponentDef::CoreModule(module) => self.core_modules.push(module),
+            ComponentDef::CoreInstance(CoreInstanceExpr::Instantiate(moduleidx, args)) => {
+                let module = get(&self.core_modules, *moduleidx)?;
+                let mut externvals = vec![];
+                for import in &module.imports {
+                    let (_, instanceidx) = args.iter().find(|(name, _)| name == &import.module).ok_or(Error::Invalid)?;
+                    let instance = get(&self.core_instances, *instanceidx)?;
+                    let (_, value) = instance.into_iter().find(|(name, _)| name == &import.name).ok_or(Error::Invalid)?;
+                    externvals.push(value);
+                }
+                let moduleinst = instanciate_module(store, module, externvals)?;
+                self.core_instances.push(moduleinst.exports.into_iter().map(|export| (export.name, export.value)).collect());
+            },
+            ComponentDef::CoreInstance(CoreInstanceExpr::Exports(exports)) => {
+                let mut instance = vec![];
+                for (name, sort, idx) in exports {
+                    instance.push((name.clone(), self.core_extern_of(*sort, *idx)?));
+                }
+                self.core_instances.push(instance);
+            },
+            ComponentDef::CoreType(functype) => self.core_types.push(functype.clone()),
+            ComponentDef::Component(component) => self.components.push(component),
+            ComponentDef::Instance(InstanceExpr::Instantiate(componentidx, args)) => {
+                let component = get(&self.components, *componentidx)?;
+                let mut imports = vec![];
+                for (name, sort, idx) in args {
+                    imports.push((name.clone(), self.extern_of(*sort, *idx)?));
+                }
+                let instance = instanciate(store, component, imports, Some(self))?;
+                self.instances.push(instance);
+            },
+            ComponentDef::Instance(InstanceExpr::Exports(exports)) => {
+                let mut instance = ComponentInst::default();
+                for (name, sort, idx) in exports {
+                    instance.exports.push((name.clone(), self.extern_of(*sort, *idx)?));
+                }
+                self.instances.push(instance);
+            },
+            ComponentDef::Alias(alias) => self.alias(alias)?,
+            ComponentDef::Type(deftype) => {
+                let tp = self.deftype(deftype)?;
+                self.types.push(tp);
+            },
+            ComponentDef::Canon(canon) => self.canon(store, canon)?,
+            ComponentDef::Import(_) | ComponentDef::Export(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn import(&mut self, value: Option<ComponentExternVal>, desc: &ExternDesc) -> Result<(), Error> {
+        match (desc, value) {
+            (ExternDesc::Func(_), Some(ComponentExternVal::Func(func))) => self.funcs.push(func),
+            (ExternDesc::Instance(typeidx), Some(ComponentExternVal::Instance(mut instance))) => {
+                // resources left to the host are distinct from every other one
+                if let ComponentType::Instance { resources } = get(&self.types, *typeidx)? {
+                    for name in resources {
+                        if instance.exports.iter().all(|(export, _)| export != &name) {
+                            let tp = ComponentType::Resource(ResourceType::new(None));
+                            instance.exports.push((name, ComponentExternVal::Type(tp)));
+                        }
+                    }
+                }
+                self.instances.push(instance);
+            },
+            (ExternDesc::Type(_), Some(ComponentExternVal::Type(tp))) => self.types.push(tp),
+            (ExternDesc::Type(TypeBound::Eq(typeidx)), None) => {
+                let tp = get(&self.types, *typeidx)?;
+                self.types.push(tp);
+            },
+            _ => return Err(Error::Invalid),
+        }
+        Ok(())
+    }
+
+    fn extern_of(&self, sort: Sort, idx: u32) -> Result<ComponentExternVal, Error> {
+        match sort {
+            Sort::Func => Ok(ComponentExternVal::Func(get(&self.funcs, idx)?)),
+            Sort::Instance => Ok(ComponentExternVal::Instance(get(&self.instances, idx)?)),
+            Sort::Type => Ok(ComponentExternVal::Type(get(&self.types, idx)?)),
+            _ => Err(Error::Invalid),
+        }
+    }
+
+    fn push_extern(&mut self, sort: Sort, value: ComponentExternVal) -> Result<(), Error> {
+        match (sort, value) {
+            (Sort::Func, ComponentExternVal::Func(func)) => self.funcs.push(func),
+            (Sort::Instance, ComponentExternVal::Instance(instance)) => self.instances.push(instance),
+            (Sort::Type, ComponentExternVal::Type(tp)) => self.types.push(tp),
+            _ => return Err(Error::Invalid),
+        }
+        Ok(())
+    }
+
+    fn core_extern_of(&self, sort: CoreSort, idx: u32) -> Result<ExternVal, Error> {
+        match sort {
+            CoreSort::Func => Ok(ExternVal::Func(get(&self.core_funcs, idx)?)),
+            CoreSort::Table => Ok(ExternVal::Table(get(&self.core_tables, idx)?)),
+            CoreSort::Memory => Ok(ExternVal::Mem(get(&self.core_mems, idx)?)),
+            CoreSort::Global => Ok(ExternVal::Global(get(&self.core_globals, idx)?)),
+            CoreSort::Tag => Ok(ExternVal::Tag(get(&self.core_tags, idx)?)),
+            _ => Err(Error::Invalid),
+        }
+    }
+
+    fn push_core_extern(&mut self, sort: CoreSort, value: ExternVal) -> Result<(), Error> {
+        match (sort, value) {
+            (CoreSort::Func, ExternVal::Func(addr)) => self.core_funcs.push(addr),
+            (CoreSort::Table, ExternVal::Table(addr)) => self.core_tables.push(addr),
+            (CoreSort::Memory, ExternVal::Mem(addr)) => self.core_mems.push(addr),
+            (CoreSort::Global, ExternVal::Global(addr)) => self.core_globals.push(addr),
+            (CoreSort::Tag, ExternVal::Tag(addr)) => self.core_tags.push(addr),
+            _ => return Err(Error::Invalid),
+        }
+        Ok(())
+    }
+
+    fn alias(&mut self, alias: &Alias) -> Result<(), Error> {
+        match &alias.target {
+            AliasTarget::Export(instanceidx, name) => {
+                let instance = get(&self.instances, *instanceidx)?;
+                let value = component_export(instance, name.clone())?;
+                self.push_extern(alias.sort, value)
+            },
+            AliasTarget::CoreExport(instanceidx, name) => {
+                let instance = get(&self.core_instances, *instanceidx)?;
+                let (_, value) = instance.into_iter().find(|(export, _)| export == name).ok_or(Error::Invalid)?;
+                match alias.sort {
+                    Sort::Core(sort) => self.push_core_extern(sort, value),
+                    _ => Err(Error::Invalid),
+                }
+            },
+            AliasTarget::Outer(count, idx) => {
+                let mut scope: &Scope<'a> = self;
+                for _ in 0..*count {
+                    scope = scope.outer.ok_or(Error::Invalid)?;
+                }
+                match alias.sort {
+                    Sort::Type => {
+                        let tp = get(&scope.types, *idx)?;
+                        self.types.push(tp);
+                    },
+                    Sort::Core(CoreSort::Type) => {
+                        let tp = get(&scope.core_types, *idx)?;
+                        self.core_types.push(tp);
+                    },
+                    Sort::Core(CoreSort::Module) => {
+                        let module = get(&scope.core_modules, *idx)?;
+                        self.core_modules.push(module);
+                    },
+                    Sort::Component => {
+                        let component = get(&scope.components, *idx)?;
+                        self.components.push(component);
+                    },
+                    _ => return Err(Error::Invalid),
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn valtype(&self, valtype: &ValTypeRef) -> Result<InterfaceType, Error> {
+        match valtype {
+            ValTypeRef::Prim(primvaltype) => Ok(primitive(primvaltype)),
+            ValTypeRef::Type(typeidx) => match get(&self.types, *typeidx)? {
+                ComponentType::Val(tp) => Ok(tp),
+                _ => Err(Error::Invalid),
+            },
+        }
+    }
+
+    fn option_valtype(&self, valtype: &Option<ValTypeRef>) -> Result<Option<InterfaceType>, Error> {
+        valtype.as_ref().map(|valtype| self.valtype(valtype)).transpose()
+    }
+
+    fn resourcetype(&self, typeidx: u32) -> Result<ResourceType, Error> {
+        match get(&self.types, typeidx)? {
+            ComponentType::Resource(tp) => Ok(tp),
+            _ => Err(Error::Invalid),
+        }
+    }
+
+    fn defvaltype(&self, defvaltype: &DefValType) -> Result<InterfaceType, Error> {
+        let tp = match defvaltype {
+            DefValType::Prim(primvaltype) => primitive(primvaltype),
+            DefValType::Record(fields) => InterfaceType::Record(fields.iter().map(|(name, tp)| Ok((name.clone(), self.valtype(tp)?))).collect::<Result<_, Error>>()?),
+            DefValType::Variant(cases) => InterfaceType::Variant(cases.iter().map(|(name, tp)| Ok((name.clone(), self.option_valtype(tp)?))).collect::<Result<_, Error>>()?),
+            DefValType::List(tp) => InterfaceType::List(Box::new(self.valtype(tp)?)),
+            DefValType::Tuple(tps) => InterfaceType::Tuple(tps.iter().map(|tp| self.valtype(tp)).collect::<Result<_, _>>()?),
+            DefValType::Flags(names) => InterfaceType::Flags(names.clone()),
+            DefValType::Enum(names) => InterfaceType::Enum(names.clone()),
+            DefValType::Option(tp) => InterfaceType::Option(Box::new(self.valtype(tp)?)),
+            DefValType::Result(ok, err) => InterfaceType::Result(self.option_valtype(ok)?.map(Box::new), self.option_valtype(err)?.map(Box::new)),
+            DefValType::Own(typeidx) => InterfaceType::Own(self.resourcetype(*typeidx)?),
+            DefValType::Borrow(typeidx) => InterfaceType::Borrow(self.resourcetype(*typeidx)?),
+        };
+        Ok(tp)
+    }
+
+    fn deftype(&mut self, deftype: &DefType) -> Result<ComponentType, Error> {
+        let tp = match deftype {
+            DefType::Val(defvaltype) => ComponentType::Val(self.defvaltype(defvaltype)?),
+            DefType::Func(functype) => {
+                let params = functype.params.iter().map(|(name, tp)| Ok((name.clone(), self.valtype(tp)?))).collect::<Result<_, Error>>()?;
+                let results = functype.results.iter().map(|tp| self.valtype(tp)).collect::<Result<_, _>>()?;
+                ComponentType::Func(ComponentFuncType { params, results })
+            },
+            DefType::Component(_) => ComponentType::Component,
+            DefType::Instance(decls) => {
+                let resources = decls.iter().filter_map(|decl| match decl {
+                    InstanceDecl::Export(name, ExternDesc::Type(TypeBound::SubResource)) => Some(name.clone()),
+                    _ => None,
+                }).collect();
+                ComponentType::Instance { resources }
+            },
+            DefType::Resource(dtor) => {
+                let dtor = match dtor {
+                    Some(funcidx) => Some(ResourceDtor::Core(get(&self.core_funcs, *funcidx)?)),
+                    None => None,
+                };
+                let tp = ResourceType::new(dtor);
+                lock(&self.handles).local.push(tp.clone());
+                ComponentType::Resource(tp)
+            },
+        };
+        Ok(tp)
+    }
+
+    fn canon_options(&self, opts: &[CanonOpt]) -> Result<CanonOptions, Error> {
+        let mut options = CanonOptions {
+            encoding: StringEncoding::Utf8,
+            memory: None,
+            realloc: None,
+            post_return: None,
+            handles: self.handles.clone(),
+        };
+        for opt in opts {
+            match opt {
+                CanonOpt::StringEncoding(encoding) => options.encoding = *encoding,
+                CanonOpt::Memory(memidx) => options.memory = Some(get(&self.core_mems, *memidx)?),
+                CanonOpt::Realloc(funcidx) => options.realloc = Some(get(&self.core_funcs, *funcidx)?),
+                CanonOpt::PostReturn(funcidx) => options.post_return = Some(get(&self.core_funcs, *funcidx)?),
+            }
+        }
+        Ok(options)
+    }
+
+    fn canon(&mut self, store: &mut Store, canon: &Canon) -> Result<(), Error> {
+        match canon {
+            Canon::Lift(funcidx, opts, typeidx) => {
+                let funcaddr = get(&self.core_funcs, *funcidx)?;
+                let tp = match get(&self.types, *typeidx)? {
+                    ComponentType::Func(tp) => tp,
+                    _ => return Err(Error::Invalid),
+                };
+                if func_type(store, funcaddr) != flatten_functype(&tp, false) {
+                    return Err(Error::Invalid);
+                }
+                let opts = self.canon_options(opts)?;
+                self.funcs.push(ComponentFunc { tp, code: ComponentCode::Lifted(funcaddr, opts) });
+            },
+            Canon::Lower(funcidx, opts) => {
+                let func = get(&self.funcs, *funcidx)?;
+                let opts = self.canon_options(opts)?;
+                let functype = flatten_functype(&func.tp, true);
+                let closure = move |store: &mut Store, args: &[Val]| {
+                    let result = lower_call(store, &opts, &func.tp, args, |store, args| component_invoke(store, &func, args));
+                    match result {
+                        Ok(vals) => HostResult::Vals(vals),
+                        Err(Error::Exit(code)) => HostResult::Exit(code),
+                        Err(_) => HostResult::Trap,
+                    }
+                };
+                let funcaddr = alloc_hostfunc(store, functype, HostCode::Closure(Arc::new(closure)))?;
+                self.core_funcs.push(funcaddr);
+            },
+            Canon::ResourceNew(typeidx) => {
+                let tp = self.resourcetype(*typeidx)?;
+                let handles = self.handles.clone();
+                let closure = move |_: &mut Store, args: &[Val]| match args {
+                    [Val::I32Const(rep)] => {
+                        let resource = Resource { tp: tp.clone(), rep: *rep };
+                        let idx = lock(&handles).insert(resource, true);
+                        HostResult::Vals(vec![Val::I32Const(idx)])
+                    },
+                    _ => HostResult::Trap,
+                };
+                let functype = (vec![ValType::I32], vec![ValType::I32]);
+                let funcaddr = alloc_hostfunc(store, functype, HostCode::Closure(Arc::new(closure)))?;
+                self.core_funcs.push(funcaddr);
+            },
+            Canon::ResourceRep(typeidx) => {
+                let tp = self.resourcetype(*typeidx)?;
+                let handles = self.handles.clone();
+                let closure = move |_: &mut Store, args: &[Val]| {
+                    let resource = match args {
+                        [Val::I32Const(idx)] => lock(&handles).get(*idx, &tp),
+                        _ => None,
+                    };
+                    match resource {
+                        Some(resource) => HostResult::Vals(vec![Val::I32Const(resource.rep)]),
+                        None => HostResult::Trap,
+                    }
+                };
+                let functype = (vec![ValType::I32], vec![ValType::I32]);
+                let funcaddr = alloc_hostfunc(store, functype, HostCode::Closure(Arc::new(closure)))?;
+                self.core_funcs.push(funcaddr);
+            },
+            Canon::ResourceDrop(typeidx) => {
+                let tp = self.resourcetype(*typeidx)?;
+                let handles = self.handles.clone();
+                let closure = move |store: &mut Store, args: &[Val]| {
+                    let removed = match args {
+                        [Val::I32Const(idx)] => lock(&handles).remove(*idx, &tp),
+                        _ => None,
+                    };
+                    match removed {
+                        Some((resource, true)) => match component_resource_drop(store, resource) {
+                            Ok(()) => HostResult::Vals(vec![]),
+                            Err(_) => HostResult::Trap,
+                        },
+                        Some((_, false)) => HostResult::Vals(vec![]),
+                        None => HostResult::Trap,
+                    }
+                };
+                let functype = (vec![ValType::I32], vec![]);
+                let funcaddr = alloc_hostfunc(store, functype, HostCode::Closure(Arc::new(closure)))?;
+                self.core_funcs.push(funcaddr);
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Call the destructor of an owned resource.
+pub fn component_resource_drop(store: &mut Store, resource: Resource) -> Result<(), Error> {
+    match resource.tp.dtor {
+        Some(ResourceDtor::Core(funcaddr)) => func_invoke(store, funcaddr, vec![Val::I32Const(resource.rep)]).1.map(|_| ()),
+        Some(ResourceDtor::Host(dtor)) => {
+            dtor(store, resource.rep);
+            Ok(())
+        },
+        Some(ResourceDtor::Closure(dtor)) => {
+            dtor(store, resource.rep);
+            Ok(())
+        },
+        None => Ok(()),
+    }
+}
+
+fn primitive(primvaltype: &PrimValType) -> InterfaceType {
+    match primvaltype {
+        PrimValType::Bool => InterfaceType::Bool,
+        PrimValType::S8 => InterfaceType::S8,
+        PrimValType::U8 => InterfaceType::U8,
+        PrimValType::S16 => InterfaceType::S16,
+        PrimValType::U16 => InterfaceType::U16,
+        PrimValType::S32 => InterfaceType::S32,
+        PrimValType::U32 => InterfaceType::U32,
+        PrimValType::S64 => InterfaceType::S64,
+        PrimValType::U64 => InterfaceType::U64,
+        PrimValType::F32 => InterfaceType::F32,
+        PrimValType::F64 => InterfaceType::F64,
+        PrimValType::Char => InterfaceType::Char,
+        PrimValType::String => InterfaceType::String,
+    }
+}
+
+#[test]
+fn test_component_instanciate() {
+    use crate::{
+        store_init, component_decode,
+        Func, Expr, Instr, MemArg, ValSize, IBinOp, Mem, MemType, Limits, Share, IdxType,
+        Global, GlobalType, Mut, Import, ImportDesc, Export, ExportDesc,
+    };
+    use crate::encoder::module2wasm;
+
+    fn leb(mut n: u32) -> Vec<u8> {
+        let mut bytes = vec![];
+        loop {
+            let byte = (n & 0x7F) as u8;
+            n >>= 7;
+            if n == 0 {
+                bytes.push(byte);
+                return bytes;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+    fn name(s: &str) -> Vec<u8> {
+        [leb(s.len() as u32), s.as_bytes().to_vec()].concat()
+    }
+    fn section(id: u8, items: Vec<Vec<u8>>) -> Vec<u8> {
+        let contents = [leb(items.len() as u32), items.concat()].concat();
+        [vec![id], leb(contents.len() as u32), contents].concat()
+    }
+    fn module(module: Module) -> Vec<u8> {
+        let bytes = module2wasm(&module);
+        [vec![1], leb(bytes.len() as u32), bytes].concat()
+    }
+    fn export(name: &str, desc: ExportDesc) -> Export {
+        Export { name: name.to_string(), desc }
+    }
+    fn store(offset: u32, local: u32) -> Vec<Instr> {
+        let memarg = MemArg { offset: 0, align: 2, memory: 0 };
+        vec![Instr::I32Const(offset), Instr::LocalGet(local), Instr::Store(ValType::I32, memarg)]
+    }
+    let memtype = MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32);
+
+    // memory with a bump allocator, aligning everything to 8
+    let memory = module(Module {
+        types: vec![(vec![ValType::I32; 4], vec![ValType::I32]).into()],
+        funcs: vec![Func { tp: 0, locals: vec![ValType::I32], body: Expr(vec![
+            Instr::GlobalGet(0),
+            Instr::LocalSet(4),
+            Instr::GlobalGet(0),
+            Instr::LocalGet(3),
+            Instr::IBinOp(ValSize::V32, IBinOp::Add),
+            Instr::I32Const(7),
+            Instr::IBinOp(ValSize::V32, IBinOp::Add),
+            Instr::I32Const(-8i32 as u32),
+            Instr::IBinOp(ValSize::V32, IBinOp::And),
+            Instr::GlobalSet(0),
+            Instr::LocalGet(4),
+        ]) }],
+        mems: vec![Mem(memtype.clone())],
+        globals: vec![Global { tp: GlobalType(ValType::I32, Mut::Var), init: Expr(vec![Instr::I32Const(1024)]) }],
+        exports: vec![export("mem", ExportDesc::Mem(0)), export("realloc", ExportDesc::Func(0))],
+        ..Module::default()
+    });
+    // len(s) -> u32, swap(p) -> p and echo(s) -> s logging s
+    let main = module(Module {
+        types: vec![(vec![ValType::I32; 2], vec![]).into(), (vec![ValType::I32; 2], vec![ValType::I32]).into()],
+        imports: vec![
+            Import { module: "env".to_string(), name: "mem".to_string(), desc: ImportDesc::Mem(memtype) },
+            Import { module: "host".to_string(), name: "log".to_string(), desc: ImportDesc::Func(0) },
+        ],
+        funcs: vec![
+            Func { tp: 1, locals: vec![], body: Expr(vec![Instr::LocalGet(1)]) },
+            Func { tp: 1, locals: vec![], body: Expr([store(16, 1), store(20, 0), vec![Instr::I32Const(16)]].concat()) },
+            Func { tp: 1, locals: vec![], body: Expr([
+                vec![Instr::LocalGet(0), Instr::LocalGet(1), Instr::Call(0)],
+                store(32, 0),
+                store(36, 1),
+                vec![Instr::I32Const(32)],
+            ].concat()) },
+        ],
+        exports: vec![export("len", ExportDesc::Func(1)), export("swap", ExportDesc::Func(2)), export("echo", ExportDesc::Func(3))],
+        ..Module::default()
+    });
+    let opts = vec![0x02, 0x03, 0x00, 0x04, 0x00];
+    let bytes = [
+        vec![0x00, 0x61, 0x73, 0x6D, 0x0D, 0x00, 0x01, 0x00],
+        section(7, vec![
+            [vec![0x40, 0x01], name("s"), vec![0x73, 0x00, 0x79]].concat(),
+            [vec![0x72, 0x02], name("x"), vec![0x7A], name("y"), vec![0x7A]].concat(),
+            [vec![0x40, 0x01], name("p"), vec![0x01, 0x00, 0x01]].concat(),
+            [vec![0x40, 0x01], name("s"), vec![0x73, 0x00, 0x73]].concat(),
+            [vec![0x40, 0x01], name("msg"), vec![0x73, 0x01, 0x00]].concat(),
+        ]),
+        section(10, vec![[vec![0x00], name("log"), vec![0x01, 0x04]].concat()]),
+        memory,
+        main,
+        section(2, vec![vec![0x00, 0x00, 0x00]]),
+        section(6, vec![
+            [vec![0x00, 0x02, 0x01, 0x00], name("mem")].concat(),
+            [vec![0x00, 0x00, 0x01, 0x00], name("realloc")].concat(),
+        ]),
+        section(8, vec![[vec![0x01, 0x00, 0x00], opts.clone()].concat()]),
+        section(2, vec![
+            [vec![0x01, 0x01], name("log"), vec![0x00, 0x01]].concat(),
+            [vec![0x00, 0x01, 0x02], name("host"), vec![0x12, 0x01], name("env"), vec![0x12, 0x00]].concat(),
+        ]),
+        section(6, vec![
+            [vec![0x00, 0x00, 0x01, 0x02], name("len")].concat(),
+            [vec![0x00, 0x00, 0x01, 0x02], name("swap")].concat(),
+            [vec![0x00, 0x00, 0x01, 0x02], name("echo")].concat(),
+        ]),
+        section(8, vec![
+            [vec![0x00, 0x00, 0x02], opts.clone(), vec![0x00]].concat(),
+            [vec![0x00, 0x00, 0x03], opts.clone(), vec![0x02]].concat(),
+            [vec![0x00, 0x00, 0x04], opts.clone(), vec![0x03]].concat(),
+        ]),
+        section(11, vec![
+            [vec![0x00], name("len"), vec![0x01, 0x01, 0x00]].concat(),
+            [vec![0x00], name("swap"), vec![0x01, 0x02, 0x00]].concat(),
+            [vec![0x00], name("echo"), vec![0x01, 0x03, 0x00]].concat(),
+        ]),
+    ].concat();
+    let component = component_decode(&mut bytes.as_slice()).unwrap();
+
+    let logged = Arc::new(Mutex::new(vec![]));
+    let log = {
+        let logged = logged.clone();
+        let tp = ComponentFuncType { params: vec![("msg".to_string(), InterfaceType::String)], results: vec![] };
+        component_func_host(tp, Arc::new(move |_, args| {
+            if let [ComponentVal::String(msg)] = args.as_slice() {
+                logged.lock().unwrap().push(msg.clone());
+            }
+            Ok(vec![])
+        }))
+    };
+    let mut store = store_init();
+    let instance = component_instanciate(&mut store, &component, vec![("log".to_string(), ComponentExternVal::Func(log))]).unwrap();
+    let func = |name: &str| match component_export(instance.clone(), name.to_string()) {
+        Ok(ComponentExternVal::Func(func)) => func,
+        _ => panic!("no export {}", name),
+    };
+
+    let results = component_invoke(&mut store, &func("len"), vec![ComponentVal::String("héllo".to_string())]).unwrap();
+    assert_eq!(results, vec![ComponentVal::U32(6)]);
+
+    let point = |x, y| ComponentVal::Record(vec![("x".to_string(), ComponentVal::S32(x)), ("y".to_string(), ComponentVal::S32(y))]);
+    let results = component_invoke(&mut store, &func("swap"), vec![point(1, -2)]).unwrap();
+    assert_eq!(results, vec![point(-2, 1)]);
+
+    let results = component_invoke(&mut store, &func("echo"), vec![ComponentVal::String("hi".to_string())]).unwrap();
+    assert_eq!(results, vec![ComponentVal::String("hi".to_string())]);
+    assert_eq!(*logged.lock().unwrap(), vec!["hi".to_string()]);
+
+    // a record is not a string
+    assert!(component_invoke(&mut store, &func("len"), vec![point(0, 0)]).is_err());
+}
+
+#[test]
+fn test_compatible() {
+    assert!(compatible("wasi:cli/stdout@0.2.0", "wasi:cli/stdout@0.2.6"));
+    assert!(compatible("a:b/c@1.0.0", "a:b/c@1.4.2"));
+    assert!(!compatible("wasi:cli/stdout@0.2.0", "wasi:cli/stdout@0.3.0"));
+    assert!(!compatible("a:b/c@0.0.1", "a:b/c@0.0.2"));
+    assert!(!compatible("wasi:cli/stdout@0.2.0", "wasi:cli/stderr@0.2.0"));
+    assert!(!compatible("a:b/c@1.0.0-rc", "a:b/c@1.0.0-rc"));
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    Name,
+    Store,
+    FuncAddr,
+};
+
+/// Value type of the component model, with its type indices resolved.
+#[derive(Clone, PartialEq, Debug)]
+pub enum InterfaceType {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    F32,
+    F64,
+    Char,
+    String,
+    List(Box<InterfaceType>),
+    Record(Vec<(Name, InterfaceType)>),
+    Tuple(Vec<InterfaceType>),
+    Variant(Vec<(Name, Option<InterfaceType>)>),
+    Enum(Vec<Name>),
+    Option(Box<InterfaceType>),
+    Result(Option<Box<InterfaceType>>, Option<Box<InterfaceType>>),
+    Flags(Vec<Name>),
+    Own(ResourceType),
+    Borrow(ResourceType),
+}
+
+impl InterfaceType {
+    /// Cases of a variant, or of the types represented as one.
+    pub(crate) fn cases(&self) -> Option<Vec<Option<InterfaceType>>> {
+        match self {
+            InterfaceType::Variant(cases) => Some(cases.iter().map(|(_, tp)| tp.clone()).collect()),
+            InterfaceType::Enum(names) => Some(names.iter().map(|_| None).collect()),
+            InterfaceType::Option(tp) => Some(vec![None, Some(*tp.clone())]),
+            InterfaceType::Result(ok, err) => Some(vec![ok.as_deref().cloned(), err.as_deref().cloned()]),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ComponentFuncType {
+    pub params: Vec<(Name, InterfaceType)>,
+    pub results: Vec<InterfaceType>,
+}
+
+/// Type definition of a component, as held by its type index space.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ComponentType {
+    Val(InterfaceType),
+    Func(ComponentFuncType),
+    Resource(ResourceType),
+    /// Instance type, with the names of the resources it exports.
+    Instance { resources: Vec<Name> },
+    Component,
+}
+
+static NEXT_RESOURCE: AtomicU64 = AtomicU64::new(0);
+
+/// Resource type, distinct from every other one, even of the same definition.
+#[derive(Clone)]
+pub struct ResourceType {
+    id: u64,
+    pub dtor: Option<ResourceDtor>,
+}
+
+type DtorClosure = Arc<dyn Fn(&mut Store, u32) + Send + Sync>;
+
+/// Destructor called with the representation of a dropped owned resource.
+#[derive(Clone)]
+pub enum ResourceDtor {
+    Core(FuncAddr),
+    Host(fn(&mut Store, u32)),
+    /// Host destructor capturing its own state, like the tables of the resources.
+    Closure(DtorClosure),
+}
+
+impl ResourceType {
+    pub fn new(dtor: Option<ResourceDtor>) -> ResourceType {
+        ResourceType { id: NEXT_RESOURCE.fetch_add(1, Ordering::Relaxed), dtor }
+    }
+}
+
+impl PartialEq for ResourceType {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl fmt::Debug for ResourceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ResourceType({})", self.id)
+    }
+}
+
+/// Resource given by its type and the `i32` representing it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Resource {
+    pub tp: ResourceType,
+    pub rep: u32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ComponentVal {
+    Bool(bool),
+    S8(i8),
+    U8(u8),
+    S16(i16),
+    U16(u16),
+    S32(i32),
+    U32(u32),
+    S64(i64),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    String(String),
+    List(Vec<ComponentVal>),
+    Record(Vec<(Name, ComponentVal)>),
+    Tuple(Vec<ComponentVal>),
+    Variant(Name, Option<Box<ComponentVal>>),
+    Enum(Name),
+    Option(Option<Box<ComponentVal>>),
+    Result(Result<Option<Box<ComponentVal>>, Option<Box<ComponentVal>>>),
+    /// Names of the flags that are set.
+    Flags(Vec<Name>),
+    Own(Resource),
+    Borrow(Resource),
+}
+
+struct Handle {
+    resource: Resource,
+    own: bool,
+}
+
+/// Resource handles of a component instance, indexed from 1 in the core code.
+#[derive(Default)]
+pub struct HandleTable {
+    handles: Vec<Option<Handle>>,
+    /// Resource types defined by the instance, whose borrows are passed by representation.
+    pub(crate) local: Vec<ResourceType>,
+}
+
+impl HandleTable {
+    pub fn insert(&mut self, resource: Resource, own: bool) -> u32 {
+        let handle = Some(Handle { resource, own });
+        match self.handles.iter().position(|handle| handle.is_none()) {
+            Some(i) => {
+                self.handles[i] = handle;
+                i as u32 + 1
+            },
+            None => {
+                self.handles.push(handle);
+                self.handles.len() as u32
+            },
+        }
+    }
+
+    fn slot(&mut self, idx: u32, tp: &ResourceType) -> Option<&mut Option<Handle>> {
+        let slot = self.handles.get_mut((idx as usize).checked_sub(1)?)?;
+        match slot {
+            Some(handle) if &handle.resource.tp == tp => Some(slot),
+            _ => None,
+        }
+    }
+
+    pub fn get(&mut self, idx: u32, tp: &ResourceType) -> Option<Resource> {
+        let slot = self.slot(idx, tp)?;
+        slot.as_ref().map(|handle| handle.resource.clone())
+    }
+
+    /// Remove a handle, with whether it owned its resource.
+    pub fn remove(&mut self, idx: u32, tp: &ResourceType) -> Option<(Resource, bool)> {
+        let slot = self.slot(idx, tp)?;
+        slot.take().map(|handle| (handle.resource, handle.own))
+    }
+}
+
+pub fn lock(handles: &Mutex<HandleTable>) -> MutexGuard<'_, HandleTable> {
+    handles.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,172 @@
+use crate::{
+    Name,
+    Error,
+};
+use super::{
+    InterfaceType,
+    ComponentVal,
+};
+
+/// Rust type standing for a component value type, as used by generated bindings.
+pub trait ComponentValue: Sized {
+    fn tp() -> InterfaceType;
+    fn into_val(self) -> ComponentVal;
+    fn from_val(val: ComponentVal) -> Result<Self, Error>;
+}
+
+/// Payload of a result case, `()` for none.
+pub trait ComponentPayload: Sized {
+    fn payload_tp() -> Option<InterfaceType>;
+    fn into_payload(self) -> Option<Box<ComponentVal>>;
+    fn from_payload(val: Option<Box<ComponentVal>>) -> Result<Self, Error>;
+}
+
+impl<T: ComponentValue> ComponentPayload for T {
+    fn payload_tp() -> Option<InterfaceType> {
+        Some(T::tp())
+    }
+    fn into_payload(self) -> Option<Box<ComponentVal>> {
+        Some(Box::new(self.into_val()))
+    }
+    fn from_payload(val: Option<Box<ComponentVal>>) -> Result<Self, Error> {
+        T::from_val(*val.ok_or(Error::Invalid)?)
+    }
+}
+
+impl ComponentPayload for () {
+    fn payload_tp() -> Option<InterfaceType> {
+        None
+    }
+    fn into_payload(self) -> Option<Box<ComponentVal>> {
+        None
+    }
+    fn from_payload(val: Option<Box<ComponentVal>>) -> Result<Self, Error> {
+        match val {
+            None => Ok(()),
+            Some(_) => Err(Error::Invalid),
+        }
+    }
+}
+
+macro_rules! primitive {
+    ($t:ty, $variant:ident) => {
+        impl ComponentValue for $t {
+            fn tp() -> InterfaceType {
+                InterfaceType::$variant
+            }
+            fn into_val(self) -> ComponentVal {
+                ComponentVal::$variant(self)
+            }
+            fn from_val(val: ComponentVal) -> Result<Self, Error> {
+                match val {
+                    ComponentVal::$variant(v) => Ok(v),
+                    _ => Err(Error::Invalid),
+                }
+            }
+        }
+    };
+}
+
+primitive!(bool, Bool);
+primitive!(i8, S8);
+primitive!(u8, U8);
+primitive!(i16, S16);
+primitive!(u16, U16);
+primitive!(i32, S32);
+primitive!(u32, U32);
+primitive!(i64, S64);
+primitive!(u64, U64);
+primitive!(f32, F32);
+primitive!(f64, F64);
+primitive!(char, Char);
+primitive!(String, String);
+
+impl<T: ComponentValue> ComponentValue for Vec<T> {
+    fn tp() -> InterfaceType {
+        InterfaceType::List(Box::new(T::tp()))
+    }
+    fn into_val(self) -> ComponentVal {
+        ComponentVal::List(self.into_iter().map(T::into_val).collect())
+    }
+    fn from_val(val: ComponentVal) -> Result<Self, Error> {
+        match val {
+            ComponentVal::List(vals) => vals.into_iter().map(T::from_val).collect(),
+            _ => Err(Error::Invalid),
+        }
+    }
+}
+
+impl<T: ComponentValue> ComponentValue for Option<T> {
+    fn tp() -> InterfaceType {
+        InterfaceType::Option(Box::new(T::tp()))
+    }
+    fn into_val(self) -> ComponentVal {
+        ComponentVal::Option(self.map(|v| Box::new(v.into_val())))
+    }
+    fn from_val(val: ComponentVal) -> Result<Self, Error> {
+        match val {
+            ComponentVal::Option(val) => val.map(|val| T::from_val(*val)).transpose(),
+            _ => Err(Error::Invalid),
+        }
+    }
+}
+
+impl<T: ComponentPayload, E: ComponentPayload> ComponentValue for Result<T, E> {
+    fn tp() -> InterfaceType {
+        InterfaceType::Result(T::payload_tp().map(Box::new), E::payload_tp().map(Box::new))
+    }
+    fn into_val(self) -> ComponentVal {
+        ComponentVal::Result(match self {
+            Ok(v) => Ok(v.into_payload()),
+            Err(e) => Err(e.into_payload()),
+        })
+    }
+    fn from_val(val: ComponentVal) -> Result<Self, Error> {
+        match val {
+            ComponentVal::Result(Ok(val)) => Ok(Ok(T::from_payload(val)?)),
+            ComponentVal::Result(Err(val)) => Ok(Err(E::from_payload(val)?)),
+            _ => Err(Error::Invalid),
+        }
+    }
+}
+
+macro_rules! tuple {
+    ($($t:ident),+) => {
+        impl<$($t: ComponentValue),+> ComponentValue for ($($t,)+) {
+            fn tp() -> InterfaceType {
+                InterfaceType::Tuple(vec![$($t::tp()),+])
+            }
+            #[allow(non_snake_case)]
+            fn into_val(self) -> ComponentVal {
+                let ($($t,)+) = self;
+                ComponentVal::Tuple(vec![$($t.into_val()),+])
+            }
+            fn from_val(val: ComponentVal) -> Result<Self, Error> {
+                match val {
+                    ComponentVal::Tuple(vals) => {
+                        let mut vals = vals.into_iter();
+                        Ok(($($t::from_val(vals.next().ok_or(Error::Invalid)?)?,)+))
+                    },
+                    _ => Err(Error::Invalid),
+                }
+            }
+        }
+    };
+}
+
+tuple!(A);
+tuple!(A, B);
+tuple!(A, B, C);
+tuple!(A, B, C, D);
+tuple!(A, B, C, D, E);
+tuple!(A, B, C, D, E, F);
+
+/// Next field of a record, or next argument of a call, converted to `T`.
+pub fn component_next<T: ComponentValue>(vals: &mut impl Iterator<Item = ComponentVal>) -> Result<T, Error> {
+    T::from_val(vals.next().ok_or(Error::Invalid)?)
+}
+
+/// Flag names set in a flags value.
+pub fn component_flags(names: &[(&str, bool)]) -> Vec<Name> {
+    names.iter().filter(|(_, set)| *set).map(|(name, _)| name.to_string()).collect()
+}
@@ -0,0 +1,493 @@
+use std::fmt;
+
+/// Error in a WIT document, at a line counted from 1.
+#[derive(Debug, PartialEq)]
+pub struct WitError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for WitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Default)]
+pub struct WitDocument {
+    /// `namespace:name` of the package, naming its interfaces when imported or exported.
+    pub package: Option<String>,
+    pub version: Option<String>,
+    pub interfaces: Vec<WitInterface>,
+    pub worlds: Vec<WitWorld>,
+}
+
+#[derive(Default)]
+pub struct WitInterface {
+    pub name: String,
+    pub uses: Vec<WitUse>,
+    pub types: Vec<WitTypeDef>,
+    pub funcs: Vec<WitFunc>,
+}
+
+#[derive(Default)]
+pub struct WitWorld {
+    pub name: String,
+    pub uses: Vec<WitUse>,
+    pub types: Vec<WitTypeDef>,
+    pub imports: Vec<WitWorldItem>,
+    pub exports: Vec<WitWorldItem>,
+}
+
+pub enum WitWorldItem {
+    Interface(String),
+    Func(WitFunc),
+}
+
+/// Types of another interface of the package, brought in by name.
+pub struct WitUse {
+    pub interface: String,
+    pub names: Vec<String>,
+}
+
+pub struct WitTypeDef {
+    pub name: String,
+    pub kind: WitTypeKind,
+}
+
+pub enum WitTypeKind {
+    Alias(WitType),
+    Record(Vec<(String, WitType)>),
+    Variant(Vec<(String, Option<WitType>)>),
+    Enum(Vec<String>),
+    Flags(Vec<String>),
+}
+
+#[derive(Clone)]
+pub struct WitFunc {
+    pub name: String,
+    pub params: Vec<(String, WitType)>,
+    pub result: Option<WitType>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum WitType {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    F32,
+    F64,
+    Char,
+    String,
+    List(Box<WitType>),
+    Option(Box<WitType>),
+    Result(Option<Box<WitType>>, Option<Box<WitType>>),
+    Tuple(Vec<WitType>),
+    Named(String),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Id(String),
+    Punct(char),
+    Arrow,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Id(id) => write!(f, "`{}`", id),
+            Token::Punct(c) => write!(f, "`{}`", c),
+            Token::Arrow => write!(f, "`->`"),
+        }
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<(Token, usize)>, WitError> {
+    let mut tokens = vec![];
+    let mut chars = src.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {},
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            },
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => {
+                            if c == '\n' { line += 1; }
+                            last = c;
+                        },
+                        None => return Err(WitError { line, message: "unterminated comment".to_string() }),
+                    }
+                }
+            },
+            '-' if chars.peek() == Some(&'>') => {
+                chars.next();
+                tokens.push((Token::Arrow, line));
+            },
+            // `%` escapes keywords used as identifiers
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '%' => {
+                let mut id = if c == '%' { String::new() } else { c.to_string() };
+                while let Some(c) = chars.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '-' || **c == '_') {
+                    id.push(*c);
+                    chars.next();
+                }
+                tokens.push((Token::Id(id), line));
+            },
+            '{' | '}' | '(' | ')' | '<' | '>' | ',' | ':' | ';' | '=' | '.' | '@' | '/' | '*' => tokens.push((Token::Punct(c), line)),
+            c => return Err(WitError { line, message: format!("unexpected character `{}`", c) }),
+        }
+    }
+    Ok(tokens)
+}
+
+struct WitParser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// Type names referenced in the current interface or world, with their line.
+    names: Vec<(String, usize)>,
+    /// Interfaces referenced by uses, imports and exports, with the type used.
+    refs: Vec<(String, Option<String>, usize)>,
+}
+
+/// Parse a WIT document of a single package, whose interfaces use no other package.
+pub fn wit_parse(src: &str) -> Result<WitDocument, WitError> {
+    let mut parser = WitParser { tokens: tokenize(src)?, pos: 0, names: vec![], refs: vec![] };
+    parser.document()
+}
+
+impl WitParser {
+    fn line(&self) -> usize {
+        match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some((_, line)) => *line,
+            None => 1,
+        }
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, WitError> {
+        Err(WitError { line: self.line(), message })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<Token, WitError> {
+        match self.tokens.get(self.pos) {
+            Some((token, _)) => {
+                self.pos += 1;
+                Ok(token.clone())
+            },
+            None => self.error("unexpected end".to_string()),
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), WitError> {
+        match self.next()? {
+            Token::Punct(p) if p == c => Ok(()),
+            token => self.error(format!("expected `{}`, found {}", c, token)),
+        }
+    }
+
+    fn id(&mut self) -> Result<String, WitError> {
+        match self.next()? {
+            Token::Id(id) => Ok(id),
+            token => self.error(format!("expected an identifier, found {}", token)),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.peek() == Some(&Token::Id(keyword.to_string())) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Comma-separated items up to `close`, allowing a trailing comma.
+    fn list<T>(&mut self, close: char, f: fn(&mut Self) -> Result<T, WitError>) -> Result<Vec<T>, WitError> {
+        let mut items = vec![];
+        while !self.eat(close) {
+            items.push(f(self)?);
+            if !self.eat(',') {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    fn document(&mut self) -> Result<WitDocument, WitError> {
+        let mut document = WitDocument::default();
+        if self.keyword("package") {
+            let namespace = self.id()?;
+            self.expect(':')?;
+            let name = self.id()?;
+            if self.eat('@') {
+                let mut version = String::new();
+                while !self.eat(';') {
+                    match self.next()? {
+                        Token::Id(id) => version.push_str(&id),
+                        Token::Punct(c) => version.push(c),
+                        Token::Arrow => return self.error("unexpected `->` in version".to_string()),
+                    }
+                }
+                document.version = Some(version);
+            } else {
+                self.expect(';')?;
+            }
+            document.package = Some(format!("{}:{}", namespace, name));
+        }
+        while self.peek().is_some() {
+            if self.keyword("interface") {
+                document.interfaces.push(self.interface()?);
+            } else if self.keyword("world") {
+                document.worlds.push(self.world()?);
+            } else {
+                let token = self.next()?;
+                return self.error(format!("expected `interface` or `world`, found {}", token));
+            }
+        }
+        for (name, tp, line) in &self.refs {
+            let message = match document.interfaces.iter().find(|interface| &interface.name == name) {
+                None => format!("unknown interface `{}`", name),
+                Some(interface) => match tp {
+                    Some(tp) if interface.types.iter().all(|typedef| &typedef.name != tp) => format!("unknown type `{}` in `{}`", tp, name),
+                    _ => continue,
+                },
+            };
+            return Err(WitError { line: *line, message });
+        }
+        Ok(document)
+    }
+
+    fn interface(&mut self) -> Result<WitInterface, WitError> {
+        let mut interface = WitInterface { name: self.id()?, ..WitInterface::default() };
+        self.expect('{')?;
+        while !self.eat('}') {
+            if self.keyword("use") {
+                interface.uses.push(self.use_()?);
+            } else if let Some(typedef) = self.typedef()? {
+                interface.types.push(typedef);
+            } else {
+                let name = self.id()?;
+                self.expect(':')?;
+                interface.funcs.push(self.func(name)?);
+            }
+        }
+        self.check_names(&interface.uses, &interface.types)?;
+        Ok(interface)
+    }
+
+    fn world(&mut self) -> Result<WitWorld, WitError> {
+        let mut world = WitWorld { name: self.id()?, ..WitWorld::default() };
+        self.expect('{')?;
+        while !self.eat('}') {
+            if self.keyword("use") {
+                world.uses.push(self.use_()?);
+            } else if self.keyword("import") {
+                let item = self.world_item()?;
+                world.imports.push(item);
+            } else if self.keyword("export") {
+                let item = self.world_item()?;
+                world.exports.push(item);
+            } else if let Some(typedef) = self.typedef()? {
+                world.types.push(typedef);
+            } else {
+                let token = self.next()?;
+                return self.error(format!("unexpected {} in world", token));
+            }
+        }
+        self.check_names(&world.uses, &world.types)?;
+        Ok(world)
+    }
+
+    /// Check the type names of an interface or a world to be defined or used in it.
+    fn check_names(&mut self, uses: &[WitUse], types: &[WitTypeDef]) -> Result<(), WitError> {
+        for (name, line) in self.names.drain(..) {
+            let used = uses.iter().any(|used| used.names.contains(&name));
+            if !used && types.iter().all(|typedef| typedef.name != name) {
+                return Err(WitError { line, message: format!("unknown type `{}`", name) });
+            }
+        }
+        Ok(())
+    }
+
+    fn world_item(&mut self) -> Result<WitWorldItem, WitError> {
+        let line = self.line();
+        let name = self.id()?;
+        if self.eat(';') {
+            self.refs.push((name.clone(), None, line));
+            return Ok(WitWorldItem::Interface(name));
+        }
+        self.expect(':')?;
+        if self.peek() == Some(&Token::Id("interface".to_string())) {
+            return self.error("inline interfaces are not supported".to_string());
+        }
+        Ok(WitWorldItem::Func(self.func(name)?))
+    }
+
+    fn use_(&mut self) -> Result<WitUse, WitError> {
+        let line = self.line();
+        let interface = self.id()?;
+        if self.peek() == Some(&Token::Punct(':')) {
+            return self.error("uses of other packages are not supported".to_string());
+        }
+        self.expect('.')?;
+        self.expect('{')?;
+        let names = self.list('}', |parser| {
+            let name = parser.id()?;
+            if parser.keyword("as") {
+                return parser.error("renaming uses is not supported".to_string());
+            }
+            Ok(name)
+        })?;
+        self.expect(';')?;
+        for name in &names {
+            self.refs.push((interface.clone(), Some(name.clone()), line));
+        }
+        Ok(WitUse { interface, names })
+    }
+
+    fn typedef(&mut self) -> Result<Option<WitTypeDef>, WitError> {
+        let kind = match self.peek() {
+            Some(Token::Id(keyword)) => keyword.clone(),
+            _ => return Ok(None),
+        };
+        // a function may be named like a keyword, followed by `:`
+        if self.tokens.get(self.pos + 1).map(|(token, _)| token) == Some(&Token::Punct(':')) {
+            return Ok(None);
+        }
+        let kind = match kind.as_str() {
+            "type" => {
+                self.pos += 1;
+                let name = self.id()?;
+                self.expect('=')?;
+                let tp = self.tp()?;
+                self.expect(';')?;
+                return Ok(Some(WitTypeDef { name, kind: WitTypeKind::Alias(tp) }));
+            },
+            "record" | "variant" | "enum" | "flags" => kind,
+            "resource" => return self.error("resources are not supported".to_string()),
+            _ => return Ok(None),
+        };
+        self.pos += 1;
+        let name = self.id()?;
+        self.expect('{')?;
+        let kind = match kind.as_str() {
+            "record" => WitTypeKind::Record(self.list('}', |parser| {
+                let name = parser.id()?;
+                parser.expect(':')?;
+                Ok((name, parser.tp()?))
+            })?),
+            "variant" => WitTypeKind::Variant(self.list('}', |parser| {
+                let name = parser.id()?;
+                if parser.eat('(') {
+                    let tp = parser.tp()?;
+                    parser.expect(')')?;
+                    Ok((name, Some(tp)))
+                } else {
+                    Ok((name, None))
+                }
+            })?),
+            "enum" => WitTypeKind::Enum(self.list('}', Self::id)?),
+            _ => WitTypeKind::Flags(self.list('}', Self::id)?),
+        };
+        Ok(Some(WitTypeDef { name, kind }))
+    }
+
+    fn func(&mut self, name: String) -> Result<WitFunc, WitError> {
+        if !self.keyword("func") {
+            return self.error(format!("expected `func` for `{}`", name));
+        }
+        self.expect('(')?;
+        let params = self.list(')', |parser| {
+            let name = parser.id()?;
+            parser.expect(':')?;
+            Ok((name, parser.tp()?))
+        })?;
+        let result = if self.peek() == Some(&Token::Arrow) {
+            self.pos += 1;
+            Some(self.tp()?)
+        } else {
+            None
+        };
+        self.expect(';')?;
+        Ok(WitFunc { name, params, result })
+    }
+
+    fn tp(&mut self) -> Result<WitType, WitError> {
+        let line = self.line();
+        let id = self.id()?;
+        let tp = match id.as_str() {
+            "bool" => WitType::Bool,
+            "s8" => WitType::S8,
+            "u8" => WitType::U8,
+            "s16" => WitType::S16,
+            "u16" => WitType::U16,
+            "s32" => WitType::S32,
+            "u32" => WitType::U32,
+            "s64" => WitType::S64,
+            "u64" => WitType::U64,
+            "f32" | "float32" => WitType::F32,
+            "f64" | "float64" => WitType::F64,
+            "char" => WitType::Char,
+            "string" => WitType::String,
+            "list" | "option" => {
+                self.expect('<')?;
+                let tp = Box::new(self.tp()?);
+                self.expect('>')?;
+                if id == "list" { WitType::List(tp) } else { WitType::Option(tp) }
+            },
+            "tuple" => {
+                self.expect('<')?;
+                let tps = self.list('>', Self::tp)?;
+                // as many as the tuples implementing `ComponentValue`
+                if tps.is_empty() || tps.len() > 6 {
+                    return Err(WitError { line, message: "tuples of 1 to 6 types are supported".to_string() });
+                }
+                WitType::Tuple(tps)
+            },
+            "result" => {
+                if !self.eat('<') {
+                    return Ok(WitType::Result(None, None));
+                }
+                let ok = if self.keyword("_") { None } else { Some(Box::new(self.tp()?)) };
+                let err = if self.eat(',') { Some(Box::new(self.tp()?)) } else { None };
+                self.expect('>')?;
+                WitType::Result(ok, err)
+            },
+            "own" | "borrow" => return self.error("resources are not supported".to_string()),
+            _ => {
+                self.names.push((id.clone(), line));
+                WitType::Named(id)
+            },
+        };
+        Ok(tp)
+    }
+}
@@ -1,5 +1,4 @@
 mod idx;
-mod elemtype;
 mod globaltype;
 mod instr;
 
@@ -16,6 +15,7 @@ mod importsec;
 mod funcsec;
 mod tablesec;
 mod memsec;
+mod tagsec;
 mod globalsec;
 mod exportsec;
 mod startsec;
@@ -27,6 +27,7 @@ mod valtype;
 mod functype;
 mod resulttype;
 mod util;
+mod component;
 use crate::{
     Module,
     Byte,
@@ -53,15 +54,17 @@ use importsec::{decode_importsec};
 use funcsec::{decode_funcsec};
 use tablesec::{decode_tablesec};
 use memsec::{decode_memsec};
+use tagsec::{decode_tagsec};
 use globalsec::{decode_globalsec};
 use exportsec::{decode_exportsec};
 use startsec::{decode_startsec};
 use elemsec::{decode_elemsec};
 use codesec::{decode_codesec};
-use datasec::{decode_datasec};
+use datasec::{decode_datasec, decode_datacountsec};
 
 use valtype::{
     decode_valtype,
+    decode_heaptype,
     byte_to_valtype,
 };
 use functype::decode_functype;
@@ -69,6 +72,8 @@ use functype::decode_functype;
 
 use util::*;
 
+pub use component::decode_component;
+
 enum Section {
     Custom,
     Type,
@@ -76,12 +81,14 @@ enum Section {
     Function,
     Table,
     Memory,
+    Tag,
     Global,
     Export,
     Start,
     Element,
     Code,
     Data,
+    DataCount,
 }
 
 pub fn decode_module(reader: &mut impl Read) -> io::Result<Module> {
@@ -93,20 +100,22 @@ pub fn decode_module(reader: &mut impl Read) -> io::Result<Module> {
         match id_to_section(section_id) {
             Section::Custom => decode_customsec(reader),
             Section::Type => { module.types = decode_typesec(reader); },
-            Section::Import => { module.imports = decode_importsec(reader) },
+            Section::Import => { module.imports = decode_importsec(reader)? },
             Section::Function => { module.funcs = decode_funcsec(reader) },
-            Section::Table => { module.tables = decode_tablesec(reader) },
+            Section::Table => { module.tables = decode_tablesec(reader)? },
             Section::Memory => { module.mems = decode_memsec(reader) },
+            Section::Tag => { module.tags = decode_tagsec(reader) },
             Section::Global => { module.globals = decode_globalsec(reader) },
             Section::Export => { module.exports = decode_exportsec(reader) },
             Section::Start => { module.start = Some(decode_startsec(reader)) },
-            Section::Element => { module.elem = decode_elemsec(reader) },
+            Section::Element => { module.elem = decode_elemsec(reader)? },
             Section::Code => {
                 for (i, code) in decode_codesec(reader).iter().enumerate() {
                     module.funcs[i].set_code(code);
                 }
             },
             Section::Data => { module.data = decode_datasec(reader) },
+            Section::DataCount => { module.datacount = Some(decode_datacountsec(reader)) },
         }
     }
 
@@ -149,6 +158,8 @@ fn id_to_section(id: Byte) -> Section {
         9 => Section::Element,
         10 => Section::Code,
         11 => Section::Data,
+        12 => Section::DataCount,
+        13 => Section::Tag,
         _ => panic!("invalid on id_to_section")
     }
 }
\ No newline at end of file
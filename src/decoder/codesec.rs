@@ -39,15 +39,13 @@ pub(super) fn decode_codesec(reader: &mut impl Read) -> Vec<Code> {
 }
 
 fn decode_code(reader: &mut impl Read) -> Code {
-    let length = decode_u32_from_leb128(reader);
-    let mut handle = reader.take(length as u64);
+    let size = decode_u32_from_leb128(reader);
+    let mut handle = reader.take(size as u64);
 
-    let length = decode_u32_from_leb128(&mut handle);
-    let mut handle = reader.take(length as u64);
     let locals_vec = decode_vec(&mut handle, decode_locals);
-    let expr = decode_expr(reader);
+    let expr = decode_expr(&mut handle);
 
-    Code { size: length, locals: locals_vec, body: expr }
+    Code { size, locals: locals_vec, body: expr }
 }
 
 fn decode_locals(reader: &mut impl Read) -> Locals {
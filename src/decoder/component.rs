@@ -0,0 +1,427 @@
+use std::io::{
+    self,
+    Read,
+};
+
+use crate::{
+    Byte,
+    Component,
+    ComponentDef,
+    CoreSort,
+    Sort,
+    CoreInstanceExpr,
+    InstanceExpr,
+    Alias,
+    AliasTarget,
+    PrimValType,
+    ValTypeRef,
+    DefValType,
+    FuncTypeDef,
+    DefType,
+    ComponentDecl,
+    InstanceDecl,
+    ExternDesc,
+    TypeBound,
+    Canon,
+    CanonOpt,
+    StringEncoding,
+    ComponentImport,
+    ComponentExport,
+};
+
+use super::{
+    decode_module,
+    decode_functype,
+    decode_vec,
+    decode_u32_from_leb128,
+    decode_i64_from_leb128,
+};
+use super::name::{decode_name, Name};
+
+pub fn decode_component(reader: &mut impl Read) -> io::Result<Component> {
+    let mut component = Component::default();
+
+    decode_preamble(reader)?;
+    while let Some(Ok(section_id)) = reader.bytes().next() {
+        let length = decode_u32_from_leb128(reader);
+        let mut contents = vec![];
+        reader.take(length as u64).read_to_end(&mut contents)?;
+        if contents.len() != length as usize {
+            panic!("invalid on decode_component");
+        }
+        let reader = &mut contents.as_slice();
+        match section_id {
+            0 => {},
+            1 => component.defs.push(ComponentDef::CoreModule(Box::new(decode_module(reader)?))),
+            2 => component.defs.extend(decode_vec(reader, decode_core_instance).into_iter().map(ComponentDef::CoreInstance)),
+            3 => component.defs.extend(decode_vec(reader, decode_core_type).into_iter().map(ComponentDef::CoreType)),
+            4 => component.defs.push(ComponentDef::Component(decode_component(reader)?)),
+            5 => component.defs.extend(decode_vec(reader, decode_instance).into_iter().map(ComponentDef::Instance)),
+            6 => component.defs.extend(decode_vec(reader, decode_alias).into_iter().map(ComponentDef::Alias)),
+            7 => component.defs.extend(decode_vec(reader, decode_deftype).into_iter().map(ComponentDef::Type)),
+            8 => component.defs.extend(decode_vec(reader, decode_canon).into_iter().map(ComponentDef::Canon)),
+            10 => component.defs.extend(decode_vec(reader, decode_import).into_iter().map(ComponentDef::Import)),
+            11 => component.defs.extend(decode_vec(reader, decode_export).into_iter().map(ComponentDef::Export)),
+            // start and value sections need component values
+            _ => panic!("invalid on decode_component"),
+        }
+    }
+
+    Ok(component)
+}
+
+fn decode_preamble(reader: &mut impl Read) -> io::Result<()> {
+    // magic, version 0x0d and layer 1
+    let preamble: [u8; 8] = [0x00, 0x61, 0x73, 0x6D, 0x0D, 0x00, 0x01, 0x00,];
+    let mut buf: [u8; 8] = [0x00; 8];
+    reader.read_exact(&mut buf)?;
+    if buf == preamble {
+        Ok(())
+    } else {
+        panic!("invalid on decode_preamble");
+    }
+}
+
+fn decode_byte(reader: &mut impl Read) -> Byte {
+    if let Some(Ok(byte)) = reader.bytes().next() {
+        byte
+    } else {
+        panic!("invalid on decode_component");
+    }
+}
+
+fn decode_option<T: Read, R>(reader: &mut T, f: fn(reader: &mut T) -> R) -> Option<R> {
+    match decode_byte(reader) {
+        0x00 => None,
+        0x01 => Some(f(reader)),
+        _ => panic!("invalid on decode_option"),
+    }
+}
+
+/// Import and export names, whose optional version suffix is dropped.
+fn decode_extern_name(reader: &mut impl Read) -> Name {
+    match decode_byte(reader) {
+        0x00 => decode_name(reader),
+        0x01 => {
+            let name = decode_name(reader);
+            let _version = decode_name(reader);
+            name
+        },
+        _ => panic!("invalid on decode_extern_name"),
+    }
+}
+
+fn decode_core_sort(reader: &mut impl Read) -> CoreSort {
+    match decode_byte(reader) {
+        0x00 => CoreSort::Func,
+        0x01 => CoreSort::Table,
+        0x02 => CoreSort::Memory,
+        0x03 => CoreSort::Global,
+        0x04 => CoreSort::Tag,
+        0x10 => CoreSort::Type,
+        0x11 => CoreSort::Module,
+        0x12 => CoreSort::Instance,
+        _ => panic!("invalid on decode_core_sort"),
+    }
+}
+
+fn decode_sort(reader: &mut impl Read) -> Sort {
+    match decode_byte(reader) {
+        0x00 => Sort::Core(decode_core_sort(reader)),
+        0x01 => Sort::Func,
+        0x02 => Sort::Value,
+        0x03 => Sort::Type,
+        0x04 => Sort::Component,
+        0x05 => Sort::Instance,
+        _ => panic!("invalid on decode_sort"),
+    }
+}
+
+fn decode_core_instance(reader: &mut impl Read) -> CoreInstanceExpr {
+    match decode_byte(reader) {
+        0x00 => {
+            let moduleidx = decode_u32_from_leb128(reader);
+            let args = decode_vec(reader, decode_core_instantiate_arg);
+            CoreInstanceExpr::Instantiate(moduleidx, args)
+        },
+        0x01 => CoreInstanceExpr::Exports(decode_vec(reader, decode_core_inline_export)),
+        _ => panic!("invalid on decode_core_instance"),
+    }
+}
+
+fn decode_core_instantiate_arg(reader: &mut impl Read) -> (Name, u32) {
+    let name = decode_name(reader);
+    // only instances are given to core modules
+    if decode_byte(reader) != 0x12 {
+        panic!("invalid on decode_core_instantiate_arg");
+    }
+    (name, decode_u32_from_leb128(reader))
+}
+
+fn decode_core_inline_export(reader: &mut impl Read) -> (Name, CoreSort, u32) {
+    let name = decode_name(reader);
+    let sort = decode_core_sort(reader);
+    (name, sort, decode_u32_from_leb128(reader))
+}
+
+fn decode_core_type(reader: &mut impl Read) -> crate::FuncType {
+    // core module types are not supported
+    if decode_byte(reader) != 0x60 {
+        panic!("invalid on decode_core_type");
+    }
+    decode_functype(reader)
+}
+
+fn decode_instance(reader: &mut impl Read) -> InstanceExpr {
+    match decode_byte(reader) {
+        0x00 => {
+            let componentidx = decode_u32_from_leb128(reader);
+            let args = decode_vec(reader, decode_instantiate_arg);
+            InstanceExpr::Instantiate(componentidx, args)
+        },
+        0x01 => InstanceExpr::Exports(decode_vec(reader, decode_inline_export)),
+        _ => panic!("invalid on decode_instance"),
+    }
+}
+
+fn decode_instantiate_arg(reader: &mut impl Read) -> (Name, Sort, u32) {
+    let name = decode_name(reader);
+    let sort = decode_sort(reader);
+    (name, sort, decode_u32_from_leb128(reader))
+}
+
+fn decode_inline_export(reader: &mut impl Read) -> (Name, Sort, u32) {
+    let name = decode_extern_name(reader);
+    let sort = decode_sort(reader);
+    (name, sort, decode_u32_from_leb128(reader))
+}
+
+fn decode_alias(reader: &mut impl Read) -> Alias {
+    let sort = decode_sort(reader);
+    let target = match decode_byte(reader) {
+        0x00 => {
+            let instanceidx = decode_u32_from_leb128(reader);
+            AliasTarget::Export(instanceidx, decode_name(reader))
+        },
+        0x01 => {
+            let instanceidx = decode_u32_from_leb128(reader);
+            AliasTarget::CoreExport(instanceidx, decode_name(reader))
+        },
+        0x02 => {
+            let count = decode_u32_from_leb128(reader);
+            AliasTarget::Outer(count, decode_u32_from_leb128(reader))
+        },
+        _ => panic!("invalid on decode_alias"),
+    };
+    Alias { sort, target }
+}
+
+fn byte_to_primvaltype(byte: Byte) -> Option<PrimValType> {
+    match byte {
+        0x7F => Some(PrimValType::Bool),
+        0x7E => Some(PrimValType::S8),
+        0x7D => Some(PrimValType::U8),
+        0x7C => Some(PrimValType::S16),
+        0x7B => Some(PrimValType::U16),
+        0x7A => Some(PrimValType::S32),
+        0x79 => Some(PrimValType::U32),
+        0x78 => Some(PrimValType::S64),
+        0x77 => Some(PrimValType::U64),
+        0x76 => Some(PrimValType::F32),
+        0x75 => Some(PrimValType::F64),
+        0x74 => Some(PrimValType::Char),
+        0x73 => Some(PrimValType::String),
+        _ => None,
+    }
+}
+
+fn decode_valtype(reader: &mut impl Read) -> ValTypeRef {
+    // s33: negative for primitive types, type index otherwise
+    let n = decode_i64_from_leb128(reader);
+    if n >= 0 {
+        ValTypeRef::Type(n as u32)
+    } else if let Some(primvaltype) = byte_to_primvaltype((n & 0x7F) as Byte) {
+        ValTypeRef::Prim(primvaltype)
+    } else {
+        panic!("invalid on decode_valtype");
+    }
+}
+
+fn decode_option_valtype(reader: &mut impl Read) -> Option<ValTypeRef> {
+    decode_option(reader, decode_valtype)
+}
+
+fn decode_labelvaltype(reader: &mut impl Read) -> (Name, ValTypeRef) {
+    let name = decode_name(reader);
+    (name, decode_valtype(reader))
+}
+
+fn decode_case(reader: &mut impl Read) -> (Name, Option<ValTypeRef>) {
+    let name = decode_name(reader);
+    let tp = decode_option_valtype(reader);
+    // refinements were removed from the format
+    if decode_byte(reader) != 0x00 {
+        panic!("invalid on decode_case");
+    }
+    (name, tp)
+}
+
+fn decode_defvaltype(byte: Byte, reader: &mut impl Read) -> DefValType {
+    if let Some(primvaltype) = byte_to_primvaltype(byte) {
+        return DefValType::Prim(primvaltype);
+    }
+    match byte {
+        0x72 => DefValType::Record(decode_vec(reader, decode_labelvaltype)),
+        0x71 => DefValType::Variant(decode_vec(reader, decode_case)),
+        0x70 => DefValType::List(decode_valtype(reader)),
+        0x6F => DefValType::Tuple(decode_vec(reader, decode_valtype)),
+        0x6E => DefValType::Flags(decode_vec(reader, decode_name)),
+        0x6D => DefValType::Enum(decode_vec(reader, decode_name)),
+        0x6B => DefValType::Option(decode_valtype(reader)),
+        0x6A => {
+            let ok = decode_option_valtype(reader);
+            DefValType::Result(ok, decode_option_valtype(reader))
+        },
+        0x69 => DefValType::Own(decode_u32_from_leb128(reader)),
+        0x68 => DefValType::Borrow(decode_u32_from_leb128(reader)),
+        _ => panic!("invalid on decode_defvaltype"),
+    }
+}
+
+fn decode_functype_def(reader: &mut impl Read) -> FuncTypeDef {
+    let params = decode_vec(reader, decode_labelvaltype);
+    let results = match decode_byte(reader) {
+        0x00 => vec![decode_valtype(reader)],
+        // named results, none in the current format
+        0x01 => decode_vec(reader, decode_labelvaltype).into_iter().map(|(_, tp)| tp).collect(),
+        _ => panic!("invalid on decode_functype_def"),
+    };
+    FuncTypeDef { params, results }
+}
+
+fn decode_deftype(reader: &mut impl Read) -> DefType {
+    match decode_byte(reader) {
+        0x40 => DefType::Func(decode_functype_def(reader)),
+        0x41 => DefType::Component(decode_vec(reader, decode_component_decl)),
+        0x42 => DefType::Instance(decode_vec(reader, decode_instance_decl)),
+        0x3F => {
+            // representation, always i32
+            if decode_byte(reader) != 0x7F {
+                panic!("invalid on decode_deftype");
+            }
+            DefType::Resource(decode_option(reader, decode_u32_from_leb128))
+        },
+        byte => DefType::Val(decode_defvaltype(byte, reader)),
+    }
+}
+
+fn decode_component_decl(reader: &mut impl Read) -> ComponentDecl {
+    match decode_byte(reader) {
+        0x03 => {
+            let name = decode_extern_name(reader);
+            ComponentDecl::Import(name, decode_externdesc(reader))
+        },
+        byte => ComponentDecl::Instance(decode_instance_decl_with(byte, reader)),
+    }
+}
+
+fn decode_instance_decl(reader: &mut impl Read) -> InstanceDecl {
+    let byte = decode_byte(reader);
+    decode_instance_decl_with(byte, reader)
+}
+
+fn decode_instance_decl_with(byte: Byte, reader: &mut impl Read) -> InstanceDecl {
+    match byte {
+        0x00 => InstanceDecl::CoreType(decode_core_type(reader)),
+        0x01 => InstanceDecl::Type(decode_deftype(reader)),
+        0x02 => InstanceDecl::Alias(decode_alias(reader)),
+        0x04 => {
+            let name = decode_extern_name(reader);
+            InstanceDecl::Export(name, decode_externdesc(reader))
+        },
+        _ => panic!("invalid on decode_instance_decl"),
+    }
+}
+
+fn decode_externdesc(reader: &mut impl Read) -> ExternDesc {
+    match decode_byte(reader) {
+        0x00 => {
+            if decode_byte(reader) != 0x11 {
+                panic!("invalid on decode_externdesc");
+            }
+            ExternDesc::CoreModule(decode_u32_from_leb128(reader))
+        },
+        0x01 => ExternDesc::Func(decode_u32_from_leb128(reader)),
+        0x02 => match decode_byte(reader) {
+            0x01 => ExternDesc::Value(decode_valtype(reader)),
+            _ => panic!("invalid on decode_externdesc"),
+        },
+        0x03 => match decode_byte(reader) {
+            0x00 => ExternDesc::Type(TypeBound::Eq(decode_u32_from_leb128(reader))),
+            0x01 => ExternDesc::Type(TypeBound::SubResource),
+            _ => panic!("invalid on decode_externdesc"),
+        },
+        0x04 => ExternDesc::Component(decode_u32_from_leb128(reader)),
+        0x05 => ExternDesc::Instance(decode_u32_from_leb128(reader)),
+        _ => panic!("invalid on decode_externdesc"),
+    }
+}
+
+fn decode_canon(reader: &mut impl Read) -> Canon {
+    match decode_byte(reader) {
+        0x00 => {
+            if decode_byte(reader) != 0x00 {
+                panic!("invalid on decode_canon");
+            }
+            let funcidx = decode_u32_from_leb128(reader);
+            let opts = decode_vec(reader, decode_canonopt);
+            Canon::Lift(funcidx, opts, decode_u32_from_leb128(reader))
+        },
+        0x01 => {
+            if decode_byte(reader) != 0x00 {
+                panic!("invalid on decode_canon");
+            }
+            let funcidx = decode_u32_from_leb128(reader);
+            Canon::Lower(funcidx, decode_vec(reader, decode_canonopt))
+        },
+        0x02 => Canon::ResourceNew(decode_u32_from_leb128(reader)),
+        0x03 => Canon::ResourceDrop(decode_u32_from_leb128(reader)),
+        0x04 => Canon::ResourceRep(decode_u32_from_leb128(reader)),
+        _ => panic!("invalid on decode_canon"),
+    }
+}
+
+fn decode_canonopt(reader: &mut impl Read) -> CanonOpt {
+    match decode_byte(reader) {
+        0x00 => CanonOpt::StringEncoding(StringEncoding::Utf8),
+        0x01 => CanonOpt::StringEncoding(StringEncoding::Utf16),
+        0x02 => CanonOpt::StringEncoding(StringEncoding::Latin1Utf16),
+        0x03 => CanonOpt::Memory(decode_u32_from_leb128(reader)),
+        0x04 => CanonOpt::Realloc(decode_u32_from_leb128(reader)),
+        0x05 => CanonOpt::PostReturn(decode_u32_from_leb128(reader)),
+        _ => panic!("invalid on decode_canonopt"),
+    }
+}
+
+fn decode_import(reader: &mut impl Read) -> ComponentImport {
+    let name = decode_extern_name(reader);
+    ComponentImport { name, desc: decode_externdesc(reader) }
+}
+
+fn decode_export(reader: &mut impl Read) -> ComponentExport {
+    let name = decode_extern_name(reader);
+    let sort = decode_sort(reader);
+    let idx = decode_u32_from_leb128(reader);
+    let desc = decode_option(reader, decode_externdesc);
+    ComponentExport { name, sort, idx, desc }
+}
+
+#[test]
+fn test_decode_valtype() {
+    let data: [u8; 1] = [0x73];
+    assert_eq!(decode_valtype(&mut data.as_ref()), ValTypeRef::Prim(PrimValType::String));
+    let data: [u8; 1] = [0x7F];
+    assert_eq!(decode_valtype(&mut data.as_ref()), ValTypeRef::Prim(PrimValType::Bool));
+    let data: [u8; 2] = [0x80, 0x01];
+    assert_eq!(decode_valtype(&mut data.as_ref()), ValTypeRef::Type(128));
+}
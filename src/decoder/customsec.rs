@@ -1,9 +1,8 @@
-use std::io::Read;
+use std::io::{self, Read};
 use super::decode_u32_from_leb128;
 
 pub(super) fn decode_customsec(reader: &mut impl Read) {
     // prefixはsection number 0
     let length = decode_u32_from_leb128(reader);
-    let mut bytes = reader.bytes();
-    let _ = (0..length).map(|_| bytes.next());
-}
\ No newline at end of file
+    let _ = io::copy(&mut reader.take(length as u64), &mut io::sink());
+}
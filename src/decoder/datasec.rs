@@ -2,6 +2,8 @@ use std::io::Read;
 
 use crate::{
     Data,
+    DataMode,
+    Byte,
 };
 use super::{
     decode_u32_from_leb128, 
@@ -18,16 +20,33 @@ pub(super) fn decode_datasec(reader: &mut impl Read) -> Vec<Data> {
     decode_vec(&mut handle, decode_data)
 }
 
+pub(super) fn decode_datacountsec(reader: &mut impl Read) -> u32 {
+    // prefixはsection number 12
+    let _length = decode_u32_from_leb128(reader);
+    decode_u32_from_leb128(reader)
+}
+
 fn decode_data(reader: &mut impl Read) -> Data {
-    let memidx = decode_memidx(reader);
-    let expr = decode_expr(reader);
-    let length = decode_u32_from_leb128(reader);
-    let mut handle = reader.take(length as u64);
-    let init = decode_vec(&mut handle, |reader| reader.bytes().next().unwrap().unwrap());
+    let mode = match decode_u32_from_leb128(reader) {
+        0 => DataMode::Active { memory: 0, offset: decode_expr(reader) },
+        1 => DataMode::Passive,
+        2 => {
+            let memidx = decode_memidx(reader);
+            DataMode::Active { memory: memidx, offset: decode_expr(reader) }
+        },
+        _ => panic!("invalid on decode_data"),
+    };
+    let init = decode_bytes(reader);
 
     Data {
-        data: memidx,
-        offset: expr,
-        init: init,
+        init,
+        mode,
     }
-}
\ No newline at end of file
+}
+
+fn decode_bytes(reader: &mut impl Read) -> Vec<Byte> {
+    let length = decode_u32_from_leb128(reader);
+    let mut bytes = vec![];
+    reader.by_ref().take(length as u64).read_to_end(&mut bytes).unwrap();
+    bytes
+}
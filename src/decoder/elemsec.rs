@@ -1,26 +1,74 @@
-use std::io::Read;
+use std::io::{self, Read};
 
-use super::{decode_u32_from_leb128, decode_vec};
+use super::{decode_u32_from_leb128, decode_vec, try_decode_vec};
 use crate::{
     Elem,
+    ElemMode,
+    RefType,
+    HeapType,
+    Expr,
+    Instr,
+    FuncIdx,
 };
 use super::idx::{decode_tableidx, decode_funcindices};
 use super::expr::{decode_expr};
+use super::valtype::decode_reftype;
 
-pub(super) fn decode_elemsec(reader: &mut impl Read) -> Vec<Elem> {
+pub(super) fn decode_elemsec(reader: &mut impl Read) -> io::Result<Vec<Elem>> {
     // prefixはsection number 9
     let length = decode_u32_from_leb128(reader);
     let mut handle = reader.take(length as u64);
-    decode_vec(&mut handle, decode_elem)
+    try_decode_vec(&mut handle, decode_elem)
 }
 
-fn decode_elem(reader: &mut impl Read) -> Elem {
-    let tableidx = decode_tableidx(reader);
-    let expr = decode_expr(reader);
-    let init = decode_funcindices(reader);
-    Elem {
-        table: tableidx,
-        offset: expr,
-        init: init,
+// bit 0: passive or declarative, bit 1: explicit table index or declarative, bit 2: expressions
+fn decode_elem(reader: &mut impl Read) -> io::Result<Elem> {
+    let flags = decode_u32_from_leb128(reader);
+    if flags > 7 {
+        panic!("invalid on decode_elem");
     }
-}
\ No newline at end of file
+
+    let mode = match flags & 0b011 {
+        0 => ElemMode::Active { table: 0, offset: decode_expr(reader) },
+        1 => ElemMode::Passive,
+        2 => {
+            let tableidx = decode_tableidx(reader);
+            ElemMode::Active { table: tableidx, offset: decode_expr(reader) }
+        },
+        _ => ElemMode::Declarative,
+    };
+
+    let uses_exprs = flags & 0b100 != 0;
+    let tp = match (flags & 0b011, uses_exprs) {
+        (0, _) => FUNCREF,
+        (_, false) => decode_elemkind(reader),
+        (_, true) => decode_reftype(reader)?,
+    };
+
+    let init = if uses_exprs {
+        decode_vec(reader, decode_expr)
+    } else {
+        decode_funcindices(reader).into_iter().map(ref_func).collect()
+    };
+
+    Ok(Elem {
+        tp,
+        init,
+        mode,
+    })
+}
+
+fn ref_func(funcidx: FuncIdx) -> Expr {
+    Expr(vec![Instr::RefFunc(funcidx)])
+}
+
+const FUNCREF: RefType = RefType { nullable: true, heap: HeapType::Func };
+
+fn decode_elemkind(reader: &mut impl Read) -> RefType {
+    // 0x00 is funcref, the only elemkind
+    if let Some(Ok(0x00)) = reader.bytes().next() {
+        FUNCREF
+    } else {
+        panic!("invalid on decode_elemkind");
+    }
+}
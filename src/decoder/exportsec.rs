@@ -7,7 +7,7 @@ use crate::{
 use super::{
     decode_u32_from_leb128, decode_vec,
 };
-use super::idx::{decode_funcidx, decode_tableidx, decode_memidx, decode_globalidx};
+use super::idx::{decode_funcidx, decode_tableidx, decode_memidx, decode_globalidx, decode_tagidx};
 use super::name::{decode_name};
 
 
@@ -34,6 +34,7 @@ fn decode_exportdesc(reader: &mut impl Read) -> ExportDesc {
             0x01 => { return decode_exportdesc_tabletype(reader) },
             0x02 => { return decode_exportdesc_memtype(reader) },
             0x03 => { return decode_exportdesc_globaltype(reader) },
+            0x04 => { return decode_exportdesc_tagtype(reader) },
             _ => panic!("invalid on decode_exportdesc"),
         }
     }
@@ -57,3 +58,6 @@ fn decode_exportdesc_globaltype(reader: &mut impl Read) -> ExportDesc {
     ExportDesc::Global(decode_globalidx(reader))
 }
 
+fn decode_exportdesc_tagtype(reader: &mut impl Read) -> ExportDesc {
+    ExportDesc::Tag(decode_tagidx(reader))
+}
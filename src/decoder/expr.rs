@@ -12,7 +12,7 @@ pub(super) fn decode_expr(reader: &mut impl Read) -> Expr {
 }
 
 pub(super) fn decode_instrs(reader: &mut impl Read) -> Vec<Instr> {
-    decode_instrs_internal(reader, 0x05)
+    decode_instrs_internal(reader, 0x0B)
 }
 // pub(super) fn decode_instrs_else(reader: &mut impl Read) -> Vec<Instr> {
 //     decode_instrs_internal(reader, 0x05)
@@ -13,7 +13,7 @@ pub(super) fn decode_globaltype(reader: &mut impl Read) -> GlobalType {
     GlobalType(valtype, mutablilty)
 }
 
-fn decode_mut(reader: &mut impl Read) -> Mut {
+pub(super) fn decode_mut(reader: &mut impl Read) -> Mut {
     if let Some(Ok(b)) = reader.bytes().next() {
         match b {
             0x00 => Mut::Const,
@@ -4,10 +4,13 @@ use crate::{
     TypeIdx,
     FuncIdx,
     TableIdx,
+    MemIdx,
     GlobalIdx,
     LabelIdx,
     LocalIdx,
-
+    ElemIdx,
+    DataIdx,
+    TagIdx,
 };
 use super::{
     decode_u32_from_leb128, 
@@ -26,7 +29,19 @@ pub(super) fn decode_tableidx(reader: &mut impl Read) -> TableIdx {
     decode_u32_from_leb128(reader)
 }
 
-pub(super) fn decode_memidx(reader: &mut impl Read) -> TypeIdx {
+pub(super) fn decode_memidx(reader: &mut impl Read) -> MemIdx {
+    decode_u32_from_leb128(reader)
+}
+
+pub(super) fn decode_dataidx(reader: &mut impl Read) -> DataIdx {
+    decode_u32_from_leb128(reader)
+}
+
+pub(super) fn decode_elemidx(reader: &mut impl Read) -> ElemIdx {
+    decode_u32_from_leb128(reader)
+}
+
+pub(super) fn decode_tagidx(reader: &mut impl Read) -> TagIdx {
     decode_u32_from_leb128(reader)
 }
 
@@ -43,13 +58,9 @@ pub(super) fn decode_localidx(reader: &mut impl Read) -> LocalIdx {
 }
 
 pub(super) fn decode_funcindices(reader: &mut impl Read) -> Vec<FuncIdx> {
-    let length = decode_u32_from_leb128(reader);
-    let mut handle = reader.take(length as u64);
-    decode_vec(&mut handle, decode_funcidx)
+    decode_vec(reader, decode_funcidx)
 }
 
 pub(super) fn decode_labelindices(reader: &mut impl Read) -> Vec<LabelIdx> {
-    let length = decode_u32_from_leb128(reader);
-    let mut handle = reader.take(length as u64);
-    decode_vec(&mut handle, decode_labelidx)
+    decode_vec(reader, decode_labelidx)
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use std::io::Read;
+use std::io::{self, Read};
 
 use crate::{
     Import,
@@ -8,40 +8,42 @@ use crate::{
 
 use super::{
     decode_u32_from_leb128, 
-    decode_vec
+    try_decode_vec
 };
 use super::memtype::{decode_memtype};
 use super::globaltype::{decode_globaltype};
 use super::tabletype::decode_tabletype;
 use super::idx::decode_typeidx;
+use super::tagsec::decode_tagtype;
 use super::name::{decode_name};
 
 
-pub(super) fn decode_importsec(reader: &mut impl Read) -> Vec<Import> {
+pub(super) fn decode_importsec(reader: &mut impl Read) -> io::Result<Vec<Import>> {
     // prefixはsection number 2
     let length = decode_u32_from_leb128(reader);
     let mut handle = reader.take(length as u64);
-    decode_vec(&mut handle, decode_import)
+    try_decode_vec(&mut handle, decode_import)
 }
 
-fn decode_import(reader: &mut impl Read) -> Import {
+fn decode_import(reader: &mut impl Read) -> io::Result<Import> {
     let module_identifier = decode_name(reader);
     let name_identifier = decode_name(reader);
-    let importdesc = decode_importdesc(reader);
-    Import {
+    let importdesc = decode_importdesc(reader)?;
+    Ok(Import {
         module: module_identifier,
         name: name_identifier,
         desc: importdesc,
-    }
+    })
 }
 
-fn decode_importdesc(reader: &mut impl Read) -> ImportDesc {
+fn decode_importdesc(reader: &mut impl Read) -> io::Result<ImportDesc> {
     if let Some(Ok(byte)) = reader.bytes().next() {
         match byte {
-            0x00 => { return decode_importdesc_func(reader) },
+            0x00 => { return Ok(decode_importdesc_func(reader)) },
             0x01 => { return decode_importdesc_tabletype(reader) },
-            0x02 => { return decode_importdesc_memtype(reader) },
-            0x03 => { return decode_importdesc_globaltype(reader) },
+            0x02 => { return Ok(decode_importdesc_memtype(reader)) },
+            0x03 => { return Ok(decode_importdesc_globaltype(reader)) },
+            0x04 => { return Ok(decode_importdesc_tagtype(reader)) },
             _ => panic!("invalid on decode_importdesc"),
         }
     }
@@ -53,8 +55,8 @@ fn decode_importdesc_func(reader: &mut impl Read) -> ImportDesc {
     ImportDesc::Func(decode_typeidx(reader))
 }
 
-fn decode_importdesc_tabletype(reader: &mut impl Read) -> ImportDesc {
-    ImportDesc::Table(decode_tabletype(reader))
+fn decode_importdesc_tabletype(reader: &mut impl Read) -> io::Result<ImportDesc> {
+    Ok(ImportDesc::Table(decode_tabletype(reader)?))
 }
 
 fn decode_importdesc_memtype(reader: &mut impl Read) -> ImportDesc {
@@ -65,3 +67,6 @@ fn decode_importdesc_globaltype(reader: &mut impl Read) -> ImportDesc {
     ImportDesc::Global(decode_globaltype(reader))
 }
 
+fn decode_importdesc_tagtype(reader: &mut impl Read) -> ImportDesc {
+    ImportDesc::Tag(decode_tagtype(reader))
+}
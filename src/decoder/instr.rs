@@ -1,21 +1,25 @@
 use std::io::Read;
 use crate::{
     ValType, 
+    RefType,
     
     Byte,
     // Expr,
 };
 use super::{
     byte_to_valtype,
+    decode_valtype,
+    decode_heaptype,
+    decode_vec,
 };
 use super::expr::{
     // decode_expr,
     decode_instrs,
 };
 
-use super::idx::{decode_labelidx, decode_labelindices, decode_funcidx, decode_localidx, decode_globalidx};
-use super::decode_u32_from_leb128;
-use crate::{Instr, BlockType, MemArg, ValSize, ValSign, ITestOp, IRelOp, FRelOp, IUnOp, IBinOp, FUnOp, FBinOp, CvtOp};
+use super::idx::{decode_labelidx, decode_labelindices, decode_typeidx, decode_funcidx, decode_tableidx, decode_memidx, decode_localidx, decode_globalidx, decode_dataidx, decode_elemidx, decode_tagidx};
+use super::{decode_u32_from_leb128, decode_u64_from_leb128, decode_i64_from_leb128};
+use crate::{Instr, Catch, BlockType, MemArg, ValSize, ValSign, ITestOp, IRelOp, FRelOp, IUnOp, IBinOp, FUnOp, FBinOp, CvtOp, LaneIdx, Shape, Half, VVBinOp, VIUnOp, VIBinOp, VFBinOp, VIShiftOp, VCvtOp, PackSize, AtomicRmwOp};
 
 
 pub fn decode_instr(b: Byte, reader: &mut impl Read) -> Instr {
@@ -28,12 +32,12 @@ pub fn decode_instr(b: Byte, reader: &mut impl Read) -> Instr {
         0x02 => {
             let block_type = decode_blocktype(reader);
             let instrs = decode_instrs(reader);
-            Instr::Block(block_type, instrs)
+            Instr::Block(block_type, instrs.into())
         },
         0x03 => {
             let block_type = decode_blocktype(reader);
             let expr = decode_instrs(reader);
-            Instr::Loop(block_type, expr)
+            Instr::Loop(block_type, expr.into())
         },
         0x04 => {
             let block_type = decode_blocktype(reader);
@@ -49,7 +53,7 @@ pub fn decode_instr(b: Byte, reader: &mut impl Read) -> Instr {
                         expr_false = Some(decode_instrs(reader));
                         break;
                     }  
-                    if b == 0x08 {
+                    if b == 0x0B {
                         // end
                         instrs_true = instrs;
                         break;
@@ -62,25 +66,33 @@ pub fn decode_instr(b: Byte, reader: &mut impl Read) -> Instr {
                 }
             }
 
-            Instr::If(block_type, instrs_true, expr_false)
+            Instr::If(block_type, instrs_true.into(), expr_false.map(Into::into))
         },
+        0x08 => Instr::Throw(decode_tagidx(reader)),
+        0x0A => Instr::ThrowRef,
         0x0C => Instr::Br(decode_labelidx(reader)),
         0x0D => Instr::BrIf(decode_labelidx(reader)),
         0x0E => Instr::BrTable(decode_labelindices(reader), decode_labelidx(reader)),
         0x0F => Instr::Return,
         0x10 => Instr::Call(decode_funcidx(reader)),
         0x11 => {
-            let funcidx = decode_funcidx(reader);
-            if let Some(Ok(b)) = reader.bytes().next() {
-                if b == 0x00 {
-                    // 0x00 is table index (for future)
-                    Instr::CallIndirect(funcidx)
-                } else {
-                    panic!("invaild on decode_instr CALL INDIRECT");
-                }
-            } else {
-                panic!("invaild on decode_instr CALL INDIRECT");
-            }
+            let typeidx = decode_typeidx(reader);
+            let tableidx = decode_tableidx(reader);
+            Instr::CallIndirect(tableidx, typeidx)
+        },
+        0x12 => Instr::ReturnCall(decode_funcidx(reader)),
+        0x13 => {
+            let typeidx = decode_typeidx(reader);
+            let tableidx = decode_tableidx(reader);
+            Instr::ReturnCallIndirect(tableidx, typeidx)
+        },
+        0x14 => Instr::CallRef(decode_typeidx(reader)),
+        0x15 => Instr::ReturnCallRef(decode_typeidx(reader)),
+        0x1F => {
+            let block_type = decode_blocktype(reader);
+            let catches = decode_vec(reader, decode_catch);
+            let instrs = decode_instrs(reader);
+            Instr::TryTable(block_type, catches, instrs.into())
         },
 
         /////////////////////////////
@@ -88,6 +100,13 @@ pub fn decode_instr(b: Byte, reader: &mut impl Read) -> Instr {
         /////////////////////////////
         0x1A => Instr::Drop(None),
         0x1B => Instr::Select(None),
+        0x1C => {
+            let valtypes = decode_vec(reader, decode_valtype);
+            if valtypes.len() != 1 {
+                panic!("invalid on decode_instr SELECT");
+            }
+            Instr::Select(Some(valtypes[0]))
+        },
 
         ///////////////////////////
         // Variable Instructions //
@@ -98,6 +117,12 @@ pub fn decode_instr(b: Byte, reader: &mut impl Read) -> Instr {
         0x23 => Instr::GlobalGet(decode_globalidx(reader)),
         0x24 => Instr::GlobalSet(decode_globalidx(reader)),
 
+        ////////////////////////
+        // Table Instructions //
+        ////////////////////////
+        0x25 => Instr::TableGet(decode_tableidx(reader)),
+        0x26 => Instr::TableSet(decode_tableidx(reader)),
+
         /////////////////////////
         // Memory Instructions //
         /////////////////////////
@@ -129,38 +154,16 @@ pub fn decode_instr(b: Byte, reader: &mut impl Read) -> Instr {
         0x3D => Instr::IStore16(ValSize::V64, decode_memarg(reader)),
         0x3E => Instr::I64Store32(decode_memarg(reader)),
 
-        0x3F => {
-            if let Some(Ok(b)) = reader.bytes().next() {
-                if b == 0x00 {
-                    // 0x00 is table index (for future)
-                    Instr::MemorySize
-                } else {
-                    panic!("invaild on decode_instr CALL INDIRECT");
-                }
-            } else {
-                panic!("invaild on decode_instr CALL INDIRECT");
-            }
-        },
-        0x40 => {
-            if let Some(Ok(b)) = reader.bytes().next() {
-                if b == 0x00 {
-                    // 0x00 is table index (for future)
-                    Instr::MemoryGrow
-                } else {
-                    panic!("invaild on decode_instr CALL INDIRECT");
-                }
-            } else {
-                panic!("invaild on decode_instr CALL INDIRECT");
-            }
-        },
+        0x3F => Instr::MemorySize(decode_memidx(reader)),
+        0x40 => Instr::MemoryGrow(decode_memidx(reader)),
 
         //////////////////////////
         // Numeric Instructions //
         //////////////////////////
-        0x41 => Instr::I32Const(0),  // TODO: i32 (readerがない)
-        0x42 => Instr::I64Const(0),  // TODO: i32 (readerがない)
-        0x43 => Instr::F32Const(0.0),  // TODO: f32 (readerがない)
-        0x44 => Instr::F64Const(0.0),  // TODO: f64 (readerがない)
+        0x41 => Instr::I32Const(decode_i64_from_leb128(reader) as i32 as u32),
+        0x42 => Instr::I64Const(decode_i64_from_leb128(reader) as u64),
+        0x43 => Instr::F32Const(decode_f32(reader)),
+        0x44 => Instr::F64Const(decode_f64(reader)),
 
         0x45 => Instr::ITestOp(ValSize::V32, ITestOp::Eqz),
         0x46 => Instr::IRelOp(ValSize::V32, IRelOp::Eq),
@@ -294,39 +297,550 @@ pub fn decode_instr(b: Byte, reader: &mut impl Read) -> Instr {
         0xBE => Instr::CvtOp(CvtOp::FReinterpretFromI(ValSize::V32)),
         0xBF => Instr::CvtOp(CvtOp::FReinterpretFromI(ValSize::V64)),
 
-        0xC0 => unimplemented!(),  // i32.extend8_s
-        0xC1 => unimplemented!(),  // i32.extend16_s
-        0xC2 => unimplemented!(),  // i64.extend8_s
-        0xC3 => unimplemented!(),  // i64.extend16_s
-        0xC4 => unimplemented!(),  // i64.extend32_s
+        0xC0 => Instr::CvtOp(CvtOp::IExtend8S(ValSize::V32)),
+        0xC1 => Instr::CvtOp(CvtOp::IExtend16S(ValSize::V32)),
+        0xC2 => Instr::CvtOp(CvtOp::IExtend8S(ValSize::V64)),
+        0xC3 => Instr::CvtOp(CvtOp::IExtend16S(ValSize::V64)),
+        0xC4 => Instr::CvtOp(CvtOp::I64Extend32S),
+
+        ////////////////////////////
+        // Reference Instructions //
+        ////////////////////////////
+        0xD0 => Instr::RefNull(decode_heaptype(reader)),
+        0xD1 => Instr::RefIsNull,
+        0xD2 => Instr::RefFunc(decode_funcidx(reader)),
+        0xD4 => Instr::RefAsNonNull,
+        0xD5 => Instr::BrOnNull(decode_labelidx(reader)),
+        0xD3 => Instr::RefEq,
+        0xD6 => Instr::BrOnNonNull(decode_labelidx(reader)),
 
         0xFC => {
-            // saturating truncation
+            // saturating truncation, bulk memory, table instructions
             let variable_length = decode_u32_from_leb128(reader);
 
             match variable_length {
-                0 => unimplemented!(),  // i32.trunc_sat_f32_s
-                1 => unimplemented!(),  // i32.trunc_sat_f32_u
-                2 => unimplemented!(),  // i32.trunc_sat_f64_s
-                3 => unimplemented!(),  // i32.trunc_sat_f64_u
-                4 => unimplemented!(),  // i64.trunc_sat_f32_s
-                5 => unimplemented!(),  // i64.trunc_sat_f32_u
-                6 => unimplemented!(),  // i64.trunc_sat_f64_s
-                7 => unimplemented!(),  // i64.trunc_sat_f64_u
+                0 => Instr::CvtOp(CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V32, ValSign::S)),
+                1 => Instr::CvtOp(CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V32, ValSign::U)),
+                2 => Instr::CvtOp(CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V64, ValSign::S)),
+                3 => Instr::CvtOp(CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V64, ValSign::U)),
+                4 => Instr::CvtOp(CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V32, ValSign::S)),
+                5 => Instr::CvtOp(CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V32, ValSign::U)),
+                6 => Instr::CvtOp(CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V64, ValSign::S)),
+                7 => Instr::CvtOp(CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V64, ValSign::U)),
+                8 => {
+                    let dataidx = decode_dataidx(reader);
+                    let memidx = decode_memidx(reader);
+                    Instr::MemoryInit(memidx, dataidx)
+                },
+                9 => Instr::DataDrop(decode_dataidx(reader)),
+                10 => {
+                    let dst = decode_memidx(reader);
+                    let src = decode_memidx(reader);
+                    Instr::MemoryCopy(dst, src)
+                },
+                11 => Instr::MemoryFill(decode_memidx(reader)),
+                12 => {
+                    let elemidx = decode_elemidx(reader);
+                    let tableidx = decode_tableidx(reader);
+                    Instr::TableInit(tableidx, elemidx)
+                },
+                13 => Instr::ElemDrop(decode_elemidx(reader)),
+                14 => {
+                    let tableidx1 = decode_tableidx(reader);
+                    let tableidx2 = decode_tableidx(reader);
+                    Instr::TableCopy(tableidx1, tableidx2)
+                },
+                15 => Instr::TableGrow(decode_tableidx(reader)),
+                16 => Instr::TableSize(decode_tableidx(reader)),
+                17 => Instr::TableFill(decode_tableidx(reader)),
                 _ => panic!("invalid on decode_instr"),
             }
         },
+        0xFB => decode_gc_instr(reader),
+        0xFD => decode_vector_instr(reader),
+        0xFE => decode_atomic_instr(reader),
         _ => unimplemented!(), 
     }
 }
 
+fn decode_gc_instr(reader: &mut impl Read) -> Instr {
+    let variable_length = decode_u32_from_leb128(reader);
+
+    match variable_length {
+        0 => Instr::StructNew(decode_typeidx(reader)),
+        1 => Instr::StructNewDefault(decode_typeidx(reader)),
+        2..=4 => {
+            let sign = decode_field_sign(variable_length - 2);
+            let typeidx = decode_typeidx(reader);
+            Instr::StructGet(sign, typeidx, decode_u32_from_leb128(reader))
+        },
+        5 => {
+            let typeidx = decode_typeidx(reader);
+            Instr::StructSet(typeidx, decode_u32_from_leb128(reader))
+        },
+        6 => Instr::ArrayNew(decode_typeidx(reader)),
+        7 => Instr::ArrayNewDefault(decode_typeidx(reader)),
+        8 => {
+            let typeidx = decode_typeidx(reader);
+            Instr::ArrayNewFixed(typeidx, decode_u32_from_leb128(reader))
+        },
+        9 => {
+            let typeidx = decode_typeidx(reader);
+            Instr::ArrayNewData(typeidx, decode_dataidx(reader))
+        },
+        10 => {
+            let typeidx = decode_typeidx(reader);
+            Instr::ArrayNewElem(typeidx, decode_elemidx(reader))
+        },
+        11..=13 => Instr::ArrayGet(decode_field_sign(variable_length - 11), decode_typeidx(reader)),
+        14 => Instr::ArraySet(decode_typeidx(reader)),
+        15 => Instr::ArrayLen,
+        16 => Instr::ArrayFill(decode_typeidx(reader)),
+        17 => {
+            let dst = decode_typeidx(reader);
+            let src = decode_typeidx(reader);
+            Instr::ArrayCopy(dst, src)
+        },
+        18 => {
+            let typeidx = decode_typeidx(reader);
+            Instr::ArrayInitData(typeidx, decode_dataidx(reader))
+        },
+        19 => {
+            let typeidx = decode_typeidx(reader);
+            Instr::ArrayInitElem(typeidx, decode_elemidx(reader))
+        },
+        20 => Instr::RefTest(RefType { nullable: false, heap: decode_heaptype(reader) }),
+        21 => Instr::RefTest(RefType { nullable: true, heap: decode_heaptype(reader) }),
+        22 => Instr::RefCast(RefType { nullable: false, heap: decode_heaptype(reader) }),
+        23 => Instr::RefCast(RefType { nullable: true, heap: decode_heaptype(reader) }),
+        24 | 25 => {
+            let flags = decode_castflags(reader);
+            let labelidx = decode_labelidx(reader);
+            let rt1 = RefType { nullable: flags & 0x01 != 0, heap: decode_heaptype(reader) };
+            let rt2 = RefType { nullable: flags & 0x02 != 0, heap: decode_heaptype(reader) };
+            if variable_length == 24 {
+                Instr::BrOnCast(labelidx, rt1, rt2)
+            } else {
+                Instr::BrOnCastFail(labelidx, rt1, rt2)
+            }
+        },
+        26 => Instr::AnyConvertExtern,
+        27 => Instr::ExternConvertAny,
+        28 => Instr::RefI31,
+        29 => Instr::I31Get(ValSign::S),
+        30 => Instr::I31Get(ValSign::U),
+        _ => panic!("invalid on decode_instr"),
+    }
+}
+
+// get, get_s and get_u follow each other
+fn decode_field_sign(n: u32) -> Option<ValSign> {
+    match n {
+        0 => None,
+        1 => Some(ValSign::S),
+        _ => Some(ValSign::U),
+    }
+}
+
+fn decode_castflags(reader: &mut impl Read) -> Byte {
+    match reader.bytes().next() {
+        Some(Ok(flags)) if flags <= 0x03 => flags,
+        _ => panic!("invalid on decode_instr"),
+    }
+}
+
+fn decode_atomic_instr(reader: &mut impl Read) -> Instr {
+    let variable_length = decode_u32_from_leb128(reader);
+
+    match variable_length {
+        0 => Instr::AtomicNotify(decode_memarg(reader)),
+        1 => Instr::AtomicWait(ValSize::V32, decode_memarg(reader)),
+        2 => Instr::AtomicWait(ValSize::V64, decode_memarg(reader)),
+        3 => {
+            decode_reserved_zero(reader);
+            Instr::AtomicFence
+        },
+        16 => Instr::AtomicLoad(ValSize::V32, None, decode_memarg(reader)),
+        17 => Instr::AtomicLoad(ValSize::V64, None, decode_memarg(reader)),
+        18 => Instr::AtomicLoad(ValSize::V32, Some(PackSize::P8), decode_memarg(reader)),
+        19 => Instr::AtomicLoad(ValSize::V32, Some(PackSize::P16), decode_memarg(reader)),
+        20 => Instr::AtomicLoad(ValSize::V64, Some(PackSize::P8), decode_memarg(reader)),
+        21 => Instr::AtomicLoad(ValSize::V64, Some(PackSize::P16), decode_memarg(reader)),
+        22 => Instr::AtomicLoad(ValSize::V64, Some(PackSize::P32), decode_memarg(reader)),
+        23 => Instr::AtomicStore(ValSize::V32, None, decode_memarg(reader)),
+        24 => Instr::AtomicStore(ValSize::V64, None, decode_memarg(reader)),
+        25 => Instr::AtomicStore(ValSize::V32, Some(PackSize::P8), decode_memarg(reader)),
+        26 => Instr::AtomicStore(ValSize::V32, Some(PackSize::P16), decode_memarg(reader)),
+        27 => Instr::AtomicStore(ValSize::V64, Some(PackSize::P8), decode_memarg(reader)),
+        28 => Instr::AtomicStore(ValSize::V64, Some(PackSize::P16), decode_memarg(reader)),
+        29 => Instr::AtomicStore(ValSize::V64, Some(PackSize::P32), decode_memarg(reader)),
+        30 => Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Add, decode_memarg(reader)),
+        31 => Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Add, decode_memarg(reader)),
+        32 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Add, decode_memarg(reader)),
+        33 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Add, decode_memarg(reader)),
+        34 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Add, decode_memarg(reader)),
+        35 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Add, decode_memarg(reader)),
+        36 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Add, decode_memarg(reader)),
+        37 => Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Sub, decode_memarg(reader)),
+        38 => Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Sub, decode_memarg(reader)),
+        39 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Sub, decode_memarg(reader)),
+        40 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Sub, decode_memarg(reader)),
+        41 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Sub, decode_memarg(reader)),
+        42 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Sub, decode_memarg(reader)),
+        43 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Sub, decode_memarg(reader)),
+        44 => Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::And, decode_memarg(reader)),
+        45 => Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::And, decode_memarg(reader)),
+        46 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::And, decode_memarg(reader)),
+        47 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::And, decode_memarg(reader)),
+        48 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::And, decode_memarg(reader)),
+        49 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::And, decode_memarg(reader)),
+        50 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::And, decode_memarg(reader)),
+        51 => Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Or, decode_memarg(reader)),
+        52 => Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Or, decode_memarg(reader)),
+        53 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Or, decode_memarg(reader)),
+        54 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Or, decode_memarg(reader)),
+        55 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Or, decode_memarg(reader)),
+        56 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Or, decode_memarg(reader)),
+        57 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Or, decode_memarg(reader)),
+        58 => Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Xor, decode_memarg(reader)),
+        59 => Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Xor, decode_memarg(reader)),
+        60 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Xor, decode_memarg(reader)),
+        61 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Xor, decode_memarg(reader)),
+        62 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Xor, decode_memarg(reader)),
+        63 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Xor, decode_memarg(reader)),
+        64 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Xor, decode_memarg(reader)),
+        65 => Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Xchg, decode_memarg(reader)),
+        66 => Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Xchg, decode_memarg(reader)),
+        67 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Xchg, decode_memarg(reader)),
+        68 => Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Xchg, decode_memarg(reader)),
+        69 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Xchg, decode_memarg(reader)),
+        70 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Xchg, decode_memarg(reader)),
+        71 => Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Xchg, decode_memarg(reader)),
+        72 => Instr::AtomicCmpxchg(ValSize::V32, None, decode_memarg(reader)),
+        73 => Instr::AtomicCmpxchg(ValSize::V64, None, decode_memarg(reader)),
+        74 => Instr::AtomicCmpxchg(ValSize::V32, Some(PackSize::P8), decode_memarg(reader)),
+        75 => Instr::AtomicCmpxchg(ValSize::V32, Some(PackSize::P16), decode_memarg(reader)),
+        76 => Instr::AtomicCmpxchg(ValSize::V64, Some(PackSize::P8), decode_memarg(reader)),
+        77 => Instr::AtomicCmpxchg(ValSize::V64, Some(PackSize::P16), decode_memarg(reader)),
+        78 => Instr::AtomicCmpxchg(ValSize::V64, Some(PackSize::P32), decode_memarg(reader)),
+        _ => panic!("invalid on decode_instr"),
+    }
+}
+
+fn decode_vector_instr(reader: &mut impl Read) -> Instr {
+    let variable_length = decode_u32_from_leb128(reader);
+
+    match variable_length {
+        0 => Instr::Load(ValType::V128, decode_memarg(reader)),
+        1 => Instr::V128LoadExtend(Shape::I16x8, ValSign::S, decode_memarg(reader)),
+        2 => Instr::V128LoadExtend(Shape::I16x8, ValSign::U, decode_memarg(reader)),
+        3 => Instr::V128LoadExtend(Shape::I32x4, ValSign::S, decode_memarg(reader)),
+        4 => Instr::V128LoadExtend(Shape::I32x4, ValSign::U, decode_memarg(reader)),
+        5 => Instr::V128LoadExtend(Shape::I64x2, ValSign::S, decode_memarg(reader)),
+        6 => Instr::V128LoadExtend(Shape::I64x2, ValSign::U, decode_memarg(reader)),
+        7 => Instr::V128LoadSplat(Shape::I8x16, decode_memarg(reader)),
+        8 => Instr::V128LoadSplat(Shape::I16x8, decode_memarg(reader)),
+        9 => Instr::V128LoadSplat(Shape::I32x4, decode_memarg(reader)),
+        10 => Instr::V128LoadSplat(Shape::I64x2, decode_memarg(reader)),
+        11 => Instr::Store(ValType::V128, decode_memarg(reader)),
+        12 => Instr::V128Const(decode_v128(reader)),
+        13 => Instr::I8x16Shuffle(decode_laneindices(reader)),
+        14 => Instr::I8x16Swizzle,
+        15 => Instr::Splat(Shape::I8x16),
+        16 => Instr::Splat(Shape::I16x8),
+        17 => Instr::Splat(Shape::I32x4),
+        18 => Instr::Splat(Shape::I64x2),
+        19 => Instr::Splat(Shape::F32x4),
+        20 => Instr::Splat(Shape::F64x2),
+        21 => Instr::ExtractLane(Shape::I8x16, Some(ValSign::S), decode_laneidx(reader)),
+        22 => Instr::ExtractLane(Shape::I8x16, Some(ValSign::U), decode_laneidx(reader)),
+        23 => Instr::ReplaceLane(Shape::I8x16, decode_laneidx(reader)),
+        24 => Instr::ExtractLane(Shape::I16x8, Some(ValSign::S), decode_laneidx(reader)),
+        25 => Instr::ExtractLane(Shape::I16x8, Some(ValSign::U), decode_laneidx(reader)),
+        26 => Instr::ReplaceLane(Shape::I16x8, decode_laneidx(reader)),
+        27 => Instr::ExtractLane(Shape::I32x4, None, decode_laneidx(reader)),
+        28 => Instr::ReplaceLane(Shape::I32x4, decode_laneidx(reader)),
+        29 => Instr::ExtractLane(Shape::I64x2, None, decode_laneidx(reader)),
+        30 => Instr::ReplaceLane(Shape::I64x2, decode_laneidx(reader)),
+        31 => Instr::ExtractLane(Shape::F32x4, None, decode_laneidx(reader)),
+        32 => Instr::ReplaceLane(Shape::F32x4, decode_laneidx(reader)),
+        33 => Instr::ExtractLane(Shape::F64x2, None, decode_laneidx(reader)),
+        34 => Instr::ReplaceLane(Shape::F64x2, decode_laneidx(reader)),
+        35 => Instr::VIRelOp(Shape::I8x16, IRelOp::Eq),
+        36 => Instr::VIRelOp(Shape::I8x16, IRelOp::Ne),
+        37 => Instr::VIRelOp(Shape::I8x16, IRelOp::Lt(ValSign::S)),
+        38 => Instr::VIRelOp(Shape::I8x16, IRelOp::Lt(ValSign::U)),
+        39 => Instr::VIRelOp(Shape::I8x16, IRelOp::Gt(ValSign::S)),
+        40 => Instr::VIRelOp(Shape::I8x16, IRelOp::Gt(ValSign::U)),
+        41 => Instr::VIRelOp(Shape::I8x16, IRelOp::Le(ValSign::S)),
+        42 => Instr::VIRelOp(Shape::I8x16, IRelOp::Le(ValSign::U)),
+        43 => Instr::VIRelOp(Shape::I8x16, IRelOp::Ge(ValSign::S)),
+        44 => Instr::VIRelOp(Shape::I8x16, IRelOp::Ge(ValSign::U)),
+        45 => Instr::VIRelOp(Shape::I16x8, IRelOp::Eq),
+        46 => Instr::VIRelOp(Shape::I16x8, IRelOp::Ne),
+        47 => Instr::VIRelOp(Shape::I16x8, IRelOp::Lt(ValSign::S)),
+        48 => Instr::VIRelOp(Shape::I16x8, IRelOp::Lt(ValSign::U)),
+        49 => Instr::VIRelOp(Shape::I16x8, IRelOp::Gt(ValSign::S)),
+        50 => Instr::VIRelOp(Shape::I16x8, IRelOp::Gt(ValSign::U)),
+        51 => Instr::VIRelOp(Shape::I16x8, IRelOp::Le(ValSign::S)),
+        52 => Instr::VIRelOp(Shape::I16x8, IRelOp::Le(ValSign::U)),
+        53 => Instr::VIRelOp(Shape::I16x8, IRelOp::Ge(ValSign::S)),
+        54 => Instr::VIRelOp(Shape::I16x8, IRelOp::Ge(ValSign::U)),
+        55 => Instr::VIRelOp(Shape::I32x4, IRelOp::Eq),
+        56 => Instr::VIRelOp(Shape::I32x4, IRelOp::Ne),
+        57 => Instr::VIRelOp(Shape::I32x4, IRelOp::Lt(ValSign::S)),
+        58 => Instr::VIRelOp(Shape::I32x4, IRelOp::Lt(ValSign::U)),
+        59 => Instr::VIRelOp(Shape::I32x4, IRelOp::Gt(ValSign::S)),
+        60 => Instr::VIRelOp(Shape::I32x4, IRelOp::Gt(ValSign::U)),
+        61 => Instr::VIRelOp(Shape::I32x4, IRelOp::Le(ValSign::S)),
+        62 => Instr::VIRelOp(Shape::I32x4, IRelOp::Le(ValSign::U)),
+        63 => Instr::VIRelOp(Shape::I32x4, IRelOp::Ge(ValSign::S)),
+        64 => Instr::VIRelOp(Shape::I32x4, IRelOp::Ge(ValSign::U)),
+        65 => Instr::VFRelOp(Shape::F32x4, FRelOp::Eq),
+        66 => Instr::VFRelOp(Shape::F32x4, FRelOp::Ne),
+        67 => Instr::VFRelOp(Shape::F32x4, FRelOp::Lt),
+        68 => Instr::VFRelOp(Shape::F32x4, FRelOp::Gt),
+        69 => Instr::VFRelOp(Shape::F32x4, FRelOp::Le),
+        70 => Instr::VFRelOp(Shape::F32x4, FRelOp::Ge),
+        71 => Instr::VFRelOp(Shape::F64x2, FRelOp::Eq),
+        72 => Instr::VFRelOp(Shape::F64x2, FRelOp::Ne),
+        73 => Instr::VFRelOp(Shape::F64x2, FRelOp::Lt),
+        74 => Instr::VFRelOp(Shape::F64x2, FRelOp::Gt),
+        75 => Instr::VFRelOp(Shape::F64x2, FRelOp::Le),
+        76 => Instr::VFRelOp(Shape::F64x2, FRelOp::Ge),
+        77 => Instr::V128Not,
+        78 => Instr::VVBinOp(VVBinOp::And),
+        79 => Instr::VVBinOp(VVBinOp::AndNot),
+        80 => Instr::VVBinOp(VVBinOp::Or),
+        81 => Instr::VVBinOp(VVBinOp::Xor),
+        82 => Instr::V128Bitselect,
+        83 => Instr::V128AnyTrue,
+        84 => {
+            let memarg = decode_memarg(reader);
+            Instr::V128LoadLane(Shape::I8x16, memarg, decode_laneidx(reader))
+        },
+        85 => {
+            let memarg = decode_memarg(reader);
+            Instr::V128LoadLane(Shape::I16x8, memarg, decode_laneidx(reader))
+        },
+        86 => {
+            let memarg = decode_memarg(reader);
+            Instr::V128LoadLane(Shape::I32x4, memarg, decode_laneidx(reader))
+        },
+        87 => {
+            let memarg = decode_memarg(reader);
+            Instr::V128LoadLane(Shape::I64x2, memarg, decode_laneidx(reader))
+        },
+        88 => {
+            let memarg = decode_memarg(reader);
+            Instr::V128StoreLane(Shape::I8x16, memarg, decode_laneidx(reader))
+        },
+        89 => {
+            let memarg = decode_memarg(reader);
+            Instr::V128StoreLane(Shape::I16x8, memarg, decode_laneidx(reader))
+        },
+        90 => {
+            let memarg = decode_memarg(reader);
+            Instr::V128StoreLane(Shape::I32x4, memarg, decode_laneidx(reader))
+        },
+        91 => {
+            let memarg = decode_memarg(reader);
+            Instr::V128StoreLane(Shape::I64x2, memarg, decode_laneidx(reader))
+        },
+        92 => Instr::V128LoadZero(ValSize::V32, decode_memarg(reader)),
+        93 => Instr::V128LoadZero(ValSize::V64, decode_memarg(reader)),
+        94 => Instr::VCvtOp(VCvtOp::F32x4DemoteF64x2Zero),
+        95 => Instr::VCvtOp(VCvtOp::F64x2PromoteLowF32x4),
+        96 => Instr::VIUnOp(Shape::I8x16, VIUnOp::Abs),
+        97 => Instr::VIUnOp(Shape::I8x16, VIUnOp::Neg),
+        98 => Instr::VIUnOp(Shape::I8x16, VIUnOp::Popcnt),
+        99 => Instr::AllTrue(Shape::I8x16),
+        100 => Instr::Bitmask(Shape::I8x16),
+        101 => Instr::Narrow(Shape::I8x16, ValSign::S),
+        102 => Instr::Narrow(Shape::I8x16, ValSign::U),
+        103 => Instr::VFUnOp(Shape::F32x4, FUnOp::Ceil),
+        104 => Instr::VFUnOp(Shape::F32x4, FUnOp::Floor),
+        105 => Instr::VFUnOp(Shape::F32x4, FUnOp::Trunc),
+        106 => Instr::VFUnOp(Shape::F32x4, FUnOp::Nearest),
+        107 => Instr::VIShiftOp(Shape::I8x16, VIShiftOp::Shl),
+        108 => Instr::VIShiftOp(Shape::I8x16, VIShiftOp::Shr(ValSign::S)),
+        109 => Instr::VIShiftOp(Shape::I8x16, VIShiftOp::Shr(ValSign::U)),
+        110 => Instr::VIBinOp(Shape::I8x16, VIBinOp::Add),
+        111 => Instr::VIBinOp(Shape::I8x16, VIBinOp::AddSat(ValSign::S)),
+        112 => Instr::VIBinOp(Shape::I8x16, VIBinOp::AddSat(ValSign::U)),
+        113 => Instr::VIBinOp(Shape::I8x16, VIBinOp::Sub),
+        114 => Instr::VIBinOp(Shape::I8x16, VIBinOp::SubSat(ValSign::S)),
+        115 => Instr::VIBinOp(Shape::I8x16, VIBinOp::SubSat(ValSign::U)),
+        116 => Instr::VFUnOp(Shape::F64x2, FUnOp::Ceil),
+        117 => Instr::VFUnOp(Shape::F64x2, FUnOp::Floor),
+        118 => Instr::VIBinOp(Shape::I8x16, VIBinOp::Min(ValSign::S)),
+        119 => Instr::VIBinOp(Shape::I8x16, VIBinOp::Min(ValSign::U)),
+        120 => Instr::VIBinOp(Shape::I8x16, VIBinOp::Max(ValSign::S)),
+        121 => Instr::VIBinOp(Shape::I8x16, VIBinOp::Max(ValSign::U)),
+        122 => Instr::VFUnOp(Shape::F64x2, FUnOp::Trunc),
+        123 => Instr::VIBinOp(Shape::I8x16, VIBinOp::AvgrU),
+        124 => Instr::ExtAddPairwise(Shape::I16x8, ValSign::S),
+        125 => Instr::ExtAddPairwise(Shape::I16x8, ValSign::U),
+        126 => Instr::ExtAddPairwise(Shape::I32x4, ValSign::S),
+        127 => Instr::ExtAddPairwise(Shape::I32x4, ValSign::U),
+        128 => Instr::VIUnOp(Shape::I16x8, VIUnOp::Abs),
+        129 => Instr::VIUnOp(Shape::I16x8, VIUnOp::Neg),
+        130 => Instr::VIBinOp(Shape::I16x8, VIBinOp::Q15MulrSatS),
+        131 => Instr::AllTrue(Shape::I16x8),
+        132 => Instr::Bitmask(Shape::I16x8),
+        133 => Instr::Narrow(Shape::I16x8, ValSign::S),
+        134 => Instr::Narrow(Shape::I16x8, ValSign::U),
+        135 => Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::Low, ValSign::S)),
+        136 => Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::High, ValSign::S)),
+        137 => Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::Low, ValSign::U)),
+        138 => Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::High, ValSign::U)),
+        139 => Instr::VIShiftOp(Shape::I16x8, VIShiftOp::Shl),
+        140 => Instr::VIShiftOp(Shape::I16x8, VIShiftOp::Shr(ValSign::S)),
+        141 => Instr::VIShiftOp(Shape::I16x8, VIShiftOp::Shr(ValSign::U)),
+        142 => Instr::VIBinOp(Shape::I16x8, VIBinOp::Add),
+        143 => Instr::VIBinOp(Shape::I16x8, VIBinOp::AddSat(ValSign::S)),
+        144 => Instr::VIBinOp(Shape::I16x8, VIBinOp::AddSat(ValSign::U)),
+        145 => Instr::VIBinOp(Shape::I16x8, VIBinOp::Sub),
+        146 => Instr::VIBinOp(Shape::I16x8, VIBinOp::SubSat(ValSign::S)),
+        147 => Instr::VIBinOp(Shape::I16x8, VIBinOp::SubSat(ValSign::U)),
+        148 => Instr::VFUnOp(Shape::F64x2, FUnOp::Nearest),
+        149 => Instr::VIBinOp(Shape::I16x8, VIBinOp::Mul),
+        150 => Instr::VIBinOp(Shape::I16x8, VIBinOp::Min(ValSign::S)),
+        151 => Instr::VIBinOp(Shape::I16x8, VIBinOp::Min(ValSign::U)),
+        152 => Instr::VIBinOp(Shape::I16x8, VIBinOp::Max(ValSign::S)),
+        153 => Instr::VIBinOp(Shape::I16x8, VIBinOp::Max(ValSign::U)),
+        155 => Instr::VIBinOp(Shape::I16x8, VIBinOp::AvgrU),
+        156 => Instr::ExtMul(Shape::I16x8, Half::Low, ValSign::S),
+        157 => Instr::ExtMul(Shape::I16x8, Half::High, ValSign::S),
+        158 => Instr::ExtMul(Shape::I16x8, Half::Low, ValSign::U),
+        159 => Instr::ExtMul(Shape::I16x8, Half::High, ValSign::U),
+        160 => Instr::VIUnOp(Shape::I32x4, VIUnOp::Abs),
+        161 => Instr::VIUnOp(Shape::I32x4, VIUnOp::Neg),
+        163 => Instr::AllTrue(Shape::I32x4),
+        164 => Instr::Bitmask(Shape::I32x4),
+        167 => Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::Low, ValSign::S)),
+        168 => Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::High, ValSign::S)),
+        169 => Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::Low, ValSign::U)),
+        170 => Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::High, ValSign::U)),
+        171 => Instr::VIShiftOp(Shape::I32x4, VIShiftOp::Shl),
+        172 => Instr::VIShiftOp(Shape::I32x4, VIShiftOp::Shr(ValSign::S)),
+        173 => Instr::VIShiftOp(Shape::I32x4, VIShiftOp::Shr(ValSign::U)),
+        174 => Instr::VIBinOp(Shape::I32x4, VIBinOp::Add),
+        177 => Instr::VIBinOp(Shape::I32x4, VIBinOp::Sub),
+        181 => Instr::VIBinOp(Shape::I32x4, VIBinOp::Mul),
+        182 => Instr::VIBinOp(Shape::I32x4, VIBinOp::Min(ValSign::S)),
+        183 => Instr::VIBinOp(Shape::I32x4, VIBinOp::Min(ValSign::U)),
+        184 => Instr::VIBinOp(Shape::I32x4, VIBinOp::Max(ValSign::S)),
+        185 => Instr::VIBinOp(Shape::I32x4, VIBinOp::Max(ValSign::U)),
+        186 => Instr::I32x4DotI16x8S,
+        188 => Instr::ExtMul(Shape::I32x4, Half::Low, ValSign::S),
+        189 => Instr::ExtMul(Shape::I32x4, Half::High, ValSign::S),
+        190 => Instr::ExtMul(Shape::I32x4, Half::Low, ValSign::U),
+        191 => Instr::ExtMul(Shape::I32x4, Half::High, ValSign::U),
+        192 => Instr::VIUnOp(Shape::I64x2, VIUnOp::Abs),
+        193 => Instr::VIUnOp(Shape::I64x2, VIUnOp::Neg),
+        195 => Instr::AllTrue(Shape::I64x2),
+        196 => Instr::Bitmask(Shape::I64x2),
+        199 => Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::Low, ValSign::S)),
+        200 => Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::High, ValSign::S)),
+        201 => Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::Low, ValSign::U)),
+        202 => Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::High, ValSign::U)),
+        203 => Instr::VIShiftOp(Shape::I64x2, VIShiftOp::Shl),
+        204 => Instr::VIShiftOp(Shape::I64x2, VIShiftOp::Shr(ValSign::S)),
+        205 => Instr::VIShiftOp(Shape::I64x2, VIShiftOp::Shr(ValSign::U)),
+        206 => Instr::VIBinOp(Shape::I64x2, VIBinOp::Add),
+        209 => Instr::VIBinOp(Shape::I64x2, VIBinOp::Sub),
+        213 => Instr::VIBinOp(Shape::I64x2, VIBinOp::Mul),
+        214 => Instr::VIRelOp(Shape::I64x2, IRelOp::Eq),
+        215 => Instr::VIRelOp(Shape::I64x2, IRelOp::Ne),
+        216 => Instr::VIRelOp(Shape::I64x2, IRelOp::Lt(ValSign::S)),
+        217 => Instr::VIRelOp(Shape::I64x2, IRelOp::Gt(ValSign::S)),
+        218 => Instr::VIRelOp(Shape::I64x2, IRelOp::Le(ValSign::S)),
+        219 => Instr::VIRelOp(Shape::I64x2, IRelOp::Ge(ValSign::S)),
+        220 => Instr::ExtMul(Shape::I64x2, Half::Low, ValSign::S),
+        221 => Instr::ExtMul(Shape::I64x2, Half::High, ValSign::S),
+        222 => Instr::ExtMul(Shape::I64x2, Half::Low, ValSign::U),
+        223 => Instr::ExtMul(Shape::I64x2, Half::High, ValSign::U),
+        224 => Instr::VFUnOp(Shape::F32x4, FUnOp::Abs),
+        225 => Instr::VFUnOp(Shape::F32x4, FUnOp::Neg),
+        227 => Instr::VFUnOp(Shape::F32x4, FUnOp::Sqrt),
+        228 => Instr::VFBinOp(Shape::F32x4, VFBinOp::Add),
+        229 => Instr::VFBinOp(Shape::F32x4, VFBinOp::Sub),
+        230 => Instr::VFBinOp(Shape::F32x4, VFBinOp::Mul),
+        231 => Instr::VFBinOp(Shape::F32x4, VFBinOp::Div),
+        232 => Instr::VFBinOp(Shape::F32x4, VFBinOp::Min),
+        233 => Instr::VFBinOp(Shape::F32x4, VFBinOp::Max),
+        234 => Instr::VFBinOp(Shape::F32x4, VFBinOp::PMin),
+        235 => Instr::VFBinOp(Shape::F32x4, VFBinOp::PMax),
+        236 => Instr::VFUnOp(Shape::F64x2, FUnOp::Abs),
+        237 => Instr::VFUnOp(Shape::F64x2, FUnOp::Neg),
+        239 => Instr::VFUnOp(Shape::F64x2, FUnOp::Sqrt),
+        240 => Instr::VFBinOp(Shape::F64x2, VFBinOp::Add),
+        241 => Instr::VFBinOp(Shape::F64x2, VFBinOp::Sub),
+        242 => Instr::VFBinOp(Shape::F64x2, VFBinOp::Mul),
+        243 => Instr::VFBinOp(Shape::F64x2, VFBinOp::Div),
+        244 => Instr::VFBinOp(Shape::F64x2, VFBinOp::Min),
+        245 => Instr::VFBinOp(Shape::F64x2, VFBinOp::Max),
+        246 => Instr::VFBinOp(Shape::F64x2, VFBinOp::PMin),
+        247 => Instr::VFBinOp(Shape::F64x2, VFBinOp::PMax),
+        248 => Instr::VCvtOp(VCvtOp::TruncSat(Shape::F32x4, ValSign::S)),
+        249 => Instr::VCvtOp(VCvtOp::TruncSat(Shape::F32x4, ValSign::U)),
+        250 => Instr::VCvtOp(VCvtOp::Convert(Shape::F32x4, ValSign::S)),
+        251 => Instr::VCvtOp(VCvtOp::Convert(Shape::F32x4, ValSign::U)),
+        252 => Instr::VCvtOp(VCvtOp::TruncSat(Shape::F64x2, ValSign::S)),
+        253 => Instr::VCvtOp(VCvtOp::TruncSat(Shape::F64x2, ValSign::U)),
+        254 => Instr::VCvtOp(VCvtOp::Convert(Shape::F64x2, ValSign::S)),
+        255 => Instr::VCvtOp(VCvtOp::Convert(Shape::F64x2, ValSign::U)),
+        256 => Instr::I8x16RelaxedSwizzle,
+        257 => Instr::RelaxedTrunc(Shape::F32x4, ValSign::S),
+        258 => Instr::RelaxedTrunc(Shape::F32x4, ValSign::U),
+        259 => Instr::RelaxedTrunc(Shape::F64x2, ValSign::S),
+        260 => Instr::RelaxedTrunc(Shape::F64x2, ValSign::U),
+        261 => Instr::RelaxedMadd(Shape::F32x4),
+        262 => Instr::RelaxedNmadd(Shape::F32x4),
+        263 => Instr::RelaxedMadd(Shape::F64x2),
+        264 => Instr::RelaxedNmadd(Shape::F64x2),
+        265 => Instr::RelaxedLaneselect(Shape::I8x16),
+        266 => Instr::RelaxedLaneselect(Shape::I16x8),
+        267 => Instr::RelaxedLaneselect(Shape::I32x4),
+        268 => Instr::RelaxedLaneselect(Shape::I64x2),
+        269 => Instr::RelaxedMin(Shape::F32x4),
+        270 => Instr::RelaxedMax(Shape::F32x4),
+        271 => Instr::RelaxedMin(Shape::F64x2),
+        272 => Instr::RelaxedMax(Shape::F64x2),
+        273 => Instr::I16x8RelaxedQ15mulrS,
+        274 => Instr::I16x8RelaxedDotI8x16I7x16S,
+        275 => Instr::I32x4RelaxedDotI8x16I7x16AddS,
+        _ => panic!("invalid on decode_instr"),
+    }
+}
+
+// 0x00 is memory index (for future)
+fn decode_reserved_zero(reader: &mut impl Read) {
+    if let Some(Ok(0x00)) = reader.bytes().next() {
+    } else {
+        panic!("invalid on decode_instr");
+    }
+}
+
 fn decode_blocktype(reader: &mut impl Read) -> BlockType {
-    // TODO: s33ではなく、u32で読んでいる
     if let Some(Ok(b)) = reader.bytes().next() {
         match b {
             0x40 => BlockType::ValType(None),
-            0x7F | 0x7E | 0x7D | 0x7C => BlockType::ValType(Some(byte_to_valtype(b))),
-            _ => BlockType::TypeIdx(decode_u32_from_leb128(reader)), 
+            0x7F | 0x7E | 0x7D | 0x7C | 0x7B | 0x70 | 0x6F | 0x69 | 0x63 | 0x64 |
+            0x6E | 0x6D | 0x6C | 0x6B | 0x6A | 0x71 | 0x73 | 0x72 | 0x74 => {
+                BlockType::ValType(Some(byte_to_valtype(b, reader)))
+            },
+            _ => {
+                // typeidx as s33, the first byte is already read
+                let first = [b];
+                let mut chained = first.as_ref().chain(reader);
+                let typeidx = decode_i64_from_leb128(&mut chained);
+                if typeidx < 0 || typeidx > u32::MAX as i64 {
+                    panic!("invalid on decode_blocktype");
+                }
+                BlockType::TypeIdx(typeidx as u32)
+            },
         }
     } else {
         panic!("invalid on decode_blocktype");
@@ -334,8 +848,78 @@ fn decode_blocktype(reader: &mut impl Read) -> BlockType {
     
 }
 
+fn decode_catch(reader: &mut impl Read) -> Catch {
+    if let Some(Ok(b)) = reader.bytes().next() {
+        match b {
+            0x00 => Catch::Tag(decode_tagidx(reader), decode_labelidx(reader)),
+            0x01 => Catch::TagRef(decode_tagidx(reader), decode_labelidx(reader)),
+            0x02 => Catch::All(decode_labelidx(reader)),
+            0x03 => Catch::AllRef(decode_labelidx(reader)),
+            _ => panic!("invalid on decode_catch"),
+        }
+    } else {
+        panic!("invalid on decode_catch");
+    }
+}
+
 fn decode_memarg(reader: &mut impl Read) -> MemArg {
-    let align = decode_u32_from_leb128(reader);
-    let offset = decode_u32_from_leb128(reader);
-    MemArg { align: align, offset: offset }
-}
\ No newline at end of file
+    let flags = decode_u32_from_leb128(reader);
+    // bit 6 of the alignment flags marks an explicit memory index
+    let (align, memory) = if flags & 0x40 != 0 {
+        (flags & !0x40, decode_memidx(reader))
+    } else {
+        (flags, 0)
+    };
+    // u64 for 64-bit memories, validated against the index type
+    let offset = decode_u64_from_leb128(reader);
+    MemArg { align, offset, memory }
+}
+
+fn decode_laneidx(reader: &mut impl Read) -> LaneIdx {
+    if let Some(Ok(b)) = reader.bytes().next() {
+        b
+    } else {
+        panic!("invalid on decode_laneidx");
+    }
+}
+
+fn decode_laneindices(reader: &mut impl Read) -> [LaneIdx; 16] {
+    let mut laneidx = [0; 16];
+    if reader.read_exact(&mut laneidx).is_err() {
+        panic!("invalid on decode_laneindices");
+    }
+    laneidx
+}
+
+fn decode_v128(reader: &mut impl Read) -> u128 {
+    let mut bytes = [0; 16];
+    if reader.read_exact(&mut bytes).is_err() {
+        panic!("invalid on decode_v128");
+    }
+    u128::from_le_bytes(bytes)
+}
+
+fn decode_f32(reader: &mut impl Read) -> f32 {
+    let mut bytes = [0; 4];
+    if reader.read_exact(&mut bytes).is_err() {
+        panic!("invalid on decode_f32");
+    }
+    f32::from_bits(u32::from_le_bytes(bytes))
+}
+
+fn decode_f64(reader: &mut impl Read) -> f64 {
+    let mut bytes = [0; 8];
+    if reader.read_exact(&mut bytes).is_err() {
+        panic!("invalid on decode_f64");
+    }
+    f64::from_bits(u64::from_le_bytes(bytes))
+}
+#[test]
+fn test_decode_blocktype() {
+    let data: [u8; 1] = [0x40];
+    assert_eq!(decode_blocktype(&mut data.as_ref()), BlockType::ValType(None));
+    let data: [u8; 1] = [0x7E];
+    assert_eq!(decode_blocktype(&mut data.as_ref()), BlockType::ValType(Some(ValType::I64)));
+    let data: [u8; 2] = [0xC0, 0x00];
+    assert_eq!(decode_blocktype(&mut data.as_ref()), BlockType::TypeIdx(64));
+}
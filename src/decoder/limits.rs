@@ -5,36 +5,66 @@ use crate::{
 };
 use super::{
     decode_u32_from_leb128,
+    decode_u64_from_leb128,
 };
 
 impl Limits {
-    pub fn new1(min: u32) -> Limits {
-        Limits { min: min, max: None }
+    pub fn new1(min: u64) -> Limits {
+        Limits { min: min, max: None, page_size_log2: None }
     }
-    pub fn new2(min: u32, max: u32) -> Limits {
-        Limits { min: min, max: Some(max) }
+    pub fn new2(min: u64, max: u64) -> Limits {
+        Limits { min: min, max: Some(max), page_size_log2: None }
     }
 }
 
 pub(super) fn decode_limits(reader: &mut impl Read) -> Limits {
-    if let Some(Ok(byte)) = reader.bytes().next() {
-        match byte {
-            0x00 => {
-                // only min
-                let min_size = decode_u32_from_leb128(reader);  // min
-                Limits::new1(min_size)
-            },  
-            0x01 => {
-                // min and max
-                let min_size = decode_u32_from_leb128(reader);  // min
-                let max_size = decode_u32_from_leb128(reader);  // max
-                Limits::new2(min_size, max_size)
-            }, 
-            _ => panic!("invalid on decode_limits"),
-        }
+    // tables have no page size
+    if let Some(Ok(byte @ (0x00 | 0x01))) = reader.bytes().next() {
+        decode_limits_with_flag(byte, reader)
     } else {
         panic!("invalid on decode_limits");
     }
 }
 
+// the flag is already read, 0x01 bit tells whether max is present
+// and 0x08 bit whether the page size exponent follows
+pub(super) fn decode_limits_with_flag(flag: u8, reader: &mut impl Read) -> Limits {
+    let limits = match flag & !0x08 {
+        0x00 => {
+            // only min
+            let min_size = decode_u32_from_leb128(reader);  // min
+            Limits::new1(min_size as u64)
+        },  
+        0x01 => {
+            // min and max
+            let min_size = decode_u32_from_leb128(reader);  // min
+            let max_size = decode_u32_from_leb128(reader);  // max
+            Limits::new2(min_size as u64, max_size as u64)
+        }, 
+        _ => panic!("invalid on decode_limits"),
+    };
+    decode_page_size(flag, limits, reader)
+}
+
+// limits of 64-bit memories, with the same flag bits
+pub(super) fn decode_limits64_with_flag(flag: u8, reader: &mut impl Read) -> Limits {
+    let limits = match flag & !0x08 {
+        0x00 => Limits::new1(decode_u64_from_leb128(reader)),
+        0x01 => {
+            let min_size = decode_u64_from_leb128(reader);  // min
+            let max_size = decode_u64_from_leb128(reader);  // max
+            Limits::new2(min_size, max_size)
+        },
+        _ => panic!("invalid on decode_limits64"),
+    };
+    decode_page_size(flag, limits, reader)
+}
+
+fn decode_page_size(flag: u8, mut limits: Limits, reader: &mut impl Read) -> Limits {
+    if flag & 0x08 != 0 {
+        limits.page_size_log2 = Some(decode_u32_from_leb128(reader));
+    }
+    limits
+}
+
 
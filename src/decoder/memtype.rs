@@ -1,13 +1,32 @@
 use std::io::Read;
 use crate::{
     MemType,
+    Share,
+    IdxType,
 };
 
 use super::limits::{
-    decode_limits
+    decode_limits_with_flag,
+    decode_limits64_with_flag,
 };
 
 
+// the flag is 0x01 for max, 0x02 for shared and 0x04 for i64 addresses
 pub(super) fn decode_memtype(reader: &mut impl Read) -> MemType {
-    MemType(decode_limits(reader))
+    if let Some(Ok(flag)) = reader.bytes().next() {
+        let share = match flag & 0x03 {
+            0x00 | 0x01 => Share::Unshared,
+            // shared memories have max
+            0x03 => Share::Shared,
+            _ => panic!("invalid on decode_memtype"),
+        };
+        // 0x08 bit tells whether the page size is custom
+        match flag & !0x0A {
+            0x00 | 0x01 => MemType(decode_limits_with_flag(flag & 0x09, reader), share, IdxType::I32),
+            0x04 | 0x05 => MemType(decode_limits64_with_flag(flag & 0x09, reader), share, IdxType::I64),
+            _ => panic!("invalid on decode_memtype"),
+        }
+    } else {
+        panic!("invalid on decode_memtype");
+    }
 }
\ No newline at end of file
@@ -1,20 +1,51 @@
-use std::io::Read;
+use std::io::{self, Read};
 
 use crate::{
     Table
 };
-use super::{decode_u32_from_leb128, decode_vec};
+use super::{decode_u32_from_leb128, try_decode_vec, decode_byte, invalid_data};
 use super::tabletype::{decode_tabletype};
+use super::expr::{decode_expr};
 
 
-pub(super) fn decode_tablesec(reader: &mut impl Read) -> Vec<Table> {
+pub(super) fn decode_tablesec(reader: &mut impl Read) -> io::Result<Vec<Table>> {
     // prefixはsection number 4
     let length = decode_u32_from_leb128(reader);
     let mut handle = reader.take(length as u64);
-    decode_vec(&mut handle, decode_table)
+    try_decode_vec(&mut handle, decode_table)
 }
 
-fn decode_table(reader: &mut impl Read) -> Table {
-    let tabletype = decode_tabletype(reader);
-    Table(tabletype)
-}
\ No newline at end of file
+// 0x40 0x00 prefixes a table with an initializer
+fn decode_table(reader: &mut impl Read) -> io::Result<Table> {
+    let b = decode_byte(reader)?;
+    if b == 0x40 {
+        if decode_byte(reader)? != 0x00 {
+            return Err(invalid_data("invalid table".to_string()));
+        }
+        let tabletype = decode_tabletype(reader)?;
+        return Ok(Table(tabletype, Some(decode_expr(reader))));
+    }
+    let first = [b];
+    let tabletype = decode_tabletype(&mut first.as_ref().chain(reader))?;
+    Ok(Table(tabletype, None))
+}
+
+#[test]
+fn test_decode_table() {
+    use crate::{Limits, RefType, HeapType, Expr, Instr};
+
+    // (table 1 (ref null 0))
+    let table = decode_table(&mut [0x63, 0x00, 0x00, 0x01].as_ref()).unwrap();
+    assert!(table.0 == crate::TableType(Limits::new1(1), RefType { nullable: true, heap: HeapType::Type(0) }));
+    assert!(table.1.is_none());
+    // (table 1 anyref)
+    let table = decode_table(&mut [0x6E, 0x00, 0x01].as_ref()).unwrap();
+    assert_eq!(table.0.1, RefType { nullable: true, heap: HeapType::Any });
+    // (table 1 (ref func) (ref.func 0))
+    let table = decode_table(&mut [0x40, 0x00, 0x64, 0x70, 0x00, 0x01, 0xD2, 0x00, 0x0B].as_ref()).unwrap();
+    assert_eq!(table.0.1, RefType { nullable: false, heap: HeapType::Func });
+    assert!(table.1 == Some(Expr(vec![Instr::RefFunc(0)])));
+
+    assert!(decode_table(&mut [0x7F, 0x00, 0x01].as_ref()).is_err());
+    assert!(decode_table(&mut [0x40, 0x01, 0x70, 0x00, 0x01].as_ref()).is_err());
+}
@@ -1,15 +1,14 @@
-use std::io::Read;
+use std::io::{self, Read};
 
 use crate::{
     TableType,
 };
 use super::limits::{decode_limits};
-use super::elemtype::{decode_elemtype};
+use super::valtype::{decode_reftype};
 
 
-pub(super) fn decode_tabletype(reader: &mut impl Read) -> TableType {
+pub(super) fn decode_tabletype(reader: &mut impl Read) -> io::Result<TableType> {
+    let reftype = decode_reftype(reader)?;
     let limits = decode_limits(reader);
-    let elem_type = decode_elemtype(reader);
-    TableType(limits, elem_type)
+    Ok(TableType(limits, reftype))
 }
-
@@ -0,0 +1,28 @@
+use std::io::Read;
+
+use crate::{
+    Tag,
+    TypeIdx,
+};
+use super::{decode_u32_from_leb128, decode_vec};
+use super::idx::decode_typeidx;
+
+pub(super) fn decode_tagsec(reader: &mut impl Read) -> Vec<Tag> {
+    // prefixはsection number 13
+    let length = decode_u32_from_leb128(reader);
+    let mut handle = reader.take(length as u64);
+    decode_vec(&mut handle, decode_tag)
+}
+
+fn decode_tag(reader: &mut impl Read) -> Tag {
+    Tag(decode_tagtype(reader))
+}
+
+pub(super) fn decode_tagtype(reader: &mut impl Read) -> TypeIdx {
+    // the attribute 0x00 is exception (the only one for now)
+    if let Some(Ok(0x00)) = reader.bytes().next() {
+        decode_typeidx(reader)
+    } else {
+        panic!("invalid on decode_tagtype");
+    }
+}
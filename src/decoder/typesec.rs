@@ -1,18 +1,78 @@
 use std::io::Read;
 
 use crate::{
-    FuncType,
-
+    SubType,
+    CompType,
+    FieldType,
+    StorageType,
+    Byte,
 };
+use super::globaltype::{decode_mut};
 use super::{
     decode_functype,
+    byte_to_valtype,
     decode_u32_from_leb128, 
     decode_vec,
 };
 
-pub(super) fn decode_typesec(reader: &mut impl Read) -> Vec<FuncType> {
+pub(super) fn decode_typesec(reader: &mut impl Read) -> Vec<SubType> {
     // prefixはsection number 1
     let length = decode_u32_from_leb128(reader);
     let mut handle = reader.take(length as u64);
-    decode_vec(&mut handle, decode_functype)
+    decode_vec(&mut handle, decode_rectype).concat()
+}
+
+fn decode_rectype(reader: &mut impl Read) -> Vec<SubType> {
+    match decode_byte(reader) {
+        0x4E => {
+            let mut subtypes = decode_vec(reader, decode_subtype);
+            let len = subtypes.len() as u32;
+            for (pos, subtype) in subtypes.iter_mut().enumerate() {
+                subtype.rec = (pos as u32, len);
+            }
+            subtypes
+        },
+        b => vec![byte_to_subtype(b, reader)],
+    }
+}
+
+fn decode_subtype(reader: &mut impl Read) -> SubType {
+    let b = decode_byte(reader);
+    byte_to_subtype(b, reader)
+}
+
+fn byte_to_subtype(b: Byte, reader: &mut impl Read) -> SubType {
+    let (is_final, supers) = match b {
+        0x50 | 0x4F => (b == 0x4F, decode_vec(reader, decode_u32_from_leb128)),
+        // a lone composite type is final
+        _ => return SubType { is_final: true, supers: vec![], comp: byte_to_comptype(b, reader), rec: (0, 1) },
+    };
+    let b = decode_byte(reader);
+    SubType { is_final, supers, comp: byte_to_comptype(b, reader), rec: (0, 1) }
+}
+
+fn byte_to_comptype(b: Byte, reader: &mut impl Read) -> CompType {
+    match b {
+        0x60 => CompType::Func(decode_functype(reader)),
+        0x5F => CompType::Struct(decode_vec(reader, decode_fieldtype)),
+        0x5E => CompType::Array(decode_fieldtype(reader)),
+        _ => panic!("invalid on decode_comptype: {:x?}", b),
+    }
+}
+
+fn decode_fieldtype(reader: &mut impl Read) -> FieldType {
+    let storagetype = match decode_byte(reader) {
+        0x78 => StorageType::I8,
+        0x77 => StorageType::I16,
+        b => StorageType::Val(byte_to_valtype(b, reader)),
+    };
+    FieldType(storagetype, decode_mut(reader))
+}
+
+fn decode_byte(reader: &mut impl Read) -> Byte {
+    if let Some(Ok(b)) = reader.bytes().next() {
+        b
+    } else {
+        panic!("invalid on decode_byte");
+    }
 }
@@ -1,10 +1,26 @@
-use std::io::Read;
+use std::io::{self, Read};
 
 pub fn decode_vec<T: Read, R>(reader: &mut T, f: fn(reader: &mut T) -> R) -> Vec<R> {
     let length = decode_u32_from_leb128(reader);
     (0..length).map(|_| f(reader)).collect()
 }
 
+// stops at the first element that fails to decode
+pub fn try_decode_vec<T: Read, R>(reader: &mut T, f: fn(reader: &mut T) -> io::Result<R>) -> io::Result<Vec<R>> {
+    let length = decode_u32_from_leb128(reader);
+    (0..length).map(|_| f(reader)).collect()
+}
+
+pub fn decode_byte(reader: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+pub fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
 pub fn decode_u32_from_leb128(reader: &mut impl Read) -> u32 {
     let mut acc: u32 = 0;
     let mut count: u8 = 0;
@@ -22,6 +38,40 @@ pub fn decode_u32_from_leb128(reader: &mut impl Read) -> u32 {
     acc
 }
 
+pub fn decode_u64_from_leb128(reader: &mut impl Read) -> u64 {
+    let mut acc: u64 = 0;
+    let mut shift: u32 = 0;
+    let mut buf = [0u8; 1];
+    while reader.read_exact(&mut buf).is_ok() {
+        let b = buf[0];
+        if shift < 64 {
+            acc |= ((b & 0b01111111) as u64) << shift;
+        }
+        shift += 7;
+        if b < 0b10000000 { break; }
+    }
+    acc
+}
+
+pub fn decode_i64_from_leb128(reader: &mut impl Read) -> i64 {
+    let mut acc: i64 = 0;
+    let mut shift: u32 = 0;
+    let mut buf = [0u8; 1];
+    while reader.read_exact(&mut buf).is_ok() {
+        let b = buf[0];
+        acc |= ((b & 0b01111111) as i64) << shift;
+        shift += 7;
+        if b < 0b10000000 {
+            // sign extend
+            if shift < 64 && b & 0b01000000 != 0 {
+                acc |= -1 << shift;
+            }
+            break;
+        }
+    }
+    acc
+}
+
 #[test]
 pub fn test_decode_u32_from_leb128() {
     use std::io::BufReader;
@@ -32,4 +82,22 @@ pub fn test_decode_u32_from_leb128() {
     let mut reader = BufReader::new(data.as_ref());
     let res = decode_u32_from_leb128(&mut reader);
     println!("{:x?}", res);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_decode_u64_from_leb128() {
+    let data: [u8; 3] = [0xE5, 0x8E, 0x26];
+    assert_eq!(decode_u64_from_leb128(&mut data.as_ref()), 624485);
+    let data: [u8; 10] = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01];
+    assert_eq!(decode_u64_from_leb128(&mut data.as_ref()), u64::MAX);
+}
+
+#[test]
+fn test_decode_i64_from_leb128() {
+    let data: [u8; 1] = [0x40];
+    assert_eq!(decode_i64_from_leb128(&mut data.as_ref()), -64);
+    let data: [u8; 2] = [0xC0, 0x00];
+    assert_eq!(decode_i64_from_leb128(&mut data.as_ref()), 64);
+    let data: [u8; 3] = [0xE5, 0x8E, 0x26];
+    assert_eq!(decode_i64_from_leb128(&mut data.as_ref()), 624485);
+}
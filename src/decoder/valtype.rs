@@ -1,24 +1,91 @@
-use std::io::Read;
+use std::io::{self, Read};
 use crate::{
     ValType,
+    RefType,
+    HeapType,
     Byte,
 };
+use super::{decode_i64_from_leb128, decode_byte, invalid_data};
 
-pub(super) fn byte_to_valtype(b :Byte) -> ValType {
+// typed references read their heap type after the first byte
+pub(super) fn byte_to_valtype(b :Byte, reader: &mut impl Read) -> ValType {
     match b {
         0x7F => ValType::I32,
         0x7E => ValType::I64,
         0x7D => ValType::F32,
         0x7C => ValType::F64,
-        _ => panic!("invalid on byte_to_valtype: {:x?}", b),
+        0x7B => ValType::V128,
+        _ => match byte_to_reftype(b, reader) {
+            Ok(reftype) => reftype.valtype(),
+            Err(_) => panic!("invalid on byte_to_valtype: {:x?}", b),
+        },
+    }
+}
+
+pub(super) fn decode_reftype(reader: &mut impl Read) -> io::Result<RefType> {
+    let b = decode_byte(reader)?;
+    byte_to_reftype(b, reader)
+}
+
+// the abstract types have shorthands for their nullable references
+fn byte_to_reftype(b: Byte, reader: &mut impl Read) -> io::Result<RefType> {
+    match b {
+        0x63 => Ok(RefType { nullable: true, heap: read_heaptype(reader)? }),
+        0x64 => Ok(RefType { nullable: false, heap: read_heaptype(reader)? }),
+        _ => match byte_to_abstract_heaptype(b) {
+            Some(heap) => Ok(RefType { nullable: true, heap }),
+            None => Err(invalid_data(format!("invalid reference type: {:x?}", b))),
+        },
     }
 }
 
 pub(super) fn decode_valtype(reader: &mut impl Read) -> ValType {
     let byte = reader.bytes().next();
     if let Some(Ok(b)) = byte {
-        byte_to_valtype(b)
+        byte_to_valtype(b, reader)
     } else {
         panic!("invalid on read_valtype");
     }
 }
+
+fn byte_to_abstract_heaptype(b: Byte) -> Option<HeapType> {
+    match b {
+        0x70 => Some(HeapType::Func),
+        0x6F => Some(HeapType::Extern),
+        0x69 => Some(HeapType::Exn),
+        0x6E => Some(HeapType::Any),
+        0x6D => Some(HeapType::Eq),
+        0x6C => Some(HeapType::I31),
+        0x6B => Some(HeapType::Struct),
+        0x6A => Some(HeapType::Array),
+        0x71 => Some(HeapType::None),
+        0x73 => Some(HeapType::NoFunc),
+        0x72 => Some(HeapType::NoExtern),
+        0x74 => Some(HeapType::NoExn),
+        _ => None,
+    }
+}
+
+pub(super) fn decode_heaptype(reader: &mut impl Read) -> HeapType {
+    match read_heaptype(reader) {
+        Ok(heaptype) => heaptype,
+        Err(_) => panic!("invalid on decode_heaptype"),
+    }
+}
+
+fn read_heaptype(reader: &mut impl Read) -> io::Result<HeapType> {
+    let b = decode_byte(reader)?;
+    match byte_to_abstract_heaptype(b) {
+        Some(heaptype) => Ok(heaptype),
+        None => {
+            // typeidx as s33, the first byte is already read
+            let first = [b];
+            let mut chained = first.as_ref().chain(reader);
+            let typeidx = decode_i64_from_leb128(&mut chained);
+            if typeidx < 0 || typeidx > u32::MAX as i64 {
+                return Err(invalid_data(format!("invalid heap type: {}", typeidx)));
+            }
+            Ok(HeapType::Type(typeidx as u32))
+        },
+    }
+}
@@ -14,7 +14,7 @@ pub fn module_encode(module: &Module) -> std::io::Result<()> {
     Ok(())
 }
 
-fn module2wasm(module: &Module) -> Vec<Byte> {
+pub(crate) fn module2wasm(module: &Module) -> Vec<Byte> {
     [
         b"\0asm".to_vec(),
         vec![0x01, 0x00, 0x00, 0x00],
@@ -23,18 +23,31 @@ fn module2wasm(module: &Module) -> Vec<Byte> {
         funcsection2wasm(&module.funcs),
         tablesection2wasm(&module.tables),
         memorysection2wasm(&module.mems),
+        tagsection2wasm(&module.tags),
         globalsection2wasm(&module.globals),
         exportsection2wasm(&module.exports),
         startsection2wasm(&module.start),
         elementsection2wasm(&module.elem),
+        datacountsection2wasm(&module.datacount),
         codesection2wasm(&module.funcs),
         datasection2wasm(&module.data),
     ]
     .concat()
 }
 
-fn typesection2wasm(types: &Vec<FuncType>) -> Vec<Byte> {
-    section2wasm(1, vector2wasm(types.iter().map(functype2wasm).collect()))
+fn typesection2wasm(types: &Vec<SubType>) -> Vec<Byte> {
+    // a recursion group is written at its first type
+    let rectypes = types.iter().enumerate()
+        .filter(|(_, subtype)| subtype.rec.0 == 0)
+        .map(|(i, subtype)| match subtype.rec.1 {
+            1 => subtype2wasm(subtype),
+            len => [
+                vec![0x4E],
+                vector2wasm(types[i..i + len as usize].iter().map(subtype2wasm).collect()),
+            ].concat(),
+        })
+        .collect();
+    section2wasm(1, vector2wasm(rectypes))
 }
 
 fn importsection2wasm(imps: &Vec<Import>) -> Vec<Byte> {
@@ -54,6 +67,10 @@ fn memorysection2wasm(mems: &Vec<Mem>) -> Vec<Byte> {
     section2wasm(5, vector2wasm(mems.iter().map(mem2wasm).collect()))
 }
 
+fn tagsection2wasm(tags: &Vec<Tag>) -> Vec<Byte> {
+    section2wasm(13, vector2wasm(tags.iter().map(tag2wasm).collect()))
+}
+
 fn globalsection2wasm(globals: &Vec<Global>) -> Vec<Byte> {
     section2wasm(6, vector2wasm(globals.iter().map(global2wasm).collect()))
 }
@@ -74,6 +91,14 @@ fn elementsection2wasm(elems: &Vec<Elem>) -> Vec<Byte> {
     section2wasm(9, vector2wasm(elems.iter().map(elem2wasm).collect())) 
 }
 
+fn datacountsection2wasm(datacount: &Option<u32>) -> Vec<Byte> {
+    if let Some(n) = datacount {
+        section2wasm(12, unsigned32_to_wasm(*n))
+    } else {
+        vec![]
+    }
+}
+
 fn codesection2wasm(funcs: &Vec<Func>) -> Vec<Byte> {
     section2wasm(10, vector2wasm(funcs.iter().map(code2wasm).collect())) 
 }
@@ -97,13 +122,23 @@ fn importdesc2wasm(desc: &ImportDesc) -> Vec<Byte> {
         ImportDesc::Table(tt) =>  [vec![0x01], tabletype2wasm(tt)].concat(),
         ImportDesc::Mem(mt) =>    [vec![0x02], memtype2wasm(mt)].concat(),
         ImportDesc::Global(gt) => [vec![0x03], globaltype2wasm(gt)].concat(),
+        ImportDesc::Tag(idx) =>   [vec![0x04, 0x00], typeidx2wasm(idx)].concat(),
     }
 }
 
-fn table2wasm(t: &Table) -> Vec<Byte> { tabletype2wasm(&t.0) }
+// tables with an initializer are prefixed by 0x40 0x00
+fn table2wasm(t: &Table) -> Vec<Byte> {
+    match &t.1 {
+        Some(init) => [vec![0x40, 0x00], tabletype2wasm(&t.0), expr2wasm(init)].concat(),
+        None => tabletype2wasm(&t.0),
+    }
+}
 
 fn mem2wasm(mem: &Mem) -> Vec<Byte> { memtype2wasm(&mem.0) }
 
+// the attribute byte is always 0, for exceptions
+fn tag2wasm(tag: &Tag) -> Vec<Byte> { [vec![0x00], typeidx2wasm(&tag.0)].concat() }
+
 fn global2wasm(global: &Global) -> Vec<Byte> {
     [
         globaltype2wasm(&global.tp),
@@ -126,17 +161,36 @@ fn exportdesc2wasm(desc: &ExportDesc) -> Vec<Byte> {
         ExportDesc::Table(idx) =>  [vec![0x01], tableidx2wasm(idx)].concat(),
         ExportDesc::Mem(idx) =>    [vec![0x02], memidx2wasm(idx)].concat(),
         ExportDesc::Global(idx) => [vec![0x03], globalidx2wasm(idx)].concat(),
+        ExportDesc::Tag(idx) =>    [vec![0x04], tagidx2wasm(idx)].concat(),
     }
 }
 
 fn elem2wasm(elem: &Elem) -> Vec<Byte> {
-    [
-        tableidx2wasm(&elem.table),
-        expr2wasm(&elem.offset),
-        bytevector2wasm(elem.init.iter().map(funcidx2wasm)
-            .collect::<Vec<Vec<Byte>>>().concat()),
-    ]
-    .concat()
+    let funcindices: Option<Vec<Vec<Byte>>> = elem.init.iter()
+        .map(|expr| match expr.0.as_slice() {
+            [Instr::RefFunc(funcidx)] if elem.tp.valtype() == ValType::FuncRef => Some(funcidx2wasm(funcidx)),
+            _ => None,
+        })
+        .collect();
+    // segments of plain function indices take the flags 0 to 3, with the elemkind 0x00(funcref)
+    let (flags, tp, init) = match funcindices {
+        Some(funcindices) => (0b000, vec![0x00], vector2wasm(funcindices)),
+        None => (0b100, reftype2wasm(&elem.tp), vector2wasm(elem.init.iter().map(expr2wasm).collect())),
+    };
+    match &elem.mode {
+        ElemMode::Active { table: 0, offset } if elem.tp.valtype() == ValType::FuncRef => [
+            unsigned32_to_wasm(flags), expr2wasm(offset), init,
+        ].concat(),
+        ElemMode::Passive => [
+            unsigned32_to_wasm(flags | 0b001), tp, init,
+        ].concat(),
+        ElemMode::Active { table, offset } => [
+            unsigned32_to_wasm(flags | 0b010), tableidx2wasm(table), expr2wasm(offset), tp, init,
+        ].concat(),
+        ElemMode::Declarative => [
+            unsigned32_to_wasm(flags | 0b011), tp, init,
+        ].concat(),
+    }
 }
 
 fn code2wasm(func: &Func) -> Vec<Byte> {
@@ -150,8 +204,7 @@ fn code2wasm(func: &Func) -> Vec<Byte> {
 
 fn func2wasm(func: &Func) -> Vec<Byte> {
     [
-        func.locals.iter().map(local2wasm)
-            .collect::<Vec<Vec<Byte>>>().concat(),
+        vector2wasm(func.locals.iter().map(local2wasm).collect()),
         expr2wasm(&func.body),
     ]
     .concat()
@@ -159,24 +212,28 @@ fn func2wasm(func: &Func) -> Vec<Byte> {
 
 fn local2wasm(local: &ValType) -> Vec<Byte> {
     [
-        0x01,  // TODO: to be compressed
+        vec![0x01],  // TODO: to be compressed
         valtype2wasm(local),
     ]
-    .to_vec()
+    .concat()
 }
 
 fn data2wasm(data: &Data) -> Vec<Byte> {
-    [
-        memidx2wasm(&data.data),
-        expr2wasm(&data.offset),
-        datastring2wasm(&data.init),
-    ]
-    .concat()
+    match &data.mode {
+        DataMode::Active { memory: 0, offset } => [
+            vec![0x00], expr2wasm(offset), datastring2wasm(&data.init),
+        ].concat(),
+        DataMode::Passive => [
+            vec![0x01], datastring2wasm(&data.init),
+        ].concat(),
+        DataMode::Active { memory, offset } => [
+            vec![0x02], memidx2wasm(memory), expr2wasm(offset), datastring2wasm(&data.init),
+        ].concat(),
+    }
 }
 
-fn datastring2wasm(_ds: &Vec<Byte>) -> Vec<Byte> {
-    // string2wasm(ds)
-    unimplemented!()
+fn datastring2wasm(ds: &[Byte]) -> Vec<Byte> {
+    bytevector2wasm(ds.to_vec())
 }
 
 fn section2wasm(id: Byte, cont: Vec<Byte>) -> Vec<Byte> {
@@ -195,12 +252,15 @@ fn memidx2wasm(idx: &MemIdx) -> Vec<Byte> { unsigned32_to_wasm(*idx) }
 fn globalidx2wasm(idx: &GlobalIdx) -> Vec<Byte> { unsigned32_to_wasm(*idx) }
 fn localidx2wasm(idx: &LocalIdx) -> Vec<Byte> { unsigned32_to_wasm(*idx) }
 fn labelidx2wasm(idx: &LabelIdx) -> Vec<Byte> { unsigned32_to_wasm(*idx) }
+fn elemidx2wasm(idx: &ElemIdx) -> Vec<Byte> { unsigned32_to_wasm(*idx) }
+fn dataidx2wasm(idx: &DataIdx) -> Vec<Byte> { unsigned32_to_wasm(*idx) }
+fn tagidx2wasm(idx: &TagIdx) -> Vec<Byte> { unsigned32_to_wasm(*idx) }
 
 fn expr2wasm(expr: &Expr) -> Vec<Byte> {
     instrs2wasm(&expr.0)
 }
 
-fn instrs2wasm(instrs: &Vec<Instr>) -> Vec<Byte> {
+fn instrs2wasm(instrs: &[Instr]) -> Vec<Byte> {
     [
         instrs.iter().map(instr2wasm).collect::<Vec<Vec<Byte>>>().concat(),
         vec![0x0B],
@@ -213,19 +273,24 @@ fn instr2wasm(instr: &Instr) -> Vec<Byte> {
         Instr::Unreachable => vec![0x00],
         Instr::Nop => vec![0x01],
         Instr::Block(rt, instrs) => [
-            vec![0x02], blocktype2wasm(rt), instrs2wasm(instrs), vec![0x0B]
+            vec![0x02], blocktype2wasm(rt), instrs2wasm(instrs)
         ].concat(),
         Instr::Loop(rt, instrs) => [
-            vec![0x03], blocktype2wasm(rt), instrs2wasm(instrs), vec![0x0B]
+            vec![0x03], blocktype2wasm(rt), instrs2wasm(instrs)
         ].concat(),
         Instr::If(rt, instrs1, instrs2) => {
-            let true_term = [vec![0x04], blocktype2wasm(rt), instrs2wasm(instrs1)].concat();
+            let mut true_term = [vec![0x04], blocktype2wasm(rt), instrs2wasm(instrs1)].concat();
             if let Some(instr2) = instrs2 {
-                [true_term, vec![0x05], instrs2wasm(instr2), vec![0x0B]].concat()
+                // else replaces the end of the true branch
+                true_term.pop();
+                [true_term, vec![0x05], instrs2wasm(instr2)].concat()
             } else {
-                [true_term, vec![0x0B]].concat()
+                true_term
             }
         },
+        Instr::TryTable(rt, catches, instrs) => [
+            vec![0x1F], blocktype2wasm(rt), vector2wasm(catches.iter().map(catch2wasm).collect()), instrs2wasm(instrs)
+        ].concat(),
         Instr::Br(labelidx) => [vec![0x0C], labelidx2wasm(labelidx)].concat(),
         Instr::BrIf(labelidx) => [vec![0x0D], labelidx2wasm(labelidx)].concat(),
         Instr::BrTable(indices, idx) => [
@@ -235,10 +300,53 @@ fn instr2wasm(instr: &Instr) -> Vec<Byte> {
         ].concat(),
         Instr::Return => vec![0x0F],
         Instr::Call(funcidx) => [vec![0x10], funcidx2wasm(funcidx)].concat(),
-        Instr::CallIndirect(typeidx) => [vec![0x11], typeidx2wasm(typeidx), vec![0x00]].concat(),
+        Instr::CallIndirect(tableidx, typeidx) => [vec![0x11], typeidx2wasm(typeidx), tableidx2wasm(tableidx)].concat(),
+        Instr::ReturnCall(funcidx) => [vec![0x12], funcidx2wasm(funcidx)].concat(),
+        Instr::ReturnCallIndirect(tableidx, typeidx) => [vec![0x13], typeidx2wasm(typeidx), tableidx2wasm(tableidx)].concat(),
+        Instr::CallRef(typeidx) => [vec![0x14], typeidx2wasm(typeidx)].concat(),
+        Instr::ReturnCallRef(typeidx) => [vec![0x15], typeidx2wasm(typeidx)].concat(),
+        Instr::Throw(tagidx) => [vec![0x08], tagidx2wasm(tagidx)].concat(),
+        Instr::ThrowRef => vec![0x0A],
+
+        Instr::RefNull(heaptype) => [vec![0xD0], heaptype2wasm(heaptype)].concat(),
+        Instr::RefIsNull => vec![0xD1],
+        Instr::RefFunc(funcidx) => [vec![0xD2], funcidx2wasm(funcidx)].concat(),
+        Instr::RefAsNonNull => vec![0xD4],
+        Instr::BrOnNull(labelidx) => [vec![0xD5], labelidx2wasm(labelidx)].concat(),
+        Instr::BrOnNonNull(labelidx) => [vec![0xD6], labelidx2wasm(labelidx)].concat(),
+        Instr::RefEq => vec![0xD3],
+        Instr::RefTest(rt) => [vec![0xFB], unsigned32_to_wasm(if rt.nullable { 21 } else { 20 }), heaptype2wasm(&rt.heap)].concat(),
+        Instr::RefCast(rt) => [vec![0xFB], unsigned32_to_wasm(if rt.nullable { 23 } else { 22 }), heaptype2wasm(&rt.heap)].concat(),
+        Instr::BrOnCast(labelidx, rt1, rt2) => [vec![0xFB], unsigned32_to_wasm(24), cast2wasm(labelidx, rt1, rt2)].concat(),
+        Instr::BrOnCastFail(labelidx, rt1, rt2) => [vec![0xFB], unsigned32_to_wasm(25), cast2wasm(labelidx, rt1, rt2)].concat(),
+
+        Instr::StructNew(typeidx) => [vec![0xFB], unsigned32_to_wasm(0), typeidx2wasm(typeidx)].concat(),
+        Instr::StructNewDefault(typeidx) => [vec![0xFB], unsigned32_to_wasm(1), typeidx2wasm(typeidx)].concat(),
+        Instr::StructGet(sign, typeidx, fieldidx) => [
+            vec![0xFB], unsigned32_to_wasm(2 + field_sign2wasm(sign)), typeidx2wasm(typeidx), unsigned32_to_wasm(*fieldidx),
+        ].concat(),
+        Instr::StructSet(typeidx, fieldidx) => [vec![0xFB], unsigned32_to_wasm(5), typeidx2wasm(typeidx), unsigned32_to_wasm(*fieldidx)].concat(),
+        Instr::ArrayNew(typeidx) => [vec![0xFB], unsigned32_to_wasm(6), typeidx2wasm(typeidx)].concat(),
+        Instr::ArrayNewDefault(typeidx) => [vec![0xFB], unsigned32_to_wasm(7), typeidx2wasm(typeidx)].concat(),
+        Instr::ArrayNewFixed(typeidx, n) => [vec![0xFB], unsigned32_to_wasm(8), typeidx2wasm(typeidx), unsigned32_to_wasm(*n)].concat(),
+        Instr::ArrayGet(sign, typeidx) => [vec![0xFB], unsigned32_to_wasm(11 + field_sign2wasm(sign)), typeidx2wasm(typeidx)].concat(),
+        Instr::ArraySet(typeidx) => [vec![0xFB], unsigned32_to_wasm(14), typeidx2wasm(typeidx)].concat(),
+        Instr::ArrayLen => [vec![0xFB], unsigned32_to_wasm(15)].concat(),
+        Instr::ArrayFill(typeidx) => [vec![0xFB], unsigned32_to_wasm(16), typeidx2wasm(typeidx)].concat(),
+        Instr::ArrayCopy(typeidx1, typeidx2) => [vec![0xFB], unsigned32_to_wasm(17), typeidx2wasm(typeidx1), typeidx2wasm(typeidx2)].concat(),
+        Instr::ArrayNewData(typeidx, dataidx) => [vec![0xFB], unsigned32_to_wasm(9), typeidx2wasm(typeidx), dataidx2wasm(dataidx)].concat(),
+        Instr::ArrayNewElem(typeidx, elemidx) => [vec![0xFB], unsigned32_to_wasm(10), typeidx2wasm(typeidx), elemidx2wasm(elemidx)].concat(),
+        Instr::ArrayInitData(typeidx, dataidx) => [vec![0xFB], unsigned32_to_wasm(18), typeidx2wasm(typeidx), dataidx2wasm(dataidx)].concat(),
+        Instr::ArrayInitElem(typeidx, elemidx) => [vec![0xFB], unsigned32_to_wasm(19), typeidx2wasm(typeidx), elemidx2wasm(elemidx)].concat(),
+        Instr::AnyConvertExtern => [vec![0xFB], unsigned32_to_wasm(26)].concat(),
+        Instr::ExternConvertAny => [vec![0xFB], unsigned32_to_wasm(27)].concat(),
+        Instr::RefI31 => [vec![0xFB], unsigned32_to_wasm(28)].concat(),
+        Instr::I31Get(ValSign::S) => [vec![0xFB], unsigned32_to_wasm(29)].concat(),
+        Instr::I31Get(ValSign::U) => [vec![0xFB], unsigned32_to_wasm(30)].concat(),
 
         Instr::Drop(_) => vec![0x1A],
-        Instr::Select(_) => vec![0x1B],
+        Instr::Select(None) => vec![0x1B],
+        Instr::Select(Some(valtype)) => [vec![0x1C, 0x01], valtype2wasm(valtype)].concat(),
 
         Instr::LocalGet(x) => [vec![0x20], localidx2wasm(x)].concat(),
         Instr::LocalSet(x) => [vec![0x21], localidx2wasm(x)].concat(),
@@ -246,6 +354,9 @@ fn instr2wasm(instr: &Instr) -> Vec<Byte> {
         Instr::GlobalGet(x) => [vec![0x23], globalidx2wasm(x)].concat(),
         Instr::GlobalSet(x) => [vec![0x24], globalidx2wasm(x)].concat(),
 
+        Instr::TableGet(x) => [vec![0x25], tableidx2wasm(x)].concat(),
+        Instr::TableSet(x) => [vec![0x26], tableidx2wasm(x)].concat(),
+
         Instr::Load(ValType::I32, memarg) => [vec![0x28], memarg2wasm(memarg)].concat(),
         Instr::Load(ValType::I64, memarg) => [vec![0x29], memarg2wasm(memarg)].concat(),
         Instr::Load(ValType::F32, memarg) => [vec![0x2A], memarg2wasm(memarg)].concat(),
@@ -266,17 +377,31 @@ fn instr2wasm(instr: &Instr) -> Vec<Byte> {
         Instr::Store(ValType::I64, memarg) => [vec![0x37], memarg2wasm(memarg)].concat(),
         Instr::Store(ValType::F32, memarg) => [vec![0x38], memarg2wasm(memarg)].concat(),
         Instr::Store(ValType::F64, memarg) => [vec![0x39], memarg2wasm(memarg)].concat(),
+        Instr::Load(ValType::V128, memarg) => [vec![0xFD], unsigned32_to_wasm(0), memarg2wasm(memarg)].concat(),
+        Instr::Store(ValType::V128, memarg) => [vec![0xFD], unsigned32_to_wasm(11), memarg2wasm(memarg)].concat(),
+        // references are not stored in memory
+        Instr::Load(_, _) | Instr::Store(_, _) => unreachable!(),
 
         Instr::IStore8(ValSize::V32, memarg) => [vec![0x3A], memarg2wasm(memarg)].concat(),
         Instr::IStore16(ValSize::V32, memarg) => [vec![0x3B], memarg2wasm(memarg)].concat(),
         Instr::IStore8(ValSize::V64, memarg) => [vec![0x3C], memarg2wasm(memarg)].concat(),
         Instr::IStore16(ValSize::V64, memarg) => [vec![0x3D], memarg2wasm(memarg)].concat(),
         Instr::I64Store32(memarg) => [vec![0x3E], memarg2wasm(memarg)].concat(),
-        Instr::MemorySize => vec![0x3F, 0x00],
-        Instr::MemoryGrow => vec![0x40, 0x00],
-
-        Instr::I32Const(n) => [vec![0x41], unsigned32_to_wasm(*n)].concat(),
-        Instr::I64Const(n) => [vec![0x42], unsigned64_to_wasm(*n)].concat(),
+        Instr::MemorySize(x) => [vec![0x3F], memidx2wasm(x)].concat(),
+        Instr::MemoryGrow(x) => [vec![0x40], memidx2wasm(x)].concat(),
+        Instr::MemoryInit(x, dataidx) => [vec![0xFC], unsigned32_to_wasm(8), dataidx2wasm(dataidx), memidx2wasm(x)].concat(),
+        Instr::DataDrop(dataidx) => [vec![0xFC], unsigned32_to_wasm(9), dataidx2wasm(dataidx)].concat(),
+        Instr::MemoryCopy(x, y) => [vec![0xFC], unsigned32_to_wasm(10), memidx2wasm(x), memidx2wasm(y)].concat(),
+        Instr::MemoryFill(x) => [vec![0xFC], unsigned32_to_wasm(11), memidx2wasm(x)].concat(),
+        Instr::TableInit(x, elemidx) => [vec![0xFC], unsigned32_to_wasm(12), elemidx2wasm(elemidx), tableidx2wasm(x)].concat(),
+        Instr::ElemDrop(elemidx) => [vec![0xFC], unsigned32_to_wasm(13), elemidx2wasm(elemidx)].concat(),
+        Instr::TableCopy(x, y) => [vec![0xFC], unsigned32_to_wasm(14), tableidx2wasm(x), tableidx2wasm(y)].concat(),
+        Instr::TableGrow(x) => [vec![0xFC], unsigned32_to_wasm(15), tableidx2wasm(x)].concat(),
+        Instr::TableSize(x) => [vec![0xFC], unsigned32_to_wasm(16), tableidx2wasm(x)].concat(),
+        Instr::TableFill(x) => [vec![0xFC], unsigned32_to_wasm(17), tableidx2wasm(x)].concat(),
+
+        Instr::I32Const(n) => [vec![0x41], signed64_to_leb128(*n as i32 as i64)].concat(),
+        Instr::I64Const(n) => [vec![0x42], signed64_to_leb128(*n as i64)].concat(),
         Instr::F32Const(n) => [vec![0x43], n.to_bits().to_le_bytes().to_vec()].concat(),
         Instr::F64Const(n) => [vec![0x44], n.to_bits().to_le_bytes().to_vec()].concat(),
 
@@ -456,11 +581,11 @@ fn instr2wasm(instr: &Instr) -> Vec<Byte> {
         },
         Instr::CvtOp(cvtop) => {
             match cvtop {
-                CvtOp::IExtend8S(ValSize::V32) => unimplemented!(),
-                CvtOp::IExtend8S(ValSize::V64) => unimplemented!(),
-                CvtOp::IExtend16S(ValSize::V32) => unimplemented!(),
-                CvtOp::IExtend16S(ValSize::V64) => unimplemented!(),
-                CvtOp::I64Extend32S => unimplemented!(),
+                CvtOp::IExtend8S(ValSize::V32) => vec![0xC0],
+                CvtOp::IExtend8S(ValSize::V64) => vec![0xC2],
+                CvtOp::IExtend16S(ValSize::V32) => vec![0xC1],
+                CvtOp::IExtend16S(ValSize::V64) => vec![0xC3],
+                CvtOp::I64Extend32S => vec![0xC4],
                 CvtOp::I32WrapFromI64 => vec![0xA7],
                 CvtOp::ITruncFromF(ValSize::V32, ValSize::V32, ValSign::S) => vec![0xA8],
                 CvtOp::ITruncFromF(ValSize::V32, ValSize::V32, ValSign::U) => vec![0xA9],
@@ -494,48 +619,462 @@ fn instr2wasm(instr: &Instr) -> Vec<Byte> {
                 CvtOp::FReinterpretFromI(ValSize::V32) => vec![0xBE],
                 CvtOp::FReinterpretFromI(ValSize::V64) => vec![0xBF],
 
-                CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V32, ValSign::U) => unimplemented!(),
-                CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V32, ValSign::S) => unimplemented!(),
-                CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V64, ValSign::U) => unimplemented!(),
-                CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V64, ValSign::S) => unimplemented!(),
-                CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V32, ValSign::U) => unimplemented!(),
-                CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V32, ValSign::S) => unimplemented!(),
-                CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V64, ValSign::U) => unimplemented!(),
-                CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V64, ValSign::S) => unimplemented!(),
+                CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V32, ValSign::U) => [vec![0xFC], unsigned32_to_wasm(1)].concat(),
+                CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V32, ValSign::S) => [vec![0xFC], unsigned32_to_wasm(0)].concat(),
+                CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V64, ValSign::U) => [vec![0xFC], unsigned32_to_wasm(3)].concat(),
+                CvtOp::ITruncSatFromF(ValSize::V32, ValSize::V64, ValSign::S) => [vec![0xFC], unsigned32_to_wasm(2)].concat(),
+                CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V32, ValSign::U) => [vec![0xFC], unsigned32_to_wasm(5)].concat(),
+                CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V32, ValSign::S) => [vec![0xFC], unsigned32_to_wasm(4)].concat(),
+                CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V64, ValSign::U) => [vec![0xFC], unsigned32_to_wasm(7)].concat(),
+                CvtOp::ITruncSatFromF(ValSize::V64, ValSize::V64, ValSign::S) => [vec![0xFC], unsigned32_to_wasm(6)].concat(),
             }
         }
+
+        // Vector Instructions
+        Instr::V128LoadExtend(Shape::I16x8, ValSign::S, memarg) => [vec![0xFD], unsigned32_to_wasm(1), memarg2wasm(memarg)].concat(),
+        Instr::V128LoadExtend(Shape::I16x8, ValSign::U, memarg) => [vec![0xFD], unsigned32_to_wasm(2), memarg2wasm(memarg)].concat(),
+        Instr::V128LoadExtend(Shape::I32x4, ValSign::S, memarg) => [vec![0xFD], unsigned32_to_wasm(3), memarg2wasm(memarg)].concat(),
+        Instr::V128LoadExtend(Shape::I32x4, ValSign::U, memarg) => [vec![0xFD], unsigned32_to_wasm(4), memarg2wasm(memarg)].concat(),
+        Instr::V128LoadExtend(Shape::I64x2, ValSign::S, memarg) => [vec![0xFD], unsigned32_to_wasm(5), memarg2wasm(memarg)].concat(),
+        Instr::V128LoadExtend(Shape::I64x2, ValSign::U, memarg) => [vec![0xFD], unsigned32_to_wasm(6), memarg2wasm(memarg)].concat(),
+        Instr::V128LoadSplat(Shape::I8x16, memarg) => [vec![0xFD], unsigned32_to_wasm(7), memarg2wasm(memarg)].concat(),
+        Instr::V128LoadSplat(Shape::I16x8, memarg) => [vec![0xFD], unsigned32_to_wasm(8), memarg2wasm(memarg)].concat(),
+        Instr::V128LoadSplat(Shape::I32x4, memarg) => [vec![0xFD], unsigned32_to_wasm(9), memarg2wasm(memarg)].concat(),
+        Instr::V128LoadSplat(Shape::I64x2, memarg) => [vec![0xFD], unsigned32_to_wasm(10), memarg2wasm(memarg)].concat(),
+        Instr::V128Const(n) => [vec![0xFD], unsigned32_to_wasm(12), n.to_le_bytes().to_vec()].concat(),
+        Instr::I8x16Shuffle(laneindices) => [vec![0xFD], unsigned32_to_wasm(13), laneindices.to_vec()].concat(),
+        Instr::I8x16Swizzle => [vec![0xFD], unsigned32_to_wasm(14)].concat(),
+        Instr::Splat(Shape::I8x16) => [vec![0xFD], unsigned32_to_wasm(15)].concat(),
+        Instr::Splat(Shape::I16x8) => [vec![0xFD], unsigned32_to_wasm(16)].concat(),
+        Instr::Splat(Shape::I32x4) => [vec![0xFD], unsigned32_to_wasm(17)].concat(),
+        Instr::Splat(Shape::I64x2) => [vec![0xFD], unsigned32_to_wasm(18)].concat(),
+        Instr::Splat(Shape::F32x4) => [vec![0xFD], unsigned32_to_wasm(19)].concat(),
+        Instr::Splat(Shape::F64x2) => [vec![0xFD], unsigned32_to_wasm(20)].concat(),
+        Instr::ExtractLane(Shape::I8x16, Some(ValSign::S), laneidx) => [vec![0xFD], unsigned32_to_wasm(21), vec![*laneidx]].concat(),
+        Instr::ExtractLane(Shape::I8x16, Some(ValSign::U), laneidx) => [vec![0xFD], unsigned32_to_wasm(22), vec![*laneidx]].concat(),
+        Instr::ReplaceLane(Shape::I8x16, laneidx) => [vec![0xFD], unsigned32_to_wasm(23), vec![*laneidx]].concat(),
+        Instr::ExtractLane(Shape::I16x8, Some(ValSign::S), laneidx) => [vec![0xFD], unsigned32_to_wasm(24), vec![*laneidx]].concat(),
+        Instr::ExtractLane(Shape::I16x8, Some(ValSign::U), laneidx) => [vec![0xFD], unsigned32_to_wasm(25), vec![*laneidx]].concat(),
+        Instr::ReplaceLane(Shape::I16x8, laneidx) => [vec![0xFD], unsigned32_to_wasm(26), vec![*laneidx]].concat(),
+        Instr::ExtractLane(Shape::I32x4, None, laneidx) => [vec![0xFD], unsigned32_to_wasm(27), vec![*laneidx]].concat(),
+        Instr::ReplaceLane(Shape::I32x4, laneidx) => [vec![0xFD], unsigned32_to_wasm(28), vec![*laneidx]].concat(),
+        Instr::ExtractLane(Shape::I64x2, None, laneidx) => [vec![0xFD], unsigned32_to_wasm(29), vec![*laneidx]].concat(),
+        Instr::ReplaceLane(Shape::I64x2, laneidx) => [vec![0xFD], unsigned32_to_wasm(30), vec![*laneidx]].concat(),
+        Instr::ExtractLane(Shape::F32x4, None, laneidx) => [vec![0xFD], unsigned32_to_wasm(31), vec![*laneidx]].concat(),
+        Instr::ReplaceLane(Shape::F32x4, laneidx) => [vec![0xFD], unsigned32_to_wasm(32), vec![*laneidx]].concat(),
+        Instr::ExtractLane(Shape::F64x2, None, laneidx) => [vec![0xFD], unsigned32_to_wasm(33), vec![*laneidx]].concat(),
+        Instr::ReplaceLane(Shape::F64x2, laneidx) => [vec![0xFD], unsigned32_to_wasm(34), vec![*laneidx]].concat(),
+        Instr::VIRelOp(Shape::I8x16, IRelOp::Eq) => [vec![0xFD], unsigned32_to_wasm(35)].concat(),
+        Instr::VIRelOp(Shape::I8x16, IRelOp::Ne) => [vec![0xFD], unsigned32_to_wasm(36)].concat(),
+        Instr::VIRelOp(Shape::I8x16, IRelOp::Lt(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(37)].concat(),
+        Instr::VIRelOp(Shape::I8x16, IRelOp::Lt(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(38)].concat(),
+        Instr::VIRelOp(Shape::I8x16, IRelOp::Gt(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(39)].concat(),
+        Instr::VIRelOp(Shape::I8x16, IRelOp::Gt(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(40)].concat(),
+        Instr::VIRelOp(Shape::I8x16, IRelOp::Le(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(41)].concat(),
+        Instr::VIRelOp(Shape::I8x16, IRelOp::Le(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(42)].concat(),
+        Instr::VIRelOp(Shape::I8x16, IRelOp::Ge(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(43)].concat(),
+        Instr::VIRelOp(Shape::I8x16, IRelOp::Ge(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(44)].concat(),
+        Instr::VIRelOp(Shape::I16x8, IRelOp::Eq) => [vec![0xFD], unsigned32_to_wasm(45)].concat(),
+        Instr::VIRelOp(Shape::I16x8, IRelOp::Ne) => [vec![0xFD], unsigned32_to_wasm(46)].concat(),
+        Instr::VIRelOp(Shape::I16x8, IRelOp::Lt(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(47)].concat(),
+        Instr::VIRelOp(Shape::I16x8, IRelOp::Lt(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(48)].concat(),
+        Instr::VIRelOp(Shape::I16x8, IRelOp::Gt(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(49)].concat(),
+        Instr::VIRelOp(Shape::I16x8, IRelOp::Gt(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(50)].concat(),
+        Instr::VIRelOp(Shape::I16x8, IRelOp::Le(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(51)].concat(),
+        Instr::VIRelOp(Shape::I16x8, IRelOp::Le(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(52)].concat(),
+        Instr::VIRelOp(Shape::I16x8, IRelOp::Ge(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(53)].concat(),
+        Instr::VIRelOp(Shape::I16x8, IRelOp::Ge(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(54)].concat(),
+        Instr::VIRelOp(Shape::I32x4, IRelOp::Eq) => [vec![0xFD], unsigned32_to_wasm(55)].concat(),
+        Instr::VIRelOp(Shape::I32x4, IRelOp::Ne) => [vec![0xFD], unsigned32_to_wasm(56)].concat(),
+        Instr::VIRelOp(Shape::I32x4, IRelOp::Lt(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(57)].concat(),
+        Instr::VIRelOp(Shape::I32x4, IRelOp::Lt(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(58)].concat(),
+        Instr::VIRelOp(Shape::I32x4, IRelOp::Gt(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(59)].concat(),
+        Instr::VIRelOp(Shape::I32x4, IRelOp::Gt(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(60)].concat(),
+        Instr::VIRelOp(Shape::I32x4, IRelOp::Le(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(61)].concat(),
+        Instr::VIRelOp(Shape::I32x4, IRelOp::Le(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(62)].concat(),
+        Instr::VIRelOp(Shape::I32x4, IRelOp::Ge(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(63)].concat(),
+        Instr::VIRelOp(Shape::I32x4, IRelOp::Ge(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(64)].concat(),
+        Instr::VFRelOp(Shape::F32x4, FRelOp::Eq) => [vec![0xFD], unsigned32_to_wasm(65)].concat(),
+        Instr::VFRelOp(Shape::F32x4, FRelOp::Ne) => [vec![0xFD], unsigned32_to_wasm(66)].concat(),
+        Instr::VFRelOp(Shape::F32x4, FRelOp::Lt) => [vec![0xFD], unsigned32_to_wasm(67)].concat(),
+        Instr::VFRelOp(Shape::F32x4, FRelOp::Gt) => [vec![0xFD], unsigned32_to_wasm(68)].concat(),
+        Instr::VFRelOp(Shape::F32x4, FRelOp::Le) => [vec![0xFD], unsigned32_to_wasm(69)].concat(),
+        Instr::VFRelOp(Shape::F32x4, FRelOp::Ge) => [vec![0xFD], unsigned32_to_wasm(70)].concat(),
+        Instr::VFRelOp(Shape::F64x2, FRelOp::Eq) => [vec![0xFD], unsigned32_to_wasm(71)].concat(),
+        Instr::VFRelOp(Shape::F64x2, FRelOp::Ne) => [vec![0xFD], unsigned32_to_wasm(72)].concat(),
+        Instr::VFRelOp(Shape::F64x2, FRelOp::Lt) => [vec![0xFD], unsigned32_to_wasm(73)].concat(),
+        Instr::VFRelOp(Shape::F64x2, FRelOp::Gt) => [vec![0xFD], unsigned32_to_wasm(74)].concat(),
+        Instr::VFRelOp(Shape::F64x2, FRelOp::Le) => [vec![0xFD], unsigned32_to_wasm(75)].concat(),
+        Instr::VFRelOp(Shape::F64x2, FRelOp::Ge) => [vec![0xFD], unsigned32_to_wasm(76)].concat(),
+        Instr::V128Not => [vec![0xFD], unsigned32_to_wasm(77)].concat(),
+        Instr::VVBinOp(VVBinOp::And) => [vec![0xFD], unsigned32_to_wasm(78)].concat(),
+        Instr::VVBinOp(VVBinOp::AndNot) => [vec![0xFD], unsigned32_to_wasm(79)].concat(),
+        Instr::VVBinOp(VVBinOp::Or) => [vec![0xFD], unsigned32_to_wasm(80)].concat(),
+        Instr::VVBinOp(VVBinOp::Xor) => [vec![0xFD], unsigned32_to_wasm(81)].concat(),
+        Instr::V128Bitselect => [vec![0xFD], unsigned32_to_wasm(82)].concat(),
+        Instr::V128AnyTrue => [vec![0xFD], unsigned32_to_wasm(83)].concat(),
+        Instr::V128LoadLane(Shape::I8x16, memarg, laneidx) => [vec![0xFD], unsigned32_to_wasm(84), memarg2wasm(memarg), vec![*laneidx]].concat(),
+        Instr::V128LoadLane(Shape::I16x8, memarg, laneidx) => [vec![0xFD], unsigned32_to_wasm(85), memarg2wasm(memarg), vec![*laneidx]].concat(),
+        Instr::V128LoadLane(Shape::I32x4, memarg, laneidx) => [vec![0xFD], unsigned32_to_wasm(86), memarg2wasm(memarg), vec![*laneidx]].concat(),
+        Instr::V128LoadLane(Shape::I64x2, memarg, laneidx) => [vec![0xFD], unsigned32_to_wasm(87), memarg2wasm(memarg), vec![*laneidx]].concat(),
+        Instr::V128StoreLane(Shape::I8x16, memarg, laneidx) => [vec![0xFD], unsigned32_to_wasm(88), memarg2wasm(memarg), vec![*laneidx]].concat(),
+        Instr::V128StoreLane(Shape::I16x8, memarg, laneidx) => [vec![0xFD], unsigned32_to_wasm(89), memarg2wasm(memarg), vec![*laneidx]].concat(),
+        Instr::V128StoreLane(Shape::I32x4, memarg, laneidx) => [vec![0xFD], unsigned32_to_wasm(90), memarg2wasm(memarg), vec![*laneidx]].concat(),
+        Instr::V128StoreLane(Shape::I64x2, memarg, laneidx) => [vec![0xFD], unsigned32_to_wasm(91), memarg2wasm(memarg), vec![*laneidx]].concat(),
+        Instr::V128LoadZero(ValSize::V32, memarg) => [vec![0xFD], unsigned32_to_wasm(92), memarg2wasm(memarg)].concat(),
+        Instr::V128LoadZero(ValSize::V64, memarg) => [vec![0xFD], unsigned32_to_wasm(93), memarg2wasm(memarg)].concat(),
+        Instr::VCvtOp(VCvtOp::F32x4DemoteF64x2Zero) => [vec![0xFD], unsigned32_to_wasm(94)].concat(),
+        Instr::VCvtOp(VCvtOp::F64x2PromoteLowF32x4) => [vec![0xFD], unsigned32_to_wasm(95)].concat(),
+        Instr::VIUnOp(Shape::I8x16, VIUnOp::Abs) => [vec![0xFD], unsigned32_to_wasm(96)].concat(),
+        Instr::VIUnOp(Shape::I8x16, VIUnOp::Neg) => [vec![0xFD], unsigned32_to_wasm(97)].concat(),
+        Instr::VIUnOp(Shape::I8x16, VIUnOp::Popcnt) => [vec![0xFD], unsigned32_to_wasm(98)].concat(),
+        Instr::AllTrue(Shape::I8x16) => [vec![0xFD], unsigned32_to_wasm(99)].concat(),
+        Instr::Bitmask(Shape::I8x16) => [vec![0xFD], unsigned32_to_wasm(100)].concat(),
+        Instr::Narrow(Shape::I8x16, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(101)].concat(),
+        Instr::Narrow(Shape::I8x16, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(102)].concat(),
+        Instr::VFUnOp(Shape::F32x4, FUnOp::Ceil) => [vec![0xFD], unsigned32_to_wasm(103)].concat(),
+        Instr::VFUnOp(Shape::F32x4, FUnOp::Floor) => [vec![0xFD], unsigned32_to_wasm(104)].concat(),
+        Instr::VFUnOp(Shape::F32x4, FUnOp::Trunc) => [vec![0xFD], unsigned32_to_wasm(105)].concat(),
+        Instr::VFUnOp(Shape::F32x4, FUnOp::Nearest) => [vec![0xFD], unsigned32_to_wasm(106)].concat(),
+        Instr::VIShiftOp(Shape::I8x16, VIShiftOp::Shl) => [vec![0xFD], unsigned32_to_wasm(107)].concat(),
+        Instr::VIShiftOp(Shape::I8x16, VIShiftOp::Shr(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(108)].concat(),
+        Instr::VIShiftOp(Shape::I8x16, VIShiftOp::Shr(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(109)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::Add) => [vec![0xFD], unsigned32_to_wasm(110)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::AddSat(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(111)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::AddSat(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(112)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::Sub) => [vec![0xFD], unsigned32_to_wasm(113)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::SubSat(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(114)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::SubSat(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(115)].concat(),
+        Instr::VFUnOp(Shape::F64x2, FUnOp::Ceil) => [vec![0xFD], unsigned32_to_wasm(116)].concat(),
+        Instr::VFUnOp(Shape::F64x2, FUnOp::Floor) => [vec![0xFD], unsigned32_to_wasm(117)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::Min(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(118)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::Min(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(119)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::Max(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(120)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::Max(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(121)].concat(),
+        Instr::VFUnOp(Shape::F64x2, FUnOp::Trunc) => [vec![0xFD], unsigned32_to_wasm(122)].concat(),
+        Instr::VIBinOp(Shape::I8x16, VIBinOp::AvgrU) => [vec![0xFD], unsigned32_to_wasm(123)].concat(),
+        Instr::ExtAddPairwise(Shape::I16x8, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(124)].concat(),
+        Instr::ExtAddPairwise(Shape::I16x8, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(125)].concat(),
+        Instr::ExtAddPairwise(Shape::I32x4, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(126)].concat(),
+        Instr::ExtAddPairwise(Shape::I32x4, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(127)].concat(),
+        Instr::VIUnOp(Shape::I16x8, VIUnOp::Abs) => [vec![0xFD], unsigned32_to_wasm(128)].concat(),
+        Instr::VIUnOp(Shape::I16x8, VIUnOp::Neg) => [vec![0xFD], unsigned32_to_wasm(129)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::Q15MulrSatS) => [vec![0xFD], unsigned32_to_wasm(130)].concat(),
+        Instr::AllTrue(Shape::I16x8) => [vec![0xFD], unsigned32_to_wasm(131)].concat(),
+        Instr::Bitmask(Shape::I16x8) => [vec![0xFD], unsigned32_to_wasm(132)].concat(),
+        Instr::Narrow(Shape::I16x8, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(133)].concat(),
+        Instr::Narrow(Shape::I16x8, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(134)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::Low, ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(135)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::High, ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(136)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::Low, ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(137)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::High, ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(138)].concat(),
+        Instr::VIShiftOp(Shape::I16x8, VIShiftOp::Shl) => [vec![0xFD], unsigned32_to_wasm(139)].concat(),
+        Instr::VIShiftOp(Shape::I16x8, VIShiftOp::Shr(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(140)].concat(),
+        Instr::VIShiftOp(Shape::I16x8, VIShiftOp::Shr(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(141)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::Add) => [vec![0xFD], unsigned32_to_wasm(142)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::AddSat(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(143)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::AddSat(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(144)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::Sub) => [vec![0xFD], unsigned32_to_wasm(145)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::SubSat(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(146)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::SubSat(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(147)].concat(),
+        Instr::VFUnOp(Shape::F64x2, FUnOp::Nearest) => [vec![0xFD], unsigned32_to_wasm(148)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::Mul) => [vec![0xFD], unsigned32_to_wasm(149)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::Min(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(150)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::Min(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(151)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::Max(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(152)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::Max(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(153)].concat(),
+        Instr::VIBinOp(Shape::I16x8, VIBinOp::AvgrU) => [vec![0xFD], unsigned32_to_wasm(155)].concat(),
+        Instr::ExtMul(Shape::I16x8, Half::Low, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(156)].concat(),
+        Instr::ExtMul(Shape::I16x8, Half::High, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(157)].concat(),
+        Instr::ExtMul(Shape::I16x8, Half::Low, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(158)].concat(),
+        Instr::ExtMul(Shape::I16x8, Half::High, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(159)].concat(),
+        Instr::VIUnOp(Shape::I32x4, VIUnOp::Abs) => [vec![0xFD], unsigned32_to_wasm(160)].concat(),
+        Instr::VIUnOp(Shape::I32x4, VIUnOp::Neg) => [vec![0xFD], unsigned32_to_wasm(161)].concat(),
+        Instr::AllTrue(Shape::I32x4) => [vec![0xFD], unsigned32_to_wasm(163)].concat(),
+        Instr::Bitmask(Shape::I32x4) => [vec![0xFD], unsigned32_to_wasm(164)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::Low, ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(167)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::High, ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(168)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::Low, ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(169)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::High, ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(170)].concat(),
+        Instr::VIShiftOp(Shape::I32x4, VIShiftOp::Shl) => [vec![0xFD], unsigned32_to_wasm(171)].concat(),
+        Instr::VIShiftOp(Shape::I32x4, VIShiftOp::Shr(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(172)].concat(),
+        Instr::VIShiftOp(Shape::I32x4, VIShiftOp::Shr(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(173)].concat(),
+        Instr::VIBinOp(Shape::I32x4, VIBinOp::Add) => [vec![0xFD], unsigned32_to_wasm(174)].concat(),
+        Instr::VIBinOp(Shape::I32x4, VIBinOp::Sub) => [vec![0xFD], unsigned32_to_wasm(177)].concat(),
+        Instr::VIBinOp(Shape::I32x4, VIBinOp::Mul) => [vec![0xFD], unsigned32_to_wasm(181)].concat(),
+        Instr::VIBinOp(Shape::I32x4, VIBinOp::Min(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(182)].concat(),
+        Instr::VIBinOp(Shape::I32x4, VIBinOp::Min(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(183)].concat(),
+        Instr::VIBinOp(Shape::I32x4, VIBinOp::Max(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(184)].concat(),
+        Instr::VIBinOp(Shape::I32x4, VIBinOp::Max(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(185)].concat(),
+        Instr::I32x4DotI16x8S => [vec![0xFD], unsigned32_to_wasm(186)].concat(),
+        Instr::ExtMul(Shape::I32x4, Half::Low, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(188)].concat(),
+        Instr::ExtMul(Shape::I32x4, Half::High, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(189)].concat(),
+        Instr::ExtMul(Shape::I32x4, Half::Low, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(190)].concat(),
+        Instr::ExtMul(Shape::I32x4, Half::High, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(191)].concat(),
+        Instr::VIUnOp(Shape::I64x2, VIUnOp::Abs) => [vec![0xFD], unsigned32_to_wasm(192)].concat(),
+        Instr::VIUnOp(Shape::I64x2, VIUnOp::Neg) => [vec![0xFD], unsigned32_to_wasm(193)].concat(),
+        Instr::AllTrue(Shape::I64x2) => [vec![0xFD], unsigned32_to_wasm(195)].concat(),
+        Instr::Bitmask(Shape::I64x2) => [vec![0xFD], unsigned32_to_wasm(196)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::Low, ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(199)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::High, ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(200)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::Low, ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(201)].concat(),
+        Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::High, ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(202)].concat(),
+        Instr::VIShiftOp(Shape::I64x2, VIShiftOp::Shl) => [vec![0xFD], unsigned32_to_wasm(203)].concat(),
+        Instr::VIShiftOp(Shape::I64x2, VIShiftOp::Shr(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(204)].concat(),
+        Instr::VIShiftOp(Shape::I64x2, VIShiftOp::Shr(ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(205)].concat(),
+        Instr::VIBinOp(Shape::I64x2, VIBinOp::Add) => [vec![0xFD], unsigned32_to_wasm(206)].concat(),
+        Instr::VIBinOp(Shape::I64x2, VIBinOp::Sub) => [vec![0xFD], unsigned32_to_wasm(209)].concat(),
+        Instr::VIBinOp(Shape::I64x2, VIBinOp::Mul) => [vec![0xFD], unsigned32_to_wasm(213)].concat(),
+        Instr::VIRelOp(Shape::I64x2, IRelOp::Eq) => [vec![0xFD], unsigned32_to_wasm(214)].concat(),
+        Instr::VIRelOp(Shape::I64x2, IRelOp::Ne) => [vec![0xFD], unsigned32_to_wasm(215)].concat(),
+        Instr::VIRelOp(Shape::I64x2, IRelOp::Lt(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(216)].concat(),
+        Instr::VIRelOp(Shape::I64x2, IRelOp::Gt(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(217)].concat(),
+        Instr::VIRelOp(Shape::I64x2, IRelOp::Le(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(218)].concat(),
+        Instr::VIRelOp(Shape::I64x2, IRelOp::Ge(ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(219)].concat(),
+        Instr::ExtMul(Shape::I64x2, Half::Low, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(220)].concat(),
+        Instr::ExtMul(Shape::I64x2, Half::High, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(221)].concat(),
+        Instr::ExtMul(Shape::I64x2, Half::Low, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(222)].concat(),
+        Instr::ExtMul(Shape::I64x2, Half::High, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(223)].concat(),
+        Instr::VFUnOp(Shape::F32x4, FUnOp::Abs) => [vec![0xFD], unsigned32_to_wasm(224)].concat(),
+        Instr::VFUnOp(Shape::F32x4, FUnOp::Neg) => [vec![0xFD], unsigned32_to_wasm(225)].concat(),
+        Instr::VFUnOp(Shape::F32x4, FUnOp::Sqrt) => [vec![0xFD], unsigned32_to_wasm(227)].concat(),
+        Instr::VFBinOp(Shape::F32x4, VFBinOp::Add) => [vec![0xFD], unsigned32_to_wasm(228)].concat(),
+        Instr::VFBinOp(Shape::F32x4, VFBinOp::Sub) => [vec![0xFD], unsigned32_to_wasm(229)].concat(),
+        Instr::VFBinOp(Shape::F32x4, VFBinOp::Mul) => [vec![0xFD], unsigned32_to_wasm(230)].concat(),
+        Instr::VFBinOp(Shape::F32x4, VFBinOp::Div) => [vec![0xFD], unsigned32_to_wasm(231)].concat(),
+        Instr::VFBinOp(Shape::F32x4, VFBinOp::Min) => [vec![0xFD], unsigned32_to_wasm(232)].concat(),
+        Instr::VFBinOp(Shape::F32x4, VFBinOp::Max) => [vec![0xFD], unsigned32_to_wasm(233)].concat(),
+        Instr::VFBinOp(Shape::F32x4, VFBinOp::PMin) => [vec![0xFD], unsigned32_to_wasm(234)].concat(),
+        Instr::VFBinOp(Shape::F32x4, VFBinOp::PMax) => [vec![0xFD], unsigned32_to_wasm(235)].concat(),
+        Instr::VFUnOp(Shape::F64x2, FUnOp::Abs) => [vec![0xFD], unsigned32_to_wasm(236)].concat(),
+        Instr::VFUnOp(Shape::F64x2, FUnOp::Neg) => [vec![0xFD], unsigned32_to_wasm(237)].concat(),
+        Instr::VFUnOp(Shape::F64x2, FUnOp::Sqrt) => [vec![0xFD], unsigned32_to_wasm(239)].concat(),
+        Instr::VFBinOp(Shape::F64x2, VFBinOp::Add) => [vec![0xFD], unsigned32_to_wasm(240)].concat(),
+        Instr::VFBinOp(Shape::F64x2, VFBinOp::Sub) => [vec![0xFD], unsigned32_to_wasm(241)].concat(),
+        Instr::VFBinOp(Shape::F64x2, VFBinOp::Mul) => [vec![0xFD], unsigned32_to_wasm(242)].concat(),
+        Instr::VFBinOp(Shape::F64x2, VFBinOp::Div) => [vec![0xFD], unsigned32_to_wasm(243)].concat(),
+        Instr::VFBinOp(Shape::F64x2, VFBinOp::Min) => [vec![0xFD], unsigned32_to_wasm(244)].concat(),
+        Instr::VFBinOp(Shape::F64x2, VFBinOp::Max) => [vec![0xFD], unsigned32_to_wasm(245)].concat(),
+        Instr::VFBinOp(Shape::F64x2, VFBinOp::PMin) => [vec![0xFD], unsigned32_to_wasm(246)].concat(),
+        Instr::VFBinOp(Shape::F64x2, VFBinOp::PMax) => [vec![0xFD], unsigned32_to_wasm(247)].concat(),
+        Instr::VCvtOp(VCvtOp::TruncSat(Shape::F32x4, ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(248)].concat(),
+        Instr::VCvtOp(VCvtOp::TruncSat(Shape::F32x4, ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(249)].concat(),
+        Instr::VCvtOp(VCvtOp::Convert(Shape::F32x4, ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(250)].concat(),
+        Instr::VCvtOp(VCvtOp::Convert(Shape::F32x4, ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(251)].concat(),
+        Instr::VCvtOp(VCvtOp::TruncSat(Shape::F64x2, ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(252)].concat(),
+        Instr::VCvtOp(VCvtOp::TruncSat(Shape::F64x2, ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(253)].concat(),
+        Instr::VCvtOp(VCvtOp::Convert(Shape::F64x2, ValSign::S)) => [vec![0xFD], unsigned32_to_wasm(254)].concat(),
+        Instr::VCvtOp(VCvtOp::Convert(Shape::F64x2, ValSign::U)) => [vec![0xFD], unsigned32_to_wasm(255)].concat(),
+        Instr::I8x16RelaxedSwizzle => [vec![0xFD], unsigned32_to_wasm(256)].concat(),
+        Instr::RelaxedTrunc(Shape::F32x4, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(257)].concat(),
+        Instr::RelaxedTrunc(Shape::F32x4, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(258)].concat(),
+        Instr::RelaxedTrunc(Shape::F64x2, ValSign::S) => [vec![0xFD], unsigned32_to_wasm(259)].concat(),
+        Instr::RelaxedTrunc(Shape::F64x2, ValSign::U) => [vec![0xFD], unsigned32_to_wasm(260)].concat(),
+        Instr::RelaxedMadd(Shape::F32x4) => [vec![0xFD], unsigned32_to_wasm(261)].concat(),
+        Instr::RelaxedNmadd(Shape::F32x4) => [vec![0xFD], unsigned32_to_wasm(262)].concat(),
+        Instr::RelaxedMadd(Shape::F64x2) => [vec![0xFD], unsigned32_to_wasm(263)].concat(),
+        Instr::RelaxedNmadd(Shape::F64x2) => [vec![0xFD], unsigned32_to_wasm(264)].concat(),
+        Instr::RelaxedLaneselect(Shape::I8x16) => [vec![0xFD], unsigned32_to_wasm(265)].concat(),
+        Instr::RelaxedLaneselect(Shape::I16x8) => [vec![0xFD], unsigned32_to_wasm(266)].concat(),
+        Instr::RelaxedLaneselect(Shape::I32x4) => [vec![0xFD], unsigned32_to_wasm(267)].concat(),
+        Instr::RelaxedLaneselect(Shape::I64x2) => [vec![0xFD], unsigned32_to_wasm(268)].concat(),
+        Instr::RelaxedMin(Shape::F32x4) => [vec![0xFD], unsigned32_to_wasm(269)].concat(),
+        Instr::RelaxedMax(Shape::F32x4) => [vec![0xFD], unsigned32_to_wasm(270)].concat(),
+        Instr::RelaxedMin(Shape::F64x2) => [vec![0xFD], unsigned32_to_wasm(271)].concat(),
+        Instr::RelaxedMax(Shape::F64x2) => [vec![0xFD], unsigned32_to_wasm(272)].concat(),
+        Instr::I16x8RelaxedQ15mulrS => [vec![0xFD], unsigned32_to_wasm(273)].concat(),
+        Instr::I16x8RelaxedDotI8x16I7x16S => [vec![0xFD], unsigned32_to_wasm(274)].concat(),
+        Instr::I32x4RelaxedDotI8x16I7x16AddS => [vec![0xFD], unsigned32_to_wasm(275)].concat(),
+
+        // Atomic Memory Instructions
+        Instr::AtomicNotify(memarg) => [vec![0xFE], unsigned32_to_wasm(0), memarg2wasm(memarg)].concat(),
+        Instr::AtomicWait(ValSize::V32, memarg) => [vec![0xFE], unsigned32_to_wasm(1), memarg2wasm(memarg)].concat(),
+        Instr::AtomicWait(ValSize::V64, memarg) => [vec![0xFE], unsigned32_to_wasm(2), memarg2wasm(memarg)].concat(),
+        Instr::AtomicFence => [vec![0xFE], unsigned32_to_wasm(3), vec![0x00]].concat(),
+        Instr::AtomicLoad(ValSize::V32, None, memarg) => [vec![0xFE], unsigned32_to_wasm(16), memarg2wasm(memarg)].concat(),
+        Instr::AtomicLoad(ValSize::V64, None, memarg) => [vec![0xFE], unsigned32_to_wasm(17), memarg2wasm(memarg)].concat(),
+        Instr::AtomicLoad(ValSize::V32, Some(PackSize::P8), memarg) => [vec![0xFE], unsigned32_to_wasm(18), memarg2wasm(memarg)].concat(),
+        Instr::AtomicLoad(ValSize::V32, Some(PackSize::P16), memarg) => [vec![0xFE], unsigned32_to_wasm(19), memarg2wasm(memarg)].concat(),
+        Instr::AtomicLoad(ValSize::V64, Some(PackSize::P8), memarg) => [vec![0xFE], unsigned32_to_wasm(20), memarg2wasm(memarg)].concat(),
+        Instr::AtomicLoad(ValSize::V64, Some(PackSize::P16), memarg) => [vec![0xFE], unsigned32_to_wasm(21), memarg2wasm(memarg)].concat(),
+        Instr::AtomicLoad(ValSize::V64, Some(PackSize::P32), memarg) => [vec![0xFE], unsigned32_to_wasm(22), memarg2wasm(memarg)].concat(),
+        Instr::AtomicStore(ValSize::V32, None, memarg) => [vec![0xFE], unsigned32_to_wasm(23), memarg2wasm(memarg)].concat(),
+        Instr::AtomicStore(ValSize::V64, None, memarg) => [vec![0xFE], unsigned32_to_wasm(24), memarg2wasm(memarg)].concat(),
+        Instr::AtomicStore(ValSize::V32, Some(PackSize::P8), memarg) => [vec![0xFE], unsigned32_to_wasm(25), memarg2wasm(memarg)].concat(),
+        Instr::AtomicStore(ValSize::V32, Some(PackSize::P16), memarg) => [vec![0xFE], unsigned32_to_wasm(26), memarg2wasm(memarg)].concat(),
+        Instr::AtomicStore(ValSize::V64, Some(PackSize::P8), memarg) => [vec![0xFE], unsigned32_to_wasm(27), memarg2wasm(memarg)].concat(),
+        Instr::AtomicStore(ValSize::V64, Some(PackSize::P16), memarg) => [vec![0xFE], unsigned32_to_wasm(28), memarg2wasm(memarg)].concat(),
+        Instr::AtomicStore(ValSize::V64, Some(PackSize::P32), memarg) => [vec![0xFE], unsigned32_to_wasm(29), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Add, memarg) => [vec![0xFE], unsigned32_to_wasm(30), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Add, memarg) => [vec![0xFE], unsigned32_to_wasm(31), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Add, memarg) => [vec![0xFE], unsigned32_to_wasm(32), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Add, memarg) => [vec![0xFE], unsigned32_to_wasm(33), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Add, memarg) => [vec![0xFE], unsigned32_to_wasm(34), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Add, memarg) => [vec![0xFE], unsigned32_to_wasm(35), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Add, memarg) => [vec![0xFE], unsigned32_to_wasm(36), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Sub, memarg) => [vec![0xFE], unsigned32_to_wasm(37), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Sub, memarg) => [vec![0xFE], unsigned32_to_wasm(38), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Sub, memarg) => [vec![0xFE], unsigned32_to_wasm(39), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Sub, memarg) => [vec![0xFE], unsigned32_to_wasm(40), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Sub, memarg) => [vec![0xFE], unsigned32_to_wasm(41), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Sub, memarg) => [vec![0xFE], unsigned32_to_wasm(42), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Sub, memarg) => [vec![0xFE], unsigned32_to_wasm(43), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::And, memarg) => [vec![0xFE], unsigned32_to_wasm(44), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::And, memarg) => [vec![0xFE], unsigned32_to_wasm(45), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::And, memarg) => [vec![0xFE], unsigned32_to_wasm(46), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::And, memarg) => [vec![0xFE], unsigned32_to_wasm(47), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::And, memarg) => [vec![0xFE], unsigned32_to_wasm(48), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::And, memarg) => [vec![0xFE], unsigned32_to_wasm(49), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::And, memarg) => [vec![0xFE], unsigned32_to_wasm(50), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Or, memarg) => [vec![0xFE], unsigned32_to_wasm(51), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Or, memarg) => [vec![0xFE], unsigned32_to_wasm(52), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Or, memarg) => [vec![0xFE], unsigned32_to_wasm(53), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Or, memarg) => [vec![0xFE], unsigned32_to_wasm(54), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Or, memarg) => [vec![0xFE], unsigned32_to_wasm(55), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Or, memarg) => [vec![0xFE], unsigned32_to_wasm(56), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Or, memarg) => [vec![0xFE], unsigned32_to_wasm(57), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Xor, memarg) => [vec![0xFE], unsigned32_to_wasm(58), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Xor, memarg) => [vec![0xFE], unsigned32_to_wasm(59), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Xor, memarg) => [vec![0xFE], unsigned32_to_wasm(60), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Xor, memarg) => [vec![0xFE], unsigned32_to_wasm(61), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Xor, memarg) => [vec![0xFE], unsigned32_to_wasm(62), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Xor, memarg) => [vec![0xFE], unsigned32_to_wasm(63), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Xor, memarg) => [vec![0xFE], unsigned32_to_wasm(64), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Xchg, memarg) => [vec![0xFE], unsigned32_to_wasm(65), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Xchg, memarg) => [vec![0xFE], unsigned32_to_wasm(66), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Xchg, memarg) => [vec![0xFE], unsigned32_to_wasm(67), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Xchg, memarg) => [vec![0xFE], unsigned32_to_wasm(68), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Xchg, memarg) => [vec![0xFE], unsigned32_to_wasm(69), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Xchg, memarg) => [vec![0xFE], unsigned32_to_wasm(70), memarg2wasm(memarg)].concat(),
+        Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Xchg, memarg) => [vec![0xFE], unsigned32_to_wasm(71), memarg2wasm(memarg)].concat(),
+        Instr::AtomicCmpxchg(ValSize::V32, None, memarg) => [vec![0xFE], unsigned32_to_wasm(72), memarg2wasm(memarg)].concat(),
+        Instr::AtomicCmpxchg(ValSize::V64, None, memarg) => [vec![0xFE], unsigned32_to_wasm(73), memarg2wasm(memarg)].concat(),
+        Instr::AtomicCmpxchg(ValSize::V32, Some(PackSize::P8), memarg) => [vec![0xFE], unsigned32_to_wasm(74), memarg2wasm(memarg)].concat(),
+        Instr::AtomicCmpxchg(ValSize::V32, Some(PackSize::P16), memarg) => [vec![0xFE], unsigned32_to_wasm(75), memarg2wasm(memarg)].concat(),
+        Instr::AtomicCmpxchg(ValSize::V64, Some(PackSize::P8), memarg) => [vec![0xFE], unsigned32_to_wasm(76), memarg2wasm(memarg)].concat(),
+        Instr::AtomicCmpxchg(ValSize::V64, Some(PackSize::P16), memarg) => [vec![0xFE], unsigned32_to_wasm(77), memarg2wasm(memarg)].concat(),
+        Instr::AtomicCmpxchg(ValSize::V64, Some(PackSize::P32), memarg) => [vec![0xFE], unsigned32_to_wasm(78), memarg2wasm(memarg)].concat(),
+        // an i32 can't be accessed as 32 packed bits
+        Instr::AtomicLoad(ValSize::V32, Some(PackSize::P32), _) |
+        Instr::AtomicStore(ValSize::V32, Some(PackSize::P32), _) |
+        Instr::AtomicRmw(ValSize::V32, Some(PackSize::P32), _, _) |
+        Instr::AtomicCmpxchg(ValSize::V32, Some(PackSize::P32), _) => unreachable!(),
+
+        // other shape combinations are rejected by validation
+        Instr::ExtractLane(..) |
+        Instr::VIRelOp(..) |
+        Instr::VFRelOp(..) |
+        Instr::VIUnOp(..) |
+        Instr::VFUnOp(..) |
+        Instr::VIBinOp(..) |
+        Instr::VFBinOp(..) |
+        Instr::VIShiftOp(..) |
+        Instr::AllTrue(..) |
+        Instr::Bitmask(..) |
+        Instr::Narrow(..) |
+        Instr::ExtMul(..) |
+        Instr::ExtAddPairwise(..) |
+        Instr::VCvtOp(..) |
+        Instr::RelaxedTrunc(..) |
+        Instr::RelaxedMadd(..) |
+        Instr::RelaxedNmadd(..) |
+        Instr::RelaxedLaneselect(..) |
+        Instr::RelaxedMin(..) |
+        Instr::RelaxedMax(..) |
+        Instr::V128LoadExtend(..) |
+        Instr::V128LoadSplat(..) |
+        Instr::V128LoadLane(..) |
+        Instr::V128StoreLane(..) => unreachable!(),
         // _ => unimplemented!(),
     }
 }
 
 fn memarg2wasm(memarg: &MemArg) -> Vec<Byte> {
+    // memory 0 keeps the single-memory encoding
+    if memarg.memory == 0 {
+        return [
+            unsigned32_to_wasm(memarg.align),
+            unsigned64_to_wasm(memarg.offset),
+        ].concat();
+    }
     [
-        unsigned32_to_wasm(memarg.align),
-        unsigned32_to_wasm(memarg.offset),
+        unsigned32_to_wasm(memarg.align | 0x40),
+        memidx2wasm(&memarg.memory),
+        unsigned64_to_wasm(memarg.offset),
     ].concat()
 }
 
 fn globaltype2wasm(gt: &GlobalType) -> Vec<Byte> {
     [
         valtype2wasm(&gt.0),
-        match gt.1 {
-             Mut::Const => 0x00,
-             Mut::Var => 0x01,
-        }
+        mut2wasm(&gt.1),
     ]
-    .to_vec()
+    .concat()
+}
+
+fn mut2wasm(m: &Mut) -> Vec<Byte> {
+    match m {
+        Mut::Const => vec![0x00],
+        Mut::Var => vec![0x01],
+    }
+}
+
+fn subtype2wasm(subtype: &SubType) -> Vec<Byte> {
+    match subtype {
+        SubType { is_final: true, supers, comp, .. } if supers.is_empty() => comptype2wasm(comp),
+        SubType { is_final, supers, comp, .. } => [
+            vec![if *is_final { 0x4F } else { 0x50 }],
+            vector2wasm(supers.iter().map(typeidx2wasm).collect()),
+            comptype2wasm(comp),
+        ].concat(),
+    }
+}
+
+fn comptype2wasm(comp: &CompType) -> Vec<Byte> {
+    match comp {
+        CompType::Func(functype) => functype2wasm(functype),
+        CompType::Struct(fields) => [vec![0x5F], vector2wasm(fields.iter().map(fieldtype2wasm).collect())].concat(),
+        CompType::Array(field) => [vec![0x5E], fieldtype2wasm(field)].concat(),
+    }
+}
+
+fn fieldtype2wasm(field: &FieldType) -> Vec<Byte> {
+    let storagetype = match &field.0 {
+        StorageType::Val(vt) => valtype2wasm(vt),
+        StorageType::I8 => vec![0x78],
+        StorageType::I16 => vec![0x77],
+    };
+    [storagetype, mut2wasm(&field.1)].concat()
 }
 
 fn tabletype2wasm(tt: &TableType) -> Vec<Byte> {
     [
-        vec![0x70],
+        reftype2wasm(&tt.1),
         limits2wasm(&tt.0),
     ]
     .concat()
 }
 
 fn memtype2wasm(mt: &MemType) -> Vec<Byte> {
-    limits2wasm(&mt.0)
+    let mut bytes = match mt.2 {
+        IdxType::I32 => limits2wasm(&mt.0),
+        IdxType::I64 => limits64_2wasm(&mt.0),
+    };
+    if mt.is_shared() {
+        bytes[0] |= 0x02;
+    }
+    if let Some(page_size_log2) = mt.0.page_size_log2 {
+        bytes[0] |= 0x08;
+        bytes.extend(unsigned32_to_wasm(page_size_log2));
+    }
+    bytes
+}
+
+fn limits64_2wasm(lim: &Limits) -> Vec<Byte> {
+    match lim.max {
+        Some(max) => [vec![0x05], unsigned64_to_wasm(lim.min), unsigned64_to_wasm(max)].concat(),
+        None => [vec![0x04], unsigned64_to_wasm(lim.min)].concat(),
+    }
 }
 
 fn limits2wasm(lim: &Limits) -> Vec<Byte> {
@@ -558,35 +1097,90 @@ fn limits2wasm(lim: &Limits) -> Vec<Byte> {
 fn functype2wasm(func: &FuncType) -> Vec<Byte> {
     [
         vec![0x60],
-        bytevector2wasm(func.0.iter().map(valtype2wasm).collect()),
-        bytevector2wasm(func.1.iter().map(valtype2wasm).collect()),
+        vector2wasm(func.0.iter().map(valtype2wasm).collect()),
+        vector2wasm(func.1.iter().map(valtype2wasm).collect()),
     ]
     .concat()
 }
 
 fn blocktype2wasm(blocktype: &BlockType) -> Vec<Byte> {
     match blocktype {
-        BlockType::TypeIdx(_typeidx) => {
-            unimplemented!()
+        BlockType::TypeIdx(typeidx) => {
+            // s33 keeps type indices apart from the negative valtype bytes
+            signed64_to_leb128(*typeidx as i64)
         },
         BlockType::ValType(None) => {
-            vec![]
+            vec![0x40]
         },
         BlockType::ValType(Some(valtype)) => {
-            vec![valtype2wasm(valtype)]
+            valtype2wasm(valtype)
         },
     }
 }
 
-fn valtype2wasm(vt: &ValType) -> Byte {
+fn valtype2wasm(vt: &ValType) -> Vec<Byte> {
     match vt {
-        ValType::I32 => 0x7F,
-        ValType::I64 => 0x7E,
-        ValType::F32 => 0x7D,
-        ValType::F64 => 0x7C,
+        ValType::I32 => vec![0x7F],
+        ValType::I64 => vec![0x7E],
+        ValType::F32 => vec![0x7D],
+        ValType::F64 => vec![0x7C],
+        ValType::V128 => vec![0x7B],
+        ValType::FuncRef => vec![0x70],
+        ValType::ExternRef => vec![0x6F],
+        ValType::ExnRef => vec![0x69],
+        // abstract heap types are their own shorthand
+        ValType::Ref(RefType { nullable: true, heap: heap @ HeapType::Type(_) }) => [vec![0x63], heaptype2wasm(heap)].concat(),
+        ValType::Ref(RefType { nullable: true, heap }) => heaptype2wasm(heap),
+        ValType::Ref(RefType { nullable: false, heap }) => [vec![0x64], heaptype2wasm(heap)].concat(),
+    }
+}
+
+fn heaptype2wasm(ht: &HeapType) -> Vec<Byte> {
+    match ht {
+        HeapType::Func => vec![0x70],
+        HeapType::Extern => vec![0x6F],
+        HeapType::Exn => vec![0x69],
+        HeapType::Any => vec![0x6E],
+        HeapType::Eq => vec![0x6D],
+        HeapType::I31 => vec![0x6C],
+        HeapType::Struct => vec![0x6B],
+        HeapType::Array => vec![0x6A],
+        HeapType::None => vec![0x71],
+        HeapType::NoFunc => vec![0x73],
+        HeapType::NoExtern => vec![0x72],
+        HeapType::NoExn => vec![0x74],
+        // s33 like the type indices of block types
+        HeapType::Type(typeidx) => signed64_to_leb128(*typeidx as i64),
+    }
+}
+
+// get, get_s and get_u follow each other
+fn field_sign2wasm(sign: &Option<ValSign>) -> u32 {
+    match sign {
+        None => 0,
+        Some(ValSign::S) => 1,
+        Some(ValSign::U) => 2,
     }
 }
 
+fn cast2wasm(labelidx: &LabelIdx, rt1: &RefType, rt2: &RefType) -> Vec<Byte> {
+    let flags = (rt1.nullable as Byte) | (rt2.nullable as Byte) << 1;
+    [vec![flags], labelidx2wasm(labelidx), heaptype2wasm(&rt1.heap), heaptype2wasm(&rt2.heap)].concat()
+}
+
+fn catch2wasm(catch: &Catch) -> Vec<Byte> {
+    match catch {
+        Catch::Tag(tagidx, labelidx) => [vec![0x00], tagidx2wasm(tagidx), labelidx2wasm(labelidx)].concat(),
+        Catch::TagRef(tagidx, labelidx) => [vec![0x01], tagidx2wasm(tagidx), labelidx2wasm(labelidx)].concat(),
+        Catch::All(labelidx) => [vec![0x02], labelidx2wasm(labelidx)].concat(),
+        Catch::AllRef(labelidx) => [vec![0x03], labelidx2wasm(labelidx)].concat(),
+    }
+}
+
+fn reftype2wasm(rt: &RefType) -> Vec<Byte> {
+    valtype2wasm(&rt.valtype())
+}
+
 fn name2wasm(name: &Name) -> Vec<Byte> {
     string2wasm(name)
 }
@@ -656,6 +1250,21 @@ fn unsigned64_to_leb128(n: u64) -> Vec<Byte> {
     }
 }
 
+fn signed64_to_leb128(n: i64) -> Vec<Byte> {
+    let mut encoded = vec![];
+    let mut n_i64 = n;
+    loop {
+        let b = (n_i64 & 0x7F) as Byte;
+        n_i64 >>= 7;
+        if (n_i64 == 0 && b & 0x40 == 0) || (n_i64 == -1 && b & 0x40 != 0) {
+            encoded.push(b);
+            return encoded;
+        } else {
+            encoded.push(b + 0x80);
+        }
+    }
+}
+
 #[test]
 fn test_globaltype2wasm() {
 // let module = Module::default();
@@ -667,9 +1276,9 @@ fn test_globaltype2wasm() {
 
 #[test]
 fn test_limits2wasm() {
-    let lim = Limits { min: 2, max: None };
+    let lim = Limits { min: 2, max: None, page_size_log2: None };
     assert_eq!(limits2wasm(&lim), vec![0x00, 2]);
-    let lim = Limits { min: 5, max: Some(8) };
+    let lim = Limits { min: 5, max: Some(8), page_size_log2: None };
     assert_eq!(limits2wasm(&lim), vec![0x01, 5, 8]);    
 }
 
@@ -686,10 +1295,12 @@ fn test_functype2wasm() {
 }
 
 #[test]
-// fn test_blocktype2wasm() {
-//     assert_eq!(blocktype2wasm(&vec![]), vec![]);
-//     assert_eq!(blocktype2wasm(&vec![ValType::I32]), vec![0x7F]);
-// }
+fn test_blocktype2wasm() {
+    assert_eq!(blocktype2wasm(&BlockType::ValType(None)), vec![0x40]);
+    assert_eq!(blocktype2wasm(&BlockType::ValType(Some(ValType::I32))), vec![0x7F]);
+    assert_eq!(blocktype2wasm(&BlockType::TypeIdx(1)), vec![0x01]);
+    assert_eq!(blocktype2wasm(&BlockType::TypeIdx(64)), vec![0xC0, 0x00]);
+}
 
 #[test]
 fn test_name2wasm() {
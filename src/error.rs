@@ -1,3 +1,5 @@
+use crate::ExnAddr;
+
 #[derive(Debug)]
 pub enum Error {
     Invalid,
@@ -5,4 +7,15 @@ pub enum Error {
     OutOfRange(String),
     Mutability(String),
     PreCondition(String),
+    /// Initializer or segment offset that is not a constant expression.
+    NotConstant(String),
+    ResourceLimit(String),
+    Trap,
+    /// Uncaught exception.
+    Exception(ExnAddr),
+    Interrupted,
+    Suspended,
+    /// Exit of the program requested by a host function, with its status.
+    Exit(u32),
+    Snapshot(String),
 }
@@ -0,0 +1,28 @@
+use crate::{
+    Store,
+    TagAddr,
+    ExnAddr,
+    Val,
+    Error,
+
+    alloc_exn,
+    find_tagtype,
+    val_matches,
+};
+
+/// Allocate an exception of the tag `tagaddr`, for a host function to throw
+/// with `HostResult::Throw`.
+pub fn exn_alloc(store: &mut Store, tagaddr: TagAddr, vals: Vec<Val>) -> Result<ExnAddr, Error> {
+    let functype = find_tagtype(store, tagaddr).ok_or(Error::Invalid)?;
+    if vals.len() != functype.0.len() { return Err(Error::Invalid); }
+    if !vals.iter().zip(&functype.0).all(|(val, valtype)| val_matches(store, val, valtype, &[])) { return Err(Error::Invalid); }
+    Ok(alloc_exn(store, tagaddr, vals))
+}
+
+pub fn exn_tag(store: &Store, exnaddr: ExnAddr) -> TagAddr {
+    store.exns[exnaddr].tag
+}
+
+pub fn exn_read(store: &Store, exnaddr: ExnAddr) -> Vec<Val> {
+    store.exns[exnaddr].fields.clone()
+}
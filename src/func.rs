@@ -6,12 +6,26 @@ use crate::{
     Error,
     FuncType,
     FuncInst,
+    Thread,
+    HostFunc,
+    AsyncHostFunc,
+    HostCode,
+    Invocation,
+    Suspension,
+    SuspendReason,
+    StackEntry,
 
     alloc_hostfunc,
+    val_matches,
 };
 
-pub fn func_alloc(store: &mut Store, functype: FuncType, hostfunc: fn()) -> FuncAddr {
-    alloc_hostfunc(store, functype, hostfunc)
+pub fn func_alloc(store: &mut Store, functype: FuncType, hostfunc: HostFunc) -> Result<FuncAddr, Error> {
+    alloc_hostfunc(store, functype, HostCode::Sync(hostfunc))
+}
+
+/// Allocate a host function whose calls suspend the execution until its future is ready.
+pub fn func_alloc_async(store: &mut Store, functype: FuncType, hostfunc: AsyncHostFunc) -> Result<FuncAddr, Error> {
+    alloc_hostfunc(store, functype, HostCode::Async(hostfunc))
 }
 
 pub fn func_type(store: &Store, funcaddr: FuncAddr) -> FuncType {
@@ -22,6 +36,300 @@ pub fn func_type(store: &Store, funcaddr: FuncAddr) -> FuncType {
 }
 
 pub fn func_invoke<'a>(store: &'a mut Store, funcaddr: FuncAddr, vals: Vec<Val>) -> (&'a mut Store, Result<Vec<Val>, Error>) {
-    Module::invoke(store, funcaddr, vals);
-    unimplemented!()
-}
\ No newline at end of file
+    let result = match Module::invoke(store, funcaddr, vals) {
+        Ok(Invocation::Done(vals)) => Ok(vals),
+        Ok(Invocation::Suspended(_)) => Err(Error::Suspended),
+        Err(error) => Err(error),
+    };
+    (store, result)
+}
+
+/// Like `func_invoke`, but a yielding host function or running out of fuel
+/// suspends the execution instead of failing.
+pub fn func_invoke_resumable(store: &mut Store, funcaddr: FuncAddr, vals: Vec<Val>) -> Result<Invocation, Error> {
+    Module::invoke(store, funcaddr, vals)
+}
+
+/// Continue a suspended execution.
+/// `vals` are the results of the yielding host function, or empty after running out of fuel.
+pub fn func_resume(store: &mut Store, suspension: Suspension, vals: Vec<Val>) -> Result<Invocation, Error> {
+    let returntypes = match &suspension.reason {
+        SuspendReason::Host(funcaddr, _) => func_type(store, *funcaddr).1,
+        SuspendReason::Fuel => vec![],
+    };
+    if returntypes.len() != vals.len() {
+        return Err(Error::Invalid);
+    }
+    // the results are checked in the types of the module waiting for them
+    let types = suspension.stack.iter().rev().find_map(|entry| match entry {
+        StackEntry::Activation(_, frame) => Some(frame.module.types.clone()),
+        _ => None,
+    }).unwrap_or_default();
+    for (returntype, val) in returntypes.iter().zip(vals.iter()) {
+        if !val_matches(store, val, returntype, &types) { return Err(Error::Invalid); }
+    }
+    let mut thread = Thread::new(store);
+    thread.resume(suspension, vals)
+}
+
+/// Invoke as a future, awaiting the pending calls of async host functions.
+/// Synchronous yields and running out of fuel fail with `Error::Suspended`.
+pub async fn func_invoke_async(store: &mut Store, funcaddr: FuncAddr, vals: Vec<Val>) -> Result<Vec<Val>, Error> {
+    let mut invocation = Module::invoke(store, funcaddr, vals)?;
+    loop {
+        match invocation {
+            Invocation::Done(vals) => return Ok(vals),
+            Invocation::Suspended(mut suspension) => {
+                let vals = match suspension.future.take() {
+                    Some(future) => future.await?,
+                    None => return Err(Error::Suspended),
+                };
+                invocation = func_resume(store, suspension, vals)?;
+            },
+        }
+    }
+}
+
+#[test]
+fn test_func_resume() {
+    use crate::{store_init, store_set_fuel, Func, Expr, Instr, ModuleInst, ValType, HostResult};
+
+    fn yielding(_store: &mut Store, _args: &[Val]) -> HostResult {
+        HostResult::Yield
+    }
+
+    // (func (param i32) (result i32) local.get 0 call $yielding)
+    let mut store = store_init();
+    let functype = (vec![ValType::I32], vec![ValType::I32]);
+    let hostaddr = func_alloc(&mut store, functype.clone(), yielding).unwrap();
+    let body = Expr(vec![Instr::LocalGet(0), Instr::Call(0)]);
+    let func = Func { tp: 0, locals: vec![], body };
+    let moduleinst = ModuleInst { types: std::sync::Arc::new(vec![functype.clone().into()]), funcaddrs: vec![hostaddr], ..ModuleInst::default() };
+    store.funcs.push(FuncInst::user(functype, moduleinst, func));
+    let funcaddr = store.funcs.len() - 1;
+
+    let suspension = match func_invoke_resumable(&mut store, funcaddr, vec![Val::I32Const(7)]) {
+        Ok(Invocation::Suspended(suspension)) => suspension,
+        _ => panic!("expected a suspension"),
+    };
+    assert_eq!(suspension.reason, SuspendReason::Host(hostaddr, vec![Val::I32Const(7)]));
+    assert!(func_resume(&mut store, suspension, vec![]).is_err());
+
+    let suspension = match func_invoke_resumable(&mut store, funcaddr, vec![Val::I32Const(7)]) {
+        Ok(Invocation::Suspended(suspension)) => suspension,
+        _ => panic!("expected a suspension"),
+    };
+    match func_resume(&mut store, suspension, vec![Val::I32Const(8)]) {
+        Ok(Invocation::Done(vals)) => assert_eq!(vals, vec![Val::I32Const(8)]),
+        _ => panic!("expected completion"),
+    }
+
+    // without a way to resume, the yield is an error
+    let (store, result) = func_invoke(&mut store, funcaddr, vec![Val::I32Const(7)]);
+    assert!(matches!(result, Err(Error::Suspended)));
+
+    // three instructions given one unit of fuel at a time suspend twice
+    store.funcs[funcaddr] = FuncInst::user(
+        (vec![], vec![ValType::I32]),
+        ModuleInst::default(),
+        Func { tp: 0, locals: vec![], body: Expr(vec![Instr::I32Const(1), Instr::I32Const(2), Instr::Drop(None)]) },
+    );
+    store_set_fuel(store, 1);
+    let mut invocation = func_invoke_resumable(store, funcaddr, vec![]).unwrap();
+    let mut suspended = 0;
+    while let Invocation::Suspended(suspension) = invocation {
+        assert_eq!(suspension.reason, SuspendReason::Fuel);
+        suspended += 1;
+        store_set_fuel(store, 1);
+        invocation = func_resume(store, suspension, vec![]).unwrap();
+    }
+    assert_eq!(suspended, 2);
+    assert!(matches!(invocation, Invocation::Done(vals) if vals == vec![Val::I32Const(1)]));
+}
+
+#[test]
+fn test_func_typed_refs() {
+    use crate::{store_init, Func, Expr, Instr, ModuleInst, ValType, RefType, HeapType, Ref, ElemType, HostResult};
+
+    fn noop(_store: &mut Store, _args: &[Val]) -> HostResult {
+        HostResult::Vals(vec![])
+    }
+    fn yielding(_store: &mut Store, _args: &[Val]) -> HostResult {
+        HostResult::Yield
+    }
+
+    // (func (param (ref null $noop)) (result (ref $noop)) local.get 0 call $yielding)
+    let mut store = store_init();
+    let typed = |nullable| ValType::Ref(RefType { nullable, heap: HeapType::Type(0) });
+    let functype = (vec![typed(true)], vec![typed(false)]);
+    let noopaddr = func_alloc(&mut store, (vec![], vec![]), noop).unwrap();
+    let hostaddr = func_alloc(&mut store, functype.clone(), yielding).unwrap();
+    let types = vec![(vec![], vec![]).into(), functype.clone().into()];
+    let moduleinst = ModuleInst { types: std::sync::Arc::new(types), funcaddrs: vec![hostaddr], ..ModuleInst::default() };
+    let body = Expr(vec![Instr::LocalGet(0), Instr::Call(0)]);
+    store.funcs.push(FuncInst::user(functype, moduleinst, Func { tp: 1, locals: vec![], body }));
+    let funcaddr = store.funcs.len() - 1;
+
+    let noopref = Val::Ref(Ref::Func(noopaddr));
+    assert!(func_invoke_resumable(&mut store, funcaddr, vec![Val::Ref(Ref::Null(ElemType::ExternRef))]).is_err());
+    assert!(func_invoke_resumable(&mut store, funcaddr, vec![Val::Ref(Ref::Func(hostaddr))]).is_err());
+    let suspension = match func_invoke_resumable(&mut store, funcaddr, vec![Val::Ref(Ref::Null(ElemType::FuncRef))]) {
+        Ok(Invocation::Suspended(suspension)) => suspension,
+        _ => panic!("expected a suspension"),
+    };
+    assert!(func_resume(&mut store, suspension, vec![Val::Ref(Ref::Null(ElemType::FuncRef))]).is_err());
+    let suspension = match func_invoke_resumable(&mut store, funcaddr, vec![noopref]) {
+        Ok(Invocation::Suspended(suspension)) => suspension,
+        _ => panic!("expected a suspension"),
+    };
+    match func_resume(&mut store, suspension, vec![noopref]) {
+        Ok(Invocation::Done(vals)) => assert_eq!(vals, vec![noopref]),
+        _ => panic!("expected completion"),
+    }
+}
+
+#[test]
+fn test_func_invoke_async() {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use crate::{store_init, Func, Expr, Instr, ModuleInst, ValType, HostFuture, IBinOp, ValSize};
+
+    struct ThreadWaker(std::thread::Thread);
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) { self.0.unpark(); }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    // ready on the second poll, like a read waiting for I/O
+    struct Delayed(bool, u32);
+    impl Future for Delayed {
+        type Output = Result<Vec<Val>, Error>;
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+            if self.0 {
+                Poll::Ready(Ok(vec![Val::I32Const(self.1 * 2)]))
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    fn double(_store: &mut Store, args: &[Val]) -> HostFuture {
+        match args[0] {
+            Val::I32Const(n) => Box::pin(Delayed(false, n)),
+            _ => unreachable!(),
+        }
+    }
+
+    // (func (param i32) (result i32) local.get 0 call $double i32.const 1 i32.add)
+    let mut store = store_init();
+    let functype = (vec![ValType::I32], vec![ValType::I32]);
+    let hostaddr = func_alloc_async(&mut store, functype.clone(), double).unwrap();
+    let body = Expr(vec![
+        Instr::LocalGet(0),
+        Instr::Call(0),
+        Instr::I32Const(1),
+        Instr::IBinOp(ValSize::V32, IBinOp::Add),
+    ]);
+    let func = Func { tp: 0, locals: vec![], body };
+    let moduleinst = ModuleInst { types: std::sync::Arc::new(vec![functype.clone().into()]), funcaddrs: vec![hostaddr], ..ModuleInst::default() };
+    store.funcs.push(FuncInst::user(functype, moduleinst, func));
+    let funcaddr = store.funcs.len() - 1;
+
+    let result = block_on(func_invoke_async(&mut store, funcaddr, vec![Val::I32Const(20)]));
+    assert_eq!(result.unwrap(), vec![Val::I32Const(41)]);
+}
+
+#[test]
+fn test_func_invoke_multi_value() {
+    use crate::{store_init, module_validate, Instr, BlockType, ValType, IBinOp, ValSize};
+    use crate::testing::{exported_funcs, TestInstance};
+
+    // (func (param i32 i32) (result i32 i32)
+    //   local.get 0 local.get 1
+    //   block (param i32 i32) (result i32 i32)
+    //     i32.sub local.get 0 local.get 1 i32.add
+    //   end)
+    let multi_value = |block: Vec<Instr>| exported_funcs(
+        vec![(vec![ValType::I32, ValType::I32], vec![ValType::I32, ValType::I32])],
+        vec![("f", 0, vec![Instr::LocalGet(0), Instr::LocalGet(1), Instr::Block(BlockType::TypeIdx(0), block.into())])],
+    );
+    let block = vec![
+        Instr::IBinOp(ValSize::V32, IBinOp::Sub),
+        Instr::LocalGet(0),
+        Instr::LocalGet(1),
+        Instr::IBinOp(ValSize::V32, IBinOp::Add),
+    ];
+
+    let mut store = store_init();
+    let instance = TestInstance::new(&mut store, multi_value(block), vec![]);
+    let result = instance.call(&mut store, "f", vec![Val::I32Const(5), Val::I32Const(3)]);
+    assert_eq!(result.unwrap(), vec![Val::I32Const(2), Val::I32Const(8)]);
+
+    // the block leaves a single value for two results
+    let block = vec![Instr::IBinOp(ValSize::V32, IBinOp::Sub)];
+    assert!(module_validate(multi_value(block)).is_err());
+}
+
+#[test]
+fn test_typed_func_import() {
+    use crate::{
+        store_init, module_instanciate,
+        Module, Table, TableType, Limits, Elem, ElemMode, ImportDesc, Export, ExportDesc, ExternVal, Expr, Instr,
+        ValType, RefType, HeapType, SubType, CompType, FieldType, StorageType, Mut, Error,
+    };
+    use crate::testing::{exported_funcs, import, TestInstance};
+
+    let strukt = |vt| SubType { is_final: true, supers: vec![], rec: (0, 1), comp: CompType::Struct(vec![FieldType(StorageType::Val(vt), Mut::Const)]) };
+    let takes = |typeidx| SubType::from((vec![ValType::Ref(RefType { nullable: true, heap: HeapType::Type(typeidx) })], vec![]));
+    let funcref = RefType { nullable: true, heap: HeapType::Func };
+    let table = || TableType(Limits { min: 1, max: None, page_size_log2: None }, funcref);
+
+    // (func (param (ref null $s)) with $s a struct of an i32, at index 0
+    let mut exporting = Module {
+        types: vec![strukt(ValType::I32), takes(0)],
+        tables: vec![Table(table(), None)],
+        elem: vec![Elem {
+            tp: funcref,
+            init: vec![Expr(vec![Instr::RefFunc(0)])],
+            mode: ElemMode::Active { table: 0, offset: Expr(vec![Instr::I32Const(0)]) },
+        }],
+        ..exported_funcs(vec![], vec![("f", 1, vec![])])
+    };
+    exporting.exports.push(Export { name: "t".to_string(), desc: ExportDesc::Table(0) });
+    // the same struct at index 1, and a struct of an i64 at index 3
+    let types = || vec![(vec![], vec![]).into(), strukt(ValType::I32), takes(1), strukt(ValType::I64), takes(3)];
+
+    let mut store = store_init();
+    let exported = TestInstance::new(&mut store, exporting, vec![]);
+    let f = ExternVal::Func(exported.func("f"));
+    let importing = |typeidx| Module { types: types(), imports: vec![import("f", ImportDesc::Func(typeidx))], ..Module::default() };
+    assert!(module_instanciate(&mut store, importing(2), vec![f.clone()]).is_ok());
+    assert!(module_instanciate(&mut store, importing(4), vec![f]).is_err());
+
+    let calling = Module {
+        types: types(),
+        imports: vec![import("t", ImportDesc::Table(table()))],
+        ..exported_funcs(vec![], vec![
+            ("same", 0, vec![Instr::RefNull(HeapType::Type(1)), Instr::I32Const(0), Instr::CallIndirect(0, 2)]),
+            ("other", 0, vec![Instr::RefNull(HeapType::Type(3)), Instr::I32Const(0), Instr::CallIndirect(0, 4)]),
+        ])
+    };
+    let calling = TestInstance::new(&mut store, calling, vec![exported.export("t")]);
+    assert_eq!(calling.call(&mut store, "same", vec![]).unwrap(), vec![]);
+    assert!(matches!(calling.call(&mut store, "other", vec![]), Err(Error::Trap)));
+}
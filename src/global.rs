@@ -1,17 +1,19 @@
+use std::sync::Arc;
+
 use crate::{
     Store,
     GlobalType,
     Val,
     GlobalAddr,
     Error,
-    Mut,
 
     alloc_global,
+    val_matches,
     find_globaltype,
 };
 
 pub fn global_alloc(store: &mut Store, globaltype: GlobalType, val: Val) -> GlobalAddr {
-    alloc_global(store, globaltype, val)
+    alloc_global(store, globaltype, Arc::default(), val)
 }
 
 pub fn global_type(store: &Store, globaladdr: GlobalAddr) -> GlobalType {
@@ -24,8 +26,111 @@ pub fn global_read(store: &Store, globaladdr: GlobalAddr) -> Val {
 }
 
 pub fn global_write(store: &mut Store, globaladdr: GlobalAddr, val: Val) -> Result<(), Error> {
-    let gi = &mut store.globals[globaladdr];
-    if gi.mutability != Mut::Var { return Err(Error::Invalid); }
-    gi.value = val;
+    let gi = &store.globals[globaladdr];
+    if !gi.tp.is_var() { return Err(Error::Mutability("global write: immutable global".to_string())); }
+    if !val_matches(store, &val, &gi.tp.0, &gi.types) { return Err(Error::Invalid); }
+    store.globals[globaladdr].value = val;
     Ok(())
 }
+
+#[test]
+fn test_constant_expr() {
+    use crate::{store_init, module_validate, Module, Global, Import, ImportDesc, Export, ExportDesc, ExternVal, Expr, Instr, ValType, ValSize, ValSign, IBinOp, Mut};
+    use crate::testing::TestInstance;
+
+    let module = |mutability: Mut, init: Vec<Instr>| Module {
+        imports: vec![Import {
+            module: "env".to_string(),
+            name: "g".to_string(),
+            desc: ImportDesc::Global(GlobalType(ValType::I32, mutability)),
+        }],
+        globals: vec![Global { tp: GlobalType(ValType::I32, Mut::Const), init: Expr(init) }],
+        exports: vec![Export { name: "g".to_string(), desc: ExportDesc::Global(1) }],
+        ..Module::default()
+    };
+    let extended = vec![
+        Instr::GlobalGet(0), Instr::I32Const(3), Instr::IBinOp(ValSize::V32, IBinOp::Mul),
+        Instr::I32Const(1), Instr::IBinOp(ValSize::V32, IBinOp::Add),
+    ];
+
+    let mut store = store_init();
+    let imported = global_alloc(&mut store, GlobalType(ValType::I32, Mut::Const), Val::I32Const(5));
+    let instance = TestInstance::new(&mut store, module(Mut::Const, extended), vec![ExternVal::Global(imported)]);
+    assert_eq!(global_read(&store, instance.global("g")), Val::I32Const(16));
+
+    // mutable globals and other instructions are not constant
+    let result = module_validate(module(Mut::Var, vec![Instr::GlobalGet(0)]));
+    assert!(matches!(result, Err(Error::NotConstant(_))));
+    let result = module_validate(module(Mut::Const, vec![
+        Instr::I32Const(6), Instr::I32Const(2), Instr::IBinOp(ValSize::V32, IBinOp::Div(ValSign::S)),
+    ]));
+    assert!(matches!(result, Err(Error::NotConstant(_))));
+    // only the imported globals are visible
+    assert!(module_validate(module(Mut::Const, vec![Instr::GlobalGet(1)])).is_err());
+}
+
+#[test]
+fn test_mutable_global_import() {
+    use crate::{store_init, module_instanciate, func_invoke, Module, Global, ImportDesc, Export, ExportDesc, ExternVal, Expr, Instr, ValType, ValSize, IBinOp, Mut};
+    use crate::testing::{exported_funcs, import, TestInstance};
+
+    let mut exporting = Module {
+        globals: vec![Global { tp: GlobalType(ValType::I32, Mut::Var), init: Expr(vec![Instr::I32Const(1)]) }],
+        ..exported_funcs(vec![(vec![], vec![])], vec![("inc", 0, vec![
+            Instr::GlobalGet(0), Instr::I32Const(1), Instr::IBinOp(ValSize::V32, IBinOp::Add), Instr::GlobalSet(0),
+        ])])
+    };
+    exporting.exports.push(Export { name: "g".to_string(), desc: ExportDesc::Global(0) });
+    let importing = |mutability: Mut| Module {
+        imports: vec![import("g", ImportDesc::Global(GlobalType(ValType::I32, mutability)))],
+        ..exported_funcs(vec![(vec![], vec![ValType::I32])], vec![("get", 0, vec![Instr::GlobalGet(0)])])
+    };
+
+    let mut store = store_init();
+    let inst_a = TestInstance::new(&mut store, exporting, vec![]);
+    let globaladdr = inst_a.global("g");
+
+    // the mutability of the import must match
+    assert!(module_instanciate(&mut store, importing(Mut::Const), vec![ExternVal::Global(globaladdr)]).is_err());
+    let inst_b = TestInstance::new(&mut store, importing(Mut::Var), vec![ExternVal::Global(globaladdr)]);
+
+    // both instances see the writes of the other and of the host
+    inst_a.call(&mut store, "inc", vec![]).unwrap();
+    assert_eq!(inst_b.call(&mut store, "get", vec![]).unwrap(), vec![Val::I32Const(2)]);
+    global_write(&mut store, globaladdr, Val::I32Const(10)).unwrap();
+    let (store, result) = func_invoke(&mut store, inst_b.func("get"), vec![]);
+    assert_eq!(result.unwrap(), vec![Val::I32Const(10)]);
+
+    let constant = global_alloc(store, GlobalType(ValType::I32, Mut::Const), Val::I32Const(0));
+    assert!(matches!(global_write(store, constant, Val::I32Const(1)), Err(Error::Mutability(_))));
+    // values of another type are rejected
+    assert!(matches!(global_write(store, globaladdr, Val::I64Const(1)), Err(Error::Invalid)));
+    assert_eq!(global_read(store, globaladdr), Val::I32Const(10));
+}
+
+#[test]
+fn test_typed_global_import() {
+    use crate::{store_init, module_instanciate, Module, Global, ImportDesc, Export, ExportDesc, ExternVal, Expr, Instr, ValType, RefType, HeapType, Mut};
+    use crate::testing::{import, TestInstance};
+
+    // the exported global refers to the second type of its module
+    let exporting = Module {
+        types: vec![(vec![], vec![]).into(), (vec![], vec![ValType::I32]).into()],
+        globals: vec![Global {
+            tp: GlobalType(ValType::Ref(RefType { nullable: true, heap: HeapType::Type(1) }), Mut::Const),
+            init: Expr(vec![Instr::RefNull(HeapType::Type(1))]),
+        }],
+        exports: vec![Export { name: "g".to_string(), desc: ExportDesc::Global(0) }],
+        ..Module::default()
+    };
+    let importing = |params: Vec<ValType>| Module {
+        types: vec![(params, vec![ValType::I32]).into()],
+        imports: vec![import("g", ImportDesc::Global(GlobalType(ValType::Ref(RefType { nullable: true, heap: HeapType::Type(0) }), Mut::Const)))],
+        ..Module::default()
+    };
+
+    let mut store = store_init();
+    let globaladdr = TestInstance::new(&mut store, exporting, vec![]).global("g");
+    assert!(module_instanciate(&mut store, importing(vec![]), vec![ExternVal::Global(globaladdr)]).is_ok());
+    assert!(module_instanciate(&mut store, importing(vec![ValType::I64]), vec![ExternVal::Global(globaladdr)]).is_err());
+}
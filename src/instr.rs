@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 mod validate;
 
 pub use validate::{
@@ -7,11 +9,19 @@ pub use validate::{
 
 use crate::{
     ValType,
+    RefType,
+    HeapType,
     TypeIdx,
     FuncIdx,
+    TableIdx,
     GlobalIdx,
     LocalIdx,
     LabelIdx,
+    ElemIdx,
+    MemIdx,
+    DataIdx,
+    TagIdx,
+    FieldIdx,
     // FuncAddr,
     // TableAddr,
     // MemAddr,
@@ -24,17 +34,33 @@ use crate::{
 pub struct Expr(pub Vec<Instr>);
 
 impl Expr {
-    pub fn is_constant(&self) -> bool {
-        unimplemented!()
+    /// Whether some instruction refers to a data segment, which requires
+    /// the data count section in the binary format.
+    pub fn uses_data_indices(&self) -> bool {
+        uses_data_indices(&self.0)
     }
 }
 
+fn uses_data_indices(instrs: &[Instr]) -> bool {
+    instrs.iter().any(|instr| match instr {
+        Instr::MemoryInit(_, _) | Instr::DataDrop(_) => true,
+        Instr::Block(_, instrs) | Instr::Loop(_, instrs) => uses_data_indices(instrs),
+        Instr::If(_, instrs1, instrs2) => {
+            uses_data_indices(instrs1) || instrs2.as_deref().is_some_and(uses_data_indices)
+        },
+        _ => false,
+    })
+}
+
 #[derive(PartialEq, Clone, Debug, Default)]
 pub struct MemArg {
-    pub offset: u32,
+    pub offset: u64,
     pub align: u32,
+    pub memory: MemIdx,
 }
 
+pub type LaneIdx = u8;
+
 #[derive(PartialEq, Clone, Debug)]
 pub enum BlockType {
     TypeIdx(TypeIdx),
@@ -44,7 +70,8 @@ impl BlockType {
     pub fn extend(&self, moduleinst: &ModuleInst) -> FuncType {
         match self {
             BlockType::TypeIdx(typeidx) => {
-                moduleinst.types[typeidx.clone() as usize].clone()
+                // validated to be a function type
+                moduleinst.types[typeidx.clone() as usize].functype().cloned().unwrap_or_default()
             },
             BlockType::ValType(None) => {
                 (vec![], vec![])
@@ -55,6 +82,19 @@ impl BlockType {
         }
     }
 }
+/// Clause of `try_table`, branching to its label with the caught values.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Catch {
+    /// `catch`, for exceptions of the tag.
+    Tag(TagIdx, LabelIdx),
+    /// `catch_ref`, which also passes the exception itself.
+    TagRef(TagIdx, LabelIdx),
+    /// `catch_all`
+    All(LabelIdx),
+    /// `catch_all_ref`
+    AllRef(LabelIdx),
+}
+
 impl Default for BlockType {
     fn default() -> Self {
         BlockType::ValType(None)
@@ -65,9 +105,11 @@ pub enum Instr {
     /* Block Instructions */
 
     // Control Instructions
-    Block(BlockType, Vec<Instr>),
-    Loop(BlockType, Vec<Instr>),
-    If(BlockType, Vec<Instr>, Option<Vec<Instr>>),
+    // bodies are shared with the labels running them
+    Block(BlockType, Arc<[Instr]>),
+    Loop(BlockType, Arc<[Instr]>),
+    If(BlockType, Arc<[Instr]>, Option<Arc<[Instr]>>),
+    TryTable(BlockType, Vec<Catch>, Arc<[Instr]>),
 
     /* Plain Instructions */
 
@@ -77,9 +119,51 @@ pub enum Instr {
     Br(LabelIdx),
     BrIf(LabelIdx),
     BrTable(Vec<LabelIdx>, LabelIdx),
+    BrOnNull(LabelIdx),
+    BrOnNonNull(LabelIdx),
     Return,
     Call(FuncIdx),
-    CallIndirect(FuncIdx),
+    CallIndirect(TableIdx, TypeIdx),
+    ReturnCall(FuncIdx),
+    ReturnCallIndirect(TableIdx, TypeIdx),
+    CallRef(TypeIdx),
+    ReturnCallRef(TypeIdx),
+    Throw(TagIdx),
+    ThrowRef,
+
+    // Reference Instructions
+    RefNull(HeapType),
+    RefIsNull,
+    RefFunc(FuncIdx),
+    RefAsNonNull,
+    RefEq,
+    RefTest(RefType),
+    RefCast(RefType),
+    BrOnCast(LabelIdx, RefType, RefType),
+    BrOnCastFail(LabelIdx, RefType, RefType),
+
+    // Aggregate Instructions
+    StructNew(TypeIdx),
+    StructNewDefault(TypeIdx),
+    /// Packed fields are extended by the sign.
+    StructGet(Option<ValSign>, TypeIdx, FieldIdx),
+    StructSet(TypeIdx, FieldIdx),
+    ArrayNew(TypeIdx),
+    ArrayNewDefault(TypeIdx),
+    ArrayNewFixed(TypeIdx, u32),
+    ArrayGet(Option<ValSign>, TypeIdx),
+    ArraySet(TypeIdx),
+    ArrayLen,
+    ArrayFill(TypeIdx),
+    ArrayCopy(TypeIdx, TypeIdx),
+    ArrayNewData(TypeIdx, DataIdx),
+    ArrayNewElem(TypeIdx, ElemIdx),
+    ArrayInitData(TypeIdx, DataIdx),
+    ArrayInitElem(TypeIdx, ElemIdx),
+    RefI31,
+    I31Get(ValSign),
+    AnyConvertExtern,
+    ExternConvertAny,
 
     // Parametric Instructions
     Drop(Option<ValType>),
@@ -101,8 +185,22 @@ pub enum Instr {
     IStore8(ValSize, MemArg),
     IStore16(ValSize, MemArg),
     I64Store32(MemArg),
-    MemorySize,
-    MemoryGrow,
+    MemorySize(MemIdx),
+    MemoryGrow(MemIdx),
+    MemoryFill(MemIdx),
+    MemoryCopy(MemIdx, MemIdx),
+    MemoryInit(MemIdx, DataIdx),
+    DataDrop(DataIdx),
+
+    // Table Instructions
+    TableGet(TableIdx),
+    TableSet(TableIdx),
+    TableSize(TableIdx),
+    TableGrow(TableIdx),
+    TableFill(TableIdx),
+    TableCopy(TableIdx, TableIdx),
+    TableInit(TableIdx, ElemIdx),
+    ElemDrop(ElemIdx),
 
     // Numeric Instructions
     I32Const(u32),
@@ -123,6 +221,61 @@ pub enum Instr {
 
     CvtOp(CvtOp),
 
+    // Vector Instructions
+    V128Const(u128),
+    V128Not,
+    VVBinOp(VVBinOp),
+    V128Bitselect,
+    V128AnyTrue,
+    I8x16Shuffle([LaneIdx; 16]),
+    I8x16Swizzle,
+    Splat(Shape),
+    ExtractLane(Shape, Option<ValSign>, LaneIdx),
+    ReplaceLane(Shape, LaneIdx),
+
+    VIRelOp(Shape, IRelOp),
+    VFRelOp(Shape, FRelOp),
+    VIUnOp(Shape, VIUnOp),
+    VFUnOp(Shape, FUnOp),
+    VIBinOp(Shape, VIBinOp),
+    VFBinOp(Shape, VFBinOp),
+    VIShiftOp(Shape, VIShiftOp),
+    AllTrue(Shape),
+    Bitmask(Shape),
+    Narrow(Shape, ValSign),
+    ExtMul(Shape, Half, ValSign),
+    ExtAddPairwise(Shape, ValSign),
+    I32x4DotI16x8S,
+    VCvtOp(VCvtOp),
+
+    // Relaxed Vector Instructions
+    I8x16RelaxedSwizzle,
+    /// `F32x4`, or `F64x2` zeroing the upper lanes.
+    RelaxedTrunc(Shape, ValSign),
+    RelaxedMadd(Shape),
+    RelaxedNmadd(Shape),
+    RelaxedLaneselect(Shape),
+    RelaxedMin(Shape),
+    RelaxedMax(Shape),
+    I16x8RelaxedQ15mulrS,
+    I16x8RelaxedDotI8x16I7x16S,
+    I32x4RelaxedDotI8x16I7x16AddS,
+
+    V128LoadExtend(Shape, ValSign, MemArg),
+    V128LoadSplat(Shape, MemArg),
+    V128LoadZero(ValSize, MemArg),
+    V128LoadLane(Shape, MemArg, LaneIdx),
+    V128StoreLane(Shape, MemArg, LaneIdx),
+
+    // Atomic Memory Instructions
+    AtomicNotify(MemArg),
+    AtomicWait(ValSize, MemArg),
+    AtomicFence,
+    AtomicLoad(ValSize, Option<PackSize>, MemArg),
+    AtomicStore(ValSize, Option<PackSize>, MemArg),
+    AtomicRmw(ValSize, Option<PackSize>, AtomicRmwOp, MemArg),
+    AtomicCmpxchg(ValSize, Option<PackSize>, MemArg),
+
     // Administrative Instructions
     // Trap,
     // Invoke(FuncAddr),
@@ -177,3 +330,87 @@ pub enum CvtOp {
     IReinterpretFromF(ValSize),
     FReinterpretFromI(ValSize),
 }
+
+/// Narrower width of an atomic access, which zero-extends on load.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PackSize { P8, P16, P32, }
+
+/// Width in bits of an atomic access, the full value unless packed.
+pub fn atomic_width(valsize: &ValSize, packsize: &Option<PackSize>) -> u32 {
+    match (valsize, packsize) {
+        (_, Some(PackSize::P8)) => 8,
+        (_, Some(PackSize::P16)) => 16,
+        (_, Some(PackSize::P32)) | (ValSize::V32, None) => 32,
+        (ValSize::V64, None) => 64,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AtomicRmwOp { Add, Sub, And, Or, Xor, Xchg, }
+
+/// Interpretation of a `v128` as lanes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape { I8x16, I16x8, I32x4, I64x2, F32x4, F64x2, }
+
+impl Shape {
+    pub fn lanes(&self) -> usize {
+        match self {
+            Shape::I8x16 => 16,
+            Shape::I16x8 => 8,
+            Shape::I32x4 | Shape::F32x4 => 4,
+            Shape::I64x2 | Shape::F64x2 => 2,
+        }
+    }
+
+    /// Width of a lane in bits.
+    pub fn width(&self) -> u32 {
+        128 / self.lanes() as u32
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Shape::F32x4 | Shape::F64x2)
+    }
+
+    /// Shape with lanes of half the width, twice as many.
+    pub fn half(&self) -> Shape {
+        match self {
+            Shape::I16x8 => Shape::I8x16,
+            Shape::I32x4 => Shape::I16x8,
+            Shape::I64x2 => Shape::I32x4,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Half { Low, High, }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VVBinOp { And, AndNot, Or, Xor, }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VIUnOp { Abs, Neg, Popcnt, }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VIBinOp {
+    Add, Sub, Mul, AddSat(ValSign), SubSat(ValSign),
+    Min(ValSign), Max(ValSign), AvgrU, Q15MulrSatS,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VFBinOp { Add, Sub, Mul, Div, Min, Max, PMin, PMax, }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VIShiftOp { Shl, Shr(ValSign), }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VCvtOp {
+    /// `shape.extend_half_(shape.half())_sx`
+    Extend(Shape, Half, ValSign),
+    /// `i32x4.trunc_sat_shape_sx`, the `f64x2` form zeroing the high lanes
+    TruncSat(Shape, ValSign),
+    /// `shape.convert_i32x4_sx`, the `f64x2` form converting the low lanes
+    Convert(Shape, ValSign),
+    F32x4DemoteF64x2Zero,
+    F64x2PromoteLowF32x4,
+}
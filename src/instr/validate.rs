@@ -2,23 +2,43 @@ mod sequence;
 
 use crate::{
     ValType as ValTypeOriginal,
+    RefType,
+    HeapType,
     ResultType as ResultTypeOriginal,
     FuncType as FuncTypeOriginal,
     TableType,
+    CompType,
+    FieldType,
+    Mut,
     GlobalType,
     TypeIdx,
+    FuncIdx,
+    TableIdx,
+    MemIdx,
+    ElemIdx,
+    DataIdx,
     GlobalIdx,
     LocalIdx,
     LabelIdx,
+    TagIdx,
+    FieldIdx,
+    StorageType,
     Error,
     Context,
     MemArg,
 };
 use super::{
     BlockType,
+    Catch,
     Instr,
     ValSize,
+    ValSign,
+    IBinOp,
     CvtOp,
+    Shape,
+    LaneIdx,
+    PackSize,
+    atomic_width,
 };
 
 #[macro_export]
@@ -48,6 +68,9 @@ macro_rules! instr_tp {
     ($arg1: ident $arg2: ident ->) => {
         ft!(vec![ValType::$arg1, ValType::$arg2], vec![])
     };
+    ($arg1: ident $arg2: ident $arg3: ident ->) => {
+        ft!(vec![ValType::$arg1, ValType::$arg2, ValType::$arg3], vec![])
+    };
     (() -> ()) => {
         ft!(vec![], vec![])
     };
@@ -58,13 +81,31 @@ macro_rules! instr_tp {
 
 type TypeValIdx = u32;
 
+// the kinds of values a type variable(TypeVal) stands for
+const ANY: TypeValIdx = 0;
+// numeric or vector
+const NUM: TypeValIdx = 1;
+const REF: TypeValIdx = 2;
+
 #[derive(Clone, PartialEq)]
 pub enum ValType {
     I32, I64, F32, F64,
+    V128,
+    FuncRef, ExternRef, ExnRef,
+    Ref(RefType),
     TypeVal(TypeValIdx),
     Ellipsis,
 }
 
+impl ValType {
+    fn reftype(&self) -> Option<RefType> {
+        match self {
+            ValType::FuncRef | ValType::ExternRef | ValType::ExnRef | ValType::Ref(_) => vt_rev(self).reftype(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub struct ResultType(pub Vec<ValType>);
 
@@ -82,14 +123,6 @@ impl ResultType {
     //     self.0.len()
     // }
 
-    fn last(&self) -> Option<&ValType> {
-        self.0.last()
-    }
-
-    fn last2(&self) -> Option<&ValType> {
-        self.0.get(self.0.len() - 2)
-    }
-
     // fn valtypes(&self) -> Vec<ValType> {
     //     self.0
     // }
@@ -103,6 +136,11 @@ pub fn vt(vt: &ValTypeOriginal) -> ValType {
         ValTypeOriginal::I64 => ValType::I64,
         ValTypeOriginal::F32 => ValType::F32,
         ValTypeOriginal::F64 => ValType::F64,
+        ValTypeOriginal::V128 => ValType::V128,
+        ValTypeOriginal::FuncRef => ValType::FuncRef,
+        ValTypeOriginal::ExternRef => ValType::ExternRef,
+        ValTypeOriginal::ExnRef => ValType::ExnRef,
+        ValTypeOriginal::Ref(reftype) => ValType::Ref(*reftype),
     }
 }
 
@@ -112,12 +150,82 @@ pub fn vt_rev(vt: &ValType) -> ValTypeOriginal {
         ValType::I64 => ValTypeOriginal::I64,
         ValType::F32 => ValTypeOriginal::F32,
         ValType::F64 => ValTypeOriginal::F64,
+        ValType::V128 => ValTypeOriginal::V128,
+        ValType::FuncRef => ValTypeOriginal::FuncRef,
+        ValType::ExternRef => ValTypeOriginal::ExternRef,
+        ValType::ExnRef => ValTypeOriginal::ExnRef,
+        ValType::Ref(reftype) => ValTypeOriginal::Ref(*reftype),
         _ => unimplemented!(),
     }
 }
 
+// type of the scalar in each lane
+fn unpacked(shape: &Shape) -> ValType {
+    match shape {
+        Shape::I8x16 | Shape::I16x8 | Shape::I32x4 => ValType::I32,
+        Shape::I64x2 => ValType::I64,
+        Shape::F32x4 => ValType::F32,
+        Shape::F64x2 => ValType::F64,
+    }
+}
+
+// type of the operand of an atomic access, an i32 can't be accessed as 32 packed bits
+fn atomic_type(valsize: &ValSize, packsize: &Option<PackSize>) -> Result<ValType, Error> {
+    match (valsize, packsize) {
+        (ValSize::V32, Some(PackSize::P32)) => Err(Error::Invalid),
+        (ValSize::V32, _) => Ok(ValType::I32),
+        (ValSize::V64, _) => Ok(ValType::I64),
+    }
+}
+
 impl Instr {
+    // the instructions holding a sequence recurse through here, so the match over
+    // all the others stays off the stack of nested blocks
     fn validate(&self, context: &Context) -> Result<FuncType, Error> {
+        match &self {
+            Instr::Block(blocktype, instrs) => {
+                let ft = blocktype.validate(context)?;
+                let vts: Vec<ValTypeOriginal> = ft.1.iter().map(vt_rev).collect();
+                let context = context.clone_with_labels(vts);
+                Instr::validate_instr_sequence(&context, instrs, &ft)?;
+                Ok(ft)
+            },
+            Instr::Loop(blocktype, instrs) => {
+                let ft = blocktype.validate(context)?;
+                let vts: Vec<ValTypeOriginal> = ft.0.iter().map(vt_rev).collect();
+                let context = context.clone_with_labels(vts);
+                Instr::validate_instr_sequence(&context, instrs, &ft)?;
+                Ok(ft)
+            },
+            Instr::If(blocktype, instrs1, instrs2) => {
+                let ft = blocktype.validate(context)?;
+                let vts: Vec<ValTypeOriginal> = ft.1.iter().map(vt_rev).collect();
+                let context = context.clone_with_labels(vts);
+                Instr::validate_instr_sequence(&context, instrs1, &ft)?;
+                // a missing else branch must leave the params as the results
+                let instrs2 = instrs2.as_deref().unwrap_or(&[]);
+                Instr::validate_instr_sequence(&context, instrs2, &ft)?;
+
+                let (ResultType(mut args), rets) = ft;
+                args.push(ValType::I32);
+                Ok((ResultType(args), rets))
+            },
+            Instr::TryTable(blocktype, catches, instrs) => {
+                // catch clauses branch from outside of the block
+                for catch in catches {
+                    Instr::check_catch(context, catch)?;
+                }
+                let ft = blocktype.validate(context)?;
+                let vts: Vec<ValTypeOriginal> = ft.1.iter().map(vt_rev).collect();
+                let context = context.clone_with_labels(vts);
+                Instr::validate_instr_sequence(&context, instrs, &ft)?;
+                Ok(ft)
+            },
+            _ => self.validate_plain(context),
+        }
+    }
+
+    fn validate_plain(&self, context: &Context) -> Result<FuncType, Error> {
         match &self {
             /*
             NUMERIC INSTRUCTIONS
@@ -183,12 +291,17 @@ impl Instr {
             /* t2.cvtop_t1_sx? */
             Instr::CvtOp(cvtop) => {
                 match cvtop {
-                    CvtOp::IExtend8S(_valsize) => unimplemented!(),
-                    CvtOp::IExtend16S(_valsize) => unimplemented!(),
-                    CvtOp::I64Extend32S => unimplemented!(),
+                    CvtOp::IExtend8S(valsize) | CvtOp::IExtend16S(valsize) => {
+                        match valsize {
+                            ValSize::V32 => instr_tp!(I32 -> I32),
+                            ValSize::V64 => instr_tp!(I64 -> I64),
+                        }
+                    },
+                    CvtOp::I64Extend32S => instr_tp!(I64 -> I64),
                     CvtOp::I32WrapFromI64 => instr_tp!(I64 -> I32),
                     CvtOp::I64ExtendFromI32(_) => instr_tp!(I32 -> I64),
-                    CvtOp::ITruncFromF(valsize_i, valsize_f, _) => {
+                    CvtOp::ITruncFromF(valsize_i, valsize_f, _) |
+                    CvtOp::ITruncSatFromF(valsize_i, valsize_f, _) => {
                         let arg_tp = match valsize_f {
                             ValSize::V32 => ValType::F32,
                             ValSize::V64 => ValType::F64,
@@ -199,7 +312,6 @@ impl Instr {
                         };               
                         ft!(vec![arg_tp], vec![ret_tp])
                     },
-                    CvtOp::ITruncSatFromF(_valsize_i, _valsize_f, _) => unimplemented!(),
                     CvtOp::F32DemoteFromF64 => instr_tp!(F64 -> F32),
                     CvtOp::F64PromoteFromF32 => instr_tp!(F32 -> F64),
                     CvtOp::FConvertFromI(valsize_f, valsize_i, _) => {
@@ -230,25 +342,213 @@ impl Instr {
             // TODO: trunc_sat
 
 
+            /*
+            VECTOR INSTRUCTIONS
+            */
+            Instr::V128Const(_) => instr_tp!(V128),
+            Instr::V128Not => instr_tp!(V128 -> V128),
+            Instr::VVBinOp(_) => instr_tp!(V128 V128 -> V128),
+            Instr::V128Bitselect => instr_tp!(V128 V128 V128 -> V128),
+            Instr::V128AnyTrue => instr_tp!(V128 -> I32),
+            Instr::I8x16Shuffle(laneindices) => {
+                if laneindices.iter().any(|laneidx| *laneidx >= 32) {
+                    return Err(Error::OutOfRange("instr i8x16shuffle validate: laneidx".to_string()));
+                }
+                instr_tp!(V128 V128 -> V128)
+            },
+            Instr::I8x16Swizzle => instr_tp!(V128 V128 -> V128),
+            Instr::Splat(shape) => ft!(vec![unpacked(shape)], vec![ValType::V128]),
+            Instr::ExtractLane(shape, sign, laneidx) => {
+                let _ = Instr::check_lane(shape, laneidx, "extractlane")?;
+                // only packed lanes are extended
+                if sign.is_some() != matches!(shape, Shape::I8x16 | Shape::I16x8) {
+                    return Err(Error::Invalid);
+                }
+                ft!(vec![ValType::V128], vec![unpacked(shape)])
+            },
+            Instr::ReplaceLane(shape, laneidx) => {
+                let _ = Instr::check_lane(shape, laneidx, "replacelane")?;
+                ft!(vec![ValType::V128, unpacked(shape)], vec![ValType::V128])
+            },
+
+            Instr::VIRelOp(shape, _) | Instr::VIBinOp(shape, _) => {
+                let _ = Instr::check_shape(shape, false)?;
+                instr_tp!(V128 V128 -> V128)
+            },
+            Instr::VFRelOp(shape, _) | Instr::VFBinOp(shape, _) => {
+                let _ = Instr::check_shape(shape, true)?;
+                instr_tp!(V128 V128 -> V128)
+            },
+            Instr::VIUnOp(shape, _) => {
+                let _ = Instr::check_shape(shape, false)?;
+                instr_tp!(V128 -> V128)
+            },
+            Instr::VFUnOp(shape, _) => {
+                let _ = Instr::check_shape(shape, true)?;
+                instr_tp!(V128 -> V128)
+            },
+            Instr::VIShiftOp(shape, _) => {
+                let _ = Instr::check_shape(shape, false)?;
+                instr_tp!(V128 I32 -> V128)
+            },
+            Instr::AllTrue(shape) | Instr::Bitmask(shape) => {
+                let _ = Instr::check_shape(shape, false)?;
+                instr_tp!(V128 -> I32)
+            },
+            Instr::Narrow(_, _) | Instr::ExtMul(_, _, _) | Instr::I32x4DotI16x8S => instr_tp!(V128 V128 -> V128),
+            Instr::ExtAddPairwise(_, _) | Instr::VCvtOp(_) => instr_tp!(V128 -> V128),
+
+            Instr::I8x16RelaxedSwizzle | Instr::I16x8RelaxedQ15mulrS | Instr::I16x8RelaxedDotI8x16I7x16S => instr_tp!(V128 V128 -> V128),
+            Instr::I32x4RelaxedDotI8x16I7x16AddS => instr_tp!(V128 V128 V128 -> V128),
+            Instr::RelaxedTrunc(shape, _) => {
+                let _ = Instr::check_shape(shape, true)?;
+                instr_tp!(V128 -> V128)
+            },
+            Instr::RelaxedMadd(shape) | Instr::RelaxedNmadd(shape) => {
+                let _ = Instr::check_shape(shape, true)?;
+                instr_tp!(V128 V128 V128 -> V128)
+            },
+            Instr::RelaxedLaneselect(shape) => {
+                let _ = Instr::check_shape(shape, false)?;
+                instr_tp!(V128 V128 V128 -> V128)
+            },
+            Instr::RelaxedMin(shape) | Instr::RelaxedMax(shape) => {
+                let _ = Instr::check_shape(shape, true)?;
+                instr_tp!(V128 V128 -> V128)
+            },
+
+            Instr::V128LoadExtend(_, _, memarg) => {
+                let opname = "v128loadextend";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let _ = Instr::check_mem_alignment(opname, memarg, 64)?;
+                ft!(vec![at], vec![ValType::V128])
+            },
+            Instr::V128LoadSplat(shape, memarg) => {
+                let opname = "v128loadsplat";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let _ = Instr::check_mem_alignment(opname, memarg, shape.width() as u8)?;
+                ft!(vec![at], vec![ValType::V128])
+            },
+            Instr::V128LoadZero(valsize, memarg) => {
+                let opname = "v128loadzero";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let width = match valsize {
+                    ValSize::V32 => 32,
+                    ValSize::V64 => 64,
+                };
+                let _ = Instr::check_mem_alignment(opname, memarg, width)?;
+                ft!(vec![at], vec![ValType::V128])
+            },
+            Instr::V128LoadLane(shape, memarg, laneidx) => {
+                let opname = "v128loadlane";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let _ = Instr::check_mem_alignment(opname, memarg, shape.width() as u8)?;
+                let _ = Instr::check_lane(shape, laneidx, opname)?;
+                ft!(vec![at, ValType::V128], vec![ValType::V128])
+            },
+            Instr::V128StoreLane(shape, memarg, laneidx) => {
+                let opname = "v128storelane";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let _ = Instr::check_mem_alignment(opname, memarg, shape.width() as u8)?;
+                let _ = Instr::check_lane(shape, laneidx, opname)?;
+                ft!(vec![at, ValType::V128], vec![])
+            },
+
+            /*
+            ATOMIC MEMORY INSTRUCTIONS
+            */
+            Instr::AtomicNotify(memarg) => {
+                let opname = "atomicnotify";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let _ = Instr::check_atomic_alignment(opname, memarg, 32)?;
+                ft!(vec![at, ValType::I32], vec![ValType::I32])
+            },
+            Instr::AtomicWait(valsize, memarg) => {
+                let opname = "atomicwait";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let _ = Instr::check_atomic_alignment(opname, memarg, atomic_width(valsize, &None))?;
+                let valtype = atomic_type(valsize, &None)?;
+                ft!(vec![at, valtype, ValType::I64], vec![ValType::I32])
+            },
+            Instr::AtomicFence => instr_tp!(() -> ()),
+            Instr::AtomicLoad(valsize, packsize, memarg) => {
+                let opname = "atomicload";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let _ = Instr::check_atomic_alignment(opname, memarg, atomic_width(valsize, packsize))?;
+                let valtype = atomic_type(valsize, packsize)?;
+                ft!(vec![at], vec![valtype])
+            },
+            Instr::AtomicStore(valsize, packsize, memarg) => {
+                let opname = "atomicstore";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let _ = Instr::check_atomic_alignment(opname, memarg, atomic_width(valsize, packsize))?;
+                let valtype = atomic_type(valsize, packsize)?;
+                ft!(vec![at, valtype], vec![])
+            },
+            Instr::AtomicRmw(valsize, packsize, _, memarg) => {
+                let opname = "atomicrmw";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let _ = Instr::check_atomic_alignment(opname, memarg, atomic_width(valsize, packsize))?;
+                let valtype = atomic_type(valsize, packsize)?;
+                ft!(vec![at, valtype.clone()], vec![valtype])
+            },
+            Instr::AtomicCmpxchg(valsize, packsize, memarg) => {
+                let opname = "atomiccmpxchg";
+                let at = Instr::check_memarg(context, memarg, opname)?;
+                let _ = Instr::check_atomic_alignment(opname, memarg, atomic_width(valsize, packsize))?;
+                let valtype = atomic_type(valsize, packsize)?;
+                ft!(vec![at, valtype.clone(), valtype.clone()], vec![valtype])
+            },
+
+
             /*
             PARAMETRIC INSTRUCTIONS
             */
 
             // value-polymorphic
-            Instr::Drop(None) => ft!(vec![ValType::TypeVal(0)], vec![]),
+            Instr::Drop(None) => ft!(vec![ValType::TypeVal(ANY)], vec![]),
             Instr::Drop(Some(valtype)) => ft!(vec![vt(valtype)], vec![]),
-            // value-polymorphic
+            // value-polymorphic, references need the typed select
             Instr::Select(None) => {
-                ft!(vec![ValType::TypeVal(0), ValType::TypeVal(0), ValType::I32], vec![ValType::TypeVal(0)])
+                ft!(vec![ValType::TypeVal(NUM), ValType::TypeVal(NUM), ValType::I32], vec![ValType::TypeVal(NUM)])
             },
             Instr::Select(Some(valtype)) => {
                 ft!(vec![vt(valtype), vt(valtype), ValType::I32], vec![vt(valtype)])
             },
+
+            /*
+            REFERENCE INSTRUCTIONS
+            */
+            Instr::RefNull(heaptype) => {
+                Instr::check_heaptype(context, heaptype, "refnull")?;
+                ft!(vec![], vec![vt(&RefType { nullable: true, heap: *heaptype }.valtype())])
+            },
+            // value-polymorphic
+            Instr::RefIsNull => ft!(vec![ValType::TypeVal(REF)], vec![ValType::I32]),
+            Instr::RefFunc(funcidx) => {
+                let functype = Instr::check_func(context, funcidx, "reffunc")?;
+                if !context.is_ref(*funcidx) {
+                    return Err(Error::Invalid);
+                }
+                // function types match structurally, so any index of an equal type will do
+                let heap = match context.types().iter().position(|subtype| subtype.functype() == Some(&functype)) {
+                    Some(typeidx) => HeapType::Type(typeidx as TypeIdx),
+                    None => HeapType::Func,
+                };
+                ft!(vec![], vec![ValType::Ref(RefType { nullable: false, heap })])
+            },
+            // typed by the reference on the stack, see validate_instr_sequence
+            Instr::RefAsNonNull | Instr::BrOnNull(_) | Instr::BrOnNonNull(_) |
+            Instr::AnyConvertExtern | Instr::ExternConvertAny => unreachable!(),
+
             /*
             VARIABLE INSTRUCTIONS
             */
             Instr::LocalGet(localidx) => {
                 let tp = Instr::check_local(context, localidx, "local.get")?;
+                if !context.is_set(*localidx) {
+                    return Err(Error::PreCondition("instr local.get validate: local is not set".to_string()));
+                }
                 ft!(vec![], vec![vt(&tp)])
             },
             Instr::LocalSet(localidx) => {
@@ -279,18 +579,20 @@ impl Instr {
             */
             Instr::Load(valtype, memarg) => {
                 let opname = "load";
-                let _ = Instr::check_mem_exist(context, opname)?;
+                let at = Instr::check_memarg(context, memarg, opname)?;
                 let width = match valtype {
                     ValTypeOriginal::I32 | ValTypeOriginal::F32 => 32,
                     ValTypeOriginal::I64 | ValTypeOriginal::F64 => 64,
+                    ValTypeOriginal::V128 => 128,
+                    _ => return Err(Error::Invalid),
                 };
                 let _ = Instr::check_mem_alignment(opname, memarg, width)?;
 
-                ft!(vec![ValType::I32], vec![vt(valtype)])
+                ft!(vec![at], vec![vt(valtype)])
             },
             Instr::ILoad8(valsize, _, memarg) => {
                 let opname = "iload8";
-                let _ = Instr::check_mem_exist(context, opname)?;
+                let at = Instr::check_memarg(context, memarg, opname)?;
                 let _ = Instr::check_mem_alignment(opname, memarg, 8)?;
 
                 let valtype = match valsize {
@@ -298,11 +600,11 @@ impl Instr {
                     ValSize::V64 => ValType::I64,
                 };
 
-                ft!(vec![ValType::I32], vec![valtype.clone()])
+                ft!(vec![at], vec![valtype.clone()])
             },
             Instr::ILoad16(valsize, _, memarg) => {
                 let opname = "iload16";
-                let _ = Instr::check_mem_exist(context, opname)?;
+                let at = Instr::check_memarg(context, memarg, opname)?;
                 let _ = Instr::check_mem_alignment(opname, memarg, 16)?;
 
                 let valtype = match valsize {
@@ -310,29 +612,31 @@ impl Instr {
                     ValSize::V64 => ValType::I64,
                 };
 
-                ft!(vec![ValType::I32], vec![valtype.clone()])
+                ft!(vec![at], vec![valtype.clone()])
             },
             Instr::I64Load32(_, memarg) => {
                 let opname = "i64load32";
-                let _ = Instr::check_mem_exist(context, opname)?;
+                let at = Instr::check_memarg(context, memarg, opname)?;
                 let _ = Instr::check_mem_alignment(opname, memarg, 32)?;
 
-                instr_tp!(I32 -> I64)
+                ft!(vec![at], vec![ValType::I64])
             },
             Instr::Store(valtype, memarg) => {
                 let opname = "store";
-                let _ = Instr::check_mem_exist(context, opname)?;
+                let at = Instr::check_memarg(context, memarg, opname)?;
                 let width = match valtype {
                     ValTypeOriginal::I32 | ValTypeOriginal::F32 => 32,
                     ValTypeOriginal::I64 | ValTypeOriginal::F64 => 64,
+                    ValTypeOriginal::V128 => 128,
+                    _ => return Err(Error::Invalid),
                 };
                 let _ = Instr::check_mem_alignment(opname, memarg, width)?;
 
-                ft!(vec![ValType::I32, vt(valtype)], vec![])
+                ft!(vec![at, vt(valtype)], vec![])
             },
             Instr::IStore8(valsize, memarg) => {
                 let opname = "istore8";
-                let _ = Instr::check_mem_exist(context, opname)?;
+                let at = Instr::check_memarg(context, memarg, opname)?;
                 let _ = Instr::check_mem_alignment(opname, memarg, 8)?;
 
                 let valtype = match valsize {
@@ -340,11 +644,11 @@ impl Instr {
                     ValSize::V64 => ValType::I64,
                 };
 
-                ft!(vec![ValType::I32, valtype.clone()], vec![])
+                ft!(vec![at, valtype.clone()], vec![])
             },
             Instr::IStore16(valsize, memarg) => {
                 let opname = "istore16";
-                let _ = Instr::check_mem_exist(context, opname)?;
+                let at = Instr::check_memarg(context, memarg, opname)?;
                 let _ = Instr::check_mem_alignment(opname, memarg, 16)?;
 
                 let valtype = match valsize {
@@ -352,58 +656,93 @@ impl Instr {
                     ValSize::V64 => ValType::I64,
                 };
 
-                ft!(vec![ValType::I32, valtype.clone()], vec![])
+                ft!(vec![at, valtype.clone()], vec![])
             },
             Instr::I64Store32(memarg) => {
                 let opname = "i64store32";
-                let _ = Instr::check_mem_exist(context, opname)?;
+                let at = Instr::check_memarg(context, memarg, opname)?;
                 let _ = Instr::check_mem_alignment(opname, memarg, 32)?;
 
-                instr_tp!(I32 I64 ->)
+                ft!(vec![at, ValType::I64], vec![])
             },
-            Instr::MemorySize => {
-                let _ = Instr::check_mem_exist(context, "memorysize")?;
-                instr_tp!(I32)
+            Instr::MemorySize(memidx) => {
+                let at = Instr::check_mem(context, memidx, "memorysize")?;
+                ft!(vec![], vec![at])
+            },
+            Instr::MemoryGrow(memidx) => {
+                let at = Instr::check_mem(context, memidx, "memorygrow")?;
+                ft!(vec![at.clone()], vec![at])
+            },
+            Instr::MemoryFill(memidx) => {
+                let at = Instr::check_mem(context, memidx, "memoryfill")?;
+                ft!(vec![at.clone(), ValType::I32, at], vec![])
+            },
+            Instr::MemoryCopy(dst, src) => {
+                let at_d = Instr::check_mem(context, dst, "memorycopy")?;
+                let at_s = Instr::check_mem(context, src, "memorycopy")?;
+                // the length fits in both memories
+                let at_n = if at_d == ValType::I64 && at_s == ValType::I64 { ValType::I64 } else { ValType::I32 };
+                ft!(vec![at_d, at_s, at_n], vec![])
             },
-            Instr::MemoryGrow => {
-                let _ = Instr::check_mem_exist(context, "memorygrow")?;
-                instr_tp!(I32 -> I32)
+            Instr::MemoryInit(memidx, dataidx) => {
+                let at = Instr::check_mem(context, memidx, "memoryinit")?;
+                let _ = Instr::check_data(context, dataidx, "memoryinit")?;
+                ft!(vec![at, ValType::I32, ValType::I32], vec![])
+            },
+            Instr::DataDrop(dataidx) => {
+                let _ = Instr::check_data(context, dataidx, "datadrop")?;
+                instr_tp!(() -> ())
             },
 
             /*
-            CONTROL INSTRUCTIONS
+            TABLE INSTRUCTIONS
             */
-            Instr::Nop => instr_tp!(() -> ()),
-            Instr::Unreachable => instr_tp!(Ellipsis -> Ellipsis),
-            Instr::Block(blocktype, instrs) => {
-                let ft = blocktype.validate(context)?;
-                let vts: Vec<ValTypeOriginal> = ft.1.0.iter().map(|v| vt_rev(v)).collect();
-                let context = context.clone_with_labels(vts);
-                Instr::validate_instr_sequence(&context, &instrs)
+            Instr::TableGet(tableidx) => {
+                let TableType(_, reftype) = Instr::check_table(context, tableidx, "tableget")?;
+                ft!(vec![ValType::I32], vec![vt(&reftype.valtype())])
             },
-            Instr::Loop(blocktype, instrs) => {
-                let ft = blocktype.validate(context)?;
-                let vts: Vec<ValTypeOriginal> = ft.0.0.iter().map(|v| vt_rev(v)).collect();
-                let context = context.clone_with_labels(vts);
-                Instr::validate_instr_sequence(&context, &instrs)
+            Instr::TableSet(tableidx) => {
+                let TableType(_, reftype) = Instr::check_table(context, tableidx, "tableset")?;
+                ft!(vec![ValType::I32, vt(&reftype.valtype())], vec![])
             },
-            Instr::If(blocktype, instrs1, None) => {
-                let ft = blocktype.validate(context)?;
-                let vts: Vec<ValTypeOriginal> = ft.1.0.iter().map(|v| vt_rev(v)).collect();
-                let context = context.clone_with_labels(vts);
-                Instr::validate_instr_sequence(&context, &instrs1)
+            Instr::TableSize(tableidx) => {
+                let _ = Instr::check_table(context, tableidx, "tablesize")?;
+                instr_tp!(I32)
             },
-            Instr::If(blocktype, instrs1, Some(instrs2)) => {
-                let ft = blocktype.validate(context)?;
-                let vts: Vec<ValTypeOriginal> = ft.1.0.iter().map(|v| vt_rev(v)).collect();
-                let context = context.clone_with_labels(vts);
-                let functype1 = Instr::validate_instr_sequence(&context, &instrs1)?;
-                let functype2 = Instr::validate_instr_sequence(&context, &instrs2)?;
-                if functype1 != functype2 {
+            Instr::TableGrow(tableidx) => {
+                let TableType(_, reftype) = Instr::check_table(context, tableidx, "tablegrow")?;
+                ft!(vec![vt(&reftype.valtype()), ValType::I32], vec![ValType::I32])
+            },
+            Instr::TableFill(tableidx) => {
+                let TableType(_, reftype) = Instr::check_table(context, tableidx, "tablefill")?;
+                ft!(vec![ValType::I32, vt(&reftype.valtype()), ValType::I32], vec![])
+            },
+            Instr::TableCopy(tableidx1, tableidx2) => {
+                let TableType(_, reftype1) = Instr::check_table(context, tableidx1, "tablecopy")?;
+                let TableType(_, reftype2) = Instr::check_table(context, tableidx2, "tablecopy")?;
+                if !reftype2.matches(&reftype1, context.types()) {
+                    return Err(Error::Invalid);
+                }
+                instr_tp!(I32 I32 I32 ->)
+            },
+            Instr::TableInit(tableidx, elemidx) => {
+                let TableType(_, reftype) = Instr::check_table(context, tableidx, "tableinit")?;
+                let elemtype = Instr::check_elem(context, elemidx, "tableinit")?;
+                if !elemtype.matches(&reftype, context.types()) {
                     return Err(Error::Invalid);
                 }
-                Ok(functype1)
+                instr_tp!(I32 I32 I32 ->)
             },
+            Instr::ElemDrop(elemidx) => {
+                let _ = Instr::check_elem(context, elemidx, "elemdrop")?;
+                instr_tp!(() -> ())
+            },
+
+            /*
+            CONTROL INSTRUCTIONS
+            */
+            Instr::Nop => instr_tp!(() -> ()),
+            Instr::Unreachable => instr_tp!(Ellipsis -> Ellipsis),
             Instr::Br(labelidx) => {
                 let label = Instr::check_label(context, labelidx, "br")?;
                 let label: Vec<ValType> = label.iter().map(|v| vt(v)).collect();
@@ -418,8 +757,13 @@ impl Instr {
                 args.push(ValType::I32);
                 ft!(args, label)
             },
-            Instr::BrTable(_labelindices, labelidx) => {
+            Instr::BrTable(labelindices, labelidx) => {
                 let label = Instr::check_label(context, labelidx, "brtable")?;
+                for labelidx in labelindices {
+                    if Instr::check_label(context, labelidx, "brtable")? != label {
+                        return Err(Error::Invalid);
+                    }
+                }
                 let label: Vec<ValType> = label.iter().map(|v| vt(v)).collect();
                 let mut args = label.clone();
                 args.push(ValType::I32);
@@ -446,13 +790,13 @@ impl Instr {
                     Err(Error::OutOfIndex("instr call validate: funcidx".to_string()))
                 }
             },
-            Instr::CallIndirect(funcidx) => {
+            Instr::CallIndirect(tableidx, typeidx) => {
                 let opname = "callindirect";
-                let tabletype = Instr::check_table_exist(context, opname)?;
-                if !tabletype.is_funcref() {
+                let tabletype = Instr::check_table(context, tableidx, opname)?;
+                if !tabletype.is_funcref(context.types()) {
                     Err(Error::PreCondition(format!("instr {} validate: table.elemtype is not funcref", opname)))
                 } else {
-                    let tp = Instr::check_type(context, funcidx, opname)?;
+                    let tp = Instr::check_type(context, typeidx, opname)?;
                     let mut tp0: Vec<ValType> = tp.0.iter().map(|v| vt(v)).collect();
                     let tp1: Vec<ValType> = tp.1.iter().map(|v| vt(v)).collect();
                     tp0.push(ValType::I32);
@@ -460,8 +804,251 @@ impl Instr {
                     ft!(tp0, tp1)
                 }
             },
+            Instr::Throw(tagidx) => {
+                let tagtype = Instr::check_tag(context, tagidx, "throw")?;
+                let mut vts = vec![ValType::Ellipsis];
+                vts.extend(tagtype.0.iter().map(vt));
+                ft!(vts, vec![ValType::Ellipsis])
+            },
+            Instr::ThrowRef => ft!(vec![ValType::Ellipsis, ValType::ExnRef], vec![ValType::Ellipsis]),
+            Instr::ReturnCall(funcidx) => {
+                if let Some(functype) = context.func(*funcidx) {
+                    Instr::check_tail_results(context, &functype.1, "returncall")?;
+                    let mut vts = vec![ValType::Ellipsis];
+                    vts.extend(functype.0.iter().map(vt));
+                    ft!(vts, vec![ValType::Ellipsis])
+                } else {
+                    Err(Error::OutOfIndex("instr returncall validate: funcidx".to_string()))
+                }
+            },
+            Instr::ReturnCallIndirect(tableidx, typeidx) => {
+                let opname = "returncallindirect";
+                let tabletype = Instr::check_table(context, tableidx, opname)?;
+                if !tabletype.is_funcref(context.types()) {
+                    Err(Error::PreCondition(format!("instr {} validate: table.elemtype is not funcref", opname)))
+                } else {
+                    let tp = Instr::check_type(context, typeidx, opname)?;
+                    Instr::check_tail_results(context, &tp.1, opname)?;
+                    let mut vts = vec![ValType::Ellipsis];
+                    vts.extend(tp.0.iter().map(vt));
+                    vts.push(ValType::I32);
+
+                    ft!(vts, vec![ValType::Ellipsis])
+                }
+            },
+            Instr::CallRef(typeidx) => {
+                let tp = Instr::check_type(context, typeidx, "callref")?;
+                let mut tp0: Vec<ValType> = tp.0.iter().map(vt).collect();
+                let tp1: Vec<ValType> = tp.1.iter().map(vt).collect();
+                tp0.push(ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx) }));
+                ft!(tp0, tp1)
+            },
+            Instr::ReturnCallRef(typeidx) => {
+                let opname = "returncallref";
+                let tp = Instr::check_type(context, typeidx, opname)?;
+                Instr::check_tail_results(context, &tp.1, opname)?;
+                let mut vts = vec![ValType::Ellipsis];
+                vts.extend(tp.0.iter().map(vt));
+                vts.push(ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx) }));
+                ft!(vts, vec![ValType::Ellipsis])
+            },
+
+            /*
+            GC INSTRUCTIONS
+            */
+            Instr::RefEq => {
+                let eqref = ValType::Ref(RefType { nullable: true, heap: HeapType::Eq });
+                ft!(vec![eqref.clone(), eqref], vec![ValType::I32])
+            },
+            Instr::RefTest(rt) | Instr::RefCast(rt) => {
+                Instr::check_heaptype(context, &rt.heap, "refcast")?;
+                // any reference of the same hierarchy
+                let top = ValType::Ref(RefType { nullable: true, heap: rt.heap.top(context.types()) });
+                match self {
+                    Instr::RefTest(_) => ft!(vec![top], vec![ValType::I32]),
+                    _ => ft!(vec![top], vec![vt(&rt.valtype())]),
+                }
+            },
+            Instr::BrOnCast(labelidx, rt1, rt2) => {
+                let label = Instr::check_cast_label(context, labelidx, rt1, rt2, *rt2, "broncast")?;
+                // the reference left on failure is not null when rt2 takes null
+                let rest = RefType { nullable: rt1.nullable && !rt2.nullable, ..*rt1 };
+                let mut args = label.clone();
+                args.push(vt(&rt1.valtype()));
+                let mut rets = label;
+                rets.push(vt(&rest.valtype()));
+                ft!(args, rets)
+            },
+            Instr::BrOnCastFail(labelidx, rt1, rt2) => {
+                let rest = RefType { nullable: rt1.nullable && !rt2.nullable, ..*rt1 };
+                let label = Instr::check_cast_label(context, labelidx, rt1, rt2, rest, "broncastfail")?;
+                let mut args = label.clone();
+                args.push(vt(&rt1.valtype()));
+                let mut rets = label;
+                rets.push(vt(&rt2.valtype()));
+                ft!(args, rets)
+            },
+            Instr::StructNew(typeidx) => {
+                let fields = Instr::check_struct(context, typeidx, "structnew")?;
+                let args = fields.iter().map(|field| vt(&field.0.unpacked())).collect();
+                ft!(args, vec![ValType::Ref(RefType { nullable: false, heap: HeapType::Type(*typeidx) })])
+            },
+            Instr::StructNewDefault(typeidx) => {
+                let fields = Instr::check_struct(context, typeidx, "structnewdefault")?;
+                if !fields.iter().all(|field| field.0.unpacked().is_defaultable()) {
+                    return Err(Error::PreCondition("instr structnewdefault validate: field is not defaultable".to_string()));
+                }
+                ft!(vec![], vec![ValType::Ref(RefType { nullable: false, heap: HeapType::Type(*typeidx) })])
+            },
+            Instr::StructGet(sign, typeidx, fieldidx) => {
+                let fields = Instr::check_struct(context, typeidx, "structget")?;
+                let field = Instr::check_field(&fields, fieldidx, "structget")?;
+                let tp = Instr::check_field_get(&field, sign, "structget")?;
+                ft!(vec![ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx) })], vec![tp])
+            },
+            Instr::StructSet(typeidx, fieldidx) => {
+                let fields = Instr::check_struct(context, typeidx, "structset")?;
+                let field = Instr::check_field(&fields, fieldidx, "structset")?;
+                let tp = Instr::check_field_mut(&field, "structset")?;
+                ft!(vec![ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx) }), tp], vec![])
+            },
+            Instr::ArrayNew(typeidx) => {
+                let field = Instr::check_array(context, typeidx, "arraynew")?;
+                let tp = vt(&field.0.unpacked());
+                ft!(vec![tp, ValType::I32], vec![ValType::Ref(RefType { nullable: false, heap: HeapType::Type(*typeidx) })])
+            },
+            Instr::ArrayNewDefault(typeidx) => {
+                let field = Instr::check_array(context, typeidx, "arraynewdefault")?;
+                if !field.0.unpacked().is_defaultable() {
+                    return Err(Error::PreCondition("instr arraynewdefault validate: element is not defaultable".to_string()));
+                }
+                ft!(vec![ValType::I32], vec![ValType::Ref(RefType { nullable: false, heap: HeapType::Type(*typeidx) })])
+            },
+            Instr::ArrayNewFixed(typeidx, n) => {
+                let field = Instr::check_array(context, typeidx, "arraynewfixed")?;
+                let args = vec![vt(&field.0.unpacked()); *n as usize];
+                ft!(args, vec![ValType::Ref(RefType { nullable: false, heap: HeapType::Type(*typeidx) })])
+            },
+            Instr::ArrayGet(sign, typeidx) => {
+                let field = Instr::check_array(context, typeidx, "arrayget")?;
+                let tp = Instr::check_field_get(&field, sign, "arrayget")?;
+                ft!(vec![ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx) }), ValType::I32], vec![tp])
+            },
+            Instr::ArraySet(typeidx) => {
+                let field = Instr::check_array(context, typeidx, "arrayset")?;
+                let tp = Instr::check_field_mut(&field, "arrayset")?;
+                ft!(vec![ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx) }), ValType::I32, tp], vec![])
+            },
+            Instr::ArrayLen => ft!(vec![ValType::Ref(RefType { nullable: true, heap: HeapType::Array })], vec![ValType::I32]),
+            Instr::ArrayFill(typeidx) => {
+                let field = Instr::check_array(context, typeidx, "arrayfill")?;
+                let tp = Instr::check_field_mut(&field, "arrayfill")?;
+                ft!(vec![ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx) }), ValType::I32, tp, ValType::I32], vec![])
+            },
+            Instr::ArrayCopy(typeidx1, typeidx2) => {
+                let dst = Instr::check_array(context, typeidx1, "arraycopy")?;
+                let src = Instr::check_array(context, typeidx2, "arraycopy")?;
+                Instr::check_field_mut(&dst, "arraycopy")?;
+                let matches = match (&src.0, &dst.0) {
+                    (StorageType::Val(vt1), StorageType::Val(vt2)) => vt1.matches(vt2, context.types()),
+                    (st1, st2) => st1 == st2,
+                };
+                if !matches {
+                    return Err(Error::PreCondition("instr arraycopy validate: element types don't match".to_string()));
+                }
+                ft!(vec![
+                    ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx1) }), ValType::I32,
+                    ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx2) }), ValType::I32,
+                    ValType::I32,
+                ], vec![])
+            },
+            Instr::ArrayNewData(typeidx, dataidx) => {
+                let field = Instr::check_array(context, typeidx, "arraynewdata")?;
+                Instr::check_data(context, dataidx, "arraynewdata")?;
+                Instr::check_field_numeric(&field, "arraynewdata")?;
+                ft!(vec![ValType::I32, ValType::I32], vec![ValType::Ref(RefType { nullable: false, heap: HeapType::Type(*typeidx) })])
+            },
+            Instr::ArrayNewElem(typeidx, elemidx) => {
+                let field = Instr::check_array(context, typeidx, "arraynewelem")?;
+                let elemtype = Instr::check_elem(context, elemidx, "arraynewelem")?;
+                Instr::check_field_elem(context, &field, &elemtype, "arraynewelem")?;
+                ft!(vec![ValType::I32, ValType::I32], vec![ValType::Ref(RefType { nullable: false, heap: HeapType::Type(*typeidx) })])
+            },
+            Instr::ArrayInitData(typeidx, dataidx) => {
+                let field = Instr::check_array(context, typeidx, "arrayinitdata")?;
+                Instr::check_data(context, dataidx, "arrayinitdata")?;
+                Instr::check_field_mut(&field, "arrayinitdata")?;
+                Instr::check_field_numeric(&field, "arrayinitdata")?;
+                ft!(vec![ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx) }), ValType::I32, ValType::I32, ValType::I32], vec![])
+            },
+            Instr::ArrayInitElem(typeidx, elemidx) => {
+                let field = Instr::check_array(context, typeidx, "arrayinitelem")?;
+                let elemtype = Instr::check_elem(context, elemidx, "arrayinitelem")?;
+                Instr::check_field_mut(&field, "arrayinitelem")?;
+                Instr::check_field_elem(context, &field, &elemtype, "arrayinitelem")?;
+                ft!(vec![ValType::Ref(RefType { nullable: true, heap: HeapType::Type(*typeidx) }), ValType::I32, ValType::I32, ValType::I32], vec![])
+            },
+            Instr::RefI31 => ft!(vec![ValType::I32], vec![ValType::Ref(RefType { nullable: false, heap: HeapType::I31 })]),
+            Instr::I31Get(_) => ft!(vec![ValType::Ref(RefType { nullable: true, heap: HeapType::I31 })], vec![ValType::I32]),
 
-            // _ => unimplemented!(),
+            Instr::Block(..) | Instr::Loop(..) | Instr::If(..) | Instr::TryTable(..) => self.validate(context),
+        }
+    }
+
+    // ref.as_non_null, br_on_null and br_on_non_null are typed by the reference
+    // on top of the stack, None when it is unknown
+    fn validate_nullable(&self, context: &Context, top: Option<RefType>) -> Result<FuncType, Error> {
+        let non_null = |rt: RefType| vt(&RefType { nullable: false, ..rt }.valtype());
+        match self {
+            Instr::RefAsNonNull => match top {
+                Some(rt) => ft!(vec![vt(&rt.valtype())], vec![non_null(rt)]),
+                None => ft!(vec![ValType::TypeVal(REF)], vec![ValType::TypeVal(REF)]),
+            },
+            Instr::BrOnNull(labelidx) => {
+                let label: Vec<ValType> = Instr::check_label(context, labelidx, "bronnull")?.iter().map(vt).collect();
+                let (arg, ret) = match top {
+                    Some(rt) => (vt(&rt.valtype()), non_null(rt)),
+                    None => (ValType::TypeVal(REF), ValType::TypeVal(REF)),
+                };
+                let mut args = label.clone();
+                args.push(arg);
+                let mut rets = label;
+                rets.push(ret);
+                ft!(args, rets)
+            },
+            Instr::BrOnNonNull(labelidx) => {
+                let mut label: Vec<ValType> = Instr::check_label(context, labelidx, "bronnonnull")?.iter().map(vt).collect();
+                // the label takes the non-null reference last
+                let last = label.pop().and_then(|v| v.reftype())
+                    .ok_or(Error::PreCondition("instr bronnonnull validate: label doesn't end with a reference".to_string()))?;
+                let arg = match top {
+                    Some(rt) if RefType { nullable: false, ..rt }.matches(&last, context.types()) => vt(&rt.valtype()),
+                    Some(_) => return Err(Error::Invalid),
+                    None => vt(&RefType { nullable: true, ..last }.valtype()),
+                };
+                let mut args = label.clone();
+                args.push(arg);
+                ft!(args, label)
+            },
+            // the conversions keep the nullability of the reference
+            Instr::AnyConvertExtern | Instr::ExternConvertAny => {
+                let nullable = top.is_none_or(|rt| rt.nullable);
+                let (from, to) = match self {
+                    Instr::AnyConvertExtern => (HeapType::Extern, HeapType::Any),
+                    _ => (HeapType::Any, HeapType::Extern),
+                };
+                ft!(vec![vt(&RefType { nullable, heap: from }.valtype())], vec![vt(&RefType { nullable, heap: to }.valtype())])
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    // a tail call returns the callee's results from the current function
+    fn check_tail_results(context: &Context, results: &[ValTypeOriginal], opname: &str) -> Result<(), Error> {
+        match context.rtn() {
+            Some(rettp) if rettp.as_slice() == results => Ok(()),
+            Some(_) => Err(Error::PreCondition(format!("instr {} validate: results differ from context.return", opname))),
+            None => Err(Error::PreCondition(format!("instr {} validate: context.return is absent", opname))),
         }
     }
 
@@ -477,6 +1064,26 @@ impl Instr {
         Ok(tp)
     }
 
+    // only immutable globals are constant, the extended-const proposal adds integer add, sub and mul
+    fn check_constant(&self, context: &Context) -> Result<(), Error> {
+        match self {
+            Instr::I32Const(_) | Instr::I64Const(_) | Instr::F32Const(_) | Instr::F64Const(_) | Instr::V128Const(_) |
+            Instr::RefNull(_) | Instr::RefFunc(_) | Instr::RefI31 |
+            Instr::StructNew(_) | Instr::StructNewDefault(_) |
+            Instr::ArrayNew(_) | Instr::ArrayNewDefault(_) | Instr::ArrayNewFixed(_, _) |
+            Instr::IBinOp(_, IBinOp::Add | IBinOp::Sub | IBinOp::Mul) => Ok(()),
+            Instr::GlobalGet(globalidx) => {
+                let globaltype = Instr::check_global(context, globalidx, "global.get")?;
+                if globaltype.is_var() {
+                    Err(Error::NotConstant(format!("constant expr validate: global {} is mutable", globalidx)))
+                } else {
+                    Ok(())
+                }
+            },
+            _ => Err(Error::NotConstant(format!("constant expr validate: {:?} is not constant", self))),
+        }
+    }
+
     fn check_global(context: &Context, globalidx: &GlobalIdx, opname: &str) -> Result<GlobalType, Error> {
         let globaltype = context.global(globalidx.clone())
             .ok_or(Error::OutOfIndex(format!("instr {} validate: globalidx", opname)))?;
@@ -489,18 +1096,161 @@ impl Instr {
         Ok(label)
     }
 
-    fn check_table_exist(context: &Context, opname: &str) -> Result<TableType, Error> {
-        let tabletype = context.table()
-            .ok_or(Error::OutOfIndex(format!("instr {} validate: not exist table", opname)))?;
+    fn check_heaptype(context: &Context, heaptype: &HeapType, opname: &str) -> Result<(), Error> {
+        match heaptype {
+            HeapType::Type(typeidx) => Instr::check_subtype(context, typeidx, opname).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_subtype(context: &Context, typeidx: &TypeIdx, opname: &str) -> Result<CompType, Error> {
+        let subtype = context.subtype(*typeidx)
+            .ok_or(Error::OutOfIndex(format!("instr {} validate: typeidx", opname)))?;
+        Ok(subtype.comp)
+    }
+
+    fn check_struct(context: &Context, typeidx: &TypeIdx, opname: &str) -> Result<Vec<FieldType>, Error> {
+        match Instr::check_subtype(context, typeidx, opname)? {
+            CompType::Struct(fields) => Ok(fields),
+            _ => Err(Error::PreCondition(format!("instr {} validate: type is not a struct", opname))),
+        }
+    }
+
+    fn check_array(context: &Context, typeidx: &TypeIdx, opname: &str) -> Result<FieldType, Error> {
+        match Instr::check_subtype(context, typeidx, opname)? {
+            CompType::Array(field) => Ok(field),
+            _ => Err(Error::PreCondition(format!("instr {} validate: type is not an array", opname))),
+        }
+    }
+
+    fn check_field(fields: &[FieldType], fieldidx: &FieldIdx, opname: &str) -> Result<FieldType, Error> {
+        let field = fields.get(*fieldidx as usize)
+            .ok_or(Error::OutOfIndex(format!("instr {} validate: fieldidx", opname)))?;
+        Ok(*field)
+    }
+
+    // packed fields are read with a sign, the others without
+    fn check_field_get(field: &FieldType, sign: &Option<ValSign>, opname: &str) -> Result<ValType, Error> {
+        if field.0.is_packed() != sign.is_some() {
+            return Err(Error::PreCondition(format!("instr {} validate: sign doesn't fit the field", opname)));
+        }
+        Ok(vt(&field.0.unpacked()))
+    }
+
+    fn check_field_mut(field: &FieldType, opname: &str) -> Result<ValType, Error> {
+        if field.1 != Mut::Var {
+            return Err(Error::PreCondition(format!("instr {} validate: field is immutable", opname)));
+        }
+        Ok(vt(&field.0.unpacked()))
+    }
+
+    // elements read from a data segment are numbers or vectors
+    fn check_field_numeric(field: &FieldType, opname: &str) -> Result<(), Error> {
+        if field.0.unpacked().reftype().is_some() {
+            return Err(Error::PreCondition(format!("instr {} validate: element is a reference", opname)));
+        }
+        Ok(())
+    }
+
+    fn check_field_elem(context: &Context, field: &FieldType, elemtype: &RefType, opname: &str) -> Result<(), Error> {
+        if !elemtype.valtype().matches(&field.0.unpacked(), context.types()) {
+            return Err(Error::PreCondition(format!("instr {} validate: element types don't match", opname)));
+        }
+        Ok(())
+    }
+
+    // br_on_cast and br_on_cast_fail, the label takes `on_branch` and `rt2` is a subtype of `rt1`
+    fn check_cast_label(context: &Context, labelidx: &LabelIdx, rt1: &RefType, rt2: &RefType, on_branch: RefType, opname: &str) -> Result<Vec<ValType>, Error> {
+        Instr::check_heaptype(context, &rt1.heap, opname)?;
+        Instr::check_heaptype(context, &rt2.heap, opname)?;
+        if !rt2.matches(rt1, context.types()) {
+            return Err(Error::PreCondition(format!("instr {} validate: cast type doesn't match the operand", opname)));
+        }
+        let mut label: Vec<ValType> = Instr::check_label(context, labelidx, opname)?.iter().map(vt).collect();
+        match label.pop().and_then(|v| v.reftype()) {
+            Some(last) if on_branch.matches(&last, context.types()) => Ok(label),
+            _ => Err(Error::PreCondition(format!("instr {} validate: label doesn't take the reference", opname))),
+        }
+    }
+
+    fn check_func(context: &Context, funcidx: &FuncIdx, opname: &str) -> Result<FuncTypeOriginal, Error> {
+        let functype = context.func(*funcidx)
+            .ok_or(Error::OutOfIndex(format!("instr {} validate: funcidx", opname)))?;
+        Ok(functype)
+    }
+
+    fn check_table(context: &Context, tableidx: &TableIdx, opname: &str) -> Result<TableType, Error> {
+        let tabletype = context.table(*tableidx)
+            .ok_or(Error::OutOfIndex(format!("instr {} validate: tableidx", opname)))?;
         Ok(tabletype)
     }
 
-    fn check_mem_exist(context: &Context, opname: &str) -> Result<(), Error> {
-        let _memtype = context.mem()
-            .ok_or(Error::OutOfIndex(format!("instr {} validate: not exist mem", opname)))?;
+    fn check_tag(context: &Context, tagidx: &TagIdx, opname: &str) -> Result<FuncTypeOriginal, Error> {
+        let tagtype = context.tag(*tagidx)
+            .ok_or(Error::OutOfIndex(format!("instr {} validate: tagidx", opname)))?;
+        Ok(tagtype)
+    }
+
+    // the label takes the values of the tag, then the exception for the _ref clauses
+    fn check_catch(context: &Context, catch: &Catch) -> Result<(), Error> {
+        let (mut vts, labelidx) = match catch {
+            Catch::Tag(tagidx, labelidx) | Catch::TagRef(tagidx, labelidx) => {
+                (Instr::check_tag(context, tagidx, "trytable")?.0, labelidx)
+            },
+            Catch::All(labelidx) | Catch::AllRef(labelidx) => (vec![], labelidx),
+        };
+        if matches!(catch, Catch::TagRef(_, _) | Catch::AllRef(_)) {
+            vts.push(ValTypeOriginal::ExnRef);
+        }
+        if Instr::check_label(context, labelidx, "trytable")? != vts {
+            return Err(Error::Invalid);
+        }
         Ok(())
     }
 
+    fn check_elem(context: &Context, elemidx: &ElemIdx, opname: &str) -> Result<RefType, Error> {
+        let elemtype = context.elem(*elemidx)
+            .ok_or(Error::OutOfIndex(format!("instr {} validate: elemidx", opname)))?;
+        Ok(elemtype)
+    }
+
+    // data segments can only be referred to when their count is declared
+    fn check_data(context: &Context, dataidx: &DataIdx, opname: &str) -> Result<(), Error> {
+        if context.data(*dataidx) {
+            Ok(())
+        } else {
+            Err(Error::OutOfIndex(format!("instr {} validate: dataidx", opname)))
+        }
+    }
+
+    fn check_lane(shape: &Shape, laneidx: &LaneIdx, opname: &str) -> Result<(), Error> {
+        if (*laneidx as usize) < shape.lanes() {
+            Ok(())
+        } else {
+            Err(Error::OutOfRange(format!("instr {} validate: laneidx", opname)))
+        }
+    }
+
+    fn check_shape(shape: &Shape, is_float: bool) -> Result<(), Error> {
+        if shape.is_float() == is_float { Ok(()) } else { Err(Error::Invalid) }
+    }
+
+    // the type of the addresses of the memory
+    fn check_mem(context: &Context, memidx: &MemIdx, opname: &str) -> Result<ValType, Error> {
+        let memtype = context.mem(*memidx)
+            .ok_or(Error::OutOfIndex(format!("instr {} validate: not exist mem", opname)))?;
+        Ok(vt(&memtype.2.valtype()))
+    }
+
+    // offsets of 32-bit memories are 32-bit
+    fn check_memarg(context: &Context, memarg: &MemArg, opname: &str) -> Result<ValType, Error> {
+        let at = Instr::check_mem(context, &memarg.memory, opname)?;
+        if at == ValType::I32 && memarg.offset > u32::MAX as u64 {
+            return Err(Error::OutOfRange(format!("instr {} validate: memarg.offset is too large", opname)));
+        }
+        Ok(at)
+    }
+
     fn check_mem_alignment(opname: &str, memarg: &MemArg, width: u8) -> Result<(), Error> {
         if memarg.is_valid(width) {
             Ok(())
@@ -508,6 +1258,15 @@ impl Instr {
             Err(Error::OutOfRange(format!("instr {} validate: memarg.align is too large", opname)))
         }
     }
+
+    // atomic accesses are always naturally aligned
+    fn check_atomic_alignment(opname: &str, memarg: &MemArg, width: u32) -> Result<(), Error> {
+        if 1 << memarg.align == width / 8 {
+            Ok(())
+        } else {
+            Err(Error::OutOfRange(format!("instr {} validate: memarg.align is not natural", opname)))
+        }
+    }
 }
 
 impl MemArg {
@@ -517,6 +1276,7 @@ impl MemArg {
             16 => self.align <= 1,
             32 => self.align <= 2,
             64 => self.align <= 3,
+            128 => self.align <= 4,
             _ => unimplemented!(),
         }
     }
@@ -1,10 +1,10 @@
+use std::borrow::Cow;
+
 use crate::{
-    // ValType as ValTypeOriginal,
+    ResultType as ResultTypeOriginal,
     Expr,
     Error,
     Context,
-    ft,
-    instr_tp,
 };
 
 use super::{
@@ -12,110 +12,135 @@ use super::{
     ValType,
     ResultType,
     FuncType,
-    vt_rev,
+    TypeValIdx,
+    NUM,
+    REF,
+    vt,
 };
 
 impl Expr {
-    pub fn validate(&self, context: &Context) -> Result<ResultType, Error> {
-        if self.is_constant() {
-            unimplemented!()
-        } else {
-            let functype = Instr::validate_instr_sequence(context, &self.0)?;
-            if functype.0.0.len() > 0 {
-                return Err(Error::Invalid);
-            }
-            Ok(ResultType(functype.1.0))
+    pub fn validate(&self, context: &Context, resulttype: &ResultTypeOriginal) -> Result<(), Error> {
+        let rets = ResultType(resulttype.iter().map(vt).collect());
+        Instr::validate_instr_sequence(context, &self.0, &(ResultType(vec![]), rets))
+    }
+
+    /// Validate an initializer or a segment offset, which must be a constant expression.
+    pub fn validate_constant(&self, context: &Context, resulttype: &ResultTypeOriginal) -> Result<(), Error> {
+        for instr in &self.0 {
+            instr.check_constant(context)?;
         }
+        self.validate(context, resulttype)
     }
 }
 
-impl ResultType {
-    pub fn strip_suffix<'a>(&'a self, suffix: &ResultType) -> Option<ResultType> {
-        let mut res = ResultType(vec![]);
+// operand stack of the validation algorithm.
+// after an unconditional branch the stack is polymorphic:
+// popping from an empty stack yields an unknown type(TypeVal).
+struct OperandStack {
+    vals: Vec<ValType>,
+    polymorphic: bool,
+}
 
-        if ResultType::is_stack_polymorphic(&self)
-        || ResultType::is_stack_polymorphic(suffix) {
-            return Some(res);
+impl OperandStack {
+    fn pop(&mut self, expected: &ValType, context: &Context) -> Result<ValType, Error> {
+        match self.vals.pop() {
+            Some(actual) => {
+                if ValType::matches(&actual, expected, context) {
+                    Ok(actual)
+                } else {
+                    Err(Error::Invalid)
+                }
+            },
+            None if self.polymorphic => Ok(expected.clone()),
+            None => Err(Error::Invalid),
         }
+    }
 
-        if self.0.len() < suffix.0.len() { return None; }
+    fn unwind(&mut self) {
+        self.vals.clear();
+        self.polymorphic = true;
+    }
+}
 
-        let max_idx = &self.0.len() - 1;
-        for (idx, valtype) in suffix.iter().rev().enumerate() {
-            let valtype1 = &self.0.get(max_idx - idx).unwrap();
-            if &valtype == valtype1 {
-                res.0.insert(0, valtype.clone());
-            } else {
-                return None;
-            }
+impl ValType {
+    fn matches(actual: &ValType, expected: &ValType, context: &Context) -> bool {
+        match (actual, expected) {
+            (ValType::TypeVal(_), _) => true,
+            (_, ValType::TypeVal(kind)) => actual.is_kind(*kind),
+            _ => match (actual.reftype(), expected.reftype()) {
+                (Some(rt1), Some(rt2)) => rt1.matches(&rt2, context.types()),
+                _ => actual == expected,
+            },
         }
-
-        Some(res)
     }
 
-    pub fn is_stack_polymorphic(rt: &ResultType) -> bool {
-        rt.0.starts_with(&[ValType::Ellipsis])
+    fn is_kind(&self, kind: TypeValIdx) -> bool {
+        let is_ref = self.reftype().is_some();
+        match kind {
+            NUM => !is_ref,
+            REF => is_ref,
+            _ => true,
+        }
     }
 }
 
 impl Instr {
-    pub fn validate_instr_sequence(context: &Context, instrs: &Vec<Instr>) -> Result<FuncType, Error> {
-        if instrs.is_empty() { return instr_tp!(Ellipsis -> Ellipsis); }
-
-        let mut ret = Err(Error::Invalid);
-        let mut rets: ResultType; 
-
-        let mut instr_resolved: Option<Instr> = None;
+    pub fn validate_instr_sequence(context: &Context, instrs: &[Instr], functype: &FuncType) -> Result<(), Error> {
+        let mut stack = OperandStack {
+            vals: functype.0.0.clone(),
+            polymorphic: false,
+        };
+        // copied when a non-defaultable local is set, it stays unset after the block
+        let mut context = Cow::Borrowed(context);
+
+        for instr in instrs {
+            let (args, rets) = match instr {
+                Instr::RefAsNonNull | Instr::BrOnNull(_) | Instr::BrOnNonNull(_) |
+                Instr::AnyConvertExtern | Instr::ExternConvertAny => {
+                    let top = stack.vals.last().and_then(ValType::reftype);
+                    instr.validate_nullable(&context, top)?
+                },
+                _ => instr.validate(&context)?,
+            };
 
-        for instr_pair in instrs.windows(2) {
-            let first_functype = {
-                if let Some(instr) = instr_resolved {
-                    instr_resolved = None;
-                    instr.validate(context)?
-                } else {
-                    instr_pair[0].validate(context)?
+            // resolve valtype for value-polymorphic instrs(drop, select)
+            let mut resolved: Option<ValType> = None;
+            for expected in args.iter().rev().filter(|v| **v != ValType::Ellipsis) {
+                let actual = stack.pop(expected, &context)?;
+                if let ValType::TypeVal(_) = expected {
+                    match &resolved {
+                        Some(valtype) if !ValType::matches(valtype, &actual, &context) => return Err(Error::Invalid),
+                        Some(ValType::TypeVal(_)) | None => resolved = Some(actual),
+                        Some(_) => (),
+                    }
                 }
-            };
-            let instr_second = &instr_pair[1];
-            let second_functype = instr_second.validate(context)?;
-            let mut second_functype_args = second_functype.0;
-            let mut second_functype_rets = second_functype.1;
-
-
-            // resolve valtype for value-polymorphic instrs
-            // TODO: better algorithm...
-            match instr_second {
-                &Instr::Drop(None) => {
-                    let valtype = first_functype.1.last().ok_or(Error::Invalid)?;
-                    second_functype_args = ResultType(vec![valtype.clone()]);
-                    instr_resolved = Some(Instr::Drop(Some(vt_rev(valtype))));
-                },
-                &Instr::Select(None) => {
-                    let valtype = first_functype.1.last2().ok_or(Error::Invalid)?;
-                    second_functype_args = ResultType(vec![valtype.clone(), valtype.clone(), ValType::I32]);
-                    second_functype_rets = ResultType(vec![valtype.clone()]);
-                    instr_resolved = Some(Instr::Select(Some(vt_rev(valtype))));
-                },
-                _ => (),
             }
 
+            if rets.0.starts_with(&[ValType::Ellipsis]) {
+                stack.unwind();
+            } else {
+                for ret in rets.0 {
+                    match ret {
+                        ValType::TypeVal(_) => stack.vals.push(resolved.clone().unwrap_or(ret)),
+                        _ => stack.vals.push(ret),
+                    }
+                }
+            }
 
-            // compare types
-            rets = first_functype.1.strip_suffix(&second_functype_args).ok_or(Error::Invalid)?;
-            
-
-            let ret_args = first_functype.0;
-            let ret_rets = {
-                if ResultType::is_stack_polymorphic(&second_functype_rets) {
-                    second_functype_rets
-                } else {
-                    rets.0.extend(second_functype_rets.0);
-                    rets.clone()
+            if let Instr::LocalSet(localidx) | Instr::LocalTee(localidx) = instr {
+                if !context.is_set(*localidx) {
+                    context.to_mut().set_local(*localidx);
                 }
-            };
-            ret = Ok((ret_args, ret_rets));
+            }
+        }
+
+        for expected in functype.1.iter().rev() {
+            stack.pop(expected, &context)?;
+        }
+        if !stack.vals.is_empty() {
+            return Err(Error::Invalid);
         }
-        
-        ret
+
+        Ok(())
     }
-}
\ No newline at end of file
+}
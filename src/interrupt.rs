@@ -0,0 +1,101 @@
+use std::sync::Arc;
+use std::sync::atomic::{
+    AtomicBool,
+    AtomicU64,
+    Ordering,
+};
+
+use crate::{
+    Store,
+};
+
+/// Cloneable handle used to stop guest code running in a store,
+/// possibly from another OS thread.
+/// The running thread checks it at every call and loop back-edge, and while it waits
+/// in `memory.atomic.wait`.
+#[derive(Clone, Default, Debug)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+    epoch: Arc<AtomicU64>,
+}
+
+impl InterruptHandle {
+    /// Trap the running code with `Error::Interrupted` at its next check.
+    /// The request is consumed by the interruption it causes.
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::SeqCst);
+    }
+
+    /// Advance the epoch, e.g. from a timer, to expire store deadlines.
+    pub fn increment_epoch(&self) {
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    fn take_interrupt(&self) -> bool {
+        self.interrupted.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Store {
+    pub fn is_interrupted(&self) -> bool {
+        if self.interrupt.take_interrupt() {
+            return true;
+        }
+        match self.epoch_deadline {
+            Some(deadline) => self.interrupt.epoch() >= deadline,
+            None => false,
+        }
+    }
+}
+
+// (func (loop (call $host) (br 0))), the host function stopping it from inside
+#[cfg(test)]
+fn infinite_loop(store: &mut Store, host: crate::HostFunc) -> crate::FuncAddr {
+    use crate::{func_alloc, FuncInst, Func, Expr, Instr, BlockType, ModuleInst};
+
+    let hostaddr = func_alloc(store, (vec![], vec![]), host).unwrap();
+    let body = Expr(vec![Instr::Loop(BlockType::ValType(None), vec![Instr::Call(0), Instr::Br(0)].into())]);
+    let func = Func { tp: 0, locals: vec![], body };
+    let moduleinst = ModuleInst { types: Arc::new(vec![(vec![], vec![]).into()]), funcaddrs: vec![hostaddr], ..ModuleInst::default() };
+    store.funcs.push(FuncInst::user((vec![], vec![]), moduleinst, func));
+    store.funcs.len() - 1
+}
+
+#[test]
+fn test_interrupt_handle() {
+    use crate::{store_init, func_invoke, Val, HostResult, Error};
+
+    fn interrupt(store: &mut Store, _args: &[Val]) -> HostResult {
+        store.interrupt.interrupt();
+        HostResult::Vals(vec![])
+    }
+
+    let mut store = store_init();
+    let funcaddr = infinite_loop(&mut store, interrupt);
+    let (store, result) = func_invoke(&mut store, funcaddr, vec![]);
+    assert!(matches!(result, Err(Error::Interrupted)));
+
+    // the interruption is consumed, the store stays usable
+    assert!(!store.is_interrupted());
+}
+
+#[test]
+fn test_epoch_deadline() {
+    use crate::{store_init, store_set_epoch_deadline, func_invoke, Val, HostResult, Error};
+
+    fn increment_epoch(store: &mut Store, _args: &[Val]) -> HostResult {
+        store.interrupt.increment_epoch();
+        HostResult::Vals(vec![])
+    }
+
+    let mut store = store_init();
+    let funcaddr = infinite_loop(&mut store, increment_epoch);
+    store_set_epoch_deadline(&mut store, 2);
+    let (store, result) = func_invoke(&mut store, funcaddr, vec![]);
+    assert!(matches!(result, Err(Error::Interrupted)));
+    assert_eq!(store.interrupt.epoch(), 2);
+}
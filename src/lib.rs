@@ -1,5 +1,29 @@
 mod store;
-pub use store::store_init;
+pub use store::{
+    store_init,
+    store_limiter,
+    store_interrupt_handle,
+    store_set_epoch_deadline,
+    store_set_fuel,
+    store_fuel,
+    store_collect,
+    store_set_relaxed_simd,
+};
+
+mod limiter;
+pub use limiter::{
+    ResourceLimiter,
+    StoreLimits,
+};
+
+mod snapshot;
+pub use snapshot::{
+    store_snapshot,
+    store_restore,
+};
+
+mod interrupt;
+pub use interrupt::InterruptHandle;
 
 mod module;
 pub use module::{
@@ -10,6 +34,7 @@ pub use module::{
     module_imports,
     module_exports,
 };
+use module::instanciate_module;
 pub use module::{
     Module,
 };
@@ -21,6 +46,10 @@ use module::{
     LocalIdx,
     LabelIdx,
     MemIdx,
+    ElemIdx,
+    DataIdx,
+    TagIdx,
+    FieldIdx,
     Func,
     Context,
 
@@ -29,11 +58,14 @@ use module::{
     Table,
     Mem,
     Global,
+    Tag,
     Export,
     ExportDesc,
     Start,
     Elem,
+    ElemMode,
     Data,
+    DataMode,
 
     alloc_hostfunc,
     alloc_table,
@@ -44,6 +76,9 @@ use module::{
     grow_mem,
     alloc_global,
     find_globaltype,
+    alloc_tag,
+    alloc_exn,
+    find_tagtype,
 };
 
 
@@ -57,6 +92,10 @@ pub use func::{
     func_alloc,
     func_type,
     func_invoke,
+    func_invoke_resumable,
+    func_resume,
+    func_alloc_async,
+    func_invoke_async,
 };
 
 mod table;
@@ -77,6 +116,8 @@ pub use mem::{
     mem_write,
     mem_size,
     mem_grow,
+    mem_shared,
+    mem_import_shared,
 };
 
 mod global;
@@ -87,6 +128,19 @@ pub use global::{
     global_write,
 };
 
+mod tag;
+pub use tag::{
+    tag_alloc,
+    tag_type,
+};
+
+mod exn;
+pub use exn::{
+    exn_alloc,
+    exn_tag,
+    exn_read,
+};
+
 mod val;
 use val::{
     Byte,
@@ -97,10 +151,21 @@ use val::{
 mod types;
 use types::{
     ValType,
+    RefType,
+    HeapType,
     ResultType,
     FuncType,
+    SubType,
+    CompType,
+    FieldType,
+    StorageType,
+    heap_matches,
+    valtype_matches,
+    functype_matches,
     Limits,
     MemType,
+    Share,
+    IdxType,
     ElemType,
     TableType,
     GlobalType,
@@ -123,15 +188,45 @@ use instr::{
     FRelOp,
     CvtOp,
     BlockType,
+    Catch,
+    LaneIdx,
+    Shape,
+    Half,
+    VVBinOp,
+    VIUnOp,
+    VIBinOp,
+    VFBinOp,
+    VIShiftOp,
+    VCvtOp,
+    PackSize,
+    AtomicRmwOp,
+    atomic_width,
 };
 
 mod error;
 pub use error::Error;
 
 mod runtime;
-use runtime::{
+pub use runtime::{
     Store,
     ModuleInst,
+    Val,
+    Ref,
+    AnyRef,
+    ExternAddr,
+    HostCode,
+    HostFunc,
+    HostClosure,
+    AsyncHostFunc,
+    HostFuture,
+    HostResult,
+    Invocation,
+    Suspension,
+    SuspendReason,
+    SharedMemory,
+    RelaxedSimd,
+};
+use runtime::{
     ExternVal,
     Frame,
     FuncInst,
@@ -140,18 +235,101 @@ use runtime::{
     TableAddr,
     MemAddr,
     StackEntry,
-    Val,
     GlobalAddr,
+    ElemAddr,
+    DataAddr,
+    TagAddr,
+    ExnAddr,
+    HeapAddr,
     GlobalInst,
+    ElemInst,
+    DataInst,
     TableInst,
     MemInst,
+    MemData,
+    TagInst,
+    ExnInst,
+    Heap,
+    HeapInst,
     ExportInst,
     Result,
+    val_matches,
+    func_matches,
+};
+
+mod component;
+pub use component::{
+    component_decode,
+    component_instanciate,
+    component_export,
+    component_func_host,
+    component_invoke,
+    component_resource_drop,
+    component_next,
+    component_flags,
+    wit_bindgen,
+};
+pub use component::{
+    Component,
+    ComponentInst,
+    ComponentExternVal,
+    ComponentFunc,
+    ComponentHostFunc,
+    ComponentFuncType,
+    ComponentType,
+    InterfaceType,
+    ComponentVal,
+    ResourceType,
+    ResourceDtor,
+    Resource,
+    ComponentValue,
+    ComponentPayload,
+    WitError,
+};
+use component::{
+    ComponentDef,
+    CoreSort,
+    Sort,
+    CoreInstanceExpr,
+    InstanceExpr,
+    Alias,
+    AliasTarget,
+    PrimValType,
+    ValTypeRef,
+    DefValType,
+    FuncTypeDef,
+    DefType,
+    ComponentDecl,
+    InstanceDecl,
+    ExternDesc,
+    TypeBound,
+    Canon,
+    CanonOpt,
+    StringEncoding,
+    ComponentImport,
+    ComponentExport,
+};
+
+mod wasi;
+pub use wasi::{
+    WasiCtx,
+    WasiFs,
+    WasiFile,
+    WasiOpenOptions,
+    HostFs,
+    MemFs,
+    WasiPipe,
+    WASI_MODULE,
+    wasi_instanciate,
+    wasi_run,
+    wasi_component_imports,
+    wasi_component_run,
 };
 
 mod decoder;
 use decoder::{
     decode_module,
+    decode_component,
 };
 
 mod parser;
@@ -162,4 +340,6 @@ use parser::{
 mod encoder;
 pub use encoder::{
     module_encode,
-};
\ No newline at end of file
+};
+#[cfg(test)]
+mod testing;
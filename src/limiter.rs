@@ -0,0 +1,257 @@
+use crate::{
+    Store,
+};
+
+/// Hook consulted by the store before it allocates or grows anything.
+/// Returning `false` denies the request.
+pub trait ResourceLimiter {
+    /// `current` and `desired` are sizes of one linear memory in bytes.
+    fn memory_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> bool;
+
+    /// `current` and `desired` are numbers of elements of one table.
+    fn table_growing(&mut self, current: u32, desired: u32, maximum: Option<u32>) -> bool;
+
+    /// `current` and `desired` are sizes of the structs and arrays of the store in bytes.
+    fn heap_growing(&mut self, _current: usize, _desired: usize) -> bool { true }
+
+    fn instance_allocating(&mut self) -> bool { true }
+
+    fn func_allocating(&mut self) -> bool { true }
+
+    /// Called when a granted allocation is given back, e.g. by a failed instanciation.
+    fn memory_released(&mut self, _size: usize) {}
+
+    fn table_released(&mut self, _elements: u32) {}
+
+    /// Called by the collector with the size of the objects it freed.
+    fn heap_released(&mut self, _size: usize) {}
+
+    fn instance_released(&mut self) {}
+
+    fn func_released(&mut self) {}
+}
+
+/// Limiter capping the totals of a whole store.
+/// `None` means unlimited, `*_used` are updated as requests are granted.
+#[derive(Default, Debug)]
+pub struct StoreLimits {
+    pub memory_size: Option<usize>,
+    pub table_elements: Option<usize>,
+    pub heap_size: Option<usize>,
+    pub instances: Option<usize>,
+    pub funcs: Option<usize>,
+
+    pub memory_used: usize,
+    pub table_elements_used: usize,
+    pub heap_used: usize,
+    pub instances_used: usize,
+    pub funcs_used: usize,
+}
+
+impl ResourceLimiter for StoreLimits {
+    fn memory_growing(&mut self, current: usize, desired: usize, _maximum: Option<usize>) -> bool {
+        let used = self.memory_used.saturating_sub(current) + desired;
+        if let Some(limit) = self.memory_size {
+            if used > limit { return false; }
+        }
+        self.memory_used = used;
+        true
+    }
+
+    fn table_growing(&mut self, current: u32, desired: u32, _maximum: Option<u32>) -> bool {
+        let used = self.table_elements_used.saturating_sub(current as usize) + desired as usize;
+        if let Some(limit) = self.table_elements {
+            if used > limit { return false; }
+        }
+        self.table_elements_used = used;
+        true
+    }
+
+    fn heap_growing(&mut self, current: usize, desired: usize) -> bool {
+        let used = self.heap_used.saturating_sub(current) + desired;
+        if let Some(limit) = self.heap_size {
+            if used > limit { return false; }
+        }
+        self.heap_used = used;
+        true
+    }
+
+    fn instance_allocating(&mut self) -> bool {
+        if let Some(limit) = self.instances {
+            if self.instances_used >= limit { return false; }
+        }
+        self.instances_used += 1;
+        true
+    }
+
+    fn func_allocating(&mut self) -> bool {
+        if let Some(limit) = self.funcs {
+            if self.funcs_used >= limit { return false; }
+        }
+        self.funcs_used += 1;
+        true
+    }
+
+    fn memory_released(&mut self, size: usize) {
+        self.memory_used = self.memory_used.saturating_sub(size);
+    }
+
+    fn table_released(&mut self, elements: u32) {
+        self.table_elements_used = self.table_elements_used.saturating_sub(elements as usize);
+    }
+
+    fn heap_released(&mut self, size: usize) {
+        self.heap_used = self.heap_used.saturating_sub(size);
+    }
+
+    fn instance_released(&mut self) {
+        self.instances_used = self.instances_used.saturating_sub(1);
+    }
+
+    fn func_released(&mut self) {
+        self.funcs_used = self.funcs_used.saturating_sub(1);
+    }
+}
+
+impl Store {
+    pub fn memory_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> bool {
+        match &mut self.limiter {
+            Some(limiter) => limiter.memory_growing(current, desired, maximum),
+            None => true,
+        }
+    }
+
+    pub fn table_growing(&mut self, current: u32, desired: u32, maximum: Option<u32>) -> bool {
+        match &mut self.limiter {
+            Some(limiter) => limiter.table_growing(current, desired, maximum),
+            None => true,
+        }
+    }
+
+    pub fn heap_growing(&mut self, current: usize, desired: usize) -> bool {
+        match &mut self.limiter {
+            Some(limiter) => limiter.heap_growing(current, desired),
+            None => true,
+        }
+    }
+
+    pub fn instance_allocating(&mut self) -> bool {
+        match &mut self.limiter {
+            Some(limiter) => limiter.instance_allocating(),
+            None => true,
+        }
+    }
+
+    pub fn func_allocating(&mut self) -> bool {
+        match &mut self.limiter {
+            Some(limiter) => limiter.func_allocating(),
+            None => true,
+        }
+    }
+
+    pub fn memory_released(&mut self, size: usize) {
+        if let Some(limiter) = &mut self.limiter { limiter.memory_released(size) }
+    }
+
+    pub fn table_released(&mut self, elements: u32) {
+        if let Some(limiter) = &mut self.limiter { limiter.table_released(elements) }
+    }
+
+    pub fn heap_released(&mut self, size: usize) {
+        if let Some(limiter) = &mut self.limiter { limiter.heap_released(size) }
+    }
+
+    pub fn instance_released(&mut self) {
+        if let Some(limiter) = &mut self.limiter { limiter.instance_released() }
+    }
+
+    pub fn func_released(&mut self) {
+        if let Some(limiter) = &mut self.limiter { limiter.func_released() }
+    }
+}
+
+#[test]
+fn test_store_limits_memory() {
+    use crate::{store_init, store_limiter, mem_alloc, mem_grow, MemType, Limits, Share, IdxType};
+
+    let mut store = store_init();
+    store_limiter(&mut store, StoreLimits { memory_size: Some(3 * 64 * 1024), ..StoreLimits::default() });
+
+    let memaddr = mem_alloc(&mut store, MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32)).unwrap();
+    assert!(mem_grow(&mut store, memaddr, 1).is_ok());
+    assert!(mem_grow(&mut store, memaddr, 2).is_err());
+    assert!(mem_alloc(&mut store, MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32)).is_ok());
+    assert!(mem_alloc(&mut store, MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32)).is_err());
+}
+
+#[test]
+fn test_store_limits_instances() {
+    use crate::{store_init, store_limiter, module_instanciate, Module, Mem, MemType, Limits, Share, IdxType};
+
+    let mut store = store_init();
+    store_limiter(&mut store, StoreLimits { instances: Some(1), table_elements: Some(10), ..StoreLimits::default() });
+
+    let module = Module::default();
+    assert!(module_instanciate(&mut store, module, vec![]).is_ok());
+    let module = Module::default();
+    assert!(module_instanciate(&mut store, module, vec![]).is_err());
+
+    let mut store = store_init();
+    store_limiter(&mut store, StoreLimits { memory_size: Some(64 * 1024), ..StoreLimits::default() });
+    let mut module = Module::default();
+    module.mems.push(Mem(MemType(Limits { min: 2, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32)));
+    assert!(module_instanciate(&mut store, module, vec![]).is_err());
+}
+
+#[test]
+fn test_store_limits_failed_instanciate() {
+    use crate::{store_init, store_limiter, module_instanciate, Module, Mem, MemType, Table, TableType, RefType, HeapType, Limits, Share, IdxType, Data, DataMode, Expr, Instr};
+
+    let mut store = store_init();
+    store_limiter(&mut store, StoreLimits { instances: Some(1), table_elements: Some(10), memory_size: Some(64 * 1024), ..StoreLimits::default() });
+    let table = || Table(TableType(Limits { min: 10, max: None, page_size_log2: None }, RefType { nullable: true, heap: HeapType::Func }), None);
+
+    // the table is granted before the memory is denied, and given back with the instance
+    let mut module = Module::default();
+    module.tables.push(table());
+    module.mems.push(Mem(MemType(Limits { min: 2, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32)));
+    assert!(module_instanciate(&mut store, module, vec![]).is_err());
+    assert!(store.tables.is_empty());
+
+    // so are the table and the memory of an instance trapping on a segment out of bounds
+    let mut module = Module::default();
+    module.tables.push(table());
+    module.mems.push(Mem(MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32)));
+    module.data.push(Data { init: vec![1], mode: DataMode::Active { memory: 0, offset: Expr(vec![Instr::I32Const(1 << 16)]) } });
+    assert!(module_instanciate(&mut store, module, vec![]).is_err());
+    assert!(store.tables.is_empty() && store.mems.is_empty());
+
+    let mut module = Module::default();
+    module.tables.push(table());
+    module.mems.push(Mem(MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32)));
+    assert!(module_instanciate(&mut store, module, vec![]).is_ok());
+}
+
+#[test]
+fn test_store_limits_heap() {
+    use crate::{store_init, store_limiter, Module, Instr, Val, ValType, SubType, CompType, FieldType, StorageType, Mut};
+    use crate::testing::{exported_funcs, TestInstance};
+
+    // (func (param i32) (result i32) (array.len (array.new_default $a (local.get 0))))
+    let array = SubType { is_final: true, supers: vec![], rec: (0, 1), comp: CompType::Array(FieldType(StorageType::Val(ValType::I32), Mut::Var)) };
+    let module = Module {
+        types: vec![array, (vec![ValType::I32], vec![ValType::I32]).into()],
+        ..exported_funcs(vec![], vec![("alloc", 1, vec![Instr::LocalGet(0), Instr::ArrayNewDefault(0), Instr::ArrayLen])])
+    };
+    let limit = 1 << 20;
+    let mut store = store_init();
+    store_limiter(&mut store, StoreLimits { heap_size: Some(limit), ..StoreLimits::default() });
+    let instance = TestInstance::new(&mut store, module, vec![]);
+
+    // the arrays dropped are collected once the limiter refuses
+    let n = (limit / 2 / std::mem::size_of::<Val>()) as u32;
+    for _ in 0..10 {
+        assert_eq!(instance.call(&mut store, "alloc", vec![Val::I32Const(n)]).unwrap(), vec![Val::I32Const(n)]);
+    }
+    assert!(instance.call(&mut store, "alloc", vec![Val::I32Const(2 * n)]).is_err());
+}
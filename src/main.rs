@@ -0,0 +1,74 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use wasmiq::{
+    WasiCtx,
+    HostFs,
+    module_decode,
+    component_decode,
+    store_init,
+    wasi_run,
+    wasi_component_run,
+};
+
+const USAGE: &str = "usage: wasmiq run [--dir DIR]... [--env KEY=VALUE]... PROGRAM.wasm [ARGS]...";
+
+/// Layer field of the binary format, after the magic and the version, which is 1 for components.
+const COMPONENT_LAYER: [u8; 2] = [1, 0];
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) != Some("run") {
+        usage();
+    }
+    let mut ctx = WasiCtx::default();
+    let mut rest = args[1..].iter();
+    let program = loop {
+        match rest.next().map(String::as_str) {
+            Some("--dir") => match rest.next() {
+                Some(dir) => ctx.preopens.push((dir.clone(), Box::new(HostFs::new(dir)))),
+                None => usage(),
+            },
+            Some("--env") => match rest.next().and_then(|env| env.split_once('=')) {
+                Some((key, value)) => ctx.env.push((key.to_string(), value.to_string())),
+                None => usage(),
+            },
+            Some(program) if !program.starts_with("--") => break program.to_string(),
+            _ => usage(),
+        }
+    };
+    ctx.args.push(program.clone());
+    ctx.args.extend(rest.cloned());
+
+    let bytes = fs::read(&program).unwrap_or_else(|error| {
+        eprintln!("wasmiq: {}: {}", program, error);
+        process::exit(1);
+    });
+    let mut store = store_init();
+    let result = if bytes.get(6..8) == Some(&COMPONENT_LAYER[..]) {
+        let component = component_decode(&mut &bytes[..]).unwrap_or_else(|error| {
+            eprintln!("wasmiq: {}: cannot decode: {:?}", program, error);
+            process::exit(1);
+        });
+        wasi_component_run(&mut store, &component, ctx)
+    } else {
+        let module = module_decode(&mut &bytes[..]).unwrap_or_else(|error| {
+            eprintln!("wasmiq: {}: cannot decode: {:?}", program, error);
+            process::exit(1);
+        });
+        wasi_run(&mut store, &module, ctx)
+    };
+    match result {
+        Ok(code) => process::exit(code as i32),
+        Err(error) => {
+            eprintln!("wasmiq: {}: {:?}", program, error);
+            process::exit(1);
+        },
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -1,7 +1,12 @@
+use std::convert::TryFrom;
+
 use crate::{
     Store,
     MemType,
     MemAddr,
+    MemInst,
+    MemData,
+    SharedMemory,
     Byte,
     Error,
 
@@ -10,7 +15,7 @@ use crate::{
     grow_mem,
 };
 
-pub fn mem_alloc(store: &mut Store, memtype: MemType) -> MemAddr {
+pub fn mem_alloc(store: &mut Store, memtype: MemType) -> Result<MemAddr, Error> {
     alloc_mem(store, memtype)
 }
 
@@ -18,25 +23,47 @@ pub fn mem_type(store: &Store, memaddr: MemAddr) -> MemType {
     find_memtype(store, memaddr).unwrap()
 }
 
-pub fn mem_read(store: &Store, memaddr: MemAddr, i: u32) -> Result<Byte, Error> {
+pub fn mem_read(store: &Store, memaddr: MemAddr, i: u64) -> Result<Byte, Error> {
     let mi = &store.mems[memaddr];
-    if i as usize >= mi.data.len() { return Err(Error::Invalid); }
-    Ok(mi.data[i as usize])
+    let i = usize::try_from(i).map_err(|_| Error::Invalid)?;
+    match mi.read(i, 1) {
+        Some(bytes) => Ok(bytes[0]),
+        None => Err(Error::Invalid),
+    }
 }
 
-pub fn mem_write(store: &mut Store, memaddr: MemAddr, i: u32, byte: Byte) -> Result<(), Error> {
+pub fn mem_write(store: &mut Store, memaddr: MemAddr, i: u64, byte: Byte) -> Result<(), Error> {
     let mi = &mut store.mems[memaddr];
-    if i as usize >= mi.data.len() { return Err(Error::Invalid); }
-    mi.data[i as usize] = byte;
-    Ok(())
+    let i = usize::try_from(i).map_err(|_| Error::Invalid)?;
+    mi.write(i, &[byte]).ok_or(Error::Invalid)
 }
 
-pub fn mem_size(store: &Store, memaddr: MemAddr) -> u32 {
+/// Size in bytes, which may be over 4GiB for memories with i64 addresses.
+pub fn mem_size(store: &Store, memaddr: MemAddr) -> u64 {
     let mi = &store.mems[memaddr];
-    mi.data.len() as u32
+    mi.size() as u64
 }
 
-pub fn mem_grow(store: &mut Store, memaddr: MemAddr, n: u32) -> Result<(), Error> {
-    let mi = &mut store.mems[memaddr];
-    grow_mem(mi, n as usize)
-}
\ No newline at end of file
+pub fn mem_grow(store: &mut Store, memaddr: MemAddr, n: u64) -> Result<(), Error> {
+    let n = usize::try_from(n).map_err(|_| Error::Invalid)?;
+    grow_mem(store, memaddr, n)
+}
+
+/// Return a handle on the memory if it is shared. The handle can be sent to other
+/// host threads and added to their stores with `mem_import_shared`.
+pub fn mem_shared(store: &Store, memaddr: MemAddr) -> Option<SharedMemory> {
+    match &store.mems[memaddr].data {
+        MemData::Shared(shared) => Some(shared.clone()),
+        MemData::Unshared(_) => None,
+    }
+}
+
+/// Add a memory shared by another store, to be provided as an import.
+pub fn mem_import_shared(store: &mut Store, shared: SharedMemory) -> MemAddr {
+    let addr = store.mems.len();
+    let max = shared.max();
+    let idxtype = shared.idxtype();
+    let page_size_log2 = shared.page_size_log2();
+    store.mems.push(MemInst{ data: MemData::Shared(shared), max, idxtype, page_size_log2 });
+    addr
+}
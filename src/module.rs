@@ -11,15 +11,19 @@ pub use instance::{
     grow_mem,
     alloc_global,
     find_globaltype,
+    alloc_tag,
+    alloc_exn,
+    find_tagtype,
 };
 
 use crate::{
     Name,
     Byte,
     ValType,
-    FuncType,
+    SubType,
     MemType,
     TableType,
+    RefType,
     GlobalType,
     Expr,
     Error,
@@ -30,16 +34,20 @@ use crate::{
 #[derive(Default)]
 pub struct Module {
     pub id: Option<String>,
-    pub types: Vec<FuncType>,
+    pub types: Vec<SubType>,
     pub funcs: Vec<Func>,
     pub tables: Vec<Table>,
     pub mems: Vec<Mem>,
     pub globals: Vec<Global>,
+    pub tags: Vec<Tag>,
     pub elem: Vec<Elem>,
     pub data: Vec<Data>,
     pub start: Option<Start>,
     pub imports: Vec<Import>,
     pub exports: Vec<Export>,
+    /// Number of data segments declared ahead of the code,
+    /// required by instructions referring to data segments.
+    pub datacount: Option<u32>,
 }
 
 pub type TypeIdx = u32;
@@ -49,6 +57,10 @@ pub type MemIdx = u32;
 pub type GlobalIdx = u32;
 pub type LabelIdx = u32;
 pub type LocalIdx = u32;
+pub type ElemIdx = u32;
+pub type DataIdx = u32;
+pub type TagIdx = u32;
+pub type FieldIdx = u32;
 
 #[derive(Clone, Default)]
 pub struct Func {
@@ -57,7 +69,8 @@ pub struct Func {
     pub body: Expr,
 }
 
-pub struct Table(pub TableType);
+/// Table, whose elements are null or the value of its initializer.
+pub struct Table(pub TableType, pub Option<Expr>);
 
 pub struct Mem(pub MemType);
 
@@ -66,17 +79,32 @@ pub struct Global {
     pub init: Expr,
 }
 
+/// Exception tag, whose type gives the values thrown with it.
+pub struct Tag(pub TypeIdx);
+
 pub struct Elem {
-    pub table: TableIdx,
-    pub offset: Expr,
-    pub init: Vec<FuncIdx>,
+    pub tp: RefType,
+    pub init: Vec<Expr>,
+    pub mode: ElemMode,
+}
+
+pub enum ElemMode {
+    Passive,
+    Active { table: TableIdx, offset: Expr },
+    /// Only declares the functions referenced by `ref.func`.
+    Declarative,
 }
 
 #[derive(Clone)]
 pub struct Data {
-    pub data: MemIdx,
-    pub offset: Expr,
     pub init: Vec<Byte>,
+    pub mode: DataMode,
+}
+
+#[derive(Clone)]
+pub enum DataMode {
+    Passive,
+    Active { memory: MemIdx, offset: Expr },
 }
 
 pub struct Start(pub FuncIdx);
@@ -91,6 +119,7 @@ pub enum ExportDesc {
     Table(TableIdx),
     Mem(MemIdx),
     Global(GlobalIdx),
+    Tag(TagIdx),
 }
 
 pub struct Import {
@@ -104,6 +133,7 @@ pub enum ImportDesc {
     Table(TableType),
     Mem(MemType),
     Global(GlobalType),
+    Tag(TypeIdx),
 }
 
 pub use validate::Context;
@@ -143,6 +173,7 @@ pub fn module_validate(module: Module) -> Result<(), Error> {
 }
 
 pub use instance::module_instanciate;
+pub(crate) use instance::instanciate_module;
 
 pub fn module_imports(module: Module) -> Vec<(Name, Name, ExternType)> {
     let externtypes = module.validate().unwrap();
@@ -1,3 +1,7 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
 use crate::{
     Store,
     Module,
@@ -11,14 +15,22 @@ use crate::{
     Thread,
     StackEntry,
     Val,
+    Ref,
+    AnyRef,
     // Instr,
     // Start,
     FuncAddr,
     GlobalType,
     GlobalAddr,
+    Mut,
     GlobalInst,
     Func,
     FuncType,
+    SubType,
+    valtype_matches,
+    functype_matches,
+    func_matches,
+    ImportDesc,
     TableInst,
     Limits,
     TableType,
@@ -26,17 +38,34 @@ use crate::{
     MemAddr,
     MemType,
     MemInst,
+    TagAddr,
+    TagInst,
+    ExnAddr,
+    ExnInst,
     Export,
     ExportDesc,
     ExportInst,
-    ElemType,
-    ValType,
+    ElemMode,
+    ElemAddr,
+    ElemInst,
+    DataMode,
+    DataAddr,
+    DataInst,
+    Byte,
     Expr,
+    HostCode,
+    Invocation,
     Result as ExecResult,
+    val_matches,
 };
 
 pub fn module_instanciate(store: &mut Store, module: Module, externvals: Vec<ExternVal>) -> Result<ModuleInst, Error> {
-    let (frame, result) = module.instanciate(store, externvals);
+    instanciate_module(store, &module, externvals)
+}
+
+/// Instanciate a module kept by its owner, like the core modules of a component.
+pub(crate) fn instanciate_module(store: &mut Store, module: &Module, externvals: Vec<ExternVal>) -> Result<ModuleInst, Error> {
+    let (frame, result) = module.instanciate(store, externvals)?;
     if let ExecResult::Vals(_) = result {
         Ok(frame.module)
     } else {
@@ -45,33 +74,29 @@ pub fn module_instanciate(store: &mut Store, module: Module, externvals: Vec<Ext
 }
 
 impl Module {
-    fn instanciate(&self, store: &mut Store, externvals: Vec<ExternVal>) -> (Frame, ExecResult) {
+    fn instanciate(&self, store: &mut Store, externvals: Vec<ExternVal>) -> Result<(Frame, ExecResult), Error> {
         let frame_default = Frame::default();
         let trap = ExecResult::Trap;
 
         let externtypes = match self.validate() {
             Err(_error) => {
-                return (frame_default, trap);
+                return Ok((frame_default, trap));
             },
             Ok(externtypes) => externtypes,
         };
         let externtypes_imp = externtypes.0;
         if externtypes_imp.len() != externvals.len() { 
-            return (frame_default, trap);
+            return Ok((frame_default, trap));
         }
         let mut globaladdrs = vec![];
-        for (ext_val, ext_type) in externvals.iter().zip(externtypes_imp) {
+        for ((ext_val, ext_type), import) in externvals.iter().zip(externtypes_imp).zip(&self.imports) {
             match ext_val {
                 ExternVal::Func(funcaddr) => {
-                    let functype = match store.funcs.get(funcaddr.clone()) {
-                        None => return (frame_default, trap),
-                        Some(FuncInst::User(funcinst)) => funcinst.tp.clone(),
-                        Some(FuncInst::Host(funcinst)) => funcinst.tp.clone(),
-                    };
-                    if let ExternType::Func(ft) = ext_type {
-                        if Module::match_functype(functype, ft) { return (frame_default, trap); }
+                    if *funcaddr >= store.funcs.len() { return Ok((frame_default, trap)); }
+                    if let (ExternType::Func(_), ImportDesc::Func(typeidx)) = (ext_type, &import.desc) {
+                        if !func_matches(store, *funcaddr, &self.types, *typeidx) { return Ok((frame_default, trap)); }
                     } else {
-                        return (frame_default, trap);
+                        return Ok((frame_default, trap));
                     }
                 },
                 ExternVal::Table(tableaddr) => {
@@ -79,12 +104,12 @@ impl Module {
                     if let Some(tabletype) = find_tabletype(store, tableaddr.clone()) {
                         tabletype
                     } else {
-                        return (frame_default, trap);
+                        return Ok((frame_default, trap));
                     };
                     if let ExternType::Table(tt) = ext_type {
-                        if Module::match_tabletype(tabletype, tt) { return (frame_default, trap); }
+                        if !self.match_tabletype(&tabletype, &store.tables[*tableaddr].types, &tt) { return Ok((frame_default, trap)); }
                     } else {
-                        return (frame_default, trap);
+                        return Ok((frame_default, trap));
                     }
                 },
                 ExternVal::Mem(memaddr) => {
@@ -92,200 +117,254 @@ impl Module {
                     if let Some(memtype) = find_memtype(store, memaddr.clone()) {
                         memtype
                     } else {
-                        return (frame_default, trap);
+                        return Ok((frame_default, trap));
                     };
                     if let ExternType::Mem(mt) = ext_type {
-                        if Module::match_memtype(memtype, mt) { return (frame_default, trap); }
+                        if !Module::match_memtype(memtype, mt) { return Ok((frame_default, trap)); }
                     } else {
-                        return (frame_default, trap);
+                        return Ok((frame_default, trap));
                     }
                 },
                 ExternVal::Global(globaladdr) => {
-                    let globaltype = 
-                    if let Some(globaltype) = find_globaltype(store, globaladdr.clone()) {
-                        globaltype
+                    let globalinst = 
+                    if let Some(globalinst) = store.globals.get(*globaladdr) {
+                        globalinst
                     } else {
-                        return (frame_default, trap);
+                        return Ok((frame_default, trap));
                     };
                     if let ExternType::Global(gt) = ext_type {
-                        if Module::match_globaltype(globaltype, gt) { return (frame_default, trap); }
+                        if !self.match_globaltype(&globalinst.tp, &globalinst.types, &gt) { return Ok((frame_default, trap)); }
                     } else {
-                        return (frame_default, trap);
+                        return Ok((frame_default, trap));
                     }
                     globaladdrs.push(globaladdr.clone());
                 },
+                ExternVal::Tag(tagaddr) => {
+                    let taginst = 
+                    if let Some(taginst) = store.tags.get(*tagaddr) {
+                        taginst
+                    } else {
+                        return Ok((frame_default, trap));
+                    };
+                    if let ExternType::Tag(ft) = ext_type {
+                        if !self.match_tagtype(&taginst.tp, &taginst.types, &ft) { return Ok((frame_default, trap)); }
+                    } else {
+                        return Ok((frame_default, trap));
+                    }
+                },
             }
         }
 
+
+        if !store.instance_allocating() {
+            return Err(Error::ResourceLimit("module instanciate: instance".to_string()));
+        }
+        let allocated = Allocated::of(store);
+
         let mut thread = Thread::new(store);
 
+        // the functions are allocated next, so ref.func can already refer to their addresses
+        // and objects allocated here share the types of the instance
+        let types = Arc::new(self.types.clone());
         let mut moduleinst_g = ModuleInst::default();
+        moduleinst_g.types = types.clone();
         moduleinst_g.globaladdrs = globaladdrs;
+        moduleinst_g.funcaddrs = externvals.iter()
+            .filter_map(|externval| if let ExternVal::Func(funcaddr) = externval { Some(*funcaddr) } else { None })
+            .chain((thread.store.funcs.len()..).take(self.funcs.len()))
+            .collect();
         let frame_g = Frame { module: moduleinst_g, locals: vec![] };
-        thread.stack.push(StackEntry::Activation(0, frame_g));
+        thread.stack.push(StackEntry::Activation(0, frame_g.clone()));
         let mut vals = vec![];
         for global in &self.globals {
-            vals.push(Self::evaluate_expr(thread.store, global.init.clone()));
+            vals.push(Self::evaluate_expr(thread.store, &frame_g, global.init.clone()));
+        }
+        let mut tablerefs = vec![];
+        for table in &self.tables {
+            let init = match &table.1 {
+                Some(init) => Self::evaluate_expr(thread.store, &frame_g, init.clone()),
+                None => Val::default(&table.0.1.valtype(), &self.types),
+            };
+            if let Val::Ref(r) = init {
+                tablerefs.push(r);
+            } else {
+                thread.store.instance_released();
+                return Ok((frame_default, trap));
+            }
+        }
+        let mut refs = vec![];
+        for elem in &self.elem {
+            let mut elemrefs = vec![];
+            for expr in &elem.init {
+                if let Val::Ref(r) = Self::evaluate_expr(thread.store, &frame_g, expr.clone()) {
+                    elemrefs.push(r);
+                } else {
+                    thread.store.instance_released();
+                    return Ok((frame_default, trap));
+                }
+            }
+            refs.push(elemrefs);
         }
         thread.stack.pop();
 
-        let moduleinst = self.alloc_module(thread.store, vec![], vals);
+        let moduleinst = match self.alloc_module(thread.store, types, externvals, vals, tablerefs, refs) {
+            Ok(moduleinst) => moduleinst,
+            Err(error) => {
+                release_module(thread.store, allocated);
+                return Err(error);
+            },
+        };
+        let instance = Allocated::of(thread.store);
         let tableaddrs = moduleinst.tableaddrs.clone();
         let memaddrs = moduleinst.memaddrs.clone();
         let frame = Frame { module: moduleinst, locals: vec![] };
-        thread.stack.push(StackEntry::Activation(0, frame));
-
-        let mut init_elem_list = vec![]; 
-        for elem in &self.elem {
-            let eo = if let Val::I32Const(eo) = Self::evaluate_expr(thread.store, elem.offset.clone()) {
-                eo
+        thread.stack.push(StackEntry::Activation(0, frame.clone()));
+
+        // active segments are applied in order, as table.init or memory.init followed by
+        // elem.drop or data.drop, so the ones before a trapping segment stay written;
+        // declarative segments are dropped right away
+        for (elemidx, elem) in self.elem.iter().enumerate() {
+            let elemaddr = frame.module.elemaddrs[elemidx];
+            let (tableidx, offset) = match &elem.mode {
+                ElemMode::Active { table, offset } => (table, offset),
+                ElemMode::Passive => continue,
+                ElemMode::Declarative => {
+                    thread.store.elems[elemaddr].elem.clear();
+                    continue;
+                },
+            };
+            let eo = if let Val::I32Const(eo) = Self::evaluate_expr(thread.store, &frame, offset.clone()) {
+                eo as usize
             } else {
-                return (frame_default, trap);
+                release_trapped(thread.store, allocated, instance);
+                return Ok((frame_default, trap));
             };
-            let tableidx = elem.table;
-            let tableaddr = tableaddrs[tableidx as usize];
-            let tableinst = &thread.store.tables[tableaddr];
-            let eend = eo as usize + elem.init.len();
+            let tableinst = &mut thread.store.tables[tableaddrs[*tableidx as usize]];
+            let elem = std::mem::take(&mut thread.store.elems[elemaddr].elem);
+            let eend = eo + elem.len();
 
             if eend > tableinst.elem.len() {
-                return (frame_default, trap);
+                release_trapped(thread.store, allocated, instance);
+                return Ok((frame_default, trap));
             }
-            init_elem_list.push(eo);
+            tableinst.elem[eo..eend].copy_from_slice(&elem);
         }
 
-        let mut init_data_list = vec![]; 
-        for data in &self.data {
-            let data_o = if let Val::I32Const(data_o) = Self::evaluate_expr(thread.store, data.offset.clone()) {
-                data_o
-            } else {
-                return (frame_default, trap);
+        for (dataidx, data) in self.data.iter().enumerate() {
+            let (memidx, offset) = match &data.mode {
+                DataMode::Active { memory, offset } => (memory, offset),
+                DataMode::Passive => continue,
+            };
+            let data_o = match Self::evaluate_expr(thread.store, &frame, offset.clone()) {
+                Val::I32Const(data_o) => data_o as u64,
+                Val::I64Const(data_o) => data_o,
+                _ => {
+                    release_trapped(thread.store, allocated, instance);
+                    return Ok((frame_default, trap));
+                },
             };
-            let memidx = data.data;
-            let memaddr = memaddrs[memidx as usize];
-            let meminst = &thread.store.mems[memaddr];
-            let dend = data_o as usize + data.init.len();
+            let meminst = &mut thread.store.mems[memaddrs[*memidx as usize]];
+            let data = std::mem::take(&mut thread.store.datas[frame.module.dataaddrs[dataidx]].data);
+            let dend = data_o.checked_add(data.len() as u64).unwrap_or(u64::MAX);
 
-            if dend > meminst.data.len() {
-                return (frame_default, trap);
+            if dend > meminst.size() as u64 {
+                release_trapped(thread.store, allocated, instance);
+                return Ok((frame_default, trap));
             }
-            init_data_list.push(data_o);
+            meminst.write(data_o as usize, &data);
         }
 
         let frame = if let Some(StackEntry::Activation(0, frame)) = thread.stack.pop() {
             frame
         } else {
-            return (frame_default, trap);
+            release_trapped(thread.store, allocated, instance);
+            return Ok((frame_default, trap));
         };
-        for (elem, eo) in self.elem.iter().zip(init_elem_list) {
-            for (j, funcidx) in elem.init.iter().enumerate() {
-                let funcaddr = frame.module.funcaddrs[funcidx.clone() as usize];
-                let tableidx = elem.table;
-                let tableaddr = tableaddrs[tableidx as usize];
-                let tableinst = &mut thread.store.tables[tableaddr];
-                tableinst.elem[eo as usize + j] = Some(funcaddr);
-            }
-        }
-        for (data, data_o) in self.data.iter().zip(init_data_list) {
-            let memidx = data.data;
-            let memaddr = memaddrs[memidx as usize];
-            let meminst = &mut thread.store.mems[memaddr];
-            for (j, byte) in data.init.iter().enumerate() {
-                meminst.data[data_o as usize + j] = byte.clone();
-            }
-        }
 
         if let Some(start) = &self.start {
             let funcaddr = frame.module.funcaddrs[start.0 as usize];
             let mut thread = Thread::new(store);
-            thread.execute_invoke(&funcaddr);
+            match thread.execute_func(&funcaddr, vec![]) {
+                Ok(Invocation::Done(_)) => {},
+                Err(Error::Exception(exnaddr)) => return Err(Error::Exception(exnaddr)),
+                _ => {
+                    release_trapped(thread.store, allocated, instance);
+                    return Ok((frame_default, trap));
+                },
+            }
         }
 
-        (frame, ExecResult::Vals(vec![]))
+        Ok((frame, ExecResult::Vals(vec![])))
     }
 
-    pub fn invoke(store: &mut Store, funcaddr: FuncAddr, vals: Vec<Val>) -> ExecResult {
+    pub fn invoke(store: &mut Store, funcaddr: FuncAddr, vals: Vec<Val>) -> Result<Invocation, Error> {
         let funcinst = if let Some(funcinst) = store.funcs.get(funcaddr) {
             funcinst
         } else {
-            return ExecResult::Trap;
+            return Err(Error::OutOfIndex("invoke".to_string()));
         };
-        let (argtypes, returntypes) = match funcinst {
-            FuncInst::User(user) => user.tp.clone(),
-            FuncInst::Host(host) => host.tp.clone(),
+        let ((argtypes, _), types) = match funcinst {
+            FuncInst::User(user) => (user.tp.clone(), user.module.types.clone()),
+            FuncInst::Host(host) => (host.tp.clone(), Arc::default()),
         };
         if vals.len() != argtypes.len() {
-            return ExecResult::Trap;
+            return Err(Error::Invalid);
         }
-        for (argtype, val) in argtypes.iter().zip(vals.clone()) {
-            let matches = match val {
-                Val::I32Const(_) => argtype == &ValType::I32,
-                Val::I64Const(_) => argtype == &ValType::I64,
-                Val::F32Const(_) => argtype == &ValType::F32,
-                Val::F64Const(_) => argtype == &ValType::F64,
-            };
-            if !matches { return ExecResult::Trap; }
+        for (argtype, val) in argtypes.iter().zip(vals.iter()) {
+            if !val_matches(store, val, argtype, &types) { return Err(Error::Invalid); }
         }
 
-        let dummy_frame = Frame{ module: ModuleInst::default(), locals: vec![] };
         let mut thread = Thread::new(store);
-        thread.stack.push(StackEntry::Activation(0, dummy_frame.clone()));
-        let vals: Vec<StackEntry> = vals.clone().iter().map(|v| StackEntry::Value(v.clone())).collect();
-        thread.stack.extend(vals);
-
-        let mut thread = Thread::new(store);
-        thread.execute_invoke(&funcaddr);
-
-        let mut returnvals = vec![];
-        for _ in 0..returntypes.len() {
-            if let Some(StackEntry::Value(v)) = thread.stack.pop() {
-                returnvals.push(v);
-            }
-        }
-
-        ExecResult::Vals(returnvals)
+        thread.execute_func(&funcaddr, vals)
     }
 
-    fn alloc_module(&self, store: &mut Store, externvals: Vec<ExternVal>, vals: Vec<Val>) -> ModuleInst {
+    fn alloc_module(&self, store: &mut Store, types: Arc<Vec<SubType>>, externvals: Vec<ExternVal>, vals: Vec<Val>, tablerefs: Vec<Ref>, refs: Vec<Vec<Ref>>) -> Result<ModuleInst, Error> {
         let mut moduleinst = ModuleInst::default();
-
-        fn hostfunc() {}
-        alloc_hostfunc(store, (vec![], vec![]), hostfunc);
+        moduleinst.types = types;
 
         let mut funcaddrs = vec![];
         for func in &self.funcs {
-            let funcaddr = alloc_func(store, func, &moduleinst);
+            let funcaddr = alloc_func(store, func, &moduleinst)?;
             funcaddrs.push(funcaddr);
         }
 
         let mut tableaddrs = vec![];
-        for table in &self.tables {
-            let tableaddr = alloc_table(store, table.0.clone());
+        for (table, init) in self.tables.iter().zip(tablerefs) {
+            let tableaddr = alloc_table(store, table.0.clone(), moduleinst.types.clone(), init)?;
             tableaddrs.push(tableaddr);
         }
 
         let mut memaddrs = vec![];
         for mem in &self.mems {
-            let memaddr = alloc_mem(store, mem.0.clone());
+            let memaddr = alloc_mem(store, mem.0.clone())?;
             memaddrs.push(memaddr);
         }
 
         let mut globaladdrs = vec![];
         for (i, global) in self.globals.iter().enumerate() {
-            let globaladdr = alloc_global(store, global.tp.clone(), vals[i]);
+            let globaladdr = alloc_global(store, global.tp.clone(), moduleinst.types.clone(), vals[i]);
             globaladdrs.push(globaladdr);
         }
 
+        let mut tagaddrs = vec![];
+        for tag in &self.tags {
+            let functype = self.types[tag.0 as usize].functype().cloned().unwrap_or_default();
+            tagaddrs.push(alloc_tag(store, functype, moduleinst.types.clone()));
+        }
+
 
         let mut funcaddrs_ext = vec![];
         let mut tableaddrs_ext = vec![];
         let mut memaddrs_ext = vec![];
         let mut globaladdrs_ext = vec![];
+        let mut tagaddrs_ext = vec![];
         for externval in externvals {
             match externval {
                 ExternVal::Func(func) => funcaddrs_ext.push(func),
                 ExternVal::Global(global) => globaladdrs_ext.push(global),
                 ExternVal::Mem(mem) => memaddrs_ext.push(mem),
                 ExternVal::Table(table) => tableaddrs_ext.push(table),
+                ExternVal::Tag(tag) => tagaddrs_ext.push(tag),
             }
         }
 
@@ -293,6 +372,17 @@ impl Module {
         tableaddrs_ext.extend(tableaddrs);
         memaddrs_ext.extend(memaddrs);
         globaladdrs_ext.extend(globaladdrs);
+        tagaddrs_ext.extend(tagaddrs);
+
+        let mut elemaddrs = vec![];
+        for elemrefs in refs {
+            elemaddrs.push(alloc_elem(store, elemrefs));
+        }
+
+        let mut dataaddrs = vec![];
+        for data in &self.data {
+            dataaddrs.push(alloc_data(store, data.init.clone()));
+        }
 
         
         let mut exportinsts = vec![];
@@ -303,23 +393,34 @@ impl Module {
                 ExportDesc::Table(tableidx) => ExternVal::Table(tableaddrs_ext[tableidx.clone() as usize]),
                 ExportDesc::Mem(memidx) => ExternVal::Mem(memaddrs_ext[memidx.clone() as usize]),
                 ExportDesc::Global(globalidx) => ExternVal::Global(globaladdrs_ext[globalidx.clone() as usize]),
+                ExportDesc::Tag(tagidx) => ExternVal::Tag(tagaddrs_ext[*tagidx as usize]),
             };
             let exportinst = ExportInst{ name: export.name.clone(), value: externval };
             exportinsts.push(exportinst);
         }
 
-        moduleinst.types = self.types.clone();
         moduleinst.funcaddrs = funcaddrs_ext;
         moduleinst.tableaddrs = tableaddrs_ext;
         moduleinst.memaddrs = memaddrs_ext;
         moduleinst.globaladdrs = globaladdrs_ext;
+        moduleinst.elemaddrs = elemaddrs;
+        moduleinst.dataaddrs = dataaddrs;
+        moduleinst.tagaddrs = tagaddrs_ext;
         moduleinst.exports = exportinsts;
 
-        moduleinst
+        // the functions were allocated before their instance was complete
+        for funcaddr in &moduleinst.funcaddrs[moduleinst.funcaddrs.len() - self.funcs.len()..] {
+            if let FuncInst::User(funcinst) = &mut store.funcs[*funcaddr] {
+                funcinst.module = moduleinst.clone();
+            }
+        }
+
+        Ok(moduleinst)
     }
 
-    fn evaluate_expr(store: &mut Store, expr: Expr) -> Val {
+    fn evaluate_expr(store: &mut Store, frame: &Frame, expr: Expr) -> Val {
         let mut thread = Thread::new(store);
+        thread.stack.push(StackEntry::Activation(0, frame.clone()));
         thread.spawn(&mut expr.0.clone());
         if let Some(StackEntry::Value(val)) = thread.stack.pop() {
             val
@@ -328,13 +429,9 @@ impl Module {
         }
     }
 
-    fn match_functype(ft1: FuncType, ft2: FuncType) -> bool {
-        ft1 != ft2
-    }
-
     fn match_limits(limits1: Limits, limits2: Limits) -> bool {
-        let Limits{min: n1, max: m1} = limits1;
-        let Limits{min: n2, max: m2} = limits2;
+        let Limits{min: n1, max: m1, ..} = limits1;
+        let Limits{min: n2, max: m2, ..} = limits2;
         if n1 >= n2 {
             if let Some(m2) = m2 {
                 if let Some(m1) = m1 {
@@ -350,80 +447,230 @@ impl Module {
         }
     }
 
-    fn match_tabletype(tt1: TableType, tt2: TableType) -> bool {
-        let TableType(limits1, elemtype1) = tt1;
-        let TableType(limits2, elemtype2) = tt2;
-        Module::match_limits(limits1, limits2) && elemtype1 == elemtype2
+    // tables are both read and written, so their reference types are equal;
+    // the exported type refers to the types of its own module
+    fn match_tabletype(&self, tt1: &TableType, types1: &[SubType], tt2: &TableType) -> bool {
+        let (vt1, vt2) = (tt1.1.valtype(), tt2.1.valtype());
+        Module::match_limits(tt1.0.clone(), tt2.0.clone())
+            && valtype_matches(types1, &vt1, &self.types, &vt2) && valtype_matches(&self.types, &vt2, types1, &vt1)
     }
 
-    fn match_memtype(MemType(limits1): MemType, MemType(limits2): MemType) -> bool {
-        Module::match_limits(limits1, limits2)
+    fn match_memtype(mt1: MemType, mt2: MemType) -> bool {
+        // the page sizes match exactly
+        let page_sizes = mt1.page_size_log2() == mt2.page_size_log2();
+        let MemType(limits1, share1, idxtype1) = mt1;
+        let MemType(limits2, share2, idxtype2) = mt2;
+        Module::match_limits(limits1, limits2) && share1 == share2 && idxtype1 == idxtype2 && page_sizes
     }
 
-    fn match_globaltype(gt1: GlobalType, gt2: GlobalType) -> bool {
-        gt1 != gt2
+    // mutable globals are aliased so their types are equal, immutable ones may be subtypes;
+    // the exported type refers to the types of its own module
+    fn match_globaltype(&self, gt1: &GlobalType, types1: &[SubType], gt2: &GlobalType) -> bool {
+        match (gt1.1, gt2.1) {
+            (Mut::Var, Mut::Var) => valtype_matches(types1, &gt1.0, &self.types, &gt2.0) && valtype_matches(&self.types, &gt2.0, types1, &gt1.0),
+            (Mut::Const, Mut::Const) => valtype_matches(types1, &gt1.0, &self.types, &gt2.0),
+            _ => false,
+        }
+    }
+
+    // tags are both thrown and caught, so their types are equal
+    fn match_tagtype(&self, ft1: &FuncType, types1: &[SubType], ft2: &FuncType) -> bool {
+        functype_matches(types1, ft1, &self.types, ft2) && functype_matches(&self.types, ft2, types1, ft1)
     }
 }
 
-fn alloc_func(store: &mut Store, func: &Func, moduleinst: &ModuleInst) -> FuncAddr {
+fn alloc_func(store: &mut Store, func: &Func, moduleinst: &ModuleInst) -> Result<FuncAddr, Error> {
+    if !store.func_allocating() {
+        return Err(Error::ResourceLimit("alloc func".to_string()));
+    }
     let addr = store.funcs.len();
-    let functype = &moduleinst.types[func.tp as usize];
-    let funcinst = FuncInst::user(functype.clone(), moduleinst.clone(), func.clone());
+    let functype = moduleinst.types[func.tp as usize].functype().cloned().unwrap_or_default();
+    let funcinst = FuncInst::user(functype, moduleinst.clone(), func.clone());
     store.funcs.push(funcinst);
-    addr
+    Ok(addr)
+}
+
+// give back the instance and the objects a failed alloc_module took from the store
+/// Lengths of the store before or after the allocation of an instance.
+#[derive(Clone, Copy, PartialEq)]
+struct Allocated {
+    funcs: usize,
+    tables: usize,
+    mems: usize,
+    globals: usize,
+    elems: usize,
+    datas: usize,
+    tags: usize,
+    exns: usize,
+}
+
+impl Allocated {
+    fn of(store: &Store) -> Allocated {
+        Allocated {
+            funcs: store.funcs.len(),
+            tables: store.tables.len(),
+            mems: store.mems.len(),
+            globals: store.globals.len(),
+            elems: store.elems.len(),
+            datas: store.datas.len(),
+            tags: store.tags.len(),
+            exns: store.exns.len(),
+        }
+    }
+}
+
+/// Release an instance allocated from `before` up to `instance` whose instantiation trapped,
+/// unless the tables, globals and exceptions allocated before reach its functions or the
+/// exceptions thrown since, like an imported table a segment wrote to, or the start
+/// function allocated more in the store. References the host took from the start
+/// function are not tracked, as with the heap.
+fn release_trapped(store: &mut Store, before: Allocated, instance: Allocated) {
+    if (Allocated { exns: instance.exns, ..Allocated::of(store) }) != instance {
+        return;
+    }
+    let mut pending: Vec<Val> = store.tables[..before.tables].iter().flat_map(|table| table.elem.iter())
+        .map(|r| Val::Ref(*r))
+        .chain(store.globals[..before.globals].iter().map(|global| global.value))
+        .chain(store.exns[..before.exns].iter().flat_map(|exn| exn.fields.iter().copied()))
+        .collect();
+    let mut visited = HashSet::new();
+    while let Some(val) = pending.pop() {
+        match val {
+            Val::Ref(Ref::Func(funcaddr)) if funcaddr >= before.funcs => return,
+            Val::Ref(Ref::Exn(exnaddr)) if exnaddr >= before.exns => return,
+            Val::Ref(Ref::Struct(addr) | Ref::Array(addr) | Ref::ExternAny(AnyRef::Struct(addr) | AnyRef::Array(addr))) => {
+                if let Some(obj) = store.heap.try_get(addr).filter(|_| visited.insert(addr)) {
+                    pending.extend(obj.fields.iter().copied());
+                }
+            },
+            _ => {},
+        }
+    }
+    release_module(store, before);
+}
+
+fn release_module(store: &mut Store, before: Allocated) {
+    let n = store.funcs.drain(before.funcs..).count();
+    for _ in 0..n {
+        store.func_released();
+    }
+    let elements: Vec<u32> = store.tables.drain(before.tables..).map(|tableinst| tableinst.elem.len() as u32).collect();
+    for n in elements {
+        store.table_released(n);
+    }
+    let sizes: Vec<usize> = store.mems.drain(before.mems..).map(|meminst| meminst.size()).collect();
+    for size in sizes {
+        store.memory_released(size);
+    }
+    store.globals.truncate(before.globals);
+    store.elems.truncate(before.elems);
+    store.datas.truncate(before.datas);
+    store.tags.truncate(before.tags);
+    store.exns.truncate(before.exns);
+    store.instance_released();
 }
 
-pub fn alloc_hostfunc(store: &mut Store, functype: FuncType, hostfunc: fn()) -> FuncAddr {
+pub fn alloc_hostfunc(store: &mut Store, functype: FuncType, hostfunc: HostCode) -> Result<FuncAddr, Error> {
+    if !store.func_allocating() {
+        return Err(Error::ResourceLimit("alloc hostfunc".to_string()));
+    }
     let addr = store.funcs.len();
     let funcinst = FuncInst::host(functype, hostfunc);
     store.funcs.push(funcinst);
-    addr
+    Ok(addr)
 }
 
-pub fn alloc_table<'a>(store: &'a mut Store, tabletype: TableType) -> TableAddr {
+pub fn alloc_table<'a>(store: &'a mut Store, tabletype: TableType, types: Arc<Vec<SubType>>, init: Ref) -> Result<TableAddr, Error> {
     let addr = store.tables.len();
-    let TableType(Limits{ min: n, max: m }, _) = tabletype;
-    let mut elem = vec![];
-    for _ in 0..n { elem.push(None) }
-    let tableinst = TableInst{ elem: elem, max: m };
+    // validated to fit in u32
+    let TableType(Limits{ min: n, max: m, .. }, tp) = tabletype;
+    let (n, m) = (n as u32, m.map(|m| m as u32));
+    if !store.table_growing(0, n, m) {
+        return Err(Error::ResourceLimit("alloc table".to_string()));
+    }
+    let elem = vec![init; n as usize];
+    let tableinst = TableInst{ elem, max: m, tp, types };
     store.tables.push(tableinst);
-    addr
+    Ok(addr)
 }
 
-pub fn alloc_mem<'a>(store: &'a mut Store, memtype: MemType) -> MemAddr {
+pub fn alloc_mem<'a>(store: &'a mut Store, memtype: MemType) -> Result<MemAddr, Error> {
     let addr = store.mems.len();
-    let MemType(Limits{ min: n, max: m }) = memtype;
-    let data = Vec::with_capacity((n * 64) as usize);
-    let meminst = MemInst{ data: data, max: m };
+    let page_size_log2 = memtype.page_size_log2();
+    let MemType(Limits{ min: n, max: m, .. }, share, idxtype) = memtype;
+    let size = match pages_to_bytes(n, page_size_log2) {
+        Some(size) => size,
+        None => return Err(Error::ResourceLimit("alloc mem".to_string())),
+    };
+    if !store.memory_growing(0, size, m.map(|m| pages_to_bytes(m, page_size_log2).unwrap_or(usize::MAX))) {
+        return Err(Error::ResourceLimit("alloc mem".to_string()));
+    }
+    // memories of several GiB may not fit in the host
+    let mut data = vec![];
+    if data.try_reserve_exact(size).is_err() {
+        store.memory_released(size);
+        return Err(Error::ResourceLimit("alloc mem".to_string()));
+    }
+    data.resize(size, 0x00);
+    let meminst = MemInst::new(data, m, share, idxtype, page_size_log2);
     store.mems.push(meminst);
-    addr
+    Ok(addr)
 }
 
-pub fn alloc_global<'a>(store: &'a mut Store, globaltype: GlobalType, val: Val) -> GlobalAddr {
+pub fn alloc_global<'a>(store: &'a mut Store, globaltype: GlobalType, types: Arc<Vec<SubType>>, val: Val) -> GlobalAddr {
     let addr = store.globals.len();
-    let globalinst = GlobalInst{ value: val, mutability: globaltype.1 };
+    let globalinst = GlobalInst{ value: val, tp: globaltype, types };
     store.globals.push(globalinst);
     addr
 }
 
+pub fn alloc_tag(store: &mut Store, functype: FuncType, types: Arc<Vec<SubType>>) -> TagAddr {
+    let addr = store.tags.len();
+    store.tags.push(TagInst{ tp: functype, types });
+    addr
+}
+
+pub fn alloc_exn(store: &mut Store, tagaddr: TagAddr, fields: Vec<Val>) -> ExnAddr {
+    let addr = store.exns.len();
+    store.exns.push(ExnInst{ tag: tagaddr, fields });
+    addr
+}
+
+fn alloc_elem(store: &mut Store, elem: Vec<Ref>) -> ElemAddr {
+    let addr = store.elems.len();
+    store.elems.push(ElemInst{ elem });
+    addr
+}
+
+fn alloc_data(store: &mut Store, data: Vec<Byte>) -> DataAddr {
+    let addr = store.datas.len();
+    store.datas.push(DataInst{ data });
+    addr
+}
+
 pub fn find_tabletype(store: &Store, tableaddr: TableAddr) -> Option<TableType> {
     match store.tables.get(tableaddr.clone()) {
         None => None,
-        Some(TableInst{elem, max: m}) => {
-            Some(TableType(Limits{min: elem.len() as u32, max: m.clone()}, ElemType::FuncRef))
+        Some(TableInst{elem, max: m, tp, ..}) => {
+            Some(TableType(Limits{min: elem.len() as u64, max: m.map(u64::from), page_size_log2: None}, *tp))
         }
     }
 }
 
-pub fn grow_table(tableinst: &mut TableInst, n: usize) -> std::result::Result<(), Error> {
-    let len = n + (tableinst.elem.len() / (64*1024));
-    if len > 2usize.pow(32) { return Err(Error::Invalid); }
-    if let Some(mx) = tableinst.max {
+pub fn grow_table(store: &mut Store, tableaddr: TableAddr, n: usize, init: Ref) -> std::result::Result<(), Error> {
+    let tableinst = &store.tables[tableaddr];
+    let current = tableinst.elem.len();
+    let len = current + n;
+    if len > u32::MAX as usize { return Err(Error::Invalid); }
+    let max = tableinst.max;
+    if let Some(mx) = max {
         if (mx as usize) < len { return Err(Error::Invalid); }
     }
-    for _ in 0..n {
-        tableinst.elem.push(None);    
+    if !store.table_growing(current as u32, len as u32, max) {
+        return Err(Error::ResourceLimit("grow table".to_string()));
     }
+    let tableinst = &mut store.tables[tableaddr];
+    tableinst.elem.resize(len, init);
 
     Ok(())
 }
@@ -431,37 +678,53 @@ pub fn grow_table(tableinst: &mut TableInst, n: usize) -> std::result::Result<()
 pub fn find_memtype(store: &Store, memaddr: TableAddr) -> Option<MemType> {
     match store.mems.get(memaddr.clone()) {
         None => None,
-        Some(MemInst{data, max}) => {
-            Some(MemType(Limits{min: (data.len()/64) as u32, max: max.clone()}))
+        Some(meminst) => {
+            // 64KiB pages are left implicit
+            let page_size_log2 = Some(meminst.page_size_log2).filter(|log2| *log2 != 16);
+            Some(MemType(Limits{min: meminst.pages(), max: meminst.max, page_size_log2}, meminst.share(), meminst.idxtype))
         }
     }
 }
 
-pub fn grow_mem(meminst: &mut MemInst, n: usize) -> std::result::Result<(), Error> {
-    let len = n + (meminst.data.len() / (64*1024));
-    if len > 2usize.pow(16) { return Err(Error::Invalid); }
-    if let Some(mx) = meminst.max {
-        if (mx as usize) < len { return Err(Error::Invalid); }
+pub fn grow_mem(store: &mut Store, memaddr: MemAddr, n: usize) -> std::result::Result<(), Error> {
+    let meminst = &store.mems[memaddr];
+    let current = meminst.size();
+    let page_size_log2 = meminst.page_size_log2;
+    let len = (n as u64).checked_add(meminst.pages()).ok_or(Error::Invalid)?;
+    if len > meminst.idxtype.max_pages(page_size_log2) { return Err(Error::Invalid); }
+    let max = meminst.max;
+    if let Some(mx) = max {
+        if mx < len { return Err(Error::Invalid); }
     }
-    for _ in 0..n {
-        let page = [0x00;64*1024];
-        meminst.data.extend(Vec::from(page));    
+    let size = pages_to_bytes(len, page_size_log2).ok_or_else(|| Error::ResourceLimit("grow mem".to_string()))?;
+    if !store.memory_growing(current, size, max.map(|mx| pages_to_bytes(mx, page_size_log2).unwrap_or(usize::MAX))) {
+        return Err(Error::ResourceLimit("grow mem".to_string()));
+    }
+    let meminst = &mut store.mems[memaddr];
+    let grown = meminst.with_data_mut(|data| {
+        data.try_reserve_exact(size - current).ok()?;
+        data.resize(size, 0x00);
+        Some(())
+    });
+    if grown.is_none() {
+        store.memory_released(size - current);
+        return Err(Error::ResourceLimit("grow mem".to_string()));
     }
-
     Ok(())
 }
 
+// None when the bytes can't be addressed on the host
+fn pages_to_bytes(pages: u64, page_size_log2: u32) -> Option<usize> {
+    usize::try_from(pages).ok()?.checked_mul(1 << page_size_log2)
+}
+
 pub fn find_globaltype(store: &Store, globaladdr: GlobalAddr) -> Option<GlobalType> {
     match store.globals.get(globaladdr.clone()) {
         None => None,
-        Some(GlobalInst{value: val, mutability: mt}) => {
-            let vt = match val {
-                Val::I32Const(_) => ValType::I32,
-                Val::I64Const(_) => ValType::I64,
-                Val::F32Const(_) => ValType::F32,
-                Val::F64Const(_) => ValType::F64,
-            };
-            Some(GlobalType(vt, mt.clone()))
-        },
+        Some(globalinst) => Some(globalinst.tp.clone()),
     }
-}
\ No newline at end of file
+}
+
+pub fn find_tagtype(store: &Store, tagaddr: TagAddr) -> Option<FuncType> {
+    store.tags.get(tagaddr).map(|taginst| taginst.tp.clone())
+}
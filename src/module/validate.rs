@@ -1,8 +1,10 @@
 use crate::{
     ValType,
+    RefType,
+    HeapType,
     ResultType,
     FuncType,
-    ElemType,
+    SubType,
     TableType,
     // Limits,
     MemType,
@@ -11,7 +13,7 @@ use crate::{
     Name,
     // Mut,
     // Expr,
-    // Instr,
+    Instr,
     Error,
 };
 use super::{
@@ -23,32 +25,55 @@ use super::{
     Mem,
     Global,
     Elem,
+    Tag,
     Start,
     ExportDesc,
     ImportDesc,
     
     TypeIdx,
     FuncIdx,
+    TableIdx,
+    MemIdx,
     GlobalIdx,
     LocalIdx,
     LabelIdx,
+    ElemIdx,
+    DataIdx,
+    TagIdx,
+    ElemMode,
+    DataMode,
 };
 
 #[derive(Clone)]
 pub struct Context {
-    types: Vec<FuncType>,
+    types: Vec<SubType>,
     funcs: Option<Vec<FuncType>>,
     tables: Option<Vec<TableType>>,
     mems: Option<Vec<MemType>>,
     globals: Option<Vec<GlobalType>>,
+    elems: Option<Vec<RefType>>,
+    datas: Option<u32>,
+    tags: Option<Vec<FuncType>>,
     locals: Option<Vec<ValType>>,
     labels: Option<Vec<ResultType>>,
     rtn: Option<ResultType>,
+    refs: Vec<FuncIdx>,
+    // non-defaultable locals which are not set yet
+    unset: Vec<LocalIdx>,
 }
 
 impl Context {
+    /// Function type at `idx`, None for other types too.
     pub fn tp(&self, idx: TypeIdx) -> Option<FuncType> {
-        self.types.get(idx.clone() as usize).cloned()
+        self.types.get(idx.clone() as usize).and_then(|subtype| subtype.functype().cloned())
+    }
+
+    pub fn subtype(&self, idx: TypeIdx) -> Option<SubType> {
+        self.types.get(idx as usize).cloned()
+    }
+
+    pub fn types(&self) -> &[SubType] {
+        &self.types
     }
 
     pub fn global(&self, idx: GlobalIdx) -> Option<GlobalType> {
@@ -57,21 +82,45 @@ impl Context {
         })
     }
 
+    pub fn elem(&self, idx: ElemIdx) -> Option<RefType> {
+        self.elems.as_ref().and_then(|elemtps| {
+            elemtps.get(idx as usize).cloned()
+        })
+    }
+
+    pub fn data(&self, idx: DataIdx) -> bool {
+        self.datas.is_some_and(|n| idx < n)
+    }
+
+    pub fn tag(&self, idx: TagIdx) -> Option<FuncType> {
+        self.tags.as_ref().and_then(|tagtps| {
+            tagtps.get(idx as usize).cloned()
+        })
+    }
+
     pub fn local(&self, idx: LocalIdx) -> Option<ValType> {
         self.locals.as_ref().and_then(|valtps| {
             valtps.get(idx.clone() as usize).cloned()
         })
     }
 
-    pub fn table(&self) -> Option<TableType> {
+    pub fn is_set(&self, idx: LocalIdx) -> bool {
+        !self.unset.contains(&idx)
+    }
+
+    pub fn set_local(&mut self, idx: LocalIdx) {
+        self.unset.retain(|x| *x != idx);
+    }
+
+    pub fn table(&self, idx: TableIdx) -> Option<TableType> {
         self.tables.as_ref().and_then(|tabletps| {
-            tabletps.get(0).cloned()
+            tabletps.get(idx as usize).cloned()
         })
     }
 
-    pub fn mem(&self) -> Option<MemType> {
-        self.mems.as_ref().and_then(|valtps| {
-            valtps.get(0).cloned()
+    pub fn mem(&self, idx: MemIdx) -> Option<MemType> {
+        self.mems.as_ref().and_then(|memtps| {
+            memtps.get(idx as usize).cloned()
         })
     }
 
@@ -85,6 +134,12 @@ impl Context {
         self.rtn.clone()
     }
 
+    /// Whether the function is declared outside of function bodies,
+    /// so `ref.func` may refer to it.
+    pub fn is_ref(&self, idx: FuncIdx) -> bool {
+        self.refs.contains(&idx)
+    }
+
     pub fn func(&self, idx: FuncIdx) -> Option<FuncType> {
         self.funcs.as_ref().and_then(|restps| {
             restps.get(idx.clone() as usize).cloned()
@@ -112,15 +167,21 @@ impl Module {
             tables: None,
             mems: None,
             globals: None,
+            elems: Some(self.elem.iter().map(|elem| elem.tp).collect()),
+            datas: self.datacount,
+            tags: None,
             locals: None,
             labels: None,
             rtn: None,
+            refs: self.func_refs(),
+            unset: vec![],
         };
 
-        let (funcs, tables, mems, globals) = {
+        let (funcs, tables, mems, tags, globals) = {
             let mut funcs: Vec<FuncType> = vec![];
             let mut tables: Vec<TableType> = vec![];
             let mut mems: Vec<MemType> = vec![];
+            let mut tags: Vec<FuncType> = vec![];
             let mut globals: Vec<GlobalType> = vec![];
 
             for imp in self.imports.iter().map(|imp| imp.desc.validate(&context)) {
@@ -130,11 +191,13 @@ impl Module {
                     ExternType::Table(tabletype) => { tables.push(tabletype); },
                     ExternType::Mem(memtype) => { mems.push(memtype); },
                     ExternType::Global(globaltype) => { globals.push(globaltype); },
+                    ExternType::Tag(functype) => { tags.push(functype); },
                 }
             }
     
-            for functype in self.funcs.iter().map(|f| f.validate(&context)) {
-                let functype = functype?;
+            for func in &self.funcs {
+                let functype = context.tp(func.tp)
+                    .ok_or(Error::OutOfIndex("func validate: self.tp".to_string()))?;
                 funcs.push(functype);
             }
 
@@ -148,32 +211,47 @@ impl Module {
                 mems.push(memtype);
             }
 
-            for globaltype in self.globals.iter().map(|t| t.validate(&context)) {
-                let globaltype = globaltype?;
-                globals.push(globaltype);
+            for functype in self.tags.iter().map(|t| t.validate(&context)) {
+                let functype = functype?;
+                tags.push(functype);
             }
 
-            (funcs, tables, mems, globals)
+            // global initializers only see the imported globals
+            let imported_globals = globals.clone();
+            globals.extend(self.globals.iter().map(|global| global.tp.clone()));
+
+            (funcs, tables, mems, tags, (imported_globals, globals))
         };
 
         context.funcs = Some(funcs);
         context.tables = Some(tables);
         context.mems = Some(mems);
+        context.tags = Some(tags);
 
+        // constant expressions only see the imported globals
         let mut context_g = context.clone();
-        context_g.globals = Some(globals);
-
-        // functype is always valid
-        // for tp in &self.types {
-        //     tp.validate()
-        // }
+        context_g.globals = Some(globals.0);
+        context.globals = Some(globals.1);
+
+        for (typeidx, subtype) in self.types.iter().enumerate() {
+            // members of a recursion group follow each other
+            let follows = match typeidx.checked_sub(1).map(|prev| self.types[prev].rec) {
+                Some((pos, len)) if pos + 1 < len => subtype.rec == (pos + 1, len),
+                _ => subtype.rec.0 == 0,
+            };
+            if !follows { return Err(Error::Invalid); }
+            subtype.validate(&self.types, typeidx as TypeIdx)?;
+        }
+        if self.types.last().is_some_and(|subtype| subtype.rec.0 + 1 != subtype.rec.1) {
+            return Err(Error::Invalid);
+        }
 
         for func in &self.funcs {
             func.validate(&context)?;
         }
 
         for table in &self.tables {
-            table.validate(&context)?;
+            table.validate_init(&context_g)?;
         }
 
         for mem in &self.mems {
@@ -185,11 +263,11 @@ impl Module {
         }
 
         for el in &self.elem {
-            el.validate(&context)?;
+            el.validate(&context_g)?;
         }
 
         for dt in &self.data {
-            dt.validate(&context)?;
+            dt.validate(&context_g)?;
         }
 
         if let Some(start) = &self.start {
@@ -209,12 +287,8 @@ impl Module {
             ets.push(externtype);
         }
 
-        if let Some(tables) = context.tables {
-            if tables.len() > 1 { return Err(Error::Invalid); }
-        }
-
-        if let Some(mems) = context.mems {
-            if mems.len() > 1 { return Err(Error::Invalid); }
+        if let Some(datacount) = self.datacount {
+            if datacount as usize != self.data.len() { return Err(Error::Invalid); }
         }
 
         let names = &self.exports.iter()
@@ -227,32 +301,49 @@ impl Module {
 
         Ok((its, ets))
     }
-}
 
-use crate::instr::{
-    // vt, 
-    vt_rev,
-};
+    // functions referenced by globals, element segments and exports
+    fn func_refs(&self) -> Vec<FuncIdx> {
+        let exprs = self.globals.iter().map(|global| &global.init)
+            .chain(self.tables.iter().filter_map(|table| table.1.as_ref()))
+            .chain(self.elem.iter().flat_map(|elem| elem.init.iter()));
+        let mut refs: Vec<FuncIdx> = exprs
+            .flat_map(|expr| expr.0.iter())
+            .filter_map(|instr| match instr {
+                Instr::RefFunc(funcidx) => Some(*funcidx),
+                _ => None,
+            })
+            .collect();
+        refs.extend(self.exports.iter().filter_map(|export| match export.desc {
+            ExportDesc::Func(funcidx) => Some(funcidx),
+            _ => None,
+        }));
+        refs
+    }
+}
 
 impl Func {
     fn validate(&self, context: &Context) -> Result<FuncType, Error> {
         let functype = context.tp(self.tp.clone())
             .ok_or(Error::OutOfIndex(format!("func validate: self.tp")))?;
         let mut new_context = context.clone();
-        let mut new_locals = functype.0.clone();
-        
-        if let Some(locals) = &context.locals {
-            new_locals.extend(locals)
+        for local in &self.locals {
+            if let Some(RefType { heap: HeapType::Type(typeidx), .. }) = local.reftype() {
+                context.subtype(typeidx).ok_or(Error::OutOfIndex("func validate: local typeidx".to_string()))?;
+            }
         }
+        // params are set by the caller
+        new_context.unset = (functype.0.len()..).zip(&self.locals)
+            .filter(|(_, local)| !local.is_defaultable())
+            .map(|(idx, _)| idx as LocalIdx)
+            .collect();
+        let mut new_locals = functype.0.clone();
+        new_locals.extend(&self.locals);
         new_context.locals = Some(new_locals);
         new_context.labels = Some(vec![functype.1.clone()]);
         new_context.rtn = Some(functype.1.clone());
 
-        let expr_type = self.body.validate(context)?;
-        let vts: Vec<ValType> = expr_type.0.iter().map(|v| vt_rev(v)).collect();
-        if vts != functype.1 {
-            return Err(Error::Invalid);
-        }
+        self.body.validate(&new_context, &functype.1)?;
 
         Ok(functype)
     }
@@ -263,6 +354,15 @@ impl Table {
         self.0.validate(context)?;
         Ok(self.0.clone())
     }
+
+    // tables of non-nullable references can't be filled with null
+    fn validate_init(&self, context: &Context) -> Result<(), Error> {
+        match &self.1 {
+            Some(init) => init.validate_constant(context, &vec![self.0.1.valtype()]),
+            None if self.0.1.nullable => Ok(()),
+            None => Err(Error::Invalid),
+        }
+    }
 }
 
 impl Mem {
@@ -272,37 +372,34 @@ impl Mem {
     }
 }
 
+impl Tag {
+    fn validate(&self, context: &Context) -> Result<FuncType, Error> {
+        let functype = context.tp(self.0)
+            .ok_or(Error::OutOfIndex("tag validate: typeidx".to_string()))?;
+        ExternType::Tag(functype.clone()).validate(context)?;
+        Ok(functype)
+    }
+}
+
 impl Global {
     fn validate(&self, context: &Context) -> Result<GlobalType, Error> {
-        let rt = self.init.validate(context)?;
-        let vts: Vec<ValType> = rt.0.iter().map(|v| vt_rev(v)).collect();
-        if vts != vec![self.tp.0] { return Err(Error::Invalid); }
-        if !self.init.is_constant() { return Err(Error::Invalid); } 
+        self.init.validate_constant(context, &vec![self.tp.0])?;
         Ok(self.tp.clone())
     }
 }
 
 impl Elem {
     fn validate(&self, context: &Context) -> Result<(), Error> {
-        if self.table != 0 { return Err(Error::Invalid); } 
-        let TableType(_limits, elemtype) = context.table().ok_or(Error::Invalid)?;
+        if let ElemMode::Active { table, offset } = &self.mode {
+            let TableType(_limits, reftype) = context.table(*table).ok_or(Error::Invalid)?;
 
-        if elemtype != ElemType::FuncRef { return Err(Error::Invalid); }
+            if !self.tp.matches(&reftype, context.types()) { return Err(Error::Invalid); }
 
-        let resulttype = self.offset.validate(context)?;
-        let vts: Vec<ValType> = resulttype.0.iter().map(|v| vt_rev(v)).collect();
-        if vts != vec![ValType::I32] {
-            return Err(Error::Invalid);
-        }
-
-        if !self.offset.is_constant() {
-            return Err(Error::Invalid);
+            offset.validate_constant(context, &vec![ValType::I32])?;
         }
 
-        for y in &self.init {
-            if context.func(y.clone()).is_none() {
-                return Err(Error::Invalid);
-            }
+        for expr in &self.init {
+            expr.validate_constant(context, &vec![self.tp.valtype()])?;
         }
 
         Ok(())
@@ -311,16 +408,11 @@ impl Elem {
 
 impl Data {
     fn validate(&self, context: &Context) -> Result<(), Error> {
-        if self.data != 0 { return Err(Error::Invalid); }
+        if let DataMode::Active { memory, offset } = &self.mode {
+            let memtype = context.mem(*memory).ok_or(Error::Invalid)?;
 
-        let resulttype = self.offset.validate(context)?;
-        let vts: Vec<ValType> = resulttype.0.iter().map(|v| vt_rev(v)).collect();
-        if vts != vec![ValType::I32] {
-            return Err(Error::Invalid);
-        }
-
-        if !self.offset.is_constant() {
-            return Err(Error::Invalid);
+            // an address of the memory
+            offset.validate_constant(context, &vec![memtype.2.valtype()])?;
         }
 
         Ok(())
@@ -346,19 +438,21 @@ impl ExportDesc {
                 Ok(ExternType::Func(functype.clone()))
             },
             ExportDesc::Table(x) => {
-                if x != &0 { return Err(Error::Invalid); }
-                let tabletype = context.table().unwrap();
+                let tabletype = context.table(*x).ok_or(Error::Invalid)?;
                 Ok(ExternType::Table(tabletype.clone()))
             },
             ExportDesc::Mem(x) => {
-                if x != &0 { return Err(Error::Invalid); }
-                let memtype = context.mem().unwrap();
+                let memtype = context.mem(*x).ok_or(Error::Invalid)?;
                 Ok(ExternType::Mem(memtype.clone()))
             },
             ExportDesc::Global(x) => {
                 let globaltype = context.global(x.clone()).ok_or(Error::Invalid)?;
                 Ok(ExternType::Global(globaltype.clone()))
             },
+            ExportDesc::Tag(x) => {
+                let functype = context.tag(*x).ok_or(Error::Invalid)?;
+                Ok(ExternType::Tag(functype))
+            },
         }
     }
 }
@@ -375,11 +469,19 @@ impl ImportDesc {
                 Ok(ExternType::Table(tabletype.clone()))
             },
             ImportDesc::Mem(memtype) => {
+                memtype.validate(context)?;
                 Ok(ExternType::Mem(memtype.clone()))
             },
             ImportDesc::Global(globaltype) => {
                 Ok(ExternType::Global(globaltype.clone()))
             },
+            ImportDesc::Tag(x) => {
+                let tp = context.tp(*x)
+                    .ok_or(Error::OutOfIndex("importdesc validate: typeidx".to_string()))?;
+                let externtype = ExternType::Tag(tp);
+                externtype.validate(context)?;
+                Ok(externtype)
+            },
         }
     }
 }
\ No newline at end of file
@@ -7,6 +7,7 @@ mod type_parser;
 mod import_parser;
 mod table_parser;
 mod memory_parser;
+mod tag_parser;
 mod global_parser;
 mod func_parser;
 mod export_parser;
@@ -32,6 +33,8 @@ use crate::{
     Name,
     Byte,
     ValType,
+    RefType,
+    HeapType,
     Limits,
 };
 
@@ -75,10 +78,11 @@ impl<R> Parser<R> where R: Read + Seek {
             self.consume()?;
         }
 
-        parse_field!(self, Type, self.parse_type()?);
+        parse_field!(self, Type | Rec, self.parse_rectype()?);
         parse_field!(self, Import, self.parse_import()?);
         parse_field!(self, Table, self.parse_table()?);
         parse_field!(self, Memory, self.parse_memory()?);
+        parse_field!(self, Tag, self.parse_tag()?);
         parse_field!(self, Global, self.parse_global()?);
         parse_field!(self, Func, self.parse_func()?);
         parse_field!(self, Export, self.parse_export()?);
@@ -96,6 +100,11 @@ impl<R> Parser<R> where R: Read + Seek {
 
         self.match_rparen()?;
 
+        // the text format has no data count section
+        if self.module.funcs.iter().any(|func| func.body.uses_data_indices()) {
+            self.module.datacount = Some(self.module.data.len() as u32);
+        }
+
         Ok(())
     }
 
@@ -154,8 +163,11 @@ impl<R> Parser<R> where R: Read + Seek {
     }
 
     fn parse_data_string(&mut self) -> Result<Vec<Byte>, ParseError> {
-        // self.parse_string();
-        unimplemented!();
+        let mut bytes = vec![];
+        while let tk!(TokenKind::String(_)) = &self.lookahead {
+            bytes.extend(self.parse_string()?.into_bytes());
+        }
+        Ok(bytes)
     }
 
     fn parse_string(&mut self) -> Result<String, ParseError> {
@@ -169,15 +181,65 @@ impl<R> Parser<R> where R: Read + Seek {
     }
 
     fn parse_valtype(&mut self) -> Result<ValType, ParseError> {
-        if let kw!(Keyword::ValType(vt)) = &self.lookahead {
-            let res = vt.clone();
-            self.consume()?;
-            Ok(res)
-        } else {
-            Err(self.err())
+        match &self.lookahead {
+            kw!(Keyword::ValType(vt)) => {
+                let res = vt.clone();
+                self.consume()?;
+                Ok(res)
+            },
+            _ => Ok(self.parse_reftype()?.valtype()),
         }
     }
 
+    fn parse_heaptype(&mut self) -> Result<HeapType, ParseError> {
+        let heap = match &self.lookahead {
+            kw!(Keyword::Func) => HeapType::Func,
+            kw!(Keyword::Extern) => HeapType::Extern,
+            kw!(Keyword::Exn) => HeapType::Exn,
+            kw!(Keyword::Any) => HeapType::Any,
+            kw!(Keyword::Eq) => HeapType::Eq,
+            kw!(Keyword::I31) => HeapType::I31,
+            kw!(Keyword::Struct) => HeapType::Struct,
+            kw!(Keyword::Array) => HeapType::Array,
+            kw!(Keyword::None) => HeapType::None,
+            kw!(Keyword::NoFunc) => HeapType::NoFunc,
+            kw!(Keyword::NoExtern) => HeapType::NoExtern,
+            kw!(Keyword::NoExn) => HeapType::NoExn,
+            tk!(TokenKind::Id(_)) | nm!(Number::Integer(_)) => {
+                let typeidx = self.resolve_id(&self.contexts[0].types.clone())?;
+                return Ok(HeapType::Type(typeidx));
+            },
+            _ => return Err(self.err()),
+        };
+        self.consume()?;
+        Ok(heap)
+    }
+
+    fn parse_reftype(&mut self) -> Result<RefType, ParseError> {
+        let heap = match &self.lookahead {
+            kw!(Keyword::FuncRef) => HeapType::Func,
+            kw!(Keyword::ExternRef) => HeapType::Extern,
+            kw!(Keyword::ExnRef) => HeapType::Exn,
+            tk!(TokenKind::LeftParen) => {
+                // '(' 'ref' 'null'? heaptype ')'
+                self.match_lparen()?;
+                self.match_keyword(Keyword::Ref)?;
+                let nullable = if let kw!(Keyword::Null) = &self.lookahead {
+                    self.consume()?;
+                    true
+                } else {
+                    false
+                };
+                let heap = self.parse_heaptype()?;
+                self.match_rparen()?;
+                return Ok(RefType { nullable, heap });
+            },
+            _ => return Err(self.err()),
+        };
+        self.consume()?;
+        Ok(RefType { nullable: true, heap })
+    }
+
     fn parse_num<T: TryFrom<usize>>(&mut self) -> Result<T, ParseError> {
         if let nm!(Number::Integer(n)) = &self.lookahead {
             if let Ok(num) = T::try_from(n.clone()) {
@@ -195,16 +257,27 @@ impl<R> Parser<R> where R: Read + Seek {
         let mut limits = Limits::default();
 
         // min
-        limits.min = self.parse_num::<u32>()?;
+        limits.min = self.parse_num::<u64>()?;
 
         // max(optional)
         if let nm!(Number::Integer(_)) = &self.lookahead {
-            limits.max = Some(self.parse_num::<u32>()?);
+            limits.max = Some(self.parse_num::<u64>()?);
         }
 
         Ok(limits)
     }
 
+    // '(' keyword ... ')' when the next field is `keyword`
+    fn is_field(&mut self, keyword: Keyword) -> Result<bool, ParseError> {
+        if !self.is_lparen()? {
+            return Ok(false);
+        }
+        match self.peek()? {
+            kw!(kw) => Ok(kw == keyword),
+            _ => Ok(false),
+        }
+    }
+
     fn parse_offset(&mut self) -> Result<Expr, ParseError> {
         self.match_keyword(Keyword::Offset)?;
 
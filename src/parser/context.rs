@@ -9,9 +9,13 @@ pub struct Context {
     pub funcs: Vec<Option<Id>>,
     pub tables: Vec<Option<Id>>,
     pub mems: Vec<Option<Id>>,
+    pub tags: Vec<Option<Id>>,
     pub globals: Vec<Option<Id>>,
+    pub elems: Vec<Option<Id>>,
+    pub datas: Vec<Option<Id>>,
     pub locals: Vec<Option<Id>>,
     pub labels: Vec<Option<Id>>,
+    pub fields: Vec<Vec<Option<Id>>>, // field ids of each type
     pub typedefs: Vec<FuncType>, // typedefs functype*
 }
 
@@ -38,12 +42,15 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
    }
    if self.tables.len() > 0 { writeln!(f, "  tables: {:?}", self.tables)?; }
    if self.mems.len() > 0 { writeln!(f, "  mems: {:?}", self.mems)?; }
+   if self.tags.len() > 0 { writeln!(f, "  tags: {:?}", self.tags)?; }
    if self.globals.len() > 0 {
       writeln!(f, "  globals:")?;
       for global in &self.globals {
          writeln!(f, "    {:?}", global)?;
       }
    }
+   if self.elems.len() > 0 { writeln!(f, "  elems: {:?}", self.elems)?; }
+   if self.datas.len() > 0 { writeln!(f, "  datas: {:?}", self.datas)?; }
    if self.locals.len() > 0 { writeln!(f, "  locals: {:?}", self.locals)?; }
    if self.labels.len() > 0 { writeln!(f, "  labels: {:?}", self.labels)?; }
    if self.typedefs.len() > 0 {
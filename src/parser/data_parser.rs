@@ -1,5 +1,6 @@
 use crate::{
     Data,
+    DataMode,
 };
 
 use super::*;
@@ -8,20 +9,42 @@ impl<R> Parser<R> where R: Read + Seek {
     pub(super) fn parse_data(&mut self) -> Result<(), ParseError> {        
         self.match_keyword(Keyword::Data)?;
 
-        // mem id
-        let memidx = self.resolve_id(&self.contexts[0].mems.clone())?;
-
-        // offset
-        self.match_lparen()?;
-        let offset = self.parse_offset()?;
+        // data id
+        parse_optional_id!(self, self.contexts[0].datas);
+
+        // mem index
+        let mut memidx = None;
+        if let nm!(Number::Integer(_)) = &self.lookahead {
+            memidx = Some(self.parse_num::<u32>()?);
+        }
+        if self.is_field(Keyword::Memory)? {
+            self.match_lparen()?;
+            self.match_keyword(Keyword::Memory)?;
+            memidx = Some(self.resolve_id(&self.contexts[0].mems.clone())?);
+            self.match_rparen()?;
+        }
+
+        // offset(absent in passive segments)
+        let mut offset = None;
+        if self.is_field(Keyword::Offset)? {
+            self.match_lparen()?;
+            offset = Some(self.parse_offset()?);
+        }
 
         // data string
         let datastring = self.parse_data_string()?;
 
+        self.match_rparen()?;
+
+        let mode = match (memidx, offset) {
+            (memidx, Some(offset)) => DataMode::Active { memory: memidx.unwrap_or(0), offset },
+            (None, None) => DataMode::Passive,
+            (Some(_), None) => return Err(self.err2("data: offset")),
+        };
+
         let data = Data {
-            data: memidx, 
-            offset: offset, 
-            init: datastring
+            init: datastring,
+            mode,
         };
 
         self.module.data.push(data);
@@ -30,4 +53,3 @@ impl<R> Parser<R> where R: Read + Seek {
     }
 
 }
-
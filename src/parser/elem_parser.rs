@@ -1,5 +1,8 @@
 use crate::{
     Elem,
+    ElemMode,
+    RefType,
+    HeapType,
 };
 
 use super::*;
@@ -8,35 +11,93 @@ impl<R> Parser<R> where R: Read + Seek {
     pub(super) fn parse_elem(&mut self) -> Result<(), ParseError> {        
         self.match_keyword(Keyword::Elem)?;
 
-        // table id
-        let tableidx = self.resolve_id(&self.contexts[0].tables.clone())?;
+        // elem id
+        parse_optional_id!(self, self.contexts[0].elems);
 
-        // offset
-        self.match_lparen()?;
-        let offset = self.parse_offset()?;
-
-        // func indices
-        let mut func_indices = vec![];
+        // table index
+        let mut tableidx = None;
+        if let nm!(Number::Integer(_)) = &self.lookahead {
+            tableidx = Some(self.parse_num::<u32>()?);
+        }
+        if self.is_field(Keyword::Table)? {
+            self.match_lparen()?;
+            self.match_keyword(Keyword::Table)?;
+            tableidx = Some(self.resolve_id(&self.contexts[0].tables.clone())?);
+            self.match_rparen()?;
+        }
 
-        loop {
-            if let tk!(TokenKind::RightParen) = self.lookahead {
-                self.match_rparen()?;
-                break;
-            }
+        // 'declare'
+        let mut declare = false;
+        if let kw!(Keyword::Declare) = self.lookahead {
+            self.consume()?;
+            declare = true;
+        }
 
-            let funcidx = self.resolve_id(&self.contexts[0].funcs.clone())?;
-            func_indices.push(funcidx);            
+        // offset(absent in passive and declarative segments)
+        let mut offset = None;
+        if self.is_field(Keyword::Offset)? {
+            self.match_lparen()?;
+            offset = Some(self.parse_offset()?);
         }
 
+        // elemlist
+        let (tp, init) = match &self.lookahead {
+            kw!(Keyword::FuncRef) | kw!(Keyword::ExternRef) => {
+                let tp = self.parse_reftype()?;
+                (tp, self.parse_elem_exprs()?)
+            },
+            _ => {
+                // 'func'
+                if let kw!(Keyword::Func) = self.lookahead {
+                    self.consume()?;
+                }
+                (RefType { nullable: true, heap: HeapType::Func }, self.parse_elem_funcs()?)
+            },
+        };
+        self.match_rparen()?;
+
+        let mode = match (tableidx, offset) {
+            (_, Some(_)) if declare => return Err(self.err2("elem: declare")),
+            (tableidx, Some(offset)) => ElemMode::Active { table: tableidx.unwrap_or(0), offset },
+            (None, None) if declare => ElemMode::Declarative,
+            (None, None) => ElemMode::Passive,
+            (Some(_), None) => return Err(self.err2("elem: offset")),
+        };
+
         let elem = Elem {
-            table: tableidx, 
-            offset: offset, 
-            init: func_indices
+            tp,
+            init,
+            mode,
         };
 
         self.module.elem.push(elem);
 
         Ok(())
     }
-}
 
+    // func indices, each as a `ref.func` expression
+    fn parse_elem_funcs(&mut self) -> Result<Vec<Expr>, ParseError> {
+        let mut init = vec![];
+        while !self.is_rparen()? {
+            let funcidx = self.resolve_id(&self.contexts[0].funcs.clone())?;
+            init.push(Expr(vec![Instr::RefFunc(funcidx)]));
+        }
+        Ok(init)
+    }
+
+    // '(' 'item' expr ')' or the abbreviated '(' instr ')'
+    fn parse_elem_exprs(&mut self) -> Result<Vec<Expr>, ParseError> {
+        let mut init = vec![];
+        while self.is_lparen()? {
+            self.match_lparen()?;
+            if let kw!(Keyword::Item) = self.lookahead {
+                self.consume()?;
+                init.push(self.parse_expr()?);
+            } else {
+                init.push(Expr(self.parse_instrs()?));
+            }
+            self.match_rparen()?;
+        }
+        Ok(init)
+    }
+}
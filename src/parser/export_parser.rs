@@ -31,6 +31,7 @@ impl<R> Parser<R> where R: Read + Seek {
             kw!(Keyword::Table) => self.parse_export_desc_table(),
             kw!(Keyword::Memory) => self.parse_export_desc_memory(),
             kw!(Keyword::Global) => self.parse_export_desc_global(),
+            kw!(Keyword::Tag) => self.parse_export_desc_tag(),
             _ => Err(self.err())
         }
     }
@@ -78,4 +79,15 @@ impl<R> Parser<R> where R: Read + Seek {
         
         Ok(ExportDesc::Global(globalidx))
     }
+
+    fn parse_export_desc_tag(&mut self) -> Result<ExportDesc, ParseError> {
+        self.match_keyword(Keyword::Tag)?;
+
+        // tag id
+        let tagidx = self.resolve_id(&self.contexts[0].tags.clone())?;
+
+        self.match_rparen()?;
+
+        Ok(ExportDesc::Tag(tagidx))
+    }
 }
@@ -1,5 +1,9 @@
 use crate::{
     FuncType,
+    TypeIdx,
+    FieldIdx,
+    TableIdx,
+    MemIdx,
 };
 
 use crate::instr::*;
@@ -31,6 +35,26 @@ macro_rules! instr_func {
     }};
 }
 
+macro_rules! instr_elem {
+    ($this:ident, $v:ident, $instr:ident) => {{
+        instr_id!($this, $v, $instr, $this.contexts[0].elems);
+    }};
+}
+
+macro_rules! instr_data {
+    ($this:ident, $v:ident, $instr:ident) => {{
+        instr_id!($this, $v, $instr, $this.contexts[0].datas);
+    }};
+}
+
+macro_rules! instr_table {
+    ($this:ident, $v:ident, $instr:ident) => {{
+        $this.consume()?;
+        let tableidx = $this.parse_optional_tableidx()?;
+        $v.push(Instr::$instr(tableidx));
+    }};
+}
+
 macro_rules! instr_label {
     ($this:ident, $v:ident, $instr:ident) => {{
         instr_id!($this, $v, $instr, $this.contexts.last().unwrap().labels);
@@ -50,11 +74,11 @@ macro_rules! instr_const {
     }};
 }
 
-macro_rules! instr_memarg {
-    ($this: ident, $v:ident, $align:expr) => {{
+macro_rules! instr_mem {
+    ($this:ident, $v:ident, $instr:ident) => {{
         $this.consume()?;
-        let memarg = MemArg { align: $align, offset: 0 };
-        $v.push(Instr::Load(ValType::I32, memarg));
+        let memidx = $this.parse_optional_memidx()?;
+        $v.push(Instr::$instr(memidx));
     }};
 }
 
@@ -67,9 +91,7 @@ macro_rules! instr_one_block {
         parse_optional_label_id!($this, new_label_context.labels);
         $this.contexts.push(new_label_context);
 
-        $this.match_lparen()?;
-
-        // resulttype
+        // blocktype
         let vt = $this.parse_blocktype()?;
 
         // instrs
@@ -83,7 +105,7 @@ macro_rules! instr_one_block {
         p!($this.contexts.last());
         $this.contexts.pop();
 
-        $v.push(Instr::$instr(vt, instrs));
+        $v.push(Instr::$instr(vt, instrs.into()));
     }};
 }
 
@@ -102,11 +124,47 @@ impl<R> Parser<R> where R: Read + Seek {
                 instr!(Instr::Block(_, _)) => instr_one_block!(self, instrs, Block),
                 instr!(Instr::Loop(_, _)) => instr_one_block!(self, instrs, Loop),
                 instr!(Instr::If(_, _, _)) => self.parse_if(&mut instrs)?,
+                instr!(Instr::TryTable(_, _, _)) => self.parse_try_table(&mut instrs)?,
                 instr!(Instr::Br(_)) => instr_label!(self, instrs, BrIf),
                 instr!(Instr::BrIf(_)) => instr_label!(self, instrs, BrIf),
                 instr!(Instr::BrTable(_, _)) => self.parse_br_table(&mut instrs)?,
+                instr!(Instr::BrOnNull(_)) => instr_label!(self, instrs, BrOnNull),
+                instr!(Instr::BrOnNonNull(_)) => instr_label!(self, instrs, BrOnNonNull),
                 instr!(Instr::Call(_)) => instr_func!(self, instrs, Call),
-                instr!(Instr::CallIndirect(_)) => self.parse_call_indirect(&mut instrs)?,
+                instr!(Instr::CallIndirect(_, _)) => self.parse_call_indirect(&mut instrs, Instr::CallIndirect)?,
+                instr!(Instr::ReturnCall(_)) => instr_func!(self, instrs, ReturnCall),
+                instr!(Instr::ReturnCallIndirect(_, _)) => self.parse_call_indirect(&mut instrs, Instr::ReturnCallIndirect)?,
+                instr!(Instr::CallRef(_)) => instr_id!(self, instrs, CallRef, self.contexts[0].types),
+                instr!(Instr::ReturnCallRef(_)) => instr_id!(self, instrs, ReturnCallRef, self.contexts[0].types),
+                instr!(Instr::Throw(_)) => instr_id!(self, instrs, Throw, self.contexts[0].tags),
+
+                // Parametric Instructions
+                instr!(Instr::Select(_)) => self.parse_select(&mut instrs)?,
+
+                // Reference Instructions
+                instr!(Instr::RefNull(_)) => {
+                    self.consume()?;
+                    let heaptype = self.parse_heaptype().map_err(|_| self.err2("ref.null"))?;
+                    instrs.push(Instr::RefNull(heaptype));
+                },
+                instr!(Instr::RefFunc(_)) => instr_func!(self, instrs, RefFunc),
+                instr!(instr @ (Instr::RefTest(_) | Instr::RefCast(_) |
+                    Instr::BrOnCast(_, _, _) | Instr::BrOnCastFail(_, _, _))) => {
+                    let instr = instr.clone();
+                    self.consume()?;
+                    instrs.push(self.parse_aggregate_immediates(instr)?);
+                },
+
+                // Aggregate Instructions
+                instr!(instr @ (Instr::StructNew(_) | Instr::StructNewDefault(_) |
+                    Instr::StructGet(_, _, _) | Instr::StructSet(_, _) |
+                    Instr::ArrayNew(_) | Instr::ArrayNewDefault(_) | Instr::ArrayNewFixed(_, _) |
+                    Instr::ArrayGet(_, _) | Instr::ArraySet(_) | Instr::ArrayFill(_) | Instr::ArrayCopy(_, _) |
+                    Instr::ArrayNewData(_, _) | Instr::ArrayNewElem(_, _) | Instr::ArrayInitData(_, _) | Instr::ArrayInitElem(_, _))) => {
+                    let instr = instr.clone();
+                    self.consume()?;
+                    instrs.push(self.parse_aggregate_immediates(instr)?);
+                },
 
                 // Variable Instructions
                 instr!(Instr::LocalGet(_)) => instr_local!(self, instrs, LocalGet),
@@ -116,23 +174,59 @@ impl<R> Parser<R> where R: Read + Seek {
                 instr!(Instr::GlobalSet(_)) => instr_global!(self, instrs, GlobalSet),
 
                 // Memory Instructions
-                instr!(Instr::ILoad8(_, _, _)) => instr_memarg!(self, instrs, 0),
-                instr!(Instr::IStore8(_, _)) => instr_memarg!(self, instrs, 0),
-
-                instr!(Instr::ILoad16(_, _, _)) => instr_memarg!(self, instrs, 1),
-                instr!(Instr::IStore16(_, _)) => instr_memarg!(self, instrs, 1),
-
-                instr!(Instr::Load(ValType::I32, _)) => instr_memarg!(self, instrs, 2),
-                instr!(Instr::Load(ValType::F32, _)) => instr_memarg!(self, instrs, 2),
-                instr!(Instr::I64Load32(_, _)) => instr_memarg!(self, instrs, 2),
-                instr!(Instr::Store(ValType::I32, _)) => instr_memarg!(self, instrs, 2),
-                instr!(Instr::Store(ValType::F32, _)) => instr_memarg!(self, instrs, 2),
-                instr!(Instr::I64Store32(_)) => instr_memarg!(self, instrs, 2),
+                instr!(instr @ (Instr::Load(ValType::I32 | ValType::I64 | ValType::F32 | ValType::F64, _) |
+                    Instr::Store(ValType::I32 | ValType::I64 | ValType::F32 | ValType::F64, _) |
+                    Instr::ILoad8(_, _, _) | Instr::ILoad16(_, _, _) | Instr::I64Load32(_, _) |
+                    Instr::IStore8(_, _) | Instr::IStore16(_, _) | Instr::I64Store32(_))) => {
+                    let instr = instr.clone();
+                    self.consume()?;
+                    instrs.push(self.parse_memory_immediates(instr)?);
+                },
 
-                instr!(Instr::Load(ValType::I64, _)) => instr_memarg!(self, instrs, 3),
-                instr!(Instr::Load(ValType::F64, _)) => instr_memarg!(self, instrs, 3),
-                instr!(Instr::Store(ValType::I64, _)) => instr_memarg!(self, instrs, 3),
-                instr!(Instr::Store(ValType::F64, _)) => instr_memarg!(self, instrs, 3),
+                instr!(Instr::MemorySize(_)) => instr_mem!(self, instrs, MemorySize),
+                instr!(Instr::MemoryGrow(_)) => instr_mem!(self, instrs, MemoryGrow),
+                instr!(Instr::MemoryFill(_)) => instr_mem!(self, instrs, MemoryFill),
+                instr!(Instr::MemoryCopy(_, _)) => {
+                    self.consume()?;
+                    let x = self.parse_optional_memidx()?;
+                    let y = self.parse_optional_memidx()?;
+                    instrs.push(Instr::MemoryCopy(x, y));
+                },
+                instr!(Instr::MemoryInit(_, _)) => {
+                    self.consume()?;
+                    // a lone index is the data index
+                    let memidx = match self.peek()? {
+                        tk!(TokenKind::Id(_)) | nm!(Number::Integer(_)) => self.parse_optional_memidx()?,
+                        _ => 0,
+                    };
+                    let dataidx = self.resolve_id(&self.contexts[0].datas.clone())?;
+                    instrs.push(Instr::MemoryInit(memidx, dataidx));
+                },
+                instr!(Instr::DataDrop(_)) => instr_data!(self, instrs, DataDrop),
+
+                // Table Instructions
+                instr!(Instr::TableGet(_)) => instr_table!(self, instrs, TableGet),
+                instr!(Instr::TableSet(_)) => instr_table!(self, instrs, TableSet),
+                instr!(Instr::TableSize(_)) => instr_table!(self, instrs, TableSize),
+                instr!(Instr::TableGrow(_)) => instr_table!(self, instrs, TableGrow),
+                instr!(Instr::TableFill(_)) => instr_table!(self, instrs, TableFill),
+                instr!(Instr::TableCopy(_, _)) => {
+                    self.consume()?;
+                    let x = self.parse_optional_tableidx()?;
+                    let y = self.parse_optional_tableidx()?;
+                    instrs.push(Instr::TableCopy(x, y));
+                },
+                instr!(Instr::TableInit(_, _)) => {
+                    self.consume()?;
+                    // a lone index is the elem index
+                    let tableidx = match self.peek()? {
+                        tk!(TokenKind::Id(_)) | nm!(Number::Integer(_)) => self.parse_optional_tableidx()?,
+                        _ => 0,
+                    };
+                    let elemidx = self.resolve_id(&self.contexts[0].elems.clone())?;
+                    instrs.push(Instr::TableInit(tableidx, elemidx));
+                },
+                instr!(Instr::ElemDrop(_)) => instr_elem!(self, instrs, ElemDrop),
 
                 // Numeric Instructions
                 instr!(Instr::I32Const(_)) => instr_const!(self, Number::Integer(n), n, instrs, I32Const, u32, "i32.const"),
@@ -140,6 +234,26 @@ impl<R> Parser<R> where R: Read + Seek {
                 instr!(Instr::F32Const(_)) => instr_const!(self, Number::FloatingPoint(n), n, instrs, F32Const, f32, "f32.const"),
                 instr!(Instr::F64Const(_)) => instr_const!(self, Number::FloatingPoint(n), n, instrs, F64Const, f64, "f64.const"),
 
+                // Vector Instructions
+                instr!(instr @ (Instr::V128Const(_) | Instr::I8x16Shuffle(_) |
+                    Instr::ExtractLane(_, _, _) | Instr::ReplaceLane(_, _) |
+                    Instr::Load(ValType::V128, _) | Instr::Store(ValType::V128, _) |
+                    Instr::V128LoadExtend(_, _, _) | Instr::V128LoadSplat(_, _) | Instr::V128LoadZero(_, _) |
+                    Instr::V128LoadLane(_, _, _) | Instr::V128StoreLane(_, _, _))) => {
+                    let instr = instr.clone();
+                    self.consume()?;
+                    instrs.push(self.parse_vector_immediates(instr)?);
+                },
+
+                // Atomic Memory Instructions
+                instr!(instr @ (Instr::AtomicNotify(_) | Instr::AtomicWait(_, _) |
+                    Instr::AtomicLoad(_, _, _) | Instr::AtomicStore(_, _, _) |
+                    Instr::AtomicRmw(_, _, _, _) | Instr::AtomicCmpxchg(_, _, _))) => {
+                    let instr = instr.clone();
+                    self.consume()?;
+                    instrs.push(self.parse_atomic_immediates(instr)?);
+                },
+
                 instr!(instr) => {
                     instrs.push(instr.clone());
                     self.consume()?;
@@ -156,12 +270,289 @@ impl<R> Parser<R> where R: Read + Seek {
     }
 
     fn parse_blocktype(&mut self) -> Result<BlockType, ParseError> {
-        unimplemented!();
+        let mut ft = FuncType::default();
+        let mut typeidx = None;
+
+        // add local context(for check)
+        self.contexts.push(Context::default());
+
+        if self.is_lparen()? {
+            match self.peek()? {
+                kw!(Keyword::Type) => {
+                    typeidx = Some(self.parse_typeuse(&mut ft.0, &mut ft.1)?);
+                },
+                kw!(Keyword::Param) | kw!(Keyword::Result) => {
+                    self.parse_signature(&mut ft.0, &mut ft.1)?;
+                },
+                _ => {},
+            }
+        }
+
+        // check params context (must not include string id)
+        if self.contexts.last().unwrap().locals.iter().any(|x| x.is_some()) {
+            return Err(self.err2("blocktype: params context (must be empty)"));
+        }
+        self.contexts.pop();
+
+        match typeidx {
+            Some(typeidx) => {
+                self.check_typeuse(typeidx, ft)?;
+                Ok(BlockType::TypeIdx(typeidx))
+            },
+            None if ft.0.is_empty() && ft.1.len() <= 1 => {
+                Ok(BlockType::ValType(ft.1.first().cloned()))
+            },
+            None => Ok(BlockType::TypeIdx(self.inline_typeidx(ft))),
+        }
+    }
+
+    // multi-value blocktype without typeuse refers to an equal typedef,
+    // or to a new one appended to the module
+    fn inline_typeidx(&mut self, ft: FuncType) -> TypeIdx {
+        let typedefs = &mut self.contexts[0].typedefs;
+        if let Some(idx) = typedefs.iter().position(|typedef| typedef == &ft) {
+            return idx as TypeIdx;
+        }
+        typedefs.push(ft.clone());
+        self.contexts[0].types.push(None);
+        self.contexts[0].fields.push(vec![]);
+        self.module.types.push(ft.into());
+        (self.module.types.len() - 1) as TypeIdx
     }
 
-    fn parse_call_indirect(&mut self, instrs: &mut Vec<Instr>) -> Result<(), ParseError> {
+    // table index, 0 when omitted
+    fn parse_optional_tableidx(&mut self) -> Result<TableIdx, ParseError> {
+        match &self.lookahead {
+            tk!(TokenKind::Id(_)) | nm!(Number::Integer(_)) => self.resolve_id(&self.contexts[0].tables.clone()),
+            _ => Ok(0),
+        }
+    }
+
+    fn parse_optional_memidx(&mut self) -> Result<MemIdx, ParseError> {
+        match &self.lookahead {
+            tk!(TokenKind::Id(_)) | nm!(Number::Integer(_)) => self.resolve_id(&self.contexts[0].mems.clone()),
+            _ => Ok(0),
+        }
+    }
+
+    fn parse_select(&mut self, instrs: &mut Vec<Instr>) -> Result<(), ParseError> {
         self.consume()?;
 
+        // result type(optional)
+        if self.is_field(Keyword::Result)? {
+            self.match_lparen()?;
+            let vt = self.parse_result()?;
+            instrs.push(Instr::Select(Some(vt)));
+        } else {
+            instrs.push(Instr::Select(None));
+        }
+
+        Ok(())
+    }
+
+    fn parse_vector_immediates(&mut self, instr: Instr) -> Result<Instr, ParseError> {
+        let instr = match instr {
+            Instr::V128Const(_) => Instr::V128Const(self.parse_v128()?),
+            Instr::I8x16Shuffle(_) => {
+                let mut laneidx = [0; 16];
+                for l in laneidx.iter_mut() {
+                    *l = self.parse_laneidx()?;
+                }
+                Instr::I8x16Shuffle(laneidx)
+            },
+            Instr::ExtractLane(shape, sign, _) => Instr::ExtractLane(shape, sign, self.parse_laneidx()?),
+            Instr::ReplaceLane(shape, _) => Instr::ReplaceLane(shape, self.parse_laneidx()?),
+            Instr::Load(vt, _) => Instr::Load(vt, self.parse_memarg(128)?),
+            Instr::Store(vt, _) => Instr::Store(vt, self.parse_memarg(128)?),
+            Instr::V128LoadExtend(shape, sign, _) => Instr::V128LoadExtend(shape, sign, self.parse_memarg(64)?),
+            Instr::V128LoadSplat(shape, _) => Instr::V128LoadSplat(shape, self.parse_memarg(shape.width())?),
+            Instr::V128LoadZero(vs, _) => {
+                let width = if vs == ValSize::V32 { 32 } else { 64 };
+                Instr::V128LoadZero(vs, self.parse_memarg(width)?)
+            },
+            Instr::V128LoadLane(shape, _, _) => {
+                let memarg = self.parse_lane_memarg(shape.width())?;
+                Instr::V128LoadLane(shape, memarg, self.parse_laneidx()?)
+            },
+            Instr::V128StoreLane(shape, _, _) => {
+                let memarg = self.parse_lane_memarg(shape.width())?;
+                Instr::V128StoreLane(shape, memarg, self.parse_laneidx()?)
+            },
+            instr => instr,
+        };
+        Ok(instr)
+    }
+
+    fn parse_atomic_immediates(&mut self, instr: Instr) -> Result<Instr, ParseError> {
+        let instr = match instr {
+            Instr::AtomicNotify(_) => Instr::AtomicNotify(self.parse_memarg(32)?),
+            Instr::AtomicWait(vs, _) => {
+                let width = atomic_width(&vs, &None);
+                Instr::AtomicWait(vs, self.parse_memarg(width)?)
+            },
+            Instr::AtomicLoad(vs, ps, _) => Instr::AtomicLoad(vs.clone(), ps, self.parse_memarg(atomic_width(&vs, &ps))?),
+            Instr::AtomicStore(vs, ps, _) => Instr::AtomicStore(vs.clone(), ps, self.parse_memarg(atomic_width(&vs, &ps))?),
+            Instr::AtomicRmw(vs, ps, op, _) => Instr::AtomicRmw(vs.clone(), ps, op, self.parse_memarg(atomic_width(&vs, &ps))?),
+            Instr::AtomicCmpxchg(vs, ps, _) => Instr::AtomicCmpxchg(vs.clone(), ps, self.parse_memarg(atomic_width(&vs, &ps))?),
+            instr => instr,
+        };
+        Ok(instr)
+    }
+
+    fn parse_aggregate_immediates(&mut self, instr: Instr) -> Result<Instr, ParseError> {
+        let instr = match instr {
+            Instr::RefTest(_) => Instr::RefTest(self.parse_cast_reftype()?),
+            Instr::RefCast(_) => Instr::RefCast(self.parse_cast_reftype()?),
+            Instr::BrOnCast(_, _, _) => {
+                let labelidx = self.resolve_id(&self.contexts.last().unwrap().labels.clone())?;
+                Instr::BrOnCast(labelidx, self.parse_cast_reftype()?, self.parse_cast_reftype()?)
+            },
+            Instr::BrOnCastFail(_, _, _) => {
+                let labelidx = self.resolve_id(&self.contexts.last().unwrap().labels.clone())?;
+                Instr::BrOnCastFail(labelidx, self.parse_cast_reftype()?, self.parse_cast_reftype()?)
+            },
+            Instr::StructNew(_) => Instr::StructNew(self.parse_typeidx()?),
+            Instr::StructNewDefault(_) => Instr::StructNewDefault(self.parse_typeidx()?),
+            Instr::StructGet(sign, _, _) => {
+                let typeidx = self.parse_typeidx()?;
+                Instr::StructGet(sign, typeidx, self.parse_fieldidx(typeidx)?)
+            },
+            Instr::StructSet(_, _) => {
+                let typeidx = self.parse_typeidx()?;
+                Instr::StructSet(typeidx, self.parse_fieldidx(typeidx)?)
+            },
+            Instr::ArrayNew(_) => Instr::ArrayNew(self.parse_typeidx()?),
+            Instr::ArrayNewDefault(_) => Instr::ArrayNewDefault(self.parse_typeidx()?),
+            Instr::ArrayNewFixed(_, _) => Instr::ArrayNewFixed(self.parse_typeidx()?, self.parse_num::<u32>()?),
+            Instr::ArrayGet(sign, _) => Instr::ArrayGet(sign, self.parse_typeidx()?),
+            Instr::ArraySet(_) => Instr::ArraySet(self.parse_typeidx()?),
+            Instr::ArrayFill(_) => Instr::ArrayFill(self.parse_typeidx()?),
+            Instr::ArrayCopy(_, _) => Instr::ArrayCopy(self.parse_typeidx()?, self.parse_typeidx()?),
+            Instr::ArrayNewData(_, _) => Instr::ArrayNewData(self.parse_typeidx()?, self.resolve_id(&self.contexts[0].datas.clone())?),
+            Instr::ArrayNewElem(_, _) => Instr::ArrayNewElem(self.parse_typeidx()?, self.resolve_id(&self.contexts[0].elems.clone())?),
+            Instr::ArrayInitData(_, _) => Instr::ArrayInitData(self.parse_typeidx()?, self.resolve_id(&self.contexts[0].datas.clone())?),
+            Instr::ArrayInitElem(_, _) => Instr::ArrayInitElem(self.parse_typeidx()?, self.resolve_id(&self.contexts[0].elems.clone())?),
+            instr => instr,
+        };
+        Ok(instr)
+    }
+
+    fn parse_typeidx(&mut self) -> Result<TypeIdx, ParseError> {
+        self.resolve_id(&self.contexts[0].types.clone())
+    }
+
+    // field ids are scoped by their struct type
+    fn parse_fieldidx(&mut self, typeidx: TypeIdx) -> Result<FieldIdx, ParseError> {
+        let fields = self.contexts[0].fields.get(typeidx as usize).cloned().unwrap_or_default();
+        self.resolve_id(&fields)
+    }
+
+    // reference type of a cast, a shorthand or '(' 'ref' 'null'? heaptype ')'
+    fn parse_cast_reftype(&mut self) -> Result<RefType, ParseError> {
+        match self.parse_valtype()?.reftype() {
+            Some(reftype) => Ok(reftype),
+            None => Err(self.err2("reftype")),
+        }
+    }
+
+    // shape followed by one number per lane
+    fn parse_v128(&mut self) -> Result<u128, ParseError> {
+        let shape = match &self.lookahead {
+            kw!(Keyword::Shape(shape)) => *shape,
+            _ => return Err(self.err2("v128.const: shape")),
+        };
+        self.consume()?;
+
+        let width = shape.width() as usize;
+        let mut v128 = 0;
+        for i in 0..shape.lanes() {
+            let bits = match (&self.lookahead, shape) {
+                (nm!(Number::Integer(n)), Shape::F32x4) => (*n as isize as f32).to_bits() as u128,
+                (nm!(Number::Integer(n)), Shape::F64x2) => (*n as isize as f64).to_bits() as u128,
+                (nm!(Number::Integer(n)), _) => *n as u128,
+                (nm!(Number::FloatingPoint(n)), Shape::F32x4) => (*n as f32).to_bits() as u128,
+                (nm!(Number::FloatingPoint(n)), Shape::F64x2) => n.to_bits() as u128,
+                _ => return Err(self.err2("v128.const: lane")),
+            };
+            v128 |= (bits & (u128::MAX >> (128 - width))) << (i * width);
+            self.consume()?;
+        }
+
+        Ok(v128)
+    }
+
+    fn parse_laneidx(&mut self) -> Result<LaneIdx, ParseError> {
+        match &self.lookahead {
+            nm!(Number::Integer(n)) if *n <= LaneIdx::MAX as usize => {
+                let laneidx = *n as LaneIdx;
+                self.consume()?;
+                Ok(laneidx)
+            },
+            _ => Err(self.err2("laneidx")),
+        }
+    }
+
+    fn parse_memory_immediates(&mut self, instr: Instr) -> Result<Instr, ParseError> {
+        let instr = match instr {
+            Instr::Load(vt, _) => {
+                let width = if vt == ValType::I32 || vt == ValType::F32 { 32 } else { 64 };
+                Instr::Load(vt, self.parse_memarg(width)?)
+            },
+            Instr::Store(vt, _) => {
+                let width = if vt == ValType::I32 || vt == ValType::F32 { 32 } else { 64 };
+                Instr::Store(vt, self.parse_memarg(width)?)
+            },
+            Instr::ILoad8(vs, sign, _) => Instr::ILoad8(vs, sign, self.parse_memarg(8)?),
+            Instr::ILoad16(vs, sign, _) => Instr::ILoad16(vs, sign, self.parse_memarg(16)?),
+            Instr::I64Load32(sign, _) => Instr::I64Load32(sign, self.parse_memarg(32)?),
+            Instr::IStore8(vs, _) => Instr::IStore8(vs, self.parse_memarg(8)?),
+            Instr::IStore16(vs, _) => Instr::IStore16(vs, self.parse_memarg(16)?),
+            Instr::I64Store32(_) => Instr::I64Store32(self.parse_memarg(32)?),
+            _ => unreachable!(),
+        };
+        Ok(instr)
+    }
+
+    // a memory index, offset=n and align=n are optional, align defaults to the natural alignment
+    fn parse_memarg(&mut self, width: u32) -> Result<MemArg, ParseError> {
+        let memory = self.parse_optional_memidx()?;
+        let mut memarg = MemArg { align: (width / 8).trailing_zeros(), offset: 0, memory };
+
+        if let tk!(TokenKind::Reserved(s)) = &self.lookahead {
+            if let Some(n) = s.strip_prefix("offset=") {
+                memarg.offset = memarg_value(n).ok_or_else(|| self.err2("memarg: offset"))?;
+                self.consume()?;
+            }
+        }
+        if let tk!(TokenKind::Reserved(s)) = &self.lookahead {
+            if let Some(n) = s.strip_prefix("align=") {
+                match memarg_value(n) {
+                    Some(n) if n.is_power_of_two() => memarg.align = n.trailing_zeros(),
+                    _ => return Err(self.err2("memarg: align")),
+                }
+                self.consume()?;
+            }
+        }
+
+        Ok(memarg)
+    }
+
+    // a lone integer is the lane index, not a memory index
+    fn parse_lane_memarg(&mut self, width: u32) -> Result<MemArg, ParseError> {
+        if let nm!(Number::Integer(_)) = self.lookahead {
+            match self.peek()? {
+                nm!(Number::Integer(_)) | tk!(TokenKind::Reserved(_)) => {},
+                _ => return Ok(MemArg { align: (width / 8).trailing_zeros(), offset: 0, memory: 0 }),
+            }
+        }
+        self.parse_memarg(width)
+    }
+
+    fn parse_call_indirect(&mut self, instrs: &mut Vec<Instr>, call: fn(TableIdx, TypeIdx) -> Instr) -> Result<(), ParseError> {
+        self.consume()?;
+
+        let tableidx = self.parse_optional_tableidx()?;
+
         let mut _ft = FuncType::default();
 
         // add local context(for check)
@@ -175,7 +566,7 @@ impl<R> Parser<R> where R: Read + Seek {
             p!(self.contexts[2].locals);
             Err(self.err2("call_indirect: params context (must be empty)"))
         } else {
-            instrs.push(Instr::CallIndirect(typeidx));
+            instrs.push(call(tableidx, typeidx));
 
             la!(self);p!(self.contexts[2]);
             self.contexts.pop();
@@ -192,9 +583,7 @@ impl<R> Parser<R> where R: Read + Seek {
         parse_optional_label_id!(self, new_label_context.labels);
         self.contexts.push(new_label_context);
 
-        self.match_lparen()?;
-
-        // resulttype
+        // blocktype
         let blocktype = self.parse_blocktype()?;
 
         // instrs1
@@ -216,7 +605,54 @@ impl<R> Parser<R> where R: Read + Seek {
         p!(self.contexts.last());
         self.contexts.pop();
 
-        instrs.push(Instr::If(blocktype, instrs1, Some(instrs2)));
+        instrs.push(Instr::If(blocktype, instrs1.into(), Some(instrs2.into())));
+
+        Ok(())
+    }
+
+    fn parse_try_table(&mut self, instrs: &mut Vec<Instr>) -> Result<(), ParseError> {
+        self.consume()?;
+
+        // label id
+        let mut new_label_context = self.contexts.last().unwrap().clone();
+        parse_optional_label_id!(self, new_label_context.labels);
+
+        // blocktype
+        let blocktype = self.parse_blocktype()?;
+
+        // catches, whose labels are outside of the try_table
+        let mut catches = vec![];
+        while self.is_lparen()? {
+            let keyword = match self.peek()? {
+                kw!(kw @ (Keyword::Catch | Keyword::CatchRef | Keyword::CatchAll | Keyword::CatchAllRef)) => kw,
+                _ => break,
+            };
+            self.match_lparen()?;
+            self.consume()?;
+            let labels = self.contexts.last().unwrap().labels.clone();
+            let catch = match keyword {
+                Keyword::Catch => Catch::Tag(self.resolve_id(&self.contexts[0].tags.clone())?, self.resolve_id(&labels)?),
+                Keyword::CatchRef => Catch::TagRef(self.resolve_id(&self.contexts[0].tags.clone())?, self.resolve_id(&labels)?),
+                Keyword::CatchAll => Catch::All(self.resolve_id(&labels)?),
+                _ => Catch::AllRef(self.resolve_id(&labels)?),
+            };
+            self.match_rparen()?;
+            catches.push(catch);
+        }
+
+        self.contexts.push(new_label_context);
+
+        // instrs
+        let body = self.parse_instrs()?;
+
+        self.match_keyword(Keyword::End)?;
+
+        // label id(repeated)
+        self.check_label_id()?;
+
+        self.contexts.pop();
+
+        instrs.push(Instr::TryTable(blocktype, catches, body.into()));
 
         Ok(())
     }
@@ -265,3 +701,10 @@ impl<R> Parser<R> where R: Read + Seek {
         Ok(())
     }
 }
+
+fn memarg_value(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
@@ -30,7 +30,7 @@ impl<R> Parser<R> where R: Read + Seek {
         let mutablity = Mut::Const;
 
         // valtype
-        let vt = if self.is_lparen()? {
+        let vt = if self.is_field(Keyword::Mutable)? {
             self.match_lparen()?;
             self.match_keyword(Keyword::Mutable)?;
             let vt = self.parse_valtype()?;
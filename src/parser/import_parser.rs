@@ -35,6 +35,7 @@ impl<R> Parser<R> where R: Read + Seek {
             kw!(Keyword::Table) => self.parse_import_desc_table(),
             kw!(Keyword::Memory) => self.parse_import_desc_memory(),
             kw!(Keyword::Global) => self.parse_import_desc_global(),
+            kw!(Keyword::Tag) => self.parse_import_desc_tag(),
             _ => Err(self.err())
         }
     }
@@ -58,6 +59,7 @@ impl<R> Parser<R> where R: Read + Seek {
 
     fn parse_import_desc_table(&mut self) -> Result<ImportDesc, ParseError> {
         let table_type = self.parse_table_type()?;
+        self.match_rparen()?;
         Ok(ImportDesc::Table(table_type))
     }
 
@@ -70,4 +72,9 @@ impl<R> Parser<R> where R: Read + Seek {
         let global_type = self.parse_global_type()?;
         Ok(ImportDesc::Global(global_type))
     }
+
+    fn parse_import_desc_tag(&mut self) -> Result<ImportDesc, ParseError> {
+        let typeidx = self.parse_tag_type()?;
+        Ok(ImportDesc::Tag(typeidx))
+    }
 }
\ No newline at end of file
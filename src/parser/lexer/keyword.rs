@@ -1,5 +1,9 @@
+use std::sync::Arc;
+
 use crate::{
     ValType,
+    RefType,
+    HeapType,
     Instr,
     LabelIdx,
 };
@@ -14,8 +18,10 @@ pub enum Keyword {
     Type,
     Import,
     Func,
+    Extern,
     Table,
     Memory,
+    Tag,
     Global,
     Export,
     Start,
@@ -27,12 +33,41 @@ pub enum Keyword {
     Result,
     AnyFunc,
     Mutable,
+    Shared,
+    PageSize,
     Offset,
     FuncRef,
+    ExternRef,
+    ExnRef,
+    Exn,
+    Ref,
+    Null,
+    Rec,
+    Sub,
+    Final,
+    Struct,
+    Array,
+    Field,
+    I8,
+    I16,
+    Any,
+    Eq,
+    I31,
+    None,
+    NoFunc,
+    NoExtern,
+    NoExn,
+    Catch,
+    CatchRef,
+    CatchAll,
+    CatchAllRef,
+    Declare,
+    Item,
     Else,
     End,
 
     ValType(ValType),
+    Shape(Shape),
 
     Instr(Instr),
 }
@@ -44,8 +79,10 @@ pub(super) fn vec_to_keyword(s: &[u8]) -> Option<Keyword> {
         b"type" => Some(Keyword::Type),
         b"import" => Some(Keyword::Import),
         b"func" => Some(Keyword::Func),
+        b"extern" => Some(Keyword::Extern),
         b"table" => Some(Keyword::Table),
         b"memory" => Some(Keyword::Memory),
+        b"tag" => Some(Keyword::Tag),
         b"global" => Some(Keyword::Global),
         b"export" => Some(Keyword::Export),
         b"start" => Some(Keyword::Start),
@@ -57,35 +94,93 @@ pub(super) fn vec_to_keyword(s: &[u8]) -> Option<Keyword> {
         b"result" => Some(Keyword::Result),
         b"anyfunc" => Some(Keyword::AnyFunc),
         b"mut" => Some(Keyword::Mutable),
+        b"shared" => Some(Keyword::Shared),
+        b"pagesize" => Some(Keyword::PageSize),
         b"offset" => Some(Keyword::Offset),
         b"funcref" => Some(Keyword::FuncRef),
+        b"externref" => Some(Keyword::ExternRef),
+        b"exnref" => Some(Keyword::ExnRef),
+        b"exn" => Some(Keyword::Exn),
+        b"ref" => Some(Keyword::Ref),
+        b"null" => Some(Keyword::Null),
+        b"rec" => Some(Keyword::Rec),
+        b"sub" => Some(Keyword::Sub),
+        b"final" => Some(Keyword::Final),
+        b"struct" => Some(Keyword::Struct),
+        b"array" => Some(Keyword::Array),
+        b"field" => Some(Keyword::Field),
+        b"i8" => Some(Keyword::I8),
+        b"i16" => Some(Keyword::I16),
+        b"any" => Some(Keyword::Any),
+        b"eq" => Some(Keyword::Eq),
+        b"i31" => Some(Keyword::I31),
+        b"none" => Some(Keyword::None),
+        b"nofunc" => Some(Keyword::NoFunc),
+        b"noextern" => Some(Keyword::NoExtern),
+        b"noexn" => Some(Keyword::NoExn),
+        b"catch" => Some(Keyword::Catch),
+        b"catch_ref" => Some(Keyword::CatchRef),
+        b"catch_all" => Some(Keyword::CatchAll),
+        b"catch_all_ref" => Some(Keyword::CatchAllRef),
+        b"declare" => Some(Keyword::Declare),
+        b"item" => Some(Keyword::Item),
         b"else" => Some(Keyword::Else),
         b"end" => Some(Keyword::End),
 
-        b"i32" | b"i64" | b"f32" | b"f64" => vec_to_valtype(s).map(|vt| Keyword::ValType(vt)),
+        b"i32" | b"i64" | b"f32" | b"f64" | b"v128" => vec_to_valtype(s).map(|vt| Keyword::ValType(vt)),
+        b"anyref" | b"eqref" | b"i31ref" | b"structref" | b"arrayref" |
+        b"nullref" | b"nullfuncref" | b"nullexternref" | b"nullexnref" => vec_to_reftype(s).map(Keyword::ValType),
+        b"i8x16" | b"i16x8" | b"i32x4" | b"i64x2" | b"f32x4" | b"f64x2" => vec_to_shape(s).map(Keyword::Shape),
 
         _ => vec_to_instr(s).map(|instr| Keyword::Instr(instr)),
     }
 }
 
+// shorthands of nullable references
+fn vec_to_reftype(s: &[u8]) -> Option<ValType> {
+    let heap = match s {
+        b"anyref" => HeapType::Any,
+        b"eqref" => HeapType::Eq,
+        b"i31ref" => HeapType::I31,
+        b"structref" => HeapType::Struct,
+        b"arrayref" => HeapType::Array,
+        b"nullref" => HeapType::None,
+        b"nullfuncref" => HeapType::NoFunc,
+        b"nullexternref" => HeapType::NoExtern,
+        b"nullexnref" => HeapType::NoExn,
+        _ => return None,
+    };
+    Some(RefType { nullable: true, heap }.valtype())
+}
+
 fn vec_to_instr(s: &[u8]) -> Option<Instr> {
     let blocktype = BlockType::default();
     let bt = default_br_table();
     let memarg = MemArg::default();
+    let reftype = RefType { nullable: true, heap: HeapType::Any };
 
     match s {
-        b"block" => Some(Instr::Block(blocktype, vec![])),
-        b"loop" => Some(Instr::Loop(blocktype, vec![])),
-        b"if" => Some(Instr::If(blocktype, vec![], None)),
+        b"block" => Some(Instr::Block(blocktype, Arc::from([]))),
+        b"loop" => Some(Instr::Loop(blocktype, Arc::from([]))),
+        b"if" => Some(Instr::If(blocktype, Arc::from([]), None)),
+        b"try_table" => Some(Instr::TryTable(blocktype, vec![], Arc::from([]))),
 
         b"unreachable" => Some(Instr::Unreachable),
         b"nop" => Some(Instr::Nop),
         b"br" => Some(Instr::Br(0)),
         b"br_if" => Some(Instr::BrIf(0)),
         b"br_table" => Some(Instr::BrTable(bt, 0)),
+        b"br_on_null" => Some(Instr::BrOnNull(0)),
+        b"br_on_non_null" => Some(Instr::BrOnNonNull(0)),
         b"return" => Some(Instr::Return),
         b"call" => Some(Instr::Call(0)),
-        b"call_indirect" => Some(Instr::CallIndirect(0)),
+        b"call_indirect" => Some(Instr::CallIndirect(0, 0)),
+        b"return_call" => Some(Instr::ReturnCall(0)),
+        b"return_call_indirect" => Some(Instr::ReturnCallIndirect(0, 0)),
+        b"call_ref" => Some(Instr::CallRef(0)),
+        b"return_call_ref" => Some(Instr::ReturnCallRef(0)),
+        b"throw" => Some(Instr::Throw(0)),
+        b"throw_ref" => Some(Instr::ThrowRef),
 
         b"drop" => Some(Instr::Drop(None)),
         b"select" => Some(Instr::Select(None)),
@@ -97,8 +192,57 @@ fn vec_to_instr(s: &[u8]) -> Option<Instr> {
         b"global.set" => Some(Instr::GlobalSet(0)),
 
         b"i64.store32" => Some(Instr::I64Store32(memarg)),
-        b"memory.size" => Some(Instr::MemorySize),
-        b"memory.grow" => Some(Instr::MemoryGrow),
+        b"memory.size" => Some(Instr::MemorySize(0)),
+        b"memory.grow" => Some(Instr::MemoryGrow(0)),
+        b"memory.fill" => Some(Instr::MemoryFill(0)),
+        b"memory.copy" => Some(Instr::MemoryCopy(0, 0)),
+        b"memory.init" => Some(Instr::MemoryInit(0, 0)),
+        b"data.drop" => Some(Instr::DataDrop(0)),
+
+        b"ref.null" => Some(Instr::RefNull(HeapType::Func)),
+        b"ref.is_null" => Some(Instr::RefIsNull),
+        b"ref.as_non_null" => Some(Instr::RefAsNonNull),
+        b"ref.func" => Some(Instr::RefFunc(0)),
+        b"ref.eq" => Some(Instr::RefEq),
+        b"ref.test" => Some(Instr::RefTest(reftype)),
+        b"ref.cast" => Some(Instr::RefCast(reftype)),
+        b"br_on_cast" => Some(Instr::BrOnCast(0, reftype, reftype)),
+        b"br_on_cast_fail" => Some(Instr::BrOnCastFail(0, reftype, reftype)),
+
+        b"struct.new" => Some(Instr::StructNew(0)),
+        b"struct.new_default" => Some(Instr::StructNewDefault(0)),
+        b"struct.get" => Some(Instr::StructGet(None, 0, 0)),
+        b"struct.get_s" => Some(Instr::StructGet(Some(ValSign::S), 0, 0)),
+        b"struct.get_u" => Some(Instr::StructGet(Some(ValSign::U), 0, 0)),
+        b"struct.set" => Some(Instr::StructSet(0, 0)),
+        b"array.new" => Some(Instr::ArrayNew(0)),
+        b"array.new_default" => Some(Instr::ArrayNewDefault(0)),
+        b"array.new_fixed" => Some(Instr::ArrayNewFixed(0, 0)),
+        b"array.get" => Some(Instr::ArrayGet(None, 0)),
+        b"array.get_s" => Some(Instr::ArrayGet(Some(ValSign::S), 0)),
+        b"array.get_u" => Some(Instr::ArrayGet(Some(ValSign::U), 0)),
+        b"array.set" => Some(Instr::ArraySet(0)),
+        b"array.len" => Some(Instr::ArrayLen),
+        b"array.fill" => Some(Instr::ArrayFill(0)),
+        b"array.copy" => Some(Instr::ArrayCopy(0, 0)),
+        b"array.new_data" => Some(Instr::ArrayNewData(0, 0)),
+        b"array.new_elem" => Some(Instr::ArrayNewElem(0, 0)),
+        b"array.init_data" => Some(Instr::ArrayInitData(0, 0)),
+        b"array.init_elem" => Some(Instr::ArrayInitElem(0, 0)),
+        b"ref.i31" => Some(Instr::RefI31),
+        b"i31.get_s" => Some(Instr::I31Get(ValSign::S)),
+        b"i31.get_u" => Some(Instr::I31Get(ValSign::U)),
+        b"any.convert_extern" => Some(Instr::AnyConvertExtern),
+        b"extern.convert_any" => Some(Instr::ExternConvertAny),
+
+        b"table.get" => Some(Instr::TableGet(0)),
+        b"table.set" => Some(Instr::TableSet(0)),
+        b"table.size" => Some(Instr::TableSize(0)),
+        b"table.grow" => Some(Instr::TableGrow(0)),
+        b"table.fill" => Some(Instr::TableFill(0)),
+        b"table.copy" => Some(Instr::TableCopy(0, 0)),
+        b"table.init" => Some(Instr::TableInit(0, 0)),
+        b"elem.drop" => Some(Instr::ElemDrop(0)),
 
         b"i32.wrap/i64" => Some(Instr::CvtOp(CvtOp::I32WrapFromI64)),
         b"i64.extend_s/i32" => Some(Instr::CvtOp(CvtOp::I64ExtendFromI32(ValSign::S))),
@@ -130,10 +274,335 @@ fn vec_to_instr(s: &[u8]) -> Option<Instr> {
         b"f32.reinterpret/i32" => Some(Instr::CvtOp(CvtOp::FReinterpretFromI(ValSize::V32))),
         b"f64.reinterpret/i64" => Some(Instr::CvtOp(CvtOp::FReinterpretFromI(ValSize::V64))),
 
+        b"v128.load" => Some(Instr::Load(ValType::V128, memarg.clone())),
+        b"v128.load8x8_s" => Some(Instr::V128LoadExtend(Shape::I16x8, ValSign::S, memarg.clone())),
+        b"v128.load8x8_u" => Some(Instr::V128LoadExtend(Shape::I16x8, ValSign::U, memarg.clone())),
+        b"v128.load16x4_s" => Some(Instr::V128LoadExtend(Shape::I32x4, ValSign::S, memarg.clone())),
+        b"v128.load16x4_u" => Some(Instr::V128LoadExtend(Shape::I32x4, ValSign::U, memarg.clone())),
+        b"v128.load32x2_s" => Some(Instr::V128LoadExtend(Shape::I64x2, ValSign::S, memarg.clone())),
+        b"v128.load32x2_u" => Some(Instr::V128LoadExtend(Shape::I64x2, ValSign::U, memarg.clone())),
+        b"v128.load8_splat" => Some(Instr::V128LoadSplat(Shape::I8x16, memarg.clone())),
+        b"v128.load16_splat" => Some(Instr::V128LoadSplat(Shape::I16x8, memarg.clone())),
+        b"v128.load32_splat" => Some(Instr::V128LoadSplat(Shape::I32x4, memarg.clone())),
+        b"v128.load64_splat" => Some(Instr::V128LoadSplat(Shape::I64x2, memarg.clone())),
+        b"v128.store" => Some(Instr::Store(ValType::V128, memarg.clone())),
+        b"v128.const" => Some(Instr::V128Const(0)),
+        b"i8x16.shuffle" => Some(Instr::I8x16Shuffle([0; 16])),
+        b"i8x16.swizzle" => Some(Instr::I8x16Swizzle),
+        b"i8x16.splat" => Some(Instr::Splat(Shape::I8x16)),
+        b"i16x8.splat" => Some(Instr::Splat(Shape::I16x8)),
+        b"i32x4.splat" => Some(Instr::Splat(Shape::I32x4)),
+        b"i64x2.splat" => Some(Instr::Splat(Shape::I64x2)),
+        b"f32x4.splat" => Some(Instr::Splat(Shape::F32x4)),
+        b"f64x2.splat" => Some(Instr::Splat(Shape::F64x2)),
+        b"i8x16.extract_lane_s" => Some(Instr::ExtractLane(Shape::I8x16, Some(ValSign::S), 0)),
+        b"i8x16.extract_lane_u" => Some(Instr::ExtractLane(Shape::I8x16, Some(ValSign::U), 0)),
+        b"i8x16.replace_lane" => Some(Instr::ReplaceLane(Shape::I8x16, 0)),
+        b"i16x8.extract_lane_s" => Some(Instr::ExtractLane(Shape::I16x8, Some(ValSign::S), 0)),
+        b"i16x8.extract_lane_u" => Some(Instr::ExtractLane(Shape::I16x8, Some(ValSign::U), 0)),
+        b"i16x8.replace_lane" => Some(Instr::ReplaceLane(Shape::I16x8, 0)),
+        b"i32x4.extract_lane" => Some(Instr::ExtractLane(Shape::I32x4, None, 0)),
+        b"i32x4.replace_lane" => Some(Instr::ReplaceLane(Shape::I32x4, 0)),
+        b"i64x2.extract_lane" => Some(Instr::ExtractLane(Shape::I64x2, None, 0)),
+        b"i64x2.replace_lane" => Some(Instr::ReplaceLane(Shape::I64x2, 0)),
+        b"f32x4.extract_lane" => Some(Instr::ExtractLane(Shape::F32x4, None, 0)),
+        b"f32x4.replace_lane" => Some(Instr::ReplaceLane(Shape::F32x4, 0)),
+        b"f64x2.extract_lane" => Some(Instr::ExtractLane(Shape::F64x2, None, 0)),
+        b"f64x2.replace_lane" => Some(Instr::ReplaceLane(Shape::F64x2, 0)),
+        b"i8x16.eq" => Some(Instr::VIRelOp(Shape::I8x16, IRelOp::Eq)),
+        b"i8x16.ne" => Some(Instr::VIRelOp(Shape::I8x16, IRelOp::Ne)),
+        b"i8x16.lt_s" => Some(Instr::VIRelOp(Shape::I8x16, IRelOp::Lt(ValSign::S))),
+        b"i8x16.lt_u" => Some(Instr::VIRelOp(Shape::I8x16, IRelOp::Lt(ValSign::U))),
+        b"i8x16.gt_s" => Some(Instr::VIRelOp(Shape::I8x16, IRelOp::Gt(ValSign::S))),
+        b"i8x16.gt_u" => Some(Instr::VIRelOp(Shape::I8x16, IRelOp::Gt(ValSign::U))),
+        b"i8x16.le_s" => Some(Instr::VIRelOp(Shape::I8x16, IRelOp::Le(ValSign::S))),
+        b"i8x16.le_u" => Some(Instr::VIRelOp(Shape::I8x16, IRelOp::Le(ValSign::U))),
+        b"i8x16.ge_s" => Some(Instr::VIRelOp(Shape::I8x16, IRelOp::Ge(ValSign::S))),
+        b"i8x16.ge_u" => Some(Instr::VIRelOp(Shape::I8x16, IRelOp::Ge(ValSign::U))),
+        b"i16x8.eq" => Some(Instr::VIRelOp(Shape::I16x8, IRelOp::Eq)),
+        b"i16x8.ne" => Some(Instr::VIRelOp(Shape::I16x8, IRelOp::Ne)),
+        b"i16x8.lt_s" => Some(Instr::VIRelOp(Shape::I16x8, IRelOp::Lt(ValSign::S))),
+        b"i16x8.lt_u" => Some(Instr::VIRelOp(Shape::I16x8, IRelOp::Lt(ValSign::U))),
+        b"i16x8.gt_s" => Some(Instr::VIRelOp(Shape::I16x8, IRelOp::Gt(ValSign::S))),
+        b"i16x8.gt_u" => Some(Instr::VIRelOp(Shape::I16x8, IRelOp::Gt(ValSign::U))),
+        b"i16x8.le_s" => Some(Instr::VIRelOp(Shape::I16x8, IRelOp::Le(ValSign::S))),
+        b"i16x8.le_u" => Some(Instr::VIRelOp(Shape::I16x8, IRelOp::Le(ValSign::U))),
+        b"i16x8.ge_s" => Some(Instr::VIRelOp(Shape::I16x8, IRelOp::Ge(ValSign::S))),
+        b"i16x8.ge_u" => Some(Instr::VIRelOp(Shape::I16x8, IRelOp::Ge(ValSign::U))),
+        b"i32x4.eq" => Some(Instr::VIRelOp(Shape::I32x4, IRelOp::Eq)),
+        b"i32x4.ne" => Some(Instr::VIRelOp(Shape::I32x4, IRelOp::Ne)),
+        b"i32x4.lt_s" => Some(Instr::VIRelOp(Shape::I32x4, IRelOp::Lt(ValSign::S))),
+        b"i32x4.lt_u" => Some(Instr::VIRelOp(Shape::I32x4, IRelOp::Lt(ValSign::U))),
+        b"i32x4.gt_s" => Some(Instr::VIRelOp(Shape::I32x4, IRelOp::Gt(ValSign::S))),
+        b"i32x4.gt_u" => Some(Instr::VIRelOp(Shape::I32x4, IRelOp::Gt(ValSign::U))),
+        b"i32x4.le_s" => Some(Instr::VIRelOp(Shape::I32x4, IRelOp::Le(ValSign::S))),
+        b"i32x4.le_u" => Some(Instr::VIRelOp(Shape::I32x4, IRelOp::Le(ValSign::U))),
+        b"i32x4.ge_s" => Some(Instr::VIRelOp(Shape::I32x4, IRelOp::Ge(ValSign::S))),
+        b"i32x4.ge_u" => Some(Instr::VIRelOp(Shape::I32x4, IRelOp::Ge(ValSign::U))),
+        b"f32x4.eq" => Some(Instr::VFRelOp(Shape::F32x4, FRelOp::Eq)),
+        b"f32x4.ne" => Some(Instr::VFRelOp(Shape::F32x4, FRelOp::Ne)),
+        b"f32x4.lt" => Some(Instr::VFRelOp(Shape::F32x4, FRelOp::Lt)),
+        b"f32x4.gt" => Some(Instr::VFRelOp(Shape::F32x4, FRelOp::Gt)),
+        b"f32x4.le" => Some(Instr::VFRelOp(Shape::F32x4, FRelOp::Le)),
+        b"f32x4.ge" => Some(Instr::VFRelOp(Shape::F32x4, FRelOp::Ge)),
+        b"f64x2.eq" => Some(Instr::VFRelOp(Shape::F64x2, FRelOp::Eq)),
+        b"f64x2.ne" => Some(Instr::VFRelOp(Shape::F64x2, FRelOp::Ne)),
+        b"f64x2.lt" => Some(Instr::VFRelOp(Shape::F64x2, FRelOp::Lt)),
+        b"f64x2.gt" => Some(Instr::VFRelOp(Shape::F64x2, FRelOp::Gt)),
+        b"f64x2.le" => Some(Instr::VFRelOp(Shape::F64x2, FRelOp::Le)),
+        b"f64x2.ge" => Some(Instr::VFRelOp(Shape::F64x2, FRelOp::Ge)),
+        b"v128.not" => Some(Instr::V128Not),
+        b"v128.and" => Some(Instr::VVBinOp(VVBinOp::And)),
+        b"v128.andnot" => Some(Instr::VVBinOp(VVBinOp::AndNot)),
+        b"v128.or" => Some(Instr::VVBinOp(VVBinOp::Or)),
+        b"v128.xor" => Some(Instr::VVBinOp(VVBinOp::Xor)),
+        b"v128.bitselect" => Some(Instr::V128Bitselect),
+        b"v128.any_true" => Some(Instr::V128AnyTrue),
+        b"v128.load8_lane" => Some(Instr::V128LoadLane(Shape::I8x16, memarg.clone(), 0)),
+        b"v128.load16_lane" => Some(Instr::V128LoadLane(Shape::I16x8, memarg.clone(), 0)),
+        b"v128.load32_lane" => Some(Instr::V128LoadLane(Shape::I32x4, memarg.clone(), 0)),
+        b"v128.load64_lane" => Some(Instr::V128LoadLane(Shape::I64x2, memarg.clone(), 0)),
+        b"v128.store8_lane" => Some(Instr::V128StoreLane(Shape::I8x16, memarg.clone(), 0)),
+        b"v128.store16_lane" => Some(Instr::V128StoreLane(Shape::I16x8, memarg.clone(), 0)),
+        b"v128.store32_lane" => Some(Instr::V128StoreLane(Shape::I32x4, memarg.clone(), 0)),
+        b"v128.store64_lane" => Some(Instr::V128StoreLane(Shape::I64x2, memarg.clone(), 0)),
+        b"v128.load32_zero" => Some(Instr::V128LoadZero(ValSize::V32, memarg.clone())),
+        b"v128.load64_zero" => Some(Instr::V128LoadZero(ValSize::V64, memarg.clone())),
+        b"f32x4.demote_f64x2_zero" => Some(Instr::VCvtOp(VCvtOp::F32x4DemoteF64x2Zero)),
+        b"f64x2.promote_low_f32x4" => Some(Instr::VCvtOp(VCvtOp::F64x2PromoteLowF32x4)),
+        b"i8x16.abs" => Some(Instr::VIUnOp(Shape::I8x16, VIUnOp::Abs)),
+        b"i8x16.neg" => Some(Instr::VIUnOp(Shape::I8x16, VIUnOp::Neg)),
+        b"i8x16.popcnt" => Some(Instr::VIUnOp(Shape::I8x16, VIUnOp::Popcnt)),
+        b"i8x16.all_true" => Some(Instr::AllTrue(Shape::I8x16)),
+        b"i8x16.bitmask" => Some(Instr::Bitmask(Shape::I8x16)),
+        b"i8x16.narrow_i16x8_s" => Some(Instr::Narrow(Shape::I8x16, ValSign::S)),
+        b"i8x16.narrow_i16x8_u" => Some(Instr::Narrow(Shape::I8x16, ValSign::U)),
+        b"f32x4.ceil" => Some(Instr::VFUnOp(Shape::F32x4, FUnOp::Ceil)),
+        b"f32x4.floor" => Some(Instr::VFUnOp(Shape::F32x4, FUnOp::Floor)),
+        b"f32x4.trunc" => Some(Instr::VFUnOp(Shape::F32x4, FUnOp::Trunc)),
+        b"f32x4.nearest" => Some(Instr::VFUnOp(Shape::F32x4, FUnOp::Nearest)),
+        b"i8x16.shl" => Some(Instr::VIShiftOp(Shape::I8x16, VIShiftOp::Shl)),
+        b"i8x16.shr_s" => Some(Instr::VIShiftOp(Shape::I8x16, VIShiftOp::Shr(ValSign::S))),
+        b"i8x16.shr_u" => Some(Instr::VIShiftOp(Shape::I8x16, VIShiftOp::Shr(ValSign::U))),
+        b"i8x16.add" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::Add)),
+        b"i8x16.add_sat_s" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::AddSat(ValSign::S))),
+        b"i8x16.add_sat_u" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::AddSat(ValSign::U))),
+        b"i8x16.sub" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::Sub)),
+        b"i8x16.sub_sat_s" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::SubSat(ValSign::S))),
+        b"i8x16.sub_sat_u" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::SubSat(ValSign::U))),
+        b"f64x2.ceil" => Some(Instr::VFUnOp(Shape::F64x2, FUnOp::Ceil)),
+        b"f64x2.floor" => Some(Instr::VFUnOp(Shape::F64x2, FUnOp::Floor)),
+        b"i8x16.min_s" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::Min(ValSign::S))),
+        b"i8x16.min_u" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::Min(ValSign::U))),
+        b"i8x16.max_s" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::Max(ValSign::S))),
+        b"i8x16.max_u" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::Max(ValSign::U))),
+        b"f64x2.trunc" => Some(Instr::VFUnOp(Shape::F64x2, FUnOp::Trunc)),
+        b"i8x16.avgr_u" => Some(Instr::VIBinOp(Shape::I8x16, VIBinOp::AvgrU)),
+        b"i16x8.extadd_pairwise_i8x16_s" => Some(Instr::ExtAddPairwise(Shape::I16x8, ValSign::S)),
+        b"i16x8.extadd_pairwise_i8x16_u" => Some(Instr::ExtAddPairwise(Shape::I16x8, ValSign::U)),
+        b"i32x4.extadd_pairwise_i16x8_s" => Some(Instr::ExtAddPairwise(Shape::I32x4, ValSign::S)),
+        b"i32x4.extadd_pairwise_i16x8_u" => Some(Instr::ExtAddPairwise(Shape::I32x4, ValSign::U)),
+        b"i16x8.abs" => Some(Instr::VIUnOp(Shape::I16x8, VIUnOp::Abs)),
+        b"i16x8.neg" => Some(Instr::VIUnOp(Shape::I16x8, VIUnOp::Neg)),
+        b"i16x8.q15mulr_sat_s" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::Q15MulrSatS)),
+        b"i16x8.all_true" => Some(Instr::AllTrue(Shape::I16x8)),
+        b"i16x8.bitmask" => Some(Instr::Bitmask(Shape::I16x8)),
+        b"i16x8.narrow_i32x4_s" => Some(Instr::Narrow(Shape::I16x8, ValSign::S)),
+        b"i16x8.narrow_i32x4_u" => Some(Instr::Narrow(Shape::I16x8, ValSign::U)),
+        b"i16x8.extend_low_i8x16_s" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::Low, ValSign::S))),
+        b"i16x8.extend_high_i8x16_s" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::High, ValSign::S))),
+        b"i16x8.extend_low_i8x16_u" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::Low, ValSign::U))),
+        b"i16x8.extend_high_i8x16_u" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I16x8, Half::High, ValSign::U))),
+        b"i16x8.shl" => Some(Instr::VIShiftOp(Shape::I16x8, VIShiftOp::Shl)),
+        b"i16x8.shr_s" => Some(Instr::VIShiftOp(Shape::I16x8, VIShiftOp::Shr(ValSign::S))),
+        b"i16x8.shr_u" => Some(Instr::VIShiftOp(Shape::I16x8, VIShiftOp::Shr(ValSign::U))),
+        b"i16x8.add" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::Add)),
+        b"i16x8.add_sat_s" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::AddSat(ValSign::S))),
+        b"i16x8.add_sat_u" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::AddSat(ValSign::U))),
+        b"i16x8.sub" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::Sub)),
+        b"i16x8.sub_sat_s" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::SubSat(ValSign::S))),
+        b"i16x8.sub_sat_u" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::SubSat(ValSign::U))),
+        b"f64x2.nearest" => Some(Instr::VFUnOp(Shape::F64x2, FUnOp::Nearest)),
+        b"i16x8.mul" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::Mul)),
+        b"i16x8.min_s" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::Min(ValSign::S))),
+        b"i16x8.min_u" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::Min(ValSign::U))),
+        b"i16x8.max_s" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::Max(ValSign::S))),
+        b"i16x8.max_u" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::Max(ValSign::U))),
+        b"i16x8.avgr_u" => Some(Instr::VIBinOp(Shape::I16x8, VIBinOp::AvgrU)),
+        b"i16x8.extmul_low_i8x16_s" => Some(Instr::ExtMul(Shape::I16x8, Half::Low, ValSign::S)),
+        b"i16x8.extmul_high_i8x16_s" => Some(Instr::ExtMul(Shape::I16x8, Half::High, ValSign::S)),
+        b"i16x8.extmul_low_i8x16_u" => Some(Instr::ExtMul(Shape::I16x8, Half::Low, ValSign::U)),
+        b"i16x8.extmul_high_i8x16_u" => Some(Instr::ExtMul(Shape::I16x8, Half::High, ValSign::U)),
+        b"i32x4.abs" => Some(Instr::VIUnOp(Shape::I32x4, VIUnOp::Abs)),
+        b"i32x4.neg" => Some(Instr::VIUnOp(Shape::I32x4, VIUnOp::Neg)),
+        b"i32x4.all_true" => Some(Instr::AllTrue(Shape::I32x4)),
+        b"i32x4.bitmask" => Some(Instr::Bitmask(Shape::I32x4)),
+        b"i32x4.extend_low_i16x8_s" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::Low, ValSign::S))),
+        b"i32x4.extend_high_i16x8_s" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::High, ValSign::S))),
+        b"i32x4.extend_low_i16x8_u" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::Low, ValSign::U))),
+        b"i32x4.extend_high_i16x8_u" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I32x4, Half::High, ValSign::U))),
+        b"i32x4.shl" => Some(Instr::VIShiftOp(Shape::I32x4, VIShiftOp::Shl)),
+        b"i32x4.shr_s" => Some(Instr::VIShiftOp(Shape::I32x4, VIShiftOp::Shr(ValSign::S))),
+        b"i32x4.shr_u" => Some(Instr::VIShiftOp(Shape::I32x4, VIShiftOp::Shr(ValSign::U))),
+        b"i32x4.add" => Some(Instr::VIBinOp(Shape::I32x4, VIBinOp::Add)),
+        b"i32x4.sub" => Some(Instr::VIBinOp(Shape::I32x4, VIBinOp::Sub)),
+        b"i32x4.mul" => Some(Instr::VIBinOp(Shape::I32x4, VIBinOp::Mul)),
+        b"i32x4.min_s" => Some(Instr::VIBinOp(Shape::I32x4, VIBinOp::Min(ValSign::S))),
+        b"i32x4.min_u" => Some(Instr::VIBinOp(Shape::I32x4, VIBinOp::Min(ValSign::U))),
+        b"i32x4.max_s" => Some(Instr::VIBinOp(Shape::I32x4, VIBinOp::Max(ValSign::S))),
+        b"i32x4.max_u" => Some(Instr::VIBinOp(Shape::I32x4, VIBinOp::Max(ValSign::U))),
+        b"i32x4.dot_i16x8_s" => Some(Instr::I32x4DotI16x8S),
+        b"i32x4.extmul_low_i16x8_s" => Some(Instr::ExtMul(Shape::I32x4, Half::Low, ValSign::S)),
+        b"i32x4.extmul_high_i16x8_s" => Some(Instr::ExtMul(Shape::I32x4, Half::High, ValSign::S)),
+        b"i32x4.extmul_low_i16x8_u" => Some(Instr::ExtMul(Shape::I32x4, Half::Low, ValSign::U)),
+        b"i32x4.extmul_high_i16x8_u" => Some(Instr::ExtMul(Shape::I32x4, Half::High, ValSign::U)),
+        b"i64x2.abs" => Some(Instr::VIUnOp(Shape::I64x2, VIUnOp::Abs)),
+        b"i64x2.neg" => Some(Instr::VIUnOp(Shape::I64x2, VIUnOp::Neg)),
+        b"i64x2.all_true" => Some(Instr::AllTrue(Shape::I64x2)),
+        b"i64x2.bitmask" => Some(Instr::Bitmask(Shape::I64x2)),
+        b"i64x2.extend_low_i32x4_s" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::Low, ValSign::S))),
+        b"i64x2.extend_high_i32x4_s" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::High, ValSign::S))),
+        b"i64x2.extend_low_i32x4_u" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::Low, ValSign::U))),
+        b"i64x2.extend_high_i32x4_u" => Some(Instr::VCvtOp(VCvtOp::Extend(Shape::I64x2, Half::High, ValSign::U))),
+        b"i64x2.shl" => Some(Instr::VIShiftOp(Shape::I64x2, VIShiftOp::Shl)),
+        b"i64x2.shr_s" => Some(Instr::VIShiftOp(Shape::I64x2, VIShiftOp::Shr(ValSign::S))),
+        b"i64x2.shr_u" => Some(Instr::VIShiftOp(Shape::I64x2, VIShiftOp::Shr(ValSign::U))),
+        b"i64x2.add" => Some(Instr::VIBinOp(Shape::I64x2, VIBinOp::Add)),
+        b"i64x2.sub" => Some(Instr::VIBinOp(Shape::I64x2, VIBinOp::Sub)),
+        b"i64x2.mul" => Some(Instr::VIBinOp(Shape::I64x2, VIBinOp::Mul)),
+        b"i64x2.eq" => Some(Instr::VIRelOp(Shape::I64x2, IRelOp::Eq)),
+        b"i64x2.ne" => Some(Instr::VIRelOp(Shape::I64x2, IRelOp::Ne)),
+        b"i64x2.lt_s" => Some(Instr::VIRelOp(Shape::I64x2, IRelOp::Lt(ValSign::S))),
+        b"i64x2.gt_s" => Some(Instr::VIRelOp(Shape::I64x2, IRelOp::Gt(ValSign::S))),
+        b"i64x2.le_s" => Some(Instr::VIRelOp(Shape::I64x2, IRelOp::Le(ValSign::S))),
+        b"i64x2.ge_s" => Some(Instr::VIRelOp(Shape::I64x2, IRelOp::Ge(ValSign::S))),
+        b"i64x2.extmul_low_i32x4_s" => Some(Instr::ExtMul(Shape::I64x2, Half::Low, ValSign::S)),
+        b"i64x2.extmul_high_i32x4_s" => Some(Instr::ExtMul(Shape::I64x2, Half::High, ValSign::S)),
+        b"i64x2.extmul_low_i32x4_u" => Some(Instr::ExtMul(Shape::I64x2, Half::Low, ValSign::U)),
+        b"i64x2.extmul_high_i32x4_u" => Some(Instr::ExtMul(Shape::I64x2, Half::High, ValSign::U)),
+        b"f32x4.abs" => Some(Instr::VFUnOp(Shape::F32x4, FUnOp::Abs)),
+        b"f32x4.neg" => Some(Instr::VFUnOp(Shape::F32x4, FUnOp::Neg)),
+        b"f32x4.sqrt" => Some(Instr::VFUnOp(Shape::F32x4, FUnOp::Sqrt)),
+        b"f32x4.add" => Some(Instr::VFBinOp(Shape::F32x4, VFBinOp::Add)),
+        b"f32x4.sub" => Some(Instr::VFBinOp(Shape::F32x4, VFBinOp::Sub)),
+        b"f32x4.mul" => Some(Instr::VFBinOp(Shape::F32x4, VFBinOp::Mul)),
+        b"f32x4.div" => Some(Instr::VFBinOp(Shape::F32x4, VFBinOp::Div)),
+        b"f32x4.min" => Some(Instr::VFBinOp(Shape::F32x4, VFBinOp::Min)),
+        b"f32x4.max" => Some(Instr::VFBinOp(Shape::F32x4, VFBinOp::Max)),
+        b"f32x4.pmin" => Some(Instr::VFBinOp(Shape::F32x4, VFBinOp::PMin)),
+        b"f32x4.pmax" => Some(Instr::VFBinOp(Shape::F32x4, VFBinOp::PMax)),
+        b"f64x2.abs" => Some(Instr::VFUnOp(Shape::F64x2, FUnOp::Abs)),
+        b"f64x2.neg" => Some(Instr::VFUnOp(Shape::F64x2, FUnOp::Neg)),
+        b"f64x2.sqrt" => Some(Instr::VFUnOp(Shape::F64x2, FUnOp::Sqrt)),
+        b"f64x2.add" => Some(Instr::VFBinOp(Shape::F64x2, VFBinOp::Add)),
+        b"f64x2.sub" => Some(Instr::VFBinOp(Shape::F64x2, VFBinOp::Sub)),
+        b"f64x2.mul" => Some(Instr::VFBinOp(Shape::F64x2, VFBinOp::Mul)),
+        b"f64x2.div" => Some(Instr::VFBinOp(Shape::F64x2, VFBinOp::Div)),
+        b"f64x2.min" => Some(Instr::VFBinOp(Shape::F64x2, VFBinOp::Min)),
+        b"f64x2.max" => Some(Instr::VFBinOp(Shape::F64x2, VFBinOp::Max)),
+        b"f64x2.pmin" => Some(Instr::VFBinOp(Shape::F64x2, VFBinOp::PMin)),
+        b"f64x2.pmax" => Some(Instr::VFBinOp(Shape::F64x2, VFBinOp::PMax)),
+        b"i32x4.trunc_sat_f32x4_s" => Some(Instr::VCvtOp(VCvtOp::TruncSat(Shape::F32x4, ValSign::S))),
+        b"i32x4.trunc_sat_f32x4_u" => Some(Instr::VCvtOp(VCvtOp::TruncSat(Shape::F32x4, ValSign::U))),
+        b"f32x4.convert_i32x4_s" => Some(Instr::VCvtOp(VCvtOp::Convert(Shape::F32x4, ValSign::S))),
+        b"f32x4.convert_i32x4_u" => Some(Instr::VCvtOp(VCvtOp::Convert(Shape::F32x4, ValSign::U))),
+        b"i32x4.trunc_sat_f64x2_s_zero" => Some(Instr::VCvtOp(VCvtOp::TruncSat(Shape::F64x2, ValSign::S))),
+        b"i32x4.trunc_sat_f64x2_u_zero" => Some(Instr::VCvtOp(VCvtOp::TruncSat(Shape::F64x2, ValSign::U))),
+        b"f64x2.convert_low_i32x4_s" => Some(Instr::VCvtOp(VCvtOp::Convert(Shape::F64x2, ValSign::S))),
+        b"f64x2.convert_low_i32x4_u" => Some(Instr::VCvtOp(VCvtOp::Convert(Shape::F64x2, ValSign::U))),
+        b"i8x16.relaxed_swizzle" => Some(Instr::I8x16RelaxedSwizzle),
+        b"i32x4.relaxed_trunc_f32x4_s" => Some(Instr::RelaxedTrunc(Shape::F32x4, ValSign::S)),
+        b"i32x4.relaxed_trunc_f32x4_u" => Some(Instr::RelaxedTrunc(Shape::F32x4, ValSign::U)),
+        b"i32x4.relaxed_trunc_f64x2_s_zero" => Some(Instr::RelaxedTrunc(Shape::F64x2, ValSign::S)),
+        b"i32x4.relaxed_trunc_f64x2_u_zero" => Some(Instr::RelaxedTrunc(Shape::F64x2, ValSign::U)),
+        b"f32x4.relaxed_madd" => Some(Instr::RelaxedMadd(Shape::F32x4)),
+        b"f32x4.relaxed_nmadd" => Some(Instr::RelaxedNmadd(Shape::F32x4)),
+        b"f64x2.relaxed_madd" => Some(Instr::RelaxedMadd(Shape::F64x2)),
+        b"f64x2.relaxed_nmadd" => Some(Instr::RelaxedNmadd(Shape::F64x2)),
+        b"i8x16.relaxed_laneselect" => Some(Instr::RelaxedLaneselect(Shape::I8x16)),
+        b"i16x8.relaxed_laneselect" => Some(Instr::RelaxedLaneselect(Shape::I16x8)),
+        b"i32x4.relaxed_laneselect" => Some(Instr::RelaxedLaneselect(Shape::I32x4)),
+        b"i64x2.relaxed_laneselect" => Some(Instr::RelaxedLaneselect(Shape::I64x2)),
+        b"f32x4.relaxed_min" => Some(Instr::RelaxedMin(Shape::F32x4)),
+        b"f32x4.relaxed_max" => Some(Instr::RelaxedMax(Shape::F32x4)),
+        b"f64x2.relaxed_min" => Some(Instr::RelaxedMin(Shape::F64x2)),
+        b"f64x2.relaxed_max" => Some(Instr::RelaxedMax(Shape::F64x2)),
+        b"i16x8.relaxed_q15mulr_s" => Some(Instr::I16x8RelaxedQ15mulrS),
+        b"i16x8.relaxed_dot_i8x16_i7x16_s" => Some(Instr::I16x8RelaxedDotI8x16I7x16S),
+        b"i32x4.relaxed_dot_i8x16_i7x16_add_s" => Some(Instr::I32x4RelaxedDotI8x16I7x16AddS),
+
+        b"memory.atomic.notify" => Some(Instr::AtomicNotify(memarg.clone())),
+        b"memory.atomic.wait32" => Some(Instr::AtomicWait(ValSize::V32, memarg.clone())),
+        b"memory.atomic.wait64" => Some(Instr::AtomicWait(ValSize::V64, memarg.clone())),
+        b"atomic.fence" => Some(Instr::AtomicFence),
+        b"i32.atomic.load" => Some(Instr::AtomicLoad(ValSize::V32, None, memarg.clone())),
+        b"i64.atomic.load" => Some(Instr::AtomicLoad(ValSize::V64, None, memarg.clone())),
+        b"i32.atomic.load8_u" => Some(Instr::AtomicLoad(ValSize::V32, Some(PackSize::P8), memarg.clone())),
+        b"i32.atomic.load16_u" => Some(Instr::AtomicLoad(ValSize::V32, Some(PackSize::P16), memarg.clone())),
+        b"i64.atomic.load8_u" => Some(Instr::AtomicLoad(ValSize::V64, Some(PackSize::P8), memarg.clone())),
+        b"i64.atomic.load16_u" => Some(Instr::AtomicLoad(ValSize::V64, Some(PackSize::P16), memarg.clone())),
+        b"i64.atomic.load32_u" => Some(Instr::AtomicLoad(ValSize::V64, Some(PackSize::P32), memarg.clone())),
+        b"i32.atomic.store" => Some(Instr::AtomicStore(ValSize::V32, None, memarg.clone())),
+        b"i64.atomic.store" => Some(Instr::AtomicStore(ValSize::V64, None, memarg.clone())),
+        b"i32.atomic.store8" => Some(Instr::AtomicStore(ValSize::V32, Some(PackSize::P8), memarg.clone())),
+        b"i32.atomic.store16" => Some(Instr::AtomicStore(ValSize::V32, Some(PackSize::P16), memarg.clone())),
+        b"i64.atomic.store8" => Some(Instr::AtomicStore(ValSize::V64, Some(PackSize::P8), memarg.clone())),
+        b"i64.atomic.store16" => Some(Instr::AtomicStore(ValSize::V64, Some(PackSize::P16), memarg.clone())),
+        b"i64.atomic.store32" => Some(Instr::AtomicStore(ValSize::V64, Some(PackSize::P32), memarg.clone())),
+        b"i32.atomic.rmw.add" => Some(Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Add, memarg.clone())),
+        b"i64.atomic.rmw.add" => Some(Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Add, memarg.clone())),
+        b"i32.atomic.rmw8.add_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Add, memarg.clone())),
+        b"i32.atomic.rmw16.add_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Add, memarg.clone())),
+        b"i64.atomic.rmw8.add_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Add, memarg.clone())),
+        b"i64.atomic.rmw16.add_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Add, memarg.clone())),
+        b"i64.atomic.rmw32.add_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Add, memarg.clone())),
+        b"i32.atomic.rmw.sub" => Some(Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Sub, memarg.clone())),
+        b"i64.atomic.rmw.sub" => Some(Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Sub, memarg.clone())),
+        b"i32.atomic.rmw8.sub_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Sub, memarg.clone())),
+        b"i32.atomic.rmw16.sub_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Sub, memarg.clone())),
+        b"i64.atomic.rmw8.sub_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Sub, memarg.clone())),
+        b"i64.atomic.rmw16.sub_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Sub, memarg.clone())),
+        b"i64.atomic.rmw32.sub_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Sub, memarg.clone())),
+        b"i32.atomic.rmw.and" => Some(Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::And, memarg.clone())),
+        b"i64.atomic.rmw.and" => Some(Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::And, memarg.clone())),
+        b"i32.atomic.rmw8.and_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::And, memarg.clone())),
+        b"i32.atomic.rmw16.and_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::And, memarg.clone())),
+        b"i64.atomic.rmw8.and_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::And, memarg.clone())),
+        b"i64.atomic.rmw16.and_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::And, memarg.clone())),
+        b"i64.atomic.rmw32.and_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::And, memarg.clone())),
+        b"i32.atomic.rmw.or" => Some(Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Or, memarg.clone())),
+        b"i64.atomic.rmw.or" => Some(Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Or, memarg.clone())),
+        b"i32.atomic.rmw8.or_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Or, memarg.clone())),
+        b"i32.atomic.rmw16.or_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Or, memarg.clone())),
+        b"i64.atomic.rmw8.or_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Or, memarg.clone())),
+        b"i64.atomic.rmw16.or_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Or, memarg.clone())),
+        b"i64.atomic.rmw32.or_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Or, memarg.clone())),
+        b"i32.atomic.rmw.xor" => Some(Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Xor, memarg.clone())),
+        b"i64.atomic.rmw.xor" => Some(Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Xor, memarg.clone())),
+        b"i32.atomic.rmw8.xor_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Xor, memarg.clone())),
+        b"i32.atomic.rmw16.xor_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Xor, memarg.clone())),
+        b"i64.atomic.rmw8.xor_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Xor, memarg.clone())),
+        b"i64.atomic.rmw16.xor_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Xor, memarg.clone())),
+        b"i64.atomic.rmw32.xor_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Xor, memarg.clone())),
+        b"i32.atomic.rmw.xchg" => Some(Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Xchg, memarg.clone())),
+        b"i64.atomic.rmw.xchg" => Some(Instr::AtomicRmw(ValSize::V64, None, AtomicRmwOp::Xchg, memarg.clone())),
+        b"i32.atomic.rmw8.xchg_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P8), AtomicRmwOp::Xchg, memarg.clone())),
+        b"i32.atomic.rmw16.xchg_u" => Some(Instr::AtomicRmw(ValSize::V32, Some(PackSize::P16), AtomicRmwOp::Xchg, memarg.clone())),
+        b"i64.atomic.rmw8.xchg_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P8), AtomicRmwOp::Xchg, memarg.clone())),
+        b"i64.atomic.rmw16.xchg_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P16), AtomicRmwOp::Xchg, memarg.clone())),
+        b"i64.atomic.rmw32.xchg_u" => Some(Instr::AtomicRmw(ValSize::V64, Some(PackSize::P32), AtomicRmwOp::Xchg, memarg.clone())),
+        b"i32.atomic.rmw.cmpxchg" => Some(Instr::AtomicCmpxchg(ValSize::V32, None, memarg.clone())),
+        b"i64.atomic.rmw.cmpxchg" => Some(Instr::AtomicCmpxchg(ValSize::V64, None, memarg.clone())),
+        b"i32.atomic.rmw8.cmpxchg_u" => Some(Instr::AtomicCmpxchg(ValSize::V32, Some(PackSize::P8), memarg.clone())),
+        b"i32.atomic.rmw16.cmpxchg_u" => Some(Instr::AtomicCmpxchg(ValSize::V32, Some(PackSize::P16), memarg.clone())),
+        b"i64.atomic.rmw8.cmpxchg_u" => Some(Instr::AtomicCmpxchg(ValSize::V64, Some(PackSize::P8), memarg.clone())),
+        b"i64.atomic.rmw16.cmpxchg_u" => Some(Instr::AtomicCmpxchg(ValSize::V64, Some(PackSize::P16), memarg.clone())),
+        b"i64.atomic.rmw32.cmpxchg_u" => Some(Instr::AtomicCmpxchg(ValSize::V64, Some(PackSize::P32), memarg.clone())),
+
         _ => {
             let mut s_iter = s.split(|&b| b == b'.');
             let vt_b = s_iter.next().unwrap();
-            let instr = s_iter.next().unwrap();
+            let instr = s_iter.next()?;
 
             let vt = vec_to_valtype(vt_b).unwrap();
             let vs = vec_to_valsize(vt_b).unwrap();
@@ -148,6 +617,7 @@ fn vec_to_instr(s: &[u8]) -> Option<Instr> {
                         ValType::I64 => Some(Instr::I64Const(0)),
                         ValType::F32 => Some(Instr::F32Const(0.0)),
                         ValType::F64 => Some(Instr::F64Const(0.0)),
+                        _ => None,
                     }
                 }
                 b"clz" => Some(Instr::IUnOp(vs, IUnOp::Clz)),
@@ -161,7 +631,8 @@ fn vec_to_instr(s: &[u8]) -> Option<Instr> {
                         },
                         ValType::F32 | ValType::F64 => {
                             Some(Instr::FBinOp(vs, vec_to_fbinop(instr).unwrap()))
-                        },                        
+                        },
+                        _ => None,
                     }                    
                 },
 
@@ -195,7 +666,8 @@ fn vec_to_instr(s: &[u8]) -> Option<Instr> {
                         },
                         ValType::F32 | ValType::F64 => {
                             Some(Instr::FRelOp(vs, vec_to_frelop(instr).unwrap()))
-                        },                        
+                        },
+                        _ => None,
                     }                    
                 }
 
@@ -233,6 +705,19 @@ fn vec_to_valtype(s: &[u8]) -> Option<ValType> {
         b"i64" => Some(ValType::I64),
         b"f32" => Some(ValType::F32),
         b"f64" => Some(ValType::F64),
+        b"v128" => Some(ValType::V128),
+        _ => None,
+    }
+}
+
+fn vec_to_shape(s: &[u8]) -> Option<Shape> {
+    match s {
+        b"i8x16" => Some(Shape::I8x16),
+        b"i16x8" => Some(Shape::I16x8),
+        b"i32x4" => Some(Shape::I32x4),
+        b"i64x2" => Some(Shape::I64x2),
+        b"f32x4" => Some(Shape::F32x4),
+        b"f64x2" => Some(Shape::F64x2),
         _ => None,
     }
 }
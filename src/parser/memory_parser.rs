@@ -1,6 +1,8 @@
 use crate::{
     Mem,
     MemType,
+    Share,
+    IdxType,
 };
 
 use super::*;
@@ -23,10 +25,38 @@ impl<R> Parser<R> where R: Read + Seek {
         // mem id
         parse_optional_id!(self, self.contexts[0].mems);
 
-        let limits = self.parse_limits()?;
+        // index type(optional)
+        let idxtype = if let kw!(Keyword::ValType(ValType::I64)) = self.lookahead {
+            self.consume()?;
+            IdxType::I64
+        } else {
+            IdxType::I32
+        };
+
+        let mut limits = self.parse_limits()?;
+
+        // page size(optional), in bytes
+        if self.is_field(Keyword::PageSize)? {
+            self.match_lparen()?;
+            self.match_keyword(Keyword::PageSize)?;
+            let page_size = self.parse_num::<u64>()?;
+            if !page_size.is_power_of_two() {
+                return Err(self.err());
+            }
+            limits.page_size_log2 = Some(page_size.trailing_zeros());
+            self.match_rparen()?;
+        }
+
+        // shared(optional)
+        let share = if let kw!(Keyword::Shared) = self.lookahead {
+            self.consume()?;
+            Share::Shared
+        } else {
+            Share::Unshared
+        };
 
         self.match_rparen()?;
 
-        Ok(MemType(limits))
+        Ok(MemType(limits, share, idxtype))
     }
 }
\ No newline at end of file
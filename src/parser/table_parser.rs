@@ -1,7 +1,8 @@
 use crate::{
     TableType,
     Table,
-    ElemType,
+    RefType,
+    HeapType,
 };
 use super::*;
 
@@ -11,13 +12,19 @@ impl<R> Parser<R> where R: Read + Seek {
         // tabletype
         let table_type = self.parse_table_type()?;
 
-        self.module.tables.push(Table(table_type));
+        // elements are null without an initializer
+        let expr = self.parse_expr()?;
+        let init = if expr.0.is_empty() { None } else { Some(expr) };
+
+        self.module.tables.push(Table(table_type, init));
+
+        self.match_rparen()?;
 
         Ok(())
     }
 
     pub(super) fn parse_table_type(&mut self) -> Result<TableType, ParseError> {
-        let mut table_type = TableType(Limits::default(), ElemType::FuncRef);
+        let mut table_type = TableType(Limits::default(), RefType { nullable: true, heap: HeapType::Func });
         self.match_keyword(Keyword::Table)?;
 
         // table id
@@ -26,10 +33,9 @@ impl<R> Parser<R> where R: Read + Seek {
         // limits
         table_type.0 = self.parse_limits()?;
 
-        // 'funcref'
-        self.match_keyword(Keyword::FuncRef)?;
+        // reftype
+        table_type.1 = self.parse_reftype()?;
 
-        self.match_rparen()?;
         Ok(table_type)
     }
 }
\ No newline at end of file
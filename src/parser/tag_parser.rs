@@ -0,0 +1,36 @@
+use crate::{
+    Tag,
+    TypeIdx,
+    FuncType,
+};
+
+use super::*;
+
+impl<R> Parser<R> where R: Read + Seek {
+
+    pub(super) fn parse_tag(&mut self) -> Result<(), ParseError> {
+        // tagtype
+        let typeidx = self.parse_tag_type()?;
+
+        self.module.tags.push(Tag(typeidx));
+
+        Ok(())
+    }
+
+    pub(super) fn parse_tag_type(&mut self) -> Result<TypeIdx, ParseError> {
+        self.match_keyword(Keyword::Tag)?;
+
+        // tag id
+        parse_optional_id!(self, self.contexts[0].tags);
+
+        // typeuse
+        let mut _ft = FuncType::default();
+        let typeidx = self.parse_typeuse(&mut _ft.0, &mut _ft.1)?;
+
+        self.check_typeuse(typeidx, _ft)?;
+
+        self.match_rparen()?;
+
+        Ok(typeidx)
+    }
+}
@@ -1,11 +1,39 @@
 use crate::{
     FuncType,
+    SubType,
+    CompType,
+    FieldType,
+    StorageType,
+    Mut,
 };
 
 // use crate::instr::*;
 use super::*;
 
 impl<R> Parser<R> where R: Read + Seek {
+    pub(super) fn parse_rectype(&mut self) -> Result<(), ParseError> {
+        if let kw!(Keyword::Type) = &self.lookahead {
+            return self.parse_type();
+        }
+
+        self.match_keyword(Keyword::Rec)?;
+
+        // the types of a group know their position in it
+        let start = self.module.types.len();
+        while self.is_lparen()? {
+            self.match_lparen()?;
+            self.parse_type()?;
+        }
+        let len = (self.module.types.len() - start) as u32;
+        for (i, subtype) in self.module.types[start..].iter_mut().enumerate() {
+            subtype.rec = (i as u32, len);
+        }
+
+        self.match_rparen()?;
+
+        Ok(())
+    }
+
     pub(super) fn parse_type(&mut self) -> Result<(), ParseError> {
 
         self.match_keyword(Keyword::Type)?;
@@ -13,18 +41,115 @@ impl<R> Parser<R> where R: Read + Seek {
         // type id
         parse_optional_id!(self, self.contexts[0].types);
 
-        // functype
+        // subtype
         self.match_lparen()?;
-        let functype = self.parse_functype()?;
+        let mut fields = vec![];
+        let subtype = self.parse_subtype(&mut fields)?;
 
-        self.module.types.push(functype.clone());
-        self.contexts[0].typedefs.push(functype);
+        self.contexts[0].typedefs.push(subtype.functype().cloned().unwrap_or_default());
+        self.contexts[0].fields.push(fields);
+        self.module.types.push(subtype);
 
         self.match_rparen()?;
 
         Ok(())
     }
 
+    // types without 'sub' are final
+    fn parse_subtype(&mut self, fields: &mut Vec<Option<Id>>) -> Result<SubType, ParseError> {
+        if let kw!(Keyword::Sub) = &self.lookahead {
+            self.consume()?;
+        } else {
+            let comp = self.parse_comptype(fields)?;
+            return Ok(SubType { is_final: true, supers: vec![], comp, rec: (0, 1) });
+        }
+
+        let is_final = if let kw!(Keyword::Final) = &self.lookahead {
+            self.consume()?;
+            true
+        } else {
+            false
+        };
+
+        let mut supers = vec![];
+        while !self.is_lparen()? {
+            supers.push(self.resolve_id(&self.contexts[0].types.clone())?);
+        }
+
+        self.match_lparen()?;
+        let comp = self.parse_comptype(fields)?;
+
+        self.match_rparen()?;
+
+        Ok(SubType { is_final, supers, comp, rec: (0, 1) })
+    }
+
+    fn parse_comptype(&mut self, fields: &mut Vec<Option<Id>>) -> Result<CompType, ParseError> {
+        match &self.lookahead {
+            kw!(Keyword::Func) => Ok(CompType::Func(self.parse_functype()?)),
+            kw!(Keyword::Struct) => {
+                self.consume()?;
+
+                // '(' 'field' id fieldtype ')' or '(' 'field' fieldtype* ')'
+                let mut fieldtypes = vec![];
+                while self.is_lparen()? {
+                    self.match_lparen()?;
+                    self.match_keyword(Keyword::Field)?;
+                    if let tk!(TokenKind::Id(s)) = &self.lookahead {
+                        fields.push(Some(s.clone()));
+                        self.consume()?;
+                        fieldtypes.push(self.parse_fieldtype()?);
+                    } else {
+                        while !self.is_rparen()? {
+                            fields.push(None);
+                            fieldtypes.push(self.parse_fieldtype()?);
+                        }
+                    }
+                    self.match_rparen()?;
+                }
+
+                self.match_rparen()?;
+
+                Ok(CompType::Struct(fieldtypes))
+            },
+            kw!(Keyword::Array) => {
+                self.consume()?;
+                let fieldtype = self.parse_fieldtype()?;
+
+                self.match_rparen()?;
+
+                Ok(CompType::Array(fieldtype))
+            },
+            _ => Err(self.err()),
+        }
+    }
+
+    fn parse_fieldtype(&mut self) -> Result<FieldType, ParseError> {
+        if self.is_field(Keyword::Mutable)? {
+            self.match_lparen()?;
+            self.match_keyword(Keyword::Mutable)?;
+            let storagetype = self.parse_storagetype()?;
+            self.match_rparen()?;
+            Ok(FieldType(storagetype, Mut::Var))
+        } else {
+            Ok(FieldType(self.parse_storagetype()?, Mut::Const))
+        }
+    }
+
+    fn parse_storagetype(&mut self) -> Result<StorageType, ParseError> {
+        match &self.lookahead {
+            kw!(Keyword::I8) => {
+                self.consume()?;
+                Ok(StorageType::I8)
+            },
+            kw!(Keyword::I16) => {
+                self.consume()?;
+                Ok(StorageType::I16)
+            },
+            _ => Ok(StorageType::Val(self.parse_valtype()?)),
+        }
+    }
+
     fn parse_functype(&mut self) -> Result<FuncType, ParseError> {
         let mut functype = FuncType::default();
 
@@ -32,11 +157,11 @@ impl<R> Parser<R> where R: Read + Seek {
 
         if !self.is_rparen()? {
             self.parse_signature(&mut functype.0, &mut functype.1)?;
-        }        
+        }
 
         self.match_rparen()?;
 
         Ok(functype)
     }
 
-}
\ No newline at end of file
+}
@@ -61,17 +61,17 @@ macro_rules! parse_optional_label_id {
 }
 
 macro_rules! parse_field {
-    ($this:ident, $field_type:ident, $f:expr) => {
+    ($this:ident, $($field_type:ident)|+, $f:expr) => {
         if !$this.is_rparen()? {            
             if let tk!(TokenKind::LeftParen) = $this.lookahead {
                 $this.consume()?;
             }
             loop {
-                if let kw!(Keyword::$field_type) = &$this.lookahead {
+                if let $(kw!(Keyword::$field_type))|+ = &$this.lookahead {
                     { $f }
                     if let tk!(TokenKind::LeftParen) = $this.lookahead {
                         let peeked = $this.peek()?;
-                        if let kw!(Keyword::$field_type) = peeked {
+                        if let $(kw!(Keyword::$field_type))|+ = peeked {
                             $this.consume()?;
 
                             continue;
@@ -6,10 +6,16 @@ mod binop;
 mod testop;
 mod relop;
 mod cvtop;
+mod vector;
+mod atomic;
 mod parametric;
+mod reference;
 mod variable;
 mod memory;
+mod table;
 mod control;
+mod aggregate;
+mod heap;
 
 pub use operation32::*;
 pub use operation64::*;
@@ -22,14 +28,30 @@ pub use parametric::*;
 pub use variable::*;
 pub use memory::*;
 pub use control::*;
+pub use heap::*;
+pub(crate) use reference::{val_matches, func_matches};
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 
 use crate::{
+    Error,
     FuncType,
+    SubType,
     Byte,
     Name,
-    Mut,
+    GlobalType,
     Func,
     Instr,
+    Catch,
+    ValType,
+    RefType,
+    ElemType,
+    TypeIdx,
+    IdxType,
+    ResourceLimiter,
+    InterruptHandle,
 };
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -38,11 +60,103 @@ pub enum Val {
     I64Const(u64),
     F32Const(f32),
     F64Const(f64),
+    V128Const(u128),
+    Ref(Ref),
+}
+
+impl Val {
+    pub fn valtype(&self) -> ValType {
+        match self {
+            Val::I32Const(_) => ValType::I32,
+            Val::I64Const(_) => ValType::I64,
+            Val::F32Const(_) => ValType::F32,
+            Val::F64Const(_) => ValType::F64,
+            Val::V128Const(_) => ValType::V128,
+            Val::Ref(r) => r.elemtype().valtype(),
+        }
+    }
+
+    /// Initial value of a local or a field of type `valtype`.
+    pub fn default(valtype: &ValType, types: &[SubType]) -> Val {
+        match valtype {
+            ValType::I32 => Val::I32Const(0),
+            ValType::I64 => Val::I64Const(0),
+            ValType::F32 => Val::F32Const(0.0),
+            ValType::F64 => Val::F64Const(0.0),
+            ValType::V128 => Val::V128Const(0),
+            ValType::FuncRef => Val::Ref(Ref::Null(ElemType::FuncRef)),
+            ValType::ExternRef => Val::Ref(Ref::Null(ElemType::ExternRef)),
+            ValType::ExnRef => Val::Ref(Ref::Null(ElemType::ExnRef)),
+            // non-nullable locals are set before they are read
+            ValType::Ref(reftype) => Val::Ref(Ref::Null(reftype.heap.elemtype(types))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Ref {
+    Null(ElemType),
+    Func(FuncAddr),
+    /// Opaque value of the host, never inspected by the engine.
+    Extern(ExternAddr),
+    Exn(ExnAddr),
+    /// Unboxed 31-bit integer.
+    I31(u32),
+    Struct(HeapAddr),
+    Array(HeapAddr),
+    /// Host value converted by `any.convert_extern`.
+    AnyExtern(ExternAddr),
+    /// Reference converted by `extern.convert_any`.
+    ExternAny(AnyRef),
+}
+
+/// Reference of the any hierarchy which is not a converted host value.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AnyRef {
+    I31(u32),
+    Struct(HeapAddr),
+    Array(HeapAddr),
+}
+
+impl Ref {
+    pub fn elemtype(&self) -> ElemType {
+        match self {
+            Ref::Null(elemtype) => *elemtype,
+            Ref::Func(_) => ElemType::FuncRef,
+            Ref::Extern(_) | Ref::ExternAny(_) => ElemType::ExternRef,
+            Ref::Exn(_) => ElemType::ExnRef,
+            Ref::I31(_) | Ref::Struct(_) | Ref::Array(_) | Ref::AnyExtern(_) => ElemType::AnyRef,
+        }
+    }
+
+    /// The reference seen from the other hierarchy, for `any.convert_extern` and `extern.convert_any`.
+    pub fn convert(&self) -> Ref {
+        match *self {
+            Ref::Null(ElemType::ExternRef) => Ref::Null(ElemType::AnyRef),
+            Ref::Null(_) => Ref::Null(ElemType::ExternRef),
+            Ref::Extern(addr) => Ref::AnyExtern(addr),
+            Ref::ExternAny(AnyRef::I31(n)) => Ref::I31(n),
+            Ref::ExternAny(AnyRef::Struct(addr)) => Ref::Struct(addr),
+            Ref::ExternAny(AnyRef::Array(addr)) => Ref::Array(addr),
+            Ref::AnyExtern(addr) => Ref::Extern(addr),
+            Ref::I31(n) => Ref::ExternAny(AnyRef::I31(n)),
+            Ref::Struct(addr) => Ref::ExternAny(AnyRef::Struct(addr)),
+            Ref::Array(addr) => Ref::ExternAny(AnyRef::Array(addr)),
+            // validated to be of the any or extern hierarchy
+            Ref::Func(_) | Ref::Exn(_) => unreachable!(),
+        }
+    }
 }
 
 pub enum Result {
     Vals(Vec<Val>),
     Trap,
+    /// An exception was thrown and is looking for a handler.
+    Throw(ExnAddr),
+    Interrupted,
+    Suspended(SuspendReason),
+    /// The program exited with this status.
+    Exit(u32),
 }
 
 impl Result {
@@ -50,6 +164,17 @@ impl Result {
     pub fn i64val(n: u64) -> Self { Result::Vals(vec![Val::I64Const(n)]) }
     pub fn f32val(n: f32) -> Self { Result::Vals(vec![Val::F32Const(n)]) }
     pub fn f64val(n: f64) -> Self { Result::Vals(vec![Val::F64Const(n)]) }
+    pub fn v128val(n: u128) -> Self { Result::Vals(vec![Val::V128Const(n)]) }
+}
+
+/// Semantics of the relaxed vector instructions, whose results may depend on the host.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum RelaxedSimd {
+    /// What the host computes fastest, close to x86 SSE.
+    #[default]
+    Fast,
+    /// The same results on every host, those of the non-relaxed counterparts.
+    Deterministic,
 }
 
 #[derive(Default)]
@@ -58,6 +183,16 @@ pub struct Store {
     pub tables: Vec<TableInst>,
     pub mems: Vec<MemInst>,
     pub globals: Vec<GlobalInst>,
+    pub elems: Vec<ElemInst>,
+    pub datas: Vec<DataInst>,
+    pub tags: Vec<TagInst>,
+    pub exns: Vec<ExnInst>,
+    pub heap: Heap,
+    pub limiter: Option<Box<dyn ResourceLimiter + Send>>,
+    pub interrupt: InterruptHandle,
+    pub epoch_deadline: Option<u64>,
+    pub fuel: Option<u64>,
+    pub relaxed_simd: RelaxedSimd,
 }
 
 type Addr = usize;
@@ -65,14 +200,31 @@ pub type FuncAddr = Addr;
 pub type TableAddr = Addr;
 pub type MemAddr = Addr;
 pub type GlobalAddr = Addr;
+pub type ElemAddr = Addr;
+pub type DataAddr = Addr;
+pub type ExternAddr = Addr;
+pub type TagAddr = Addr;
+pub type ExnAddr = Addr;
+
+/// Address of a struct or an array. The slot of a freed object is reused with
+/// the next generation, which a reference to the freed one doesn't reach.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct HeapAddr {
+    pub index: usize,
+    pub generation: u32,
+}
 
 #[derive(Default, PartialEq, Clone, Debug)]
 pub struct ModuleInst {
-    pub types: Vec<FuncType>,
+    /// Shared with the objects allocated by the module, which keep their type.
+    pub types: Arc<Vec<SubType>>,
     pub funcaddrs: Vec<FuncAddr>,
     pub tableaddrs: Vec<TableAddr>,
     pub memaddrs: Vec<MemAddr>,
     pub globaladdrs: Vec<GlobalAddr>,
+    pub elemaddrs: Vec<ElemAddr>,
+    pub dataaddrs: Vec<DataAddr>,
+    pub tagaddrs: Vec<TagAddr>,
     pub exports: Vec<ExportInst>,
 }
 
@@ -84,9 +236,10 @@ pub enum FuncInst {
 
 impl FuncInst {
     pub fn user(tp: FuncType, module: ModuleInst, code: Func) -> FuncInst {
-        FuncInst::User(UserFuncInst {tp, module, code})
+        let body = code.body.0.clone().into();
+        FuncInst::User(UserFuncInst {tp, module, code, body})
     }
-    pub fn host(tp: FuncType, hostcode: fn()) -> FuncInst {
+    pub fn host(tp: FuncType, hostcode: HostCode) -> FuncInst {
         FuncInst::Host(HostFuncInst {tp, hostcode})
     }
 }
@@ -94,32 +247,138 @@ impl FuncInst {
 #[derive(Clone)]
 pub struct UserFuncInst {
     pub tp: FuncType,
-    module: ModuleInst,
-    code: Func,
+    pub(crate) module: ModuleInst,
+    pub(crate) code: Func,
+    /// Body of `code`, shared with the labels running it.
+    pub(crate) body: Arc<[Instr]>,
 }
 
 #[derive(Clone)]
 pub struct HostFuncInst {
     pub tp: FuncType,
-    pub hostcode: fn(),
+    pub hostcode: HostCode,
+}
+
+#[derive(Clone)]
+pub enum HostCode {
+    Sync(HostFunc),
+    Async(AsyncHostFunc),
+    /// Host function capturing its own state, like the lowered functions of a component.
+    Closure(HostClosure),
+}
+
+/// Host function called with its arguments, in order.
+pub type HostFunc = fn(&mut Store, &[Val]) -> HostResult;
+
+/// Shared host function, called like `HostFunc`.
+pub type HostClosure = Arc<dyn Fn(&mut Store, &[Val]) -> HostResult + Send + Sync>;
+
+/// Host function returning a future of its results.
+/// The future cannot borrow the store: read what it needs before returning it.
+pub type AsyncHostFunc = fn(&mut Store, &[Val]) -> HostFuture;
+
+pub type HostFuture = Pin<Box<dyn Future<Output = std::result::Result<Vec<Val>, Error>> + Send>>;
+
+pub enum HostResult {
+    Vals(Vec<Val>),
+    Trap,
+    /// Throw the exception in the calling thread.
+    Throw(ExnAddr),
+    /// Suspend the calling thread; its results are given on resumption.
+    Yield,
+    /// Stop the whole program with an exit status, as WASI `proc_exit`.
+    Exit(u32),
+}
+
+/// Outcome of a resumable invocation.
+pub enum Invocation {
+    Done(Vec<Val>),
+    Suspended(Suspension),
+}
+
+/// Execution state of a suspended thread, owned apart from the store.
+pub struct Suspension {
+    pub reason: SuspendReason,
+    /// Pending call of an async host function, whose output resumes the execution.
+    pub future: Option<HostFuture>,
+    pub(crate) stack: Vec<StackEntry>,
+    /// Counted by the heap of the store, which collects nothing while the stack is held here.
+    pub(crate) suspended: Arc<()>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SuspendReason {
+    /// A host function yielded when called with these arguments.
+    Host(FuncAddr, Vec<Val>),
+    /// The store ran out of fuel.
+    Fuel,
 }
 
 #[derive(Clone)]
 pub struct TableInst {
-    pub elem: Vec<FuncElem>,
+    pub elem: Vec<Ref>,
     pub max: Option<u32>,
+    pub tp: RefType,
+    /// Types of the allocating module, which `tp` refers to.
+    pub types: Arc<Vec<SubType>>,
 }
-type FuncElem = Option<FuncAddr>;
 
 #[derive(Clone)]
 pub struct MemInst {
+    pub data: MemData,
+    pub max: Option<u64>,
+    pub idxtype: IdxType,
+    pub page_size_log2: u32,
+}
+
+/// Bytes of a memory, behind a lock when shared with other stores.
+#[derive(Clone)]
+pub enum MemData {
+    Unshared(Vec<Byte>),
+    Shared(SharedMemory),
+}
+
+/// Element segment of an instance, emptied when dropped.
+#[derive(Clone)]
+pub struct ElemInst {
+    pub elem: Vec<Ref>,
+}
+
+/// Data segment of an instance, emptied when dropped.
+#[derive(Clone)]
+pub struct DataInst {
     pub data: Vec<Byte>,
-    pub max: Option<u32>,
 }
 
+/// Global of an instance, aliased by the instances importing it.
 pub struct GlobalInst {
     pub value: Val,
-    pub mutability: Mut, 
+    pub tp: GlobalType,
+    /// Types of the allocating module, which `tp` refers to.
+    pub types: Arc<Vec<SubType>>,
+}
+
+#[derive(Clone)]
+pub struct TagInst {
+    pub tp: FuncType,
+    /// Types of the allocating module, which `tp` refers to.
+    pub types: Arc<Vec<SubType>>,
+}
+
+/// Thrown exception, with the values given to its tag.
+#[derive(Clone)]
+pub struct ExnInst {
+    pub tag: TagAddr,
+    pub fields: Vec<Val>,
+}
+
+/// Struct, or array whose fields are its elements.
+#[derive(Clone)]
+pub struct HeapInst {
+    pub tp: TypeIdx,
+    /// Types of the allocating module, `tp` is one of them.
+    pub types: Arc<Vec<SubType>>,
+    pub fields: Vec<Val>,
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -134,12 +393,25 @@ pub enum ExternVal {
     Table(TableAddr),
     Mem(MemAddr),
     Global(GlobalAddr),
+    Tag(TagAddr),
 }
 
 pub enum StackEntry {
     Value(Val),
-    Label(u32, Vec<Instr>),
+    Label(Label),
     Activation(u32, Frame),
+    /// Catch clauses of a `try_table`, right under its label.
+    Handler(Vec<Catch>),
+}
+
+/// A label together with the instruction sequence it encloses.
+/// `pc` is the index of the next instruction to execute in `instrs`.
+#[derive(Clone, Debug)]
+pub struct Label {
+    pub arity: u32,
+    pub instrs: Arc<[Instr]>,
+    pub pc: usize,
+    pub is_loop: bool,
 }
 
 #[derive(Default, PartialEq, Clone, Debug)]
@@ -151,6 +423,7 @@ pub struct Frame {
 pub struct Thread<'a> {
     pub store: &'a mut Store,
     pub stack: Vec<StackEntry>,
+    pub pending: Option<HostFuture>,
 }
 
 impl<'a> Thread<'a> {
@@ -158,6 +431,7 @@ impl<'a> Thread<'a> {
         Thread {
             store: store,
             stack: vec![],
+            pending: None,
         }
     }
 }
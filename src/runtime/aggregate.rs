@@ -0,0 +1,547 @@
+use crate::{
+    TypeIdx,
+    FieldIdx,
+    DataIdx,
+    ElemIdx,
+    ValSign,
+    CompType,
+    FieldType,
+    StorageType,
+};
+
+use std::slice;
+
+use super::*;
+
+/// Longest array allocated by `array.new` and `array.new_default`, above it they trap.
+const MAX_ARRAY_LEN: usize = 1 << 26;
+
+fn fieldtypes(types: &[SubType], typeidx: &TypeIdx) -> Vec<FieldType> {
+    match &types[*typeidx as usize].comp {
+        CompType::Struct(fields) => fields.clone(),
+        CompType::Array(field) => vec![*field],
+        CompType::Func(_) => unreachable!(),
+    }
+}
+
+// packed fields keep only their low bits
+fn pack(storagetype: &StorageType, val: Val) -> Val {
+    match (storagetype, val) {
+        (StorageType::I8, Val::I32Const(n)) => Val::I32Const(n & 0xFF),
+        (StorageType::I16, Val::I32Const(n)) => Val::I32Const(n & 0xFFFF),
+        (_, val) => val,
+    }
+}
+
+fn unpack(storagetype: &StorageType, sign: &Option<ValSign>, val: Val) -> Val {
+    match (storagetype, sign, val) {
+        (StorageType::I8, Some(ValSign::S), Val::I32Const(n)) => Val::I32Const(n as u8 as i8 as i32 as u32),
+        (StorageType::I16, Some(ValSign::S), Val::I32Const(n)) => Val::I32Const(n as u16 as i16 as i32 as u32),
+        (_, _, val) => val,
+    }
+}
+
+fn storage_size(storagetype: &StorageType) -> usize {
+    match storagetype.unpacked() {
+        _ if *storagetype == StorageType::I8 => 1,
+        _ if *storagetype == StorageType::I16 => 2,
+        ValType::I32 | ValType::F32 => 4,
+        ValType::I64 | ValType::F64 => 8,
+        ValType::V128 => 16,
+        // validated to be no reference
+        _ => unreachable!(),
+    }
+}
+
+// an element of an array read from the little endian bytes of a data segment
+fn read_field(storagetype: &StorageType, bytes: &[Byte]) -> Val {
+    let mut buf = [0x00; 16];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    let c = u128::from_le_bytes(buf);
+    match storagetype.unpacked() {
+        ValType::I32 => Val::I32Const(c as u32),
+        ValType::I64 => Val::I64Const(c as u64),
+        ValType::F32 => Val::F32Const(f32::from_bits(c as u32)),
+        ValType::F64 => Val::F64Const(f64::from_bits(c as u64)),
+        ValType::V128 => Val::V128Const(c),
+        _ => unreachable!(),
+    }
+}
+
+impl<'a> Thread<'a> {
+    fn alloc_aggregate(&mut self, typeidx: &TypeIdx, fields: Vec<Val>) -> Option<HeapAddr> {
+        if !self.heap_reserve(fields.len(), &fields) {
+            return None;
+        }
+        let types = self.current_types();
+        Some(self.alloc_heap(HeapInst{ tp: *typeidx, types, fields }))
+    }
+
+    // the elements are only built once the limiter granted them
+    fn alloc_array(&mut self, typeidx: &TypeIdx, n: usize, val: Val) -> Option<HeapAddr> {
+        if n > MAX_ARRAY_LEN || !self.heap_reserve(n, slice::from_ref(&val)) {
+            return None;
+        }
+        let types = self.current_types();
+        Some(self.alloc_heap(HeapInst{ tp: *typeidx, types, fields: vec![val; n] }))
+    }
+
+    fn pop_vals(&mut self, n: usize) -> Vec<Val> {
+        let mut vals: Vec<Val> = (0..n).map(|_| self.pop_val()).collect();
+        vals.reverse();
+        vals
+    }
+
+    pub fn execute_structnew(&mut self, typeidx: &TypeIdx) -> Result {
+        let fieldtypes = fieldtypes(&self.current_types(), typeidx);
+        let vals = self.pop_vals(fieldtypes.len());
+        let fields = fieldtypes.iter().zip(vals).map(|(FieldType(st, _), val)| pack(st, val)).collect();
+        match self.alloc_aggregate(typeidx, fields) {
+            Some(addr) => Result::Vals(vec![Val::Ref(Ref::Struct(addr))]),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_structnewdefault(&mut self, typeidx: &TypeIdx) -> Result {
+        let types = self.current_types();
+        let fields = fieldtypes(&types, typeidx).iter().map(|FieldType(st, _)| Val::default(&st.unpacked(), &types)).collect();
+        match self.alloc_aggregate(typeidx, fields) {
+            Some(addr) => Result::Vals(vec![Val::Ref(Ref::Struct(addr))]),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_structget(&mut self, sign: &Option<ValSign>, typeidx: &TypeIdx, fieldidx: &FieldIdx) -> Result {
+        let FieldType(st, _) = &fieldtypes(&self.current_types(), typeidx)[*fieldidx as usize];
+        match self.pop_ref() {
+            Ref::Struct(addr) => {
+                let val = self.store.heap.get(addr).fields[*fieldidx as usize];
+                Result::Vals(vec![unpack(st, sign, val)])
+            },
+            _ => Result::Trap,
+        }
+    }
+
+    pub fn execute_structset(&mut self, typeidx: &TypeIdx, fieldidx: &FieldIdx) -> Result {
+        let FieldType(st, _) = &fieldtypes(&self.current_types(), typeidx)[*fieldidx as usize];
+        let val = self.pop_val();
+        match self.pop_ref() {
+            Ref::Struct(addr) => {
+                self.store.heap.get_mut(addr).fields[*fieldidx as usize] = pack(st, val);
+                Result::Vals(vec![])
+            },
+            _ => Result::Trap,
+        }
+    }
+
+    pub fn execute_arraynew(&mut self, typeidx: &TypeIdx) -> Result {
+        let FieldType(st, _) = &fieldtypes(&self.current_types(), typeidx)[0];
+        let n = self.pop_i32() as usize;
+        let val = pack(st, self.pop_val());
+        match self.alloc_array(typeidx, n, val) {
+            Some(addr) => Result::Vals(vec![Val::Ref(Ref::Array(addr))]),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_arraynewdefault(&mut self, typeidx: &TypeIdx) -> Result {
+        let types = self.current_types();
+        let FieldType(st, _) = &fieldtypes(&types, typeidx)[0];
+        let n = self.pop_i32() as usize;
+        match self.alloc_array(typeidx, n, Val::default(&st.unpacked(), &types)) {
+            Some(addr) => Result::Vals(vec![Val::Ref(Ref::Array(addr))]),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_arraynewfixed(&mut self, typeidx: &TypeIdx, n: &u32) -> Result {
+        let FieldType(st, _) = &fieldtypes(&self.current_types(), typeidx)[0];
+        let fields = self.pop_vals(*n as usize).into_iter().map(|val| pack(st, val)).collect();
+        match self.alloc_aggregate(typeidx, fields) {
+            Some(addr) => Result::Vals(vec![Val::Ref(Ref::Array(addr))]),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_arrayget(&mut self, sign: &Option<ValSign>, typeidx: &TypeIdx) -> Result {
+        let FieldType(st, _) = &fieldtypes(&self.current_types(), typeidx)[0];
+        let i = self.pop_i32() as usize;
+        match self.pop_ref() {
+            Ref::Array(addr) => match self.store.heap.get(addr).fields.get(i) {
+                Some(val) => Result::Vals(vec![unpack(st, sign, *val)]),
+                None => Result::Trap,
+            },
+            _ => Result::Trap,
+        }
+    }
+
+    pub fn execute_arrayset(&mut self, typeidx: &TypeIdx) -> Result {
+        let FieldType(st, _) = &fieldtypes(&self.current_types(), typeidx)[0];
+        let val = self.pop_val();
+        let i = self.pop_i32() as usize;
+        match self.pop_ref() {
+            Ref::Array(addr) => match self.store.heap.get_mut(addr).fields.get_mut(i) {
+                Some(field) => {
+                    *field = pack(st, val);
+                    Result::Vals(vec![])
+                },
+                None => Result::Trap,
+            },
+            _ => Result::Trap,
+        }
+    }
+
+    pub fn execute_arraylen(&mut self) -> Result {
+        match self.pop_ref() {
+            Ref::Array(addr) => Result::i32val(self.store.heap.get(addr).fields.len() as u32),
+            _ => Result::Trap,
+        }
+    }
+
+    pub fn execute_arrayfill(&mut self, typeidx: &TypeIdx) -> Result {
+        let FieldType(st, _) = &fieldtypes(&self.current_types(), typeidx)[0];
+        let n = self.pop_i32() as usize;
+        let val = pack(st, self.pop_val());
+        let d = self.pop_i32() as usize;
+        match self.pop_ref() {
+            Ref::Array(addr) => {
+                let fields = &mut self.store.heap.get_mut(addr).fields;
+                if d + n > fields.len() {
+                    return Result::Trap;
+                }
+                fields[d..d + n].fill(val);
+                Result::Vals(vec![])
+            },
+            _ => Result::Trap,
+        }
+    }
+
+    // `n` elements from `s` in a data segment, None when they are out of it
+    fn read_data(&self, dataidx: &DataIdx, storagetype: &StorageType, s: usize, n: usize) -> Option<Vec<Val>> {
+        let (_, frame) = self.current_frame();
+        let data = &self.store.datas[frame.module.dataaddrs[*dataidx as usize]].data;
+        let size = storage_size(storagetype);
+        let bytes = data.get(s..s.checked_add(n.checked_mul(size)?)?)?;
+        Some(bytes.chunks(size).map(|bytes| read_field(storagetype, bytes)).collect())
+    }
+
+    // `n` references from `s` in an element segment, None when they are out of it
+    fn read_elem(&self, elemidx: &ElemIdx, s: usize, n: usize) -> Option<Vec<Val>> {
+        let (_, frame) = self.current_frame();
+        let elem = &self.store.elems[frame.module.elemaddrs[*elemidx as usize]].elem;
+        Some(elem.get(s..s.checked_add(n)?)?.iter().map(|r| Val::Ref(*r)).collect())
+    }
+
+    pub fn execute_arraynewdata(&mut self, typeidx: &TypeIdx, dataidx: &DataIdx) -> Result {
+        let FieldType(st, _) = fieldtypes(&self.current_types(), typeidx)[0];
+        let n = self.pop_i32() as usize;
+        let s = self.pop_i32() as usize;
+        match self.read_data(dataidx, &st, s, n) {
+            Some(fields) if n <= MAX_ARRAY_LEN => match self.alloc_aggregate(typeidx, fields) {
+                Some(addr) => Result::Vals(vec![Val::Ref(Ref::Array(addr))]),
+                None => Result::Trap,
+            },
+            _ => Result::Trap,
+        }
+    }
+
+    pub fn execute_arraynewelem(&mut self, typeidx: &TypeIdx, elemidx: &ElemIdx) -> Result {
+        let n = self.pop_i32() as usize;
+        let s = self.pop_i32() as usize;
+        match self.read_elem(elemidx, s, n) {
+            Some(fields) if n <= MAX_ARRAY_LEN => match self.alloc_aggregate(typeidx, fields) {
+                Some(addr) => Result::Vals(vec![Val::Ref(Ref::Array(addr))]),
+                None => Result::Trap,
+            },
+            _ => Result::Trap,
+        }
+    }
+
+    pub fn execute_arrayinitdata(&mut self, typeidx: &TypeIdx, dataidx: &DataIdx) -> Result {
+        let FieldType(st, _) = fieldtypes(&self.current_types(), typeidx)[0];
+        let n = self.pop_i32() as usize;
+        let s = self.pop_i32() as usize;
+        let d = self.pop_i32() as usize;
+        let Ref::Array(addr) = self.pop_ref() else {
+            return Result::Trap;
+        };
+        if d + n > self.store.heap.get(addr).fields.len() {
+            return Result::Trap;
+        }
+        match self.read_data(dataidx, &st, s, n) {
+            Some(vals) => {
+                self.store.heap.get_mut(addr).fields[d..d + n].copy_from_slice(&vals);
+                Result::Vals(vec![])
+            },
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_arrayinitelem(&mut self, elemidx: &ElemIdx) -> Result {
+        let n = self.pop_i32() as usize;
+        let s = self.pop_i32() as usize;
+        let d = self.pop_i32() as usize;
+        let Ref::Array(addr) = self.pop_ref() else {
+            return Result::Trap;
+        };
+        if d + n > self.store.heap.get(addr).fields.len() {
+            return Result::Trap;
+        }
+        match self.read_elem(elemidx, s, n) {
+            Some(vals) => {
+                self.store.heap.get_mut(addr).fields[d..d + n].copy_from_slice(&vals);
+                Result::Vals(vec![])
+            },
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_arraycopy(&mut self) -> Result {
+        let n = self.pop_i32() as usize;
+        let s = self.pop_i32() as usize;
+        let src = self.pop_ref();
+        let d = self.pop_i32() as usize;
+        let dst = self.pop_ref();
+        let (Ref::Array(addr1), Ref::Array(addr2)) = (dst, src) else {
+            return Result::Trap;
+        };
+
+        if s + n > self.store.heap.get(addr2).fields.len() || d + n > self.store.heap.get(addr1).fields.len() {
+            return Result::Trap;
+        }
+        if addr1 == addr2 {
+            self.store.heap.get_mut(addr1).fields.copy_within(s..s + n, d);
+        } else {
+            let vals = self.store.heap.get(addr2).fields[s..s + n].to_vec();
+            self.store.heap.get_mut(addr1).fields[d..d + n].copy_from_slice(&vals);
+        }
+        Result::Vals(vec![])
+    }
+}
+
+#[test]
+fn test_gc() {
+    use crate::{
+        store_init, store_collect, func_invoke, module_validate, module_instanciate,
+        Module, Func, Global, GlobalType, Expr, Instr, BlockType, IBinOp, IRelOp, ValSize, RefType, HeapType, Mut, Error,
+    };
+
+    let rt = |nullable, heap| RefType { nullable, heap };
+    let reftype = |nullable, typeidx| rt(nullable, HeapType::Type(typeidx)).valtype();
+    let field = |st, mutability| FieldType(st, mutability);
+    let func = |tp, locals, body| Func { tp, locals, body: Expr(body) };
+    let module = |types: Vec<SubType>, funcs: Vec<Func>| Module {
+        types,
+        funcs,
+        globals: vec![Global { tp: GlobalType(reftype(true, 1), Mut::Var), init: Expr(vec![Instr::StructNewDefault(1)]) }],
+        ..Module::default()
+    };
+    let types = |is_final| vec![
+        (vec![], vec![ValType::I32]).into(),
+        // (sub (struct (field (mut i32)) (field (mut i8))))
+        SubType { is_final, supers: vec![], rec: (0, 1), comp: CompType::Struct(vec![
+            field(StorageType::Val(ValType::I32), Mut::Var), field(StorageType::I8, Mut::Var),
+        ]) },
+        // (sub 1 (struct (field (mut i32)) (field (mut i8)) (field i64)))
+        SubType { is_final: true, supers: vec![1], rec: (0, 1), comp: CompType::Struct(vec![
+            field(StorageType::Val(ValType::I32), Mut::Var), field(StorageType::I8, Mut::Var), field(StorageType::Val(ValType::I64), Mut::Const),
+        ]) },
+        // (array (mut i16))
+        SubType { is_final: true, supers: vec![], rec: (0, 1), comp: CompType::Array(field(StorageType::I16, Mut::Var)) },
+        (vec![], vec![]).into(),
+    ];
+    let add = Instr::IBinOp(ValSize::V32, IBinOp::Add);
+    let funcs = vec![
+        func(0, vec![], vec![Instr::I32Const(5), Instr::I32Const(0x1FF), Instr::StructNew(1), Instr::StructGet(Some(ValSign::S), 1, 1)]),
+        func(0, vec![reftype(true, 1)], vec![
+            Instr::StructNewDefault(2), Instr::LocalSet(0),
+            Instr::LocalGet(0), Instr::I32Const(7), Instr::StructSet(1, 0),
+            Instr::LocalGet(0), Instr::StructGet(None, 1, 0),
+        ]),
+        func(0, vec![], vec![Instr::StructNewDefault(1), Instr::RefTest(rt(false, HeapType::Type(2)))]),
+        func(0, vec![], vec![Instr::StructNewDefault(2), Instr::RefCast(rt(false, HeapType::Type(1))), Instr::RefTest(rt(false, HeapType::Eq))]),
+        func(0, vec![], vec![Instr::StructNewDefault(1), Instr::RefCast(rt(false, HeapType::Type(2))), Instr::Drop(None), Instr::I32Const(0)]),
+        func(0, vec![reftype(true, 3)], vec![
+            Instr::I32Const(0x12345), Instr::I32Const(3), Instr::ArrayNew(3), Instr::LocalSet(0),
+            Instr::LocalGet(0), Instr::I32Const(1), Instr::ArrayGet(Some(ValSign::U), 3),
+            Instr::LocalGet(0), Instr::ArrayLen, add.clone(),
+        ]),
+        // [1, 2, 3, 4] becomes [1, 1, 2, 3]
+        func(0, vec![reftype(true, 3)], vec![
+            Instr::I32Const(1), Instr::I32Const(2), Instr::I32Const(3), Instr::I32Const(4), Instr::ArrayNewFixed(3, 4), Instr::LocalSet(0),
+            Instr::LocalGet(0), Instr::I32Const(1), Instr::LocalGet(0), Instr::I32Const(0), Instr::I32Const(3), Instr::ArrayCopy(3, 3),
+            Instr::LocalGet(0), Instr::I32Const(3), Instr::ArrayGet(Some(ValSign::S), 3),
+        ]),
+        func(0, vec![], vec![
+            Instr::I32Const(2), Instr::ArrayNewDefault(3), Instr::I32Const(1), Instr::I32Const(9), Instr::I32Const(2), Instr::ArrayFill(3),
+            Instr::I32Const(0),
+        ]),
+        func(0, vec![], vec![Instr::I32Const(u32::MAX), Instr::RefI31, Instr::I31Get(ValSign::U)]),
+        // block (result (ref 2)) struct.new_default 2 br_on_cast 0 (ref null 1) (ref 2) drop i32.const 0 return end drop i32.const 1
+        func(0, vec![], vec![
+            Instr::Block(BlockType::ValType(Some(reftype(false, 2))), vec![
+                Instr::StructNewDefault(2), Instr::BrOnCast(0, rt(true, HeapType::Type(1)), rt(false, HeapType::Type(2))),
+                Instr::Drop(None), Instr::I32Const(0), Instr::Return,
+            ].into()),
+            Instr::Drop(None), Instr::I32Const(1),
+        ]),
+        func(0, vec![reftype(true, 1)], vec![Instr::StructNewDefault(1), Instr::LocalTee(0), Instr::LocalGet(0), Instr::RefEq]),
+        // garbage only
+        func(4, vec![ValType::I32], vec![
+            Instr::Loop(BlockType::ValType(None), vec![
+                Instr::StructNewDefault(1), Instr::Drop(None),
+                Instr::LocalGet(0), Instr::I32Const(1), add.clone(), Instr::LocalTee(0),
+                Instr::I32Const(5000), Instr::IRelOp(ValSize::V32, IRelOp::Lt(ValSign::U)), Instr::BrIf(0),
+            ].into()),
+        ]),
+    ];
+
+    let mut store = store_init();
+    let moduleinst = module_instanciate(&mut store, module(types(false), funcs), vec![]).unwrap();
+    let invoke = |store: &mut Store, funcidx: usize| func_invoke(store, moduleinst.funcaddrs[funcidx], vec![]).1;
+    assert_eq!(invoke(&mut store, 0).unwrap(), vec![Val::I32Const(u32::MAX)]);
+    assert_eq!(invoke(&mut store, 1).unwrap(), vec![Val::I32Const(7)]);
+    assert_eq!(invoke(&mut store, 2).unwrap(), vec![Val::I32Const(0)]);
+    assert_eq!(invoke(&mut store, 3).unwrap(), vec![Val::I32Const(1)]);
+    assert!(matches!(invoke(&mut store, 4), Err(Error::Trap)));
+    assert_eq!(invoke(&mut store, 5).unwrap(), vec![Val::I32Const(0x2348)]);
+    assert_eq!(invoke(&mut store, 6).unwrap(), vec![Val::I32Const(3)]);
+    assert!(matches!(invoke(&mut store, 7), Err(Error::Trap)));
+    assert_eq!(invoke(&mut store, 8).unwrap(), vec![Val::I32Const(0x7FFF_FFFF)]);
+    assert_eq!(invoke(&mut store, 9).unwrap(), vec![Val::I32Const(1)]);
+    assert_eq!(invoke(&mut store, 10).unwrap(), vec![Val::I32Const(1)]);
+
+    // the loop collects as it allocates and reuses the slots, the global keeps its struct
+    invoke(&mut store, 11).unwrap();
+    assert!(store.heap.len() < 5000);
+    assert!(store.heap.objs.len() < 5000);
+    assert_eq!(store_collect(&mut store), 1);
+
+    // a final type has no subtypes
+    assert!(module_validate(module(types(true), vec![])).is_err());
+}
+
+#[test]
+fn test_gc_suspended_and_host_refs() {
+    use crate::{
+        store_init, store_collect, func_alloc, func_invoke, func_invoke_resumable, module_instanciate,
+        Module, Func, Import, ImportDesc, ExternVal, Expr, Instr, Invocation, HostResult, RefType, HeapType, Mut, Error,
+    };
+
+    fn yielding(_store: &mut Store, _args: &[Val]) -> HostResult {
+        HostResult::Yield
+    }
+
+    let structref = RefType { nullable: true, heap: HeapType::Type(0) }.valtype();
+    let func = |tp, body| Func { tp, locals: vec![], body: Expr(body) };
+    let module = Module {
+        types: vec![
+            SubType { is_final: true, supers: vec![], rec: (0, 1), comp: CompType::Struct(vec![FieldType(StorageType::Val(ValType::I32), Mut::Const)]) },
+            (vec![], vec![structref]).into(),
+            (vec![structref], vec![ValType::I32]).into(),
+            (vec![], vec![]).into(),
+        ],
+        imports: vec![Import { module: "host".to_string(), name: "yield".to_string(), desc: ImportDesc::Func(3) }],
+        funcs: vec![
+            func(1, vec![Instr::I32Const(7), Instr::StructNew(0)]),
+            func(2, vec![Instr::LocalGet(0), Instr::StructGet(None, 0, 0)]),
+            // the struct stays on the stack of the suspended thread
+            func(1, vec![Instr::StructNewDefault(0), Instr::Call(0)]),
+        ],
+        ..Module::default()
+    };
+
+    let mut store = store_init();
+    let hostaddr = func_alloc(&mut store, (vec![], vec![]), yielding).unwrap();
+    let moduleinst = module_instanciate(&mut store, module, vec![ExternVal::Func(hostaddr)]).unwrap();
+    let funcaddr = |funcidx: usize| moduleinst.funcaddrs[funcidx];
+
+    let suspension = match func_invoke_resumable(&mut store, funcaddr(3), vec![]) {
+        Ok(Invocation::Suspended(suspension)) => suspension,
+        _ => panic!("expected a suspension"),
+    };
+    assert_eq!(store_collect(&mut store), 1);
+    drop(suspension);
+    assert_eq!(store_collect(&mut store), 0);
+    let (store, result) = func_invoke(&mut store, funcaddr(3), vec![]);
+    assert!(matches!(result, Err(Error::Suspended)));
+    assert_eq!(store_collect(store), 0);
+
+    // a reference kept by the host is freed, its slot goes to the next object under a new generation
+    let (store, result) = func_invoke(store, funcaddr(1), vec![]);
+    let kept = result.unwrap()[0];
+    assert_eq!(func_invoke(store, funcaddr(2), vec![kept]).1.unwrap(), vec![Val::I32Const(7)]);
+    assert_eq!(store_collect(store), 0);
+    let (store, result) = func_invoke(store, funcaddr(1), vec![]);
+    assert_ne!(result.unwrap()[0], kept);
+    assert!(matches!(func_invoke(store, funcaddr(2), vec![kept]).1, Err(Error::Invalid)));
+}
+
+#[test]
+fn test_array_segments_and_conversions() {
+    use crate::{
+        store_init, func_invoke, module_decode, module_instanciate, encoder::module2wasm,
+        Module, Func, Data, DataMode, Elem, ElemMode, Expr, Instr, RefType, HeapType, Mut, Error,
+    };
+
+    let i31 = RefType { nullable: false, heap: HeapType::I31 };
+    let func = |tp, locals, body| Func { tp, locals, body: Expr(body) };
+    let local = |typeidx| RefType { nullable: true, heap: HeapType::Type(typeidx) }.valtype();
+    let module = Module {
+        types: vec![
+            (vec![], vec![ValType::I32]).into(),
+            // (array (mut i16))
+            SubType { is_final: true, supers: vec![], rec: (0, 1), comp: CompType::Array(FieldType(StorageType::I16, Mut::Var)) },
+            // (array (mut anyref))
+            SubType { is_final: true, supers: vec![], rec: (0, 1), comp: CompType::Array(FieldType(StorageType::Val(ElemType::AnyRef.valtype()), Mut::Var)) },
+            (vec![ValType::ExternRef], vec![ValType::ExternRef]).into(),
+        ],
+        funcs: vec![
+            func(0, vec![], vec![
+                Instr::I32Const(0), Instr::I32Const(2), Instr::ArrayNewData(1, 0),
+                Instr::I32Const(1), Instr::ArrayGet(Some(ValSign::S), 1),
+            ]),
+            func(0, vec![], vec![Instr::I32Const(3), Instr::I32Const(1), Instr::ArrayNewData(1, 0), Instr::Drop(None), Instr::I32Const(0)]),
+            func(0, vec![], vec![
+                Instr::I32Const(0), Instr::I32Const(2), Instr::ArrayNewElem(2, 0),
+                Instr::I32Const(1), Instr::ArrayGet(None, 2), Instr::RefCast(i31), Instr::I31Get(ValSign::U),
+            ]),
+            func(0, vec![local(1)], vec![
+                Instr::I32Const(3), Instr::ArrayNewDefault(1), Instr::LocalSet(0),
+                Instr::LocalGet(0), Instr::I32Const(1), Instr::I32Const(0), Instr::I32Const(2), Instr::ArrayInitData(1, 0),
+                Instr::LocalGet(0), Instr::I32Const(2), Instr::ArrayGet(Some(ValSign::U), 1),
+            ]),
+            func(0, vec![local(2)], vec![
+                Instr::I32Const(2), Instr::ArrayNewDefault(2), Instr::LocalSet(0),
+                Instr::LocalGet(0), Instr::I32Const(0), Instr::I32Const(1), Instr::I32Const(1), Instr::ArrayInitElem(2, 0),
+                Instr::LocalGet(0), Instr::I32Const(0), Instr::ArrayGet(None, 2), Instr::RefCast(i31), Instr::I31Get(ValSign::U),
+            ]),
+            func(0, vec![], vec![
+                Instr::I32Const(5), Instr::RefI31, Instr::ExternConvertAny, Instr::AnyConvertExtern,
+                Instr::RefCast(i31), Instr::I31Get(ValSign::U),
+            ]),
+            func(3, vec![], vec![Instr::LocalGet(0), Instr::AnyConvertExtern, Instr::ExternConvertAny]),
+        ],
+        elem: vec![Elem {
+            tp: RefType { nullable: true, heap: HeapType::Any },
+            init: vec![Expr(vec![Instr::I32Const(10), Instr::RefI31]), Expr(vec![Instr::I32Const(20), Instr::RefI31])],
+            mode: ElemMode::Passive,
+        }],
+        data: vec![Data { init: vec![0x34, 0x12, 0x00, 0x80], mode: DataMode::Passive }],
+        datacount: Some(1),
+        ..Module::default()
+    };
+
+    // through the binary format
+    let module = module_decode(&mut module2wasm(&module).as_slice()).unwrap();
+    let mut store = store_init();
+    let moduleinst = module_instanciate(&mut store, module, vec![]).unwrap();
+    let invoke = |store: &mut Store, funcidx: usize, args: Vec<Val>| func_invoke(store, moduleinst.funcaddrs[funcidx], args).1;
+    assert_eq!(invoke(&mut store, 0, vec![]).unwrap(), vec![Val::I32Const(0xffff8000)]);
+    assert!(matches!(invoke(&mut store, 1, vec![]), Err(Error::Trap)));
+    assert_eq!(invoke(&mut store, 2, vec![]).unwrap(), vec![Val::I32Const(20)]);
+    assert_eq!(invoke(&mut store, 3, vec![]).unwrap(), vec![Val::I32Const(0x8000)]);
+    assert_eq!(invoke(&mut store, 4, vec![]).unwrap(), vec![Val::I32Const(20)]);
+    assert_eq!(invoke(&mut store, 5, vec![]).unwrap(), vec![Val::I32Const(5)]);
+    assert_eq!(invoke(&mut store, 6, vec![Val::Ref(Ref::Extern(3))]).unwrap(), vec![Val::Ref(Ref::Extern(3))]);
+}
@@ -0,0 +1,264 @@
+use std::sync::atomic::{fence, Ordering};
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use crate::{
+    MemArg,
+    ValSize,
+    PackSize,
+    AtomicRmwOp,
+
+    atomic_width,
+};
+
+use super::*;
+
+fn from_le(bytes: &[Byte]) -> u64 {
+    let mut buf = [0x00; 8];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
+fn atomic_val(valsize: &ValSize, c: u64) -> Result {
+    match valsize {
+        ValSize::V32 => Result::i32val(c as u32),
+        ValSize::V64 => Result::i64val(c),
+    }
+}
+
+impl<'a> Thread<'a> {
+    fn pop_atomic_operand(&mut self, valsize: &ValSize) -> u64 {
+        match valsize {
+            ValSize::V32 => self.pop_i32() as u64,
+            ValSize::V64 => self.pop_i64(),
+        }
+    }
+
+    // effective address of an access of `n` bytes, None when misaligned or out of bounds
+    fn atomic_ea(&mut self, memarg: &MemArg, n: usize) -> Option<(MemAddr, usize)> {
+        let (_, frame) = self.current_frame();
+        let memaddr = frame.module.memaddrs[memarg.memory as usize];
+        let ea = self.pop_ea(memarg)?;
+        if !ea.is_multiple_of(n) || ea.checked_add(n)? > self.store.mems[memaddr].size() {
+            return None;
+        }
+        Some((memaddr, ea))
+    }
+
+    // replace the `n` bytes at `ea` with `f` of them in one step, returning the old value
+    fn atomic_modify(&mut self, memaddr: MemAddr, ea: usize, n: usize, f: impl FnOnce(u64) -> u64) -> u64 {
+        self.store.mems[memaddr].with_data_mut(|data| {
+            let old = from_le(&data[ea..ea + n]);
+            data[ea..ea + n].copy_from_slice(&f(old).to_le_bytes()[..n]);
+            old
+        })
+    }
+
+    pub fn execute_atomic_load(&mut self, valsize: &ValSize, packsize: &Option<PackSize>, memarg: &MemArg) -> Result {
+        let n = atomic_width(valsize, packsize) as usize / 8;
+        match self.atomic_ea(memarg, n) {
+            Some((memaddr, ea)) => {
+                let c = self.store.mems[memaddr].with_data(|data| from_le(&data[ea..ea + n]));
+                atomic_val(valsize, c)
+            },
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_atomic_store(&mut self, valsize: &ValSize, packsize: &Option<PackSize>, memarg: &MemArg) -> Result {
+        let n = atomic_width(valsize, packsize) as usize / 8;
+        let c = self.pop_atomic_operand(valsize);
+        match self.atomic_ea(memarg, n) {
+            Some((memaddr, ea)) => {
+                self.atomic_modify(memaddr, ea, n, |_| c);
+                Result::Vals(vec![])
+            },
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_atomic_rmw(&mut self, valsize: &ValSize, packsize: &Option<PackSize>, op: &AtomicRmwOp, memarg: &MemArg) -> Result {
+        let n = atomic_width(valsize, packsize) as usize / 8;
+        let c = self.pop_atomic_operand(valsize);
+        match self.atomic_ea(memarg, n) {
+            Some((memaddr, ea)) => {
+                let old = self.atomic_modify(memaddr, ea, n, |old| match op {
+                    AtomicRmwOp::Add => old.wrapping_add(c),
+                    AtomicRmwOp::Sub => old.wrapping_sub(c),
+                    AtomicRmwOp::And => old & c,
+                    AtomicRmwOp::Or => old | c,
+                    AtomicRmwOp::Xor => old ^ c,
+                    AtomicRmwOp::Xchg => c,
+                });
+                atomic_val(valsize, old)
+            },
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_atomic_cmpxchg(&mut self, valsize: &ValSize, packsize: &Option<PackSize>, memarg: &MemArg) -> Result {
+        let n = atomic_width(valsize, packsize) as usize / 8;
+        let replacement = self.pop_atomic_operand(valsize);
+        // the expected value is wrapped to the access width
+        let expected = from_le(&self.pop_atomic_operand(valsize).to_le_bytes()[..n]);
+        match self.atomic_ea(memarg, n) {
+            Some((memaddr, ea)) => {
+                let old = self.atomic_modify(memaddr, ea, n, |old| if old == expected { replacement } else { old });
+                atomic_val(valsize, old)
+            },
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_atomic_wait(&mut self, valsize: &ValSize, memarg: &MemArg) -> Result {
+        let n = atomic_width(valsize, &None) as usize / 8;
+        // a negative timeout waits forever
+        let timeout = u64::try_from(self.pop_i64() as i64).ok().map(Duration::from_nanos);
+        let expected = self.pop_atomic_operand(valsize);
+        let (memaddr, ea) = match self.atomic_ea(memarg, n) {
+            Some(ea) => ea,
+            None => return Result::Trap,
+        };
+        let store = &*self.store;
+        let mut interrupted = false;
+        match &store.mems[memaddr].data {
+            MemData::Shared(shared) => match shared.wait(ea, &expected.to_le_bytes()[..n], timeout, || {
+                interrupted = store.is_interrupted();
+                interrupted
+            }) {
+                Some(c) => Result::i32val(c),
+                None if interrupted => Result::Interrupted,
+                None => Result::Trap,
+            },
+            // no other thread could notify
+            MemData::Unshared(_) => Result::Trap,
+        }
+    }
+
+    pub fn execute_atomic_notify(&mut self, memarg: &MemArg) -> Result {
+        let count = self.pop_i32();
+        let (memaddr, ea) = match self.atomic_ea(memarg, 4) {
+            Some(ea) => ea,
+            None => return Result::Trap,
+        };
+        match &self.store.mems[memaddr].data {
+            MemData::Shared(shared) => Result::i32val(shared.notify(ea, count)),
+            MemData::Unshared(_) => Result::i32val(0),
+        }
+    }
+
+    pub fn execute_atomic_fence(&mut self) -> Result {
+        // shared memories are only accessed under their lock, which already orders the accesses
+        fence(Ordering::SeqCst);
+        Result::Vals(vec![])
+    }
+}
+
+#[test]
+fn test_shared_memory() {
+    use crate::{
+        store_init, module_validate, mem_alloc, mem_shared, mem_import_shared,
+        Module, MemType, Limits, Share, IdxType, Import, ImportDesc, Instr, ValType, Error,
+    };
+    use crate::testing::{exported_funcs, TestInstance};
+
+    let memtype = MemType(Limits { min: 1, max: Some(1), page_size_log2: None }, Share::Shared, IdxType::I32);
+    let memarg = |align| MemArg { align, offset: 0, memory: 0 };
+    let module = || Module {
+        imports: vec![Import { module: "env".to_string(), name: "mem".to_string(), desc: ImportDesc::Mem(memtype.clone()) }],
+        ..exported_funcs(vec![(vec![], vec![ValType::I32])], vec![
+            ("add", 0, vec![Instr::I32Const(0), Instr::I32Const(1), Instr::AtomicRmw(ValSize::V32, None, AtomicRmwOp::Add, memarg(2))]),
+            ("wait", 0, vec![Instr::I32Const(8), Instr::I32Const(0), Instr::I64Const(-1i64 as u64), Instr::AtomicWait(ValSize::V32, memarg(2))]),
+            ("notify", 0, vec![Instr::I32Const(8), Instr::I32Const(1), Instr::AtomicNotify(memarg(2))]),
+            ("cmpxchg", 0, vec![
+                Instr::I32Const(4), Instr::I32Const(0x1FF), Instr::I32Const(0x1AB),
+                Instr::AtomicCmpxchg(ValSize::V32, Some(PackSize::P8), memarg(0)),
+                Instr::I32Const(0), Instr::AtomicLoad(ValSize::V32, None, memarg(2)),
+                Instr::IBinOp(ValSize::V32, crate::IBinOp::Add),
+            ]),
+            ("timeout", 0, vec![Instr::I32Const(8), Instr::I32Const(0), Instr::I64Const(1000), Instr::AtomicWait(ValSize::V32, memarg(2))]),
+            ("misaligned", 0, vec![Instr::I32Const(2), Instr::AtomicLoad(ValSize::V32, None, memarg(2))]),
+        ])
+    };
+
+    let mut store = store_init();
+    let memaddr = mem_alloc(&mut store, memtype.clone()).unwrap();
+    let shared = mem_shared(&store, memaddr).unwrap();
+
+    // every host thread runs its own store against the same memory
+    let run = |shared: SharedMemory, names: &[&str]| {
+        let mut store = store_init();
+        let memaddr = mem_import_shared(&mut store, shared);
+        let instance = TestInstance::new(&mut store, module(), vec![ExternVal::Mem(memaddr)]);
+        names.iter().map(|name| instance.call(&mut store, name, vec![])).collect::<Vec<_>>()
+    };
+
+    std::thread::scope(|scope| {
+        let waiter = scope.spawn(|| run(shared.clone(), &["wait"]));
+        for _ in 0..4 {
+            scope.spawn(|| run(shared.clone(), &["add"; 100]));
+        }
+        // the waiter may not be blocked yet
+        while let [Ok(woken)] = run(shared.clone(), &["notify"]).as_slice() {
+            if woken == &vec![Val::I32Const(1)] {
+                break;
+            }
+            std::thread::yield_now();
+        }
+        assert_eq!(waiter.join().unwrap()[0].as_ref().unwrap(), &vec![Val::I32Const(0)]);
+    });
+
+    // the packed expected value is wrapped to 0xFF, which doesn't match 0
+    let results = run(shared.clone(), &["cmpxchg", "cmpxchg", "timeout", "misaligned"]);
+    assert_eq!(results[0].as_ref().unwrap(), &vec![Val::I32Const(400)]);
+    assert_eq!(results[1].as_ref().unwrap(), &vec![Val::I32Const(400)]);
+    assert_eq!(results[2].as_ref().unwrap(), &vec![Val::I32Const(2)]);
+    assert!(matches!(results[3], Err(Error::Trap)));
+
+    // shared memories need max
+    let mut module = module();
+    module.imports[0].desc = ImportDesc::Mem(MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Shared, IdxType::I32));
+    assert!(module_validate(module).is_err());
+}
+
+#[test]
+fn test_wait_interrupted() {
+    use crate::{
+        store_init, store_set_epoch_deadline, mem_alloc, func_alloc,
+        Module, MemType, Limits, Share, IdxType, Export, ExportDesc, ImportDesc, Instr, ValType, Store, HostResult, Error,
+    };
+    use crate::testing::{func, import, TestInstance};
+
+    // the host function stops the store right before the wait, which is the next check
+    fn interrupt(store: &mut Store, _args: &[Val]) -> HostResult {
+        store.interrupt.interrupt();
+        HostResult::Vals(vec![])
+    }
+    fn increment_epoch(store: &mut Store, _args: &[Val]) -> HostResult {
+        store.interrupt.increment_epoch();
+        HostResult::Vals(vec![])
+    }
+
+    // (func (result i32) (call $host) (memory.atomic.wait32 (i32.const 0) (i32.const 0) (i64.const -1)))
+    let memtype = MemType(Limits { min: 1, max: Some(1), page_size_log2: None }, Share::Shared, IdxType::I32);
+    let module = || Module {
+        types: vec![(vec![], vec![]).into(), (vec![], vec![ValType::I32]).into()],
+        imports: vec![import("host", ImportDesc::Func(0)), import("mem", ImportDesc::Mem(memtype.clone()))],
+        funcs: vec![func(1, vec![
+            Instr::Call(0),
+            Instr::I32Const(0), Instr::I32Const(0), Instr::I64Const(-1i64 as u64),
+            Instr::AtomicWait(ValSize::V32, MemArg { align: 2, offset: 0, memory: 0 }),
+        ])],
+        exports: vec![Export { name: "wait".to_string(), desc: ExportDesc::Func(1) }],
+        ..Module::default()
+    };
+
+    for host in [interrupt as fn(&mut Store, &[Val]) -> HostResult, increment_epoch] {
+        let mut store = store_init();
+        store_set_epoch_deadline(&mut store, 1);
+        let hostaddr = func_alloc(&mut store, (vec![], vec![]), host).unwrap();
+        let memaddr = mem_alloc(&mut store, memtype.clone()).unwrap();
+        let instance = TestInstance::new(&mut store, module(), vec![ExternVal::Func(hostaddr), ExternVal::Mem(memaddr)]);
+        assert!(matches!(instance.call(&mut store, "wait", vec![]), Err(Error::Interrupted)));
+    }
+}
@@ -1,81 +1,81 @@
 use crate::{
     FuncIdx,
+    TableIdx,
+    TypeIdx,
     LabelIdx,
+    TagIdx,
     BlockType,
+    RefType,
+
+    alloc_exn,
 };
 
 use super::*;
 
 impl<'a> Thread<'a> {
-    pub fn execute_block(&mut self, blocktype: &BlockType, instrs: &Vec<Instr>) -> Result {
+    pub fn execute_block(&mut self, blocktype: &BlockType, instrs: &Arc<[Instr]>) -> Result {
         let (_, frame) = self.current_frame();
         let (argtypes, returntypes) = blocktype.extend(&frame.module);
-        let mut vals = vec![];
-        for _ in 0..argtypes.len() {
-            if let Some(StackEntry::Value(val)) = self.stack.pop() {
-                vals.push(StackEntry::Value(val));
-            } else {
-                unreachable!();
-            }
-        }
-        let label = StackEntry::Label(returntypes.len() as u32, vec![]);
-        self.execute_instrs_with_label(label, instrs)
+        self.enter_block(argtypes.len(), returntypes.len() as u32, instrs, false)
     }
 
-    pub fn execute_loop(&mut self, blocktype: &BlockType, instrs: &Vec<Instr>) -> Result {
+    pub fn execute_loop(&mut self, blocktype: &BlockType, instrs: &Arc<[Instr]>) -> Result {
         let (_, frame) = self.current_frame();
         let (argtypes, _) = blocktype.extend(&frame.module);
-        let mut vals = vec![];
-        for _ in 0..argtypes.len() {
-            if let Some(StackEntry::Value(val)) = self.stack.pop() {
-                vals.push(StackEntry::Value(val));
-            } else {
-                unreachable!();
-            }
-        }
-        let cont = vec![Instr::Loop(blocktype.clone(), instrs.clone())];
-        let label = StackEntry::Label(argtypes.len() as u32, cont);
-        self.execute_instrs_with_label(label, instrs)
+        self.enter_block(argtypes.len(), argtypes.len() as u32, instrs, true)
     }
 
-    pub fn execute_if(&mut self, blocktype: &BlockType, instrs1: &Vec<Instr>, instrs2: &Option<Vec<Instr>>) -> Result {
+    pub fn execute_if(&mut self, blocktype: &BlockType, instrs1: &Arc<[Instr]>, instrs2: &Option<Arc<[Instr]>>) -> Result {
         let (_, frame) = self.current_frame();
         let (argtypes, returntypes) = blocktype.extend(&frame.module);
 
-        let label = StackEntry::Label(returntypes.len() as u32, vec![]);
-
         let c = if let Some(StackEntry::Value(Val::I32Const(c))) = self.stack.pop() {
             c
         } else {
             unreachable!();
         };
 
-        let mut vals = vec![];
-        for _ in 0..argtypes.len() {
-            if let Some(StackEntry::Value(val)) = self.stack.pop() {
-                vals.push(StackEntry::Value(val));
+        if c == 0 {
+            if let Some(instrs) = instrs2 {
+                self.enter_block(argtypes.len(), returntypes.len() as u32, instrs, false)
             } else {
-                unreachable!();
+                self.enter_block(argtypes.len(), returntypes.len() as u32, &Arc::from([]), false)
             }
+        } else {
+            self.enter_block(argtypes.len(), returntypes.len() as u32, instrs1, false)
         }
+    }
 
-        if c == 0 {
-            if let Some(instrs) = instrs2 {
-                self.execute_instrs_with_label(label, instrs)
+    pub fn execute_trytable(&mut self, blocktype: &BlockType, catches: &[Catch], instrs: &Arc<[Instr]>) -> Result {
+        let (_, frame) = self.current_frame();
+        let (argtypes, returntypes) = blocktype.extend(&frame.module);
+        let idx = self.stack.len() - argtypes.len();
+        self.stack.insert(idx, StackEntry::Handler(catches.to_vec()));
+        self.enter_block(argtypes.len(), returntypes.len() as u32, instrs, false)
+    }
+
+    /// Push a label under the `n` block parameters on top of the stack.
+    fn enter_block(&mut self, n: usize, arity: u32, instrs: &Arc<[Instr]>, is_loop: bool) -> Result {
+        let mut vals = vec![];
+        for _ in 0..n {
+            if let Some(StackEntry::Value(val)) = self.stack.pop() {
+                vals.push(val);
             } else {
-                self.execute_instrs_with_label(label, &vec![])
+                unreachable!();
             }
-        } else {
-            self.execute_instrs_with_label(label, instrs1)
         }
+        vals.reverse();
+        let label = Label{ arity, instrs: instrs.clone(), pc: 0, is_loop };
+        self.stack.push(StackEntry::Label(label));
+        Result::Vals(vals)
     }
 
-    fn find_label(&mut self, labelidx: &LabelIdx) -> (u32, Vec<Instr>) {
+    fn find_label(&self, labelidx: &LabelIdx) -> usize {
         let mut cnt = 0;
-        for entry in self.stack.iter().rev() {
-            if let StackEntry::Label(arity, cont) = entry {
+        for (idx, entry) in self.stack.iter().enumerate().rev() {
+            if let StackEntry::Label(_) = entry {
                 if &cnt == labelidx {
-                    return (arity.clone(), cont.clone());
+                    return idx;
                 }
                 cnt += 1;
             }
@@ -84,26 +84,28 @@ impl<'a> Thread<'a> {
     }
 
     pub fn execute_br(&mut self, labelidx: &LabelIdx) -> Result {
-        let (n, cont) = self.find_label(labelidx);
-        let mut vals = vec![];
-        {
-            for _ in 0..n.clone() {
-                if let Some(val) = self.stack.pop() {
-                    vals.push(val);
-                } else {
-                    unreachable!();
-                }
-            }
-        }
+        let idx = self.find_label(labelidx);
+        let (n, is_loop) = if let StackEntry::Label(label) = &self.stack[idx] {
+            (label.arity as usize, label.is_loop)
+        } else {
+            unreachable!()
+        };
+        let vals = self.stack.split_off(self.stack.len() - n);
 
-        for _ in 0..labelidx.clone() {
-            while let Some(StackEntry::Value(_)) = self.stack.pop() {}
-            self.stack.pop();
+        if is_loop {
+            self.stack.truncate(idx + 1);
+            if let StackEntry::Label(label) = &mut self.stack[idx] {
+                label.pc = 0;
+            }
+            if self.store.is_interrupted() {
+                return Result::Interrupted;
+            }
+        } else {
+            self.stack.truncate(idx);
         }
 
         self.stack.extend(vals);
-
-        self.execute_instrs(&cont)
+        Result::Vals(vec![])
     }
 
     pub fn execute_brif(&mut self, labelidx: &LabelIdx) -> Result {
@@ -131,23 +133,40 @@ impl<'a> Thread<'a> {
         }
     }
 
-    pub fn execute_return(&mut self) -> Result {
-        let (n, _) = self.current_frame();
+    pub fn execute_bronnull(&mut self, labelidx: &LabelIdx) -> Result {
+        match self.pop_ref() {
+            Ref::Null(_) => self.execute_br(labelidx),
+            r => Result::Vals(vec![Val::Ref(r)]),
+        }
+    }
 
-        let mut vals = vec![];
-        for _ in 0..n {
-            if let Some(StackEntry::Value(val)) = self.stack.pop() {
-                vals.push(val);
-            } else {
-                unreachable!();
-            }
+    /// Branch with the reference when it has the type `rt` and `on_match` holds, or when it
+    /// does not and `on_match` does not.
+    pub fn execute_broncast(&mut self, labelidx: &LabelIdx, rt: &RefType, on_match: bool) -> Result {
+        let r = self.pop_ref();
+        let matched = self.ref_matches(&r, rt);
+        self.stack.push(StackEntry::Value(Val::Ref(r)));
+        if matched == on_match {
+            self.execute_br(labelidx)
+        } else {
+            Result::Vals(vec![])
         }
-        loop {
-            if let Some(StackEntry::Activation(_, _)) = self.stack.pop() {
-                break;
-            }
+    }
+
+    pub fn execute_bronnonnull(&mut self, labelidx: &LabelIdx) -> Result {
+        match self.pop_ref() {
+            Ref::Null(_) => Result::Vals(vec![]),
+            r => {
+                self.stack.push(StackEntry::Value(Val::Ref(r)));
+                self.execute_br(labelidx)
+            },
         }
-        Result::Vals(vals)
+    }
+
+    pub fn execute_return(&mut self) -> Result {
+        let idx = self.stack.iter().rposition(|entry| matches!(entry, StackEntry::Activation(_, _))).unwrap();
+        self.return_from(idx);
+        Result::Vals(vec![])
     }
 
     pub fn execute_call(&mut self, funcidx: &FuncIdx) -> Result {
@@ -156,29 +175,297 @@ impl<'a> Thread<'a> {
         self.execute_invoke(&a)
     }
 
-    pub fn execute_callindirect(&mut self, funcidx: &FuncIdx) -> Result {
+    pub fn execute_callindirect(&mut self, tableidx: &TableIdx, typeidx: &TypeIdx) -> Result {
+        match self.indirect_funcaddr(tableidx, typeidx) {
+            Some(a) => self.execute_invoke(&a),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_returncall(&mut self, funcidx: &FuncIdx) -> Result {
+        let (_, frame) = self.current_frame();
+        let a = frame.module.funcaddrs[*funcidx as usize];
+        self.execute_tail_invoke(&a)
+    }
+
+    pub fn execute_returncallindirect(&mut self, tableidx: &TableIdx, typeidx: &TypeIdx) -> Result {
+        match self.indirect_funcaddr(tableidx, typeidx) {
+            Some(a) => self.execute_tail_invoke(&a),
+            None => Result::Trap,
+        }
+    }
+
+    // the type of the reference is checked by validation
+    pub fn execute_callref(&mut self) -> Result {
+        match self.pop_ref() {
+            Ref::Func(a) => self.execute_invoke(&a),
+            _ => Result::Trap,
+        }
+    }
+
+    pub fn execute_returncallref(&mut self) -> Result {
+        match self.pop_ref() {
+            Ref::Func(a) => self.execute_tail_invoke(&a),
+            _ => Result::Trap,
+        }
+    }
+
+    /// Pop the table index and look up the function it refers to, None when the call traps.
+    fn indirect_funcaddr(&mut self, tableidx: &TableIdx, typeidx: &TypeIdx) -> Option<FuncAddr> {
         let (_, frame) = self.current_frame();
-        let ta = frame.module.tableaddrs[0];
+        let ta = frame.module.tableaddrs[*tableidx as usize];
         let table = &self.store.tables[ta];
-        let ft_expect = &frame.module.types[funcidx.clone() as usize];
         if let Some(StackEntry::Value(Val::I32Const(i))) = self.stack.pop() {
-            if (i as usize) < table.elem.len() { return Result::Trap; }
-            if let Some(a) = table.elem[i as usize] {
-                if let FuncInst::User(f) = &self.store.funcs[a] {
-                    let ft_actual = &f.tp;
-                    if ft_actual != ft_expect {
-                        Result::Trap
-                    } else {
-                        self.execute_invoke(&a)
-                    }
+            if (i as usize) >= table.elem.len() { return None; }
+            if let Ref::Func(a) = table.elem[i as usize] {
+                // the type of the function is a subtype of the expected one, declared in its own module
+                if !func_matches(self.store, a, &frame.module.types, *typeidx) {
+                    None
                 } else {
-                    unimplemented!()
+                    Some(a)
                 }
             } else {
-                Result::Trap
+                None
             }
         } else {
             unreachable!()
         }
     }
-}
\ No newline at end of file
+
+    /// Invoke the function at `funcaddr` in place of the current activation,
+    /// so tail calls don't grow the stack.
+    fn execute_tail_invoke(&mut self, funcaddr: &FuncAddr) -> Result {
+        let n = match &self.store.funcs[*funcaddr] {
+            FuncInst::User(f) => f.tp.0.len(),
+            FuncInst::Host(f) => f.tp.0.len(),
+        };
+        let idx = self.stack.iter().rposition(|entry| matches!(entry, StackEntry::Activation(_, _))).unwrap();
+        let args = self.stack.split_off(self.stack.len() - n);
+        self.stack.truncate(idx);
+        self.stack.extend(args);
+        self.execute_invoke(funcaddr)
+    }
+
+    pub fn execute_throw(&mut self, tagidx: &TagIdx) -> Result {
+        let (_, frame) = self.current_frame();
+        let tagaddr = frame.module.tagaddrs[*tagidx as usize];
+        let n = self.store.tags[tagaddr].tp.0.len();
+        let fields = self.stack.split_off(self.stack.len() - n).into_iter().map(|entry| match entry {
+            StackEntry::Value(val) => val,
+            _ => unreachable!(),
+        }).collect();
+        Result::Throw(alloc_exn(self.store, tagaddr, fields))
+    }
+
+    pub fn execute_throwref(&mut self) -> Result {
+        match self.pop_ref() {
+            Ref::Exn(exnaddr) => Result::Throw(exnaddr),
+            _ => Result::Trap,
+        }
+    }
+
+    /// Unwind to the innermost handler above `base` catching the exception and branch to its label,
+    /// or leave the stack as is when there is none.
+    pub(super) fn catch_exception(&mut self, base: usize, exnaddr: ExnAddr) -> Result {
+        let ExnInst{ tag, fields } = self.store.exns[exnaddr].clone();
+        let exnref = Val::Ref(Ref::Exn(exnaddr));
+        let mut top = self.stack.len();
+        while let Some(idx) = self.stack[base..top].iter().rposition(|entry| matches!(entry, StackEntry::Handler(_))) {
+            let idx = base + idx;
+            let catches = if let StackEntry::Handler(catches) = &self.stack[idx] {
+                catches.clone()
+            } else {
+                unreachable!()
+            };
+            // tags are those of the function the handler is in
+            let tagaddrs = self.stack[..idx].iter().rev().find_map(|entry| match entry {
+                StackEntry::Activation(_, frame) => Some(frame.module.tagaddrs.clone()),
+                _ => None,
+            }).unwrap();
+            for catch in catches {
+                let (vals, labelidx) = match catch {
+                    Catch::Tag(x, l) if tagaddrs[x as usize] == tag => (fields, l),
+                    Catch::TagRef(x, l) if tagaddrs[x as usize] == tag => ([fields, vec![exnref]].concat(), l),
+                    Catch::All(l) => (vec![], l),
+                    Catch::AllRef(l) => (vec![exnref], l),
+                    _ => continue,
+                };
+                self.stack.truncate(idx);
+                self.stack.extend(vals.into_iter().map(StackEntry::Value));
+                return self.execute_br(&labelidx);
+            }
+            top = idx;
+        }
+        Result::Throw(exnaddr)
+    }
+}
+
+#[test]
+fn test_return_call() {
+    use crate::{store_init, store_set_fuel, func_invoke_resumable, func_resume, Func, Expr, ModuleInst, IBinOp, ITestOp, ValSize, ValType, Invocation};
+
+    // sum of 1..=n, accumulating in the second parameter and recursing in tail position
+    let body = Expr(vec![
+        Instr::LocalGet(0),
+        Instr::ITestOp(ValSize::V32, ITestOp::Eqz),
+        Instr::If(BlockType::ValType(Some(ValType::I32)), vec![
+            Instr::LocalGet(1),
+        ].into(), Some(vec![
+            Instr::LocalGet(0),
+            Instr::I32Const(1),
+            Instr::IBinOp(ValSize::V32, IBinOp::Sub),
+            Instr::LocalGet(0),
+            Instr::LocalGet(1),
+            Instr::IBinOp(ValSize::V32, IBinOp::Add),
+            Instr::ReturnCall(0),
+        ].into())),
+    ]);
+    let functype = (vec![ValType::I32, ValType::I32], vec![ValType::I32]);
+    let func = Func { tp: 0, locals: vec![], body };
+    let moduleinst = ModuleInst { types: Arc::new(vec![functype.clone().into()]), funcaddrs: vec![0], ..ModuleInst::default() };
+
+    let mut store = store_init();
+    store.funcs.push(FuncInst::user(functype, moduleinst, func));
+    store_set_fuel(&mut store, 10_000);
+    let suspension = match func_invoke_resumable(&mut store, 0, vec![Val::I32Const(20_000), Val::I32Const(0)]) {
+        Ok(Invocation::Suspended(suspension)) => suspension,
+        _ => panic!("expected to run out of fuel"),
+    };
+    // thousands of calls deep, but only the last activation is left
+    assert!(suspension.stack.len() < 16);
+
+    store_set_fuel(&mut store, u64::MAX);
+    match func_resume(&mut store, suspension, vec![]) {
+        Ok(Invocation::Done(vals)) => assert_eq!(vals, vec![Val::I32Const(200_010_000)]),
+        _ => panic!("expected the sum"),
+    }
+}
+
+#[test]
+fn test_exception() {
+    use crate::{store_init, func_alloc, func_invoke, tag_alloc, exn_alloc, exn_tag, exn_read, Func, Expr, ModuleInst, IBinOp, ValSize, ValType, HeapType, HostResult, Error};
+
+    fn host(store: &mut Store, args: &[Val]) -> HostResult {
+        match exn_alloc(store, 0, args.to_vec()) {
+            Ok(exnaddr) => HostResult::Throw(exnaddr),
+            Err(_) => HostResult::Trap,
+        }
+    }
+
+    // block (result i32) try_table (catch 0 0) local.get 0 call f end i32.const 0 end i32.const 1 i32.add
+    let catcher = |funcidx| Expr(vec![
+        Instr::Block(BlockType::ValType(Some(ValType::I32)), vec![
+            Instr::TryTable(BlockType::ValType(None), vec![Catch::Tag(0, 0)], vec![
+                Instr::LocalGet(0),
+                Instr::Call(funcidx),
+            ].into()),
+            Instr::I32Const(0),
+        ].into()),
+        Instr::I32Const(1),
+        Instr::IBinOp(ValSize::V32, IBinOp::Add),
+    ]);
+    let thrower = Expr(vec![Instr::LocalGet(0), Instr::Throw(0)]);
+    // block (result exnref) try_table (catch_all_ref 0) local.get 0 call 0 end ref.null exn end throw_ref
+    let rethrower = Expr(vec![
+        Instr::Block(BlockType::ValType(Some(ValType::ExnRef)), vec![
+            Instr::TryTable(BlockType::ValType(None), vec![Catch::AllRef(0)], vec![
+                Instr::LocalGet(0),
+                Instr::Call(0),
+            ].into()),
+            Instr::RefNull(HeapType::Exn),
+        ].into()),
+        Instr::ThrowRef,
+    ]);
+
+    let mut store = store_init();
+    let tagaddr = tag_alloc(&mut store, (vec![ValType::I32], vec![])).unwrap();
+    assert!(tag_alloc(&mut store, (vec![], vec![ValType::I32])).is_err());
+    let types = [(vec![ValType::I32], vec![]), (vec![ValType::I32], vec![ValType::I32])];
+    let moduleinst = ModuleInst { types: Arc::new(types.iter().cloned().map(SubType::from).collect()), funcaddrs: vec![0, 1, 2, 3, 4], tagaddrs: vec![tagaddr], ..ModuleInst::default() };
+    for (tp, body) in [(0, thrower), (1, catcher(0)), (0, rethrower)] {
+        store.funcs.push(FuncInst::user(types[tp].clone(), moduleinst.clone(), Func { tp: tp as u32, locals: vec![], body }));
+    }
+    func_alloc(&mut store, types[0].clone(), host).unwrap();
+    store.funcs.push(FuncInst::user(types[1].clone(), moduleinst, Func { tp: 1, locals: vec![], body: catcher(3) }));
+
+    // caught in the calling function, with the thrown value
+    let (store, result) = func_invoke(&mut store, 1, vec![Val::I32Const(41)]);
+    assert_eq!(result.unwrap(), vec![Val::I32Const(42)]);
+
+    // rethrown and not caught
+    let (store, result) = func_invoke(store, 2, vec![Val::I32Const(7)]);
+    let exnaddr = match result {
+        Err(Error::Exception(exnaddr)) => exnaddr,
+        _ => panic!("expected an exception"),
+    };
+    assert_eq!(exn_tag(store, exnaddr), tagaddr);
+    assert_eq!(exn_read(store, exnaddr), vec![Val::I32Const(7)]);
+
+    // thrown by a host function
+    let (store, result) = func_invoke(store, 4, vec![Val::I32Const(9)]);
+    assert_eq!(result.unwrap(), vec![Val::I32Const(10)]);
+    assert!(exn_alloc(store, tagaddr, vec![]).is_err());
+}
+
+#[test]
+fn test_call_ref() {
+    use crate::{
+        store_init, func_invoke, module_validate, module_instanciate,
+        Module, Func, Export, ExportDesc, Expr, IBinOp, ValSize, ValType, RefType, HeapType, Error,
+    };
+
+    let typed = |nullable| ValType::Ref(RefType { nullable, heap: HeapType::Type(1) });
+    let func = |tp, locals, body| Func { tp, locals, body: Expr(body) };
+    let module = |funcs: Vec<Func>| Module {
+        types: vec![
+            (vec![], vec![ValType::I32]).into(),
+            (vec![ValType::I32], vec![ValType::I32]).into(),
+            (vec![typed(true)], vec![ValType::I32]).into(),
+        ],
+        exports: vec![Export { name: "inc".to_string(), desc: ExportDesc::Func(0) }],
+        funcs,
+        ..Module::default()
+    };
+    let funcs = vec![
+        func(1, vec![], vec![Instr::LocalGet(0), Instr::I32Const(1), Instr::IBinOp(ValSize::V32, IBinOp::Add)]),
+        func(0, vec![], vec![Instr::I32Const(41), Instr::RefFunc(0), Instr::CallRef(1)]),
+        func(0, vec![], vec![Instr::I32Const(1), Instr::RefNull(HeapType::Type(1)), Instr::CallRef(1)]),
+        // i32.const 10 block (result (ref 1)) local.get 0 br_on_non_null 0 i32.const 0 return end call_ref 1
+        func(2, vec![], vec![
+            Instr::I32Const(10),
+            Instr::Block(BlockType::ValType(Some(typed(false))), vec![
+                Instr::LocalGet(0), Instr::BrOnNonNull(0), Instr::I32Const(0), Instr::Return,
+            ].into()),
+            Instr::CallRef(1),
+        ]),
+        func(0, vec![], vec![Instr::RefFunc(0), Instr::Call(3)]),
+        func(0, vec![], vec![Instr::RefNull(HeapType::Type(1)), Instr::Call(3)]),
+        // block local.get 0 br_on_null 0 drop i32.const 1 return end i32.const 2
+        func(2, vec![], vec![
+            Instr::Block(BlockType::ValType(None), vec![
+                Instr::LocalGet(0), Instr::BrOnNull(0), Instr::Drop(None), Instr::I32Const(1), Instr::Return,
+            ].into()),
+            Instr::I32Const(2),
+        ]),
+        func(0, vec![], vec![Instr::RefNull(HeapType::Type(1)), Instr::Call(6)]),
+        func(0, vec![], vec![Instr::RefNull(HeapType::Type(1)), Instr::RefAsNonNull, Instr::Drop(None), Instr::I32Const(0)]),
+        // a non-nullable local is read only after it is set
+        func(0, vec![typed(false)], vec![
+            Instr::RefFunc(0), Instr::LocalSet(0), Instr::I32Const(2), Instr::LocalGet(0), Instr::CallRef(1),
+        ]),
+    ];
+
+    let mut store = store_init();
+    let moduleinst = module_instanciate(&mut store, module(funcs), vec![]).unwrap();
+    let invoke = |store: &mut Store, funcidx: usize| func_invoke(store, moduleinst.funcaddrs[funcidx], vec![]).1;
+    assert_eq!(invoke(&mut store, 1).unwrap(), vec![Val::I32Const(42)]);
+    assert!(matches!(invoke(&mut store, 2), Err(Error::Trap)));
+    assert_eq!(invoke(&mut store, 4).unwrap(), vec![Val::I32Const(11)]);
+    assert_eq!(invoke(&mut store, 5).unwrap(), vec![Val::I32Const(0)]);
+    assert_eq!(invoke(&mut store, 7).unwrap(), vec![Val::I32Const(2)]);
+    assert!(matches!(invoke(&mut store, 8), Err(Error::Trap)));
+    assert_eq!(invoke(&mut store, 9).unwrap(), vec![Val::I32Const(3)]);
+
+    let unset = func(0, vec![typed(false)], vec![Instr::I32Const(2), Instr::LocalGet(0), Instr::CallRef(1)]);
+    assert!(module_validate(module(vec![unset])).is_err());
+}
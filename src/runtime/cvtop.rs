@@ -1,17 +1,9 @@
 use super::*;
 
-macro_rules! val {
-    (i, 32, $m:ident) => { Val::I32Const($m) };
-    (i, 64, $m:ident) => { Val::I64Const($m) };
-}
-
 macro_rules! extendN_op {
-    ($this:ident, $m:ident, $mp:pat, $mr:expr, $sz:ident) => {
+    ($this:ident, $m:ident, $mp:pat, $mr:expr) => {
         match $this.stack.pop() {
-            Some(StackEntry::Value($mp)) => {
-                if $m > $sz::MAX.into() { return Result::Trap; }
-                Result::Vals(vec![$mr])
-            },
+            Some(StackEntry::Value($mp)) => Result::Vals(vec![$mr]),
             _ => Result::Trap,
         }
     };
@@ -19,19 +11,19 @@ macro_rules! extendN_op {
 
 impl<'a> Thread<'a> {
     pub fn execute_i32extend8s(&mut self) -> Result {
-        extendN_op!(self, v, val!(i, 32, v), val!(i, 32, v), u8)
+        extendN_op!(self, v, Val::I32Const(v), Val::I32Const(v as u8 as i8 as i32 as u32))
     }
     pub fn execute_i64extend8s(&mut self) -> Result {
-        extendN_op!(self, v, val!(i, 64, v), val!(i, 64, v), u8)
+        extendN_op!(self, v, Val::I64Const(v), Val::I64Const(v as u8 as i8 as i64 as u64))
     }
     pub fn execute_i32extend16s(&mut self) -> Result {
-        extendN_op!(self, v, val!(i, 32, v), val!(i, 32, v), u16)
+        extendN_op!(self, v, Val::I32Const(v), Val::I32Const(v as u16 as i16 as i32 as u32))
     }
     pub fn execute_i64extend16s(&mut self) -> Result {
-        extendN_op!(self, v, val!(i, 64, v), val!(i, 64, v), u16)
+        extendN_op!(self, v, Val::I64Const(v), Val::I64Const(v as u16 as i16 as i64 as u64))
     }
     pub fn execute_i64extend32s(&mut self) -> Result {
-        extendN_op!(self, v, val!(i, 64, v), val!(i, 64, v), u32)
+        extendN_op!(self, v, Val::I64Const(v), Val::I64Const(v as u32 as i32 as i64 as u64))
     }
 }
 
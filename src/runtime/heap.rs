@@ -0,0 +1,220 @@
+use std::collections::HashSet;
+use std::mem;
+use std::slice;
+
+use super::*;
+
+/// Allocations between two collections, at least.
+const COLLECT_INTERVAL: usize = 1024;
+
+/// Bytes charged to the limiter for an object of `n` fields.
+fn obj_size(n: usize) -> usize {
+    mem::size_of::<HeapInst>().saturating_add(n.saturating_mul(mem::size_of::<Val>()))
+}
+
+/// Structs and arrays of a store, freed by a tracing collector once no root reaches them.
+/// The roots are the globals, tables, element segments and exceptions of the store and
+/// the stack of the running thread. References kept by the host are not roots, so a slot
+/// is reused under a new generation: such a reference can't reach the next object.
+#[derive(Default)]
+pub struct Heap {
+    pub objs: Vec<Option<HeapInst>>,
+    /// Generation of each slot, bumped when its object is freed.
+    pub(crate) generations: Vec<u32>,
+    /// Slots of the freed objects, taken by the next allocations.
+    free: Vec<usize>,
+    /// Number of objects not freed yet.
+    live: usize,
+    /// Allocations since the last collection.
+    allocated: usize,
+    /// Size of the objects not freed yet, as charged to the limiter.
+    bytes: usize,
+    /// Threads running in the store, nested by host functions invoking the store again.
+    /// A thread collects only when it is the only one.
+    pub(crate) threads: usize,
+    /// Shared with the suspensions of the store, whose stacks hold references
+    /// until they are resumed or dropped. Nothing is collected while there are any.
+    pub(crate) suspended: Arc<()>,
+    /// Constant expressions being evaluated, whose results are no roots yet.
+    pub(crate) constants: usize,
+}
+
+impl Heap {
+    pub fn get(&self, addr: HeapAddr) -> &HeapInst {
+        self.try_get(addr).expect("reference to a collected object")
+    }
+
+    pub fn get_mut(&mut self, addr: HeapAddr) -> &mut HeapInst {
+        assert!(self.generations.get(addr.index) == Some(&addr.generation), "reference to a collected object");
+        self.objs[addr.index].as_mut().expect("reference to a collected object")
+    }
+
+    /// The object at `addr`, unless it was collected.
+    pub fn try_get(&self, addr: HeapAddr) -> Option<&HeapInst> {
+        if self.generations.get(addr.index) != Some(&addr.generation) {
+            return None;
+        }
+        self.objs[addr.index].as_ref()
+    }
+
+    /// Whether `r` refers to no collected object.
+    pub fn is_live(&self, r: &Ref) -> bool {
+        match r {
+            Ref::Struct(addr) | Ref::Array(addr) |
+            Ref::ExternAny(AnyRef::Struct(addr) | AnyRef::Array(addr)) => self.try_get(*addr).is_some(),
+            _ => true,
+        }
+    }
+
+    /// Number of live objects.
+    pub fn len(&self) -> usize {
+        self.live
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A heap holding `objs` at their `generations`, the freed ones being `None`.
+    pub(crate) fn restore(objs: Vec<Option<HeapInst>>, generations: Vec<u32>) -> Heap {
+        let free = objs.iter().enumerate().filter(|(_, obj)| obj.is_none()).map(|(index, _)| index).collect();
+        let live = objs.iter().flatten().count();
+        let bytes = objs.iter().flatten().map(|obj| obj_size(obj.fields.len())).sum();
+        Heap{ objs, generations, free, live, bytes, ..Heap::default() }
+    }
+
+    fn alloc(&mut self, inst: HeapInst) -> HeapAddr {
+        self.allocated += 1;
+        self.live += 1;
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.objs[index] = Some(inst);
+                index
+            },
+            None => {
+                self.objs.push(Some(inst));
+                self.generations.push(0);
+                self.objs.len() - 1
+            },
+        };
+        HeapAddr{ index, generation: self.generations[index] }
+    }
+
+    pub(crate) fn suspensions(&self) -> usize {
+        Arc::strong_count(&self.suspended) - 1
+    }
+
+    // only a thread running alone sees all the roots
+    fn can_collect(&self) -> bool {
+        self.threads == 1 && self.suspensions() == 0 && self.constants == 0
+    }
+
+    // the live objects may double before the next collection
+    fn should_collect(&self) -> bool {
+        self.can_collect() && self.allocated >= COLLECT_INTERVAL.max(self.len())
+    }
+}
+
+fn heap_addr(val: &Val) -> Option<HeapAddr> {
+    match val {
+        Val::Ref(Ref::Struct(addr) | Ref::Array(addr)) => Some(*addr),
+        Val::Ref(Ref::ExternAny(AnyRef::Struct(addr) | AnyRef::Array(addr))) => Some(*addr),
+        _ => None,
+    }
+}
+
+impl Store {
+    /// Free the objects reached neither from the store nor from `roots`.
+    pub(crate) fn collect<'a>(&mut self, roots: impl Iterator<Item = &'a Val>) {
+        let refs = self.tables.iter().flat_map(|table| table.elem.iter())
+            .chain(self.elems.iter().flat_map(|elem| elem.elem.iter()))
+            .map(|r| Val::Ref(*r));
+        let vals = self.globals.iter().map(|global| global.value)
+            .chain(self.exns.iter().flat_map(|exn| exn.fields.iter().copied()));
+        let mut pending: Vec<HeapAddr> = roots.filter_map(heap_addr)
+            .chain(refs.chain(vals).filter_map(|val| heap_addr(&val)))
+            .collect();
+
+        // mark
+        let mut marked = HashSet::new();
+        while let Some(addr) = pending.pop() {
+            // the host may have stored a reference it kept across a collection
+            let Some(obj) = self.heap.try_get(addr) else { continue };
+            if marked.insert(addr.index) {
+                pending.extend(obj.fields.iter().filter_map(heap_addr));
+            }
+        }
+
+        // sweep
+        let heap = &mut self.heap;
+        let mut freed = 0;
+        for (index, slot) in heap.objs.iter_mut().enumerate() {
+            if marked.contains(&index) { continue; }
+            if let Some(obj) = slot.take() {
+                freed += obj_size(obj.fields.len());
+                heap.generations[index] = heap.generations[index].wrapping_add(1);
+                heap.free.push(index);
+                heap.live -= 1;
+            }
+        }
+        heap.allocated = 0;
+        self.heap.bytes -= freed;
+        self.heap_released(freed);
+    }
+
+    // false when the limiter refuses
+    fn heap_charging(&mut self, size: usize) -> bool {
+        let current = self.heap.bytes;
+        let desired = current.saturating_add(size);
+        if !self.heap_growing(current, desired) {
+            return false;
+        }
+        self.heap.bytes = desired;
+        true
+    }
+}
+
+impl<'a> Thread<'a> {
+    // `roots` are popped from the stack already
+    fn collect_garbage(&mut self, roots: &[Val]) {
+        let stack = self.stack.iter().flat_map(|entry| match entry {
+            StackEntry::Value(val) => slice::from_ref(val),
+            StackEntry::Activation(_, frame) => frame.locals.as_slice(),
+            _ => &[],
+        });
+        self.store.collect(stack.chain(roots.iter()));
+    }
+
+    /// Charge the limiter for an object of `n` fields before it is built, after a collection
+    /// when enough were allocated or the limiter refuses. `roots` are its fields popped from
+    /// the stack already. False when the limiter refuses anyway.
+    pub(super) fn heap_reserve(&mut self, n: usize, roots: &[Val]) -> bool {
+        if self.store.heap.should_collect() {
+            self.collect_garbage(roots);
+        }
+        let size = obj_size(n);
+        if self.store.heap_charging(size) {
+            return true;
+        }
+        if !self.store.heap.can_collect() {
+            return false;
+        }
+        self.collect_garbage(roots);
+        self.store.heap_charging(size)
+    }
+
+    /// Allocate a struct or an array reserved with `heap_reserve`.
+    pub(super) fn alloc_heap(&mut self, inst: HeapInst) -> HeapAddr {
+        self.store.heap.alloc(inst)
+    }
+
+    /// Types of the module of the current frame.
+    pub(super) fn current_types(&self) -> Arc<Vec<SubType>> {
+        for entry in self.stack.iter().rev() {
+            if let StackEntry::Activation(_, frame) = entry {
+                return frame.module.types.clone();
+            }
+        }
+        unreachable!()
+    }
+}
@@ -1,8 +1,16 @@
+use std::sync::{Arc, Mutex, MutexGuard, Condvar};
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
 use crate::{
     ValType,
     MemArg,
     ValSize,
     ValSign,
+    MemIdx,
+    DataIdx,
+    Share,
+    IdxType,
 
     grow_mem,
 };
@@ -12,6 +20,164 @@ use super::{
     Result as ExecResult,
 };
 
+/// Longest a `memory.atomic.wait` blocks before checking whether its store was interrupted.
+const WAIT_SLICE: Duration = Duration::from_millis(10);
+
+/// Memory which can be used from several stores, on any host thread.
+/// Every access holds the lock, so atomic instructions are atomic among the sharing threads.
+#[derive(Clone)]
+pub struct SharedMemory(Arc<SharedMemoryInner>);
+
+struct SharedMemoryInner {
+    state: Mutex<SharedState>,
+    notified: Condvar,
+    max: Option<u64>,
+    idxtype: IdxType,
+    page_size_log2: u32,
+}
+
+struct SharedState {
+    data: Vec<Byte>,
+    // ticket and address of every thread blocked in memory.atomic.wait
+    waiters: Vec<(u64, usize)>,
+    next_ticket: u64,
+}
+
+impl SharedMemory {
+    pub fn new(data: Vec<Byte>, max: Option<u64>, idxtype: IdxType, page_size_log2: u32) -> Self {
+        let state = SharedState { data, waiters: vec![], next_ticket: 0 };
+        let inner = SharedMemoryInner { state: Mutex::new(state), notified: Condvar::new(), max, idxtype, page_size_log2 };
+        SharedMemory(Arc::new(inner))
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        self.0.max
+    }
+
+    pub fn idxtype(&self) -> IdxType {
+        self.0.idxtype
+    }
+
+    pub fn page_size_log2(&self) -> u32 {
+        self.0.page_size_log2
+    }
+
+    // a thread panicking while holding the lock leaves the bytes usable
+    fn lock(&self) -> MutexGuard<'_, SharedState> {
+        self.0.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Block until notified on `ea` or until `timeout` elapses, when the bytes at `ea` equal `expected`.
+    /// `interrupted` is checked every `WAIT_SLICE` of the wait.
+    /// Returns 0 when woken, 1 when the bytes differ and 2 on timeout, None when out of bounds or interrupted.
+    pub fn wait(&self, ea: usize, expected: &[Byte], timeout: Option<Duration>, mut interrupted: impl FnMut() -> bool) -> Option<u32> {
+        let mut state = self.lock();
+        if state.data.get(ea..ea.checked_add(expected.len())?)? != expected {
+            return Some(1);
+        }
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.waiters.push((ticket, ea));
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if !state.waiters.iter().any(|(t, _)| *t == ticket) {
+                return Some(0);
+            }
+            if interrupted() {
+                state.waiters.retain(|(t, _)| *t != ticket);
+                return None;
+            }
+            let slice = match deadline {
+                None => WAIT_SLICE,
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        state.waiters.retain(|(t, _)| *t != ticket);
+                        return Some(2);
+                    }
+                    (deadline - now).min(WAIT_SLICE)
+                },
+            };
+            state = self.0.notified.wait_timeout(state, slice)
+                .unwrap_or_else(|poisoned| poisoned.into_inner()).0;
+        }
+    }
+
+    /// Wake up to `count` threads waiting on `ea`, in the order they started waiting.
+    /// Returns the number of woken threads.
+    pub fn notify(&self, ea: usize, count: u32) -> u32 {
+        let mut state = self.lock();
+        let mut woken = 0;
+        state.waiters.retain(|(_, addr)| {
+            if woken < count && *addr == ea {
+                woken += 1;
+                false
+            } else {
+                true
+            }
+        });
+        if woken > 0 {
+            self.0.notified.notify_all();
+        }
+        woken
+    }
+}
+
+impl MemInst {
+    pub fn new(data: Vec<Byte>, max: Option<u64>, share: Share, idxtype: IdxType, page_size_log2: u32) -> Self {
+        let data = match share {
+            Share::Unshared => MemData::Unshared(data),
+            Share::Shared => MemData::Shared(SharedMemory::new(data, max, idxtype, page_size_log2)),
+        };
+        MemInst { data, max, idxtype, page_size_log2 }
+    }
+
+    pub fn share(&self) -> Share {
+        match self.data {
+            MemData::Unshared(_) => Share::Unshared,
+            MemData::Shared(_) => Share::Shared,
+        }
+    }
+
+    /// Size in bytes.
+    pub fn size(&self) -> usize {
+        self.with_data(|data| data.len())
+    }
+
+    /// Size in pages of the memory type.
+    pub fn pages(&self) -> u64 {
+        (self.size() >> self.page_size_log2) as u64
+    }
+
+    /// Run `f` on the bytes, holding the lock of a shared memory.
+    pub fn with_data<T>(&self, f: impl FnOnce(&[Byte]) -> T) -> T {
+        match &self.data {
+            MemData::Unshared(data) => f(data),
+            MemData::Shared(shared) => f(&shared.lock().data),
+        }
+    }
+
+    pub fn with_data_mut<T>(&mut self, f: impl FnOnce(&mut Vec<Byte>) -> T) -> T {
+        match &mut self.data {
+            MemData::Unshared(data) => f(data),
+            MemData::Shared(shared) => f(&mut shared.lock().data),
+        }
+    }
+
+    /// The `n` bytes at `ea`, None when out of bounds.
+    pub fn read(&self, ea: usize, n: usize) -> Option<Vec<Byte>> {
+        self.with_data(|data| data.get(ea..ea.checked_add(n)?).map(|bytes| bytes.to_vec()))
+    }
+
+    pub fn write(&mut self, ea: usize, bytes: &[Byte]) -> Option<()> {
+        self.with_data_mut(|data| {
+            data.get_mut(ea..ea.checked_add(bytes.len())?)?.copy_from_slice(bytes);
+            Some(())
+        })
+    }
+}
+
 impl<'a> Thread<'a> {
     pub fn execute_load(&mut self, valtype: &ValType, memarg: &MemArg) -> ExecResult {
         self.execute_load_internal(valtype, &ValSign::U, memarg, Thread::valtype2usize(valtype))
@@ -25,80 +191,31 @@ impl<'a> Thread<'a> {
     pub fn execute_i64load32(&mut self, valsign: &ValSign, memarg: &MemArg)-> ExecResult {
         self.execute_load_internal(&ValType::I64, valsign, memarg, 32)
     }
-    pub fn execute_load_internal(&mut self, valtype: &ValType,  _valsign: &ValSign, memarg: &MemArg, n: u32) -> ExecResult {
+    pub fn execute_load_internal(&mut self, valtype: &ValType, valsign: &ValSign, memarg: &MemArg, n: u32) -> ExecResult {
         let (_, frame) = self.current_frame();
-        let memaddr = frame.module.memaddrs[0];
+        let memaddr = frame.module.memaddrs[memarg.memory as usize];
+        let ea = match self.pop_ea(memarg) {
+            Some(ea) => ea,
+            None => return Result::Trap,
+        };
         let mem = &self.store.mems[memaddr];
-        let c = if let Some(StackEntry::Value(Val::I32Const(c))) = self.stack.pop() {
-            c
-        } else {
-            unreachable!()
+        let slice = match mem.read(ea, (n / 8) as usize) {
+            Some(slice) => slice,
+            None => return Result::Trap,
         };
-
-        let ea = c + memarg.offset;
-        let ea = ea as usize;
-        let max = ea + (n / 8) as usize;
-        if max > mem.data.len() {
-            return Result::Trap;
+        let mut bytes = [0x00; 8];
+        bytes[..slice.len()].copy_from_slice(&slice);
+        let mut v = u64::from_le_bytes(bytes);
+        if let ValSign::S = valsign {
+            let shift = 64 - n;
+            v = (((v << shift) as i64) >> shift) as u64;
         }
-
-        let slice = &mem.data[ea..max];
-        match n {
-            8 => {
-                let mut bytes = [0x00; 1];
-                for i in 0..1 {
-                    bytes[i] = slice[i];
-                }
-                let v = u8::from_le_bytes(bytes);
-                match valtype {
-                    ValType::I32 => Result::i32val(v as u32),
-                    ValType::I64 => Result::i64val(v as u64),
-                    // ValType::F32 => Result::f32val(v as f32),
-                    // ValType::F64 => Result::f64val(v as f64),
-                    _ => unreachable!(),
-                }
-            },
-            16 => {
-                let mut bytes = [0x00; 2];
-                for i in 0..2 {
-                    bytes[i] = slice[i];
-                }
-                let v = u16::from_le_bytes(bytes);
-                match valtype {
-                    ValType::I32 => Result::i32val(v as u32),
-                    ValType::I64 => Result::i64val(v as u64),
-                    // ValType::F32 => Result::f32val(v as f32),
-                    // ValType::F64 => Result::f64val(v as f64),
-                    _ => unreachable!(),
-                }
-            },
-            32 => {
-                let mut bytes = [0x00; 4];
-                for i in 0..4 {
-                    bytes[i] = slice[i];
-                }
-                let v = u32::from_le_bytes(bytes);
-                match valtype {
-                    ValType::I32 => Result::i32val(v as u32),
-                    ValType::I64 => Result::i64val(v as u64),
-                    ValType::F32 => Result::f32val(v as f32),
-                    ValType::F64 => Result::f64val(v as f64),
-                }
-            },
-            64 => {
-                let mut bytes = [0x00; 8];
-                for i in 0..8 {
-                    bytes[i] = slice[i];
-                }
-                let v = u64::from_le_bytes(bytes);
-                match valtype {
-                    ValType::I32 => Result::i32val(v as u32),
-                    ValType::I64 => Result::i64val(v as u64),
-                    ValType::F32 => Result::f32val(v as f32),
-                    ValType::F64 => Result::f64val(v as f64),
-                }
-            },
-            _ => unreachable!(),
+        match valtype {
+            ValType::I32 => Result::i32val(v as u32),
+            ValType::I64 => Result::i64val(v),
+            ValType::F32 => Result::f32val(f32::from_bits(v as u32)),
+            ValType::F64 => Result::f64val(f64::from_bits(v)),
+            ValType::V128 | ValType::FuncRef | ValType::ExternRef | ValType::ExnRef | ValType::Ref(_) => unreachable!(),
         }
     }
 
@@ -117,50 +234,12 @@ impl<'a> Thread<'a> {
 
     pub fn execute_store_internal(&mut self, valtype: &ValType, memarg: &MemArg, n: u32) -> ExecResult {
         let (_, frame) = self.current_frame();
-        let memaddr = frame.module.memaddrs[0];
-        let mem = &mut self.store.mems[memaddr];
-        let c = if let Some(StackEntry::Value(Val::I32Const(c))) = self.stack.pop() {
-            c
-        } else {
-            unreachable!()
-        };
-
-        let ea = c + memarg.offset;
-
-        let ea = ea as usize;
-        let max = ea + (n / 8) as usize;
-        if max > mem.data.len() {
-            return Result::Trap;
-        }
-        let slice = &mut mem.data[ea..max];
+        let memaddr = frame.module.memaddrs[memarg.memory as usize];
 
-        match valtype {
-            ValType::I32 => {
-                let wrapped_n =
-                if let Some(StackEntry::Value(Val::I32Const(v))) = self.stack.pop() {
-                    v % 2u32.pow(n)
-                } else {
-                    unreachable!()
-                };
-                let bytes = wrapped_n.to_le_bytes();
-                for i in 0..(n/8) as usize {
-                    slice[i] = bytes[i];
-                }
-                Result::Vals(vec![])
-            },
-            ValType::I64 => {
-                let wrapped_n = 
-                if let Some(StackEntry::Value(Val::I64Const(v))) = self.stack.pop() {
-                    v % 2u64.pow(n)
-                } else {
-                    unreachable!()
-                };
-                let bytes = wrapped_n.to_le_bytes();
-                for i in 0..(n/8) as usize {
-                    slice[i] = bytes[i];
-                }
-                Result::Vals(vec![])
-            },
+        // the value is above the address, wrapped by keeping its low bytes
+        let bytes = match valtype {
+            ValType::I32 => self.pop_i32().to_le_bytes().to_vec(),
+            ValType::I64 => self.pop_i64().to_le_bytes().to_vec(),
             ValType::F32 => {
                 let n = 
                 if let Some(StackEntry::Value(Val::F32Const(n))) = self.stack.pop() {
@@ -168,11 +247,7 @@ impl<'a> Thread<'a> {
                 } else {
                     unreachable!()
                 };
-                let bytes = n.to_le_bytes();
-                for i in 0..4 {
-                    slice[i] = bytes[i];
-                }
-                Result::Vals(vec![])
+                n.to_le_bytes().to_vec()
             },
             ValType::F64 => {
                 let n = 
@@ -181,19 +256,33 @@ impl<'a> Thread<'a> {
                 } else {
                     unreachable!()
                 };
-                let bytes = n.to_le_bytes();
-                for i in 0..8 {
-                    slice[i] = bytes[i];
-                }
-                Result::Vals(vec![])
+                n.to_le_bytes().to_vec()
             },
+            ValType::V128 | ValType::FuncRef | ValType::ExternRef | ValType::ExnRef | ValType::Ref(_) => unreachable!(),
+        };
+
+        let ea = match self.pop_ea(memarg) {
+            Some(ea) => ea,
+            None => return Result::Trap,
+        };
+        let mem = &mut self.store.mems[memaddr];
+        if mem.write(ea, &bytes[..(n / 8) as usize]).is_none() {
+            return Result::Trap;
         }
+        Result::Vals(vec![])
+    }
+
+    /// Pop an address and add the offset of `memarg`, None when it overflows.
+    pub(super) fn pop_ea(&mut self, memarg: &MemArg) -> Option<usize> {
+        let ea = self.pop_addr().checked_add(memarg.offset)?;
+        usize::try_from(ea).ok()
     }
 
     fn valtype2usize(valtype: &ValType) -> u32 {
         match valtype {
             ValType::I32 | ValType::F32 => 32,
             ValType::I64 | ValType::F64 => 64,
+            ValType::V128 | ValType::FuncRef | ValType::ExternRef | ValType::ExnRef | ValType::Ref(_) => unreachable!(),
         }
     }
 
@@ -204,31 +293,300 @@ impl<'a> Thread<'a> {
         }
     }
 
-    pub fn execute_memorysize(&mut self) -> Result {
+    pub fn execute_memorysize(&mut self, memidx: &MemIdx) -> Result {
         let (_, frame) = self.current_frame();
-        let memaddr = frame.module.memaddrs[0];
+        let memaddr = frame.module.memaddrs[*memidx as usize];
         let mem = &self.store.mems[memaddr];
-        let sz = mem.data.len() / (64*1024);
-        Result::Vals(vec![Val::I32Const(sz as u32)])
+        Result::Vals(vec![addr_val(mem.idxtype, mem.pages())])
     }
 
-    pub fn execute_memorygrow(&mut self) -> Result {
+    pub fn execute_memorygrow(&mut self, memidx: &MemIdx) -> Result {
         let (_, frame) = self.current_frame();
-        let memaddr = frame.module.memaddrs[0];
+        let memaddr = frame.module.memaddrs[*memidx as usize];
         let meminst = &self.store.mems[memaddr];
-        let sz = meminst.data.len() / (64*1024);
-        let n = if let Some(StackEntry::Value(Val::I32Const(n))) = self.stack.pop() {
-            n
+        let idxtype = meminst.idxtype;
+        let sz = meminst.pages();
+        let n = self.pop_addr();
+
+        // -1 of the index type
+        let err = u64::MAX;
+
+        match usize::try_from(n) {
+            Ok(n) if grow_mem(self.store, memaddr, n).is_ok() => Result::Vals(vec![addr_val(idxtype, sz)]),
+            _ => Result::Vals(vec![addr_val(idxtype, err)]),
+        }
+    }
+
+    pub fn execute_memoryfill(&mut self, memidx: &MemIdx) -> Result {
+        let (_, frame) = self.current_frame();
+        let memaddr = frame.module.memaddrs[*memidx as usize];
+        let n = self.pop_addr();
+        let val = self.pop_i32() as u8;
+        let d = self.pop_addr();
+
+        let mem = &mut self.store.mems[memaddr];
+        let filled = mem.with_data_mut(|data| {
+            data.get_mut(range(d, n)?).map(|bytes| bytes.fill(val))
+        });
+        if filled.is_none() {
+            return Result::Trap;
+        }
+        Result::Vals(vec![])
+    }
+
+    pub fn execute_memorycopy(&mut self, dst: &MemIdx, src: &MemIdx) -> Result {
+        let (_, frame) = self.current_frame();
+        let dst_memaddr = frame.module.memaddrs[*dst as usize];
+        let src_memaddr = frame.module.memaddrs[*src as usize];
+        let n = self.pop_addr();
+        let s = self.pop_addr();
+        let d = self.pop_addr();
+
+        let (src, dst) = match (range(s, n), range(d, n)) {
+            (Some(src), Some(dst)) => (src, dst),
+            _ => return Result::Trap,
+        };
+        let copied = if dst_memaddr == src_memaddr {
+            self.store.mems[dst_memaddr].with_data_mut(|data| {
+                if src.end <= data.len() && dst.end <= data.len() {
+                    data.copy_within(src, dst.start);
+                    true
+                } else {
+                    false
+                }
+            })
         } else {
-            unreachable!()
+            // bounds of both memories are checked before writing
+            match self.store.mems[src_memaddr].read(src.start, src.len()) {
+                Some(bytes) => self.store.mems[dst_memaddr].write(dst.start, &bytes).is_some(),
+                None => false,
+            }
         };
+        if !copied {
+            return Result::Trap;
+        }
+        Result::Vals(vec![])
+    }
 
-        let err = u32::MAX;
+    pub fn execute_memoryinit(&mut self, memidx: &MemIdx, dataidx: &DataIdx) -> Result {
+        let (_, frame) = self.current_frame();
+        let memaddr = frame.module.memaddrs[*memidx as usize];
+        let dataaddr = frame.module.dataaddrs[*dataidx as usize];
+        let n = self.pop_i32() as usize;
+        let s = self.pop_i32() as usize;
+        let d = match usize::try_from(self.pop_addr()) {
+            Ok(d) => d,
+            Err(_) => return Result::Trap,
+        };
 
-        if let Err(_) = grow_mem(&mut self.store.mems[memaddr], n as usize){
-            Result::Vals(vec![Val::I32Const(err)])
-        } else {
-            Result::Vals(vec![Val::I32Const(sz as u32)])
+        let data = &self.store.datas[dataaddr].data;
+        let mem = &mut self.store.mems[memaddr];
+        if s + n > data.len() || mem.write(d, &data[s..s + n]).is_none() {
+            return Result::Trap;
         }
+        Result::Vals(vec![])
+    }
+
+    pub fn execute_datadrop(&mut self, dataidx: &DataIdx) -> Result {
+        let (_, frame) = self.current_frame();
+        let dataaddr = frame.module.dataaddrs[*dataidx as usize];
+        self.store.datas[dataaddr].data = vec![];
+        Result::Vals(vec![])
     }
-}
\ No newline at end of file
+}
+
+// a value of the index type of a memory
+fn addr_val(idxtype: IdxType, n: u64) -> Val {
+    match idxtype {
+        IdxType::I32 => Val::I32Const(n as u32),
+        IdxType::I64 => Val::I64Const(n),
+    }
+}
+
+// the `n` bytes from `start`, None when they overflow
+fn range(start: u64, n: u64) -> Option<std::ops::Range<usize>> {
+    let start = usize::try_from(start).ok()?;
+    let end = start.checked_add(usize::try_from(n).ok()?)?;
+    Some(start..end)
+}
+
+#[test]
+fn test_bulk_memory() {
+    use crate::{store_init, module_validate, func_invoke, Module, Mem, MemType, Limits, Share, IdxType, Data, DataMode, Instr, Error};
+    use crate::testing::{exported_funcs, TestInstance};
+
+    let module = |datacount: Option<u32>| Module {
+        mems: vec![Mem(MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32))],
+        data: vec![Data { init: vec![1, 2, 3, 4], mode: DataMode::Passive }],
+        datacount,
+        ..exported_funcs(vec![(vec![], vec![ValType::I32])], vec![("f", 0, vec![
+            Instr::I32Const(8), Instr::I32Const(1), Instr::I32Const(3), Instr::MemoryInit(0, 0),
+            Instr::DataDrop(0),
+            Instr::I32Const(0), Instr::I32Const(8), Instr::I32Const(3), Instr::MemoryCopy(0, 0),
+            Instr::I32Const(1), Instr::I32Const(0xff), Instr::I32Const(1), Instr::MemoryFill(0),
+            Instr::I32Const(0), Instr::Load(ValType::I32, MemArg { offset: 0, align: 2, memory: 0 }),
+        ])])
+    };
+
+    // memory.init and data.drop need the data count section
+    assert!(module_validate(module(None)).is_err());
+
+    let mut store = store_init();
+    let instance = TestInstance::new(&mut store, module(Some(1)), vec![]);
+    assert_eq!(instance.call(&mut store, "f", vec![]).unwrap(), vec![Val::I32Const(0x0004ff02)]);
+
+    // the segment was dropped by the first call
+    let (_, result) = func_invoke(&mut store, instance.func("f"), vec![]);
+    assert!(matches!(result, Err(Error::Trap)));
+}
+
+#[test]
+fn test_memory64() {
+    use crate::{store_init, module_validate, Module, Mem, MemType, Limits, Share, IdxType, Instr, Error};
+    use crate::testing::{exported_funcs, TestInstance};
+
+    let module = |idxtype: IdxType, addr: Instr, offset: u64| Module {
+        mems: vec![Mem(MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, idxtype))],
+        ..exported_funcs(vec![(vec![], vec![ValType::I32]), (vec![], vec![ValType::I64])], vec![
+            ("store", 0, vec![
+                addr.clone(), Instr::I32Const(42), Instr::Store(ValType::I32, MemArg { offset, align: 2, memory: 0 }),
+                addr, Instr::Load(ValType::I32, MemArg { offset, align: 2, memory: 0 }),
+            ]),
+            ("grow", 1, vec![Instr::I64Const(1), Instr::MemoryGrow(0), Instr::Drop(None), Instr::MemorySize(0)]),
+        ])
+    };
+    let invoke = |module: Module, name: &str| {
+        let mut store = store_init();
+        TestInstance::new(&mut store, module, vec![]).call(&mut store, name, vec![])
+    };
+
+    let result = invoke(module(IdxType::I64, Instr::I64Const(8), 0), "store");
+    assert_eq!(result.unwrap(), vec![Val::I32Const(42)]);
+    let result = invoke(module(IdxType::I64, Instr::I64Const(8), 1 << 40), "store");
+    assert!(matches!(result, Err(Error::Trap)));
+    let result = invoke(module(IdxType::I64, Instr::I64Const(8), 0), "grow");
+    assert_eq!(result.unwrap(), vec![Val::I64Const(2)]);
+
+    // addresses must match the index type of the memory
+    assert!(module_validate(module(IdxType::I64, Instr::I32Const(8), 0)).is_err());
+    // offsets of i32 memories must fit in 32 bits
+    assert!(module_validate(module(IdxType::I32, Instr::I32Const(8), 1 << 32)).is_err());
+}
+
+#[test]
+fn test_multi_memory() {
+    use crate::{store_init, module_validate, Module, Mem, MemType, Limits, Share, IdxType, Data, DataMode, Expr, Instr, ValSize};
+    use crate::testing::{exported_funcs, TestInstance};
+
+    let mem = |min| Mem(MemType(Limits { min, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32));
+    let module = |memory: u32| Module {
+        mems: vec![mem(1), mem(2)],
+        data: vec![
+            Data { init: vec![1, 2, 3, 4], mode: DataMode::Active { memory: 1, offset: Expr(vec![Instr::I32Const(0)]) } },
+            Data { init: vec![0xfe], mode: DataMode::Active { memory: 1, offset: Expr(vec![Instr::I32Const(8)]) } },
+        ],
+        ..exported_funcs(vec![(vec![], vec![ValType::I32, ValType::I32, ValType::I32, ValType::I64])], vec![("f", 0, vec![
+            Instr::I32Const(0), Instr::I32Const(0), Instr::I32Const(4), Instr::MemoryCopy(0, 1),
+            Instr::I32Const(0), Instr::Load(ValType::I32, MemArg { offset: 0, align: 2, memory: 0 }),
+            Instr::I32Const(0), Instr::Load(ValType::I32, MemArg { offset: 0, align: 2, memory }),
+            Instr::MemorySize(1),
+            Instr::I32Const(8), Instr::ILoad8(ValSize::V64, ValSign::S, MemArg { offset: 0, align: 0, memory }),
+        ])])
+    };
+
+    // memory 2 does not exist
+    assert!(module_validate(module(2)).is_err());
+
+    let mut store = store_init();
+    let instance = TestInstance::new(&mut store, module(1), vec![]);
+    assert_eq!(instance.call(&mut store, "f", vec![]).unwrap(), vec![
+        Val::I32Const(0x04030201), Val::I32Const(0x04030201), Val::I32Const(2), Val::I64Const(0xfffffffffffffffe),
+    ]);
+}
+
+#[test]
+fn test_custom_page_sizes() {
+    use crate::{store_init, module_validate, module_instanciate, Module, Mem, MemType, Limits, Share, IdxType, Data, DataMode, Expr, Instr, Error};
+    use crate::testing::{exported_funcs, TestInstance};
+
+    let module = |page_size_log2: u32, init: Vec<u8>| Module {
+        mems: vec![Mem(MemType(Limits { min: 4, max: Some(6), page_size_log2: Some(page_size_log2) }, Share::Unshared, IdxType::I32))],
+        data: vec![Data { init, mode: DataMode::Active { memory: 0, offset: Expr(vec![Instr::I32Const(0)]) } }],
+        ..exported_funcs(vec![(vec![], vec![ValType::I32, ValType::I32, ValType::I32]), (vec![], vec![ValType::I32])], vec![
+            ("grow", 0, vec![
+                Instr::I32Const(2), Instr::MemoryGrow(0),
+                Instr::I32Const(1), Instr::MemoryGrow(0),
+                Instr::MemorySize(0),
+            ]),
+            ("load", 1, vec![Instr::I32Const(2), Instr::Load(ValType::I32, MemArg { offset: 0, align: 2, memory: 0 })]),
+        ])
+    };
+
+    // pages are of 1 byte or 64KiB
+    assert!(module_validate(module(1, vec![])).is_err());
+    // the segment is larger than the 4 bytes
+    let mut store = store_init();
+    assert!(module_instanciate(&mut store, module(0, vec![1, 2, 3, 4, 5]), vec![]).is_err());
+
+    // bytes 2 to 5 are out of bounds until the memory grows by 2 bytes
+    let mut store = store_init();
+    let instance = TestInstance::new(&mut store, module(0, vec![1, 2, 3, 4]), vec![]);
+    assert!(matches!(instance.call(&mut store, "load", vec![]), Err(Error::Trap)));
+    assert_eq!(instance.call(&mut store, "grow", vec![]).unwrap(), vec![Val::I32Const(4), Val::I32Const(-1i32 as u32), Val::I32Const(6)]);
+    assert_eq!(instance.call(&mut store, "load", vec![]).unwrap(), vec![Val::I32Const(0x00000403)]);
+}
+
+#[test]
+fn test_load_extend() {
+    use crate::{store_init, Module, Mem, MemType, Limits, Share, IdxType, Data, DataMode, Expr, Instr, ValSize};
+    use crate::testing::{exported_funcs, TestInstance};
+
+    let memarg = MemArg { offset: 0, align: 0, memory: 0 };
+    let mut init = vec![0x80, 0xff, 0xff, 0xff];
+    init.extend(1.5f32.to_le_bytes());
+    init.extend((-0.25f64).to_le_bytes());
+    let module = Module {
+        mems: vec![Mem(MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32))],
+        data: vec![Data { init, mode: DataMode::Active { memory: 0, offset: Expr(vec![Instr::I32Const(0)]) } }],
+        ..exported_funcs(
+            vec![(vec![], vec![ValType::I32, ValType::I32, ValType::I64, ValType::I64, ValType::I64, ValType::F32, ValType::F64])],
+            vec![("f", 0, vec![
+                Instr::I32Const(0), Instr::ILoad8(ValSize::V32, ValSign::S, memarg.clone()),
+                Instr::I32Const(0), Instr::ILoad8(ValSize::V32, ValSign::U, memarg.clone()),
+                Instr::I32Const(0), Instr::ILoad16(ValSize::V64, ValSign::S, memarg.clone()),
+                Instr::I32Const(0), Instr::I64Load32(ValSign::S, memarg.clone()),
+                Instr::I32Const(0), Instr::I64Load32(ValSign::U, memarg.clone()),
+                Instr::I32Const(4), Instr::Load(ValType::F32, memarg.clone()),
+                Instr::I32Const(8), Instr::Load(ValType::F64, memarg),
+            ])],
+        )
+    };
+
+    let mut store = store_init();
+    let instance = TestInstance::new(&mut store, module, vec![]);
+    assert_eq!(instance.call(&mut store, "f", vec![]).unwrap(), vec![
+        Val::I32Const(0xffffff80), Val::I32Const(0x80),
+        Val::I64Const(0xffffffffffffff80), Val::I64Const(0xffffffffffffff80), Val::I64Const(0xffffff80),
+        Val::F32Const(1.5), Val::F64Const(-0.25),
+    ]);
+}
+
+#[test]
+fn test_data_segments_in_order() {
+    use crate::{store_init, mem_alloc, module_instanciate, Module, MemType, Limits, Share, IdxType, ImportDesc, ExternVal, Data, DataMode, Expr, Instr};
+    use crate::testing::import;
+
+    let memtype = MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32);
+    let data = |offset: u32, init: &[u8]| Data { init: init.to_vec(), mode: DataMode::Active { memory: 0, offset: Expr(vec![Instr::I32Const(offset)]) } };
+    let module = Module {
+        imports: vec![import("mem", ImportDesc::Mem(memtype.clone()))],
+        data: vec![data(0, b"ab"), data(0xffff, b"cd"), data(2, b"ef")],
+        ..Module::default()
+    };
+
+    // the segment before the one out of bounds is written to the imported memory, the one after it is not
+    let mut store = store_init();
+    let memaddr = mem_alloc(&mut store, memtype).unwrap();
+    assert!(module_instanciate(&mut store, module, vec![ExternVal::Mem(memaddr)]).is_err());
+    assert_eq!(store.mems[memaddr].read(0, 4).unwrap(), b"ab\0\0");
+}
@@ -45,12 +45,12 @@ pub fn fceil32(f: f32) -> f32 { f.ceil() }
 pub fn ffloor32(f: f32) -> f32 { f.floor() }
 pub fn ftrunc32(f: f32) -> f32 { f.trunc() }
 pub fn fnearest32(f: f32) -> f32 { f.round() }
-pub fn feq32(c1: f32, c2: f32) -> f32 { if c1 == c2 { 1.0 } else { 0.0 } }
-pub fn fne32(c1: f32, c2: f32) -> f32 { if c1 != c2 { 1.0 } else { 0.0 } }
-pub fn flt32(c1: f32, c2: f32) -> f32 { if c1 < c2 { 1.0 } else { 0.0 } }
-pub fn fgt32(c1: f32, c2: f32) -> f32 { if c1 > c2 { 1.0 } else { 0.0 } }
-pub fn fle32(c1: f32, c2: f32) -> f32 { if c1 <= c2 { 1.0 } else { 0.0 } }
-pub fn fge32(c1: f32, c2: f32) -> f32 { if c1 >= c2 { 1.0 } else { 0.0 } }
+pub fn feq32(c1: f32, c2: f32) -> u32 { if c1 == c2 { 1 } else { 0 } }
+pub fn fne32(c1: f32, c2: f32) -> u32 { if c1 != c2 { 1 } else { 0 } }
+pub fn flt32(c1: f32, c2: f32) -> u32 { if c1 < c2 { 1 } else { 0 } }
+pub fn fgt32(c1: f32, c2: f32) -> u32 { if c1 > c2 { 1 } else { 0 } }
+pub fn fle32(c1: f32, c2: f32) -> u32 { if c1 <= c2 { 1 } else { 0 } }
+pub fn fge32(c1: f32, c2: f32) -> u32 { if c1 >= c2 { 1 } else { 0 } }
 
 // extend_u
 // fn extend8s32(_c: u8) -> u32 { unimplemented!() }
@@ -18,17 +18,17 @@ pub fn irotr64(c1: u64, c2: u64) -> u64 { c1.rotate_right((c2 % 64) as u32) }
 pub fn iclz64(i: u64) -> u64 { i.leading_zeros().into() }
 pub fn ictz64(i: u64) -> u64 { i.trailing_zeros().into() }
 pub fn ipopcnt64(i: u64) -> u64 { i.count_ones().into() }
-pub fn ieqz64(i: u64) -> u64 { if i == 0 { 1 } else { 0 } }
-pub fn ieq64(c1: u64, c2: u64) -> u64 { if c1 == c2 { 1 } else { 0 } }
-pub fn ine64(c1: u64, c2: u64) -> u64 { if c1 != c2 { 1 } else { 0 } }
-pub fn ilt_u64(c1: u64, c2: u64) -> u64 { if c1 < c2 { 1 } else { 0 } }
-pub fn ilt_s64(c1: u64, c2: u64) -> u64 { if signed64(c1) < signed64(c2) { 1 } else { 0 } }
-pub fn igt_u64(c1: u64, c2: u64) -> u64 { if c1 > c2 { 1 } else { 0 } }
-pub fn igt_s64(c1: u64, c2: u64) -> u64 { if signed64(c1) > signed64(c2) { 1 } else { 0 } }
-pub fn ile_u64(c1: u64, c2: u64) -> u64 { if c1 <= c2 { 1 } else { 0 } }
-pub fn ile_s64(c1: u64, c2: u64) -> u64 { if signed64(c1) <= signed64(c2) { 1 } else { 0 } }
-pub fn ige_u64(c1: u64, c2: u64) -> u64 { if c1 >= c2 { 1 } else { 0 } }
-pub fn ige_s64(c1: u64, c2: u64) -> u64 { if signed64(c1) >= signed64(c2) { 1 } else { 0 } }
+pub fn ieqz64(i: u64) -> u32 { if i == 0 { 1 } else { 0 } }
+pub fn ieq64(c1: u64, c2: u64) -> u32 { if c1 == c2 { 1 } else { 0 } }
+pub fn ine64(c1: u64, c2: u64) -> u32 { if c1 != c2 { 1 } else { 0 } }
+pub fn ilt_u64(c1: u64, c2: u64) -> u32 { if c1 < c2 { 1 } else { 0 } }
+pub fn ilt_s64(c1: u64, c2: u64) -> u32 { if signed64(c1) < signed64(c2) { 1 } else { 0 } }
+pub fn igt_u64(c1: u64, c2: u64) -> u32 { if c1 > c2 { 1 } else { 0 } }
+pub fn igt_s64(c1: u64, c2: u64) -> u32 { if signed64(c1) > signed64(c2) { 1 } else { 0 } }
+pub fn ile_u64(c1: u64, c2: u64) -> u32 { if c1 <= c2 { 1 } else { 0 } }
+pub fn ile_s64(c1: u64, c2: u64) -> u32 { if signed64(c1) <= signed64(c2) { 1 } else { 0 } }
+pub fn ige_u64(c1: u64, c2: u64) -> u32 { if c1 >= c2 { 1 } else { 0 } }
+pub fn ige_s64(c1: u64, c2: u64) -> u32 { if signed64(c1) >= signed64(c2) { 1 } else { 0 } }
 // iextendM_s
 
 pub fn fadd64(c1: f64, c2: f64) -> f64 { c1 + c2 }
@@ -45,12 +45,12 @@ pub fn fceil64(f: f64) -> f64 { f.ceil() }
 pub fn ffloor64(f: f64) -> f64 { f.floor() }
 pub fn ftrunc64(f: f64) -> f64 { f.trunc() }
 pub fn fnearest64(f: f64) -> f64 { f.round() }
-pub fn feq64(c1: f64, c2: f64) -> f64 { if c1 == c2 { 1.0 } else { 0.0 } }
-pub fn fne64(c1: f64, c2: f64) -> f64 { if c1 != c2 { 1.0 } else { 0.0 } }
-pub fn flt64(c1: f64, c2: f64) -> f64 { if c1 < c2 { 1.0 } else { 0.0 } }
-pub fn fgt64(c1: f64, c2: f64) -> f64 { if c1 > c2 { 1.0 } else { 0.0 } }
-pub fn fle64(c1: f64, c2: f64) -> f64 { if c1 <= c2 { 1.0 } else { 0.0 } }
-pub fn fge64(c1: f64, c2: f64) -> f64 { if c1 >= c2 { 1.0 } else { 0.0 } }
+pub fn feq64(c1: f64, c2: f64) -> u32 { if c1 == c2 { 1 } else { 0 } }
+pub fn fne64(c1: f64, c2: f64) -> u32 { if c1 != c2 { 1 } else { 0 } }
+pub fn flt64(c1: f64, c2: f64) -> u32 { if c1 < c2 { 1 } else { 0 } }
+pub fn fgt64(c1: f64, c2: f64) -> u32 { if c1 > c2 { 1 } else { 0 } }
+pub fn fle64(c1: f64, c2: f64) -> u32 { if c1 <= c2 { 1 } else { 0 } }
+pub fn fge64(c1: f64, c2: f64) -> u32 { if c1 >= c2 { 1 } else { 0 } }
 
 // extend_u
 // extend_s
@@ -0,0 +1,120 @@
+use crate::{
+    FuncIdx,
+    ValSign,
+    RefType,
+    HeapType,
+    heap_matches,
+    functype_matches,
+};
+
+use super::*;
+
+impl<'a> Thread<'a> {
+    pub fn execute_refisnull(&mut self) -> Result {
+        let is_null = matches!(self.pop_ref(), Ref::Null(_));
+        Result::i32val(is_null as u32)
+    }
+
+    pub fn execute_reffunc(&mut self, funcidx: &FuncIdx) -> Result {
+        let (_, frame) = self.current_frame();
+        let funcaddr = frame.module.funcaddrs[*funcidx as usize];
+        Result::Vals(vec![Val::Ref(Ref::Func(funcaddr))])
+    }
+
+    pub fn execute_refasnonnull(&mut self) -> Result {
+        match self.pop_ref() {
+            Ref::Null(_) => Result::Trap,
+            r => Result::Vals(vec![Val::Ref(r)]),
+        }
+    }
+
+    pub fn execute_refeq(&mut self) -> Result {
+        let r2 = self.pop_ref();
+        let r1 = self.pop_ref();
+        let eq = match (r1, r2) {
+            (Ref::Null(_), Ref::Null(_)) => true,
+            (r1, r2) => r1 == r2,
+        };
+        Result::i32val(eq as u32)
+    }
+
+    pub fn execute_refconvert(&mut self) -> Result {
+        let r = self.pop_ref();
+        Result::Vals(vec![Val::Ref(r.convert())])
+    }
+
+    pub fn execute_refi31(&mut self) -> Result {
+        let n = self.pop_i32();
+        Result::Vals(vec![Val::Ref(Ref::I31(n & 0x7FFF_FFFF))])
+    }
+
+    pub fn execute_i31get(&mut self, sign: &ValSign) -> Result {
+        match (self.pop_ref(), sign) {
+            (Ref::I31(n), ValSign::U) => Result::i32val(n),
+            (Ref::I31(n), ValSign::S) => Result::i32val(((n << 1) as i32 >> 1) as u32),
+            _ => Result::Trap,
+        }
+    }
+
+    /// Whether the reference `r` has the type `rt`, in the types of the current module.
+    pub(super) fn ref_matches(&self, r: &Ref, rt: &RefType) -> bool {
+        ref_matches(self.store, r, rt, &self.current_types())
+    }
+
+    pub fn execute_reftest(&mut self, rt: &RefType) -> Result {
+        let r = self.pop_ref();
+        Result::i32val(self.ref_matches(&r, rt) as u32)
+    }
+
+    pub fn execute_refcast(&mut self, rt: &RefType) -> Result {
+        let r = self.pop_ref();
+        if self.ref_matches(&r, rt) {
+            Result::Vals(vec![Val::Ref(r)])
+        } else {
+            Result::Trap
+        }
+    }
+}
+
+/// Whether the reference `r` has the type `rt`, in the types `types`.
+fn ref_matches(store: &Store, r: &Ref, rt: &RefType, types: &[SubType]) -> bool {
+    match r {
+        Ref::Null(_) => rt.nullable,
+        Ref::I31(_) => heap_matches(&[], HeapType::I31, types, rt.heap),
+        // a reference kept by the host may have been collected
+        Ref::Struct(addr) | Ref::Array(addr) => match store.heap.try_get(*addr) {
+            Some(obj) => heap_matches(&obj.types, HeapType::Type(obj.tp), types, rt.heap),
+            None => false,
+        },
+        Ref::Func(addr) => match rt.heap {
+            HeapType::Type(y) => func_matches(store, *addr, types, y),
+            heap => heap == HeapType::Func,
+        },
+        Ref::Extern(_) | Ref::ExternAny(_) => rt.heap == HeapType::Extern,
+        Ref::AnyExtern(_) => rt.heap == HeapType::Any,
+        Ref::Exn(_) => rt.heap == HeapType::Exn,
+    }
+}
+
+/// Whether the function at `funcaddr` has the type `typeidx` of `types`, or a subtype of it.
+pub(crate) fn func_matches(store: &Store, funcaddr: FuncAddr, types: &[SubType], typeidx: TypeIdx) -> bool {
+    match &store.funcs[funcaddr] {
+        FuncInst::User(f) => heap_matches(&f.module.types, HeapType::Type(f.code.tp), types, HeapType::Type(typeidx)),
+        // the type of a host function has no index, nor supertypes
+        FuncInst::Host(f) => types.get(typeidx as usize).and_then(SubType::functype)
+            .is_some_and(|functype| functype_matches(&[], &f.tp, types, functype)),
+    }
+}
+
+/// Whether the value `val` passed in by the host has the type `valtype`, in the types `types`.
+pub(crate) fn val_matches(store: &Store, val: &Val, valtype: &ValType, types: &[SubType]) -> bool {
+    match (val, valtype.reftype()) {
+        // a null also has to be of the hierarchy of the type
+        (Val::Ref(r @ Ref::Null(_)), Some(rt)) => {
+            let top = ValType::Ref(RefType { nullable: true, heap: rt.heap.top(types) });
+            val.valtype().matches(&top, types) && ref_matches(store, r, &rt, types)
+        },
+        (Val::Ref(r), Some(rt)) => ref_matches(store, r, &rt, types),
+        _ => val.valtype() == *valtype,
+    }
+}
@@ -39,11 +39,11 @@ impl<'a> Thread<'a> {
     pub fn execute_ige_u64(&mut self) -> Result { self.execute_irelop64(ige_u64) }
     pub fn execute_ige_s64(&mut self) -> Result { self.execute_irelop64(ige_s64) }
 
-    fn execute_irelop64(&mut self, func: fn(u64, u64) -> u64) -> Result {
+    fn execute_irelop64(&mut self, func: fn(u64, u64) -> u32) -> Result {
         // assert!(self.stack.len() >= 2);
         if let Some(StackEntry::Value(Val::I64Const(c2))) = self.stack.pop() {
             if let Some(StackEntry::Value(Val::I64Const(c1))) = self.stack.pop() {
-                Result::Vals(vec![Val::I64Const(func(c1, c2))])
+                Result::Vals(vec![Val::I32Const(func(c1, c2))])
             } else {
                 Result::Trap
             }
@@ -61,11 +61,11 @@ impl<'a> Thread<'a> {
     pub fn execute_fle32(&mut self) -> Result { self.execute_frelop32(fle32) }
     pub fn execute_fge32(&mut self) -> Result { self.execute_frelop32(fge32) }
 
-    fn execute_frelop32(&mut self, func: fn(f32, f32) -> f32) -> Result {
+    fn execute_frelop32(&mut self, func: fn(f32, f32) -> u32) -> Result {
         // assert!(self.stack.len() >= 2);
         if let Some(StackEntry::Value(Val::F32Const(c2))) = self.stack.pop() {
             if let Some(StackEntry::Value(Val::F32Const(c1))) = self.stack.pop() {
-                Result::Vals(vec![Val::F32Const(func(c1, c2))])
+                Result::Vals(vec![Val::I32Const(func(c1, c2))])
             } else {
                 Result::Trap
             }
@@ -81,11 +81,11 @@ impl<'a> Thread<'a> {
     pub fn execute_fle64(&mut self) -> Result { self.execute_frelop64(fle64) }
     pub fn execute_fge64(&mut self) -> Result { self.execute_frelop64(fge64) }
 
-    fn execute_frelop64(&mut self, func: fn(f64, f64) -> f64) -> Result {
+    fn execute_frelop64(&mut self, func: fn(f64, f64) -> u32) -> Result {
         // assert!(self.stack.len() >= 2);
         if let Some(StackEntry::Value(Val::F64Const(c2))) = self.stack.pop() {
             if let Some(StackEntry::Value(Val::F64Const(c1))) = self.stack.pop() {
-                Result::Vals(vec![Val::F64Const(func(c1, c2))])
+                Result::Vals(vec![Val::I32Const(func(c1, c2))])
             } else {
                 Result::Trap
             }
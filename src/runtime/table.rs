@@ -0,0 +1,224 @@
+use crate::{
+    TableIdx,
+    ElemIdx,
+
+    grow_table,
+};
+
+use super::*;
+
+impl<'a> Thread<'a> {
+    fn tableaddr(&self, tableidx: &TableIdx) -> TableAddr {
+        let (_, frame) = self.current_frame();
+        frame.module.tableaddrs[*tableidx as usize]
+    }
+
+    pub fn execute_tableget(&mut self, tableidx: &TableIdx) -> Result {
+        let tableaddr = self.tableaddr(tableidx);
+        let i = self.pop_i32() as usize;
+
+        let table = &self.store.tables[tableaddr];
+        match table.elem.get(i) {
+            Some(r) => Result::Vals(vec![Val::Ref(*r)]),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_tableset(&mut self, tableidx: &TableIdx) -> Result {
+        let tableaddr = self.tableaddr(tableidx);
+        let val = self.pop_ref();
+        let i = self.pop_i32() as usize;
+
+        let table = &mut self.store.tables[tableaddr];
+        match table.elem.get_mut(i) {
+            Some(r) => {
+                *r = val;
+                Result::Vals(vec![])
+            },
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_tablesize(&mut self, tableidx: &TableIdx) -> Result {
+        let tableaddr = self.tableaddr(tableidx);
+        let sz = self.store.tables[tableaddr].elem.len();
+        Result::i32val(sz as u32)
+    }
+
+    pub fn execute_tablegrow(&mut self, tableidx: &TableIdx) -> Result {
+        let tableaddr = self.tableaddr(tableidx);
+        let n = self.pop_i32();
+        let val = self.pop_ref();
+
+        let sz = self.store.tables[tableaddr].elem.len();
+        let err = u32::MAX;
+
+        if grow_table(self.store, tableaddr, n as usize, val).is_err() {
+            Result::i32val(err)
+        } else {
+            Result::i32val(sz as u32)
+        }
+    }
+
+    pub fn execute_tablefill(&mut self, tableidx: &TableIdx) -> Result {
+        let tableaddr = self.tableaddr(tableidx);
+        let n = self.pop_i32() as usize;
+        let val = self.pop_ref();
+        let i = self.pop_i32() as usize;
+
+        let table = &mut self.store.tables[tableaddr];
+        if i + n > table.elem.len() {
+            return Result::Trap;
+        }
+        table.elem[i..i + n].fill(val);
+        Result::Vals(vec![])
+    }
+
+    pub fn execute_tablecopy(&mut self, tableidx1: &TableIdx, tableidx2: &TableIdx) -> Result {
+        let tableaddr1 = self.tableaddr(tableidx1);
+        let tableaddr2 = self.tableaddr(tableidx2);
+        let n = self.pop_i32() as usize;
+        let s = self.pop_i32() as usize;
+        let d = self.pop_i32() as usize;
+
+        if s + n > self.store.tables[tableaddr2].elem.len() || d + n > self.store.tables[tableaddr1].elem.len() {
+            return Result::Trap;
+        }
+        if tableaddr1 == tableaddr2 {
+            self.store.tables[tableaddr1].elem.copy_within(s..s + n, d);
+        } else {
+            let src = self.store.tables[tableaddr2].elem[s..s + n].to_vec();
+            self.store.tables[tableaddr1].elem[d..d + n].copy_from_slice(&src);
+        }
+        Result::Vals(vec![])
+    }
+
+    pub fn execute_tableinit(&mut self, tableidx: &TableIdx, elemidx: &ElemIdx) -> Result {
+        let tableaddr = self.tableaddr(tableidx);
+        let (_, frame) = self.current_frame();
+        let elemaddr = frame.module.elemaddrs[*elemidx as usize];
+        let n = self.pop_i32() as usize;
+        let s = self.pop_i32() as usize;
+        let d = self.pop_i32() as usize;
+
+        let elem = &self.store.elems[elemaddr].elem;
+        let table = &mut self.store.tables[tableaddr];
+        if s + n > elem.len() || d + n > table.elem.len() {
+            return Result::Trap;
+        }
+        table.elem[d..d + n].copy_from_slice(&elem[s..s + n]);
+        Result::Vals(vec![])
+    }
+
+    pub fn execute_elemdrop(&mut self, elemidx: &ElemIdx) -> Result {
+        let (_, frame) = self.current_frame();
+        let elemaddr = frame.module.elemaddrs[*elemidx as usize];
+        self.store.elems[elemaddr].elem = vec![];
+        Result::Vals(vec![])
+    }
+}
+
+#[test]
+fn test_reference_types() {
+    use crate::{
+        store_init, module_validate,
+        Module, Table, TableType, Limits, RefType, HeapType, Elem, ElemMode, Expr, Instr, IBinOp, ValSize, ValType, Ref,
+    };
+    use crate::testing::{exported_funcs, func, TestInstance};
+
+    let module = Module {
+        tables: vec![
+            Table(TableType(Limits { min: 2, max: None, page_size_log2: None }, RefType { nullable: true, heap: HeapType::Extern }), None),
+            Table(TableType(Limits { min: 1, max: None, page_size_log2: None }, RefType { nullable: true, heap: HeapType::Func }), None),
+        ],
+        elem: vec![Elem {
+            tp: RefType { nullable: true, heap: HeapType::Func },
+            init: vec![Expr(vec![Instr::RefFunc(1)])],
+            mode: ElemMode::Active { table: 1, offset: Expr(vec![Instr::I32Const(0)]) },
+        }],
+        ..exported_funcs(vec![(vec![ValType::ExternRef], vec![ValType::ExternRef]), (vec![], vec![ValType::I32])], vec![
+            ("roundtrip", 0, vec![Instr::I32Const(1), Instr::LocalGet(0), Instr::TableSet(0), Instr::I32Const(1), Instr::TableGet(0)]),
+            ("seven", 1, vec![Instr::I32Const(7)]),
+            ("call", 1, vec![Instr::I32Const(0), Instr::CallIndirect(1, 1)]),
+            ("grow", 1, vec![
+                Instr::RefNull(HeapType::Extern), Instr::I32Const(3), Instr::TableGrow(0), Instr::Drop(None),
+                Instr::I32Const(4), Instr::TableGet(0), Instr::RefIsNull,
+                Instr::TableSize(0), Instr::IBinOp(ValSize::V32, IBinOp::Add),
+            ]),
+        ])
+    };
+
+    let mut store = store_init();
+    let instance = TestInstance::new(&mut store, module, vec![]);
+    // host references pass through unchanged
+    let result = instance.call(&mut store, "roundtrip", vec![Val::Ref(Ref::Extern(42))]);
+    assert_eq!(result.unwrap(), vec![Val::Ref(Ref::Extern(42))]);
+    assert_eq!(instance.call(&mut store, "call", vec![]).unwrap(), vec![Val::I32Const(7)]);
+    assert_eq!(instance.call(&mut store, "grow", vec![]).unwrap(), vec![Val::I32Const(6)]);
+
+    // ref.func needs a declared function
+    let module = Module {
+        types: vec![(vec![], vec![]).into()],
+        funcs: vec![func(0, vec![Instr::RefFunc(0), Instr::Drop(None)])],
+        ..Module::default()
+    };
+    assert!(module_validate(module).is_err());
+}
+
+#[test]
+fn test_typed_table() {
+    use crate::{
+        store_init, module_decode, module_validate, encoder::module2wasm,
+        Module, Table, TableType, Limits, RefType, HeapType, Expr, Instr, ValType,
+    };
+    use crate::testing::{exported_funcs, TestInstance};
+
+    // (table 2 (ref 0) (ref.func 0)), round-tripped through the binary format
+    let table = |init| Table(TableType(Limits { min: 2, max: None, page_size_log2: None }, RefType { nullable: false, heap: HeapType::Type(0) }), init);
+    let module = || Module {
+        tables: vec![table(Some(Expr(vec![Instr::RefFunc(0)])))],
+        ..exported_funcs(vec![(vec![], vec![ValType::I32])], vec![
+            ("seven", 0, vec![Instr::I32Const(7)]),
+            ("call", 0, vec![Instr::I32Const(1), Instr::CallIndirect(0, 0)]),
+        ])
+    };
+    let decoded = module_decode(&mut module2wasm(&module()).as_slice()).unwrap();
+
+    let mut store = store_init();
+    let instance = TestInstance::new(&mut store, decoded, vec![]);
+    assert_eq!(instance.call(&mut store, "call", vec![]).unwrap(), vec![Val::I32Const(7)]);
+
+    // non-nullable tables can't start out null
+    let module = Module { tables: vec![table(None)], ..module() };
+    assert!(module_validate(module).is_err());
+}
+
+
+#[test]
+fn test_elem_segments_in_order() {
+    use crate::{
+        store_init, table_alloc, module_instanciate, func_invoke,
+        Module, TableType, Limits, RefType, HeapType, Elem, ElemMode, Expr, Instr, ImportDesc, ExternVal, ValType, Ref,
+    };
+    use crate::testing::{exported_funcs, import};
+
+    let tabletype = TableType(Limits { min: 2, max: None, page_size_log2: None }, RefType { nullable: true, heap: HeapType::Func });
+    let elem = |offset| Elem {
+        tp: RefType { nullable: true, heap: HeapType::Func },
+        init: vec![Expr(vec![Instr::RefFunc(0)])],
+        mode: ElemMode::Active { table: 0, offset: Expr(vec![Instr::I32Const(offset)]) },
+    };
+    let module = Module {
+        imports: vec![import("table", ImportDesc::Table(tabletype.clone()))],
+        elem: vec![elem(0), elem(2)],
+        ..exported_funcs(vec![(vec![], vec![ValType::I32])], vec![("seven", 0, vec![Instr::I32Const(7)])])
+    };
+
+    // the function written to the imported table before the segment out of bounds
+    // stays in the store with its instance
+    let mut store = store_init();
+    let tableaddr = table_alloc(&mut store, tabletype).unwrap();
+    assert!(module_instanciate(&mut store, module, vec![ExternVal::Table(tableaddr)]).is_err());
+    let Ref::Func(funcaddr) = store.tables[tableaddr].elem[0] else { panic!("expected a function") };
+    assert_eq!(func_invoke(&mut store, funcaddr, vec![]).1.unwrap(), vec![Val::I32Const(7)]);
+}
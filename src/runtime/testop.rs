@@ -19,10 +19,10 @@ impl<'a> Thread<'a> {
 
     pub fn execute_ieqz64(&mut self) -> Result { self.execute_itestop64(ieqz64) }
 
-    fn execute_itestop64(&mut self, func: fn(u64) -> u64) -> Result {
+    fn execute_itestop64(&mut self, func: fn(u64) -> u32) -> Result {
         // assert!(vals.len() >= 1);
         if let Some(StackEntry::Value(Val::I64Const(c))) = self.stack.pop() {
-            Result::Vals(vec![Val::I64Const(func(c))])
+            Result::Vals(vec![Val::I32Const(func(c))])
         } else {
             Result::Trap
         }
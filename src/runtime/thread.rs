@@ -12,30 +12,145 @@ use crate::{
     FRelOp,
     CvtOp,
     ValType,
+    Error,
+    val_matches,
 };
 
 use super::*;
 
 
 impl<'a> Thread<'a> {
-    pub fn spawn(&mut self, instrs: &Vec<Instr>) {
-        self.execute_instrs(instrs);
+    /// Execute `instrs` in the current frame, leaving their results on the stack.
+    /// Constant expressions are not metered.
+    pub fn spawn(&mut self, instrs: &[Instr]) -> Result {
+        let base = self.stack.len();
+        self.stack.push(StackEntry::Label(Label{ arity: 0, instrs: instrs.into(), pc: 0, is_loop: false }));
+        let fuel = self.store.fuel.take();
+        self.store.heap.constants += 1;
+        let result = self.run(base);
+        self.store.heap.constants -= 1;
+        self.store.fuel = fuel;
+        result
     }
 
-    pub fn execute_instrs(&mut self, instrs: &Vec<Instr>) -> Result {
-        for instr in instrs {
-            match self.execute_instr(instr) {
+    /// Invoke the function at `funcaddr` with `args` and run it until it returns or suspends.
+    pub fn execute_func(&mut self, funcaddr: &FuncAddr, args: Vec<Val>) -> std::result::Result<Invocation, Error> {
+        let base = self.stack.len();
+        self.store.heap.threads += 1;
+        self.stack.extend(args.into_iter().map(StackEntry::Value));
+        let result = match self.execute_invoke(funcaddr) {
+            Result::Vals(vals) => {
+                self.stack.extend(vals.into_iter().map(StackEntry::Value));
+                self.run(base)
+            },
+            result => result,
+        };
+        self.complete(base, result)
+    }
+
+    /// Continue a suspended execution, `vals` being the results of the yielding host function.
+    pub fn resume(&mut self, suspension: Suspension, vals: Vec<Val>) -> std::result::Result<Invocation, Error> {
+        let base = self.stack.len();
+        let Suspension{ stack, suspended, .. } = suspension;
+        // the stack is a root of this thread from now on
+        drop(suspended);
+        self.store.heap.threads += 1;
+        self.stack.extend(stack);
+        self.stack.extend(vals.into_iter().map(StackEntry::Value));
+        let result = self.run(base);
+        self.complete(base, result)
+    }
+
+    fn complete(&mut self, base: usize, result: Result) -> std::result::Result<Invocation, Error> {
+        self.store.heap.threads -= 1;
+        match result {
+            Result::Vals(_) => {
+                let vals = self.stack.split_off(base).into_iter().map(|entry| match entry {
+                    StackEntry::Value(val) => val,
+                    _ => unreachable!(),
+                }).collect();
+                Ok(Invocation::Done(vals))
+            },
+            Result::Trap => Err(Error::Trap),
+            Result::Throw(exnaddr) => Err(Error::Exception(exnaddr)),
+            Result::Interrupted => Err(Error::Interrupted),
+            Result::Exit(code) => Err(Error::Exit(code)),
+            Result::Suspended(reason) => {
+                let stack = self.stack.split_off(base);
+                let suspended = self.store.heap.suspended.clone();
+                Ok(Invocation::Suspended(Suspension{ reason, future: self.pending.take(), stack, suspended }))
+            },
+        }
+    }
+
+    /// Execute until no label or activation above `base` is left.
+    fn run(&mut self, base: usize) -> Result {
+        while let Some((instrs, pc)) = self.next_instr(base) {
+            if let Some(fuel) = &mut self.store.fuel {
+                if *fuel == 0 {
+                    self.rewind_instr();
+                    return Result::Suspended(SuspendReason::Fuel);
+                }
+                *fuel -= 1;
+            }
+            match self.execute_instr(&instrs[pc]) {
                 Result::Vals(vals) => {
-                    let vals: Vec<StackEntry> = vals.iter()
-                        .map(|v| StackEntry::Value(v.clone())).collect();
-                    self.stack.extend(vals);
+                    self.stack.extend(vals.into_iter().map(StackEntry::Value));
+                },
+                Result::Suspended(reason) => return Result::Suspended(reason),
+                Result::Throw(exnaddr) => match self.catch_exception(base, exnaddr) {
+                    Result::Vals(_) => {},
+                    result => {
+                        self.stack.truncate(base);
+                        return result;
+                    },
+                },
+                result => {
+                    self.stack.truncate(base);
+                    return result;
                 },
-                Result::Trap => return Result::Trap,
             }
         }
         Result::Vals(vec![])
     }
 
+    /// Undo the last `next_instr`, which left its label on top of the control entries.
+    fn rewind_instr(&mut self) {
+        for entry in self.stack.iter_mut().rev() {
+            if let StackEntry::Label(label) = entry {
+                label.pc -= 1;
+                return;
+            }
+        }
+    }
+
+    /// Body of the innermost label and the position of its next instruction.
+    fn next_instr(&mut self, base: usize) -> Option<(Arc<[Instr]>, usize)> {
+        loop {
+            let idx = self.stack.iter().rposition(|entry| !matches!(entry, StackEntry::Value(_)))?;
+            if idx < base { return None; }
+            match &mut self.stack[idx] {
+                StackEntry::Label(label) => {
+                    if label.pc < label.instrs.len() {
+                        label.pc += 1;
+                        return Some((label.instrs.clone(), label.pc - 1));
+                    }
+                    // end of the block: the results stay on the stack
+                    self.stack.remove(idx);
+                },
+                StackEntry::Activation(_, _) => {
+                    // end of the function body
+                    self.return_from(idx);
+                },
+                StackEntry::Handler(_) => {
+                    // end of the try_table, after its label
+                    self.stack.remove(idx);
+                },
+                StackEntry::Value(_) => unreachable!(),
+            }
+        }
+    }
+
     fn execute_instr(&mut self, instr: &Instr) -> Result {
         match instr {
             /* Block Instructions */
@@ -44,6 +159,7 @@ impl<'a> Thread<'a> {
             Instr::Block(blocktype, instrs) => self.execute_block(blocktype, instrs),
             Instr::Loop(blocktype, instrs) => self.execute_loop(blocktype, instrs),
             Instr::If(blocktype, instrs1, instrs2) => self.execute_if(blocktype, instrs1, instrs2),
+            Instr::TryTable(blocktype, catches, instrs) => self.execute_trytable(blocktype, catches, instrs),
     
     
             /* Plain Instructions */
@@ -54,9 +170,49 @@ impl<'a> Thread<'a> {
             Instr::Br(labelidx) => self.execute_br(labelidx),
             Instr::BrIf(labelidx) => self.execute_brif(labelidx),
             Instr::BrTable(labelindices, labelidx) => self.execute_brtable(labelindices, labelidx),
+            Instr::BrOnNull(labelidx) => self.execute_bronnull(labelidx),
+            Instr::BrOnNonNull(labelidx) => self.execute_bronnonnull(labelidx),
+            Instr::BrOnCast(labelidx, _, rt) => self.execute_broncast(labelidx, rt, true),
+            Instr::BrOnCastFail(labelidx, _, rt) => self.execute_broncast(labelidx, rt, false),
             Instr::Return => self.execute_return(),
             Instr::Call(funcidx) => self.execute_call(funcidx),
-            Instr::CallIndirect(funcidx) => self.execute_callindirect(funcidx),
+            Instr::CallIndirect(tableidx, typeidx) => self.execute_callindirect(tableidx, typeidx),
+            Instr::ReturnCall(funcidx) => self.execute_returncall(funcidx),
+            Instr::ReturnCallIndirect(tableidx, typeidx) => self.execute_returncallindirect(tableidx, typeidx),
+            Instr::CallRef(_) => self.execute_callref(),
+            Instr::ReturnCallRef(_) => self.execute_returncallref(),
+            Instr::Throw(tagidx) => self.execute_throw(tagidx),
+            Instr::ThrowRef => self.execute_throwref(),
+
+            // Reference Instructions
+            Instr::RefNull(heaptype) => Result::Vals(vec![Val::Ref(Ref::Null(heaptype.elemtype(&self.current_types())))]),
+            Instr::RefIsNull => self.execute_refisnull(),
+            Instr::RefFunc(funcidx) => self.execute_reffunc(funcidx),
+            Instr::RefAsNonNull => self.execute_refasnonnull(),
+            Instr::RefEq => self.execute_refeq(),
+            Instr::RefTest(rt) => self.execute_reftest(rt),
+            Instr::RefCast(rt) => self.execute_refcast(rt),
+
+            // Aggregate Instructions
+            Instr::StructNew(typeidx) => self.execute_structnew(typeidx),
+            Instr::StructNewDefault(typeidx) => self.execute_structnewdefault(typeidx),
+            Instr::StructGet(sign, typeidx, fieldidx) => self.execute_structget(sign, typeidx, fieldidx),
+            Instr::StructSet(typeidx, fieldidx) => self.execute_structset(typeidx, fieldidx),
+            Instr::ArrayNew(typeidx) => self.execute_arraynew(typeidx),
+            Instr::ArrayNewDefault(typeidx) => self.execute_arraynewdefault(typeidx),
+            Instr::ArrayNewFixed(typeidx, n) => self.execute_arraynewfixed(typeidx, n),
+            Instr::ArrayGet(sign, typeidx) => self.execute_arrayget(sign, typeidx),
+            Instr::ArraySet(typeidx) => self.execute_arrayset(typeidx),
+            Instr::ArrayLen => self.execute_arraylen(),
+            Instr::ArrayFill(typeidx) => self.execute_arrayfill(typeidx),
+            Instr::ArrayCopy(_, _) => self.execute_arraycopy(),
+            Instr::ArrayNewData(typeidx, dataidx) => self.execute_arraynewdata(typeidx, dataidx),
+            Instr::ArrayNewElem(typeidx, elemidx) => self.execute_arraynewelem(typeidx, elemidx),
+            Instr::ArrayInitData(typeidx, dataidx) => self.execute_arrayinitdata(typeidx, dataidx),
+            Instr::ArrayInitElem(_, elemidx) => self.execute_arrayinitelem(elemidx),
+            Instr::RefI31 => self.execute_refi31(),
+            Instr::I31Get(sign) => self.execute_i31get(sign),
+            Instr::AnyConvertExtern | Instr::ExternConvertAny => self.execute_refconvert(),
     
             // Parametric Instructions
             Instr::Drop(_) => self.execute_drop(),
@@ -70,6 +226,8 @@ impl<'a> Thread<'a> {
             Instr::GlobalSet(globalidx) => self.execute_globalset(globalidx),
     
             // Memory Instructions
+            Instr::Load(ValType::V128, memarg) => self.execute_v128load(memarg),
+            Instr::Store(ValType::V128, memarg) => self.execute_v128store(memarg),
             Instr::Load(valtype, memarg) => self.execute_load(valtype, memarg),
             Instr::Store(valtype, memarg) => self.execute_store(valtype, memarg),
             Instr::ILoad8(valsize, valsign, memarg) => self.execute_iload8(valsize, valsign, memarg),
@@ -78,8 +236,22 @@ impl<'a> Thread<'a> {
             Instr::IStore8(valsize, memarg) => self.execute_istore8(valsize, memarg),
             Instr::IStore16(valsize, memarg) => self.execute_istore16(valsize, memarg),
             Instr::I64Store32(memarg) => self.execute_i64store32(memarg),
-            Instr::MemorySize => self.execute_memorysize(),
-            Instr::MemoryGrow => self.execute_memorygrow(),
+            Instr::MemorySize(memidx) => self.execute_memorysize(memidx),
+            Instr::MemoryGrow(memidx) => self.execute_memorygrow(memidx),
+            Instr::MemoryFill(memidx) => self.execute_memoryfill(memidx),
+            Instr::MemoryCopy(dst, src) => self.execute_memorycopy(dst, src),
+            Instr::MemoryInit(memidx, dataidx) => self.execute_memoryinit(memidx, dataidx),
+            Instr::DataDrop(dataidx) => self.execute_datadrop(dataidx),
+
+            // Table Instructions
+            Instr::TableGet(tableidx) => self.execute_tableget(tableidx),
+            Instr::TableSet(tableidx) => self.execute_tableset(tableidx),
+            Instr::TableSize(tableidx) => self.execute_tablesize(tableidx),
+            Instr::TableGrow(tableidx) => self.execute_tablegrow(tableidx),
+            Instr::TableFill(tableidx) => self.execute_tablefill(tableidx),
+            Instr::TableCopy(tableidx1, tableidx2) => self.execute_tablecopy(tableidx1, tableidx2),
+            Instr::TableInit(tableidx, elemidx) => self.execute_tableinit(tableidx, elemidx),
+            Instr::ElemDrop(elemidx) => self.execute_elemdrop(elemidx),
     
             // Numeric Instructions
             Instr::I32Const(i) => Result::Vals(vec![Val::I32Const(i.clone())]),
@@ -227,92 +399,128 @@ impl<'a> Thread<'a> {
             Instr::CvtOp(CvtOp::IReinterpretFromF(ValSize::V64)) => self.execute_i64reinterpret_f64(),
             Instr::CvtOp(CvtOp::FReinterpretFromI(ValSize::V32)) => self.execute_f32reinterpret_i32(),
             Instr::CvtOp(CvtOp::FReinterpretFromI(ValSize::V64)) => self.execute_f64reinterpret_f64(),
+
+            // Vector Instructions
+            Instr::V128Const(v) => Result::v128val(*v),
+            Instr::V128Not => self.execute_vunop(|v| !v),
+            Instr::VVBinOp(op) => self.execute_vvbinop(op),
+            Instr::V128Bitselect => self.execute_v128bitselect(),
+            Instr::V128AnyTrue => self.execute_v128anytrue(),
+            Instr::I8x16Shuffle(laneindices) => self.execute_i8x16shuffle(laneindices),
+            Instr::I8x16Swizzle => self.execute_i8x16swizzle(),
+            Instr::Splat(shape) => self.execute_splat(shape),
+            Instr::ExtractLane(shape, sign, laneidx) => self.execute_extractlane(shape, sign, laneidx),
+            Instr::ReplaceLane(shape, laneidx) => self.execute_replacelane(shape, laneidx),
+            Instr::VIRelOp(shape, op) => self.execute_virelop(shape, op),
+            Instr::VFRelOp(shape, op) => self.execute_vfrelop(shape, op),
+            Instr::VIUnOp(shape, op) => self.execute_viunop(shape, op),
+            Instr::VFUnOp(shape, op) => self.execute_vfunop(shape, op),
+            Instr::VIBinOp(shape, op) => self.execute_vibinop(shape, op),
+            Instr::VFBinOp(shape, op) => self.execute_vfbinop(shape, op),
+            Instr::VIShiftOp(shape, op) => self.execute_vishiftop(shape, op),
+            Instr::AllTrue(shape) => self.execute_alltrue(shape),
+            Instr::Bitmask(shape) => self.execute_bitmask(shape),
+            Instr::Narrow(shape, sign) => self.execute_narrow(shape, sign),
+            Instr::ExtMul(shape, half, sign) => self.execute_extmul(shape, half, sign),
+            Instr::ExtAddPairwise(shape, sign) => self.execute_extaddpairwise(shape, sign),
+            Instr::I32x4DotI16x8S => self.execute_i32x4dot_i16x8s(),
+            Instr::I8x16RelaxedSwizzle => self.execute_i8x16relaxed_swizzle(),
+            Instr::RelaxedTrunc(shape, sign) => self.execute_relaxed_trunc(shape, sign),
+            Instr::RelaxedMadd(shape) => self.execute_relaxed_madd(shape, false),
+            Instr::RelaxedNmadd(shape) => self.execute_relaxed_madd(shape, true),
+            Instr::RelaxedLaneselect(shape) => self.execute_relaxed_laneselect(shape),
+            Instr::RelaxedMin(shape) => self.execute_relaxed_minmax(shape, false),
+            Instr::RelaxedMax(shape) => self.execute_relaxed_minmax(shape, true),
+            Instr::I16x8RelaxedQ15mulrS => self.execute_i16x8relaxed_q15mulrs(),
+            Instr::I16x8RelaxedDotI8x16I7x16S => self.execute_i16x8relaxed_dot_i8x16_i7x16s(),
+            Instr::I32x4RelaxedDotI8x16I7x16AddS => self.execute_i32x4relaxed_dot_i8x16_i7x16_adds(),
+            Instr::VCvtOp(cvtop) => self.execute_vcvtop(cvtop),
+            Instr::V128LoadExtend(shape, sign, memarg) => self.execute_v128loadextend(shape, sign, memarg),
+            Instr::V128LoadSplat(shape, memarg) => self.execute_v128loadsplat(shape, memarg),
+            Instr::V128LoadZero(valsize, memarg) => self.execute_v128loadzero(valsize, memarg),
+            Instr::V128LoadLane(shape, memarg, laneidx) => self.execute_v128loadlane(shape, memarg, laneidx),
+            Instr::V128StoreLane(shape, memarg, laneidx) => self.execute_v128storelane(shape, memarg, laneidx),
+
+            // Atomic Memory Instructions
+            Instr::AtomicNotify(memarg) => self.execute_atomic_notify(memarg),
+            Instr::AtomicWait(valsize, memarg) => self.execute_atomic_wait(valsize, memarg),
+            Instr::AtomicFence => self.execute_atomic_fence(),
+            Instr::AtomicLoad(valsize, packsize, memarg) => self.execute_atomic_load(valsize, packsize, memarg),
+            Instr::AtomicStore(valsize, packsize, memarg) => self.execute_atomic_store(valsize, packsize, memarg),
+            Instr::AtomicRmw(valsize, packsize, op, memarg) => self.execute_atomic_rmw(valsize, packsize, op, memarg),
+            Instr::AtomicCmpxchg(valsize, packsize, memarg) => self.execute_atomic_cmpxchg(valsize, packsize, memarg),
         }
     }
 
     pub fn execute_invoke(&mut self, funcaddr: &FuncAddr) -> Result {
-        // let mut instrs = vec![];
-        let funcinst = self.store.funcs[funcaddr.clone()].clone();
-    
-        match funcinst {
+        let hostfunc = match &self.store.funcs[*funcaddr] {
             FuncInst::User(userfunc) => {
-                let (argtypes, returntypes) = userfunc.tp;
-                let localtypes = userfunc.code.locals;
-                let expr = userfunc.code.body;
-                let mut args = vec![];
-                
-                let arg_count = argtypes.len();
-                for _ in 0..arg_count {
-                    if let Some(StackEntry::Value(val)) = self.stack.pop() {
-                        args.push(val);
-                    }
-                }
-    
-                let mut locals = vec![];
-                for localtype in localtypes {
-                    let val = match localtype {
-                        ValType::I32 => Val::I32Const(0),
-                        ValType::I64 => Val::I64Const(0),
-                        ValType::F32 => Val::F32Const(0.0),
-                        ValType::F64 => Val::F64Const(0.0),
-                    };
-                    locals.push(val);
+                if self.store.is_interrupted() {
+                    return Result::Interrupted;
                 }
-    
-                locals.extend(args);
-    
-                let frame = Frame{ module: userfunc.module, locals: locals };
-                let m = returntypes.len();
-                let activation = StackEntry::Activation(m as u32, frame);
-                self.stack.push(activation);
-                let label = StackEntry::Label(m as u32, vec![]);
 
-                if let Result::Vals(vals) = self.execute_instrs_with_label(label, &expr.0) {
-                    let vals: Vec<StackEntry> = vals.iter().map(|v| StackEntry::Value(v.clone())).collect();
-                    self.stack.extend(vals);
-                } else {
-                    return Result::Trap;
-                }
-
-
-                let mut vals = vec![];
-                let n = m;
-                for _ in 0..n {
-                    if let Some(StackEntry::Value(val)) = self.stack.pop() {
-                        vals.push(val);
-                    }
+                let (argtypes, returntypes) = &userfunc.tp;
+                let args = self.stack.split_off(self.stack.len() - argtypes.len());
+                let mut locals: Vec<Val> = args.into_iter().map(|entry| match entry {
+                    StackEntry::Value(val) => val,
+                    _ => unreachable!(),
+                }).collect();
+                for localtype in &userfunc.code.locals {
+                    locals.push(Val::default(localtype, &userfunc.module.types));
                 }
+    
+                let frame = Frame{ module: userfunc.module.clone(), locals };
+                let m = returntypes.len() as u32;
+                let instrs = userfunc.body.clone();
+                self.stack.push(StackEntry::Activation(m, frame));
+                self.stack.push(StackEntry::Label(Label{ arity: m, instrs, pc: 0, is_loop: false }));
 
-                // pop the label
-                self.stack.pop();  
+                return Result::Vals(vec![]);
+            },
+            FuncInst::Host(hostfunc) => hostfunc.clone(),
+        };
 
-                Result::Vals(vals)
+        let (argtypes, returntypes) = hostfunc.tp;
+        let args: Vec<Val> = self.stack.split_off(self.stack.len() - argtypes.len()).into_iter().map(|entry| match entry {
+            StackEntry::Value(val) => val,
+            _ => unreachable!(),
+        }).collect();
+        let result = match hostfunc.hostcode {
+            HostCode::Sync(f) => f(self.store, &args),
+            HostCode::Closure(f) => f(self.store, &args),
+            HostCode::Async(f) => {
+                self.pending = Some(f(self.store, &args));
+                return Result::Suspended(SuspendReason::Host(*funcaddr, args));
             },
-            FuncInst::Host(hostfunc) => {
-                let f = hostfunc.hostcode;
-                f();
-                unimplemented!()
+        };
+        match result {
+            HostResult::Vals(vals) => {
+                // the host may return references it kept across a collection
+                let types = self.stack.iter().rev().find_map(|entry| match entry {
+                    StackEntry::Activation(_, frame) => Some(frame.module.types.clone()),
+                    _ => None,
+                }).unwrap_or_default();
+                let typed = vals.len() == returntypes.len()
+                    && vals.iter().zip(&returntypes).all(|(val, valtype)| val_matches(self.store, val, valtype, &types));
+                if typed { Result::Vals(vals) } else { Result::Trap }
             },
+            HostResult::Trap => Result::Trap,
+            HostResult::Throw(exnaddr) => Result::Throw(exnaddr),
+            HostResult::Yield => Result::Suspended(SuspendReason::Host(*funcaddr, args)),
+            HostResult::Exit(code) => Result::Exit(code),
         }
     }
 
-    pub fn execute_instrs_with_label(&mut self, label: StackEntry, instrs: &Vec<Instr>) -> Result {
-        self.stack.push(label);
-
-        self.execute_instrs(instrs);
-
-        let mut vals = vec![];
-        let m = vals.len();
-        for _ in 0..m {
-            if let Some(StackEntry::Value(val)) = self.stack.pop() {
-                vals.push(val);
-            }
-        }
-
-        // pop the label
-        self.stack.pop();  
-        
-        Result::Vals(vals)
+    /// Pop the activation at `idx` with everything above it but its results.
+    pub fn return_from(&mut self, idx: usize) {
+        let n = if let StackEntry::Activation(n, _) = &self.stack[idx] {
+            *n as usize
+        } else {
+            unreachable!()
+        };
+        let vals = self.stack.split_off(self.stack.len() - n);
+        self.stack.truncate(idx);
+        self.stack.extend(vals);
     }
 
     pub fn current_frame(&self) -> (u32, Frame) {
@@ -323,9 +531,90 @@ impl<'a> Thread<'a> {
         }
         unreachable!()
     }
-}
 
+    pub(super) fn pop_i32(&mut self) -> u32 {
+        if let Some(StackEntry::Value(Val::I32Const(n))) = self.stack.pop() {
+            n
+        } else {
+            unreachable!()
+        }
+    }
+
+    pub(super) fn pop_i64(&mut self) -> u64 {
+        if let Some(StackEntry::Value(Val::I64Const(n))) = self.stack.pop() {
+            n
+        } else {
+            unreachable!()
+        }
+    }
 
+    /// Address of a memory, an i32 or an i64 depending on its index type.
+    pub(super) fn pop_addr(&mut self) -> u64 {
+        match self.stack.pop() {
+            Some(StackEntry::Value(Val::I32Const(n))) => n as u64,
+            Some(StackEntry::Value(Val::I64Const(n))) => n,
+            _ => unreachable!(),
+        }
+    }
 
+    pub(super) fn pop_val(&mut self) -> Val {
+        if let Some(StackEntry::Value(val)) = self.stack.pop() {
+            val
+        } else {
+            unreachable!()
+        }
+    }
 
+    pub(super) fn pop_ref(&mut self) -> Ref {
+        if let Some(StackEntry::Value(Val::Ref(r))) = self.stack.pop() {
+            r
+        } else {
+            unreachable!()
+        }
+    }
 
+    pub fn current_frame_mut(&mut self) -> &mut Frame {
+        for entry in self.stack.iter_mut().rev() {
+            if let StackEntry::Activation(_, frame) = entry {
+                return frame;
+            }
+        }
+        unreachable!()
+    }
+}
+
+#[test]
+fn test_execute_func() {
+    use crate::{store_init, func_invoke, Func, Expr, BlockType, ModuleInst, IBinOp, IRelOp};
+
+    // sum of 1..=n, counting down in a loop
+    let body = Expr(vec![
+        Instr::Block(BlockType::ValType(None), vec![
+            Instr::Loop(BlockType::ValType(None), vec![
+                Instr::LocalGet(0),
+                Instr::ITestOp(ValSize::V32, ITestOp::Eqz),
+                Instr::BrIf(1),
+                Instr::LocalGet(1),
+                Instr::LocalGet(0),
+                Instr::IBinOp(ValSize::V32, IBinOp::Add),
+                Instr::LocalSet(1),
+                Instr::LocalGet(0),
+                Instr::I32Const(1),
+                Instr::IBinOp(ValSize::V32, IBinOp::Sub),
+                Instr::LocalTee(0),
+                Instr::I32Const(0),
+                Instr::IRelOp(ValSize::V32, IRelOp::Ne),
+                Instr::BrIf(0),
+            ].into()),
+        ].into()),
+        Instr::LocalGet(1),
+    ]);
+    let functype = (vec![ValType::I32], vec![ValType::I32]);
+    let func = Func { tp: 0, locals: vec![ValType::I32], body };
+    let moduleinst = ModuleInst { types: Arc::new(vec![functype.clone().into()]), ..ModuleInst::default() };
+
+    let mut store = store_init();
+    store.funcs.push(FuncInst::user(functype, moduleinst, func));
+    let (_, result) = func_invoke(&mut store, 0, vec![Val::I32Const(10)]);
+    assert_eq!(result.unwrap(), vec![Val::I32Const(55)]);
+}
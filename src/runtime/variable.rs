@@ -13,8 +13,8 @@ impl<'a> Thread<'a> {
     }
 
     pub fn execute_localset(&mut self, localidx: &LocalIdx) -> Result {
-        let (_, mut frame) = self.current_frame();
         if let Some(StackEntry::Value(val)) = self.stack.pop() {
+            let frame = self.current_frame_mut();
             frame.locals[localidx.clone() as usize] = val;
             Result::Vals(vec![])
         } else {
@@ -23,8 +23,8 @@ impl<'a> Thread<'a> {
     }
     
     pub fn execute_localtee(&mut self, localidx: &LocalIdx) -> Result {
-        let (_, mut frame) = self.current_frame();
         if let Some(StackEntry::Value(val)) = self.stack.pop() {
+            let frame = self.current_frame_mut();
             frame.locals[localidx.clone() as usize] = val;
             Result::Vals(vec![val])
         } else {
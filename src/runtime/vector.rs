@@ -0,0 +1,756 @@
+use crate::{
+    MemArg,
+    ValSize,
+    ValSign,
+    IRelOp,
+    FRelOp,
+    FUnOp,
+    LaneIdx,
+    Shape,
+    Half,
+    VVBinOp,
+    VIUnOp,
+    VIBinOp,
+    VFBinOp,
+    VIShiftOp,
+    VCvtOp,
+};
+
+use super::*;
+
+/* lanes */
+
+fn lane_mask(shape: &Shape) -> u64 {
+    u64::MAX >> (64 - shape.width())
+}
+
+// lanes zero-extended to 64 bits
+fn ilanes(shape: &Shape, v: u128) -> Vec<u64> {
+    let width = shape.width();
+    (0..shape.lanes()).map(|i| (v >> (i as u32 * width)) as u64 & lane_mask(shape)).collect()
+}
+
+// lanes are wrapped to the width of the shape
+fn from_ilanes(shape: &Shape, lanes: &[u64]) -> u128 {
+    let width = shape.width();
+    lanes.iter().enumerate()
+        .fold(0, |v, (i, c)| v | ((c & lane_mask(shape)) as u128) << (i as u32 * width))
+}
+
+fn signed(shape: &Shape, c: u64) -> i64 {
+    let shift = 64 - shape.width();
+    ((c << shift) as i64) >> shift
+}
+
+fn extend(shape: &Shape, sign: &ValSign, c: u64) -> i64 {
+    match sign {
+        ValSign::S => signed(shape, c),
+        ValSign::U => c as i64,
+    }
+}
+
+fn saturate(shape: &Shape, sign: &ValSign, c: i64) -> u64 {
+    let width = shape.width();
+    let (min, max) = match sign {
+        ValSign::S => (-(1i64 << (width - 1)), (1i64 << (width - 1)) - 1),
+        ValSign::U => (0, ((1u64 << width) - 1) as i64),
+    };
+    c.clamp(min, max) as u64
+}
+
+fn f32lanes(v: u128) -> Vec<f32> {
+    ilanes(&Shape::F32x4, v).into_iter().map(|c| f32::from_bits(c as u32)).collect()
+}
+
+fn f64lanes(v: u128) -> Vec<f64> {
+    ilanes(&Shape::F64x2, v).into_iter().map(f64::from_bits).collect()
+}
+
+fn from_f32lanes(lanes: &[f32]) -> u128 {
+    from_ilanes(&Shape::F32x4, &lanes.iter().map(|f| f.to_bits() as u64).collect::<Vec<_>>())
+}
+
+fn from_f64lanes(lanes: &[f64]) -> u128 {
+    from_ilanes(&Shape::F64x2, &lanes.iter().map(|f| f.to_bits()).collect::<Vec<_>>())
+}
+
+fn bool_lane(b: bool) -> u64 { if b { u64::MAX } else { 0 } }
+
+fn imap(shape: &Shape, v: u128, func: impl Fn(u64) -> u64) -> u128 {
+    from_ilanes(shape, &ilanes(shape, v).into_iter().map(func).collect::<Vec<_>>())
+}
+
+fn izip(shape: &Shape, v1: u128, v2: u128, func: impl Fn(u64, u64) -> u64) -> u128 {
+    let lanes: Vec<u64> = ilanes(shape, v1).into_iter()
+        .zip(ilanes(shape, v2))
+        .map(|(c1, c2)| func(c1, c2))
+        .collect();
+    from_ilanes(shape, &lanes)
+}
+
+fn fmap(shape: &Shape, v: u128, func32: fn(f32) -> f32, func64: fn(f64) -> f64) -> u128 {
+    match shape {
+        Shape::F32x4 => from_f32lanes(&f32lanes(v).into_iter().map(func32).collect::<Vec<_>>()),
+        Shape::F64x2 => from_f64lanes(&f64lanes(v).into_iter().map(func64).collect::<Vec<_>>()),
+        _ => unreachable!(),
+    }
+}
+
+fn fzip(shape: &Shape, v1: u128, v2: u128, func32: fn(f32, f32) -> f32, func64: fn(f64, f64) -> f64) -> u128 {
+    match shape {
+        Shape::F32x4 => {
+            let lanes: Vec<f32> = f32lanes(v1).into_iter().zip(f32lanes(v2)).map(|(c1, c2)| func32(c1, c2)).collect();
+            from_f32lanes(&lanes)
+        },
+        Shape::F64x2 => {
+            let lanes: Vec<f64> = f64lanes(v1).into_iter().zip(f64lanes(v2)).map(|(c1, c2)| func64(c1, c2)).collect();
+            from_f64lanes(&lanes)
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn fcompare(shape: &Shape, v1: u128, v2: u128, func32: fn(&f32, &f32) -> bool, func64: fn(&f64, &f64) -> bool) -> u128 {
+    let lanes: Vec<u64> = match shape {
+        Shape::F32x4 => f32lanes(v1).iter().zip(f32lanes(v2).iter()).map(|(c1, c2)| bool_lane(func32(c1, c2))).collect(),
+        Shape::F64x2 => f64lanes(v1).iter().zip(f64lanes(v2).iter()).map(|(c1, c2)| bool_lane(func64(c1, c2))).collect(),
+        _ => unreachable!(),
+    };
+    from_ilanes(shape, &lanes)
+}
+
+fn fpmin32(c1: f32, c2: f32) -> f32 { if c2 < c1 { c2 } else { c1 } }
+fn fpmax32(c1: f32, c2: f32) -> f32 { if c1 < c2 { c2 } else { c1 } }
+fn fpmin64(c1: f64, c2: f64) -> f64 { if c2 < c1 { c2 } else { c1 } }
+fn fpmax64(c1: f64, c2: f64) -> f64 { if c1 < c2 { c2 } else { c1 } }
+
+// lanes of the low or high half of `v`, seen with the half-width shape of `shape`
+fn half_lanes(shape: &Shape, half: &Half, sign: &ValSign, v: u128) -> Vec<i64> {
+    let src = shape.half();
+    let lanes = ilanes(&src, v);
+    let range = match half {
+        Half::Low => 0..shape.lanes(),
+        Half::High => shape.lanes()..src.lanes(),
+    };
+    lanes[range].iter().map(|c| extend(&src, sign, *c)).collect()
+}
+
+fn vcvtop(cvtop: &VCvtOp, v: u128) -> u128 {
+    match cvtop {
+        VCvtOp::Extend(shape, half, sign) => {
+            let lanes: Vec<u64> = half_lanes(shape, half, sign, v).into_iter().map(|c| c as u64).collect();
+            from_ilanes(shape, &lanes)
+        },
+        VCvtOp::TruncSat(shape, sign) => {
+            let lanes: Vec<f64> = match shape {
+                Shape::F32x4 => f32lanes(v).into_iter().map(|f| f as f64).collect(),
+                _ => f64lanes(v),
+            };
+            // `as` saturates and maps NaN to 0
+            let lanes: Vec<u64> = lanes.into_iter().map(|f| match sign {
+                ValSign::S => f as i32 as u64,
+                ValSign::U => f as u32 as u64,
+            }).collect();
+            from_ilanes(&Shape::I32x4, &lanes)
+        },
+        VCvtOp::Convert(shape, sign) => {
+            let lanes: Vec<i64> = ilanes(&Shape::I32x4, v).into_iter().map(|c| extend(&Shape::I32x4, sign, c)).collect();
+            match shape {
+                Shape::F32x4 => from_f32lanes(&lanes.into_iter().map(|c| c as f32).collect::<Vec<_>>()),
+                _ => from_f64lanes(&lanes[..2].iter().map(|c| *c as f64).collect::<Vec<_>>()),
+            }
+        },
+        VCvtOp::F32x4DemoteF64x2Zero => {
+            from_f32lanes(&f64lanes(v).into_iter().map(|f| f as f32).collect::<Vec<_>>())
+        },
+        VCvtOp::F64x2PromoteLowF32x4 => {
+            from_f64lanes(&f32lanes(v)[..2].iter().map(|f| *f as f64).collect::<Vec<_>>())
+        },
+    }
+}
+
+fn vibinop(shape: &Shape, op: &VIBinOp, v1: u128, v2: u128) -> u128 {
+    match op {
+        VIBinOp::Add => izip(shape, v1, v2, |c1, c2| c1.wrapping_add(c2)),
+        VIBinOp::Sub => izip(shape, v1, v2, |c1, c2| c1.wrapping_sub(c2)),
+        VIBinOp::Mul => izip(shape, v1, v2, |c1, c2| c1.wrapping_mul(c2)),
+        VIBinOp::AddSat(sign) => izip(shape, v1, v2, |c1, c2| {
+            saturate(shape, sign, extend(shape, sign, c1) + extend(shape, sign, c2))
+        }),
+        VIBinOp::SubSat(sign) => izip(shape, v1, v2, |c1, c2| {
+            saturate(shape, sign, extend(shape, sign, c1) - extend(shape, sign, c2))
+        }),
+        VIBinOp::Min(sign) => izip(shape, v1, v2, |c1, c2| {
+            if extend(shape, sign, c1) <= extend(shape, sign, c2) { c1 } else { c2 }
+        }),
+        VIBinOp::Max(sign) => izip(shape, v1, v2, |c1, c2| {
+            if extend(shape, sign, c1) >= extend(shape, sign, c2) { c1 } else { c2 }
+        }),
+        VIBinOp::AvgrU => izip(shape, v1, v2, |c1, c2| (c1 + c2).div_ceil(2)),
+        VIBinOp::Q15MulrSatS => izip(shape, v1, v2, |c1, c2| {
+            let product = signed(shape, c1) * signed(shape, c2);
+            saturate(shape, &ValSign::S, (product + 0x4000) >> 15)
+        }),
+    }
+}
+
+fn virelop(shape: &Shape, op: &IRelOp, v1: u128, v2: u128) -> u128 {
+    izip(shape, v1, v2, |c1, c2| bool_lane(match op {
+        IRelOp::Eq => c1 == c2,
+        IRelOp::Ne => c1 != c2,
+        IRelOp::Lt(sign) => extend(shape, sign, c1) < extend(shape, sign, c2),
+        IRelOp::Gt(sign) => extend(shape, sign, c1) > extend(shape, sign, c2),
+        IRelOp::Le(sign) => extend(shape, sign, c1) <= extend(shape, sign, c2),
+        IRelOp::Ge(sign) => extend(shape, sign, c1) >= extend(shape, sign, c2),
+    }))
+}
+
+fn vfrelop(shape: &Shape, op: &FRelOp, v1: u128, v2: u128) -> u128 {
+    match op {
+        FRelOp::Eq => fcompare(shape, v1, v2, f32::eq, f64::eq),
+        FRelOp::Ne => fcompare(shape, v1, v2, f32::ne, f64::ne),
+        FRelOp::Lt => fcompare(shape, v1, v2, f32::lt, f64::lt),
+        FRelOp::Gt => fcompare(shape, v1, v2, f32::gt, f64::gt),
+        FRelOp::Le => fcompare(shape, v1, v2, f32::le, f64::le),
+        FRelOp::Ge => fcompare(shape, v1, v2, f32::ge, f64::ge),
+    }
+}
+
+fn vfunop(shape: &Shape, op: &FUnOp, v: u128) -> u128 {
+    match op {
+        FUnOp::Abs => fmap(shape, v, fabs32, fabs64),
+        FUnOp::Neg => fmap(shape, v, fneg32, fneg64),
+        FUnOp::Sqrt => fmap(shape, v, fsqrt32, fsqrt64),
+        FUnOp::Ceil => fmap(shape, v, fceil32, fceil64),
+        FUnOp::Floor => fmap(shape, v, ffloor32, ffloor64),
+        FUnOp::Trunc => fmap(shape, v, ftrunc32, ftrunc64),
+        FUnOp::Nearest => fmap(shape, v, fnearest32, fnearest64),
+    }
+}
+
+fn vfbinop(shape: &Shape, op: &VFBinOp, v1: u128, v2: u128) -> u128 {
+    match op {
+        VFBinOp::Add => fzip(shape, v1, v2, fadd32, fadd64),
+        VFBinOp::Sub => fzip(shape, v1, v2, fsub32, fsub64),
+        VFBinOp::Mul => fzip(shape, v1, v2, fmul32, fmul64),
+        VFBinOp::Div => fzip(shape, v1, v2, fdiv32, fdiv64),
+        VFBinOp::Min => fzip(shape, v1, v2, fmin32, fmin64),
+        VFBinOp::Max => fzip(shape, v1, v2, fmax32, fmax64),
+        VFBinOp::PMin => fzip(shape, v1, v2, fpmin32, fpmin64),
+        VFBinOp::PMax => fzip(shape, v1, v2, fpmax32, fpmax64),
+    }
+}
+
+// scalar of a lane, as pushed by `extract_lane` and taken by `splat` and `replace_lane`
+fn lane_val(shape: &Shape, sign: &Option<ValSign>, c: u64) -> Val {
+    match shape {
+        Shape::I8x16 | Shape::I16x8 => {
+            let sign = sign.clone().unwrap_or(ValSign::U);
+            Val::I32Const(extend(shape, &sign, c) as u32)
+        },
+        Shape::I32x4 => Val::I32Const(c as u32),
+        Shape::I64x2 => Val::I64Const(c),
+        Shape::F32x4 => Val::F32Const(f32::from_bits(c as u32)),
+        Shape::F64x2 => Val::F64Const(f64::from_bits(c)),
+    }
+}
+
+fn val_lane(val: Val) -> u64 {
+    match val {
+        Val::I32Const(c) => c as u64,
+        Val::I64Const(c) => c,
+        Val::F32Const(f) => f.to_bits() as u64,
+        Val::F64Const(f) => f.to_bits(),
+        _ => unreachable!(),
+    }
+}
+
+impl<'a> Thread<'a> {
+    fn pop_v128(&mut self) -> u128 {
+        if let Some(StackEntry::Value(Val::V128Const(v))) = self.stack.pop() {
+            v
+        } else {
+            unreachable!()
+        }
+    }
+
+    pub(super) fn execute_vunop(&mut self, func: impl Fn(u128) -> u128) -> Result {
+        let v = self.pop_v128();
+        Result::v128val(func(v))
+    }
+
+    fn execute_vbinop(&mut self, func: impl Fn(u128, u128) -> u128) -> Result {
+        let v2 = self.pop_v128();
+        let v1 = self.pop_v128();
+        Result::v128val(func(v1, v2))
+    }
+
+    pub fn execute_vvbinop(&mut self, op: &VVBinOp) -> Result {
+        self.execute_vbinop(|v1, v2| match op {
+            VVBinOp::And => v1 & v2,
+            VVBinOp::AndNot => v1 & !v2,
+            VVBinOp::Or => v1 | v2,
+            VVBinOp::Xor => v1 ^ v2,
+        })
+    }
+
+    pub fn execute_v128bitselect(&mut self) -> Result {
+        let c = self.pop_v128();
+        let v2 = self.pop_v128();
+        let v1 = self.pop_v128();
+        Result::v128val((v1 & c) | (v2 & !c))
+    }
+
+    pub fn execute_v128anytrue(&mut self) -> Result {
+        let v = self.pop_v128();
+        Result::i32val((v != 0) as u32)
+    }
+
+    pub fn execute_i8x16shuffle(&mut self, laneindices: &[LaneIdx; 16]) -> Result {
+        self.execute_vbinop(|v1, v2| {
+            let lanes = [ilanes(&Shape::I8x16, v1), ilanes(&Shape::I8x16, v2)].concat();
+            let lanes: Vec<u64> = laneindices.iter().map(|i| lanes[*i as usize]).collect();
+            from_ilanes(&Shape::I8x16, &lanes)
+        })
+    }
+
+    pub fn execute_i8x16swizzle(&mut self) -> Result {
+        self.execute_vbinop(|v1, v2| {
+            let lanes = ilanes(&Shape::I8x16, v1);
+            let lanes: Vec<u64> = ilanes(&Shape::I8x16, v2).into_iter()
+                .map(|i| lanes.get(i as usize).cloned().unwrap_or(0))
+                .collect();
+            from_ilanes(&Shape::I8x16, &lanes)
+        })
+    }
+
+    pub fn execute_splat(&mut self, shape: &Shape) -> Result {
+        let c = val_lane(self.pop_val());
+        Result::v128val(from_ilanes(shape, &vec![c; shape.lanes()]))
+    }
+
+    pub fn execute_extractlane(&mut self, shape: &Shape, sign: &Option<ValSign>, laneidx: &LaneIdx) -> Result {
+        let v = self.pop_v128();
+        let c = ilanes(shape, v)[*laneidx as usize];
+        Result::Vals(vec![lane_val(shape, sign, c)])
+    }
+
+    pub fn execute_replacelane(&mut self, shape: &Shape, laneidx: &LaneIdx) -> Result {
+        let c = val_lane(self.pop_val());
+        let v = self.pop_v128();
+        let mut lanes = ilanes(shape, v);
+        lanes[*laneidx as usize] = c;
+        Result::v128val(from_ilanes(shape, &lanes))
+    }
+
+    pub fn execute_virelop(&mut self, shape: &Shape, op: &IRelOp) -> Result {
+        self.execute_vbinop(|v1, v2| virelop(shape, op, v1, v2))
+    }
+
+    pub fn execute_vfrelop(&mut self, shape: &Shape, op: &FRelOp) -> Result {
+        self.execute_vbinop(|v1, v2| vfrelop(shape, op, v1, v2))
+    }
+
+    pub fn execute_viunop(&mut self, shape: &Shape, op: &VIUnOp) -> Result {
+        self.execute_vunop(|v| match op {
+            VIUnOp::Abs => imap(shape, v, |c| signed(shape, c).unsigned_abs()),
+            VIUnOp::Neg => imap(shape, v, |c| c.wrapping_neg()),
+            VIUnOp::Popcnt => imap(shape, v, |c| c.count_ones() as u64),
+        })
+    }
+
+    pub fn execute_vfunop(&mut self, shape: &Shape, op: &FUnOp) -> Result {
+        self.execute_vunop(|v| vfunop(shape, op, v))
+    }
+
+    pub fn execute_vibinop(&mut self, shape: &Shape, op: &VIBinOp) -> Result {
+        self.execute_vbinop(|v1, v2| vibinop(shape, op, v1, v2))
+    }
+
+    pub fn execute_vfbinop(&mut self, shape: &Shape, op: &VFBinOp) -> Result {
+        self.execute_vbinop(|v1, v2| vfbinop(shape, op, v1, v2))
+    }
+
+    pub fn execute_vishiftop(&mut self, shape: &Shape, op: &VIShiftOp) -> Result {
+        let k = (self.pop_i32() % shape.width()) as u64;
+        self.execute_vunop(|v| match op {
+            VIShiftOp::Shl => imap(shape, v, |c| c << k),
+            VIShiftOp::Shr(ValSign::S) => imap(shape, v, |c| (signed(shape, c) >> k) as u64),
+            VIShiftOp::Shr(ValSign::U) => imap(shape, v, |c| c >> k),
+        })
+    }
+
+    pub fn execute_alltrue(&mut self, shape: &Shape) -> Result {
+        let v = self.pop_v128();
+        Result::i32val(ilanes(shape, v).iter().all(|c| *c != 0) as u32)
+    }
+
+    pub fn execute_bitmask(&mut self, shape: &Shape) -> Result {
+        let v = self.pop_v128();
+        let bits = ilanes(shape, v).iter().enumerate()
+            .fold(0, |bits, (i, c)| bits | (((c >> (shape.width() - 1)) as u32) << i));
+        Result::i32val(bits)
+    }
+
+    pub fn execute_narrow(&mut self, shape: &Shape, sign: &ValSign) -> Result {
+        self.execute_vbinop(|v1, v2| {
+            let src = match shape {
+                Shape::I8x16 => Shape::I16x8,
+                _ => Shape::I32x4,
+            };
+            let lanes: Vec<u64> = [ilanes(&src, v1), ilanes(&src, v2)].concat().into_iter()
+                .map(|c| saturate(shape, sign, signed(&src, c)))
+                .collect();
+            from_ilanes(shape, &lanes)
+        })
+    }
+
+    pub fn execute_extmul(&mut self, shape: &Shape, half: &Half, sign: &ValSign) -> Result {
+        self.execute_vbinop(|v1, v2| {
+            let lanes: Vec<u64> = half_lanes(shape, half, sign, v1).into_iter()
+                .zip(half_lanes(shape, half, sign, v2))
+                .map(|(c1, c2)| c1.wrapping_mul(c2) as u64)
+                .collect();
+            from_ilanes(shape, &lanes)
+        })
+    }
+
+    pub fn execute_extaddpairwise(&mut self, shape: &Shape, sign: &ValSign) -> Result {
+        self.execute_vunop(|v| {
+            let src = shape.half();
+            let lanes: Vec<i64> = ilanes(&src, v).into_iter().map(|c| extend(&src, sign, c)).collect();
+            let lanes: Vec<u64> = lanes.chunks(2).map(|pair| (pair[0] + pair[1]) as u64).collect();
+            from_ilanes(shape, &lanes)
+        })
+    }
+
+    pub fn execute_i32x4dot_i16x8s(&mut self) -> Result {
+        self.execute_vbinop(|v1, v2| {
+            let products: Vec<i64> = ilanes(&Shape::I16x8, v1).into_iter()
+                .zip(ilanes(&Shape::I16x8, v2))
+                .map(|(c1, c2)| signed(&Shape::I16x8, c1) * signed(&Shape::I16x8, c2))
+                .collect();
+            let lanes: Vec<u64> = products.chunks(2).map(|pair| (pair[0] + pair[1]) as u64).collect();
+            from_ilanes(&Shape::I32x4, &lanes)
+        })
+    }
+
+    pub fn execute_vcvtop(&mut self, cvtop: &VCvtOp) -> Result {
+        self.execute_vunop(|v| vcvtop(cvtop, v))
+    }
+}
+
+/* relaxed */
+
+// the deterministic profile computes what the non-relaxed counterparts do,
+// the fast one what x86 SSE does, where the proposal allows both
+impl<'a> Thread<'a> {
+    fn deterministic(&self) -> bool {
+        self.store.relaxed_simd == RelaxedSimd::Deterministic
+    }
+
+    pub fn execute_i8x16relaxed_swizzle(&mut self) -> Result {
+        if self.deterministic() {
+            return self.execute_i8x16swizzle();
+        }
+        // pshufb: the top bit zeroes the lane, the low bits index
+        self.execute_vbinop(|v1, v2| {
+            let lanes = ilanes(&Shape::I8x16, v1);
+            let lanes: Vec<u64> = ilanes(&Shape::I8x16, v2).into_iter()
+                .map(|i| if i & 0x80 != 0 { 0 } else { lanes[i as usize & 0x0F] })
+                .collect();
+            from_ilanes(&Shape::I8x16, &lanes)
+        })
+    }
+
+    pub fn execute_relaxed_trunc(&mut self, shape: &Shape, sign: &ValSign) -> Result {
+        if self.deterministic() {
+            return self.execute_vcvtop(&VCvtOp::TruncSat(*shape, sign.clone()));
+        }
+        // cvttps2dq: NaN and out of range give the integer indefinite
+        self.execute_vunop(|v| {
+            let lanes: Vec<f64> = match shape {
+                Shape::F32x4 => f32lanes(v).into_iter().map(|f| f as f64).collect(),
+                _ => f64lanes(v),
+            };
+            let lanes: Vec<u64> = lanes.into_iter().map(|f| match sign {
+                ValSign::S if f > -2147483649.0 && f < 2147483648.0 => f as i32 as u64,
+                ValSign::S => 0x8000_0000,
+                ValSign::U if f > -1.0 && f < 4294967296.0 => f as u32 as u64,
+                ValSign::U => 0xFFFF_FFFF,
+            }).collect();
+            from_ilanes(&Shape::I32x4, &lanes)
+        })
+    }
+
+    // `negate` for nmadd, computing -(a * b) + c
+    pub fn execute_relaxed_madd(&mut self, shape: &Shape, negate: bool) -> Result {
+        // the deterministic profile rounds once as fma does, SSE without FMA rounds the product too
+        let fused = self.deterministic();
+        let c = self.pop_v128();
+        let b = self.pop_v128();
+        let a = self.pop_v128();
+        let a = if negate { vfunop(shape, &FUnOp::Neg, a) } else { a };
+        let v = match shape {
+            Shape::F32x4 => {
+                let lanes: Vec<f32> = f32lanes(a).into_iter().zip(f32lanes(b)).zip(f32lanes(c))
+                    .map(|((c1, c2), c3)| if fused { c1.mul_add(c2, c3) } else { c1 * c2 + c3 })
+                    .collect();
+                from_f32lanes(&lanes)
+            },
+            _ => {
+                let lanes: Vec<f64> = f64lanes(a).into_iter().zip(f64lanes(b)).zip(f64lanes(c))
+                    .map(|((c1, c2), c3)| if fused { c1.mul_add(c2, c3) } else { c1 * c2 + c3 })
+                    .collect();
+                from_f64lanes(&lanes)
+            },
+        };
+        Result::v128val(v)
+    }
+
+    pub fn execute_relaxed_laneselect(&mut self, shape: &Shape) -> Result {
+        if self.deterministic() {
+            return self.execute_v128bitselect();
+        }
+        // blendv: the top bit of a mask lane selects the whole lane
+        let c = self.pop_v128();
+        let v2 = self.pop_v128();
+        let v1 = self.pop_v128();
+        let mask = imap(shape, c, |m| if signed(shape, m) < 0 { u64::MAX } else { 0 });
+        Result::v128val((v1 & mask) | (v2 & !mask))
+    }
+
+    // `max` for relaxed_max
+    pub fn execute_relaxed_minmax(&mut self, shape: &Shape, max: bool) -> Result {
+        let op = match (self.deterministic(), max) {
+            (true, false) => VFBinOp::Min,
+            (true, true) => VFBinOp::Max,
+            // minps and maxps: the second operand on NaN or equal
+            (false, false) => return self.execute_vbinop(|v1, v2| {
+                fzip(shape, v1, v2, |c1, c2| if c1 < c2 { c1 } else { c2 }, |c1, c2| if c1 < c2 { c1 } else { c2 })
+            }),
+            (false, true) => return self.execute_vbinop(|v1, v2| {
+                fzip(shape, v1, v2, |c1, c2| if c1 > c2 { c1 } else { c2 }, |c1, c2| if c1 > c2 { c1 } else { c2 })
+            }),
+        };
+        self.execute_vfbinop(shape, &op)
+    }
+
+    pub fn execute_i16x8relaxed_q15mulrs(&mut self) -> Result {
+        if self.deterministic() {
+            return self.execute_vibinop(&Shape::I16x8, &VIBinOp::Q15MulrSatS);
+        }
+        // pmulhrsw: -0x8000 * -0x8000 wraps
+        self.execute_vbinop(|v1, v2| izip(&Shape::I16x8, v1, v2, |c1, c2| {
+            let product = signed(&Shape::I16x8, c1) * signed(&Shape::I16x8, c2);
+            ((product + 0x4000) >> 15) as u64
+        }))
+    }
+
+    // products of adjacent i8 lanes, the second operand read signed or, as pmaddubsw does, unsigned
+    fn relaxed_dot_products(&self, v1: u128, v2: u128) -> Vec<i64> {
+        let sign = if self.deterministic() { ValSign::S } else { ValSign::U };
+        ilanes(&Shape::I8x16, v1).into_iter()
+            .zip(ilanes(&Shape::I8x16, v2))
+            .map(|(c1, c2)| signed(&Shape::I8x16, c1) * extend(&Shape::I8x16, &sign, c2))
+            .collect()
+    }
+
+    pub fn execute_i16x8relaxed_dot_i8x16_i7x16s(&mut self) -> Result {
+        let v2 = self.pop_v128();
+        let v1 = self.pop_v128();
+        let lanes: Vec<u64> = self.relaxed_dot_products(v1, v2).chunks(2)
+            .map(|pair| saturate(&Shape::I16x8, &ValSign::S, pair[0] + pair[1]))
+            .collect();
+        Result::v128val(from_ilanes(&Shape::I16x8, &lanes))
+    }
+
+    pub fn execute_i32x4relaxed_dot_i8x16_i7x16_adds(&mut self) -> Result {
+        let c = self.pop_v128();
+        let v2 = self.pop_v128();
+        let v1 = self.pop_v128();
+        let sums: Vec<i64> = self.relaxed_dot_products(v1, v2).chunks(2)
+            .map(|pair| signed(&Shape::I16x8, saturate(&Shape::I16x8, &ValSign::S, pair[0] + pair[1])))
+            .collect();
+        let lanes: Vec<u64> = sums.chunks(2).zip(ilanes(&Shape::I32x4, c))
+            .map(|(pair, c)| (pair[0] + pair[1]).wrapping_add(signed(&Shape::I32x4, c)) as u64)
+            .collect();
+        Result::v128val(from_ilanes(&Shape::I32x4, &lanes))
+    }
+}
+
+impl<'a> Thread<'a> {
+    // effective address of an access of `n` bytes, None when out of bounds
+    fn vector_ea(&mut self, memarg: &MemArg, n: usize) -> Option<(MemAddr, usize)> {
+        let (_, frame) = self.current_frame();
+        let memaddr = frame.module.memaddrs[memarg.memory as usize];
+        let ea = self.pop_ea(memarg)?;
+        if ea.checked_add(n)? > self.store.mems[memaddr].size() {
+            return None;
+        }
+        Some((memaddr, ea))
+    }
+
+    fn vector_read(&mut self, memarg: &MemArg, n: usize) -> Option<u128> {
+        let (memaddr, ea) = self.vector_ea(memarg, n)?;
+        let mut bytes = [0x00; 16];
+        bytes[..n].copy_from_slice(&self.store.mems[memaddr].read(ea, n)?);
+        Some(u128::from_le_bytes(bytes))
+    }
+
+    pub fn execute_v128load(&mut self, memarg: &MemArg) -> Result {
+        match self.vector_read(memarg, 16) {
+            Some(v) => Result::v128val(v),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_v128store(&mut self, memarg: &MemArg) -> Result {
+        let v = self.pop_v128();
+        match self.vector_ea(memarg, 16) {
+            Some((memaddr, ea)) => {
+                self.store.mems[memaddr].write(ea, &v.to_le_bytes());
+                Result::Vals(vec![])
+            },
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_v128loadextend(&mut self, shape: &Shape, sign: &ValSign, memarg: &MemArg) -> Result {
+        match self.vector_read(memarg, 8) {
+            Some(v) => Result::v128val(vcvtop(&VCvtOp::Extend(*shape, Half::Low, sign.clone()), v)),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_v128loadsplat(&mut self, shape: &Shape, memarg: &MemArg) -> Result {
+        match self.vector_read(memarg, shape.width() as usize / 8) {
+            Some(c) => Result::v128val(from_ilanes(shape, &vec![c as u64; shape.lanes()])),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_v128loadzero(&mut self, valsize: &ValSize, memarg: &MemArg) -> Result {
+        let n = match valsize {
+            ValSize::V32 => 4,
+            ValSize::V64 => 8,
+        };
+        match self.vector_read(memarg, n) {
+            Some(v) => Result::v128val(v),
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_v128loadlane(&mut self, shape: &Shape, memarg: &MemArg, laneidx: &LaneIdx) -> Result {
+        let v = self.pop_v128();
+        match self.vector_read(memarg, shape.width() as usize / 8) {
+            Some(c) => {
+                let mut lanes = ilanes(shape, v);
+                lanes[*laneidx as usize] = c as u64;
+                Result::v128val(from_ilanes(shape, &lanes))
+            },
+            None => Result::Trap,
+        }
+    }
+
+    pub fn execute_v128storelane(&mut self, shape: &Shape, memarg: &MemArg, laneidx: &LaneIdx) -> Result {
+        let v = self.pop_v128();
+        let n = shape.width() as usize / 8;
+        match self.vector_ea(memarg, n) {
+            Some((memaddr, ea)) => {
+                let c = ilanes(shape, v)[*laneidx as usize];
+                self.store.mems[memaddr].write(ea, &c.to_le_bytes()[..n]);
+                Result::Vals(vec![])
+            },
+            None => Result::Trap,
+        }
+    }
+}
+
+#[test]
+fn test_vector() {
+    use crate::{store_init, Module, Mem, MemType, Limits, Share, IdxType, Instr, ValType, IBinOp};
+    use crate::testing::{exported_funcs, TestInstance};
+
+    let i32x4 = |lanes: [u32; 4]| lanes.iter().enumerate().fold(0, |v, (i, &c)| v | (c as u128) << (i * 32));
+    let memarg = MemArg { align: 4, offset: 0, memory: 0 };
+    let module = Module {
+        mems: vec![Mem(MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32))],
+        ..exported_funcs(vec![(vec![], vec![ValType::I32]), (vec![], vec![ValType::V128])], vec![
+            ("add", 0, vec![
+                Instr::V128Const(i32x4([1, 2, 3, 4])), Instr::V128Const(i32x4([10, 20, 30, 40])),
+                Instr::VIBinOp(Shape::I32x4, VIBinOp::Add), Instr::ExtractLane(Shape::I32x4, None, 3),
+            ]),
+            ("sat", 0, vec![
+                Instr::I32Const(120), Instr::Splat(Shape::I8x16), Instr::I32Const(10), Instr::Splat(Shape::I8x16),
+                Instr::VIBinOp(Shape::I8x16, VIBinOp::AddSat(ValSign::S)), Instr::ExtractLane(Shape::I8x16, Some(ValSign::S), 0),
+                Instr::I32Const(-1i32 as u32), Instr::Splat(Shape::I16x8), Instr::ExtractLane(Shape::I16x8, Some(ValSign::U), 7),
+                Instr::IBinOp(ValSize::V32, IBinOp::Add),
+            ]),
+            ("memory", 1, vec![
+                Instr::I32Const(16), Instr::V128Const(i32x4([1, 2, 3, 4])), Instr::Store(ValType::V128, memarg.clone()),
+                Instr::I32Const(16), Instr::Load(ValType::V128, memarg), Instr::V128Const(0),
+                Instr::I8x16Shuffle([12, 13, 14, 15, 8, 9, 10, 11, 4, 5, 6, 7, 0, 1, 2, 3]),
+                Instr::I32Const(9), Instr::ReplaceLane(Shape::I32x4, 0),
+            ]),
+        ])
+    };
+
+    let mut store = store_init();
+    let instance = TestInstance::new(&mut store, module, vec![]);
+    assert_eq!(instance.call(&mut store, "add", vec![]).unwrap(), vec![Val::I32Const(44)]);
+    assert_eq!(instance.call(&mut store, "sat", vec![]).unwrap(), vec![Val::I32Const(127 + 0xFFFF)]);
+    assert_eq!(instance.call(&mut store, "memory", vec![]).unwrap(), vec![Val::V128Const(i32x4([9, 3, 2, 1]))]);
+}
+
+#[test]
+fn test_relaxed_simd() {
+    use crate::{store_init, store_set_relaxed_simd, Instr, ValType};
+    use crate::testing::{exported_funcs, TestInstance};
+
+    let splat = |shape: &Shape, c: u64| from_ilanes(shape, &vec![c; shape.lanes()]);
+    let f32x4 = |c: f32| splat(&Shape::F32x4, c.to_bits() as u64);
+    let f64x2 = |c: f64| splat(&Shape::F64x2, c.to_bits());
+    let names = ["swizzle", "trunc", "madd", "q15mulr", "dot", "nmadd"];
+    let bodies = || vec![
+        vec![Instr::V128Const(0x0F0E0D0C_0B0A0908_07060504_03020100), Instr::V128Const(splat(&Shape::I8x16, 0x11)), Instr::I8x16RelaxedSwizzle],
+        vec![Instr::V128Const(splat(&Shape::F32x4, f32::NAN.to_bits() as u64)), Instr::RelaxedTrunc(Shape::F32x4, ValSign::S)],
+        vec![
+            Instr::V128Const(f64x2(1.0 + 2f64.powi(-30))), Instr::V128Const(f64x2(1.0 + 2f64.powi(-30))),
+            Instr::V128Const(f64x2(-1.0 - 2f64.powi(-29))), Instr::RelaxedMadd(Shape::F64x2),
+        ],
+        vec![Instr::V128Const(splat(&Shape::I16x8, 0x8000)), Instr::V128Const(splat(&Shape::I16x8, 0x8000)), Instr::I16x8RelaxedQ15mulrS],
+        vec![
+            Instr::V128Const(splat(&Shape::I8x16, 1)), Instr::V128Const(splat(&Shape::I8x16, 0xFF)), Instr::V128Const(0),
+            Instr::I32x4RelaxedDotI8x16I7x16AddS,
+        ],
+        vec![
+            Instr::V128Const(f32x4(1.0 + 2f32.powi(-12))), Instr::V128Const(f32x4(1.0 + 2f32.powi(-12))),
+            Instr::V128Const(f32x4(1.0 + 2f32.powi(-11))), Instr::RelaxedNmadd(Shape::F32x4),
+        ],
+    ];
+    let module = || exported_funcs(
+        vec![(vec![], vec![ValType::V128])],
+        names.iter().zip(bodies()).map(|(name, body)| (*name, 0, body)).collect(),
+    );
+
+    let run = |mode: RelaxedSimd| -> Vec<u128> {
+        let mut store = store_init();
+        store_set_relaxed_simd(&mut store, mode);
+        let instance = TestInstance::new(&mut store, module(), vec![]);
+        names.iter().map(|name| match instance.call(&mut store, name, vec![]).unwrap()[..] {
+            [Val::V128Const(v)] => v,
+            _ => panic!("expected a v128"),
+        }).collect()
+    };
+
+    // the product rounds away the low bits of the result unless fused
+    assert_eq!(run(RelaxedSimd::Deterministic), vec![
+        0, 0, f64x2(2f64.powi(-60)), splat(&Shape::I16x8, 0x7FFF), splat(&Shape::I32x4, -4i32 as u32 as u64),
+        f32x4(-2f32.powi(-24)),
+    ]);
+    assert_eq!(run(RelaxedSimd::Fast), vec![
+        splat(&Shape::I8x16, 0x01), splat(&Shape::I32x4, 0x8000_0000), f64x2(0.0),
+        splat(&Shape::I16x8, 0x8000), splat(&Shape::I32x4, 4 * 0xFF), f32x4(0.0),
+    ]);
+}
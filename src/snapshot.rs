@@ -0,0 +1,865 @@
+use std::convert::{TryFrom, TryInto};
+use std::sync::Arc;
+
+use crate::{
+    Store,
+    Module,
+    ModuleInst,
+    ExportInst,
+    ExternVal,
+    FuncInst,
+    TableInst,
+    MemInst,
+    GlobalInst,
+    ElemInst,
+    DataInst,
+    TagInst,
+    ExnInst,
+    Heap,
+    HeapInst,
+    HeapAddr,
+    HostCode,
+    FuncType,
+    SubType,
+    CompType,
+    FieldType,
+    StorageType,
+    ValType,
+    RefType,
+    HeapType,
+    ElemType,
+    Val,
+    Ref,
+    Mut,
+    GlobalType,
+    Share,
+    IdxType,
+    Byte,
+    Error,
+    ImportDesc,
+};
+
+const MAGIC: [Byte; 4] = *b"WQSS";
+const VERSION: u32 = 14;
+
+/// Serialize the store and the instances `moduleinsts` living in it.
+/// Functions are saved as references into their instance, so the instance of
+/// every function in the store must be in `moduleinsts`.
+pub fn store_snapshot(store: &Store, moduleinsts: &[ModuleInst]) -> Result<Vec<Byte>, Error> {
+    let mut writer = Writer::default();
+    writer.bytes(&MAGIC);
+    writer.u32(VERSION);
+
+    writer.len(moduleinsts.len());
+    for moduleinst in moduleinsts {
+        writer.moduleinst(moduleinst);
+    }
+
+    writer.len(store.funcs.len());
+    for (funcaddr, funcinst) in store.funcs.iter().enumerate() {
+        match funcinst {
+            FuncInst::User(user) => {
+                let moduleidx = moduleinsts.iter().position(|m| m == &user.module)
+                    .ok_or_else(|| Error::Snapshot(format!("instance of function {} is not given", funcaddr)))?;
+                let pos = user.module.funcaddrs.iter().rposition(|a| a == &funcaddr)
+                    .ok_or_else(|| Error::Snapshot(format!("function {} is not in its instance", funcaddr)))?;
+                writer.byte(0x00);
+                writer.len(moduleidx);
+                writer.len(pos);
+            },
+            FuncInst::Host(host) => {
+                writer.byte(0x01);
+                writer.functype(&host.tp);
+            },
+        }
+    }
+
+    writer.len(store.tables.len());
+    for (tableaddr, tableinst) in store.tables.iter().enumerate() {
+        if !writer.types(&tableinst.types, moduleinsts) {
+            return Err(Error::Snapshot(format!("instance of table {} is not given", tableaddr)));
+        }
+        writer.valtype(&tableinst.tp.valtype());
+        writer.max(&tableinst.max.map(u64::from));
+        writer.refs(&tableinst.elem);
+    }
+
+    writer.len(store.mems.len());
+    for (memaddr, meminst) in store.mems.iter().enumerate() {
+        // other stores may be using a shared memory
+        if meminst.share() == Share::Shared {
+            return Err(Error::Snapshot(format!("memory {} is shared", memaddr)));
+        }
+        writer.byte(if meminst.idxtype == IdxType::I64 { 0x01 } else { 0x00 });
+        writer.byte(if meminst.share() == Share::Shared { 0x01 } else { 0x00 });
+        writer.max(&meminst.max);
+        writer.u32(meminst.page_size_log2);
+        // memories may be larger than 4GiB
+        meminst.with_data(|data| {
+            writer.u64(data.len() as u64);
+            writer.bytes(data);
+        });
+    }
+
+    // the type of a global may refer to the types of its instance
+    writer.len(store.globals.len());
+    for (globaladdr, globalinst) in store.globals.iter().enumerate() {
+        if !writer.types(&globalinst.types, moduleinsts) {
+            return Err(Error::Snapshot(format!("instance of global {} is not given", globaladdr)));
+        }
+        writer.valtype(&globalinst.tp.0);
+        writer.byte(if globalinst.tp.is_var() { 0x01 } else { 0x00 });
+        writer.val(&globalinst.value);
+    }
+
+    writer.len(store.elems.len());
+    for eleminst in &store.elems {
+        writer.refs(&eleminst.elem);
+    }
+
+    writer.len(store.datas.len());
+    for datainst in &store.datas {
+        writer.len(datainst.data.len());
+        writer.bytes(&datainst.data);
+    }
+
+    writer.len(store.tags.len());
+    for (tagaddr, taginst) in store.tags.iter().enumerate() {
+        if !writer.types(&taginst.types, moduleinsts) {
+            return Err(Error::Snapshot(format!("instance of tag {} is not given", tagaddr)));
+        }
+        writer.functype(&taginst.tp);
+    }
+
+    writer.len(store.exns.len());
+    for exninst in &store.exns {
+        writer.len(exninst.tag);
+        writer.len(exninst.fields.len());
+        for val in &exninst.fields { writer.val(val); }
+    }
+
+    // objects refer to the types of the instance that allocated them,
+    // references to them to the generation of their slot
+    writer.len(store.heap.objs.len());
+    for (heapaddr, (obj, generation)) in store.heap.objs.iter().zip(&store.heap.generations).enumerate() {
+        writer.u32(*generation);
+        match obj {
+            None => writer.byte(0x00),
+            Some(obj) => {
+                let moduleidx = moduleinsts.iter().position(|m| Arc::ptr_eq(&m.types, &obj.types))
+                    .ok_or_else(|| Error::Snapshot(format!("instance of object {} is not given", heapaddr)))?;
+                writer.byte(0x01);
+                writer.len(moduleidx);
+                writer.u32(obj.tp);
+                writer.len(obj.fields.len());
+                for val in &obj.fields { writer.val(val); }
+            },
+        }
+    }
+
+    Ok(writer.0)
+}
+
+/// Rebuild a store from `store_snapshot` output.
+/// `modules` are the modules of the saved instances, in the same order,
+/// and `hostfuncs` the host functions of the store, in allocation order.
+pub fn store_restore(bytes: &[Byte], modules: &[Module], hostfuncs: Vec<HostCode>) -> Result<(Store, Vec<ModuleInst>), Error> {
+    let mut reader = Reader{ bytes, pos: 0 };
+    if reader.take(4)? != MAGIC {
+        return Err(Error::Snapshot("not a snapshot".to_string()));
+    }
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(Error::Snapshot(format!("unsupported version {}", version)));
+    }
+
+    let mut moduleinsts = vec![];
+    for _ in 0..reader.len()? {
+        moduleinsts.push(reader.moduleinst()?);
+    }
+    if moduleinsts.len() != modules.len() {
+        return Err(Error::Snapshot("modules do not match the instances".to_string()));
+    }
+
+    let mut store = Store::default();
+    let mut hostfuncs = hostfuncs.into_iter();
+    for _ in 0..reader.len()? {
+        let funcinst = match reader.byte()? {
+            0x00 => {
+                let moduleidx = reader.len()?;
+                let pos = reader.len()?;
+                let (moduleinst, module) = match (moduleinsts.get(moduleidx), modules.get(moduleidx)) {
+                    (Some(moduleinst), Some(module)) => (moduleinst, module),
+                    _ => return Err(Error::Snapshot("instance out of range".to_string())),
+                };
+                let imported = module.imports.iter().filter(|import| matches!(import.desc, ImportDesc::Func(_))).count();
+                let func = match pos.checked_sub(imported).and_then(|idx| module.funcs.get(idx)) {
+                    Some(func) => func,
+                    None => return Err(Error::Snapshot("function out of range".to_string())),
+                };
+                let functype = match moduleinst.types.get(func.tp as usize).and_then(SubType::functype) {
+                    Some(functype) => functype.clone(),
+                    None => return Err(Error::Snapshot("function type out of range".to_string())),
+                };
+                FuncInst::user(functype, moduleinst.clone(), func.clone())
+            },
+            0x01 => {
+                let functype = reader.functype()?;
+                match hostfuncs.next() {
+                    Some(hostcode) => FuncInst::host(functype, hostcode),
+                    None => return Err(Error::Snapshot("missing host function".to_string())),
+                }
+            },
+            _ => return Err(Error::Snapshot("invalid function".to_string())),
+        };
+        store.funcs.push(funcinst);
+    }
+    if hostfuncs.next().is_some() {
+        return Err(Error::Snapshot("too many host functions".to_string()));
+    }
+
+    for _ in 0..reader.len()? {
+        let types = reader.types(&moduleinsts)?;
+        let tp = match reader.valtype()?.reftype() {
+            Some(tp) => tp,
+            None => return Err(Error::Snapshot("invalid reference type".to_string())),
+        };
+        let max = match reader.max()?.map(u32::try_from) {
+            None => None,
+            Some(Ok(max)) => Some(max),
+            Some(Err(_)) => return Err(Error::Snapshot("invalid limits".to_string())),
+        };
+        let elem = reader.refs()?;
+        store.tables.push(TableInst{ elem, max, tp, types });
+    }
+
+    for _ in 0..reader.len()? {
+        let idxtype = match reader.byte()? {
+            0x00 => IdxType::I32,
+            0x01 => IdxType::I64,
+            _ => return Err(Error::Snapshot("invalid index type".to_string())),
+        };
+        let share = match reader.byte()? {
+            0x00 => Share::Unshared,
+            0x01 => Share::Shared,
+            _ => return Err(Error::Snapshot("invalid sharing".to_string())),
+        };
+        let max = reader.max()?;
+        let page_size_log2 = match reader.u32()? {
+            page_size_log2 @ (0 | 16) => page_size_log2,
+            _ => return Err(Error::Snapshot("invalid page size".to_string())),
+        };
+        if share == Share::Shared && max.is_none() {
+            return Err(Error::Snapshot("shared memory without maximum".to_string()));
+        }
+        let n = reader.u64()?;
+        // whole pages, within the maximum and the index type
+        let pages = n >> page_size_log2;
+        if pages << page_size_log2 != n || pages > max.unwrap_or(u64::MAX).min(idxtype.max_pages(page_size_log2)) {
+            return Err(Error::Snapshot("memory size out of its limits".to_string()));
+        }
+        let n = usize::try_from(n).map_err(|_| Error::Snapshot("memory too large".to_string()))?;
+        let data = reader.take(n)?.to_vec();
+        store.mems.push(MemInst::new(data, max, share, idxtype, page_size_log2));
+    }
+
+    for _ in 0..reader.len()? {
+        let types = reader.types(&moduleinsts)?;
+        let valtype = reader.valtype()?;
+        let mutability = match reader.byte()? {
+            0x00 => Mut::Const,
+            0x01 => Mut::Var,
+            _ => return Err(Error::Snapshot("invalid mutability".to_string())),
+        };
+        let value = reader.val()?;
+        if !val_fits(&value, &valtype, &types) {
+            return Err(Error::Snapshot("global value of another type".to_string()));
+        }
+        store.globals.push(GlobalInst{ value, tp: GlobalType(valtype, mutability), types });
+    }
+
+    for _ in 0..reader.len()? {
+        let elem = reader.refs()?;
+        store.elems.push(ElemInst{ elem });
+    }
+
+    for _ in 0..reader.len()? {
+        let n = reader.len()?;
+        let data = reader.take(n)?.to_vec();
+        store.datas.push(DataInst{ data });
+    }
+
+    for _ in 0..reader.len()? {
+        let types = reader.types(&moduleinsts)?;
+        let tp = reader.functype()?;
+        store.tags.push(TagInst{ tp, types });
+    }
+
+    for _ in 0..reader.len()? {
+        let tag = reader.len()?;
+        let mut fields = vec![];
+        for _ in 0..reader.len()? { fields.push(reader.val()?); }
+        store.exns.push(ExnInst{ tag, fields });
+    }
+
+    let mut objs = vec![];
+    let mut generations = vec![];
+    for _ in 0..reader.len()? {
+        generations.push(reader.u32()?);
+        let obj = match reader.byte()? {
+            0x00 => None,
+            0x01 => {
+                let types = match moduleinsts.get(reader.len()?) {
+                    Some(moduleinst) => moduleinst.types.clone(),
+                    None => return Err(Error::Snapshot("instance out of range".to_string())),
+                };
+                let tp = reader.u32()?;
+                if tp as usize >= types.len() {
+                    return Err(Error::Snapshot("object type out of range".to_string()));
+                }
+                let mut fields = vec![];
+                for _ in 0..reader.len()? { fields.push(reader.val()?); }
+                Some(HeapInst{ tp, types, fields })
+            },
+            _ => return Err(Error::Snapshot("invalid object".to_string())),
+        };
+        objs.push(obj);
+    }
+    store.heap = Heap::restore(objs, generations);
+
+    if reader.pos != bytes.len() {
+        return Err(Error::Snapshot("trailing bytes".to_string()));
+    }
+
+    // every address must point into the restored store
+    let funcs = store.funcs.len();
+    let mut refs = store.tables.iter().flat_map(|t| t.elem.iter())
+        .chain(store.elems.iter().flat_map(|e| e.elem.iter()))
+        .chain(store.globals.iter().map(|g| &g.value)
+            .chain(store.exns.iter().flat_map(|e| e.fields.iter()))
+            .chain(store.heap.objs.iter().flatten().flat_map(|o| o.fields.iter()))
+            .filter_map(|v| match v {
+                Val::Ref(r) => Some(r),
+                _ => None,
+            }));
+    let mut valid = refs.all(|r| match r {
+        Ref::Func(a) => *a < funcs,
+        Ref::Exn(a) => *a < store.exns.len(),
+        Ref::Struct(_) | Ref::Array(_) | Ref::ExternAny(_) => store.heap.is_live(r),
+        _ => true,
+    }) && store.exns.iter().all(|e| e.tag < store.tags.len());
+    for moduleinst in &moduleinsts {
+        valid &= moduleinst.funcaddrs.iter().all(|a| *a < funcs)
+            && moduleinst.tableaddrs.iter().all(|a| *a < store.tables.len())
+            && moduleinst.memaddrs.iter().all(|a| *a < store.mems.len())
+            && moduleinst.globaladdrs.iter().all(|a| *a < store.globals.len())
+            && moduleinst.elemaddrs.iter().all(|a| *a < store.elems.len())
+            && moduleinst.dataaddrs.iter().all(|a| *a < store.datas.len())
+            && moduleinst.tagaddrs.iter().all(|a| *a < store.tags.len());
+    }
+    if !valid {
+        return Err(Error::Snapshot("address out of range".to_string()));
+    }
+
+    Ok((store, moduleinsts))
+}
+
+#[derive(Default)]
+struct Writer(Vec<Byte>);
+
+impl Writer {
+    fn byte(&mut self, b: Byte) { self.0.push(b); }
+    fn bytes(&mut self, bs: &[Byte]) { self.0.extend_from_slice(bs); }
+    fn u32(&mut self, n: u32) { self.bytes(&n.to_le_bytes()); }
+    fn u64(&mut self, n: u64) { self.bytes(&n.to_le_bytes()); }
+    fn u128(&mut self, n: u128) { self.bytes(&n.to_le_bytes()); }
+    fn len(&mut self, n: usize) { self.u32(n as u32); }
+
+    fn max(&mut self, max: &Option<u64>) {
+        match max {
+            None => self.byte(0x00),
+            Some(m) => {
+                self.byte(0x01);
+                self.u64(*m);
+            },
+        }
+    }
+
+    fn valtype(&mut self, vt: &ValType) {
+        match vt {
+            ValType::I32 => self.byte(0x7F),
+            ValType::I64 => self.byte(0x7E),
+            ValType::F32 => self.byte(0x7D),
+            ValType::F64 => self.byte(0x7C),
+            ValType::V128 => self.byte(0x7B),
+            ValType::FuncRef => self.byte(0x70),
+            ValType::ExternRef => self.byte(0x6F),
+            ValType::ExnRef => self.byte(0x69),
+            ValType::Ref(reftype) => {
+                self.byte(if reftype.nullable { 0x63 } else { 0x64 });
+                self.heaptype(&reftype.heap);
+            },
+        }
+    }
+
+    // type indices are prefixed by 0x00
+    fn heaptype(&mut self, ht: &HeapType) {
+        match ht {
+            HeapType::Func => self.byte(0x70),
+            HeapType::Extern => self.byte(0x6F),
+            HeapType::Exn => self.byte(0x69),
+            HeapType::Any => self.byte(0x6E),
+            HeapType::Eq => self.byte(0x6D),
+            HeapType::I31 => self.byte(0x6C),
+            HeapType::Struct => self.byte(0x6B),
+            HeapType::Array => self.byte(0x6A),
+            HeapType::None => self.byte(0x71),
+            HeapType::NoFunc => self.byte(0x73),
+            HeapType::NoExtern => self.byte(0x72),
+            HeapType::NoExn => self.byte(0x74),
+            HeapType::Type(typeidx) => {
+                self.byte(0x00);
+                self.u32(*typeidx);
+            },
+        }
+    }
+
+    fn functype(&mut self, ft: &FuncType) {
+        for rt in &[&ft.0, &ft.1] {
+            self.len(rt.len());
+            for vt in rt.iter() { self.valtype(vt); }
+        }
+    }
+
+    fn subtype(&mut self, st: &SubType) {
+        self.byte(if st.is_final { 0x01 } else { 0x00 });
+        self.len(st.supers.len());
+        for typeidx in &st.supers { self.u32(*typeidx); }
+        self.u32(st.rec.0);
+        self.u32(st.rec.1);
+        match &st.comp {
+            CompType::Func(ft) => {
+                self.byte(0x60);
+                self.functype(ft);
+            },
+            CompType::Struct(fields) => {
+                self.byte(0x5F);
+                self.len(fields.len());
+                for field in fields { self.fieldtype(field); }
+            },
+            CompType::Array(field) => {
+                self.byte(0x5E);
+                self.fieldtype(field);
+            },
+        }
+    }
+
+    fn fieldtype(&mut self, FieldType(st, mutability): &FieldType) {
+        match st {
+            StorageType::Val(vt) => self.valtype(vt),
+            StorageType::I8 => self.byte(0x78),
+            StorageType::I16 => self.byte(0x77),
+        }
+        self.byte(if *mutability == Mut::Var { 0x01 } else { 0x00 });
+    }
+
+    fn val(&mut self, val: &Val) {
+        self.valtype(&val.valtype());
+        match val {
+            Val::I32Const(n) => self.u32(*n),
+            Val::I64Const(n) => self.u64(*n),
+            Val::F32Const(f) => self.u32(f.to_bits()),
+            Val::F64Const(f) => self.u64(f.to_bits()),
+            Val::V128Const(n) => self.u128(*n),
+            Val::Ref(r) => self.reference(r),
+        }
+    }
+
+    fn addrs(&mut self, addrs: &[usize]) {
+        self.len(addrs.len());
+        for addr in addrs { self.len(*addr); }
+    }
+
+    fn heapaddr(&mut self, heapaddr: &HeapAddr) {
+        self.len(heapaddr.index);
+        self.u32(heapaddr.generation);
+    }
+
+    fn reference(&mut self, r: &Ref) {
+        match r {
+            Ref::Null(elemtype) => {
+                self.byte(0x00);
+                self.valtype(&elemtype.valtype());
+            },
+            Ref::Func(funcaddr) => {
+                self.byte(0x01);
+                self.len(*funcaddr);
+            },
+            Ref::Extern(externaddr) => {
+                self.byte(0x02);
+                self.len(*externaddr);
+            },
+            Ref::Exn(exnaddr) => {
+                self.byte(0x03);
+                self.len(*exnaddr);
+            },
+            Ref::I31(n) => {
+                self.byte(0x04);
+                self.u32(*n);
+            },
+            Ref::Struct(heapaddr) => {
+                self.byte(0x05);
+                self.heapaddr(heapaddr);
+            },
+            Ref::Array(heapaddr) => {
+                self.byte(0x06);
+                self.heapaddr(heapaddr);
+            },
+            Ref::AnyExtern(externaddr) => {
+                self.byte(0x07);
+                self.len(*externaddr);
+            },
+            // the converted reference follows
+            Ref::ExternAny(_) => {
+                self.byte(0x08);
+                self.reference(&r.convert());
+            },
+        }
+    }
+
+    fn refs(&mut self, elem: &[Ref]) {
+        self.len(elem.len());
+        for r in elem { self.reference(r); }
+    }
+
+    /// Types of an instance, by its position in `moduleinsts`, or none for the host.
+    /// False when the instance is not given.
+    fn types(&mut self, types: &Arc<Vec<SubType>>, moduleinsts: &[ModuleInst]) -> bool {
+        if types.is_empty() {
+            self.byte(0x00);
+            return true;
+        }
+        match moduleinsts.iter().position(|m| Arc::ptr_eq(&m.types, types)) {
+            Some(moduleidx) => {
+                self.byte(0x01);
+                self.len(moduleidx);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn moduleinst(&mut self, moduleinst: &ModuleInst) {
+        self.len(moduleinst.types.len());
+        for st in moduleinst.types.iter() { self.subtype(st); }
+        self.addrs(&moduleinst.funcaddrs);
+        self.addrs(&moduleinst.tableaddrs);
+        self.addrs(&moduleinst.memaddrs);
+        self.addrs(&moduleinst.globaladdrs);
+        self.addrs(&moduleinst.elemaddrs);
+        self.addrs(&moduleinst.dataaddrs);
+        self.addrs(&moduleinst.tagaddrs);
+        self.len(moduleinst.exports.len());
+        for export in &moduleinst.exports {
+            self.len(export.name.len());
+            self.bytes(export.name.as_bytes());
+            let (tag, addr) = match export.value {
+                ExternVal::Func(addr) => (0x00, addr),
+                ExternVal::Table(addr) => (0x01, addr),
+                ExternVal::Mem(addr) => (0x02, addr),
+                ExternVal::Global(addr) => (0x03, addr),
+                ExternVal::Tag(addr) => (0x04, addr),
+            };
+            self.byte(tag);
+            self.len(addr);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [Byte],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [Byte], Error> {
+        if self.bytes.len() - self.pos < n {
+            return Err(Error::Snapshot("unexpected end".to_string()));
+        }
+        let bs = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bs)
+    }
+
+    fn byte(&mut self) -> Result<Byte, Error> { Ok(self.take(1)?[0]) }
+    fn u32(&mut self) -> Result<u32, Error> { Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap())) }
+    fn u64(&mut self) -> Result<u64, Error> { Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap())) }
+    fn u128(&mut self) -> Result<u128, Error> { Ok(u128::from_le_bytes(self.take(16)?.try_into().unwrap())) }
+    fn len(&mut self) -> Result<usize, Error> { Ok(self.u32()? as usize) }
+
+    fn max(&mut self) -> Result<Option<u64>, Error> {
+        match self.byte()? {
+            0x00 => Ok(None),
+            0x01 => Ok(Some(self.u64()?)),
+            _ => Err(Error::Snapshot("invalid limits".to_string())),
+        }
+    }
+
+    fn valtype(&mut self) -> Result<ValType, Error> {
+        match self.byte()? {
+            0x7F => Ok(ValType::I32),
+            0x7E => Ok(ValType::I64),
+            0x7D => Ok(ValType::F32),
+            0x7C => Ok(ValType::F64),
+            0x7B => Ok(ValType::V128),
+            0x70 => Ok(ValType::FuncRef),
+            0x6F => Ok(ValType::ExternRef),
+            0x69 => Ok(ValType::ExnRef),
+            0x63 => Ok(RefType { nullable: true, heap: self.heaptype()? }.valtype()),
+            0x64 => Ok(RefType { nullable: false, heap: self.heaptype()? }.valtype()),
+            _ => Err(Error::Snapshot("invalid value type".to_string())),
+        }
+    }
+
+    fn heaptype(&mut self) -> Result<HeapType, Error> {
+        match self.byte()? {
+            0x70 => Ok(HeapType::Func),
+            0x6F => Ok(HeapType::Extern),
+            0x69 => Ok(HeapType::Exn),
+            0x6E => Ok(HeapType::Any),
+            0x6D => Ok(HeapType::Eq),
+            0x6C => Ok(HeapType::I31),
+            0x6B => Ok(HeapType::Struct),
+            0x6A => Ok(HeapType::Array),
+            0x71 => Ok(HeapType::None),
+            0x73 => Ok(HeapType::NoFunc),
+            0x72 => Ok(HeapType::NoExtern),
+            0x74 => Ok(HeapType::NoExn),
+            0x00 => Ok(HeapType::Type(self.u32()?)),
+            _ => Err(Error::Snapshot("invalid heap type".to_string())),
+        }
+    }
+
+    fn elemtype(&mut self) -> Result<ElemType, Error> {
+        match self.valtype()? {
+            ValType::FuncRef => Ok(ElemType::FuncRef),
+            ValType::ExternRef => Ok(ElemType::ExternRef),
+            ValType::ExnRef => Ok(ElemType::ExnRef),
+            vt if vt == ElemType::AnyRef.valtype() => Ok(ElemType::AnyRef),
+            _ => Err(Error::Snapshot("invalid reference type".to_string())),
+        }
+    }
+
+    fn functype(&mut self) -> Result<FuncType, Error> {
+        let mut rts = vec![];
+        for _ in 0..2 {
+            let mut rt = vec![];
+            for _ in 0..self.len()? { rt.push(self.valtype()?); }
+            rts.push(rt);
+        }
+        let returntypes = rts.pop().unwrap();
+        let argtypes = rts.pop().unwrap();
+        Ok((argtypes, returntypes))
+    }
+
+    fn subtype(&mut self) -> Result<SubType, Error> {
+        let is_final = match self.byte()? {
+            0x00 => false,
+            0x01 => true,
+            _ => return Err(Error::Snapshot("invalid subtype".to_string())),
+        };
+        let mut supers = vec![];
+        for _ in 0..self.len()? { supers.push(self.u32()?); }
+        let rec = (self.u32()?, self.u32()?);
+        let comp = match self.byte()? {
+            0x60 => CompType::Func(self.functype()?),
+            0x5F => {
+                let mut fields = vec![];
+                for _ in 0..self.len()? { fields.push(self.fieldtype()?); }
+                CompType::Struct(fields)
+            },
+            0x5E => CompType::Array(self.fieldtype()?),
+            _ => return Err(Error::Snapshot("invalid composite type".to_string())),
+        };
+        Ok(SubType{ is_final, supers, comp, rec })
+    }
+
+    fn fieldtype(&mut self) -> Result<FieldType, Error> {
+        let st = match self.bytes.get(self.pos) {
+            Some(0x78) => { self.pos += 1; StorageType::I8 },
+            Some(0x77) => { self.pos += 1; StorageType::I16 },
+            _ => StorageType::Val(self.valtype()?),
+        };
+        let mutability = match self.byte()? {
+            0x00 => Mut::Const,
+            0x01 => Mut::Var,
+            _ => return Err(Error::Snapshot("invalid mutability".to_string())),
+        };
+        Ok(FieldType(st, mutability))
+    }
+
+    fn val(&mut self) -> Result<Val, Error> {
+        match self.valtype()? {
+            ValType::I32 => Ok(Val::I32Const(self.u32()?)),
+            ValType::I64 => Ok(Val::I64Const(self.u64()?)),
+            ValType::F32 => Ok(Val::F32Const(f32::from_bits(self.u32()?))),
+            ValType::F64 => Ok(Val::F64Const(f64::from_bits(self.u64()?))),
+            ValType::V128 => Ok(Val::V128Const(self.u128()?)),
+            ValType::FuncRef | ValType::ExternRef | ValType::ExnRef | ValType::Ref(_) => Ok(Val::Ref(self.reference()?)),
+        }
+    }
+
+    fn addrs(&mut self) -> Result<Vec<usize>, Error> {
+        let mut addrs = vec![];
+        for _ in 0..self.len()? { addrs.push(self.len()?); }
+        Ok(addrs)
+    }
+
+    fn heapaddr(&mut self) -> Result<HeapAddr, Error> {
+        let index = self.len()?;
+        let generation = self.u32()?;
+        Ok(HeapAddr{ index, generation })
+    }
+
+    fn reference(&mut self) -> Result<Ref, Error> {
+        match self.byte()? {
+            0x00 => Ok(Ref::Null(self.elemtype()?)),
+            0x01 => Ok(Ref::Func(self.len()?)),
+            0x02 => Ok(Ref::Extern(self.len()?)),
+            0x03 => Ok(Ref::Exn(self.len()?)),
+            0x04 => Ok(Ref::I31(self.u32()?)),
+            0x05 => Ok(Ref::Struct(self.heapaddr()?)),
+            0x06 => Ok(Ref::Array(self.heapaddr()?)),
+            0x07 => Ok(Ref::AnyExtern(self.len()?)),
+            0x08 => match self.reference()? {
+                r @ (Ref::I31(_) | Ref::Struct(_) | Ref::Array(_)) => Ok(r.convert()),
+                _ => Err(Error::Snapshot("invalid reference".to_string())),
+            },
+            _ => Err(Error::Snapshot("invalid reference".to_string())),
+        }
+    }
+
+    fn refs(&mut self) -> Result<Vec<Ref>, Error> {
+        let mut elem = vec![];
+        for _ in 0..self.len()? { elem.push(self.reference()?); }
+        Ok(elem)
+    }
+
+    fn types(&mut self, moduleinsts: &[ModuleInst]) -> Result<Arc<Vec<SubType>>, Error> {
+        match self.byte()? {
+            0x00 => Ok(Arc::default()),
+            0x01 => match moduleinsts.get(self.len()?) {
+                Some(moduleinst) => Ok(moduleinst.types.clone()),
+                None => Err(Error::Snapshot("instance out of range".to_string())),
+            },
+            _ => Err(Error::Snapshot("invalid types".to_string())),
+        }
+    }
+
+    fn moduleinst(&mut self) -> Result<ModuleInst, Error> {
+        let mut types = vec![];
+        for _ in 0..self.len()? { types.push(self.subtype()?); }
+        let funcaddrs = self.addrs()?;
+        let tableaddrs = self.addrs()?;
+        let memaddrs = self.addrs()?;
+        let globaladdrs = self.addrs()?;
+        let elemaddrs = self.addrs()?;
+        let dataaddrs = self.addrs()?;
+        let tagaddrs = self.addrs()?;
+        let mut exports = vec![];
+        for _ in 0..self.len()? {
+            let n = self.len()?;
+            let name = String::from_utf8(self.take(n)?.to_vec())
+                .map_err(|_| Error::Snapshot("invalid export name".to_string()))?;
+            let value = match (self.byte()?, self.len()?) {
+                (0x00, addr) => ExternVal::Func(addr),
+                (0x01, addr) => ExternVal::Table(addr),
+                (0x02, addr) => ExternVal::Mem(addr),
+                (0x03, addr) => ExternVal::Global(addr),
+                (0x04, addr) => ExternVal::Tag(addr),
+                _ => return Err(Error::Snapshot("invalid export".to_string())),
+            };
+            exports.push(ExportInst{ name, value });
+        }
+        Ok(ModuleInst{ types: Arc::new(types), funcaddrs, tableaddrs, memaddrs, globaladdrs, elemaddrs, dataaddrs, tagaddrs, exports })
+    }
+}
+
+/// Whether `value` may be of type `valtype`. References are checked for their hierarchy
+/// and null only, as the objects they refer to are not restored yet.
+fn val_fits(value: &Val, valtype: &ValType, types: &[SubType]) -> bool {
+    match (value, valtype.reftype()) {
+        (Val::Ref(r), Some(reftype)) => {
+            r.elemtype() == reftype.heap.elemtype(types) && (reftype.nullable || !matches!(r, Ref::Null(_)))
+        },
+        (Val::Ref(_), None) | (_, Some(_)) => false,
+        (value, None) => value.valtype() == *valtype,
+    }
+}
+
+#[test]
+fn test_store_snapshot() {
+    use crate::{
+        store_init, func_alloc, func_invoke, mem_alloc, mem_write, mem_read, global_alloc, global_read,
+        Func, Expr, Instr, MemType, Limits, Share, GlobalType, HostResult,
+    };
+
+    fn host(_store: &mut Store, _args: &[Val]) -> HostResult { HostResult::Vals(vec![]) }
+
+    // (module (import "env" "host" (func)) (func (result i32) global.get 0))
+    fn module() -> Module {
+        Module {
+            types: vec![(vec![], vec![]).into(), (vec![], vec![ValType::I32]).into()],
+            imports: vec![crate::Import{ module: "env".to_string(), name: "host".to_string(), desc: ImportDesc::Func(0) }],
+            funcs: vec![Func{ tp: 1, locals: vec![], body: Expr(vec![Instr::GlobalGet(0)]) }],
+            ..Module::default()
+        }
+    }
+
+    // the store after instantiating the module and running it for a while
+    let mut store = store_init();
+    let hostaddr = func_alloc(&mut store, (vec![], vec![]), host).unwrap();
+    let globaladdr = global_alloc(&mut store, GlobalType(ValType::I32, Mut::Var), Val::I32Const(7));
+    let memaddr = mem_alloc(&mut store, MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32)).unwrap();
+    mem_write(&mut store, memaddr, 42, 0xAB).unwrap();
+    let moduleinst = ModuleInst {
+        types: Arc::new(module().types),
+        funcaddrs: vec![hostaddr, 1],
+        memaddrs: vec![memaddr],
+        globaladdrs: vec![globaladdr],
+        exports: vec![ExportInst{ name: "f".to_string(), value: ExternVal::Func(1) }],
+        ..ModuleInst::default()
+    };
+    store.funcs.push(FuncInst::user((vec![], vec![ValType::I32]), moduleinst.clone(), module().funcs[0].clone()));
+    global_alloc(&mut store, GlobalType(ValType::F64, Mut::Const), Val::F64Const(1.5));
+    global_alloc(&mut store, GlobalType(ValType::ExternRef, Mut::Var), Val::Ref(Ref::Extern(9)));
+    global_alloc(&mut store, GlobalType(ValType::V128, Mut::Var), Val::V128Const(u128::MAX - 1));
+
+    let bytes = store_snapshot(&store, std::slice::from_ref(&moduleinst)).unwrap();
+    assert!(store_snapshot(&store, &[]).is_err());
+
+    let (mut restored, moduleinsts) = store_restore(&bytes, &[module()], vec![HostCode::Sync(host)]).unwrap();
+    assert_eq!(moduleinsts, vec![moduleinst]);
+    assert_eq!(mem_read(&restored, memaddr, 42).unwrap(), 0xAB);
+    assert_eq!(global_read(&restored, 1), Val::F64Const(1.5));
+    assert_eq!(global_read(&restored, 2), Val::Ref(Ref::Extern(9)));
+    assert_eq!(global_read(&restored, 3), Val::V128Const(u128::MAX - 1));
+    let (_, result) = func_invoke(&mut restored, 1, vec![]);
+    assert_eq!(result.unwrap(), vec![Val::I32Const(7)]);
+
+    assert!(store_restore(&bytes, &[module()], vec![]).is_err());
+    assert!(store_restore(&bytes[..bytes.len() - 1], &[module()], vec![HostCode::Sync(host)]).is_err());
+    let mut bad = bytes.clone();
+    bad[4] = 1;
+    assert!(store_restore(&bad, &[module()], vec![HostCode::Sync(host)]).is_err());
+
+    // a memory of part of a page, and an i32 global holding an i64
+    let mut store = store_init();
+    store.mems.push(MemInst::new(vec![0; 3], None, Share::Unshared, IdxType::I32, 16));
+    let bytes = store_snapshot(&store, &[]).unwrap();
+    assert!(matches!(store_restore(&bytes, &[], vec![]), Err(Error::Snapshot(_))));
+    let mut store = store_init();
+    store.globals.push(GlobalInst{ value: Val::I64Const(1), tp: GlobalType(ValType::I32, Mut::Const), types: Arc::default() });
+    let bytes = store_snapshot(&store, &[]).unwrap();
+    assert!(matches!(store_restore(&bytes, &[], vec![]), Err(Error::Snapshot(_))));
+}
@@ -1,5 +1,8 @@
 use crate::{
     Store,
+    ResourceLimiter,
+    InterruptHandle,
+    RelaxedSimd,
 };
 
 /// Return the enpty store.
@@ -7,6 +10,48 @@ pub fn store_init() -> Store {
     Store::default()
 }
 
+/// Install a limiter consulted on every allocation and growth in the store.
+pub fn store_limiter(store: &mut Store, limiter: impl ResourceLimiter + Send + 'static) {
+    store.limiter = Some(Box::new(limiter));
+}
+
+/// Return a handle which interrupts code running in the store.
+pub fn store_interrupt_handle(store: &Store) -> InterruptHandle {
+    store.interrupt.clone()
+}
+
+/// Set the fuel available to code running in the store, one unit per instruction.
+/// Running out of fuel suspends the execution.
+pub fn store_set_fuel(store: &mut Store, fuel: u64) {
+    store.fuel = Some(fuel);
+}
+
+/// Return the remaining fuel, or `None` if execution is not metered.
+pub fn store_fuel(store: &Store) -> Option<u64> {
+    store.fuel
+}
+
+/// Interrupt code running in the store once the epoch has advanced `delta` times from now.
+pub fn store_set_epoch_deadline(store: &mut Store, delta: u64) {
+    store.epoch_deadline = Some(store.interrupt.epoch() + delta);
+}
+
+/// Choose the semantics of the relaxed vector instructions run in the store.
+pub fn store_set_relaxed_simd(store: &mut Store, mode: RelaxedSimd) {
+    store.relaxed_simd = mode;
+}
+
+/// Free the structs and arrays reached neither from the globals, tables, element segments
+/// nor exceptions of the store, and return the number of live ones.
+/// Nothing is freed while a suspended invocation holds references.
+/// References kept by the host are not roots, using them after they are freed is an error.
+pub fn store_collect(store: &mut Store) -> usize {
+    if store.heap.threads == 0 && store.heap.suspensions() == 0 {
+        store.collect(std::iter::empty());
+    }
+    store.heap.len()
+}
+
 // #[derive(Default)]
 // pub struct Store {
 //     funcs: Vec<FuncInst>,
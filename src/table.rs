@@ -1,33 +1,44 @@
+use std::sync::Arc;
+
 use crate::{
     Store,
     TableType,
     TableAddr,
-    FuncAddr,
+    HeapType,
+    Ref,
+    Val,
     Error,
 
+    val_matches,
+
     alloc_table,
     find_tabletype,
     grow_table,
 };
 
-pub fn table_alloc(store: &mut Store, tabletype: TableType) -> TableAddr {
-    alloc_table(store, tabletype)
+// host tables have no types to refer to and start out null
+pub fn table_alloc(store: &mut Store, tabletype: TableType) -> Result<TableAddr, Error> {
+    if !tabletype.1.nullable || matches!(tabletype.1.heap, HeapType::Type(_)) { return Err(Error::Invalid); }
+    let init = Ref::Null(tabletype.1.heap.elemtype(&[]));
+    alloc_table(store, tabletype, Arc::default(), init)
 }
 
 pub fn table_type(store: &Store, tableaddr: TableAddr) -> TableType {
     find_tabletype(store, tableaddr).unwrap()
 }
 
-pub fn table_read(store: &Store, tableaddr: TableAddr, i: u32) -> Result<Option<FuncAddr>, Error> {
+pub fn table_read(store: &Store, tableaddr: TableAddr, i: u32) -> Result<Ref, Error> {
     let ti = &store.tables[tableaddr];
     if i as usize >= ti.elem.len() { return Err(Error::Invalid); }
     Ok(ti.elem[i as usize])
 }
 
-pub fn table_write(store: &mut Store, tableaddr: TableAddr, i: u32, funcaddr: Option<FuncAddr>) -> Result<(), Error> {
-    let ti = &mut store.tables[tableaddr];
+pub fn table_write(store: &mut Store, tableaddr: TableAddr, i: u32, r: Ref) -> Result<(), Error> {
+    let ti = &store.tables[tableaddr];
     if i as usize >= ti.elem.len() { return Err(Error::Invalid); }
-    ti.elem[i as usize] = funcaddr;
+    if !val_matches(store, &Val::Ref(r), &ti.tp.valtype(), &ti.types) || !store.heap.is_live(&r) { return Err(Error::Invalid); }
+    let ti = &mut store.tables[tableaddr];
+    ti.elem[i as usize] = r;
     Ok(())
 }
 
@@ -36,7 +47,8 @@ pub fn table_size(store: &Store, tableaddr: TableAddr) -> u32 {
     ti.elem.len() as u32
 }
 
-pub fn table_grow(store: &mut Store, tableaddr: TableAddr, n: u32) -> Result<(), Error> {
-    let ti = &mut store.tables[tableaddr];
-    grow_table(ti, n as usize)
+pub fn table_grow(store: &mut Store, tableaddr: TableAddr, n: u32, r: Ref) -> Result<(), Error> {
+    let ti = &store.tables[tableaddr];
+    if !val_matches(store, &Val::Ref(r), &ti.tp.valtype(), &ti.types) || !store.heap.is_live(&r) { return Err(Error::Invalid); }
+    grow_table(store, tableaddr, n as usize, r)
 }
\ No newline at end of file
@@ -0,0 +1,21 @@
+use std::sync::Arc;
+
+use crate::{
+    Store,
+    FuncType,
+    TagAddr,
+    Error,
+
+    alloc_tag,
+    find_tagtype,
+};
+
+/// Allocate an exception tag, whose params are the values thrown with it.
+pub fn tag_alloc(store: &mut Store, functype: FuncType) -> Result<TagAddr, Error> {
+    if !functype.1.is_empty() { return Err(Error::Invalid); }
+    Ok(alloc_tag(store, functype, Arc::default()))
+}
+
+pub fn tag_type(store: &Store, tagaddr: TagAddr) -> FuncType {
+    find_tagtype(store, tagaddr).unwrap()
+}
@@ -0,0 +1,81 @@
+//! Fixtures of the tests: modules built from their functions,
+//! and instances whose exports are looked up by name.
+
+use crate::{
+    Store,
+    Module,
+    ModuleInst,
+    Func,
+    FuncType,
+    TypeIdx,
+    Export,
+    ExportDesc,
+    Import,
+    ImportDesc,
+    ExternVal,
+    FuncAddr,
+    GlobalAddr,
+    Expr,
+    Instr,
+    Val,
+    Error,
+
+    module_instanciate,
+    instance_export,
+    func_invoke,
+};
+
+/// Function without locals.
+pub(crate) fn func(tp: TypeIdx, body: Vec<Instr>) -> Func {
+    Func { tp, locals: vec![], body: Expr(body) }
+}
+
+/// Import from the module `env`, as instances are given their imports in order.
+pub(crate) fn import(name: &str, desc: ImportDesc) -> Import {
+    Import { module: "env".to_string(), name: name.to_string(), desc }
+}
+
+/// Module of `types` and functions without locals, each exported by its name,
+/// so it has no imported functions.
+pub(crate) fn exported_funcs(types: Vec<FuncType>, funcs: Vec<(&str, TypeIdx, Vec<Instr>)>) -> Module {
+    let exports = funcs.iter().enumerate()
+        .map(|(i, (name, _, _))| Export { name: name.to_string(), desc: ExportDesc::Func(i as u32) })
+        .collect();
+    Module {
+        types: types.into_iter().map(Into::into).collect(),
+        funcs: funcs.into_iter().map(|(_, tp, body)| func(tp, body)).collect(),
+        exports,
+        ..Module::default()
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct TestInstance(pub ModuleInst);
+
+impl TestInstance {
+    pub fn new(store: &mut Store, module: Module, externvals: Vec<ExternVal>) -> TestInstance {
+        TestInstance(module_instanciate(store, module, externvals).unwrap())
+    }
+
+    pub fn export(&self, name: &str) -> ExternVal {
+        instance_export(self.0.clone(), name.to_string()).unwrap()
+    }
+
+    pub fn func(&self, name: &str) -> FuncAddr {
+        match self.export(name) {
+            ExternVal::Func(funcaddr) => funcaddr,
+            _ => panic!("{} is not a func", name),
+        }
+    }
+
+    pub fn global(&self, name: &str) -> GlobalAddr {
+        match self.export(name) {
+            ExternVal::Global(globaladdr) => globaladdr,
+            _ => panic!("{} is not a global", name),
+        }
+    }
+
+    pub fn call(&self, store: &mut Store, name: &str, args: Vec<Val>) -> Result<Vec<Val>, Error> {
+        func_invoke(store, self.func(name), args).1
+    }
+}
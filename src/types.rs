@@ -2,55 +2,449 @@ use crate::{
     Mut,
     Error,
     Context,
+    TypeIdx,
 };
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum ValType {
     I32, I64, F32, F64,
+    V128,
+    FuncRef, ExternRef, ExnRef,
+    /// Any other reference type, the nullable abstract ones are always
+    /// FuncRef, ExternRef or ExnRef.
+    Ref(RefType),
+}
+
+impl ValType {
+    pub fn is_ref(&self) -> bool {
+        self.reftype().is_some()
+    }
+
+    pub fn reftype(&self) -> Option<RefType> {
+        match self {
+            ValType::FuncRef => Some(RefType { nullable: true, heap: HeapType::Func }),
+            ValType::ExternRef => Some(RefType { nullable: true, heap: HeapType::Extern }),
+            ValType::ExnRef => Some(RefType { nullable: true, heap: HeapType::Exn }),
+            ValType::Ref(reftype) => Some(*reftype),
+            _ => None,
+        }
+    }
+
+    /// Locals of this type need no initialization.
+    pub fn is_defaultable(&self) -> bool {
+        self.reftype().is_none_or(|reftype| reftype.nullable)
+    }
+
+    /// Subtyping, with the type indices of `types`.
+    pub fn matches(&self, other: &ValType, types: &[SubType]) -> bool {
+        match (self.reftype(), other.reftype()) {
+            (Some(rt1), Some(rt2)) => rt1.matches(&rt2, types),
+            _ => self == other,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RefType {
+    pub nullable: bool,
+    pub heap: HeapType,
+}
+
+impl RefType {
+    pub fn valtype(&self) -> ValType {
+        match (self.nullable, self.heap) {
+            (true, HeapType::Func) => ValType::FuncRef,
+            (true, HeapType::Extern) => ValType::ExternRef,
+            (true, HeapType::Exn) => ValType::ExnRef,
+            _ => ValType::Ref(*self),
+        }
+    }
+
+    pub fn matches(&self, other: &RefType, types: &[SubType]) -> bool {
+        (other.nullable || !self.nullable) && self.heap.matches(&other.heap, types)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HeapType {
+    Func,
+    Extern,
+    Exn,
+    Any,
+    Eq,
+    I31,
+    Struct,
+    Array,
+    /// Bottom of the any hierarchy, only inhabited by null.
+    None,
+    NoFunc,
+    NoExtern,
+    NoExn,
+    /// A type of the module.
+    Type(TypeIdx),
+}
+
+impl HeapType {
+    pub fn matches(&self, other: &HeapType, types: &[SubType]) -> bool {
+        heap_matches(types, *self, types, *other)
+    }
+
+    /// Top of the hierarchy of this type, whose references share their null.
+    pub fn top(&self, types: &[SubType]) -> HeapType {
+        match self {
+            HeapType::Func | HeapType::NoFunc => HeapType::Func,
+            HeapType::Extern | HeapType::NoExtern => HeapType::Extern,
+            HeapType::Exn | HeapType::NoExn => HeapType::Exn,
+            HeapType::Type(typeidx) => match types.get(*typeidx as usize).map(|subtype| &subtype.comp) {
+                Some(CompType::Func(_)) => HeapType::Func,
+                _ => HeapType::Any,
+            },
+            _ => HeapType::Any,
+        }
+    }
+
+    pub fn elemtype(&self, types: &[SubType]) -> ElemType {
+        match self.top(types) {
+            HeapType::Func => ElemType::FuncRef,
+            HeapType::Extern => ElemType::ExternRef,
+            HeapType::Exn => ElemType::ExnRef,
+            _ => ElemType::AnyRef,
+        }
+    }
+}
+
+/// Subtyping of heap types, where the indices of `h1` are types of `t1`
+/// and those of `h2` types of `t2`.
+pub fn heap_matches(t1: &[SubType], h1: HeapType, t2: &[SubType], h2: HeapType) -> bool {
+    match (h1, h2) {
+        (HeapType::Type(x), HeapType::Type(y)) => {
+            type_equiv(t1, x, t2, y) || t1.get(x as usize).is_some_and(|subtype| {
+                subtype.supers.iter().any(|sup| heap_matches(t1, HeapType::Type(*sup), t2, h2))
+            })
+        },
+        (HeapType::Type(x), _) => match t1.get(x as usize).map(|subtype| &subtype.comp) {
+            Some(CompType::Func(_)) => heap_matches(t1, HeapType::Func, t2, h2),
+            Some(CompType::Struct(_)) => heap_matches(t1, HeapType::Struct, t2, h2),
+            Some(CompType::Array(_)) => heap_matches(t1, HeapType::Array, t2, h2),
+            None => false,
+        },
+        (HeapType::None, _) => h2.top(t2) == HeapType::Any,
+        (HeapType::NoFunc, _) => h2.top(t2) == HeapType::Func,
+        (HeapType::NoExtern, _) => h2.top(t2) == HeapType::Extern,
+        (HeapType::NoExn, _) => h2.top(t2) == HeapType::Exn,
+        (_, HeapType::Type(_)) => false,
+        (HeapType::I31 | HeapType::Struct | HeapType::Array, HeapType::Eq | HeapType::Any) => true,
+        (HeapType::Eq, HeapType::Any) => true,
+        _ => h1 == h2,
+    }
+}
+
+/// Subtyping of value types, where the indices of `vt1` are types of `t1`
+/// and those of `vt2` types of `t2`.
+pub fn valtype_matches(t1: &[SubType], vt1: &ValType, t2: &[SubType], vt2: &ValType) -> bool {
+    match (vt1.reftype(), vt2.reftype()) {
+        (Some(rt1), Some(rt2)) => (rt2.nullable || !rt1.nullable) && heap_matches(t1, rt1.heap, t2, rt2.heap),
+        _ => vt1 == vt2,
+    }
+}
+
+/// Subtyping of function types across modules, with contravariant parameters.
+pub fn functype_matches(t1: &[SubType], ft1: &FuncType, t2: &[SubType], ft2: &FuncType) -> bool {
+    let vals_match = |t1, vts1: &ResultType, t2, vts2: &ResultType| {
+        vts1.len() == vts2.len() && vts1.iter().zip(vts2).all(|(vt1, vt2)| valtype_matches(t1, vt1, t2, vt2))
+    };
+    vals_match(t2, &ft2.0, t1, &ft1.0) && vals_match(t1, &ft1.1, t2, &ft2.1)
+}
+
+/// Equivalence of types, which are equal when their recursion groups have
+/// the same structure and they are at the same position in them.
+pub fn type_equiv(t1: &[SubType], x: TypeIdx, t2: &[SubType], y: TypeIdx) -> bool {
+    if std::ptr::eq(t1, t2) && x == y { return true; }
+    let (rec1, rec2) = match (t1.get(x as usize), t2.get(y as usize)) {
+        (Some(st1), Some(st2)) => (st1.rec, st2.rec),
+        _ => return false,
+    };
+    if rec1 != rec2 { return false; }
+    let g1 = RecGroup { types: t1, start: x - rec1.0, len: rec1.1 };
+    let g2 = RecGroup { types: t2, start: y - rec2.0, len: rec2.1 };
+    (0..rec1.1).all(|i| {
+        match (t1.get((g1.start + i) as usize), t2.get((g2.start + i) as usize)) {
+            (Some(st1), Some(st2)) => g1.subtype_equiv(st1, &g2, st2),
+            _ => false,
+        }
+    })
+}
+
+// indices inside the group are compared by their position in it
+struct RecGroup<'a> {
+    types: &'a [SubType],
+    start: TypeIdx,
+    len: u32,
+}
+
+impl RecGroup<'_> {
+    fn idx_equiv(&self, x: TypeIdx, other: &RecGroup, y: TypeIdx) -> bool {
+        let inner = |group: &RecGroup, idx: TypeIdx| idx.checked_sub(group.start).filter(|pos| *pos < group.len);
+        match (inner(self, x), inner(other, y)) {
+            (Some(pos1), Some(pos2)) => pos1 == pos2,
+            (None, None) => type_equiv(self.types, x, other.types, y),
+            _ => false,
+        }
+    }
+
+    fn valtype_equiv(&self, vt1: &ValType, other: &RecGroup, vt2: &ValType) -> bool {
+        match (vt1.reftype(), vt2.reftype()) {
+            (Some(rt1), Some(rt2)) => rt1.nullable == rt2.nullable && match (rt1.heap, rt2.heap) {
+                (HeapType::Type(x), HeapType::Type(y)) => self.idx_equiv(x, other, y),
+                (h1, h2) => h1 == h2,
+            },
+            _ => vt1 == vt2,
+        }
+    }
+
+    fn field_equiv(&self, ft1: &FieldType, other: &RecGroup, ft2: &FieldType) -> bool {
+        ft1.1 == ft2.1 && match (&ft1.0, &ft2.0) {
+            (StorageType::Val(vt1), StorageType::Val(vt2)) => self.valtype_equiv(vt1, other, vt2),
+            (st1, st2) => st1 == st2,
+        }
+    }
+
+    fn subtype_equiv(&self, st1: &SubType, other: &RecGroup, st2: &SubType) -> bool {
+        let vals_equiv = |vts1: &ResultType, vts2: &ResultType| {
+            vts1.len() == vts2.len() && vts1.iter().zip(vts2).all(|(vt1, vt2)| self.valtype_equiv(vt1, other, vt2))
+        };
+        st1.is_final == st2.is_final
+            && st1.supers.len() == st2.supers.len()
+            && st1.supers.iter().zip(&st2.supers).all(|(x, y)| self.idx_equiv(*x, other, *y))
+            && match (&st1.comp, &st2.comp) {
+                (CompType::Func(ft1), CompType::Func(ft2)) => vals_equiv(&ft1.0, &ft2.0) && vals_equiv(&ft1.1, &ft2.1),
+                (CompType::Struct(fields1), CompType::Struct(fields2)) => {
+                    fields1.len() == fields2.len()
+                        && fields1.iter().zip(fields2).all(|(ft1, ft2)| self.field_equiv(ft1, other, ft2))
+                },
+                (CompType::Array(ft1), CompType::Array(ft2)) => self.field_equiv(ft1, other, ft2),
+                _ => false,
+            }
+    }
 }
 
 pub type ResultType = Vec<ValType>;
 
 pub type FuncType = (ResultType, ResultType);
 
+/// A type of the type section, which may declare its supertype.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SubType {
+    pub is_final: bool,
+    pub supers: Vec<TypeIdx>,
+    pub comp: CompType,
+    /// Position in its recursion group and the size of the group,
+    /// `(0, 1)` for a type on its own.
+    pub rec: (u32, u32),
+}
+
+impl SubType {
+    pub fn functype(&self) -> Option<&FuncType> {
+        match &self.comp {
+            CompType::Func(functype) => Some(functype),
+            _ => None,
+        }
+    }
+
+    /// The type at `typeidx` of `types` is well-formed and matches its supertype.
+    pub fn validate(&self, types: &[SubType], typeidx: TypeIdx) -> Result<(), Error> {
+        // references reach no further than the end of the recursion group
+        let (pos, len) = self.rec;
+        if pos >= len || pos > typeidx { return Err(Error::Invalid); }
+        let end = (typeidx - pos + len) as usize;
+        if end > types.len() { return Err(Error::Invalid); }
+        let in_group = |vt: &ValType| match vt.reftype() {
+            Some(RefType { heap: HeapType::Type(idx), .. }) => (idx as usize) < end,
+            _ => true,
+        };
+        let fields_ok = match &self.comp {
+            CompType::Func((params, results)) => params.iter().chain(results).all(in_group),
+            CompType::Struct(fields) => fields.iter().all(|field| in_group(&field.0.unpacked())),
+            CompType::Array(field) => in_group(&field.0.unpacked()),
+        };
+        if !fields_ok { return Err(Error::Invalid); }
+
+        if self.supers.len() > 1 { return Err(Error::Invalid); }
+        for sup in &self.supers {
+            // supertypes are declared before
+            if *sup >= typeidx { return Err(Error::Invalid); }
+            let supertype = &types[*sup as usize];
+            if supertype.is_final || !self.comp.matches(&supertype.comp, types) {
+                return Err(Error::Invalid);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<FuncType> for SubType {
+    fn from(functype: FuncType) -> SubType {
+        SubType { is_final: true, supers: vec![], comp: CompType::Func(functype), rec: (0, 1) }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum CompType {
+    Func(FuncType),
+    Struct(Vec<FieldType>),
+    Array(FieldType),
+}
+
+impl CompType {
+    pub fn matches(&self, other: &CompType, types: &[SubType]) -> bool {
+        let vals_match = |vts1: &ResultType, vts2: &ResultType| {
+            vts1.len() == vts2.len() && vts1.iter().zip(vts2).all(|(vt1, vt2)| vt1.matches(vt2, types))
+        };
+        match (self, other) {
+            // parameters are contravariant
+            (CompType::Func(ft1), CompType::Func(ft2)) => vals_match(&ft2.0, &ft1.0) && vals_match(&ft1.1, &ft2.1),
+            // the fields of the supertype are a prefix
+            (CompType::Struct(fields1), CompType::Struct(fields2)) => {
+                fields1.len() >= fields2.len()
+                    && fields1.iter().zip(fields2).all(|(ft1, ft2)| ft1.matches(ft2, types))
+            },
+            (CompType::Array(ft1), CompType::Array(ft2)) => ft1.matches(ft2, types),
+            _ => false,
+        }
+    }
+}
+
+/// Field of a struct, or the elements of an array.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FieldType(pub StorageType, pub Mut);
+
+impl FieldType {
+    pub fn matches(&self, other: &FieldType, types: &[SubType]) -> bool {
+        if self.1 != other.1 { return false; }
+        match (&self.0, &other.0) {
+            (StorageType::Val(vt1), StorageType::Val(vt2)) => match self.1 {
+                // mutable fields are read and written, so they are invariant
+                Mut::Var => vt1.matches(vt2, types) && vt2.matches(vt1, types),
+                Mut::Const => vt1.matches(vt2, types),
+            },
+            (st1, st2) => st1 == st2,
+        }
+    }
+}
+
+/// Packed types are stored in 8 or 16 bits, and read as i32.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StorageType {
+    Val(ValType),
+    I8,
+    I16,
+}
+
+impl StorageType {
+    pub fn unpacked(&self) -> ValType {
+        match self {
+            StorageType::Val(vt) => *vt,
+            StorageType::I8 | StorageType::I16 => ValType::I32,
+        }
+    }
+
+    pub fn is_packed(&self) -> bool {
+        !matches!(self, StorageType::Val(_))
+    }
+}
+
 #[derive(Clone, Default, PartialEq)]
 pub struct Limits {
-    pub min: u32,
-    pub max: Option<u32>,
+    pub min: u64,
+    pub max: Option<u64>,
+    /// Exponent of the page size of a memory, 64KiB pages when absent. Tables have none.
+    pub page_size_log2: Option<u32>,
 }
 
 impl Limits {
-    pub fn validate(&self, _context: &Context, value: usize) -> Result<usize, Error> {
+    /// Both bounds must be within `range`.
+    pub fn validate(&self, _context: &Context, range: u64) -> Result<(), Error> {
+        if self.min > range { return Err(Error::Invalid); }
         if let Some(max) = self.max {
-            if max < self.min { return Err(Error::Invalid); }
+            if max < self.min || max > range { return Err(Error::Invalid); }
         }
-        Ok(value)
+        Ok(())
     }
 }
 
 #[derive(Clone, PartialEq)]
-pub struct MemType(pub Limits);
+pub struct MemType(pub Limits, pub Share, pub IdxType);
 
 impl MemType {
+    pub fn is_shared(&self) -> bool { self.1 == Share::Shared }
+    pub fn page_size_log2(&self) -> u32 { self.0.page_size_log2.unwrap_or(16) }
     pub fn validate(&self, context: &Context) -> Result<(), Error> {
-        let _ = self.0.validate(context, u16::MAX as usize)?;
+        // pages of 1 byte or 64KiB
+        if !matches!(self.page_size_log2(), 0 | 16) {
+            return Err(Error::Invalid);
+        }
+        self.0.validate(context, self.2.max_pages(self.page_size_log2()))?;
+        // shared memories can't be moved, so they are bounded
+        if self.is_shared() && self.0.max.is_none() {
+            return Err(Error::Invalid);
+        }
         Ok(())
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Share { Unshared, Shared }
+
+/// Type of the addresses of a memory, i64 for memories larger than 4GiB.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IdxType { I32, I64 }
+
+impl IdxType {
+    pub fn valtype(&self) -> ValType {
+        match self {
+            IdxType::I32 => ValType::I32,
+            IdxType::I64 => ValType::I64,
+        }
+    }
+
+    /// Pages of 2^`page_size_log2` bytes addressable with the index type.
+    pub fn max_pages(&self, page_size_log2: u32) -> u64 {
+        let bits = match self {
+            IdxType::I32 => 32,
+            IdxType::I64 => 64,
+        };
+        1u64.checked_shl(bits - page_size_log2).unwrap_or(u64::MAX)
+    }
+}
+
 #[derive(Clone, PartialEq)]
-pub struct TableType(pub Limits, pub ElemType);
+pub struct TableType(pub Limits, pub RefType);
 
 impl TableType {
-    pub fn is_funcref(&self) -> bool { true }
+    /// The table holds functions, which `call_indirect` requires.
+    pub fn is_funcref(&self, types: &[SubType]) -> bool { self.1.heap.matches(&HeapType::Func, types) }
     pub fn validate(&self, context: &Context) -> Result<(), Error> {
-        let _ = self.0.validate(context, u32::MAX as usize)?;
+        self.0.validate(context, u32::MAX as u64)?;
+        if let HeapType::Type(typeidx) = self.1.heap {
+            context.subtype(typeidx).ok_or(Error::Invalid)?;
+        }
         Ok(())
     }
 }
 
-#[derive(Clone, PartialEq)]
-pub enum ElemType { FuncRef, }
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ElemType { FuncRef, ExternRef, ExnRef, AnyRef, }
+
+impl ElemType {
+    pub fn valtype(&self) -> ValType {
+        match self {
+            ElemType::FuncRef => ValType::FuncRef,
+            ElemType::ExternRef => ValType::ExternRef,
+            ElemType::ExnRef => ValType::ExnRef,
+            ElemType::AnyRef => RefType { nullable: true, heap: HeapType::Any }.valtype(),
+        }
+    }
+}
 
 #[derive(Clone, PartialEq)]
 pub struct GlobalType(pub ValType, pub Mut);
@@ -64,6 +458,7 @@ pub enum ExternType {
     Table(TableType),
     Mem(MemType),
     Global(GlobalType),
+    Tag(FuncType),
 }
 
 impl ExternType {
@@ -77,6 +472,10 @@ impl ExternType {
                 memtype.validate(context)?;
             },
             ExternType::Global(_) => {},
+            ExternType::Tag(functype) => {
+                // exceptions carry values one way
+                if !functype.1.is_empty() { return Err(Error::Invalid); }
+            },
         }
         
         Ok(())
@@ -1,5 +1,5 @@
 pub type Name = String;
 pub type Byte = u8;
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Mut { Const, Var }
@@ -0,0 +1,799 @@
+use std::convert::{TryFrom, TryInto};
+use std::fs::File;
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::mem;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    Store,
+    Module,
+    ModuleInst,
+    MemAddr,
+    Val,
+    ValType,
+    FuncType,
+    Error,
+    ExternVal,
+    HostCode,
+    HostResult,
+    HostFunc,
+    ImportDesc,
+    alloc_hostfunc,
+    instanciate_module,
+    instance_export,
+    func_invoke,
+};
+
+/// Name of the module whose imports are provided by `wasi_instanciate`.
+pub const WASI_MODULE: &str = "wasi_snapshot_preview1";
+
+type Errno = u32;
+
+const ESUCCESS: Errno = 0;
+const EACCES: Errno = 2;
+const EBADF: Errno = 8;
+const EEXIST: Errno = 20;
+const EFAULT: Errno = 21;
+const EFBIG: Errno = 22;
+const EINVAL: Errno = 28;
+const EIO: Errno = 29;
+const EISDIR: Errno = 31;
+const ENOENT: Errno = 44;
+const ENOSYS: Errno = 52;
+const ENOTDIR: Errno = 54;
+const ESPIPE: Errno = 70;
+const ENOTCAPABLE: Errno = 76;
+
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const FILETYPE_DIRECTORY: u8 = 3;
+const FILETYPE_REGULAR_FILE: u8 = 4;
+
+const OFLAGS_CREAT: u32 = 1;
+const OFLAGS_DIRECTORY: u32 = 2;
+const OFLAGS_EXCL: u32 = 4;
+const OFLAGS_TRUNC: u32 = 8;
+
+const FDFLAGS_APPEND: u32 = 1;
+
+const RIGHTS_FD_READ: u64 = 1 << 1;
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+const RIGHTS_ALL: u64 = (1 << 30) - 1;
+
+const EVENTTYPE_CLOCK: u8 = 0;
+const SUBCLOCKFLAGS_ABSTIME: u16 = 1;
+
+mod vfs;
+pub use vfs::{
+    WasiFs,
+    WasiFile,
+    WasiOpenOptions,
+    HostFs,
+    MemFs,
+    WasiPipe,
+};
+mod preview2;
+pub use preview2::{
+    wasi_component_imports,
+    wasi_component_run,
+};
+
+/// Arguments, environment, preopened directories and standard streams of a WASI program,
+/// with the file descriptors it opens while running.
+pub struct WasiCtx {
+    /// Arguments, the first one naming the program.
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    /// Directories given to the program, each under a guest name.
+    /// Paths opened from them cannot leave them.
+    pub preopens: Vec<(String, Box<dyn WasiFs>)>,
+    pub stdin: Box<dyn Read + Send>,
+    pub stdout: Box<dyn Write + Send>,
+    pub stderr: Box<dyn Write + Send>,
+    fds: Vec<Option<Fd>>,
+    memory: Option<MemAddr>,
+    start: Option<Instant>,
+}
+
+/// Context of the host process, with its standard streams and no directory.
+impl Default for WasiCtx {
+    fn default() -> WasiCtx {
+        WasiCtx {
+            args: vec![],
+            env: vec![],
+            preopens: vec![],
+            stdin: Box::new(io::stdin()),
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            fds: vec![],
+            memory: None,
+            start: None,
+        }
+    }
+}
+
+enum Fd {
+    Reader(Box<dyn Read + Send>),
+    Writer(Box<dyn Write + Send>),
+    File(Box<dyn WasiFile>),
+    /// Preopened directory, by its index.
+    Preopen(usize),
+    /// Directory given by the preopened one it is in, and its path there.
+    Dir(usize, PathBuf),
+}
+
+/// WASI function with its parameters, `i` for `i32` and `I` for `i64`, returning an errno.
+type WasiFunc = fn(&mut WasiCtx, &mut Store, &[Val]) -> Result<(), Errno>;
+
+const FUNCS: &[(&str, &str, WasiFunc)] = &[
+    ("args_get", "ii", args_get),
+    ("args_sizes_get", "ii", args_sizes_get),
+    ("environ_get", "ii", environ_get),
+    ("environ_sizes_get", "ii", environ_sizes_get),
+    ("clock_res_get", "ii", clock_res_get),
+    ("clock_time_get", "iIi", clock_time_get),
+    ("random_get", "ii", random_get),
+    ("fd_read", "iiii", fd_read),
+    ("fd_write", "iiii", fd_write),
+    ("fd_seek", "iIii", fd_seek),
+    ("fd_tell", "ii", fd_tell),
+    ("fd_close", "i", fd_close),
+    ("fd_fdstat_get", "ii", fd_fdstat_get),
+    ("fd_prestat_get", "ii", fd_prestat_get),
+    ("fd_prestat_dir_name", "iii", fd_prestat_dir_name),
+    ("fd_readdir", "iiiIi", fd_readdir),
+    ("path_open", "iiiiiIIii", path_open),
+    ("poll_oneoff", "iiii", poll_oneoff),
+    ("sched_yield", "", sched_yield),
+];
+
+/// Instanciate a module importing WASI preview1, and nothing else.
+/// The functions it imports but WASI does not provide here return `ENOSYS`.
+pub fn wasi_instanciate(store: &mut Store, module: &Module, mut ctx: WasiCtx) -> Result<ModuleInst, Error> {
+    ctx.fds = vec![
+        Some(Fd::Reader(mem::replace(&mut ctx.stdin, Box::new(io::empty())))),
+        Some(Fd::Writer(mem::replace(&mut ctx.stdout, Box::new(io::sink())))),
+        Some(Fd::Writer(mem::replace(&mut ctx.stderr, Box::new(io::sink())))),
+    ];
+    for i in 0..ctx.preopens.len() {
+        ctx.fds.push(Some(Fd::Preopen(i)));
+    }
+    ctx.start = Some(Instant::now());
+    let ctx = Arc::new(Mutex::new(ctx));
+
+    let mut externvals = vec![];
+    for import in &module.imports {
+        let typeidx = match (&import.desc, import.module.as_str()) {
+            (ImportDesc::Func(typeidx), WASI_MODULE) => *typeidx,
+            _ => return Err(Error::OutOfIndex(format!("wasi: unknown import {}::{}", import.module, import.name))),
+        };
+        let (functype, code) = match FUNCS.iter().find(|(name, _, _)| *name == import.name) {
+            Some((_, params, f)) => {
+                let (f, ctx) = (*f, ctx.clone());
+                let params = params.chars().map(|c| if c == 'I' { ValType::I64 } else { ValType::I32 }).collect();
+                let code = HostCode::Closure(Arc::new(move |store: &mut Store, args: &[Val]| {
+                    let errno = match f(&mut lock(&ctx), store, args) {
+                        Ok(()) => ESUCCESS,
+                        Err(errno) => errno,
+                    };
+                    HostResult::Vals(vec![Val::I32Const(errno)])
+                }));
+                ((params, vec![ValType::I32]), code)
+            },
+            None if import.name == "proc_exit" => ((vec![ValType::I32], vec![]), HostCode::Sync(proc_exit)),
+            None => {
+                let functype: FuncType = module.types.get(typeidx as usize)
+                    .and_then(|subtype| subtype.functype())
+                    .cloned()
+                    .ok_or(Error::OutOfIndex("wasi: typeidx".to_string()))?;
+                let code: HostFunc = if functype.1 == [ValType::I32] {
+                    |_, _| HostResult::Vals(vec![Val::I32Const(ENOSYS)])
+                } else {
+                    |_, _| HostResult::Trap
+                };
+                (functype, HostCode::Sync(code))
+            },
+        };
+        externvals.push(ExternVal::Func(alloc_hostfunc(store, functype, code)?));
+    }
+
+    let moduleinst = instanciate_module(store, module, externvals)?;
+    if let Ok(ExternVal::Mem(memaddr)) = instance_export(moduleinst.clone(), "memory".to_string()) {
+        lock(&ctx).memory = Some(memaddr);
+    }
+    Ok(moduleinst)
+}
+
+/// Run a WASI command by calling its `_start` export, and return its exit status.
+pub fn wasi_run(store: &mut Store, module: &Module, ctx: WasiCtx) -> Result<u32, Error> {
+    let moduleinst = wasi_instanciate(store, module, ctx)?;
+    let funcaddr = match instance_export(moduleinst, "_start".to_string())? {
+        ExternVal::Func(funcaddr) => funcaddr,
+        _ => return Err(Error::Invalid),
+    };
+    match func_invoke(store, funcaddr, vec![]).1 {
+        Ok(_) => Ok(0),
+        Err(Error::Exit(code)) => Ok(code),
+        Err(error) => Err(error),
+    }
+}
+
+fn lock(ctx: &Mutex<WasiCtx>) -> MutexGuard<'_, WasiCtx> {
+    ctx.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn proc_exit(_: &mut Store, args: &[Val]) -> HostResult {
+    HostResult::Exit(arg32(args, 0))
+}
+
+fn arg32(args: &[Val], i: usize) -> u32 {
+    match args.get(i) {
+        Some(Val::I32Const(v)) => *v,
+        _ => 0,
+    }
+}
+
+fn arg64(args: &[Val], i: usize) -> u64 {
+    match args.get(i) {
+        Some(Val::I64Const(v)) => *v,
+        _ => 0,
+    }
+}
+
+/// Address of the `i`th element of `size` bytes in the array at `ptr`,
+/// `EFAULT` when it is past the address space.
+fn element(ptr: u32, i: u32, size: u32) -> Result<u32, Errno> {
+    i.checked_mul(size).and_then(|offset| ptr.checked_add(offset)).ok_or(EFAULT)
+}
+
+fn errno(error: io::Error) -> Errno {
+    match error.kind() {
+        io::ErrorKind::NotFound => ENOENT,
+        io::ErrorKind::PermissionDenied => EACCES,
+        io::ErrorKind::AlreadyExists => EEXIST,
+        io::ErrorKind::InvalidInput => EINVAL,
+        io::ErrorKind::FileTooLarge => EFBIG,
+        _ => EIO,
+    }
+}
+
+impl WasiCtx {
+    fn read(&self, store: &Store, ptr: u32, n: u32) -> Result<Vec<u8>, Errno> {
+        let memaddr = self.memory.ok_or(EFAULT)?;
+        store.mems[memaddr].read(ptr as usize, n as usize).ok_or(EFAULT)
+    }
+
+    /// `EFAULT` unless the `n` bytes at `ptr` are in memory, before making a buffer of them.
+    fn check(&self, store: &Store, ptr: u32, n: u32) -> Result<(), Errno> {
+        let memaddr = self.memory.ok_or(EFAULT)?;
+        match (ptr as usize).checked_add(n as usize) {
+            Some(end) if end <= store.mems[memaddr].size() => Ok(()),
+            _ => Err(EFAULT),
+        }
+    }
+
+    fn write(&self, store: &mut Store, ptr: u32, bytes: &[u8]) -> Result<(), Errno> {
+        let memaddr = self.memory.ok_or(EFAULT)?;
+        store.mems[memaddr].write(ptr as usize, bytes).ok_or(EFAULT)
+    }
+
+    fn read_u32(&self, store: &Store, ptr: u32) -> Result<u32, Errno> {
+        let bytes = self.read(store, ptr, 4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn write_u32(&self, store: &mut Store, ptr: u32, v: u32) -> Result<(), Errno> {
+        self.write(store, ptr, &v.to_le_bytes())
+    }
+
+    fn write_u64(&self, store: &mut Store, ptr: u32, v: u64) -> Result<(), Errno> {
+        self.write(store, ptr, &v.to_le_bytes())
+    }
+
+    /// Buffers of an array of `iovec` or `ciovec`, as `(ptr, len)`.
+    fn iovecs(&self, store: &Store, ptr: u32, n: u32) -> Result<Vec<(u32, u32)>, Errno> {
+        (0..n).map(|i| {
+            let iovec = element(ptr, i, 8)?;
+            Ok((self.read_u32(store, iovec)?, self.read_u32(store, element(iovec, 1, 4)?)?))
+        }).collect()
+    }
+
+    fn fd(&mut self, fd: u32) -> Result<&mut Fd, Errno> {
+        match self.fds.get_mut(fd as usize) {
+            Some(Some(fd)) => Ok(fd),
+            _ => Err(EBADF),
+        }
+    }
+
+    /// Preopened directory of a directory descriptor, and its path there.
+    fn dir(&mut self, fd: u32) -> Result<(usize, PathBuf), Errno> {
+        match self.fd(fd)? {
+            Fd::Preopen(preopen) => Ok((*preopen, PathBuf::new())),
+            Fd::Dir(preopen, path) => Ok((*preopen, path.clone())),
+            _ => Err(ENOTDIR),
+        }
+    }
+
+    fn preopen_name(&mut self, fd: u32) -> Result<String, Errno> {
+        match self.fd(fd)? {
+            Fd::Preopen(preopen) => {
+                let preopen = *preopen;
+                Ok(self.preopens[preopen].0.clone())
+            },
+            _ => Err(EBADF),
+        }
+    }
+
+    fn insert(&mut self, fd: Fd) -> u32 {
+        match self.fds.iter().position(|fd| fd.is_none()) {
+            Some(i) => {
+                self.fds[i] = Some(fd);
+                i as u32
+            },
+            None => {
+                self.fds.push(Some(fd));
+                self.fds.len() as u32 - 1
+            },
+        }
+    }
+
+    /// Write strings each followed by a NUL, with the array of their pointers.
+    fn write_strings(&self, store: &mut Store, strings: &[String], ptrs: u32, buf: u32) -> Result<(), Errno> {
+        let mut offset = buf;
+        for (i, s) in strings.iter().enumerate() {
+            self.write_u32(store, element(ptrs, i as u32, 4)?, offset)?;
+            self.write(store, offset, s.as_bytes())?;
+            let end = element(offset, s.len() as u32, 1)?;
+            self.write(store, end, &[0])?;
+            offset = element(end, 1, 1)?;
+        }
+        Ok(())
+    }
+
+    fn write_sizes(&self, store: &mut Store, strings: &[String], count: u32, size: u32) -> Result<(), Errno> {
+        self.write_u32(store, count, strings.len() as u32)?;
+        self.write_u32(store, size, strings.iter().map(|s| s.len() as u32 + 1).sum())
+    }
+
+    fn environ(&self) -> Vec<String> {
+        self.env.iter().map(|(k, v)| format!("{}={}", k, v)).collect()
+    }
+
+    fn now(&self, id: u32) -> Result<u64, Errno> {
+        match id {
+            // realtime
+            0 => SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).map_err(|_| EIO),
+            // monotonic, process and thread CPU time
+            1..=3 => Ok(self.start.map_or(0, |start| start.elapsed().as_nanos() as u64)),
+            _ => Err(EINVAL),
+        }
+    }
+}
+
+/// Path of `path` under the directory `dir`, refusing to leave the preopened one.
+/// `dir` is where a descriptor was opened, which holds no symbolic link since the
+/// filesystems refuse to follow them, so `..` climbs from the directory itself.
+fn resolve(dir: &Path, path: &str) -> Result<PathBuf, Errno> {
+    let mut resolved = dir.to_path_buf();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::CurDir => {},
+            Component::ParentDir if resolved.pop() => {},
+            _ => return Err(ENOTCAPABLE),
+        }
+    }
+    Ok(resolved)
+}
+
+fn args_get(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    ctx.write_strings(store, &ctx.args, arg32(args, 0), arg32(args, 1))
+}
+
+fn args_sizes_get(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    ctx.write_sizes(store, &ctx.args, arg32(args, 0), arg32(args, 1))
+}
+
+fn environ_get(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    ctx.write_strings(store, &ctx.environ(), arg32(args, 0), arg32(args, 1))
+}
+
+fn environ_sizes_get(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    ctx.write_sizes(store, &ctx.environ(), arg32(args, 0), arg32(args, 1))
+}
+
+fn clock_res_get(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    ctx.now(arg32(args, 0))?;
+    ctx.write_u64(store, arg32(args, 1), 1)
+}
+
+fn clock_time_get(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let time = ctx.now(arg32(args, 0))?;
+    ctx.write_u64(store, arg32(args, 2), time)
+}
+
+/// Bytes from the random source of the host, fit for cryptography.
+fn random_bytes(len: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0; len];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn random_get(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let (ptr, len) = (arg32(args, 0), arg32(args, 1));
+    ctx.check(store, ptr, len)?;
+    ctx.write(store, ptr, &random_bytes(len as usize).map_err(errno)?)
+}
+
+fn fd_read(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let iovecs = ctx.iovecs(store, arg32(args, 1), arg32(args, 2))?;
+    let mut nread = 0;
+    for (ptr, len) in iovecs {
+        ctx.check(store, ptr, len)?;
+        let mut buf = vec![0; len as usize];
+        let n = match ctx.fd(arg32(args, 0))? {
+            Fd::Reader(reader) => reader.read(&mut buf),
+            Fd::File(file) => file.read(&mut buf),
+            Fd::Preopen(_) | Fd::Dir(..) => return Err(EISDIR),
+            Fd::Writer(_) => return Err(EBADF),
+        }.map_err(errno)?;
+        ctx.write(store, ptr, &buf[..n])?;
+        nread += n as u32;
+        if n < buf.len() {
+            break;
+        }
+    }
+    ctx.write_u32(store, arg32(args, 3), nread)
+}
+
+fn fd_write(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let iovecs = ctx.iovecs(store, arg32(args, 1), arg32(args, 2))?;
+    let mut bytes = vec![];
+    for (ptr, len) in iovecs {
+        bytes.extend(ctx.read(store, ptr, len)?);
+    }
+    match ctx.fd(arg32(args, 0))? {
+        Fd::Writer(writer) => writer.write_all(&bytes).and_then(|()| writer.flush()),
+        Fd::File(file) => file.write_all(&bytes),
+        Fd::Preopen(_) | Fd::Dir(..) => return Err(EISDIR),
+        Fd::Reader(_) => return Err(EBADF),
+    }.map_err(errno)?;
+    ctx.write_u32(store, arg32(args, 3), bytes.len() as u32)
+}
+
+fn fd_seek(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let offset = arg64(args, 1) as i64;
+    let pos = match arg32(args, 2) {
+        0 => SeekFrom::Start(u64::try_from(offset).map_err(|_| EINVAL)?),
+        1 => SeekFrom::Current(offset),
+        2 => SeekFrom::End(offset),
+        _ => return Err(EINVAL),
+    };
+    let pos = match ctx.fd(arg32(args, 0))? {
+        Fd::File(file) => file.seek(pos).map_err(errno)?,
+        Fd::Preopen(_) | Fd::Dir(..) => return Err(EISDIR),
+        Fd::Reader(_) | Fd::Writer(_) => return Err(ESPIPE),
+    };
+    ctx.write_u64(store, arg32(args, 3), pos)
+}
+
+fn fd_tell(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let pos = match ctx.fd(arg32(args, 0))? {
+        Fd::File(file) => file.stream_position().map_err(errno)?,
+        Fd::Preopen(_) | Fd::Dir(..) => return Err(EISDIR),
+        Fd::Reader(_) | Fd::Writer(_) => return Err(ESPIPE),
+    };
+    ctx.write_u64(store, arg32(args, 1), pos)
+}
+
+fn fd_close(ctx: &mut WasiCtx, _: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    ctx.fd(arg32(args, 0))?;
+    ctx.fds[arg32(args, 0) as usize] = None;
+    Ok(())
+}
+
+fn fd_fdstat_get(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let filetype = match ctx.fd(arg32(args, 0))? {
+        Fd::Reader(_) | Fd::Writer(_) => FILETYPE_CHARACTER_DEVICE,
+        Fd::File(_) => FILETYPE_REGULAR_FILE,
+        Fd::Preopen(_) | Fd::Dir(..) => FILETYPE_DIRECTORY,
+    };
+    // filetype, flags, rights and inherited rights
+    let mut fdstat = [0; 24];
+    fdstat[0] = filetype;
+    fdstat[8..16].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+    fdstat[16..24].copy_from_slice(&RIGHTS_ALL.to_le_bytes());
+    ctx.write(store, arg32(args, 1), &fdstat)
+}
+
+fn fd_prestat_get(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let len = ctx.preopen_name(arg32(args, 0))?.len() as u32;
+    // tag of a directory, then the length of its name
+    let ptr = arg32(args, 1);
+    ctx.write_u32(store, ptr, 0)?;
+    ctx.write_u32(store, element(ptr, 1, 4)?, len)
+}
+
+fn fd_prestat_dir_name(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let name = ctx.preopen_name(arg32(args, 0))?;
+    let len = (arg32(args, 2) as usize).min(name.len());
+    ctx.write(store, arg32(args, 1), &name.as_bytes()[..len])
+}
+
+fn fd_readdir(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let (preopen, dir) = ctx.dir(arg32(args, 0))?;
+    let mut entries = vec![(".".to_string(), true), ("..".to_string(), true)];
+    let mut names = ctx.preopens[preopen].1.read_dir(&dir).map_err(errno)?;
+    names.sort();
+    entries.extend(names);
+
+    // each entry is a dirent of the cookie of the next one, inode, name length and type,
+    // then the name; the last one is cut at the end of the buffer
+    let mut buf = vec![];
+    for (i, (name, is_dir)) in entries.iter().enumerate().skip(arg64(args, 3) as usize) {
+        let filetype = if *is_dir { FILETYPE_DIRECTORY } else { FILETYPE_REGULAR_FILE };
+        buf.extend_from_slice(&(i as u64 + 1).to_le_bytes());
+        buf.extend_from_slice(&0u64.to_le_bytes());
+        buf.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&[filetype, 0, 0, 0]);
+        buf.extend_from_slice(name.as_bytes());
+        if buf.len() >= arg32(args, 2) as usize {
+            break;
+        }
+    }
+    buf.truncate(arg32(args, 2) as usize);
+    ctx.write(store, arg32(args, 1), &buf)?;
+    ctx.write_u32(store, arg32(args, 4), buf.len() as u32)
+}
+
+fn path_open(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let (preopen, dir) = ctx.dir(arg32(args, 0))?;
+    let path = ctx.read(store, arg32(args, 2), arg32(args, 3))?;
+    let path = String::from_utf8(path).map_err(|_| EINVAL)?;
+    let path = resolve(&dir, &path)?;
+    let oflags = arg32(args, 4);
+    let rights = arg64(args, 5);
+    let fdflags = arg32(args, 7);
+
+    let fs = &ctx.preopens[preopen].1;
+    let fd = match fs.is_dir(&path) {
+        Ok(true) => {
+            if oflags & (OFLAGS_CREAT | OFLAGS_TRUNC) != 0 {
+                return Err(if oflags & OFLAGS_EXCL != 0 { EEXIST } else { EISDIR });
+            }
+            Fd::Dir(preopen, path)
+        },
+        Ok(false) if oflags & OFLAGS_DIRECTORY != 0 => return Err(ENOTDIR),
+        Err(error) if oflags & OFLAGS_DIRECTORY != 0 => return Err(errno(error)),
+        _ => {
+            let write = rights & RIGHTS_FD_WRITE != 0;
+            let options = WasiOpenOptions {
+                read: rights & RIGHTS_FD_READ != 0 || !write,
+                write,
+                append: fdflags & FDFLAGS_APPEND != 0,
+                create: oflags & OFLAGS_CREAT != 0,
+                create_new: oflags & OFLAGS_CREAT != 0 && oflags & OFLAGS_EXCL != 0,
+                truncate: oflags & OFLAGS_TRUNC != 0,
+            };
+            Fd::File(fs.open(&path, &options).map_err(errno)?)
+        },
+    };
+    let fd = ctx.insert(fd);
+    ctx.write_u32(store, arg32(args, 8), fd)
+}
+
+/// Wait for the earliest clock subscription; the others are ready at once.
+fn poll_oneoff(ctx: &mut WasiCtx, store: &mut Store, args: &[Val]) -> Result<(), Errno> {
+    let (subscriptions, events, n) = (arg32(args, 0), arg32(args, 1), arg32(args, 2));
+    if n == 0 {
+        return Err(EINVAL);
+    }
+    // each subscription is its userdata, tag and contents, an event its userdata,
+    // error, type and the contents of fd events
+    let mut clocks = vec![];
+    let mut ready = vec![];
+    for i in 0..n {
+        let sub = ctx.read(store, element(subscriptions, i, 48)?, 48)?;
+        let userdata = u64::from_le_bytes(sub[0..8].try_into().unwrap());
+        let tag = sub[8];
+        if tag == EVENTTYPE_CLOCK {
+            let id = u32::from_le_bytes(sub[16..20].try_into().unwrap());
+            let timeout = u64::from_le_bytes(sub[24..32].try_into().unwrap());
+            let flags = u16::from_le_bytes(sub[40..42].try_into().unwrap());
+            let now = match ctx.now(id) {
+                Ok(now) => now,
+                Err(errno) => {
+                    ready.push((userdata, errno, tag));
+                    continue;
+                },
+            };
+            let delay = if flags & SUBCLOCKFLAGS_ABSTIME != 0 { timeout.saturating_sub(now) } else { timeout };
+            clocks.push((userdata, delay));
+        } else {
+            ready.push((userdata, ESUCCESS, tag));
+        }
+    }
+    if ready.is_empty() {
+        if let Some(delay) = clocks.iter().map(|(_, delay)| *delay).min() {
+            std::thread::sleep(Duration::from_nanos(delay));
+            ready.extend(clocks.iter().filter(|(_, d)| *d == delay).map(|(userdata, _)| (*userdata, ESUCCESS, EVENTTYPE_CLOCK)));
+        }
+    }
+    for (i, (userdata, errno, tp)) in ready.iter().enumerate() {
+        let mut event = [0; 32];
+        event[0..8].copy_from_slice(&userdata.to_le_bytes());
+        event[8..10].copy_from_slice(&(*errno as u16).to_le_bytes());
+        event[10] = *tp;
+        ctx.write(store, element(events, i as u32, 32)?, &event)?;
+    }
+    ctx.write_u32(store, arg32(args, 3), ready.len() as u32)
+}
+
+fn sched_yield(_: &mut WasiCtx, _: &mut Store, _: &[Val]) -> Result<(), Errno> {
+    std::thread::yield_now();
+    Ok(())
+}
+
+#[test]
+fn test_wasi_run() {
+    use crate::{
+        store_init,
+        Func, Expr, Instr, MemArg, Mem, MemType, Limits, Share, IdxType,
+        Import, Export, ExportDesc, Data, DataMode,
+    };
+
+    assert_eq!(resolve(Path::new("a"), "b/../c").unwrap(), Path::new("a/c"));
+    assert_eq!(resolve(Path::new("a"), "../../b"), Err(ENOTCAPABLE));
+    // from a descriptor opened on a subdirectory
+    assert_eq!(resolve(Path::new("a/b"), "../c").unwrap(), Path::new("a/c"));
+    assert_eq!(resolve(Path::new("a/b"), "./../../c").unwrap(), Path::new("c"));
+    assert_eq!(resolve(Path::new(""), "/etc"), Err(ENOTCAPABLE));
+
+    fn import(name: &str, tp: u32) -> Import {
+        Import { module: WASI_MODULE.to_string(), name: name.to_string(), desc: ImportDesc::Func(tp) }
+    }
+    let memarg = MemArg { offset: 0, align: 2, memory: 0 };
+    let data = |offset: u32, init: &[u8]| Data {
+        init: init.to_vec(),
+        mode: DataMode::Active { memory: 0, offset: Expr(vec![Instr::I32Const(offset)]) },
+    };
+    let i32s = |n: usize| vec![ValType::I32; n];
+    let write = |fd: Vec<Instr>, iovec: u32| [
+        fd,
+        vec![Instr::I32Const(iovec), Instr::I32Const(1), Instr::I32Const(304), Instr::Call(1), Instr::Drop(None)],
+    ].concat();
+    // echo stdin to stdout, write "hello" to stderr and to "out.txt", and exit with 7
+    let module = Module {
+        types: vec![
+            ([i32s(5), vec![ValType::I64, ValType::I64], i32s(2)].concat(), i32s(1)).into(),
+            (i32s(4), i32s(1)).into(),
+            (i32s(1), vec![]).into(),
+            (vec![], vec![]).into(),
+        ],
+        imports: vec![import("path_open", 0), import("fd_write", 1), import("proc_exit", 2), import("fd_read", 1)],
+        funcs: vec![Func { tp: 3, locals: vec![], body: Expr([
+            vec![Instr::I32Const(0), Instr::I32Const(200), Instr::I32Const(1), Instr::I32Const(300), Instr::Call(3), Instr::Drop(None)],
+            vec![Instr::I32Const(212), Instr::I32Const(300), Instr::Load(ValType::I32, memarg.clone()), Instr::Store(ValType::I32, memarg.clone())],
+            write(vec![Instr::I32Const(1)], 208),
+            write(vec![Instr::I32Const(2)], 216),
+            vec![
+                Instr::I32Const(3), Instr::I32Const(0), Instr::I32Const(0), Instr::I32Const(7),
+                Instr::I32Const(OFLAGS_CREAT | OFLAGS_TRUNC), Instr::I64Const(RIGHTS_FD_WRITE), Instr::I64Const(0),
+                Instr::I32Const(0), Instr::I32Const(100), Instr::Call(0), Instr::Drop(None),
+            ],
+            write(vec![Instr::I32Const(100), Instr::Load(ValType::I32, memarg)], 216),
+            vec![Instr::I32Const(7), Instr::Call(2)],
+        ].concat()) }],
+        mems: vec![Mem(MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32))],
+        data: vec![
+            data(0, b"out.txt"),
+            data(16, b"hello"),
+            data(200, &[32, 0, 0, 0, 16, 0, 0, 0, 32, 0, 0, 0, 0, 0, 0, 0, 16, 0, 0, 0, 5, 0, 0, 0]),
+        ],
+        exports: vec![
+            Export { name: "memory".to_string(), desc: ExportDesc::Mem(0) },
+            Export { name: "_start".to_string(), desc: ExportDesc::Func(4) },
+        ],
+        ..Module::default()
+    };
+    let fs = MemFs::default();
+    let (stdout, stderr) = (WasiPipe::default(), WasiPipe::default());
+    let ctx = WasiCtx {
+        preopens: vec![(".".to_string(), Box::new(fs.clone()))],
+        stdin: Box::new(WasiPipe::new(b"echo")),
+        stdout: Box::new(stdout.clone()),
+        stderr: Box::new(stderr.clone()),
+        ..WasiCtx::default()
+    };
+    let mut store = store_init();
+    assert_eq!(wasi_run(&mut store, &module, ctx).unwrap(), 7);
+    assert_eq!(stdout.contents(), b"echo");
+    assert_eq!(stderr.contents(), b"hello");
+    assert_eq!(fs.read("out.txt").unwrap(), b"hello");
+}
+
+#[test]
+fn test_wasi_rustc() {
+    use std::fs::File;
+    use crate::{store_init, module_decode};
+
+    // built by `rustc --target wasm32-wasip1 -C opt-level=s -C lto -C codegen-units=1 -C strip=symbols -C panic=abort hello.rs`
+    let mut file = File::open("tests/wasm/hello.wasm").unwrap();
+    let module = module_decode(&mut file).unwrap();
+    let fs = MemFs::default();
+    fs.write("in.txt", b"0.25\n");
+    let stdout = WasiPipe::default();
+    let ctx = WasiCtx {
+        args: vec!["hello".to_string(), "in.txt".to_string()],
+        env: vec![("NAME".to_string(), "wasmiq".to_string())],
+        preopens: vec![(".".to_string(), Box::new(fs.clone()))],
+        stdin: Box::new(WasiPipe::new(b"echo\n")),
+        stdout: Box::new(stdout.clone()),
+        ..WasiCtx::default()
+    };
+    let mut store = store_init();
+    assert_eq!(wasi_run(&mut store, &module, ctx).unwrap(), 2);
+    assert_eq!(stdout.contents(), b"ECHO wasmiq\n");
+    assert_eq!(fs.read("out.txt").unwrap(), b"2147483647 -51");
+}
+
+#[test]
+fn test_wasi_fault() {
+    use crate::{
+        store_init,
+        Func, Expr, Instr, Mem, MemType, Limits, Share, IdxType, Import, Export, ExportDesc,
+    };
+    use crate::testing::TestInstance;
+
+    assert_eq!(element(u32::MAX - 3, 1, 4), Err(EFAULT));
+    assert_eq!(element(8, u32::MAX, 48), Err(EFAULT));
+    assert_eq!(element(8, 2, 4), Ok(16));
+
+    let i32s = |n: usize| vec![ValType::I32; n];
+    // forward the parameters to an imported function
+    let call = |funcidx: u32, tp: u32, n: u32| Func {
+        tp,
+        locals: vec![],
+        body: Expr([(0..n).map(Instr::LocalGet).collect(), vec![Instr::Call(funcidx)]].concat()),
+    };
+    let import = |name: &str, tp: u32| Import { module: WASI_MODULE.to_string(), name: name.to_string(), desc: ImportDesc::Func(tp) };
+    let export = |name: &str, desc: ExportDesc| Export { name: name.to_string(), desc };
+    let module = Module {
+        types: vec![(i32s(2), i32s(1)).into(), (i32s(4), i32s(1)).into()],
+        imports: vec![import("args_get", 0), import("poll_oneoff", 1), import("fd_write", 1), import("fd_read", 1)],
+        funcs: vec![call(0, 0, 2), call(1, 1, 4), call(2, 1, 4), call(3, 1, 4)],
+        mems: vec![Mem(MemType(Limits { min: 1, max: None, page_size_log2: None }, Share::Unshared, IdxType::I32))],
+        exports: vec![
+            export("memory", ExportDesc::Mem(0)),
+            export("args_get", ExportDesc::Func(4)),
+            export("poll_oneoff", ExportDesc::Func(5)),
+            export("fd_write", ExportDesc::Func(6)),
+            export("fd_read", ExportDesc::Func(7)),
+        ],
+        ..Module::default()
+    };
+    let ctx = WasiCtx { args: vec!["a".to_string(), "b".to_string()], stdin: Box::new(io::empty()), ..WasiCtx::default() };
+    let mut store = store_init();
+    let instance = TestInstance(wasi_instanciate(&mut store, &module, ctx).unwrap());
+    if let ExternVal::Mem(memaddr) = instance.export("memory") {
+        store.mems[memaddr].write(32, &[16u32.to_le_bytes(), u32::MAX.to_le_bytes()].concat()).unwrap();
+    }
+    let mut invoke = |name: &str, args: &[u32]| {
+        instance.call(&mut store, name, args.iter().map(|arg| Val::I32Const(*arg)).collect()).unwrap()
+    };
+    assert_eq!(invoke("args_get", &[0, 16]), vec![Val::I32Const(ESUCCESS)]);
+    assert_eq!(invoke("args_get", &[0, u32::MAX - 1]), vec![Val::I32Const(EFAULT)]);
+    assert_eq!(invoke("args_get", &[u32::MAX - 3, 16]), vec![Val::I32Const(EFAULT)]);
+    assert_eq!(invoke("poll_oneoff", &[u32::MAX - 47, 0, 1, 0]), vec![Val::I32Const(EFAULT)]);
+    assert_eq!(invoke("fd_write", &[1, u32::MAX - 3, 1, 0]), vec![Val::I32Const(EFAULT)]);
+    // the iovec at 32 is of 4 GiB at 16, past the memory
+    assert_eq!(invoke("fd_read", &[0, 32, 1, 8]), vec![Val::I32Const(EFAULT)]);
+}
+
+#[test]
+fn test_random_bytes() {
+    let (a, b) = (random_bytes(32).unwrap(), random_bytes(32).unwrap());
+    assert_eq!(a.len(), 32);
+    assert_ne!(a, b);
+}
@@ -0,0 +1,1127 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    Name,
+    Store,
+    Error,
+    Component,
+    ComponentInst,
+    ComponentExternVal,
+    ComponentFunc,
+    ComponentFuncType,
+    ComponentType,
+    InterfaceType,
+    ComponentVal,
+    ComponentValue,
+    ResourceType,
+    ResourceDtor,
+    Resource,
+    component_instanciate,
+    component_export,
+    component_func_host,
+    component_invoke,
+    component_next,
+};
+use super::{
+    WasiCtx,
+    WasiFs,
+    WasiFile,
+    WasiOpenOptions,
+    random_bytes,
+    resolve,
+};
+
+/// Version of the WASI interfaces given to components, in the names of their instances.
+const VERSION: &str = "0.2.0";
+
+/// Most bytes read by one call, whatever length is asked.
+const MAX_READ: u64 = 1 << 20;
+/// Bytes that may be written by one call, as told by `check-write`.
+const MAX_WRITE: u64 = 1 << 20;
+
+const ERROR_CODES: &[&str] = &[
+    "access", "would-block", "already", "bad-descriptor", "busy", "deadlock", "quota", "exist",
+    "file-too-large", "illegal-byte-sequence", "in-progress", "interrupted", "invalid", "io",
+    "is-directory", "loop", "too-many-links", "message-size", "name-too-long", "no-device",
+    "no-entry", "no-lock", "insufficient-memory", "insufficient-space", "not-directory", "not-empty",
+    "not-recoverable", "unsupported", "no-tty", "no-such-device", "overflow", "not-permitted",
+    "pipe", "read-only", "invalid-seek", "text-file-busy", "cross-device",
+];
+
+const DESCRIPTOR_TYPES: &[&str] = &[
+    "unknown", "block-device", "character-device", "directory", "fifo", "symbolic-link",
+    "regular-file", "socket",
+];
+
+/// Case of `error-code` of `wasi:filesystem/types`.
+type Code = &'static str;
+
+type SharedFile = Arc<Mutex<Box<dyn WasiFile>>>;
+
+/// Resource types of the WASI interfaces, the same in every instance using them.
+struct Types {
+    error: ResourceType,
+    pollable: ResourceType,
+    input_stream: ResourceType,
+    output_stream: ResourceType,
+    descriptor: ResourceType,
+    directory_entry_stream: ResourceType,
+    terminal_input: ResourceType,
+    terminal_output: ResourceType,
+}
+
+/// State behind the WASI instances of a component, with the resources it holds by representation.
+struct Host {
+    ctx: WasiCtx,
+    types: Types,
+    table: Vec<Option<Entry>>,
+    start: Instant,
+}
+
+enum Entry {
+    Error(io::Error),
+    /// Pollable ready from an instant, or never.
+    Pollable(Option<Instant>),
+    Input(Input),
+    Output(Output),
+    Descriptor(Descriptor),
+    DirectoryEntries(std::vec::IntoIter<(String, bool)>),
+}
+
+enum Input {
+    Stdin,
+    /// File with the offset of the next read.
+    File(SharedFile, u64),
+}
+
+enum Output {
+    Stdout,
+    Stderr,
+    /// File with the offset of the next write, or appended to.
+    File(SharedFile, Option<u64>),
+}
+
+/// File or directory given by the preopened directory it is in and its path there.
+#[derive(Clone)]
+struct Descriptor {
+    preopen: usize,
+    path: PathBuf,
+    file: Option<SharedFile>,
+    read: bool,
+    write: bool,
+}
+
+enum StreamError {
+    Failed(io::Error),
+    Closed,
+}
+
+type Args = std::vec::IntoIter<ComponentVal>;
+
+enum HostFn {
+    Plain(fn(&mut Host, &mut Args) -> Result<Vec<ComponentVal>, Error>),
+    /// Function returning a `result` with a `stream-error`.
+    Stream(fn(&mut Host, &mut Args) -> Result<Option<ComponentVal>, StreamError>),
+    /// Function returning a `result` with an `error-code` of the filesystem.
+    Fs(fn(&mut Host, &mut Args) -> Result<Option<ComponentVal>, Code>),
+}
+
+/// Function of an interface with its parameters, its results and its code.
+/// The results of `Stream` and `Fs` functions give the `ok` type of their `result`.
+type FuncDef = (&'static str, Vec<(&'static str, InterfaceType)>, Vec<InterfaceType>, HostFn);
+
+/// Interface by name, with the types and the functions it exports.
+type Interface = (&'static str, Vec<(&'static str, ComponentType)>, Vec<FuncDef>);
+
+/// Instances of the `wasi:cli/command` world, but for sockets, to instanciate a component with.
+pub fn wasi_component_imports(ctx: WasiCtx) -> Vec<(Name, ComponentExternVal)> {
+    let host = Arc::new_cyclic(|weak| Mutex::new(Host {
+        ctx,
+        types: Types::new(weak),
+        table: vec![],
+        start: Instant::now(),
+    }));
+    let interfaces = interfaces(&lock(&host).types);
+    let mut imports = vec![];
+    for (name, types, funcs) in interfaces {
+        let mut exports = vec![];
+        for (name, tp) in types {
+            exports.push((name.to_string(), ComponentExternVal::Type(tp)));
+        }
+        for (name, params, results, code) in funcs {
+            let func = host_func(&host, params, results, code);
+            exports.push((name.to_string(), ComponentExternVal::Func(func)));
+        }
+        imports.push((format!("{}@{}", name, VERSION), ComponentExternVal::Instance(ComponentInst { exports })));
+    }
+    imports
+}
+
+/// Run a WASI command component by calling the `run` function of its `wasi:cli/run` export,
+/// and return its exit status.
+pub fn wasi_component_run(store: &mut Store, component: &Component, ctx: WasiCtx) -> Result<u32, Error> {
+    let componentinst = component_instanciate(store, component, wasi_component_imports(ctx))?;
+    let func = match component_export(componentinst, format!("wasi:cli/run@{}", VERSION))? {
+        ComponentExternVal::Instance(instance) => match component_export(instance, "run".to_string())? {
+            ComponentExternVal::Func(func) => func,
+            _ => return Err(Error::Invalid),
+        },
+        _ => return Err(Error::Invalid),
+    };
+    match component_invoke(store, &func, vec![]) {
+        Ok(results) if results == vec![ComponentVal::Result(Ok(None))] => Ok(0),
+        Ok(_) => Ok(1),
+        Err(Error::Exit(code)) => Ok(code),
+        Err(error) => Err(error),
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn host_func(host: &Arc<Mutex<Host>>, params: Vec<(&str, InterfaceType)>, results: Vec<InterfaceType>, code: HostFn) -> ComponentFunc {
+    let results = match code {
+        HostFn::Plain(_) => results,
+        HostFn::Stream(_) => vec![result(results.into_iter().next(), Some(stream_error(&lock(host).types)))],
+        HostFn::Fs(_) => vec![result(results.into_iter().next(), Some(error_code()))],
+    };
+    let tp = ComponentFuncType {
+        params: params.into_iter().map(|(name, tp)| (name.to_string(), tp)).collect(),
+        results,
+    };
+    let host = host.clone();
+    component_func_host(tp, Arc::new(move |_: &mut Store, args: Vec<ComponentVal>| {
+        let mut host = lock(&host);
+        let args = &mut args.into_iter();
+        match code {
+            HostFn::Plain(f) => f(&mut host, args),
+            HostFn::Stream(f) => {
+                let result = match f(&mut host, args) {
+                    Ok(val) => Ok(val.map(Box::new)),
+                    Err(StreamError::Failed(error)) => {
+                        let error = host.own(|types| &types.error, Entry::Error(error));
+                        Err(Some(Box::new(ComponentVal::Variant("last-operation-failed".to_string(), Some(Box::new(error))))))
+                    },
+                    Err(StreamError::Closed) => Err(Some(Box::new(ComponentVal::Variant("closed".to_string(), None)))),
+                };
+                Ok(vec![ComponentVal::Result(result)])
+            },
+            HostFn::Fs(f) => {
+                let result = match f(&mut host, args) {
+                    Ok(val) => Ok(val.map(Box::new)),
+                    Err(code) => Err(Some(Box::new(ComponentVal::Enum(code.to_string())))),
+                };
+                Ok(vec![ComponentVal::Result(result)])
+            },
+        }
+    }))
+}
+
+impl Types {
+    fn new(host: &Weak<Mutex<Host>>) -> Types {
+        let resource = || {
+            let host = host.clone();
+            ResourceType::new(Some(ResourceDtor::Closure(Arc::new(move |_: &mut Store, rep: u32| {
+                if let Some(host) = host.upgrade() {
+                    if let Some(entry) = lock(&host).table.get_mut(rep as usize) {
+                        *entry = None;
+                    }
+                }
+            }))))
+        };
+        Types {
+            error: resource(),
+            pollable: resource(),
+            input_stream: resource(),
+            output_stream: resource(),
+            descriptor: resource(),
+            directory_entry_stream: resource(),
+            terminal_input: ResourceType::new(None),
+            terminal_output: ResourceType::new(None),
+        }
+    }
+}
+
+impl Host {
+    /// New owned resource of the type selected in `types`.
+    fn own(&mut self, tp: fn(&Types) -> &ResourceType, entry: Entry) -> ComponentVal {
+        let rep = match self.table.iter().position(Option::is_none) {
+            Some(i) => {
+                self.table[i] = Some(entry);
+                i
+            },
+            None => {
+                self.table.push(Some(entry));
+                self.table.len() - 1
+            },
+        };
+        ComponentVal::Own(Resource { tp: tp(&self.types).clone(), rep: rep as u32 })
+    }
+
+    /// Entry of the resource given by a handle.
+    fn get(&mut self, val: Option<ComponentVal>) -> Option<&mut Entry> {
+        let rep = match val? {
+            ComponentVal::Own(resource) | ComponentVal::Borrow(resource) => resource.rep,
+            _ => return None,
+        };
+        self.table.get_mut(rep as usize)?.as_mut()
+    }
+
+    fn entry(&mut self, args: &mut Args) -> Option<&mut Entry> {
+        self.get(args.next())
+    }
+
+    fn descriptor(&mut self, args: &mut Args) -> Result<Descriptor, Code> {
+        match self.entry(args) {
+            Some(Entry::Descriptor(descriptor)) => Ok(descriptor.clone()),
+            _ => Err("bad-descriptor"),
+        }
+    }
+
+    fn fs(&self, descriptor: &Descriptor) -> &dyn WasiFs {
+        &*self.ctx.preopens[descriptor.preopen].1
+    }
+
+    fn read(&mut self, stream: Option<ComponentVal>, len: u64) -> Result<Vec<u8>, StreamError> {
+        let mut buf = vec![0; len.min(MAX_READ) as usize];
+        let n = match self.get(stream) {
+            Some(Entry::Input(Input::Stdin)) => self.ctx.stdin.read(&mut buf),
+            Some(Entry::Input(Input::File(file, offset))) => {
+                let mut file = lock(file);
+                let n = file.seek(SeekFrom::Start(*offset)).and_then(|_| file.read(&mut buf));
+                *offset += *n.as_ref().unwrap_or(&0) as u64;
+                n
+            },
+            _ => return Err(StreamError::Closed),
+        }.map_err(StreamError::Failed)?;
+        if n == 0 && !buf.is_empty() {
+            return Err(StreamError::Closed);
+        }
+        buf.truncate(n);
+        Ok(buf)
+    }
+
+    fn write(&mut self, stream: Option<ComponentVal>, bytes: &[u8], flush: bool) -> Result<(), StreamError> {
+        let writer: &mut dyn Write = match self.get(stream) {
+            Some(Entry::Output(Output::Stdout)) => &mut self.ctx.stdout,
+            Some(Entry::Output(Output::Stderr)) => &mut self.ctx.stderr,
+            Some(Entry::Output(Output::File(file, offset))) => {
+                let mut file = lock(file);
+                return match offset {
+                    Some(offset) => file.seek(SeekFrom::Start(*offset)).and_then(|_| file.write_all(bytes)).map(|_| *offset += bytes.len() as u64),
+                    None => file.seek(SeekFrom::End(0)).and_then(|_| file.write_all(bytes)),
+                }.map_err(StreamError::Failed);
+            },
+            _ => return Err(StreamError::Closed),
+        };
+        writer.write_all(bytes).map_err(StreamError::Failed)?;
+        if flush {
+            writer.flush().map_err(StreamError::Failed)?;
+        }
+        Ok(())
+    }
+}
+
+fn interfaces(types: &Types) -> Vec<Interface> {
+    use InterfaceType::*;
+    use HostFn::*;
+    let resource = |tp: &ResourceType| ComponentType::Resource(tp.clone());
+    let error = || borrow(&types.error);
+    let pollable = || borrow(&types.pollable);
+    let input = || borrow(&types.input_stream);
+    let output = || borrow(&types.output_stream);
+    let descriptor = || borrow(&types.descriptor);
+    let bytes = || list(U8);
+    vec![
+        ("wasi:io/error", vec![("error", resource(&types.error))], vec![
+            ("[method]error.to-debug-string", vec![("self", error())], vec![String], Plain(error_to_debug_string)),
+        ]),
+        ("wasi:io/poll", vec![("pollable", resource(&types.pollable))], vec![
+            ("[method]pollable.ready", vec![("self", pollable())], vec![Bool], Plain(pollable_ready)),
+            ("[method]pollable.block", vec![("self", pollable())], vec![], Plain(pollable_block)),
+            ("poll", vec![("in", list(pollable()))], vec![list(U32)], Plain(poll)),
+        ]),
+        ("wasi:io/streams", vec![
+            ("error", resource(&types.error)),
+            ("pollable", resource(&types.pollable)),
+            ("stream-error", ComponentType::Val(stream_error(types))),
+            ("input-stream", resource(&types.input_stream)),
+            ("output-stream", resource(&types.output_stream)),
+        ], vec![
+            ("[method]input-stream.read", vec![("self", input()), ("len", U64)], vec![bytes()], Stream(input_read)),
+            ("[method]input-stream.blocking-read", vec![("self", input()), ("len", U64)], vec![bytes()], Stream(input_read)),
+            ("[method]input-stream.skip", vec![("self", input()), ("len", U64)], vec![U64], Stream(input_skip)),
+            ("[method]input-stream.blocking-skip", vec![("self", input()), ("len", U64)], vec![U64], Stream(input_skip)),
+            ("[method]input-stream.subscribe", vec![("self", input())], vec![own(&types.pollable)], Plain(subscribe)),
+            ("[method]output-stream.check-write", vec![("self", output())], vec![U64], Stream(output_check_write)),
+            ("[method]output-stream.write", vec![("self", output()), ("contents", bytes())], vec![], Stream(output_write)),
+            ("[method]output-stream.blocking-write-and-flush", vec![("self", output()), ("contents", bytes())], vec![], Stream(output_write_and_flush)),
+            ("[method]output-stream.flush", vec![("self", output())], vec![], Stream(output_flush)),
+            ("[method]output-stream.blocking-flush", vec![("self", output())], vec![], Stream(output_flush)),
+            ("[method]output-stream.subscribe", vec![("self", output())], vec![own(&types.pollable)], Plain(subscribe)),
+            ("[method]output-stream.write-zeroes", vec![("self", output()), ("len", U64)], vec![], Stream(output_write_zeroes)),
+            ("[method]output-stream.blocking-write-zeroes-and-flush", vec![("self", output()), ("len", U64)], vec![], Stream(output_write_zeroes)),
+            ("[method]output-stream.splice", vec![("self", output()), ("src", input()), ("len", U64)], vec![U64], Stream(output_splice)),
+            ("[method]output-stream.blocking-splice", vec![("self", output()), ("src", input()), ("len", U64)], vec![U64], Stream(output_splice)),
+        ]),
+        ("wasi:clocks/monotonic-clock", vec![("pollable", resource(&types.pollable))], vec![
+            ("now", vec![], vec![U64], Plain(monotonic_now)),
+            ("resolution", vec![], vec![U64], Plain(monotonic_resolution)),
+            ("subscribe-instant", vec![("when", U64)], vec![own(&types.pollable)], Plain(subscribe_instant)),
+            ("subscribe-duration", vec![("when", U64)], vec![own(&types.pollable)], Plain(subscribe_duration)),
+        ]),
+        ("wasi:clocks/wall-clock", vec![("datetime", ComponentType::Val(datetime()))], vec![
+            ("now", vec![], vec![datetime()], Plain(wall_now)),
+            ("resolution", vec![], vec![datetime()], Plain(wall_resolution)),
+        ]),
+        ("wasi:random/random", vec![], vec![
+            ("get-random-bytes", vec![("len", U64)], vec![bytes()], Plain(random_get_bytes)),
+            ("get-random-u64", vec![], vec![U64], Plain(random_get_u64)),
+        ]),
+        ("wasi:random/insecure", vec![], vec![
+            ("get-insecure-random-bytes", vec![("len", U64)], vec![bytes()], Plain(random_get_bytes)),
+            ("get-insecure-random-u64", vec![], vec![U64], Plain(random_get_u64)),
+        ]),
+        ("wasi:random/insecure-seed", vec![], vec![
+            ("insecure-seed", vec![], vec![Tuple(vec![U64, U64])], Plain(random_insecure_seed)),
+        ]),
+        ("wasi:filesystem/types", vec![
+            ("input-stream", resource(&types.input_stream)),
+            ("output-stream", resource(&types.output_stream)),
+            ("error", resource(&types.error)),
+            ("datetime", ComponentType::Val(datetime())),
+            ("descriptor-type", ComponentType::Val(descriptor_type())),
+            ("descriptor-flags", ComponentType::Val(descriptor_flags())),
+            ("path-flags", ComponentType::Val(path_flags())),
+            ("open-flags", ComponentType::Val(open_flags())),
+            ("descriptor-stat", ComponentType::Val(stat_record())),
+            ("new-timestamp", ComponentType::Val(new_timestamp())),
+            ("directory-entry", ComponentType::Val(directory_entry())),
+            ("error-code", ComponentType::Val(error_code())),
+            ("advice", ComponentType::Val(advice())),
+            ("metadata-hash-value", ComponentType::Val(metadata_hash_value())),
+            ("descriptor", resource(&types.descriptor)),
+            ("directory-entry-stream", resource(&types.directory_entry_stream)),
+        ], vec![
+            ("[method]descriptor.read-via-stream", vec![("self", descriptor()), ("offset", U64)], vec![own(&types.input_stream)], Fs(descriptor_read_via_stream)),
+            ("[method]descriptor.write-via-stream", vec![("self", descriptor()), ("offset", U64)], vec![own(&types.output_stream)], Fs(descriptor_write_via_stream)),
+            ("[method]descriptor.append-via-stream", vec![("self", descriptor())], vec![own(&types.output_stream)], Fs(descriptor_append_via_stream)),
+            ("[method]descriptor.advise", vec![("self", descriptor()), ("offset", U64), ("length", U64), ("advice", advice())], vec![], Fs(descriptor_advise)),
+            ("[method]descriptor.sync-data", vec![("self", descriptor())], vec![], Fs(descriptor_sync)),
+            ("[method]descriptor.get-flags", vec![("self", descriptor())], vec![descriptor_flags()], Fs(descriptor_get_flags)),
+            ("[method]descriptor.get-type", vec![("self", descriptor())], vec![descriptor_type()], Fs(descriptor_get_type)),
+            ("[method]descriptor.set-size", vec![("self", descriptor()), ("size", U64)], vec![], Fs(unsupported)),
+            ("[method]descriptor.set-times", vec![("self", descriptor()), ("data-access-timestamp", new_timestamp()), ("data-modification-timestamp", new_timestamp())], vec![], Fs(unsupported)),
+            ("[method]descriptor.read", vec![("self", descriptor()), ("length", U64), ("offset", U64)], vec![Tuple(vec![bytes(), Bool])], Fs(descriptor_read)),
+            ("[method]descriptor.write", vec![("self", descriptor()), ("buffer", bytes()), ("offset", U64)], vec![U64], Fs(descriptor_write)),
+            ("[method]descriptor.read-directory", vec![("self", descriptor())], vec![own(&types.directory_entry_stream)], Fs(descriptor_read_directory)),
+            ("[method]descriptor.sync", vec![("self", descriptor())], vec![], Fs(descriptor_sync)),
+            ("[method]descriptor.create-directory-at", vec![("self", descriptor()), ("path", String)], vec![], Fs(descriptor_create_directory_at)),
+            ("[method]descriptor.stat", vec![("self", descriptor())], vec![stat_record()], Fs(descriptor_stat)),
+            ("[method]descriptor.stat-at", vec![("self", descriptor()), ("path-flags", path_flags()), ("path", String)], vec![stat_record()], Fs(descriptor_stat_at)),
+            ("[method]descriptor.set-times-at", vec![("self", descriptor()), ("path-flags", path_flags()), ("path", String), ("data-access-timestamp", new_timestamp()), ("data-modification-timestamp", new_timestamp())], vec![], Fs(unsupported)),
+            ("[method]descriptor.link-at", vec![("self", descriptor()), ("old-path-flags", path_flags()), ("old-path", String), ("new-descriptor", descriptor()), ("new-path", String)], vec![], Fs(unsupported)),
+            ("[method]descriptor.open-at", vec![("self", descriptor()), ("path-flags", path_flags()), ("path", String), ("open-flags", open_flags()), ("flags", descriptor_flags())], vec![own(&types.descriptor)], Fs(descriptor_open_at)),
+            ("[method]descriptor.readlink-at", vec![("self", descriptor()), ("path", String)], vec![String], Fs(unsupported)),
+            ("[method]descriptor.remove-directory-at", vec![("self", descriptor()), ("path", String)], vec![], Fs(descriptor_remove_directory_at)),
+            ("[method]descriptor.rename-at", vec![("self", descriptor()), ("old-path", String), ("new-descriptor", descriptor()), ("new-path", String)], vec![], Fs(unsupported)),
+            ("[method]descriptor.symlink-at", vec![("self", descriptor()), ("old-path", String), ("new-path", String)], vec![], Fs(unsupported)),
+            ("[method]descriptor.unlink-file-at", vec![("self", descriptor()), ("path", String)], vec![], Fs(descriptor_unlink_file_at)),
+            ("[method]descriptor.is-same-object", vec![("self", descriptor()), ("other", descriptor())], vec![Bool], Plain(descriptor_is_same_object)),
+            ("[method]descriptor.metadata-hash", vec![("self", descriptor())], vec![metadata_hash_value()], Fs(descriptor_metadata_hash)),
+            ("[method]descriptor.metadata-hash-at", vec![("self", descriptor()), ("path-flags", path_flags()), ("path", String)], vec![metadata_hash_value()], Fs(descriptor_metadata_hash_at)),
+            ("[method]directory-entry-stream.read-directory-entry", vec![("self", borrow(&types.directory_entry_stream))], vec![option(directory_entry())], Fs(read_directory_entry)),
+            ("filesystem-error-code", vec![("err", error())], vec![option(error_code())], Plain(filesystem_error_code)),
+        ]),
+        ("wasi:filesystem/preopens", vec![("descriptor", resource(&types.descriptor))], vec![
+            ("get-directories", vec![], vec![list(Tuple(vec![own(&types.descriptor), String]))], Plain(get_directories)),
+        ]),
+        ("wasi:cli/environment", vec![], vec![
+            ("get-environment", vec![], vec![list(Tuple(vec![String, String]))], Plain(get_environment)),
+            ("get-arguments", vec![], vec![list(String)], Plain(get_arguments)),
+            ("initial-cwd", vec![], vec![option(String)], Plain(initial_cwd)),
+        ]),
+        ("wasi:cli/exit", vec![], vec![
+            ("exit", vec![("status", InterfaceType::Result(None, None))], vec![], Plain(exit)),
+        ]),
+        ("wasi:cli/stdin", vec![("input-stream", resource(&types.input_stream))], vec![
+            ("get-stdin", vec![], vec![own(&types.input_stream)], Plain(get_stdin)),
+        ]),
+        ("wasi:cli/stdout", vec![("output-stream", resource(&types.output_stream))], vec![
+            ("get-stdout", vec![], vec![own(&types.output_stream)], Plain(get_stdout)),
+        ]),
+        ("wasi:cli/stderr", vec![("output-stream", resource(&types.output_stream))], vec![
+            ("get-stderr", vec![], vec![own(&types.output_stream)], Plain(get_stderr)),
+        ]),
+        ("wasi:cli/terminal-input", vec![("terminal-input", resource(&types.terminal_input))], vec![]),
+        ("wasi:cli/terminal-output", vec![("terminal-output", resource(&types.terminal_output))], vec![]),
+        ("wasi:cli/terminal-stdin", vec![("terminal-input", resource(&types.terminal_input))], vec![
+            ("get-terminal-stdin", vec![], vec![option(own(&types.terminal_input))], Plain(no_terminal)),
+        ]),
+        ("wasi:cli/terminal-stdout", vec![("terminal-output", resource(&types.terminal_output))], vec![
+            ("get-terminal-stdout", vec![], vec![option(own(&types.terminal_output))], Plain(no_terminal)),
+        ]),
+        ("wasi:cli/terminal-stderr", vec![("terminal-output", resource(&types.terminal_output))], vec![
+            ("get-terminal-stderr", vec![], vec![option(own(&types.terminal_output))], Plain(no_terminal)),
+        ]),
+    ]
+}
+
+fn own(tp: &ResourceType) -> InterfaceType {
+    InterfaceType::Own(tp.clone())
+}
+
+fn borrow(tp: &ResourceType) -> InterfaceType {
+    InterfaceType::Borrow(tp.clone())
+}
+
+fn list(tp: InterfaceType) -> InterfaceType {
+    InterfaceType::List(Box::new(tp))
+}
+
+fn option(tp: InterfaceType) -> InterfaceType {
+    InterfaceType::Option(Box::new(tp))
+}
+
+fn result(ok: Option<InterfaceType>, err: Option<InterfaceType>) -> InterfaceType {
+    InterfaceType::Result(ok.map(Box::new), err.map(Box::new))
+}
+
+fn names(names: &[&str]) -> Vec<Name> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn record(fields: Vec<(&str, InterfaceType)>) -> InterfaceType {
+    InterfaceType::Record(fields.into_iter().map(|(name, tp)| (name.to_string(), tp)).collect())
+}
+
+fn stream_error(types: &Types) -> InterfaceType {
+    InterfaceType::Variant(vec![
+        ("last-operation-failed".to_string(), Some(own(&types.error))),
+        ("closed".to_string(), None),
+    ])
+}
+
+fn datetime() -> InterfaceType {
+    record(vec![("seconds", InterfaceType::U64), ("nanoseconds", InterfaceType::U32)])
+}
+
+fn descriptor_type() -> InterfaceType {
+    InterfaceType::Enum(names(DESCRIPTOR_TYPES))
+}
+
+fn descriptor_flags() -> InterfaceType {
+    InterfaceType::Flags(names(&["read", "write", "file-integrity-sync", "data-integrity-sync", "requested-write-sync", "mutate-directory"]))
+}
+
+fn path_flags() -> InterfaceType {
+    InterfaceType::Flags(names(&["symlink-follow"]))
+}
+
+fn open_flags() -> InterfaceType {
+    InterfaceType::Flags(names(&["create", "directory", "exclusive", "truncate"]))
+}
+
+fn stat_record() -> InterfaceType {
+    record(vec![
+        ("type", descriptor_type()),
+        ("link-count", InterfaceType::U64),
+        ("size", InterfaceType::U64),
+        ("data-access-timestamp", option(datetime())),
+        ("data-modification-timestamp", option(datetime())),
+        ("status-change-timestamp", option(datetime())),
+    ])
+}
+
+fn new_timestamp() -> InterfaceType {
+    InterfaceType::Variant(vec![
+        ("no-change".to_string(), None),
+        ("now".to_string(), None),
+        ("timestamp".to_string(), Some(datetime())),
+    ])
+}
+
+fn directory_entry() -> InterfaceType {
+    record(vec![("type", descriptor_type()), ("name", InterfaceType::String)])
+}
+
+fn error_code() -> InterfaceType {
+    InterfaceType::Enum(names(ERROR_CODES))
+}
+
+fn advice() -> InterfaceType {
+    InterfaceType::Enum(names(&["normal", "sequential", "random", "will-need", "dont-need", "no-reuse"]))
+}
+
+fn metadata_hash_value() -> InterfaceType {
+    record(vec![("lower", InterfaceType::U64), ("upper", InterfaceType::U64)])
+}
+
+fn arg<T: ComponentValue>(args: &mut Args) -> Option<T> {
+    component_next(args).ok()
+}
+
+fn flags(args: &mut Args) -> Vec<Name> {
+    match args.next() {
+        Some(ComponentVal::Flags(names)) => names,
+        _ => vec![],
+    }
+}
+
+fn some(val: ComponentVal) -> ComponentVal {
+    ComponentVal::Option(Some(Box::new(val)))
+}
+
+fn bytes(bytes: Vec<u8>) -> ComponentVal {
+    ComponentVal::List(bytes.into_iter().map(ComponentVal::U8).collect())
+}
+
+fn fields(fields: Vec<(&str, ComponentVal)>) -> ComponentVal {
+    ComponentVal::Record(fields.into_iter().map(|(name, val)| (name.to_string(), val)).collect())
+}
+
+fn code(error: &io::Error) -> Code {
+    match error.kind() {
+        io::ErrorKind::NotFound => "no-entry",
+        io::ErrorKind::PermissionDenied => "not-permitted",
+        io::ErrorKind::AlreadyExists => "exist",
+        io::ErrorKind::InvalidInput => "invalid",
+        io::ErrorKind::DirectoryNotEmpty => "not-empty",
+        io::ErrorKind::FileTooLarge => "file-too-large",
+        _ => "io",
+    }
+}
+
+fn error_to_debug_string(host: &mut Host, args: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    match host.entry(args) {
+        Some(Entry::Error(error)) => Ok(vec![ComponentVal::String(error.to_string())]),
+        _ => Err(Error::Trap),
+    }
+}
+
+fn pollable_ready(host: &mut Host, args: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    match host.entry(args) {
+        Some(Entry::Pollable(at)) => Ok(vec![ComponentVal::Bool(at.is_some_and(|at| at <= Instant::now()))]),
+        _ => Err(Error::Trap),
+    }
+}
+
+fn pollable_block(host: &mut Host, args: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    let val = args.next().ok_or(Error::Trap)?;
+    poll(host, &mut vec![ComponentVal::List(vec![val])].into_iter())?;
+    Ok(vec![])
+}
+
+/// Wait for some of the pollables to be ready, and return their indices.
+/// Waiting only for pollables that are never ready traps, since nothing could wake it up.
+fn poll(host: &mut Host, args: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    let mut deadlines = vec![];
+    let pollables = match args.next() {
+        Some(ComponentVal::List(pollables)) => pollables,
+        _ => return Err(Error::Trap),
+    };
+    for val in pollables {
+        match host.get(Some(val)) {
+            Some(Entry::Pollable(at)) => deadlines.push(*at),
+            _ => return Err(Error::Trap),
+        }
+    }
+    let first = deadlines.iter().flatten().min().ok_or(Error::Trap)?;
+    if let Some(duration) = first.checked_duration_since(Instant::now()) {
+        thread::sleep(duration);
+    }
+    let now = Instant::now();
+    let ready = deadlines.iter().enumerate().filter(|(_, at)| at.is_some_and(|at| at <= now));
+    Ok(vec![ComponentVal::List(ready.map(|(i, _)| ComponentVal::U32(i as u32)).collect())])
+}
+
+fn subscribe(host: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![host.own(|types| &types.pollable, Entry::Pollable(Some(Instant::now())))])
+}
+
+fn input_read(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, StreamError> {
+    let stream = args.next();
+    let len = arg::<u64>(args).ok_or(StreamError::Closed)?;
+    Ok(Some(bytes(host.read(stream, len)?)))
+}
+
+fn input_skip(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, StreamError> {
+    let stream = args.next();
+    let len = arg::<u64>(args).ok_or(StreamError::Closed)?;
+    Ok(Some(ComponentVal::U64(host.read(stream, len)?.len() as u64)))
+}
+
+fn output_check_write(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, StreamError> {
+    match host.entry(args) {
+        Some(Entry::Output(_)) => Ok(Some(ComponentVal::U64(MAX_WRITE))),
+        _ => Err(StreamError::Closed),
+    }
+}
+
+fn output_write(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, StreamError> {
+    let stream = args.next();
+    let contents = arg::<Vec<u8>>(args).ok_or(StreamError::Closed)?;
+    host.write(stream, &contents, false)?;
+    Ok(None)
+}
+
+fn output_write_and_flush(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, StreamError> {
+    let stream = args.next();
+    let contents = arg::<Vec<u8>>(args).ok_or(StreamError::Closed)?;
+    host.write(stream, &contents, true)?;
+    Ok(None)
+}
+
+fn output_flush(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, StreamError> {
+    host.write(args.next(), &[], true)?;
+    Ok(None)
+}
+
+fn output_write_zeroes(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, StreamError> {
+    let stream = args.next();
+    let len = arg::<u64>(args).ok_or(StreamError::Closed)?;
+    host.write(stream, &vec![0; len.min(MAX_WRITE) as usize], true)?;
+    Ok(None)
+}
+
+fn output_splice(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, StreamError> {
+    let stream = args.next();
+    let src = args.next();
+    let len = arg::<u64>(args).ok_or(StreamError::Closed)?;
+    let contents = host.read(src, len)?;
+    host.write(stream, &contents, true)?;
+    Ok(Some(ComponentVal::U64(contents.len() as u64)))
+}
+
+fn monotonic_now(host: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![ComponentVal::U64(host.start.elapsed().as_nanos() as u64)])
+}
+
+fn monotonic_resolution(_: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![ComponentVal::U64(1)])
+}
+
+fn subscribe_instant(host: &mut Host, args: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    let when = arg::<u64>(args).ok_or(Error::Trap)?;
+    let at = host.start.checked_add(Duration::from_nanos(when));
+    Ok(vec![host.own(|types| &types.pollable, Entry::Pollable(at))])
+}
+
+fn subscribe_duration(host: &mut Host, args: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    let when = arg::<u64>(args).ok_or(Error::Trap)?;
+    let at = Instant::now().checked_add(Duration::from_nanos(when));
+    Ok(vec![host.own(|types| &types.pollable, Entry::Pollable(at))])
+}
+
+fn wall_now(_: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok(vec![fields(vec![("seconds", ComponentVal::U64(time.as_secs())), ("nanoseconds", ComponentVal::U32(time.subsec_nanos()))])])
+}
+
+fn wall_resolution(_: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![fields(vec![("seconds", ComponentVal::U64(0)), ("nanoseconds", ComponentVal::U32(1))])])
+}
+
+// the interfaces cannot report an error, so an unreadable source traps
+fn random_u64() -> Result<u64, Error> {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&random_bytes(8).map_err(|_| Error::Trap)?);
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn random_get_bytes(_: &mut Host, args: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    let len = arg::<u64>(args).ok_or(Error::Trap)?;
+    Ok(vec![bytes(random_bytes(len as usize).map_err(|_| Error::Trap)?)])
+}
+
+fn random_get_u64(_: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![ComponentVal::U64(random_u64()?)])
+}
+
+fn random_insecure_seed(_: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![ComponentVal::Tuple(vec![ComponentVal::U64(random_u64()?), ComponentVal::U64(random_u64()?)])])
+}
+
+fn unsupported(_: &mut Host, _: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    Err("unsupported")
+}
+
+fn descriptor_read_via_stream(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    let offset = arg::<u64>(args).ok_or("invalid")?;
+    let file = descriptor.file.ok_or("is-directory")?;
+    Ok(Some(host.own(|types| &types.input_stream, Entry::Input(Input::File(file, offset)))))
+}
+
+fn descriptor_write_via_stream(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    let offset = arg::<u64>(args).ok_or("invalid")?;
+    let file = descriptor.file.ok_or("is-directory")?;
+    Ok(Some(host.own(|types| &types.output_stream, Entry::Output(Output::File(file, Some(offset))))))
+}
+
+fn descriptor_append_via_stream(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    let file = descriptor.file.ok_or("is-directory")?;
+    Ok(Some(host.own(|types| &types.output_stream, Entry::Output(Output::File(file, None)))))
+}
+
+fn descriptor_advise(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    host.descriptor(args)?;
+    Ok(None)
+}
+
+fn descriptor_sync(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    if let Some(file) = host.descriptor(args)?.file {
+        lock(&file).flush().map_err(|error| code(&error))?;
+    }
+    Ok(None)
+}
+
+fn descriptor_get_flags(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    let mut flags = vec![];
+    if descriptor.read {
+        flags.push("read".to_string());
+    }
+    if descriptor.write {
+        flags.push("write".to_string());
+        if descriptor.file.is_none() {
+            flags.push("mutate-directory".to_string());
+        }
+    }
+    Ok(Some(ComponentVal::Flags(flags)))
+}
+
+fn descriptor_get_type(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    let tp = if descriptor.file.is_some() { "regular-file" } else { "directory" };
+    Ok(Some(ComponentVal::Enum(tp.to_string())))
+}
+
+fn descriptor_read(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    let length = arg::<u64>(args).ok_or("invalid")?;
+    let offset = arg::<u64>(args).ok_or("invalid")?;
+    let file = descriptor.file.ok_or("is-directory")?;
+    if !descriptor.read {
+        return Err("bad-descriptor");
+    }
+    let mut file = lock(&file);
+    let mut buf = vec![];
+    file.seek(SeekFrom::Start(offset)).map_err(|error| code(&error))?;
+    (&mut *file).take(length.min(MAX_READ)).read_to_end(&mut buf).map_err(|error| code(&error))?;
+    let end = (buf.len() as u64) < length;
+    Ok(Some(ComponentVal::Tuple(vec![bytes(buf), ComponentVal::Bool(end)])))
+}
+
+fn descriptor_write(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    let buffer = arg::<Vec<u8>>(args).ok_or("invalid")?;
+    let offset = arg::<u64>(args).ok_or("invalid")?;
+    let file = descriptor.file.ok_or("is-directory")?;
+    if !descriptor.write {
+        return Err("bad-descriptor");
+    }
+    let mut file = lock(&file);
+    file.seek(SeekFrom::Start(offset)).and_then(|_| file.write_all(&buffer)).map_err(|error| code(&error))?;
+    Ok(Some(ComponentVal::U64(buffer.len() as u64)))
+}
+
+fn descriptor_read_directory(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    if descriptor.file.is_some() {
+        return Err("not-directory");
+    }
+    let entries = host.fs(&descriptor).read_dir(&descriptor.path).map_err(|error| code(&error))?;
+    Ok(Some(host.own(|types| &types.directory_entry_stream, Entry::DirectoryEntries(entries.into_iter()))))
+}
+
+fn read_directory_entry(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let entries = match host.entry(args) {
+        Some(Entry::DirectoryEntries(entries)) => entries,
+        _ => return Err("bad-descriptor"),
+    };
+    let entry = entries.next().map(|(name, dir)| {
+        let tp = if dir { "directory" } else { "regular-file" };
+        Box::new(fields(vec![("type", ComponentVal::Enum(tp.to_string())), ("name", ComponentVal::String(name))]))
+    });
+    Ok(Some(ComponentVal::Option(entry)))
+}
+
+/// Path under a directory descriptor, refusing to leave its preopened directory.
+fn path_at(descriptor: &Descriptor, path: &str) -> Result<PathBuf, Code> {
+    if descriptor.file.is_some() {
+        return Err("not-directory");
+    }
+    resolve(&descriptor.path, path).map_err(|_| "not-permitted")
+}
+
+fn descriptor_create_directory_at(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    let path = path_at(&descriptor, &arg::<String>(args).ok_or("invalid")?)?;
+    host.fs(&descriptor).create_dir(&path).map_err(|error| code(&error))?;
+    Ok(None)
+}
+
+fn descriptor_remove_directory_at(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    let path = path_at(&descriptor, &arg::<String>(args).ok_or("invalid")?)?;
+    host.fs(&descriptor).remove_dir(&path).map_err(|error| code(&error))?;
+    Ok(None)
+}
+
+fn descriptor_unlink_file_at(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    let path = path_at(&descriptor, &arg::<String>(args).ok_or("invalid")?)?;
+    host.fs(&descriptor).remove_file(&path).map_err(|error| code(&error))?;
+    Ok(None)
+}
+
+fn stat(fs: &dyn WasiFs, path: &Path, file: Option<&SharedFile>) -> Result<ComponentVal, Code> {
+    let (tp, size) = if file.is_none() && fs.is_dir(path).map_err(|error| code(&error))? {
+        ("directory", 0)
+    } else {
+        let size = match file {
+            Some(file) => lock(file).seek(SeekFrom::End(0)),
+            None => {
+                let options = WasiOpenOptions { read: true, ..WasiOpenOptions::default() };
+                fs.open(path, &options).and_then(|mut file| file.seek(SeekFrom::End(0)))
+            },
+        };
+        ("regular-file", size.map_err(|error| code(&error))?)
+    };
+    Ok(fields(vec![
+        ("type", ComponentVal::Enum(tp.to_string())),
+        ("link-count", ComponentVal::U64(1)),
+        ("size", ComponentVal::U64(size)),
+        ("data-access-timestamp", ComponentVal::Option(None)),
+        ("data-modification-timestamp", ComponentVal::Option(None)),
+        ("status-change-timestamp", ComponentVal::Option(None)),
+    ]))
+}
+
+fn descriptor_stat(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    stat(host.fs(&descriptor), &descriptor.path, descriptor.file.as_ref()).map(Some)
+}
+
+fn descriptor_stat_at(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    flags(args);
+    let path = path_at(&descriptor, &arg::<String>(args).ok_or("invalid")?)?;
+    stat(host.fs(&descriptor), &path, None).map(Some)
+}
+
+fn descriptor_open_at(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let parent = host.descriptor(args)?;
+    flags(args);
+    let path = arg::<String>(args).ok_or("invalid")?;
+    let open = flags(args);
+    let flags = flags(args);
+    let path = path_at(&parent, &path)?;
+    let has = |flags: &[Name], name: &str| flags.iter().any(|flag| flag == name);
+    let read = has(&flags, "read");
+    let write = has(&flags, "write");
+    let fs = host.fs(&parent);
+    let dir = match fs.is_dir(&path) {
+        Ok(dir) => Some(dir),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => return Err(code(&error)),
+    };
+    let descriptor = if dir == Some(true) || has(&open, "directory") {
+        match dir {
+            Some(true) if has(&open, "exclusive") => return Err("exist"),
+            Some(true) => Descriptor { preopen: parent.preopen, path, file: None, read, write: write || has(&flags, "mutate-directory") },
+            Some(false) => return Err("not-directory"),
+            None => return Err("no-entry"),
+        }
+    } else {
+        let options = WasiOpenOptions {
+            read: read || !write,
+            write,
+            append: false,
+            create: has(&open, "create"),
+            create_new: has(&open, "exclusive"),
+            truncate: has(&open, "truncate"),
+        };
+        let file = fs.open(&path, &options).map_err(|error| code(&error))?;
+        Descriptor { preopen: parent.preopen, path, file: Some(Arc::new(Mutex::new(file))), read, write }
+    };
+    Ok(Some(host.own(|types| &types.descriptor, Entry::Descriptor(descriptor))))
+}
+
+fn descriptor_is_same_object(host: &mut Host, args: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    let this = host.descriptor(args).map_err(|_| Error::Trap)?;
+    let other = host.descriptor(args).map_err(|_| Error::Trap)?;
+    Ok(vec![ComponentVal::Bool(this.preopen == other.preopen && this.path == other.path)])
+}
+
+fn metadata_hash(preopen: usize, path: &Path) -> ComponentVal {
+    let mut lower = DefaultHasher::new();
+    (preopen, path).hash(&mut lower);
+    let mut upper = DefaultHasher::new();
+    (path, preopen).hash(&mut upper);
+    fields(vec![("lower", ComponentVal::U64(lower.finish())), ("upper", ComponentVal::U64(upper.finish()))])
+}
+
+fn descriptor_metadata_hash(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    Ok(Some(metadata_hash(descriptor.preopen, &descriptor.path)))
+}
+
+fn descriptor_metadata_hash_at(host: &mut Host, args: &mut Args) -> Result<Option<ComponentVal>, Code> {
+    let descriptor = host.descriptor(args)?;
+    flags(args);
+    let path = arg::<String>(args).ok_or("invalid")?;
+    let path = path_at(&descriptor, &path)?;
+    host.fs(&descriptor).is_dir(&path).map_err(|error| code(&error))?;
+    Ok(Some(metadata_hash(descriptor.preopen, &path)))
+}
+
+fn filesystem_error_code(host: &mut Host, args: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    match host.entry(args) {
+        Some(Entry::Error(error)) => Ok(vec![some(ComponentVal::Enum(code(error).to_string()))]),
+        _ => Err(Error::Trap),
+    }
+}
+
+fn get_directories(host: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    let mut directories = vec![];
+    for preopen in 0..host.ctx.preopens.len() {
+        let name = host.ctx.preopens[preopen].0.clone();
+        let descriptor = Descriptor { preopen, path: PathBuf::new(), file: None, read: true, write: true };
+        let descriptor = host.own(|types| &types.descriptor, Entry::Descriptor(descriptor));
+        directories.push(ComponentVal::Tuple(vec![descriptor, ComponentVal::String(name)]));
+    }
+    Ok(vec![ComponentVal::List(directories)])
+}
+
+fn get_environment(host: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![host.ctx.env.clone().into_val()])
+}
+
+fn get_arguments(host: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![host.ctx.args.clone().into_val()])
+}
+
+fn initial_cwd(_: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![ComponentVal::Option(None)])
+}
+
+fn exit(_: &mut Host, args: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    match args.next() {
+        Some(ComponentVal::Result(Ok(_))) => Err(Error::Exit(0)),
+        _ => Err(Error::Exit(1)),
+    }
+}
+
+fn get_stdin(host: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![host.own(|types| &types.input_stream, Entry::Input(Input::Stdin))])
+}
+
+fn get_stdout(host: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![host.own(|types| &types.output_stream, Entry::Output(Output::Stdout))])
+}
+
+fn get_stderr(host: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![host.own(|types| &types.output_stream, Entry::Output(Output::Stderr))])
+}
+
+fn no_terminal(_: &mut Host, _: &mut Args) -> Result<Vec<ComponentVal>, Error> {
+    Ok(vec![ComponentVal::Option(None)])
+}
+
+#[test]
+fn test_wasi_component_imports() {
+    use crate::{
+        store_init,
+        component_resource_drop,
+        MemFs,
+        WasiPipe,
+    };
+
+    let fs = MemFs::default();
+    fs.write("in.txt", b"abc");
+    let stdout = WasiPipe::default();
+    let ctx = WasiCtx {
+        args: vec!["prog".to_string()],
+        preopens: vec![("/".to_string(), Box::new(fs.clone()))],
+        stdin: Box::new(WasiPipe::new(b"xyz")),
+        stdout: Box::new(stdout.clone()),
+        ..WasiCtx::default()
+    };
+    let imports = ComponentInst { exports: wasi_component_imports(ctx) };
+    let mut store = store_init();
+    let call = |store: &mut Store, interface: &str, name: &str, args: Vec<ComponentVal>| {
+        let instance = match component_export(imports.clone(), format!("{}@{}", interface, VERSION)) {
+            Ok(ComponentExternVal::Instance(instance)) => instance,
+            _ => panic!("no instance {}", interface),
+        };
+        match component_export(instance, name.to_string()) {
+            Ok(ComponentExternVal::Func(func)) => component_invoke(store, &func, args),
+            _ => panic!("no function {}", name),
+        }
+    };
+    let borrow = |val: &ComponentVal| match val {
+        ComponentVal::Own(resource) => ComponentVal::Borrow(resource.clone()),
+        _ => panic!("not a resource"),
+    };
+    let ok = |val: ComponentVal| vec![ComponentVal::Result(Ok(Some(Box::new(val))))];
+    let err = |val: ComponentVal| vec![ComponentVal::Result(Err(Some(Box::new(val))))];
+    let string = |s: &str| ComponentVal::String(s.to_string());
+
+    assert_eq!(call(&mut store, "wasi:cli/environment", "get-arguments", vec![]).unwrap(), vec![vec!["prog".to_string()].into_val()]);
+
+    let stdin = call(&mut store, "wasi:cli/stdin", "get-stdin", vec![]).unwrap().remove(0);
+    let read = vec![borrow(&stdin), ComponentVal::U64(10)];
+    assert_eq!(call(&mut store, "wasi:io/streams", "[method]input-stream.blocking-read", read.clone()).unwrap(), ok(bytes(b"xyz".to_vec())));
+    assert_eq!(call(&mut store, "wasi:io/streams", "[method]input-stream.blocking-read", read).unwrap(), err(ComponentVal::Variant("closed".to_string(), None)));
+    let output = call(&mut store, "wasi:cli/stdout", "get-stdout", vec![]).unwrap().remove(0);
+    let write = vec![borrow(&output), bytes(b"xyz".to_vec())];
+    assert_eq!(call(&mut store, "wasi:io/streams", "[method]output-stream.blocking-write-and-flush", write).unwrap(), vec![ComponentVal::Result(Ok(None))]);
+    assert_eq!(stdout.contents(), b"xyz");
+
+    let dir = match call(&mut store, "wasi:filesystem/preopens", "get-directories", vec![]).unwrap().remove(0) {
+        ComponentVal::List(mut dirs) => match dirs.remove(0) {
+            ComponentVal::Tuple(mut dir) => dir.remove(0),
+            _ => panic!("not a tuple"),
+        },
+        _ => panic!("not a list"),
+    };
+    let open = |path: &str, create: bool| vec![
+        borrow(&dir),
+        ComponentVal::Flags(vec![]),
+        string(path),
+        ComponentVal::Flags(if create { names(&["create"]) } else { vec![] }),
+        ComponentVal::Flags(names(&["write"])),
+    ];
+    assert_eq!(call(&mut store, "wasi:filesystem/types", "[method]descriptor.open-at", open("none.txt", false)).unwrap(), err(ComponentVal::Enum("no-entry".to_string())));
+    let file = match call(&mut store, "wasi:filesystem/types", "[method]descriptor.open-at", open("out.txt", true)).unwrap().remove(0) {
+        ComponentVal::Result(Ok(Some(file))) => *file,
+        result => panic!("{:?}", result),
+    };
+    let write = vec![borrow(&file), bytes(b"hello".to_vec()), ComponentVal::U64(0)];
+    assert_eq!(call(&mut store, "wasi:filesystem/types", "[method]descriptor.write", write.clone()).unwrap(), ok(ComponentVal::U64(5)));
+    assert_eq!(fs.read("out.txt").unwrap(), b"hello");
+    match call(&mut store, "wasi:filesystem/types", "[method]descriptor.stat-at", vec![borrow(&dir), ComponentVal::Flags(vec![]), string("in.txt")]).unwrap().remove(0) {
+        ComponentVal::Result(Ok(Some(stat))) => match *stat {
+            ComponentVal::Record(fields) => assert!(fields.contains(&("size".to_string(), ComponentVal::U64(3)))),
+            stat => panic!("{:?}", stat),
+        },
+        result => panic!("{:?}", result),
+    }
+    if let ComponentVal::Own(resource) = file {
+        component_resource_drop(&mut store, resource).unwrap();
+    }
+    assert_eq!(call(&mut store, "wasi:filesystem/types", "[method]descriptor.write", write).unwrap(), err(ComponentVal::Enum("bad-descriptor".to_string())));
+
+    assert!(matches!(call(&mut store, "wasi:cli/exit", "exit", vec![ComponentVal::Result(Err(None))]), Err(Error::Exit(1))));
+}
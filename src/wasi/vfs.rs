@@ -0,0 +1,436 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Filesystem of a preopened directory, whose paths are relative to it,
+/// without `..` or `.` in them.
+pub trait WasiFs: Send {
+    /// Whether `path` is a directory, failing with `NotFound` if there is nothing.
+    fn is_dir(&self, path: &Path) -> io::Result<bool>;
+
+    fn open(&self, path: &Path, options: &WasiOpenOptions) -> io::Result<Box<dyn WasiFile>>;
+
+    /// Names in a directory, with whether each one is a directory.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<(String, bool)>>;
+
+    /// Create a directory in an existing one.
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Remove an empty directory.
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+}
+
+/// Open file of a `WasiFs`.
+pub trait WasiFile: Read + Write + Seek + Send {}
+
+impl<T: Read + Write + Seek + Send> WasiFile for T {}
+
+/// How to open a file, as `std::fs::OpenOptions`.
+#[derive(Default, Debug, Clone)]
+pub struct WasiOpenOptions {
+    pub read: bool,
+    pub write: bool,
+    pub append: bool,
+    pub create: bool,
+    pub create_new: bool,
+    pub truncate: bool,
+}
+
+/// Directory of the host.
+pub struct HostFs {
+    root: PathBuf,
+}
+
+impl HostFs {
+    pub fn new(root: impl Into<PathBuf>) -> HostFs {
+        HostFs { root: root.into() }
+    }
+
+    // symbolic links may lead out of the directory, even dangling ones once a file is
+    // created through them, so none is followed
+    fn path(&self, path: &Path) -> io::Result<PathBuf> {
+        let mut resolved = self.root.clone();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                _ => return Err(io::Error::new(io::ErrorKind::PermissionDenied, "path outside of the directory")),
+            }
+            match fs::symlink_metadata(&resolved) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    return Err(io::Error::new(io::ErrorKind::PermissionDenied, "symbolic link"));
+                },
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                _ => {},
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+impl WasiFs for HostFs {
+    fn is_dir(&self, path: &Path) -> io::Result<bool> {
+        Ok(fs::metadata(self.path(path)?)?.is_dir())
+    }
+
+    fn open(&self, path: &Path, options: &WasiOpenOptions) -> io::Result<Box<dyn WasiFile>> {
+        let file = OpenOptions::new()
+            .read(options.read)
+            .write(options.write)
+            .append(options.append)
+            .create(options.create)
+            .create_new(options.create_new)
+            .truncate(options.truncate)
+            .open(self.path(path)?)?;
+        Ok(Box::new(file))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<(String, bool)>> {
+        let mut entries = vec![];
+        for entry in fs::read_dir(self.path(path)?)? {
+            let entry = entry?;
+            entries.push((entry.file_name().to_string_lossy().into_owned(), entry.file_type()?.is_dir()));
+        }
+        Ok(entries)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(self.path(path)?)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(self.path(path)?)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(self.path(path)?)
+    }
+}
+
+type MemData = Arc<Mutex<Vec<u8>>>;
+
+enum MemNode {
+    File(MemData),
+    Dir(BTreeMap<String, MemNode>),
+}
+
+/// Filesystem held in memory, shared by its clones,
+/// so that its contents stay visible to the host after a run.
+#[derive(Clone)]
+pub struct MemFs {
+    root: Arc<Mutex<MemNode>>,
+}
+
+impl Default for MemFs {
+    fn default() -> MemFs {
+        MemFs { root: Arc::new(Mutex::new(MemNode::Dir(BTreeMap::new()))) }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no such file or directory")
+}
+
+fn names(path: &Path) -> Vec<String> {
+    path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+        _ => None,
+    }).collect()
+}
+
+impl MemNode {
+    fn get(&self, names: &[String]) -> Option<&MemNode> {
+        match names.split_first() {
+            None => Some(self),
+            Some((name, rest)) => match self {
+                MemNode::Dir(entries) => entries.get(name)?.get(rest),
+                MemNode::File(_) => None,
+            },
+        }
+    }
+
+    fn dir_mut(&mut self, names: &[String]) -> Option<&mut BTreeMap<String, MemNode>> {
+        match (self, names.split_first()) {
+            (MemNode::Dir(entries), None) => Some(entries),
+            (MemNode::Dir(entries), Some((name, rest))) => entries.get_mut(name)?.dir_mut(rest),
+            _ => None,
+        }
+    }
+}
+
+impl MemFs {
+    /// Contents of the file at `path`.
+    pub fn read(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        match lock(&self.root).get(&names(path.as_ref()))? {
+            MemNode::File(data) => Some(lock(data).clone()),
+            MemNode::Dir(_) => None,
+        }
+    }
+
+    /// Create or replace the file at `path`, with the missing directories above it.
+    pub fn write(&self, path: impl AsRef<Path>, contents: &[u8]) {
+        let names = names(path.as_ref());
+        if let Some((name, dirs)) = names.split_last() {
+            self.create_dir_all(dirs.iter().collect::<PathBuf>());
+            if let Some(entries) = lock(&self.root).dir_mut(dirs) {
+                entries.insert(name.clone(), MemNode::File(Arc::new(Mutex::new(contents.to_vec()))));
+            }
+        }
+    }
+
+    /// Create the directory at `path`, with the missing ones above it.
+    pub fn create_dir_all(&self, path: impl AsRef<Path>) {
+        let mut root = lock(&self.root);
+        let mut node = &mut *root;
+        for name in names(path.as_ref()) {
+            node = match node {
+                MemNode::Dir(entries) => entries.entry(name).or_insert_with(|| MemNode::Dir(BTreeMap::new())),
+                MemNode::File(_) => return,
+            };
+        }
+    }
+}
+
+impl WasiFs for MemFs {
+    fn is_dir(&self, path: &Path) -> io::Result<bool> {
+        match lock(&self.root).get(&names(path)).ok_or_else(not_found)? {
+            MemNode::Dir(_) => Ok(true),
+            MemNode::File(_) => Ok(false),
+        }
+    }
+
+    fn open(&self, path: &Path, options: &WasiOpenOptions) -> io::Result<Box<dyn WasiFile>> {
+        let names = names(path);
+        let (name, dirs) = names.split_last().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "directory"))?;
+        let mut root = lock(&self.root);
+        let entries = root.dir_mut(dirs).ok_or_else(not_found)?;
+        let data = match entries.get(name) {
+            Some(_) if options.create_new => return Err(io::Error::new(io::ErrorKind::AlreadyExists, "file exists")),
+            Some(MemNode::File(data)) => data.clone(),
+            Some(MemNode::Dir(_)) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "directory")),
+            None if options.create || options.create_new => {
+                let data = MemData::default();
+                entries.insert(name.clone(), MemNode::File(data.clone()));
+                data
+            },
+            None => return Err(not_found()),
+        };
+        if options.truncate {
+            lock(&data).clear();
+        }
+        Ok(Box::new(MemFile { data, pos: 0, options: options.clone() }))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<(String, bool)>> {
+        match lock(&self.root).get(&names(path)).ok_or_else(not_found)? {
+            MemNode::Dir(entries) => Ok(entries.iter().map(|(name, node)| (name.clone(), matches!(node, MemNode::Dir(_)))).collect()),
+            MemNode::File(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory")),
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let names = names(path);
+        let (name, dirs) = names.split_last().ok_or_else(|| io::Error::new(io::ErrorKind::AlreadyExists, "file exists"))?;
+        let mut root = lock(&self.root);
+        let entries = root.dir_mut(dirs).ok_or_else(not_found)?;
+        if entries.contains_key(name) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "file exists"));
+        }
+        entries.insert(name.clone(), MemNode::Dir(BTreeMap::new()));
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let names = names(path);
+        let (name, dirs) = names.split_last().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "directory"))?;
+        let mut root = lock(&self.root);
+        let entries = root.dir_mut(dirs).ok_or_else(not_found)?;
+        match entries.get(name).ok_or_else(not_found)? {
+            MemNode::File(_) => {
+                entries.remove(name);
+                Ok(())
+            },
+            MemNode::Dir(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, "directory")),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let names = names(path);
+        let (name, dirs) = names.split_last().ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, "root directory"))?;
+        let mut root = lock(&self.root);
+        let entries = root.dir_mut(dirs).ok_or_else(not_found)?;
+        match entries.get(name).ok_or_else(not_found)? {
+            MemNode::Dir(children) if children.is_empty() => {
+                entries.remove(name);
+                Ok(())
+            },
+            MemNode::Dir(_) => Err(io::Error::new(io::ErrorKind::DirectoryNotEmpty, "directory not empty")),
+            MemNode::File(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a directory")),
+        }
+    }
+}
+
+/// Size a `MemFile` doesn't grow past, since a write past its end fills the gap in memory.
+const MAX_FILE_SIZE: u64 = 1 << 30;
+
+struct MemFile {
+    data: MemData,
+    pos: u64,
+    options: WasiOpenOptions,
+}
+
+impl Read for MemFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.options.read {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "not open for reading"));
+        }
+        let data = lock(&self.data);
+        let start = (self.pos as usize).min(data.len());
+        let n = buf.len().min(data.len() - start);
+        buf[..n].copy_from_slice(&data[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for MemFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.options.write {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "not open for writing"));
+        }
+        let mut data = lock(&self.data);
+        if self.options.append {
+            self.pos = data.len() as u64;
+        }
+        let end = self.pos.checked_add(buf.len() as u64).filter(|&end| end <= MAX_FILE_SIZE)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::FileTooLarge, "file too large"))? as usize;
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[self.pos as usize..end].copy_from_slice(buf);
+        self.pos = end as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MemFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i64),
+            SeekFrom::Current(offset) => (self.pos as i64, offset),
+            SeekFrom::End(offset) => (lock(&self.data).len() as i64, offset),
+        };
+        match base.checked_add(offset) {
+            Some(pos) if pos >= 0 => {
+                self.pos = pos as u64;
+                Ok(self.pos)
+            },
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "negative position")),
+        }
+    }
+}
+
+/// Buffer shared by its clones, to feed the standard input of a program or capture its output.
+#[derive(Clone, Default)]
+pub struct WasiPipe {
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl WasiPipe {
+    pub fn new(contents: &[u8]) -> WasiPipe {
+        WasiPipe { data: Arc::new(Mutex::new(contents.to_vec())) }
+    }
+
+    /// What is written and not read yet.
+    pub fn contents(&self) -> Vec<u8> {
+        lock(&self.data).clone()
+    }
+}
+
+impl Read for WasiPipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut data = lock(&self.data);
+        let n = buf.len().min(data.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        data.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for WasiPipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        lock(&self.data).extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_mem_fs() {
+    let fs = MemFs::default();
+    fs.write("a/b.txt", b"abc");
+    fs.create_dir_all("a/c");
+    assert_eq!(fs.read_dir(Path::new("a")).unwrap(), vec![("b.txt".to_string(), false), ("c".to_string(), true)]);
+    assert!(fs.is_dir(Path::new("a/c")).unwrap());
+    assert_eq!(fs.is_dir(Path::new("d")).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+    let options = WasiOpenOptions { write: true, append: true, ..WasiOpenOptions::default() };
+    let mut file = fs.open(Path::new("a/b.txt"), &options).unwrap();
+    file.write_all(b"de").unwrap();
+    assert_eq!(fs.read("a/b.txt").unwrap(), b"abcde");
+
+    let options = WasiOpenOptions { read: true, create_new: true, ..WasiOpenOptions::default() };
+    assert_eq!(fs.open(Path::new("a/b.txt"), &options).err().unwrap().kind(), io::ErrorKind::AlreadyExists);
+    let options = WasiOpenOptions { read: true, ..WasiOpenOptions::default() };
+    let mut file = fs.open(Path::new("a/b.txt"), &options).unwrap();
+    file.seek(SeekFrom::End(-2)).unwrap();
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).unwrap();
+    assert_eq!(buf, "de");
+
+    let options = WasiOpenOptions { write: true, ..WasiOpenOptions::default() };
+    let mut file = fs.open(Path::new("a/b.txt"), &options).unwrap();
+    file.seek(SeekFrom::Start(1 << 62)).unwrap();
+    assert_eq!(file.write(b"f").unwrap_err().kind(), io::ErrorKind::FileTooLarge);
+    assert_eq!(fs.read("a/b.txt").unwrap(), b"abcde");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_host_fs_symlink() {
+    use std::os::unix::fs::symlink;
+
+    let dir = std::env::temp_dir().join(format!("wasmiq-host-fs-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let (root, outside) = (dir.join("root"), dir.join("outside"));
+    fs::create_dir_all(root.join("a")).unwrap();
+    fs::create_dir_all(&outside).unwrap();
+    symlink(outside.join("new.txt"), root.join("dangling")).unwrap();
+    symlink(&outside, root.join("a/out")).unwrap();
+
+    let fs = HostFs::new(&root);
+    let options = WasiOpenOptions { write: true, create: true, ..WasiOpenOptions::default() };
+    fs.open(Path::new("a/b.txt"), &options).unwrap().write_all(b"b").unwrap();
+    assert_eq!(fs::read(root.join("a/b.txt")).unwrap(), b"b");
+    // creating through a dangling link would leave the directory
+    assert_eq!(fs.open(Path::new("dangling"), &options).err().unwrap().kind(), io::ErrorKind::PermissionDenied);
+    assert!(!outside.join("new.txt").exists());
+    assert_eq!(fs.open(Path::new("a/out/new.txt"), &options).err().unwrap().kind(), io::ErrorKind::PermissionDenied);
+    assert_eq!(fs.read_dir(Path::new("a/out")).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+    assert!(fs.is_dir(Path::new("a")).unwrap());
+    fs::remove_dir_all(&dir).unwrap();
+}
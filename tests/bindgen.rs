@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+
+use wasmiq::{
+    store_init, wit_bindgen, component_func_host, component_invoke,
+    ComponentInst, ComponentExternVal, ComponentFuncType, ComponentVal, ComponentValue,
+};
+
+// generated from app.wit, regenerate it with `wit_bindgen` when the generator changes
+include!("bindgen/app.rs");
+
+#[test]
+fn test_bindgen_snapshot() {
+    let out = wit_bindgen(include_str!("bindgen/app.wit")).unwrap();
+    assert_eq!(out, include_str!("bindgen/app.rs"));
+}
+
+#[derive(Default)]
+struct Logger {
+    logged: Vec<(types::Level, String)>,
+    last: Option<types::Point>,
+}
+
+impl logging::Host for Logger {
+    fn log(&mut self, level: types::Level, msg: String) -> types::Id {
+        self.logged.push((level, msg));
+        self.logged.len() as types::Id
+    }
+    fn last(&mut self) -> Option<types::Point> {
+        self.last.clone()
+    }
+    fn check(&mut self, perms: types::Perms, shapes: Vec<types::Shape>) -> Result<u32, String> {
+        if !perms.read {
+            return Err("unreadable".to_string());
+        }
+        Ok(shapes.iter().map(|shape| match shape {
+            types::Shape::Dot(_) => 1,
+            types::Shape::Line(_) => 2,
+            types::Shape::Empty => 0,
+        }).sum())
+    }
+}
+
+struct Clock;
+
+impl app::Host for Clock {
+    fn now(&mut self) -> u64 { 42 }
+}
+
+// the host side of the bindings provides the instance the guest side calls into
+#[test]
+fn test_bindgen_round_trip() {
+    let logger = Arc::new(Mutex::new(Logger { last: Some(types::Point { x: 1, y: -2 }), ..Logger::default() }));
+    let mut exports = vec![];
+    logging::add_to_imports(&mut exports, logger.clone());
+    let run = component_func_host(
+        ComponentFuncType {
+            params: vec![("args".to_string(), <Vec<String> as ComponentValue>::tp())],
+            results: vec![<Result<(), String> as ComponentValue>::tp()],
+        },
+        Arc::new(|_, args| {
+            let args = Vec::<String>::from_val(args.into_iter().next().unwrap())?;
+            let result: Result<(), String> = if args.is_empty() { Err("no args".to_string()) } else { Ok(()) };
+            Ok(vec![result.into_val()])
+        }),
+    );
+    exports.push(("run".to_string(), ComponentExternVal::Func(run)));
+
+    let mut store = store_init();
+    let app = app::App::from_instance(&ComponentInst { exports }).unwrap();
+    assert_eq!(app.logging.log(&mut store, types::Level::Warn, "hot".to_string()).unwrap(), 1);
+    assert_eq!(logger.lock().unwrap().logged, vec![(types::Level::Warn, "hot".to_string())]);
+    assert_eq!(app.logging.last(&mut store).unwrap(), Some(types::Point { x: 1, y: -2 }));
+    let shapes = vec![
+        types::Shape::Dot(types::Point { x: 0, y: 0 }),
+        types::Shape::Line((types::Point { x: 0, y: 0 }, types::Point { x: 3, y: 4 })),
+        types::Shape::Empty,
+    ];
+    let perms = types::Perms { read: true, write: false };
+    assert_eq!(app.logging.check(&mut store, perms, shapes.clone()).unwrap(), Ok(3));
+    assert_eq!(app.logging.check(&mut store, types::Perms::default(), shapes).unwrap(), Err("unreadable".to_string()));
+    assert_eq!(app.run(&mut store, vec!["a".to_string()]).unwrap(), Ok(()));
+    assert_eq!(app.run(&mut store, vec![]).unwrap(), Err("no args".to_string()));
+
+    // functions of a world are imported by their own name
+    let mut imports = vec![];
+    app::add_to_imports(&mut imports, Arc::new(Mutex::new(Clock)));
+    let [(name, ComponentExternVal::Func(now))] = &imports[..] else {
+        panic!("expected a func import");
+    };
+    assert_eq!(name, "now");
+    assert_eq!(component_invoke(&mut store, now, vec![]).unwrap(), vec![ComponentVal::U64(42)]);
+}
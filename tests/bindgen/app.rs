@@ -0,0 +1,311 @@
+pub mod types {
+    #![allow(unused, clippy::all)]
+    use std::sync::{Arc, Mutex};
+    use wasmiq::{
+        Store,
+        Error,
+        ComponentVal,
+        ComponentValue,
+        ComponentFuncType,
+        ComponentFunc,
+        ComponentInst,
+        ComponentExternVal,
+        InterfaceType,
+        component_export,
+        component_func_host,
+        component_invoke,
+        component_next,
+        component_flags,
+    };
+
+    pub type Id = u64;
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    impl ComponentValue for Point {
+        fn tp() -> InterfaceType {
+            InterfaceType::Record(vec![("x".to_string(), <i32 as ComponentValue>::tp()), ("y".to_string(), <i32 as ComponentValue>::tp())])
+        }
+        fn into_val(self) -> ComponentVal {
+            ComponentVal::Record(vec![("x".to_string(), self.x.into_val()), ("y".to_string(), self.y.into_val())])
+        }
+        fn from_val(val: ComponentVal) -> Result<Self, Error> {
+            match val {
+            ComponentVal::Record(fields) => {
+                let mut vals = fields.into_iter().map(|(_, val)| val);
+                Ok(Point { x: component_next(&mut vals)?, y: component_next(&mut vals)? })
+            },
+            _ => Err(Error::Invalid),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Level {
+        Info,
+        Warn,
+    }
+
+    impl ComponentValue for Level {
+        fn tp() -> InterfaceType {
+            InterfaceType::Enum(vec!["info".to_string(), "warn".to_string()])
+        }
+        fn into_val(self) -> ComponentVal {
+            ComponentVal::Enum(match self {
+                Level::Info => "info",
+                Level::Warn => "warn",
+            }.to_string())
+        }
+        fn from_val(val: ComponentVal) -> Result<Self, Error> {
+            match val {
+            ComponentVal::Enum(case) => match case.as_str() {
+                "info" => Ok(Level::Info),
+                "warn" => Ok(Level::Warn),
+                _ => Err(Error::Invalid),
+            },
+            _ => Err(Error::Invalid),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+    pub struct Perms {
+        pub read: bool,
+        pub write: bool,
+    }
+
+    impl ComponentValue for Perms {
+        fn tp() -> InterfaceType {
+            InterfaceType::Flags(vec!["read".to_string(), "write".to_string()])
+        }
+        fn into_val(self) -> ComponentVal {
+            ComponentVal::Flags(component_flags(&[("read", self.read), ("write", self.write)]))
+        }
+        fn from_val(val: ComponentVal) -> Result<Self, Error> {
+            match val {
+            ComponentVal::Flags(names) => Ok(Perms { read: names.iter().any(|name| name == "read"), write: names.iter().any(|name| name == "write") }),
+            _ => Err(Error::Invalid),
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Shape {
+        Dot(Point),
+        Line((Point, Point,)),
+        Empty,
+    }
+
+    impl ComponentValue for Shape {
+        fn tp() -> InterfaceType {
+            InterfaceType::Variant(vec![("dot".to_string(), Some(<Point as ComponentValue>::tp())), ("line".to_string(), Some(<(Point, Point,) as ComponentValue>::tp())), ("empty".to_string(), None)])
+        }
+        fn into_val(self) -> ComponentVal {
+            match self {
+                Shape::Dot(val) => ComponentVal::Variant("dot".to_string(), Some(Box::new(val.into_val()))),
+                Shape::Line(val) => ComponentVal::Variant("line".to_string(), Some(Box::new(val.into_val()))),
+                Shape::Empty => ComponentVal::Variant("empty".to_string(), None),
+            }
+        }
+        fn from_val(val: ComponentVal) -> Result<Self, Error> {
+            match val {
+            ComponentVal::Variant(case, payload) => match (case.as_str(), payload) {
+                ("dot", Some(val)) => Ok(Shape::Dot(<Point as ComponentValue>::from_val(*val)?)),
+                ("line", Some(val)) => Ok(Shape::Line(<(Point, Point,) as ComponentValue>::from_val(*val)?)),
+                ("empty", None) => Ok(Shape::Empty),
+                _ => Err(Error::Invalid),
+            },
+            _ => Err(Error::Invalid),
+            }
+        }
+    }
+
+    pub trait Host {
+    }
+
+    pub fn add_to_imports<T: Host + Send + 'static>(imports: &mut Vec<(String, ComponentExternVal)>, host: Arc<Mutex<T>>) {
+        let mut exports = vec![];
+        imports.push(("demo:app/types@0.1.0".to_string(), ComponentExternVal::Instance(ComponentInst { exports })));
+    }
+
+    #[derive(Clone)]
+    pub struct Guest {
+    }
+
+    impl Guest {
+        pub fn from_instance(instance: &ComponentInst) -> Result<Self, Error> {
+            Ok(Guest {
+            })
+        }
+    }
+}
+
+pub mod logging {
+    #![allow(unused, clippy::all)]
+    use std::sync::{Arc, Mutex};
+    use wasmiq::{
+        Store,
+        Error,
+        ComponentVal,
+        ComponentValue,
+        ComponentFuncType,
+        ComponentFunc,
+        ComponentInst,
+        ComponentExternVal,
+        InterfaceType,
+        component_export,
+        component_func_host,
+        component_invoke,
+        component_next,
+        component_flags,
+    };
+
+    pub use super::types::{Id, Level, Point, Perms, Shape};
+
+    pub trait Host {
+        fn log(&mut self, level: Level, msg: String) -> Id;
+        fn last(&mut self) -> Option<Point>;
+        fn check(&mut self, perms: Perms, shapes: Vec<Shape>) -> Result<u32, String>;
+    }
+
+    pub fn add_to_imports<T: Host + Send + 'static>(imports: &mut Vec<(String, ComponentExternVal)>, host: Arc<Mutex<T>>) {
+        let mut exports = vec![];
+        {
+            let host = host.clone();
+            let func = component_func_host(ComponentFuncType { params: vec![("level".to_string(), <Level as ComponentValue>::tp()), ("msg".to_string(), <String as ComponentValue>::tp())], results: vec![<Id as ComponentValue>::tp()] }, Arc::new(move |_: &mut Store, args: Vec<ComponentVal>| {
+                let mut args = args.into_iter();
+                let level = component_next(&mut args)?;
+                let msg = component_next(&mut args)?;
+                Ok(vec![host.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).log(level, msg).into_val()])
+            }));
+            exports.push(("log".to_string(), ComponentExternVal::Func(func)));
+        }
+        {
+            let host = host.clone();
+            let func = component_func_host(ComponentFuncType { params: vec![], results: vec![<Option<Point> as ComponentValue>::tp()] }, Arc::new(move |_: &mut Store, args: Vec<ComponentVal>| {
+                let mut args = args.into_iter();
+                Ok(vec![host.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).last().into_val()])
+            }));
+            exports.push(("last".to_string(), ComponentExternVal::Func(func)));
+        }
+        {
+            let host = host.clone();
+            let func = component_func_host(ComponentFuncType { params: vec![("perms".to_string(), <Perms as ComponentValue>::tp()), ("shapes".to_string(), <Vec<Shape> as ComponentValue>::tp())], results: vec![<Result<u32, String> as ComponentValue>::tp()] }, Arc::new(move |_: &mut Store, args: Vec<ComponentVal>| {
+                let mut args = args.into_iter();
+                let perms = component_next(&mut args)?;
+                let shapes = component_next(&mut args)?;
+                Ok(vec![host.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).check(perms, shapes).into_val()])
+            }));
+            exports.push(("check".to_string(), ComponentExternVal::Func(func)));
+        }
+        imports.push(("demo:app/logging@0.1.0".to_string(), ComponentExternVal::Instance(ComponentInst { exports })));
+    }
+
+    #[derive(Clone)]
+    pub struct Guest {
+        log: ComponentFunc,
+        last: ComponentFunc,
+        check: ComponentFunc,
+    }
+
+    impl Guest {
+        pub fn from_instance(instance: &ComponentInst) -> Result<Self, Error> {
+            Ok(Guest {
+                log: match component_export(instance.clone(), "log".to_string())? {
+                    ComponentExternVal::Func(func) => func,
+                    _ => return Err(Error::Invalid),
+                },
+                last: match component_export(instance.clone(), "last".to_string())? {
+                    ComponentExternVal::Func(func) => func,
+                    _ => return Err(Error::Invalid),
+                },
+                check: match component_export(instance.clone(), "check".to_string())? {
+                    ComponentExternVal::Func(func) => func,
+                    _ => return Err(Error::Invalid),
+                },
+            })
+        }
+
+        pub fn log(&self, store: &mut Store, level: Level, msg: String) -> Result<Id, Error> {
+            let mut results = component_invoke(store, &self.log, vec![level.into_val(), msg.into_val()])?.into_iter();
+            component_next(&mut results)
+        }
+
+        pub fn last(&self, store: &mut Store) -> Result<Option<Point>, Error> {
+            let mut results = component_invoke(store, &self.last, vec![])?.into_iter();
+            component_next(&mut results)
+        }
+
+        pub fn check(&self, store: &mut Store, perms: Perms, shapes: Vec<Shape>) -> Result<Result<u32, String>, Error> {
+            let mut results = component_invoke(store, &self.check, vec![perms.into_val(), shapes.into_val()])?.into_iter();
+            component_next(&mut results)
+        }
+    }
+}
+
+pub mod app {
+    #![allow(unused, clippy::all)]
+    use std::sync::{Arc, Mutex};
+    use wasmiq::{
+        Store,
+        Error,
+        ComponentVal,
+        ComponentValue,
+        ComponentFuncType,
+        ComponentFunc,
+        ComponentInst,
+        ComponentExternVal,
+        InterfaceType,
+        component_export,
+        component_func_host,
+        component_invoke,
+        component_next,
+        component_flags,
+    };
+
+    pub trait Host {
+        fn now(&mut self) -> u64;
+    }
+
+    pub fn add_to_imports<T: Host + Send + 'static>(imports: &mut Vec<(String, ComponentExternVal)>, host: Arc<Mutex<T>>) {
+        {
+            let host = host.clone();
+            let func = component_func_host(ComponentFuncType { params: vec![], results: vec![<u64 as ComponentValue>::tp()] }, Arc::new(move |_: &mut Store, args: Vec<ComponentVal>| {
+                let mut args = args.into_iter();
+                Ok(vec![host.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).now().into_val()])
+            }));
+            imports.push(("now".to_string(), ComponentExternVal::Func(func)));
+        }
+    }
+
+    #[derive(Clone)]
+    pub struct App {
+        run: ComponentFunc,
+        pub logging: super::logging::Guest,
+    }
+
+    impl App {
+        pub fn from_instance(instance: &ComponentInst) -> Result<Self, Error> {
+            Ok(App {
+                run: match component_export(instance.clone(), "run".to_string())? {
+                    ComponentExternVal::Func(func) => func,
+                    _ => return Err(Error::Invalid),
+                },
+                logging: match component_export(instance.clone(), "demo:app/logging@0.1.0".to_string())? {
+                    ComponentExternVal::Instance(instance) => super::logging::Guest::from_instance(&instance)?,
+                    _ => return Err(Error::Invalid),
+                },
+            })
+        }
+
+        pub fn run(&self, store: &mut Store, args: Vec<String>) -> Result<Result<(), String>, Error> {
+            let mut results = component_invoke(store, &self.run, vec![args.into_val()])?.into_iter();
+            component_next(&mut results)
+        }
+    }
+}
+
@@ -0,0 +1,56 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+// a preopened directory holding in.txt, fresh for each test
+fn dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("wasmiq-run-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("in.txt"), "one\ntwo\n").unwrap();
+    dir
+}
+
+fn run(dir: &Path, args: &[&str], stdin: &[u8]) -> Output {
+    let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/wasm").join(args[0]);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wasmiq"))
+        .current_dir(dir)
+        .args(&["run", "--dir", ".", "--env", "GREETING=hi", "--env", "NAME=wasmiq"])
+        .arg(program)
+        .args(&args[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// a wasm32-wasip2 command component, built from cat.rs by
+// `rustc --target wasm32-wasip2 -C opt-level=s -C lto -C codegen-units=1 -C strip=symbols -C panic=abort cat.rs`
+#[test]
+fn test_run_component() {
+    let dir = dir("component");
+    let output = run(&dir, &["cat.wasm", "in.txt"], b"");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert_eq!(output.stdout, b"hi: one\ntwo\n");
+    // it exits with 3, which wasi:cli/exit reports as a failure
+    assert_eq!(output.status.code(), Some(1));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// a wasm32-wasip1 module, built from hello.rs the same way
+#[test]
+fn test_run_module() {
+    let dir = dir("module");
+    fs::write(dir.join("in.txt"), "-1.5\n").unwrap();
+    let output = run(&dir, &["hello.wasm", "in.txt", "x"], b"echo\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert_eq!(output.stdout, b"ECHO wasmiq\n");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(fs::read_to_string(dir.join("out.txt")).unwrap(), "-2147483648 -51");
+    fs::remove_dir_all(&dir).unwrap();
+}
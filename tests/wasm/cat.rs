@@ -0,0 +1,14 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let text = fs::read_to_string(&args[1]).unwrap();
+    let mut stdout = io::stdout();
+    write!(stdout, "{}: {}", env::var("GREETING").unwrap(), text).unwrap();
+    stdout.flush().unwrap();
+    // wasi:cli/exit only tells a failure from a success
+    process::exit(3);
+}
@@ -0,0 +1,18 @@
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input).unwrap();
+    let text = fs::read_to_string(&args[1]).unwrap();
+    // saturating float to int casts and sign extensions
+    let x: f64 = text.trim().parse().unwrap();
+    let n = (x * 1e10) as i32;
+    let b = (input.len() as u8).wrapping_add(200) as i8 as i32;
+    fs::write("out.txt", format!("{} {}", n, b)).unwrap();
+    writeln!(io::stdout(), "{} {}", input.trim().to_uppercase(), env::var("NAME").unwrap()).unwrap();
+    process::exit(args.len() as i32);
+}